@@ -0,0 +1,336 @@
+//! Timings of the low-level encode/decode stages exposed by `bpx::raw`.
+//!
+//! Run with `cargo bench`. This harness only uses the standard library; the
+//! average time and number of allocations per iteration are printed for each stage.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fs::File,
+    hint::black_box,
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant}
+};
+
+use bpx::{
+    builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+    encoder::{Encoder, EncoderProfile},
+    header::SectionFlags,
+    raw,
+    decoder::{Decoder, SliceDecoder},
+    sd::{Object, Value},
+    section::{new_section_data, BufferPool},
+    variant::package::PackageBuilder,
+    Interface
+};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8
+    {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout)
+    {
+        unsafe { System.dealloc(ptr, layout) };
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8
+    {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn bench<F: FnMut()>(name: &str, iterations: u32, mut f: F)
+{
+    f();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let elapsed: Duration = start.elapsed();
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / iterations as usize;
+    println!(
+        "{:<28} {:>12.3?} / iter {:>10} allocs / iter",
+        name,
+        elapsed / iterations,
+        allocations
+    );
+}
+
+fn save_small_sections(pool: Option<&BufferPool>)
+{
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    if let Some(pool) = pool {
+        encoder = encoder.with_buffer_pool(pool.clone());
+    }
+    for i in 0..10000u32 {
+        let handle = encoder
+            .create_section(SectionHeaderBuilder::new().with_size(64).build())
+            .unwrap();
+        let mut section = encoder.open_section(handle).unwrap();
+        for _ in 0..16 {
+            section.write_all(&i.to_le_bytes()).unwrap();
+        }
+    }
+    encoder.save().unwrap();
+    black_box(encoder);
+}
+
+fn save_file_sections<TWrite: Write>(mut encoder: Encoder<TWrite>)
+{
+    for i in 0..10000u32 {
+        let handle = encoder
+            .create_section(SectionHeaderBuilder::new().with_size(4).build())
+            .unwrap();
+        encoder.open_section(handle).unwrap().write_all(&i.to_le_bytes()).unwrap();
+    }
+    encoder.save().unwrap();
+}
+
+fn save_profile(profile: EncoderProfile, objects: &[Vec<u8>]) -> usize
+{
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    encoder.apply_profile(profile);
+    {
+        let mut package = PackageBuilder::new().with_profile(profile).build(&mut encoder).unwrap();
+        for (i, data) in objects.iter().enumerate() {
+            package.pack_object(&format!("object{}", i), &mut data.as_slice()).unwrap();
+        }
+    }
+    encoder.save().unwrap();
+    encoder.into_inner().len()
+}
+
+fn main()
+{
+    let payload: Vec<u8> = (0..1 << 20).map(|v: u32| (v % 251) as u8).collect();
+    let mut section = new_section_data(None).unwrap();
+    section.write_all(&payload).unwrap();
+
+    for (name, flags) in [
+        ("write_section raw", SectionFlags::CHECK_CRC32),
+        ("write_section unchecked", SectionFlags::empty()),
+        ("write_section xz", SectionFlags::CHECK_CRC32 | SectionFlags::COMPRESS_XZ)
+    ] {
+        bench(name, 5, || {
+            let mut out = Vec::with_capacity(payload.len());
+            black_box(raw::write_section_to(flags, section.as_mut(), &mut out).unwrap());
+        });
+    }
+
+    let mut large = new_section_data(None).unwrap();
+    for _ in 0..100 {
+        large.write_all(&payload[..1000000]).unwrap();
+    }
+    let mut state: u64 = 0x2545F4914F6CDD1D;
+    let offsets: Vec<u64> = (0..100000)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            // Reads cluster in a 64 KiB window like string or SD lookups do.
+            (state >> 33) % 65536
+        })
+        .collect();
+    bench("random 16 B reads (100k)", 5, || {
+        let mut buf = [0; 16];
+        for offset in &offsets {
+            large.seek(SeekFrom::Start(*offset)).unwrap();
+            large.read_exact(&mut buf).unwrap();
+            black_box(&buf);
+        }
+    });
+
+    bench("load_in_memory (100 MB)", 5, || {
+        black_box(large.load_in_memory().unwrap());
+    });
+
+    bench("save 10k small sections", 5, || save_small_sections(None));
+    let pool = BufferPool::new();
+    bench("save 10k pooled sections", 5, || save_small_sections(Some(&pool)));
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(Cursor::new(&mut buf)).unwrap();
+        for _ in 0..256 {
+            encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+        }
+        encoder.save().unwrap();
+    }
+    bench("read_headers (256)", 1000, || {
+        black_box(raw::read_headers(&mut Cursor::new(&buf)).unwrap());
+    });
+
+    let mut small = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut small).unwrap();
+        for i in 0..10000u32 {
+            let handle = encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+            encoder.open_section(handle).unwrap().write_all(&i.to_le_bytes()).unwrap();
+        }
+        encoder.save().unwrap();
+    }
+    bench("Decoder open (10k)", 5, || {
+        let mut decoder = Decoder::new(Cursor::new(&small)).unwrap();
+        for i in 0..10000 {
+            let handle = decoder.find_section_by_index(i).unwrap();
+            black_box(decoder.open_section(handle).unwrap().size());
+        }
+    });
+    bench("SliceDecoder open (10k)", 5, || {
+        let mut decoder = SliceDecoder::new(&small).unwrap();
+        for i in 0..10000 {
+            let handle = decoder.find_section_by_index(i).unwrap();
+            black_box(decoder.open_section(handle).unwrap().size());
+        }
+    });
+    let decoder = SliceDecoder::new(&small).unwrap();
+    bench("section_bytes (10k)", 5, || {
+        for i in 0..10000 {
+            let handle = decoder.find_section_by_index(i).unwrap();
+            black_box(decoder.section_bytes(handle).unwrap());
+        }
+    });
+
+    let mut packed = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut packed).unwrap();
+        for i in 0..500u32 {
+            let header = SectionHeaderBuilder::new()
+                .with_compression(CompressionMethod::Xz)
+                .with_checksum(Checksum::Crc32)
+                .build();
+            let handle = encoder.create_section(header).unwrap();
+            let mut section = encoder.open_section(handle).unwrap();
+            for j in 0..4096u32 {
+                section.write_all(&(i ^ j).to_le_bytes()).unwrap();
+            }
+        }
+        encoder.save().unwrap();
+    }
+    bench("open 500 xz sections", 3, || {
+        let mut decoder = Decoder::new(Cursor::new(&packed)).unwrap();
+        for i in 0..500 {
+            let handle = decoder.find_section_by_index(i).unwrap();
+            black_box(decoder.open_section(handle).unwrap().size());
+        }
+    });
+    bench("load_sections 500 xz", 3, || {
+        let mut decoder = Decoder::new(Cursor::new(&packed)).unwrap();
+        let handles: Vec<_> = decoder.iter_sections().collect();
+        decoder.load_sections(&handles, 0).unwrap();
+        black_box(decoder);
+    });
+
+    let mut obj = Object::new();
+    for i in 0..255 {
+        obj.set(&format!("key{}", i), Value::from(i as u32));
+    }
+    let mut sd = Vec::new();
+    obj.write(&mut sd).unwrap();
+    bench("sd encode (255 props)", 100, || {
+        let mut out = Vec::new();
+        obj.write(&mut out).unwrap();
+        black_box(out);
+    });
+    let mut tree = Object::new();
+    for i in 0..200 {
+        let mut child = Object::new();
+        for j in 0..250 {
+            child.set(&format!("key{}", j), Value::from(format!("value {} {}", i, j)));
+        }
+        tree.set(&format!("child{}", i), Value::from(child));
+    }
+    bench("sd encode (50k props)", 20, || {
+        let mut out = Vec::new();
+        tree.write(&mut out).unwrap();
+        black_box(out);
+    });
+    bench("sd validate (255 props)", 100, || {
+        raw::sd_validate(&mut sd.as_slice()).unwrap();
+    });
+
+    let mut strings = new_section_data(None).unwrap();
+    let ptrs: Vec<u32> = (0..1000)
+        .map(|i| raw::write_string(&format!("string number {}", i), strings.as_mut()).unwrap())
+        .collect();
+    bench("read_string (1000)", 100, || {
+        for ptr in &ptrs {
+            black_box(raw::read_string(*ptr, strings.as_mut()).unwrap());
+        }
+    });
+
+    // 12 MB of assets: text-like, random and repetitive files.
+    let words = ["texture", "mesh", "shader", "0.5", "1.0", "{", "}", "name", "\n", "material"];
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+    for i in 0..48u32 {
+        let data: Vec<u8> = match i % 3 {
+            0 => (0..262144u32)
+                .map(|_| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    (state >> 56) as u8
+                })
+                .collect(),
+            1 => (0..262144 / 6)
+                .flat_map(|_| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    let word = words[(state >> 60) as usize % words.len()];
+                    word.bytes().chain(std::iter::once(b' '))
+                })
+                .collect(),
+            _ => vec![(i % 7) as u8; 262144]
+        };
+        objects.push(data);
+    }
+    let small: Vec<Vec<u8>> = (0..1024u32)
+        .map(|i| format!("small asset {} {}", i, words[i as usize % words.len()]).into_bytes())
+        .collect();
+    for (name, profile) in [
+        ("Fast", EncoderProfile::Fast),
+        ("Balanced", EncoderProfile::Balanced),
+        ("Smallest", EncoderProfile::Smallest)
+    ] {
+        for (set, objects) in [("12 MB", &objects), ("1024 small", &small)] {
+            let name = format!("save {} package {}", set, name);
+            bench(&name, 3, || {
+                black_box(save_profile(profile, objects));
+            });
+            println!("{:<28} {:>12} bytes", name, save_profile(profile, objects));
+        }
+    }
+
+    let path = std::env::temp_dir().join("bpx_bench_file_backend.bpx");
+    bench("save 10k raw File", 5, || save_file_sections(Encoder::new(File::create(&path).unwrap()).unwrap()));
+    bench("save 10k create_file", 5, || save_file_sections(Encoder::create_file(&path).unwrap()));
+    bench("Decoder::new raw File (10k)", 5, || {
+        black_box(Decoder::new(File::open(&path).unwrap()).unwrap());
+    });
+    bench("Decoder::open_file (10k)", 5, || {
+        black_box(Decoder::open_file(&path).unwrap());
+    });
+    bench("load 10k raw File", 5, || {
+        let mut decoder = Decoder::new(File::open(&path).unwrap()).unwrap();
+        for i in 0..10000 {
+            let handle = decoder.find_section_by_index(i).unwrap();
+            black_box(decoder.open_section(handle).unwrap().size());
+        }
+    });
+    bench("load 10k open_file", 5, || {
+        let mut decoder = Decoder::open_file(&path).unwrap();
+        for i in 0..10000 {
+            let handle = decoder.find_section_by_index(i).unwrap();
+            black_box(decoder.open_section(handle).unwrap().size());
+        }
+    });
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,79 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Creates a package from a folder, then extracts it again and compares the result.
+//!
+//! Run with `cargo run --example package_from_folder`.
+
+use std::path::Path;
+
+use bpx::{
+    decoder::Decoder,
+    encoder::Encoder,
+    variant::package::{source::PathTree, utils::UnpackOptions, PackageBuilder, PackageDecoder}
+};
+
+fn write_tree(root: &Path)
+{
+    std::fs::create_dir_all(root.join("textures").join("ui")).unwrap();
+    std::fs::write(root.join("readme.txt"), b"An example asset folder").unwrap();
+    std::fs::write(root.join("textures").join("stone.png"), vec![0x42; 100000]).unwrap();
+    std::fs::write(root.join("textures").join("ui").join("button.png"), b"button").unwrap();
+}
+
+fn main()
+{
+    let work = std::env::temp_dir().join(format!("bpx_example_folder_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&work);
+    let source = work.join("assets");
+    let output = work.join("extracted");
+    let path = work.join("assets.bpxp");
+    write_tree(&source);
+
+    // Objects are named after their path in the tree: "assets/textures/stone.png".
+    let mut encoder = Encoder::create_file(&path).unwrap();
+    let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    package.pack_tree(&mut PathTree::with_name("assets", &source)).unwrap();
+    encoder.save().unwrap();
+    drop(encoder);
+
+    let mut decoder = Decoder::open_file(&path).unwrap();
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    let report = package.extract_all(&output, UnpackOptions::default()).unwrap();
+    for name in &report.extracted {
+        println!("extracted {}", name);
+    }
+    assert_eq!(report.extracted.len(), 3);
+    for file in ["readme.txt", "textures/stone.png", "textures/ui/button.png"] {
+        let original = std::fs::read(source.join(file)).unwrap();
+        let extracted = std::fs::read(output.join("assets").join(file)).unwrap();
+        assert_eq!(original, extracted, "{} differs after extraction", file);
+    }
+    println!("{} byte(s) extracted from {}", report.bytes, path.display());
+    std::fs::remove_dir_all(&work).unwrap();
+}
@@ -0,0 +1,67 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Attaches metadata to a package and reads it back.
+//!
+//! Run with `cargo run --example package_metadata`.
+
+use std::io::Cursor;
+
+use bpx::{
+    decoder::Decoder,
+    encoder::Encoder,
+    sd::Value,
+    variant::package::{PackageBuilder, PackageDecoder, PackageMetadata}
+};
+
+fn main()
+{
+    let mut metadata = PackageMetadata::new("example", "1.0.0").with_current_build_date();
+    metadata.author = Some("BlockProject 3D".into());
+    metadata.description = Some("A package showing how to use metadata".into());
+    metadata.custom.set("engine", Value::from("bp3d"));
+
+    let mut buf = Vec::new();
+    let mut encoder = Encoder::new(&mut buf).unwrap();
+    let mut package = PackageBuilder::new()
+        .with_typed_metadata(metadata.clone())
+        .build(&mut encoder)
+        .unwrap();
+    package.pack_object("data.bin", &mut [1, 2, 3, 4].as_ref()).unwrap();
+    encoder.save().unwrap();
+    drop(encoder);
+
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    let read = package.read_typed_metadata().unwrap().unwrap();
+    assert_eq!(read, metadata);
+    println!("{} {} by {}", read.name, read.version, read.author.as_deref().unwrap_or("unknown"));
+    if let Some(engine) = read.custom.get("engine") {
+        println!("engine: {:?}", engine);
+    }
+}
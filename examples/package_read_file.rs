@@ -0,0 +1,70 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Reads a single file out of a package without extracting anything else.
+//!
+//! Run with `cargo run --example package_read_file`.
+
+use std::io::Cursor;
+
+use bpx::{
+    decoder::Decoder,
+    encoder::Encoder,
+    variant::package::{PackageBuilder, PackageDecoder}
+};
+
+fn main()
+{
+    let mut buf = Vec::new();
+    let mut encoder = Encoder::new(&mut buf).unwrap();
+    let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    package.pack_object("config/game.cfg", &mut b"fullscreen=true".as_ref()).unwrap();
+    package.pack_object("config/keys.cfg", &mut b"jump=space".as_ref()).unwrap();
+    encoder.save().unwrap();
+    drop(encoder);
+
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+
+    // A single lookup scans the object table directly.
+    let obj = package.find_object("config/keys.cfg").unwrap().unwrap();
+    let mut data = Vec::new();
+    package.unpack_object(&obj, &mut data).unwrap();
+    assert_eq!(data, b"jump=space");
+    println!("config/keys.cfg: {}", String::from_utf8_lossy(&data));
+
+    // Repeated lookups are cheaper through a lookup table.
+    let mut table = package.read_object_table().unwrap();
+    table.build_lookup_table(&mut package).unwrap();
+    let obj = *table.find_object("config/game.cfg").unwrap().unwrap();
+    let mut data = Vec::new();
+    package.unpack_object(&obj, &mut data).unwrap();
+    assert_eq!(data, b"fullscreen=true");
+    println!("config/game.cfg: {}", String::from_utf8_lossy(&data));
+    assert!(package.find_object("config/missing.cfg").unwrap().is_none());
+}
@@ -0,0 +1,7 @@
+use std::path::Path;
+
+fn main()
+{
+    // Run from the fuzz directory: cargo run --bin gen_corpus
+    bpx::fuzz::write_corpus(Path::new("corpus")).expect("failed to write corpus");
+}
@@ -0,0 +1,87 @@
+/*
+ * Copyright (c) 2021, BlockProject 3D
+ *
+ * All rights reserved.
+ *
+ * Redistribution and use in source and binary forms, with or without modification,
+ * are permitted provided that the following conditions are met:
+ *
+ *     * Redistributions of source code must retain the above copyright notice,
+ *       this list of conditions and the following disclaimer.
+ *     * Redistributions in binary form must reproduce the above copyright notice,
+ *       this list of conditions and the following disclaimer in the documentation
+ *       and/or other materials provided with the distribution.
+ *     * Neither the name of BlockProject 3D nor the names of its contributors
+ *       may be used to endorse or promote products derived from this software
+ *       without specific prior written permission.
+ *
+ * THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+ * "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+ * LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+ * A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+ * CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+ * EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+ * PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+ * PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+ * LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+ * NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+ * SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+ */
+
+/* C declarations for the bpx crate built with the `ffi` feature. */
+
+#ifndef BPX_H
+#define BPX_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define BPX_OK 0
+#define BPX_ERR_INVALID_ARGUMENT 1
+#define BPX_ERR_IO 2
+#define BPX_ERR_CORRUPTION 3
+#define BPX_ERR_CHECKSUM 4
+#define BPX_ERR_UNSUPPORTED 5
+#define BPX_ERR_CAPACITY 6
+#define BPX_ERR_NOT_FOUND 7
+#define BPX_ERR_BUFFER_TOO_SMALL 8
+#define BPX_ERR_OTHER 255
+
+typedef struct BpxDecoder BpxDecoder;
+typedef struct BpxPackage BpxPackage;
+
+typedef struct BpxSectionInfo
+{
+    uint32_t index;
+    uint8_t btype;
+    uint8_t flags;
+    uint32_t size;
+    uint32_t csize;
+} BpxSectionInfo;
+
+int bpx_last_error_code(void);
+const char *bpx_last_error_message(void);
+
+int bpx_decoder_open_file(const char *path, BpxDecoder **out);
+int bpx_decoder_open_memory(const uint8_t *data, size_t len, BpxDecoder **out);
+void bpx_decoder_free(BpxDecoder *decoder);
+uint32_t bpx_decoder_section_count(const BpxDecoder *decoder);
+int bpx_decoder_section_info(const BpxDecoder *decoder, uint32_t index, BpxSectionInfo *out);
+int bpx_decoder_read_section(BpxDecoder *decoder, uint32_t index, uint8_t *buf, size_t len, size_t *written);
+
+int bpx_package_open(BpxDecoder *decoder, BpxPackage **out);
+void bpx_package_free(BpxPackage *package);
+uint32_t bpx_package_entry_count(const BpxPackage *package);
+int bpx_package_entry(const BpxPackage *package, uint32_t index, const char **name, uint64_t *size);
+int bpx_package_extract(BpxPackage *package, const char *name, uint8_t *buf, size_t len, size_t *written);
+int bpx_package_extract_file(BpxPackage *package, const char *name, const char *path);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
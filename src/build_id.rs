@@ -0,0 +1,102 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Helpers to store a build identifier in a BPX.
+//!
+//! The BPX Main Header has no spare field, so the identifier is stored in a
+//! dedicated section of type [SECTION_TYPE_BUILD_ID](crate::header::SECTION_TYPE_BUILD_ID)
+//! made of exactly 8 bytes: the identifier as a little endian u64.
+
+use std::io::SeekFrom;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    builder::{Checksum, SectionHeaderBuilder},
+    error::{section_context, Error},
+    header::SECTION_TYPE_BUILD_ID,
+    utils::read_fill,
+    Interface,
+    Result,
+    WriteInterface
+};
+
+const BUILD_ID_SIZE: usize = 8;
+
+/// Reads the build identifier, None if the BPX has no build identifier section.
+pub(crate) fn read_build_id<TInterface: Interface>(interface: &mut TInterface) -> Result<Option<u64>>
+{
+    let handle = match interface.find_section_by_type(SECTION_TYPE_BUILD_ID) {
+        Some(v) => v,
+        None => return Ok(None)
+    };
+    let context = section_context(interface, handle);
+    let mut data = interface.open_section(handle)?;
+    if data.size() != BUILD_ID_SIZE {
+        return Err(context(Error::Corruption(format!(
+            "build id section has {} bytes, expected {}",
+            data.size(),
+            BUILD_ID_SIZE
+        ))));
+    }
+    let mut buf: [u8; BUILD_ID_SIZE] = [0; BUILD_ID_SIZE];
+    data.seek(SeekFrom::Start(0)).map_err(|e| context(e.into()))?;
+    if read_fill(&mut *data, &mut buf).map_err(|e| context(e.into()))? != BUILD_ID_SIZE {
+        return Err(context(Error::Truncation("read build id")));
+    }
+    return Ok(Some(LittleEndian::read_u64(&buf)));
+}
+
+/// Sets the build identifier, creating the build identifier section if needed.
+pub(crate) fn set_build_id<TInterface: WriteInterface>(interface: &mut TInterface, id: u64) -> Result<()>
+{
+    let handle = match interface.find_section_by_type(SECTION_TYPE_BUILD_ID) {
+        Some(v) => v,
+        None => interface.create_section(
+            SectionHeaderBuilder::new()
+                .with_type(SECTION_TYPE_BUILD_ID)
+                .with_size(BUILD_ID_SIZE as u32)
+                .with_checksum(Checksum::Crc32)
+                .build()
+        )?
+    };
+    let context = section_context(interface, handle);
+    let mut data = interface.open_section(handle)?;
+    if data.size() > BUILD_ID_SIZE {
+        return Err(context(Error::Corruption(format!(
+            "build id section has {} bytes, expected {}",
+            data.size(),
+            BUILD_ID_SIZE
+        ))));
+    }
+    let mut buf: [u8; BUILD_ID_SIZE] = [0; BUILD_ID_SIZE];
+    LittleEndian::write_u64(&mut buf, id);
+    data.seek(SeekFrom::Start(0)).map_err(|e| context(e.into()))?;
+    data.write_all(&buf).map_err(|e| context(e.into()))?;
+    return Ok(());
+}
@@ -28,18 +28,15 @@
 
 //! High-level utilities to generate low-level file headers.
 
-use crate::header::{
-    MainHeader,
-    SectionHeader,
-    FLAG_CHECK_CRC32,
-    FLAG_CHECK_WEAK,
-    FLAG_COMPRESS_XZ,
-    FLAG_COMPRESS_ZLIB
+use crate::{
+    header::{check_type_ext_range, MainHeader, SectionFlags, SectionHeader, SECTION_TYPE_SD, SECTION_TYPE_STRING},
+    Result
 };
 
 const COMPRESSION_THRESHOLD: u32 = 65536;
 
 /// The compression method to use for a section.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum CompressionMethod
 {
     /// Use the xz compression algorithm with extreme preset.
@@ -54,6 +51,7 @@ pub enum CompressionMethod
 }
 
 /// The checksum algorithm to use for a section
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Checksum
 {
     /// The weak checksum is a very fast algorithm which is computed
@@ -69,7 +67,61 @@ pub enum Checksum
     Crc32
 }
 
+impl Checksum
+{
+    /// Returns the stable identifier of this checksum algorithm.
+    ///
+    /// *Identifiers are never reused: 0 is reserved for sections without a
+    /// checksum, 1 is [Weak](Checksum::Weak) and 2 is [Crc32](Checksum::Crc32).
+    /// A new algorithm gets the next free identifier together with a new
+    /// section flag, so that readers which do not know the identifier can
+    /// reject it instead of mis-verifying the section.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::Checksum;
+    ///
+    /// assert_eq!(Checksum::Weak.algorithm_id(), 1);
+    /// assert_eq!(Checksum::Crc32.algorithm_id(), 2);
+    /// ```
+    pub fn algorithm_id(self) -> u8
+    {
+        return match self {
+            Checksum::Weak => 1,
+            Checksum::Crc32 => 2
+        };
+    }
+
+    /// Looks up a checksum algorithm from its stable identifier.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: the identifier returned by [algorithm_id](Checksum::algorithm_id).
+    ///
+    /// returns: Option<Checksum>; None for 0 (no checksum) and unknown identifiers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::Checksum;
+    ///
+    /// assert_eq!(Checksum::from_algorithm_id(2), Some(Checksum::Crc32));
+    /// assert_eq!(Checksum::from_algorithm_id(0), None);
+    /// assert_eq!(Checksum::from_algorithm_id(3), None);
+    /// ```
+    pub fn from_algorithm_id(id: u8) -> Option<Checksum>
+    {
+        return match id {
+            1 => Some(Checksum::Weak),
+            2 => Some(Checksum::Crc32),
+            _ => None
+        };
+    }
+}
+
 /// Utility to easily generate a [SectionHeader](crate::header::SectionHeader).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct SectionHeaderBuilder
 {
     header: SectionHeader
@@ -85,6 +137,76 @@ impl SectionHeaderBuilder
         };
     }
 
+    /// Creates a section header builder for a strings section.
+    ///
+    /// *Strings sections are of type [SECTION_TYPE_STRING](crate::header::SECTION_TYPE_STRING)
+    /// and use a weak checksum and zlib compression. The returned builder can still be customized.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::{Checksum, CompressionMethod, SectionHeaderBuilder};
+    /// use bpx::header::SECTION_TYPE_STRING;
+    ///
+    /// let header = SectionHeaderBuilder::strings().build();
+    /// assert_eq!(header.btype, SECTION_TYPE_STRING);
+    /// assert_eq!(header.checksum(), Some(Checksum::Weak));
+    /// assert_eq!(header.compression(), Some(CompressionMethod::Zlib));
+    /// ```
+    pub fn strings() -> SectionHeaderBuilder
+    {
+        return SectionHeaderBuilder::new()
+            .with_type(SECTION_TYPE_STRING)
+            .with_checksum(Checksum::Weak)
+            .with_compression(CompressionMethod::Zlib);
+    }
+
+    /// Creates a section header builder for a Structured Data section.
+    ///
+    /// *Structured Data sections are of type [SECTION_TYPE_SD](crate::header::SECTION_TYPE_SD)
+    /// and use a weak checksum and zlib compression. The returned builder can still be customized.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::{Checksum, CompressionMethod, SectionHeaderBuilder};
+    /// use bpx::header::SECTION_TYPE_SD;
+    ///
+    /// let header = SectionHeaderBuilder::structured_data().build();
+    /// assert_eq!(header.btype, SECTION_TYPE_SD);
+    /// assert_eq!(header.checksum(), Some(Checksum::Weak));
+    /// assert_eq!(header.compression(), Some(CompressionMethod::Zlib));
+    /// ```
+    pub fn structured_data() -> SectionHeaderBuilder
+    {
+        return SectionHeaderBuilder::new()
+            .with_type(SECTION_TYPE_SD)
+            .with_checksum(Checksum::Weak)
+            .with_compression(CompressionMethod::Zlib);
+    }
+
+    /// Creates a section header builder for a section of large binary data.
+    ///
+    /// *Bulk data sections use a CRC32 checksum and xz compression; their type byte
+    /// is left to the caller. The returned builder can still be customized.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::{Checksum, CompressionMethod, SectionHeaderBuilder};
+    ///
+    /// let header = SectionHeaderBuilder::bulk_data().with_type(1).build();
+    /// assert_eq!(header.btype, 1);
+    /// assert_eq!(header.checksum(), Some(Checksum::Crc32));
+    /// assert_eq!(header.compression(), Some(CompressionMethod::Xz));
+    /// ```
+    pub fn bulk_data() -> SectionHeaderBuilder
+    {
+        return SectionHeaderBuilder::new()
+            .with_checksum(Checksum::Crc32)
+            .with_compression(CompressionMethod::Xz);
+    }
+
     /// Defines the size in bytes of the section.
     ///
     /// *By default, the size of the section is not known, and the encoder
@@ -138,10 +260,39 @@ impl SectionHeaderBuilder
         return self;
     }
 
+    /// Defines the application defined tag byte of the section.
+    ///
+    /// *The default tag is 0. The tag is written as is and never interpreted,
+    /// see [user_tag](crate::header::SectionHeader::user_tag).*
+    ///
+    /// # Arguments
+    ///
+    /// * `tag`: the tag byte of the section.
+    ///
+    /// returns: SectionHeaderBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::SectionHeaderBuilder;
+    ///
+    /// let header = SectionHeaderBuilder::new()
+    ///     .with_user_tag(0x42)
+    ///     .build();
+    /// assert_eq!(header.user_tag(), 0x42);
+    /// ```
+    pub fn with_user_tag(mut self, tag: u8) -> Self
+    {
+        self.header.user_tag = tag;
+        return self;
+    }
+
     /// Defines the compression algorithm to use when compressing the section.
     ///
     /// *The default is to not perform any compression at all.*
     ///
+    /// *Calling this again replaces the previously selected compression method.*
+    ///
     /// # Arguments
     ///
     /// * `method`: the [CompressionMethod](self::CompressionMethod) to use for saving this section.
@@ -161,10 +312,13 @@ impl SectionHeaderBuilder
     /// ```
     pub fn with_compression(mut self, method: CompressionMethod) -> Self
     {
+        let mut flags = self.header.flags();
+        flags.remove(SectionFlags::COMPRESS_XZ | SectionFlags::COMPRESS_ZLIB);
         match method {
-            CompressionMethod::Xz => self.header.flags |= FLAG_COMPRESS_XZ,
-            CompressionMethod::Zlib => self.header.flags |= FLAG_COMPRESS_ZLIB
+            CompressionMethod::Xz => flags.insert(SectionFlags::COMPRESS_XZ),
+            CompressionMethod::Zlib => flags.insert(SectionFlags::COMPRESS_ZLIB)
         }
+        self.header.flags = flags.bits();
         self.header.csize = COMPRESSION_THRESHOLD;
         return self;
     }
@@ -205,6 +359,8 @@ impl SectionHeaderBuilder
     /// *By default, no checksum is applied and the checksum
     /// field of the BPX Section Header is set to 0.*
     ///
+    /// *Calling this again replaces the previously selected checksum algorithm.*
+    ///
     /// # Arguments
     ///
     /// * `chksum`: the new [Checksum](self::Checksum) algorithm to use for data verification.
@@ -224,13 +380,68 @@ impl SectionHeaderBuilder
     /// ```
     pub fn with_checksum(mut self, chksum: Checksum) -> Self
     {
+        let mut flags = self.header.flags();
+        flags.remove(SectionFlags::CHECK_CRC32 | SectionFlags::CHECK_WEAK);
         match chksum {
-            Checksum::Crc32 => self.header.flags |= FLAG_CHECK_CRC32,
-            Checksum::Weak => self.header.flags |= FLAG_CHECK_WEAK
+            Checksum::Crc32 => flags.insert(SectionFlags::CHECK_CRC32),
+            Checksum::Weak => flags.insert(SectionFlags::CHECK_WEAK)
         }
+        self.header.flags = flags.bits();
         return self;
     }
 
+    /// Returns the size of the section in bytes, as set by [with_size](SectionHeaderBuilder::with_size).
+    pub fn get_size(&self) -> u32
+    {
+        return self.header.size;
+    }
+
+    /// Returns the type byte of the section, as set by [with_type](SectionHeaderBuilder::with_type).
+    pub fn get_type(&self) -> u8
+    {
+        return self.header.btype;
+    }
+
+    /// Returns the compression method of the section if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::{CompressionMethod, SectionHeaderBuilder};
+    ///
+    /// let builder = SectionHeaderBuilder::new();
+    /// assert_eq!(builder.get_compression(), None);
+    /// let builder = builder.with_compression(CompressionMethod::Xz);
+    /// assert_eq!(builder.get_compression(), Some(CompressionMethod::Xz));
+    /// ```
+    pub fn get_compression(&self) -> Option<CompressionMethod>
+    {
+        return self.header.compression();
+    }
+
+    /// Returns the compression threshold of the section, as set by [with_threshold](SectionHeaderBuilder::with_threshold).
+    pub fn get_threshold(&self) -> u32
+    {
+        return self.header.csize;
+    }
+
+    /// Returns the checksum algorithm of the section if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::{Checksum, SectionHeaderBuilder};
+    ///
+    /// let builder = SectionHeaderBuilder::new();
+    /// assert_eq!(builder.get_checksum(), None);
+    /// let builder = builder.with_checksum(Checksum::Crc32);
+    /// assert_eq!(builder.get_checksum(), Some(Checksum::Crc32));
+    /// ```
+    pub fn get_checksum(&self) -> Option<Checksum>
+    {
+        return self.header.checksum();
+    }
+
     /// Consumes self and returns the generated [SectionHeader](crate::header::SectionHeader).
     ///
     /// # Examples
@@ -258,7 +469,16 @@ impl SectionHeaderBuilder
     }
 }
 
+impl Default for SectionHeaderBuilder
+{
+    fn default() -> Self
+    {
+        return Self::new();
+    }
+}
+
 /// Utility to easily generate a [MainHeader](crate::header::MainHeader).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct MainHeaderBuilder
 {
     header: MainHeader
@@ -326,6 +546,48 @@ impl MainHeaderBuilder
         return self;
     }
 
+    /// Writes a range of bytes in the Extended Type Information field,
+    /// leaving the other bytes untouched.
+    ///
+    /// *This is intended for small application-specific tags. The bytes
+    /// reserved by the variant of the BPX (see
+    /// [get_type_ext_reserved_range](crate::header::get_type_ext_reserved_range))
+    /// can only be written through [with_type_ext](MainHeaderBuilder::with_type_ext);
+    /// the type byte must therefore be defined before calling this function.*
+    ///
+    /// # Arguments
+    ///
+    /// * `offset`: the offset of the first byte in the Extended Type Information field.
+    /// * `data`: the bytes to write.
+    ///
+    /// returns: Result<MainHeaderBuilder, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the range does not fit
+    /// in the Extended Type Information field or if it overlaps bytes reserved
+    /// by the variant.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::MainHeaderBuilder;
+    ///
+    /// let builder = MainHeaderBuilder::new()
+    ///     .with_type('P' as u8)
+    ///     .with_type_ext_range(4, &[42, 0, 0, 0])
+    ///     .unwrap();
+    /// assert_eq!(builder.get_type_ext()[4], 42);
+    /// assert!(builder.with_type_ext_range(2, &[1, 2]).is_err());
+    /// assert!(builder.with_type_ext_range(15, &[1, 2]).is_err());
+    /// ```
+    pub fn with_type_ext_range(mut self, offset: usize, data: &[u8]) -> Result<Self>
+    {
+        let range = check_type_ext_range(self.header.btype, offset, data.len())?;
+        self.header.type_ext[range].copy_from_slice(data);
+        return Ok(self);
+    }
+
     /// Defines the version of the BPX.
     ///
     /// *The default value of the version int is given by
@@ -357,6 +619,24 @@ impl MainHeaderBuilder
         return self;
     }
 
+    /// Returns the type byte of the BPX, as set by [with_type](MainHeaderBuilder::with_type).
+    pub fn get_type(&self) -> u8
+    {
+        return self.header.btype;
+    }
+
+    /// Returns the extended type information of the BPX, as set by [with_type_ext](MainHeaderBuilder::with_type_ext).
+    pub fn get_type_ext(&self) -> [u8; 16]
+    {
+        return self.header.type_ext;
+    }
+
+    /// Returns the version of the BPX, as set by [with_version](MainHeaderBuilder::with_version).
+    pub fn get_version(&self) -> u32
+    {
+        return self.header.version;
+    }
+
     /// Consumes self and returns the generated [MainHeader](crate::header::MainHeader).
     ///
     /// # Examples
@@ -378,3 +658,11 @@ impl MainHeaderBuilder
         return self.header;
     }
 }
+
+impl Default for MainHeaderBuilder
+{
+    fn default() -> Self
+    {
+        return Self::new();
+    }
+}
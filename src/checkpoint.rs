@@ -0,0 +1,220 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Helpers to store intermediate checksums of the sections of a BPX.
+//!
+//! The checkpoints are stored in a dedicated section of type
+//! [SECTION_TYPE_CHECKPOINTS](crate::header::SECTION_TYPE_CHECKPOINTS) made of
+//! little endian u32 values: the chunk size in bytes, followed for each
+//! checkpointed section by its index, its number of chunks and the CRC32 of
+//! each chunk of its uncompressed data. The last chunk of a section may be
+//! shorter than the chunk size. A chunk size of 0 means the BPX has no checkpoint.
+
+use std::io::{Read, SeekFrom};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    compression::{Checksum, Crc32Checksum},
+    error::{section_context, Error},
+    header::SECTION_TYPE_CHECKPOINTS,
+    Interface,
+    Result
+};
+
+const READ_BLOCK_SIZE: usize = 8192;
+
+/// The checksums of all chunks of a section.
+pub(crate) struct Checkpoints
+{
+    pub interval: u32,
+    pub chunks: Vec<u32>
+}
+
+/// Computes the CRC32 of each chunk of `interval` bytes of `data`.
+pub(crate) fn chunk_checksums<TRead: Read + ?Sized>(data: &mut TRead, interval: u32) -> Result<Vec<u32>>
+{
+    let mut buf: [u8; READ_BLOCK_SIZE] = [0; READ_BLOCK_SIZE];
+    let mut verifier = ChunkVerifier::new(interval, Vec::new());
+    loop {
+        let len = data.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        verifier.advance(&buf[..len], false)?;
+    }
+    if verifier.len > 0 {
+        verifier.chunks.push(verifier.current.value());
+    }
+    return Ok(verifier.chunks);
+}
+
+/// Encodes a checkpoints section from the chunk checksums of each section index.
+pub(crate) fn encode_checkpoints(interval: u32, sections: &[(u32, Vec<u32>)]) -> Vec<u8>
+{
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&interval.to_le_bytes());
+    for (index, chunks) in sections {
+        buf.extend_from_slice(&index.to_le_bytes());
+        buf.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+        for chunk in chunks {
+            buf.extend_from_slice(&chunk.to_le_bytes());
+        }
+    }
+    return buf;
+}
+
+/// Reads the checkpoints of the section at `index`, None if the section has none.
+pub(crate) fn read_checkpoints<TInterface: Interface>(
+    interface: &mut TInterface,
+    index: u32
+) -> Result<Option<Checkpoints>>
+{
+    let size = match interface.find_section_by_index(index) {
+        Some(v) => interface.get_section_header(v).size,
+        None => return Ok(None)
+    };
+    let handle = match interface.find_section_by_type(SECTION_TYPE_CHECKPOINTS) {
+        Some(v) if interface.get_section_index(v) != index => v,
+        _ => return Ok(None)
+    };
+    let context = section_context(interface, handle);
+    let data = {
+        let mut section = interface.open_section(handle)?;
+        section.seek(SeekFrom::Start(0)).map_err(|e| context(e.into()))?;
+        let mut data = Vec::with_capacity(section.size());
+        section.read_to_end(&mut data).map_err(|e| context(e.into()))?;
+        data
+    };
+    let truncated = || context(Error::Truncation("read checkpoints"));
+    let mut values = data
+        .chunks(4)
+        .map(|v| if v.len() == 4 { Some(LittleEndian::read_u32(v)) } else { None });
+    let interval = values.next().flatten().ok_or_else(truncated)?;
+    if interval == 0 {
+        return Ok(None);
+    }
+    // The end of the section is only allowed between two records.
+    while let Some(record) = values.next() {
+        let record = record.ok_or_else(truncated)?;
+        let count = values.next().flatten().ok_or_else(truncated)?;
+        let chunks = (0..count)
+            .map(|_| values.next().flatten().ok_or_else(truncated))
+            .collect::<Result<Vec<u32>>>()?;
+        if record != index {
+            continue;
+        }
+        let expected = (size as u64).div_ceil(interval as u64);
+        if count as u64 != expected {
+            return Err(context(Error::Corruption(format!(
+                "section {} has {} checkpoints but its {} bytes make {} chunks",
+                index, count, size, expected
+            ))));
+        }
+        return Ok(Some(Checkpoints { interval, chunks }));
+    }
+    return Ok(None);
+}
+
+/// Checks data against the checksums of its chunks as it is read.
+pub(crate) struct ChunkVerifier
+{
+    interval: usize,
+    chunks: Vec<u32>,
+    current: Crc32Checksum,
+    len: usize,
+    next: usize
+}
+
+impl ChunkVerifier
+{
+    pub fn new(interval: u32, chunks: Vec<u32>) -> ChunkVerifier
+    {
+        return ChunkVerifier {
+            interval: interval as usize,
+            chunks,
+            current: Crc32Checksum::new(),
+            len: 0,
+            next: 0
+        };
+    }
+
+    /// Verifies each chunk completed by `data`.
+    pub fn push(&mut self, data: &[u8]) -> Result<()>
+    {
+        return self.advance(data, true);
+    }
+
+    /// Walks `data`, verifying or appending the checksum of each chunk it completes.
+    fn advance(&mut self, mut data: &[u8], verify: bool) -> Result<()>
+    {
+        while !data.is_empty() {
+            let len = std::cmp::min(self.interval - self.len, data.len());
+            self.current.push(&data[..len]);
+            self.len += len;
+            data = &data[len..];
+            if self.len < self.interval {
+                continue;
+            }
+            if verify {
+                self.check()?;
+            } else {
+                let chksum = std::mem::replace(&mut self.current, Crc32Checksum::new());
+                self.chunks.push(chksum.finish());
+                self.len = 0;
+            }
+        }
+        return Ok(());
+    }
+
+    /// Verifies the last chunk, which may be shorter than the chunk size.
+    pub fn finish(&mut self) -> Result<()>
+    {
+        if self.len > 0 {
+            self.check()?;
+        }
+        return Ok(());
+    }
+
+    fn check(&mut self) -> Result<()>
+    {
+        // The next chunk starts here even if this one is corrupted.
+        let chksum = std::mem::replace(&mut self.current, Crc32Checksum::new());
+        let v = chksum.finish();
+        let expected = self.chunks.get(self.next).copied();
+        self.len = 0;
+        self.next += 1;
+        let expected = expected.ok_or_else(|| {
+            Error::Corruption(format!("section is longer than its {} checkpoints", self.chunks.len()))
+        })?;
+        if v != expected {
+            return Err(Error::Checksum(v, expected));
+        }
+        return Ok(());
+    }
+}
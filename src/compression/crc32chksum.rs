@@ -69,9 +69,13 @@ impl Checksum for Crc32Checksum
         }
     }
 
-    fn finish(mut self) -> u32
+    fn value(&self) -> u32
     {
-        self.current ^= 0xFFFFFFFF;
-        return self.current;
+        return self.current ^ 0xFFFFFFFF;
+    }
+
+    fn finish(self) -> u32
+    {
+        return self.value();
     }
 }
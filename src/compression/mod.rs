@@ -26,37 +26,143 @@
 // NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
 // SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use std::io::{Read, Write};
+//! Compression and checksum algorithms used for BPX sections.
+
+use std::{
+    fmt::{Display, Formatter},
+    io::{Read, Write}
+};
 
 use crate::Result;
 
 mod crc32chksum;
+mod nochksum;
+mod timedchksum;
 mod weakchksum;
 mod xz;
 mod zlib;
 
-pub use crc32chksum::Crc32Checksum;
-pub use weakchksum::WeakChecksum;
-pub use xz::XzCompressionMethod;
-pub use zlib::ZlibCompressionMethod;
+pub(crate) use crc32chksum::Crc32Checksum;
+pub(crate) use nochksum::NoChecksum;
+pub(crate) use timedchksum::TimedChecksum;
+pub(crate) use weakchksum::WeakChecksum;
+pub(crate) use xz::XzCompressionMethod;
+pub(crate) use zlib::ZlibCompressionMethod;
+#[cfg(feature = "zip")]
+pub(crate) use zlib::{crc32_update, RawDeflateWriter};
+
+/// Represents a compression error.
+#[derive(Debug)]
+pub enum Error
+{
+    /// Describes a compression error.
+    ///
+    /// # Arguments
+    /// * error description string.
+    Deflate(&'static str),
+
+    /// Describes a decompression error.
+    ///
+    /// # Arguments
+    /// * error description string.
+    Inflate(&'static str)
+}
+
+impl Display for Error
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        return match self {
+            Error::Deflate(e) => f.write_str(&format!("deflate error ({})", e)),
+            Error::Inflate(e) => f.write_str(&format!("inflate error ({})", e))
+        };
+    }
+}
+
+impl std::error::Error for Error {}
 
-pub trait Checksum
+pub(crate) trait Checksum
 {
     fn push(&mut self, buffer: &[u8]);
+
+    /// Returns the checksum of the data pushed so far, without finishing it.
+    fn value(&self) -> u32;
+
     fn finish(self) -> u32;
 }
 
-pub trait Inflater
+pub(crate) trait Inflater
 {
+    /// Inflates exactly `deflated_size` bytes of input which must produce
+    /// exactly `inflated_size` bytes of output.
     fn inflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
         input: &mut TRead,
         output: &mut TWrite,
         deflated_size: usize,
+        inflated_size: usize,
         chksum: &mut TChecksum
     ) -> Result<()>;
+
+    /// Creates an inflater producing the output of `deflated_size` bytes of
+    /// input on demand, the output must be exactly `inflated_size` bytes.
+    fn stream(deflated_size: usize, inflated_size: usize) -> Result<Box<dyn InflateStream>>;
+}
+
+/// An inflater pulling its input as its output is requested.
+pub(crate) trait InflateStream
+{
+    /// Inflates up to `out.len()` bytes, returns 0 once the stream has ended
+    /// and was checked against the sizes it was created with.
+    fn inflate_some(&mut self, input: &mut dyn Read, out: &mut [u8]) -> Result<usize>;
+}
+
+/// Counts the bytes produced by an inflater, failing as soon as they exceed
+/// the size declared by the section header.
+pub(crate) struct InflatedSize
+{
+    count: usize,
+    expected: usize
+}
+
+impl InflatedSize
+{
+    pub fn new(expected: usize) -> InflatedSize
+    {
+        return InflatedSize { count: 0, expected };
+    }
+
+    pub fn push(&mut self, len: usize) -> Result<()>
+    {
+        self.count += len;
+        if self.count > self.expected {
+            return Err(crate::error::Error::Corruption(format!(
+                "section inflates to at least {} bytes but its header declares {}",
+                self.count, self.expected
+            )));
+        }
+        return Ok(());
+    }
+
+    /// Checks the stream ended with the declared size and no compressed byte left.
+    pub fn finish(self, unconsumed: usize) -> Result<()>
+    {
+        if self.count != self.expected {
+            return Err(crate::error::Error::Corruption(format!(
+                "section inflates to {} bytes but its header declares {}",
+                self.count, self.expected
+            )));
+        }
+        if unconsumed > 0 {
+            return Err(crate::error::Error::Corruption(format!(
+                "{} compressed bytes left after the end of the section stream",
+                unconsumed
+            )));
+        }
+        return Ok(());
+    }
 }
 
-pub trait Deflater
+pub(crate) trait Deflater
 {
     fn deflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
         input: &mut TRead,
@@ -0,0 +1,81 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::time::{Duration, Instant};
+
+use crate::compression::Checksum;
+
+/// Wraps a checksum to measure the time spent computing it, once per pushed buffer.
+pub struct TimedChecksum<TChecksum: Checksum>
+{
+    inner: TChecksum,
+    elapsed: Option<Duration>
+}
+
+impl<TChecksum: Checksum> TimedChecksum<TChecksum>
+{
+    /// Creates a new wrapper, which only measures time if `timed` is true.
+    pub fn new(inner: TChecksum, timed: bool) -> TimedChecksum<TChecksum>
+    {
+        return TimedChecksum {
+            inner,
+            elapsed: if timed { Some(Duration::ZERO) } else { None }
+        };
+    }
+
+    /// Returns the time spent in the wrapped checksum, zero if not timed.
+    pub fn elapsed(&self) -> Duration
+    {
+        return self.elapsed.unwrap_or(Duration::ZERO);
+    }
+}
+
+impl<TChecksum: Checksum> Checksum for TimedChecksum<TChecksum>
+{
+    fn push(&mut self, buffer: &[u8])
+    {
+        match self.elapsed.as_mut() {
+            Some(elapsed) => {
+                let start = Instant::now();
+                self.inner.push(buffer);
+                *elapsed += start.elapsed();
+            },
+            None => self.inner.push(buffer)
+        }
+    }
+
+    fn value(&self) -> u32
+    {
+        return self.inner.value();
+    }
+
+    fn finish(self) -> u32
+    {
+        return self.inner.finish();
+    }
+}
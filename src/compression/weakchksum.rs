@@ -39,11 +39,16 @@ impl Checksum for WeakChecksum
 {
     fn push(&mut self, data: &[u8])
     {
-        for i in 0..data.len() {
-            self.current += Wrapping(data[i] as u32);
+        for byte in data {
+            self.current += Wrapping(*byte as u32);
         }
     }
 
+    fn value(&self) -> u32
+    {
+        return self.current.0;
+    }
+
     fn finish(self) -> u32
     {
         return self.current.0;
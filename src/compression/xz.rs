@@ -50,9 +50,10 @@ use lzma_sys::{
     LZMA_UNSUPPORTED_CHECK
 };
 
+use super::Error;
 use crate::{
-    compression::{Checksum, Deflater, Inflater},
-    error::Error,
+    compression::{Checksum, Deflater, InflateStream, InflatedSize, Inflater},
+    utils::read_fill,
     Result
 };
 
@@ -86,10 +87,10 @@ fn new_encoder() -> Result<lzma_stream>
             return Ok(stream);
         }
         match res {
-            LZMA_MEM_ERROR => return Err(Error::Deflate("Memory allocation failure")),
-            LZMA_OPTIONS_ERROR => return Err(Error::Deflate("Specified filter chain is not supported")),
-            LZMA_UNSUPPORTED_CHECK => return Err(Error::Deflate("Specified integrity check is not supported")),
-            _ => return Err(Error::Deflate("Unknown error, possibly a bug"))
+            LZMA_MEM_ERROR => return Err(Error::Deflate("Memory allocation failure").into()),
+            LZMA_OPTIONS_ERROR => return Err(Error::Deflate("Specified filter chain is not supported").into()),
+            LZMA_UNSUPPORTED_CHECK => return Err(Error::Deflate("Specified integrity check is not supported").into()),
+            _ => return Err(Error::Deflate("Unknown error, possibly a bug").into())
         };
     }
 }
@@ -103,10 +104,10 @@ fn new_decoder() -> Result<lzma_stream>
             return Ok(stream);
         }
         match res {
-            LZMA_MEM_ERROR => return Err(Error::Inflate("Memory allocation failure")),
-            LZMA_OPTIONS_ERROR => return Err(Error::Inflate("Specified filter chain is not supported")),
-            LZMA_UNSUPPORTED_CHECK => return Err(Error::Inflate("Specified integrity check is not supported")),
-            _ => return Err(Error::Inflate("Unknown error, possibly a bug"))
+            LZMA_MEM_ERROR => return Err(Error::Inflate("Memory allocation failure").into()),
+            LZMA_OPTIONS_ERROR => return Err(Error::Inflate("Specified filter chain is not supported").into()),
+            LZMA_UNSUPPORTED_CHECK => return Err(Error::Inflate("Specified integrity check is not supported").into()),
+            _ => return Err(Error::Inflate("Unknown error, possibly a bug").into())
         };
     }
 }
@@ -131,7 +132,11 @@ fn do_deflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
     stream.avail_out = ENCODER_BUF_SIZE;
     loop {
         if stream.avail_in == 0 && count < inflated_size {
-            let len = input.read(&mut inbuf)?;
+            let wanted = std::cmp::min(ENCODER_BUF_SIZE, inflated_size - count);
+            let len = read_fill(input, &mut inbuf[0..wanted])?;
+            if len < wanted {
+                return Err(crate::error::Error::Truncation("xz deflate"));
+            }
             count += len;
             chksum.push(&inbuf[0..len]);
             stream.avail_in = len;
@@ -145,7 +150,7 @@ fn do_deflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
             if stream.avail_out == 0 || res == LZMA_STREAM_END {
                 let size = ENCODER_BUF_SIZE - stream.avail_out;
                 csize += size;
-                output.write(&outbuf[0..size])?;
+                output.write_all(&outbuf[0..size])?;
                 stream.avail_out = ENCODER_BUF_SIZE;
                 stream.next_out = outbuf.as_mut_ptr();
             }
@@ -154,9 +159,9 @@ fn do_deflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
                     break;
                 }
                 match res {
-                    LZMA_MEM_ERROR => return Err(Error::Deflate("Memory allocation failure")),
-                    LZMA_DATA_ERROR => return Err(Error::Deflate("LZMA data error")),
-                    _ => return Err(Error::Deflate("Unknown error, possibly a bug"))
+                    LZMA_MEM_ERROR => return Err(Error::Deflate("Memory allocation failure").into()),
+                    LZMA_DATA_ERROR => return Err(Error::Deflate("LZMA data error").into()),
+                    _ => return Err(Error::Deflate("Unknown error, possibly a bug").into())
                 };
             }
         }
@@ -169,6 +174,7 @@ fn do_inflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
     input: &mut TRead,
     output: &mut TWrite,
     deflated_size: usize,
+    inflated_size: usize,
     chksum: &mut TChecksum
 ) -> Result<()>
 {
@@ -176,6 +182,7 @@ fn do_inflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
     let mut inbuf: [u8; ENCODER_BUF_SIZE] = [0; ENCODER_BUF_SIZE];
     let mut outbuf: [u8; DECODER_BUF_SIZE] = [0; DECODER_BUF_SIZE];
     let mut remaining = deflated_size;
+    let mut produced = InflatedSize::new(inflated_size);
 
     stream.next_in = inbuf.as_ptr();
     stream.avail_in = 0;
@@ -183,7 +190,11 @@ fn do_inflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
     stream.avail_out = DECODER_BUF_SIZE;
     loop {
         if stream.avail_in == 0 && remaining > 0 {
-            let res = input.read(&mut inbuf[0..std::cmp::min(ENCODER_BUF_SIZE, remaining)])?;
+            let wanted = std::cmp::min(ENCODER_BUF_SIZE, remaining);
+            let res = read_fill(input, &mut inbuf[0..wanted])?;
+            if res < wanted {
+                return Err(crate::error::Error::Truncation("xz inflate"));
+            }
             remaining -= res;
             stream.avail_in = res;
             stream.next_in = inbuf.as_ptr();
@@ -195,24 +206,24 @@ fn do_inflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
             let res = lzma_code(stream, action);
             if stream.avail_out == 0 || res == LZMA_STREAM_END {
                 let size = DECODER_BUF_SIZE - stream.avail_out;
+                produced.push(size)?;
                 chksum.push(&outbuf[0..size]);
-                output.write(&outbuf[0..size])?;
+                output.write_all(&outbuf[0..size])?;
                 stream.avail_out = DECODER_BUF_SIZE;
                 stream.next_out = outbuf.as_mut_ptr();
             }
             if res != LZMA_OK {
                 if res == LZMA_STREAM_END {
-                    break;
+                    return produced.finish(remaining + stream.avail_in);
                 }
                 match res {
-                    LZMA_MEM_ERROR => return Err(Error::Inflate("Memory allocation failure")),
-                    LZMA_DATA_ERROR | LZMA_BUF_ERROR => return Err(Error::Inflate("LZMA data error")),
-                    _ => return Err(Error::Inflate("Unknown error, possibly a bug"))
+                    LZMA_MEM_ERROR => return Err(Error::Inflate("Memory allocation failure").into()),
+                    LZMA_DATA_ERROR | LZMA_BUF_ERROR => return Err(Error::Inflate("LZMA data error").into()),
+                    _ => return Err(Error::Inflate("Unknown error, possibly a bug").into())
                 };
             }
         }
     }
-    return Ok(());
 }
 
 pub struct XzCompressionMethod {}
@@ -241,14 +252,93 @@ impl Inflater for XzCompressionMethod
         input: &mut TRead,
         output: &mut TWrite,
         deflated_size: usize,
+        inflated_size: usize,
         chksum: &mut TChecksum
     ) -> Result<()>
     {
         let mut stream = new_decoder()?;
-        let res = do_inflate(&mut stream, input, output, deflated_size, chksum);
+        let res = do_inflate(&mut stream, input, output, deflated_size, inflated_size, chksum);
         unsafe {
             lzma_end(&mut stream);
         }
         return res;
     }
+
+    fn stream(deflated_size: usize, inflated_size: usize) -> Result<Box<dyn InflateStream>>
+    {
+        return Ok(Box::new(XzInflateStream {
+            stream: new_decoder()?,
+            inbuf: Box::new([0; ENCODER_BUF_SIZE]),
+            remaining: deflated_size,
+            produced: Some(InflatedSize::new(inflated_size))
+        }));
+    }
+}
+
+struct XzInflateStream
+{
+    stream: lzma_stream,
+    // Boxed so that the input does not move between calls.
+    inbuf: Box<[u8; ENCODER_BUF_SIZE]>,
+    remaining: usize,
+    // None once the end of the stream was reached and checked.
+    produced: Option<InflatedSize>
+}
+
+impl InflateStream for XzInflateStream
+{
+    fn inflate_some(&mut self, input: &mut dyn Read, out: &mut [u8]) -> Result<usize>
+    {
+        let produced = match self.produced.as_mut() {
+            Some(v) => v,
+            None => return Ok(0)
+        };
+        if out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.stream.avail_in == 0 && self.remaining > 0 {
+                let wanted = std::cmp::min(ENCODER_BUF_SIZE, self.remaining);
+                let len = read_fill(input, &mut self.inbuf[0..wanted])?;
+                if len < wanted {
+                    return Err(crate::error::Error::Truncation("xz inflate"));
+                }
+                self.remaining -= len;
+                self.stream.avail_in = len;
+                self.stream.next_in = self.inbuf.as_ptr();
+            }
+            let action = if self.remaining == 0 { LZMA_FINISH } else { LZMA_RUN };
+            self.stream.avail_out = out.len();
+            self.stream.next_out = out.as_mut_ptr();
+            let res = unsafe { lzma_code(&mut self.stream, action) };
+            let len = out.len() - self.stream.avail_out;
+            produced.push(len)?;
+            match res {
+                LZMA_OK => (),
+                LZMA_STREAM_END => {
+                    let unconsumed = self.remaining + self.stream.avail_in;
+                    if let Some(produced) = self.produced.take() {
+                        produced.finish(unconsumed)?;
+                    }
+                    return Ok(len);
+                },
+                LZMA_MEM_ERROR => return Err(Error::Inflate("Memory allocation failure").into()),
+                LZMA_DATA_ERROR | LZMA_BUF_ERROR => return Err(Error::Inflate("LZMA data error").into()),
+                _ => return Err(Error::Inflate("Unknown error, possibly a bug").into())
+            }
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+impl Drop for XzInflateStream
+{
+    fn drop(&mut self)
+    {
+        unsafe {
+            lzma_end(&mut self.stream);
+        }
+    }
 }
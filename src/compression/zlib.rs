@@ -36,6 +36,8 @@ use libz_sys::{
     inflateEnd,
     inflateInit_,
     z_stream,
+    zlibVersion,
+    Z_BUF_ERROR,
     Z_DATA_ERROR,
     Z_DEFAULT_COMPRESSION,
     Z_FINISH,
@@ -43,13 +45,15 @@ use libz_sys::{
     Z_NEED_DICT,
     Z_NO_FLUSH,
     Z_OK,
+    Z_STREAM_END,
     Z_STREAM_ERROR,
     Z_VERSION_ERROR
 };
 
+use super::Error;
 use crate::{
-    compression::{Checksum, Deflater, Inflater},
-    error::Error,
+    compression::{Checksum, Deflater, InflateStream, InflatedSize, Inflater},
+    utils::read_fill,
     Result
 };
 
@@ -63,48 +67,44 @@ const DECODER_BUF_SIZE: usize = ENCODER_BUF_SIZE * 2;
 unsafe fn zstream_zeroed() -> z_stream
 {
     let arr: [u8; std::mem::size_of::<z_stream>()] = [0; std::mem::size_of::<z_stream>()];
-    return std::mem::transmute(arr);
+    return std::mem::transmute::<[u8; std::mem::size_of::<z_stream>()], z_stream>(arr);
 }
 
-fn new_encoder() -> Result<z_stream>
+// zlib keeps a pointer to the stream it was initialized with, so streams are
+// initialized in place and must not move until they are ended.
+fn init_encoder(stream: &mut z_stream) -> Result<()>
 {
     unsafe {
-        let mut stream: z_stream = zstream_zeroed();
         let err = deflateInit_(
-            &mut stream as _,
+            stream as _,
             Z_DEFAULT_COMPRESSION,
-            "1.1.3".as_ptr() as _,
+            zlibVersion(),
             std::mem::size_of::<z_stream>() as _
         );
         if err == Z_OK {
-            return Ok(stream);
+            return Ok(());
         }
         return match err {
-            Z_MEM_ERROR => Err(Error::Deflate("Memory allocation failure")),
-            Z_STREAM_ERROR => Err(Error::Deflate("Invalid compression level")),
-            Z_VERSION_ERROR => Err(Error::Deflate("Version mismatch")),
-            _ => Err(Error::Deflate("Unknown error, possibly a bug"))
+            Z_MEM_ERROR => Err(Error::Deflate("Memory allocation failure").into()),
+            Z_STREAM_ERROR => Err(Error::Deflate("Invalid compression level").into()),
+            Z_VERSION_ERROR => Err(Error::Deflate("Version mismatch").into()),
+            _ => Err(Error::Deflate("Unknown error, possibly a bug").into())
         }
     }
 }
 
-fn new_decoder() -> Result<z_stream>
+fn init_decoder(stream: &mut z_stream) -> Result<()>
 {
     unsafe {
-        let mut stream: z_stream = zstream_zeroed();
-        let err = inflateInit_(
-            &mut stream as _,
-            "1.1.3".as_ptr() as _,
-            std::mem::size_of::<z_stream>() as _
-        );
+        let err = inflateInit_(stream as _, zlibVersion(), std::mem::size_of::<z_stream>() as _);
         if err == Z_OK {
-            return Ok(stream);
+            return Ok(());
         }
         return match err {
-            Z_MEM_ERROR => Err(Error::Deflate("Memory allocation failure")),
-            Z_DATA_ERROR => Err(Error::Deflate("ZLIB data error")),
-            Z_VERSION_ERROR => Err(Error::Deflate("Version mismatch")),
-            _ => Err(Error::Deflate("Unknown error, possibly a bug"))
+            Z_MEM_ERROR => Err(Error::Inflate("Memory allocation failure").into()),
+            Z_DATA_ERROR => Err(Error::Inflate("ZLIB data error").into()),
+            Z_VERSION_ERROR => Err(Error::Inflate("Version mismatch").into()),
+            _ => Err(Error::Inflate("Unknown error, possibly a bug").into())
         }
     }
 }
@@ -123,7 +123,11 @@ fn do_deflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
     let mut csize: usize = 0;
 
     loop {
-        let len = input.read(&mut inbuf)?;
+        let wanted = std::cmp::min(ENCODER_BUF_SIZE, inflated_size - count);
+        let len = read_fill(input, &mut inbuf[0..wanted])?;
+        if len < wanted {
+            return Err(crate::error::Error::Truncation("zlib deflate"));
+        }
         count += len;
         chksum.push(&inbuf[0..len]);
         stream.avail_in = len as _;
@@ -140,19 +144,19 @@ fn do_deflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
             stream.next_out = outbuf.as_mut_ptr();
             unsafe {
                 let err = deflate(stream, action);
-                if err != Z_OK {
+                if err != Z_OK && err != Z_STREAM_END && err != Z_BUF_ERROR {
                     return match err {
-                        Z_MEM_ERROR => Err(Error::Deflate("Memory allocation failure")),
-                        Z_STREAM_ERROR => Err(Error::Deflate("Invalid compression level")),
-                        Z_VERSION_ERROR => Err(Error::Deflate("Version mismatch")),
-                        _ => Err(Error::Deflate("Unknown error, possibly a bug"))
+                        Z_MEM_ERROR => Err(Error::Deflate("Memory allocation failure").into()),
+                        Z_STREAM_ERROR => Err(Error::Deflate("Invalid compression level").into()),
+                        Z_VERSION_ERROR => Err(Error::Deflate("Version mismatch").into()),
+                        _ => Err(Error::Deflate("Unknown error, possibly a bug").into())
                     }
                 }
             }
             let len = ENCODER_BUF_SIZE - stream.avail_out as usize;
-            output.write(&outbuf[0..len])?;
+            output.write_all(&outbuf[0..len])?;
             csize += len;
-            if stream.avail_out == 0 {
+            if stream.avail_out != 0 {
                 break;
             }
         }
@@ -168,19 +172,23 @@ fn do_inflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
     input: &mut TRead,
     output: &mut TWrite,
     deflated_size: usize,
+    inflated_size: usize,
     chksum: &mut TChecksum
 ) -> Result<()>
 {
     let mut inbuf: [u8; DECODER_BUF_SIZE] = [0; DECODER_BUF_SIZE];
     let mut outbuf: [u8; DECODER_BUF_SIZE] = [0; DECODER_BUF_SIZE];
     let mut remaining = deflated_size;
+    let mut produced = InflatedSize::new(inflated_size);
+    let mut ended = false;
 
-    loop {
-        let len = input.read(&mut inbuf)?;
-        remaining -= len;
-        if remaining == 0 && len == 0 {
-            break;
+    while remaining > 0 && !ended {
+        let wanted = std::cmp::min(DECODER_BUF_SIZE, remaining);
+        let len = read_fill(input, &mut inbuf[0..wanted])?;
+        if len < wanted {
+            return Err(crate::error::Error::Truncation("zlib inflate"));
         }
+        remaining -= len;
         stream.avail_in = len as _;
         stream.next_in = inbuf.as_mut_ptr();
         loop {
@@ -189,22 +197,27 @@ fn do_inflate<TRead: Read, TWrite: Write, TChecksum: Checksum>(
             unsafe {
                 let err = inflate(stream, Z_NO_FLUSH);
                 match err {
-                    Z_MEM_ERROR => return Err(Error::Deflate("Memory allocation failure")),
-                    Z_DATA_ERROR => return Err(Error::Deflate("ZLIB data error")),
-                    Z_NEED_DICT => return Err(Error::Deflate("ZLIB data error")),
-                    Z_VERSION_ERROR => return Err(Error::Deflate("Version mismatch")),
+                    Z_MEM_ERROR => return Err(Error::Inflate("Memory allocation failure").into()),
+                    Z_DATA_ERROR => return Err(Error::Inflate("ZLIB data error").into()),
+                    Z_NEED_DICT => return Err(Error::Inflate("ZLIB data error").into()),
+                    Z_VERSION_ERROR => return Err(Error::Inflate("Version mismatch").into()),
+                    Z_STREAM_END => ended = true,
                     _ => ()
                 }
             }
             let len = DECODER_BUF_SIZE - stream.avail_out as usize;
+            produced.push(len)?;
             chksum.push(&outbuf[0..len]);
-            output.write(&outbuf[0..len])?;
-            if stream.avail_out == 0 {
+            output.write_all(&outbuf[0..len])?;
+            if stream.avail_out != 0 || ended {
                 break;
             }
         }
     }
-    return Ok(());
+    if !ended {
+        return Err(crate::error::Error::Truncation("zlib inflate"));
+    }
+    return produced.finish(remaining + stream.avail_in as usize);
 }
 
 pub struct ZlibCompressionMethod {}
@@ -218,7 +231,8 @@ impl Deflater for ZlibCompressionMethod
         chksum: &mut TChecksum
     ) -> Result<usize>
     {
-        let mut encoder = new_encoder()?;
+        let mut encoder = unsafe { zstream_zeroed() };
+        init_encoder(&mut encoder)?;
         let res = do_deflate(&mut encoder, input, output, inflated_size, chksum);
         unsafe {
             deflateEnd(&mut encoder);
@@ -233,14 +247,206 @@ impl Inflater for ZlibCompressionMethod
         input: &mut TRead,
         output: &mut TWrite,
         deflated_size: usize,
+        inflated_size: usize,
         chksum: &mut TChecksum
     ) -> Result<()>
     {
-        let mut decoder = new_decoder()?;
-        let res = do_inflate(&mut decoder, input, output, deflated_size, chksum);
+        let mut decoder = unsafe { zstream_zeroed() };
+        init_decoder(&mut decoder)?;
+        let res = do_inflate(&mut decoder, input, output, deflated_size, inflated_size, chksum);
         unsafe {
             inflateEnd(&mut decoder);
         }
         return res;
     }
+
+    fn stream(deflated_size: usize, inflated_size: usize) -> Result<Box<dyn InflateStream>>
+    {
+        let mut stream = Box::new(unsafe { zstream_zeroed() });
+        init_decoder(&mut stream)?;
+        return Ok(Box::new(ZlibInflateStream {
+            stream,
+            inbuf: Box::new([0; DECODER_BUF_SIZE]),
+            remaining: deflated_size,
+            produced: Some(InflatedSize::new(inflated_size))
+        }));
+    }
+}
+
+struct ZlibInflateStream
+{
+    // Both boxed so that neither the stream nor its input move between calls.
+    stream: Box<z_stream>,
+    inbuf: Box<[u8; DECODER_BUF_SIZE]>,
+    remaining: usize,
+    // None once the end of the stream was reached and checked.
+    produced: Option<InflatedSize>
+}
+
+impl InflateStream for ZlibInflateStream
+{
+    fn inflate_some(&mut self, input: &mut dyn Read, out: &mut [u8]) -> Result<usize>
+    {
+        let produced = match self.produced.as_mut() {
+            Some(v) => v,
+            None => return Ok(0)
+        };
+        if out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.stream.avail_in == 0 && self.remaining > 0 {
+                let wanted = std::cmp::min(DECODER_BUF_SIZE, self.remaining);
+                let len = read_fill(input, &mut self.inbuf[0..wanted])?;
+                if len < wanted {
+                    return Err(crate::error::Error::Truncation("zlib inflate"));
+                }
+                self.remaining -= len;
+                self.stream.avail_in = len as _;
+                self.stream.next_in = self.inbuf.as_mut_ptr();
+            }
+            let wanted = std::cmp::min(out.len(), u32::MAX as usize);
+            self.stream.avail_out = wanted as _;
+            self.stream.next_out = out.as_mut_ptr();
+            let err = unsafe { inflate(&mut *self.stream, Z_NO_FLUSH) };
+            match err {
+                Z_MEM_ERROR => return Err(Error::Inflate("Memory allocation failure").into()),
+                Z_DATA_ERROR => return Err(Error::Inflate("ZLIB data error").into()),
+                Z_NEED_DICT => return Err(Error::Inflate("ZLIB data error").into()),
+                Z_VERSION_ERROR => return Err(Error::Inflate("Version mismatch").into()),
+                _ => ()
+            }
+            let len = wanted - self.stream.avail_out as usize;
+            produced.push(len)?;
+            if err == Z_STREAM_END {
+                let unconsumed = self.remaining + self.stream.avail_in as usize;
+                if let Some(produced) = self.produced.take() {
+                    produced.finish(unconsumed)?;
+                }
+                return Ok(len);
+            }
+            if len > 0 {
+                return Ok(len);
+            }
+            if self.stream.avail_in == 0 && self.remaining == 0 {
+                return Err(crate::error::Error::Truncation("zlib inflate"));
+            }
+        }
+    }
+}
+
+impl Drop for ZlibInflateStream
+{
+    fn drop(&mut self)
+    {
+        unsafe {
+            inflateEnd(&mut *self.stream);
+        }
+    }
+}
+
+/// Computes the standard (zip/gzip) CRC32 of data, continuing from `crc`.
+#[cfg(feature = "zip")]
+pub(crate) fn crc32_update(crc: u32, data: &[u8]) -> u32
+{
+    let mut crc = crc as libz_sys::uLong;
+    // zlib takes the length as a 32 bits integer.
+    for chunk in data.chunks(u32::MAX as usize) {
+        crc = unsafe { libz_sys::crc32(crc, chunk.as_ptr(), chunk.len() as _) };
+    }
+    return crc as u32;
+}
+
+/// Streams data written to it as a raw deflate stream (no zlib header),
+/// as used by zip archives.
+#[cfg(feature = "zip")]
+pub(crate) struct RawDeflateWriter<TWrite: Write>
+{
+    // Boxed so that the stream does not move after initialization.
+    stream: Box<z_stream>,
+    output: TWrite,
+    outbuf: [u8; ENCODER_BUF_SIZE]
+}
+
+#[cfg(feature = "zip")]
+impl<TWrite: Write> RawDeflateWriter<TWrite>
+{
+    pub fn new(output: TWrite) -> Result<RawDeflateWriter<TWrite>>
+    {
+        let mut stream = Box::new(unsafe { zstream_zeroed() });
+        let err = unsafe {
+            libz_sys::deflateInit2_(
+                &mut *stream as _,
+                Z_DEFAULT_COMPRESSION,
+                libz_sys::Z_DEFLATED,
+                -15,
+                8,
+                libz_sys::Z_DEFAULT_STRATEGY,
+                zlibVersion(),
+                std::mem::size_of::<z_stream>() as _
+            )
+        };
+        if err != Z_OK {
+            return Err(Error::Deflate("Unable to initialize raw deflate stream").into());
+        }
+        return Ok(RawDeflateWriter {
+            stream,
+            output,
+            outbuf: [0; ENCODER_BUF_SIZE]
+        });
+    }
+
+    fn run(&mut self, input: &[u8], action: std::os::raw::c_int) -> std::io::Result<()>
+    {
+        self.stream.avail_in = input.len() as _;
+        // zlib never writes through next_in.
+        self.stream.next_in = input.as_ptr() as *mut _;
+        loop {
+            self.stream.avail_out = ENCODER_BUF_SIZE as _;
+            self.stream.next_out = self.outbuf.as_mut_ptr();
+            let err = unsafe { deflate(&mut *self.stream, action) };
+            if err != Z_OK && err != Z_STREAM_END && err != Z_BUF_ERROR {
+                return Err(std::io::Error::other(Error::Deflate("Unknown error, possibly a bug")));
+            }
+            let len = ENCODER_BUF_SIZE - self.stream.avail_out as usize;
+            self.output.write_all(&self.outbuf[0..len])?;
+            if self.stream.avail_out != 0 && (action != Z_FINISH || err == Z_STREAM_END) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Terminates the deflate stream.
+    pub fn finish(mut self) -> std::io::Result<()>
+    {
+        return self.run(&[], Z_FINISH);
+    }
+}
+
+#[cfg(feature = "zip")]
+impl<TWrite: Write> Write for RawDeflateWriter<TWrite>
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>
+    {
+        // Keep avail_in within the 32 bits of zlib.
+        let len = std::cmp::min(buf.len(), u32::MAX as usize);
+        self.run(&buf[0..len], Z_NO_FLUSH)?;
+        return Ok(len);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()>
+    {
+        return self.output.flush();
+    }
+}
+
+#[cfg(feature = "zip")]
+impl<TWrite: Write> Drop for RawDeflateWriter<TWrite>
+{
+    fn drop(&mut self)
+    {
+        unsafe {
+            deflateEnd(&mut *self.stream);
+        }
+    }
 }
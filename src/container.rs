@@ -0,0 +1,1057 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The BPX container.
+//!
+//! A [Container](self::Container) reads and writes a BPX through a single IO
+//! backend which allows editing existing files in place.
+//!
+//! *Sections which are not modified are saved byte for byte, including their
+//! header, so sections of types or with flags unknown to this crate survive
+//! an open-edit-save cycle.*
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    io,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path
+};
+
+use crate::{
+    build_id,
+    compression::{Checksum, Crc32Checksum},
+    decoder::{load_section, read_section_header_table},
+    encoder::{create_raw_section, create_section, get_flags, write_section},
+    error::Error,
+    format::{MAIN_HEADER_SIZE, MAX_SECTION_SIZE, SECTION_HEADER_SIZE},
+    header::{MainHeader, SectionFlags, SectionHeader, SECTION_TYPE_NAMES},
+    names,
+    section::{
+        new_section_data,
+        new_staging_buffer,
+        section_data_from_content,
+        storage_report,
+        SectionData,
+        SectionGuard,
+        StorageReport,
+        TempStorage
+    },
+    utils::{section_count, write_atomically, OptionExtension},
+    Interface,
+    Result,
+    SectionHandle,
+    WriteInterface
+};
+
+const READ_BLOCK_SIZE: usize = 8192;
+
+/// Represents the IO backend for a BPX container.
+pub trait IoBackend: io::Read + io::Write + io::Seek
+{
+}
+impl<T: io::Read + io::Write + io::Seek> IoBackend for T {}
+
+struct SectionEntry
+{
+    header: SectionHeader,
+    data: Option<Box<dyn SectionData>>,
+    // True if the header points to the data of this section in the IO backend.
+    stored: bool,
+    // Checksum of the section data at the time it was loaded or saved.
+    baseline: Option<u32>,
+    // Pre-compressed bytes of a section created with create_section_raw, until saved.
+    raw: Option<Box<dyn SectionData>>
+}
+
+/// Bytes reclaimed by [compact](Container::compact).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompactReport
+{
+    /// The number of bytes in the IO backend of the container.
+    pub input_size: u64,
+
+    /// The size of the compacted file.
+    pub output_size: u64,
+
+    /// The number of bytes of the saved file which no section refers to, left
+    /// by sections which shrunk when saved in place.
+    pub slack: u64,
+
+    /// The number of bytes following the end of the saved file in the IO backend,
+    /// left by saves which made the file smaller.
+    pub trailing: u64,
+
+    /// The number of bytes saved by storing sections uncompressed when compression
+    /// made them larger.
+    pub recompressed: u64,
+
+    /// The number of sections stored uncompressed for this reason.
+    pub decompressed_sections: usize
+}
+
+impl CompactReport
+{
+    /// Returns the number of bytes the compacted file is smaller than the IO backend of the container.
+    pub fn reclaimed(&self) -> u64
+    {
+        return self.input_size.saturating_sub(self.output_size);
+    }
+}
+
+/// The BPX container.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use bpx::container::Container;
+/// use bpx::header::SectionHeader;
+/// use bpx::Interface;
+///
+/// let mut file = Container::create(Cursor::new(Vec::<u8>::new()));
+/// let handle = file.sections().create(SectionHeader::new()).unwrap();
+/// file.sections().open(handle).unwrap().write_all(b"test").unwrap();
+/// file.save().unwrap();
+///
+/// let mut file = Container::open(file.into_inner()).unwrap();
+/// let handle = file.find_section_by_index(0).unwrap();
+/// assert_eq!(file.sections().open(handle).unwrap().load_in_memory().unwrap(), b"test");
+/// ```
+pub struct Container<TBackend: IoBackend>
+{
+    main_header: MainHeader,
+    sections: Vec<SectionEntry>,
+    layout_changed: bool,
+    // The file size and unreferenced bytes of the BPX in the IO backend.
+    stored_size: u64,
+    slack: u64,
+    file: TBackend
+}
+
+impl<TBackend: IoBackend> Container<TBackend>
+{
+    /// Creates a new empty BPX container.
+    ///
+    /// *The content of the IO backend is overwritten on the first call to
+    /// [save](Container::save).*
+    ///
+    /// # Arguments
+    ///
+    /// * `file`: An [IoBackend](self::IoBackend) to use for reading and writing the data.
+    ///
+    /// returns: Container<TBackend>
+    pub fn create(file: TBackend) -> Container<TBackend>
+    {
+        return Container {
+            main_header: MainHeader::new(),
+            sections: Vec::new(),
+            layout_changed: true,
+            stored_size: 0,
+            slack: 0,
+            file
+        };
+    }
+
+    /// Opens an existing BPX container.
+    ///
+    /// *The file is read from the start of the IO backend. Sections are
+    /// loaded from the IO backend the first time they are opened.*
+    ///
+    /// # Arguments
+    ///
+    /// * `file`: An [IoBackend](self::IoBackend) to use for reading and writing the data.
+    ///
+    /// returns: Result<Container<TBackend>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if some headers
+    /// could not be read or if the header data is corrupted.
+    pub fn open(mut file: TBackend) -> Result<Container<TBackend>>
+    {
+        file.seek(SeekFrom::Start(0))?;
+        let (checksum, main_header) = MainHeader::read(&mut file)?;
+        let headers = read_section_header_table(&mut file, &main_header, checksum)?;
+        let slack = unreferenced_bytes(main_header.file_size, &headers);
+        let sections = headers
+            .into_iter()
+            .map(|header| SectionEntry {
+                header,
+                data: None,
+                stored: true,
+                baseline: None,
+                raw: None
+            })
+            .collect();
+        return Ok(Container {
+            main_header,
+            sections,
+            layout_changed: false,
+            stored_size: main_header.file_size,
+            slack,
+            file
+        });
+    }
+
+    /// Sets the BPX Main Header.
+    ///
+    /// *The section count of the new header is always replaced by the
+    /// number of sections in this container.*
+    ///
+    /// # Arguments
+    ///
+    /// * `main_header`: the new [MainHeader](crate::header::MainHeader).
+    pub fn set_main_header(&mut self, main_header: MainHeader)
+    {
+        self.main_header = main_header;
+        self.main_header.section_num = self.sections.len() as u32;
+    }
+
+    /// Returns an accessor to create, remove and open sections.
+    ///
+    /// returns: Sections<TBackend>
+    pub fn sections(&mut self) -> Sections<'_, TBackend>
+    {
+        return Sections { container: self };
+    }
+
+    /// Attaches a name to a section, creating the section names table if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section to name.
+    /// * `name`: the name of the section.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](crate::error::Error) if the name is invalid, already used
+    /// by another section or could not be written.
+    pub fn name_section(&mut self, handle: SectionHandle, name: &str) -> Result<()>
+    {
+        return names::name_section(self, handle, name);
+    }
+
+    /// Sets the build identifier, creating the build identifier section if needed.
+    ///
+    /// *The build identifier section is preserved as is on save until this
+    /// is called again. See [build_id](crate::Interface::build_id).*
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: the build identifier.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](crate::error::Error) if the build identifier section
+    /// is corrupted or could not be written.
+    pub fn set_build_id(&mut self, id: u64) -> Result<()>
+    {
+        return build_id::set_build_id(self, id);
+    }
+
+    /// Consumes this container and returns the underlying IO backend.
+    ///
+    /// *Changes which were not saved are lost.*
+    ///
+    /// returns: TBackend
+    pub fn into_inner(self) -> TBackend
+    {
+        return self.file;
+    }
+
+    fn create_section(&mut self, header: SectionHeader) -> Result<SectionHandle>
+    {
+        let count = section_count(self.sections.len() + 1)?;
+        let (data, _) = create_section(&header, None, &TempStorage::new())?;
+        self.sections.push(SectionEntry {
+            header,
+            data: Some(data),
+            stored: false,
+            baseline: None,
+            raw: None
+        });
+        self.main_header.section_num = count;
+        self.layout_changed = true;
+        return Ok(SectionHandle(self.sections.len() - 1));
+    }
+
+    fn create_section_raw<TRead: Read>(
+        &mut self,
+        header: SectionHeader,
+        compressed: &mut TRead
+    ) -> Result<SectionHandle>
+    {
+        let count = section_count(self.sections.len() + 1)?;
+        let raw = create_raw_section(&header, compressed)?;
+        self.sections.push(SectionEntry {
+            header,
+            data: None,
+            stored: false,
+            baseline: None,
+            raw: Some(raw)
+        });
+        self.main_header.section_num = count;
+        self.layout_changed = true;
+        return Ok(SectionHandle(self.sections.len() - 1));
+    }
+
+    fn rewrite_names(&mut self, table: SectionHandle, removed: u32) -> Result<Box<dyn SectionData>>
+    {
+        let context = crate::error::section_context(self, table);
+        let mut data = self.open_section(table)?;
+        let old = names::low_level_read_names(&mut *data).map_err(&context)?;
+        let mut records: Vec<(u32, String)> = old
+            .into_iter()
+            .filter(|(index, _)| *index != removed)
+            .map(|(index, name)| (if index > removed { index - 1 } else { index }, name))
+            .collect();
+        records.sort_unstable();
+        let mut data = new_section_data(None)?;
+        for (index, name) in records {
+            names::low_level_write_name(index, &name, data.as_mut()).map_err(&context)?;
+        }
+        return Ok(data);
+    }
+
+    fn remove_section(&mut self, handle: SectionHandle) -> Result<()>
+    {
+        if handle.0 >= self.sections.len() {
+            return Err(Error::InvalidHandle(handle.0));
+        }
+        let removed = handle.0 as u32;
+        let names = match self.find_section_by_type(SECTION_TYPE_NAMES) {
+            Some(table) if table.0 != handle.0 => Some((table, self.rewrite_names(table, removed)?)),
+            _ => None
+        };
+        self.sections.remove(handle.0);
+        if let Some((table, data)) = names {
+            let index = if table.0 > handle.0 { table.0 - 1 } else { table.0 };
+            self.sections[index].data = Some(data);
+        }
+        self.main_header.section_num = section_count(self.sections.len())?;
+        self.layout_changed = true;
+        return Ok(());
+    }
+
+    fn is_unchanged(&mut self, index: usize) -> Result<bool>
+    {
+        let entry = &mut self.sections[index];
+        if !entry.stored && entry.raw.is_none() {
+            return Ok(false);
+        }
+        return match (entry.data.as_mut(), entry.baseline) {
+            (None, _) => Ok(true),
+            (Some(data), Some(baseline)) => {
+                Ok(data.size() == entry.header.size as usize && content_checksum(data.as_mut())? == baseline)
+            },
+            (Some(_), None) => Ok(false)
+        };
+    }
+
+    fn write_section_data<TWrite: Write>(&mut self, index: usize, out: &mut TWrite) -> Result<u64>
+    {
+        let header = self.compress_section(index, out)?;
+        self.sections[index].header = header;
+        return Ok(header.csize as u64);
+    }
+
+    /// Compresses the data of a section and returns the header describing the written bytes.
+    fn compress_section<TWrite: Write>(&mut self, index: usize, out: &mut TWrite) -> Result<SectionHeader>
+    {
+        let entry = &mut self.sections[index];
+        let data = entry.data.get_or_insert_with_err(|| new_section_data(None))?;
+        if data.size() as u64 > MAX_SECTION_SIZE {
+            return Err(Error::Capacity(data.size()));
+        }
+        data.seek(SeekFrom::Start(0))?;
+        let flags = get_flags(&entry.header, data.size() as u32);
+        let (csize, chksum) = write_section(flags, data.as_mut(), out)?;
+        return Ok(SectionHeader {
+            csize: csize as u32,
+            size: data.size() as u32,
+            chksum,
+            flags: flags.bits(),
+            ..entry.header
+        });
+    }
+
+    fn write_header_table(&mut self) -> Result<()>
+    {
+        let mut file_size = MAIN_HEADER_SIZE as u64 + self.sections.len() as u64 * SECTION_HEADER_SIZE as u64;
+        let mut chksum_sht: u32 = 0;
+
+        for entry in &self.sections {
+            file_size = std::cmp::max(file_size, entry.header.pointer + entry.header.csize as u64);
+            chksum_sht = chksum_sht.wrapping_add(entry.header.get_checksum());
+        }
+        self.main_header.section_num = section_count(self.sections.len())?;
+        self.main_header.file_size = file_size;
+        self.main_header.chksum = 0;
+        self.main_header.chksum = chksum_sht.wrapping_add(self.main_header.get_checksum());
+        self.file.seek(SeekFrom::Start(0))?;
+        self.main_header.write(&mut self.file)?;
+        for entry in &self.sections {
+            entry.header.write(&mut self.file)?;
+        }
+        let headers: Vec<SectionHeader> = self.sections.iter().map(|v| v.header).collect();
+        self.stored_size = file_size;
+        self.slack = unreferenced_bytes(file_size, &headers);
+        return Ok(());
+    }
+
+    /// Writes all changes to the underlying IO backend.
+    ///
+    /// *If no section was created, removed or modified since the container
+    /// was opened or last saved, only the headers are rewritten. If only
+    /// existing sections were modified and each of them still fits in the
+    /// bytes it occupies in the file once compressed, these sections are
+    /// patched in place and nothing else is written apart from the headers.
+    /// Otherwise the whole file is rewritten, sections which did not change
+    /// are copied as is without being decompressed and compressed again.*
+    ///
+    /// **The IO backend is never truncated, if the new file is smaller than
+    /// the previous one the remaining bytes are left untouched after the end
+    /// of the file.**
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if some data could
+    /// not be read or written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::container::Container;
+    /// use bpx::header::SectionHeader;
+    /// use bpx::Interface;
+    ///
+    /// let mut file = Container::create(Cursor::new(Vec::<u8>::new()));
+    /// file.sections().create(SectionHeader::new()).unwrap();
+    /// file.sections().create(SectionHeader::new()).unwrap();
+    /// file.save().unwrap();
+    ///
+    /// let mut file = Container::open(file.into_inner()).unwrap();
+    /// let handle = file.find_section_by_index(0).unwrap();
+    /// file.sections().remove(handle).unwrap();
+    /// file.save().unwrap();
+    /// let file = Container::open(file.into_inner()).unwrap();
+    /// assert_eq!(file.get_main_header().section_num, 1);
+    /// ```
+    pub fn save(&mut self) -> Result<()>
+    {
+        let mut unchanged = Vec::with_capacity(self.sections.len());
+        for i in 0..self.sections.len() {
+            let btype = self.sections[i].header.btype;
+            unchanged.push(self.is_unchanged(i).map_err(|e| e.with_section(i as u32, btype))?);
+        }
+        if !self.layout_changed && unchanged.iter().all(|v| *v) {
+            self.write_header_table()?;
+            self.file.flush()?;
+            return Ok(());
+        }
+        // Modified sections are compressed first to know whether they fit in place.
+        let mut compressed = new_staging_buffer()?;
+        let mut offsets = vec![None; self.sections.len()];
+        let mut offset = 0;
+        let mut in_place = !self.layout_changed;
+        for i in (0..self.sections.len()).filter(|i| !unchanged[*i]) {
+            let btype = self.sections[i].header.btype;
+            let previous = self.sections[i].header;
+            let csize = self
+                .write_section_data(i, &mut compressed)
+                .map_err(|e| e.with_section(i as u32, btype))?;
+            in_place &= csize <= previous.csize as u64 && !self.is_shared(i, &previous);
+            offsets[i] = Some(offset);
+            offset += csize;
+        }
+        if in_place {
+            for (i, offset) in offsets.into_iter().enumerate() {
+                if let Some(offset) = offset {
+                    let header = self.sections[i].header;
+                    self.file.seek(SeekFrom::Start(header.pointer))?;
+                    copy_stored(&mut compressed, &SectionHeader { pointer: offset, ..header }, &mut self.file)?;
+                }
+            }
+            self.write_header_table()?;
+            self.file.flush()?;
+            return self.mark_saved();
+        }
+        let tables_size = MAIN_HEADER_SIZE as u64 + self.sections.len() as u64 * SECTION_HEADER_SIZE as u64;
+        let mut ptr = tables_size;
+        let mut staged = new_staging_buffer()?;
+        for (i, unchanged) in unchanged.into_iter().enumerate() {
+            let btype = self.sections[i].header.btype;
+            let context = move |e: Error| e.with_section(i as u32, btype);
+            let entry = &mut self.sections[i];
+            let csize = if let (true, Some(raw)) = (unchanged, entry.raw.as_mut()) {
+                copy_stored(raw, &SectionHeader { pointer: 0, ..entry.header }, &mut staged).map_err(context)?
+            } else if unchanged {
+                copy_stored(&mut self.file, &entry.header, &mut staged).map_err(context)?
+            } else {
+                let header = SectionHeader {
+                    pointer: offsets[i].unwrap_or(0),
+                    ..entry.header
+                };
+                copy_stored(&mut compressed, &header, &mut staged).map_err(context)?
+            };
+            self.sections[i].header.pointer = ptr;
+            ptr += csize;
+        }
+        self.write_header_table()?;
+        staged.seek(SeekFrom::Start(0))?;
+        let count = io::copy(&mut staged.take(ptr - tables_size), &mut self.file)?;
+        if count != ptr - tables_size {
+            return Err(Error::Truncation("container save"));
+        }
+        self.file.flush()?;
+        self.layout_changed = false;
+        return self.mark_saved();
+    }
+
+    /// Writes the current state of this container to a file, replacing it only once it is complete.
+    ///
+    /// *The BPX is written to a temporary file next to `path` which is renamed
+    /// over `path` on success, see [write_atomically](crate::utils::write_atomically);
+    /// if saving fails, the previous content of `path` is left untouched. This
+    /// container keeps reading from and saving to its IO backend, so `path` is
+    /// usually the file the backend was opened from, to be reopened afterwards.
+    /// On Windows the backend must be closed first if it is `path`, as an open
+    /// file cannot be replaced.*
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: the path of the file to write.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if some data could
+    /// not be read or written or the file could not be replaced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::container::Container;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::header::SectionHeader;
+    /// use bpx::Interface;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("file.bpx");
+    /// let mut file = Container::create(Cursor::new(Vec::<u8>::new()));
+    /// let handle = file.sections().create(SectionHeader::new()).unwrap();
+    /// file.sections().open(handle).unwrap().write_all(b"test").unwrap();
+    /// file.save_to_path(&path).unwrap();
+    ///
+    /// let mut decoder = Decoder::open_file(&path).unwrap();
+    /// let handle = decoder.find_section_by_index(0).unwrap();
+    /// assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), b"test");
+    /// ```
+    pub fn save_to_path<TPath: AsRef<Path>>(&mut self, path: TPath) -> Result<()>
+    {
+        return write_atomically(path, |file| self.write_copy(file));
+    }
+
+    /// Writes all headers and sections to a new file without modifying this container.
+    fn write_copy<TWrite: Write + Seek>(&mut self, out: &mut TWrite) -> Result<()>
+    {
+        let tables_size = MAIN_HEADER_SIZE as u64 + self.sections.len() as u64 * SECTION_HEADER_SIZE as u64;
+        let mut headers = Vec::with_capacity(self.sections.len());
+        let mut ptr = tables_size;
+        out.seek(SeekFrom::Start(tables_size))?;
+        for i in 0..self.sections.len() {
+            let btype = self.sections[i].header.btype;
+            let context = move |e: Error| e.with_section(i as u32, btype);
+            let mut header = if self.is_unchanged(i).map_err(context)? {
+                let entry = &mut self.sections[i];
+                match entry.raw.as_mut() {
+                    Some(raw) => copy_stored(raw, &SectionHeader { pointer: 0, ..entry.header }, out),
+                    None => copy_stored(&mut self.file, &entry.header, out)
+                }
+                .map_err(context)?;
+                entry.header
+            } else {
+                self.compress_section(i, out).map_err(context)?
+            };
+            header.pointer = ptr;
+            ptr += header.csize as u64;
+            headers.push(header);
+        }
+        let mut main_header = self.main_header;
+        main_header.section_num = section_count(headers.len())?;
+        main_header.file_size = ptr;
+        main_header.chksum = 0;
+        let chksum_sht = headers.iter().fold(0u32, |acc, v| acc.wrapping_add(v.get_checksum()));
+        main_header.chksum = chksum_sht.wrapping_add(main_header.get_checksum());
+        out.seek(SeekFrom::Start(0))?;
+        main_header.write(out)?;
+        for header in &headers {
+            header.write(out)?;
+        }
+        return Ok(());
+    }
+
+    /// Writes a compacted copy of this container to a new file.
+    ///
+    /// *The copy is written like [save_to_path](Container::save_to_path) does, without
+    /// modifying this container, but drops the bytes of the IO backend which no section
+    /// refers to and stores uncompressed the sections which compression made larger.
+    /// Payloads shared by several unmodified sections are written once. Other sections
+    /// are copied as is without being decompressed and compressed again.*
+    ///
+    /// # Arguments
+    ///
+    /// * `out`: the backend to write the compacted file to.
+    ///
+    /// returns: Result<CompactReport, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if some data could
+    /// not be read or written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::container::Container;
+    /// use bpx::header::SectionHeader;
+    /// use bpx::Interface;
+    ///
+    /// let mut file = Container::create(Cursor::new(Vec::<u8>::new()));
+    /// let first = file.sections().create(SectionHeader::new()).unwrap();
+    /// let second = file.sections().create(SectionHeader::new()).unwrap();
+    /// file.sections().open(first).unwrap().write_all(&[1; 64]).unwrap();
+    /// file.sections().open(second).unwrap().write_all(&[2; 64]).unwrap();
+    /// file.save().unwrap();
+    /// // The first section is patched in place, leaving 60 unused bytes.
+    /// file.sections().replace(first, b"test".to_vec()).unwrap();
+    /// file.save().unwrap();
+    ///
+    /// let mut out = Cursor::new(Vec::new());
+    /// let report = file.compact(&mut out).unwrap();
+    /// assert_eq!(report.slack, 60);
+    /// assert_eq!(report.reclaimed(), 60);
+    /// let mut file = Container::open(out).unwrap();
+    /// assert_eq!(file.sections().open(first).unwrap().load_in_memory().unwrap(), b"test");
+    /// ```
+    pub fn compact<TWrite: Write + Seek>(&mut self, out: &mut TWrite) -> Result<CompactReport>
+    {
+        let input_size = self.file.seek(SeekFrom::End(0))?;
+        let mut report = CompactReport {
+            input_size,
+            slack: self.slack,
+            trailing: input_size.saturating_sub(self.stored_size),
+            ..Default::default()
+        };
+        let tables_size = MAIN_HEADER_SIZE as u64 + self.sections.len() as u64 * SECTION_HEADER_SIZE as u64;
+        let mut headers = Vec::with_capacity(self.sections.len());
+        // New pointer of each stored payload already written, by stored pointer and size.
+        let mut written: HashMap<(u64, u32), u64> = HashMap::new();
+        let mut compressed = new_staging_buffer()?;
+        let mut ptr = tables_size;
+        out.seek(SeekFrom::Start(tables_size))?;
+        for i in 0..self.sections.len() {
+            let btype = self.sections[i].header.btype;
+            let context = move |e: Error| e.with_section(i as u32, btype);
+            let unchanged = self.is_unchanged(i).map_err(context)?;
+            let stored = self.sections[i].stored && self.sections[i].raw.is_none();
+            let mut header = if unchanged {
+                self.sections[i].header
+            } else {
+                compressed.seek(SeekFrom::Start(0))?;
+                self.compress_section(i, &mut compressed).map_err(context)?
+            };
+            if header.compression().is_some() && header.csize >= header.size {
+                let raw = self.store_uncompressed(i, header, out).map_err(context)?;
+                report.recompressed += (header.csize - raw.csize) as u64;
+                report.decompressed_sections += 1;
+                header = raw;
+            } else if !unchanged {
+                copy_stored(&mut compressed, &SectionHeader { pointer: 0, ..header }, out).map_err(context)?;
+            } else if let (true, Some(pointer)) = (stored, written.get(&(header.pointer, header.csize))) {
+                headers.push(SectionHeader { pointer: *pointer, ..header });
+                continue;
+            } else {
+                let entry = &mut self.sections[i];
+                match entry.raw.as_mut() {
+                    Some(raw) => copy_stored(raw, &SectionHeader { pointer: 0, ..header }, out),
+                    None => copy_stored(&mut self.file, &header, out)
+                }
+                .map_err(context)?;
+                if stored && header.csize > 0 {
+                    written.insert((header.pointer, header.csize), ptr);
+                }
+            }
+            header.pointer = ptr;
+            ptr += header.csize as u64;
+            headers.push(header);
+        }
+        let mut main_header = self.main_header;
+        main_header.section_num = section_count(headers.len())?;
+        main_header.file_size = ptr;
+        main_header.chksum = 0;
+        let chksum_sht = headers.iter().fold(0u32, |acc, v| acc.wrapping_add(v.get_checksum()));
+        main_header.chksum = chksum_sht.wrapping_add(main_header.get_checksum());
+        out.seek(SeekFrom::Start(0))?;
+        main_header.write(out)?;
+        for header in &headers {
+            header.write(out)?;
+        }
+        report.output_size = ptr;
+        return Ok(report);
+    }
+
+    /// Writes the data of a section without compression and returns the header describing the written bytes.
+    fn store_uncompressed<TWrite: Write>(
+        &mut self,
+        index: usize,
+        header: SectionHeader,
+        out: &mut TWrite
+    ) -> Result<SectionHeader>
+    {
+        let mut flags = header.flags();
+        flags.remove(SectionFlags::COMPRESS_XZ | SectionFlags::COMPRESS_ZLIB);
+        let mut data = self.open_section(SectionHandle(index))?;
+        data.seek(SeekFrom::Start(0))?;
+        let (csize, chksum) = write_section(flags, &mut *data, out)?;
+        return Ok(SectionHeader {
+            csize: csize as u32,
+            size: data.size() as u32,
+            chksum,
+            flags: flags.bits(),
+            ..header
+        });
+    }
+
+    /// Checks if the stored bytes of a section are also used by another section
+    /// (see [set_deduplicate](crate::encoder::Encoder::set_deduplicate)).
+    fn is_shared(&self, index: usize, stored: &SectionHeader) -> bool
+    {
+        return stored.csize > 0
+            && self.sections.iter().enumerate().any(|(i, entry)| {
+                i != index && entry.header.pointer == stored.pointer && entry.header.csize > 0
+            });
+    }
+
+    fn mark_saved(&mut self) -> Result<()>
+    {
+        for entry in &mut self.sections {
+            entry.stored = true;
+            entry.raw = None;
+            if let Some(data) = entry.data.as_mut() {
+                entry.baseline = Some(content_checksum(data.as_mut())?);
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Accessor to the sections of a BPX [Container](self::Container).
+pub struct Sections<'a, TBackend: IoBackend>
+{
+    container: &'a mut Container<TBackend>
+}
+
+impl<'a, TBackend: IoBackend> Sections<'a, TBackend>
+{
+    /// Creates a new section.
+    ///
+    /// # Arguments
+    ///
+    /// * `header`: the [SectionHeader](crate::header::SectionHeader) of the new section.
+    ///
+    /// returns: Result<SectionHandle, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the section data could not be allocated.
+    pub fn create(&mut self, header: SectionHeader) -> Result<SectionHandle>
+    {
+        return self.container.create_section(header);
+    }
+
+    /// Creates a new section from bytes which are already compressed.
+    ///
+    /// *The bytes are saved as is unless the section is opened and modified.
+    /// See [create_section_raw](crate::encoder::Encoder::create_section_raw).*
+    ///
+    /// # Arguments
+    ///
+    /// * `header`: the [SectionHeader](crate::header::SectionHeader) describing the stored bytes.
+    /// * `compressed`: a [Read](std::io::Read) producing `csize` bytes.
+    ///
+    /// returns: Result<SectionHandle, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the header is invalid
+    /// or `compressed` produces less than `csize` bytes.
+    pub fn create_raw<TRead: Read>(&mut self, header: SectionHeader, compressed: &mut TRead) -> Result<SectionHandle>
+    {
+        return self.container.create_section_raw(header, compressed);
+    }
+
+    /// Removes a section.
+    ///
+    /// *The indices of the sections following the removed section are shifted
+    /// down by one, handles to these sections are no longer valid and must be
+    /// obtained again. Names attached to sections are updated accordingly.*
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section to remove.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the handle is invalid or
+    /// if the section names table could not be updated.
+    pub fn remove(&mut self, handle: SectionHandle) -> Result<()>
+    {
+        return self.container.remove_section(handle);
+    }
+
+    /// Replaces the whole content of a section.
+    ///
+    /// *Unlike writing through [open](Sections::open), the new content may
+    /// be shorter than the previous one. The section is not loaded from the
+    /// IO backend.*
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    /// * `content`: the new content of the section.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the handle is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::container::Container;
+    /// use bpx::header::SectionHeader;
+    /// use bpx::Interface;
+    ///
+    /// let mut file = Container::create(Cursor::new(Vec::<u8>::new()));
+    /// let handle = file.sections().create(SectionHeader::new()).unwrap();
+    /// file.sections().open(handle).unwrap().write_all(b"a long text").unwrap();
+    /// file.sections().replace(handle, b"short".to_vec()).unwrap();
+    /// assert_eq!(file.sections().open(handle).unwrap().load_in_memory().unwrap(), b"short");
+    /// ```
+    pub fn replace(&mut self, handle: SectionHandle, content: Vec<u8>) -> Result<()>
+    {
+        let entry = self
+            .container
+            .sections
+            .get_mut(handle.0)
+            .ok_or(Error::InvalidHandle(handle.0))?;
+        entry.data = Some(section_data_from_content(content));
+        return Ok(());
+    }
+
+    /// Opens a section for read and/or write.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: Result<SectionGuard, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the handle is invalid or
+    /// if the section could not be loaded from the IO backend.
+    pub fn open(self, handle: SectionHandle) -> Result<SectionGuard<'a>>
+    {
+        return self.container.open_section(handle);
+    }
+
+    /// Returns the number of sections.
+    pub fn len(&self) -> usize
+    {
+        return self.container.sections.len();
+    }
+
+    /// Returns true if there are no sections.
+    pub fn is_empty(&self) -> bool
+    {
+        return self.container.sections.is_empty();
+    }
+}
+
+impl<TBackend: IoBackend> Interface for Container<TBackend>
+{
+    fn find_section_by_index(&self, index: u32) -> Option<SectionHandle>
+    {
+        let index = usize::try_from(index).ok()?;
+        if self.sections.get(index).is_some() {
+            return Some(SectionHandle(index));
+        }
+        return None;
+    }
+
+    fn get_section_header(&self, handle: SectionHandle) -> &SectionHeader
+    {
+        return &self.sections[handle.0].header;
+    }
+
+    fn try_get_section_header(&self, handle: SectionHandle) -> Option<&SectionHeader>
+    {
+        return self.sections.get(handle.0).map(|v| &v.header);
+    }
+
+    fn get_section_index(&self, handle: SectionHandle) -> u32
+    {
+        return handle.0 as u32;
+    }
+
+    fn open_section(&mut self, handle: SectionHandle) -> Result<SectionGuard<'_>>
+    {
+        let file = &mut self.file;
+        let SectionEntry {
+            header,
+            data,
+            baseline,
+            raw,
+            ..
+        } = self.sections.get_mut(handle.0).ok_or(Error::InvalidHandle(handle.0))?;
+        let object = data
+            .get_or_insert_with_err(|| {
+                let mut section = match raw.as_mut() {
+                    Some(raw) => load_section(raw, &SectionHeader { pointer: 0, ..*header })?,
+                    None => load_section(file, header)?
+                };
+                *baseline = Some(content_checksum(section.as_mut())?);
+                return Ok(section);
+            })
+            .map_err(|e: Error| e.with_section(handle.0 as u32, header.btype))?;
+        return Ok(SectionGuard::new(object.as_mut())?);
+    }
+
+    fn get_main_header(&self) -> &MainHeader
+    {
+        return &self.main_header;
+    }
+
+    fn storage_report(&self) -> StorageReport
+    {
+        return storage_report(self.sections.iter().map(|v| v.data.as_deref()));
+    }
+
+    fn section_remaining_capacity(&mut self, handle: SectionHandle) -> Result<u64>
+    {
+        let entry = self.sections.get(handle.0).ok_or(Error::InvalidHandle(handle.0))?;
+        let size = match &entry.data {
+            Some(data) => data.size() as u64,
+            // Stored sections are not loaded just to read their size.
+            None if entry.stored || entry.raw.is_some() => entry.header.size as u64,
+            None => self.open_section(handle)?.size() as u64
+        };
+        return Ok(MAX_SECTION_SIZE.saturating_sub(size));
+    }
+}
+
+impl<TBackend: IoBackend> WriteInterface for Container<TBackend>
+{
+    fn set_main_header(&mut self, main_header: MainHeader)
+    {
+        Container::set_main_header(self, main_header);
+    }
+
+    fn create_section(&mut self, header: SectionHeader) -> Result<SectionHandle>
+    {
+        return Container::create_section(self, header);
+    }
+}
+
+fn content_checksum(data: &mut dyn SectionData) -> Result<u32>
+{
+    let mut buf: [u8; READ_BLOCK_SIZE] = [0; READ_BLOCK_SIZE];
+    let mut chksum = Crc32Checksum::new();
+    let pos = data.stream_position()?;
+
+    data.seek(SeekFrom::Start(0))?;
+    loop {
+        let res = data.read(&mut buf)?;
+        if res == 0 {
+            break;
+        }
+        chksum.push(&buf[0..res]);
+    }
+    data.seek(SeekFrom::Start(pos))?;
+    return Ok(chksum.finish());
+}
+
+/// Returns the number of bytes of a BPX which are neither headers nor section data.
+fn unreferenced_bytes(file_size: u64, sections: &[SectionHeader]) -> u64
+{
+    let mut ranges: Vec<(u64, u64)> = sections
+        .iter()
+        .filter(|v| v.csize > 0)
+        .map(|v| (v.pointer, v.pointer + v.csize as u64))
+        .collect();
+    ranges.sort_unstable();
+    let mut used = MAIN_HEADER_SIZE as u64 + sections.len() as u64 * SECTION_HEADER_SIZE as u64;
+    let mut end = 0;
+    for (start, stop) in ranges {
+        let start = std::cmp::max(start, end);
+        if stop > start {
+            used += stop - start;
+            end = stop;
+        }
+    }
+    return file_size.saturating_sub(used);
+}
+
+fn copy_stored<TBackend: IoBackend, TWrite: Write>(
+    file: &mut TBackend,
+    header: &SectionHeader,
+    out: &mut TWrite
+) -> Result<u64>
+{
+    file.seek(SeekFrom::Start(header.pointer))?;
+    let count = io::copy(&mut file.take(header.csize as u64), out)?;
+    if count != header.csize as u64 {
+        return Err(Error::Truncation("copy section"));
+    }
+    return Ok(count);
+}
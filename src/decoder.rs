@@ -28,120 +28,1611 @@
 
 //! The BPX decoder.
 
-use std::{io, io::Write};
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io,
+    io::{BufReader, Cursor, Read, Take, Write},
+    ops::Range,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+        Mutex,
+        OnceLock
+    },
+    time::{Duration, Instant}
+};
+
+use crate::{
+    builder,
+    checkpoint::{read_checkpoints, ChunkVerifier},
+    compression::{
+        Checksum,
+        Crc32Checksum,
+        InflateStream,
+        Inflater,
+        NoChecksum,
+        TimedChecksum,
+        WeakChecksum,
+        XzCompressionMethod,
+        ZlibCompressionMethod
+    },
+    error::Error,
+    find_section_index,
+    find_sections_with_type,
+    format::{DIGEST_FOOTER_SIZE, MAIN_HEADER_SIZE, MAX_SECTION_SIZE, SECTION_HEADER_SIZE},
+    header::{get_type_ext_reserved_range, MainHeader, SectionHeader, DIGEST_FOOTER_SIGNATURE},
+    instrument::{Event, Instrumentation},
+    section::{
+        budgeted_section_from_content,
+        new_budgeted_section_data,
+        new_section_data,
+        storage_report,
+        MemoryBudget,
+        SectionData,
+        SectionGuard,
+        SharedSection,
+        SliceSection,
+        Spool,
+        StorageReport,
+        TempStorage,
+        MEMORY_THRESHOLD
+    },
+    strings::read_string_in_slice,
+    utils::{hexdump, read_fill, OptionExtension},
+    Interface,
+    Result,
+    SectionHandle
+};
+
+const READ_BLOCK_SIZE: usize = 8192;
+const FILE_BUFFER_SIZE: usize = 8192;
+
+/// Represents the IO backend for a BPX decoder.
+///
+/// *Any [Read](std::io::Read) + [Seek](std::io::Seek) can be used. To read a
+/// BPX from disk, prefer [open_file](Decoder::open_file) which picks a
+/// buffered file backend.*
+pub trait IoBackend: io::Seek + io::Read
+{
+}
+impl<T: io::Seek + io::Read> IoBackend for T {}
+
+/// An [IoBackend] exposing the part of a stream which starts at a given offset.
+///
+/// *Positions are relative to the start offset, so that a BPX embedded in a
+/// larger stream can be decoded as if it owned the whole stream
+/// (see [new_embedded](Decoder::new_embedded)).*
+pub struct Embedded<TBackend: IoBackend>
+{
+    inner: TBackend,
+    start: u64
+}
+
+impl<TBackend: IoBackend> Embedded<TBackend>
+{
+    /// Creates a view of a stream starting at `start`.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner`: the stream.
+    /// * `start`: the offset in `inner` of the first byte of the view.
+    ///
+    /// returns: Result<Embedded<TBackend>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if `inner` could not seek to `start`.
+    pub fn new(mut inner: TBackend, start: u64) -> Result<Embedded<TBackend>>
+    {
+        inner.seek(io::SeekFrom::Start(start))?;
+        return Ok(Embedded { inner, start });
+    }
+
+    /// Returns the offset in the underlying stream of the first byte of this view.
+    pub fn start_offset(&self) -> u64
+    {
+        return self.start;
+    }
+
+    /// Consumes this view and returns the underlying stream.
+    pub fn into_inner(self) -> TBackend
+    {
+        return self.inner;
+    }
+
+    fn relative(&self, pos: u64) -> io::Result<u64>
+    {
+        return pos.checked_sub(self.start).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of an embedded stream")
+        });
+    }
+}
+
+impl<TBackend: IoBackend> io::Read for Embedded<TBackend>
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        return self.inner.read(buf);
+    }
+}
+
+impl<TBackend: IoBackend> io::Seek for Embedded<TBackend>
+{
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64>
+    {
+        let pos = match pos {
+            io::SeekFrom::Start(v) => {
+                let pos = self.start.checked_add(v).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "seek past the end of an embedded stream")
+                })?;
+                io::SeekFrom::Start(pos)
+            },
+            io::SeekFrom::Current(v) => {
+                let current = self.inner.stream_position()?;
+                let target = current.checked_add_signed(v).unwrap_or(0);
+                self.relative(target)?;
+                io::SeekFrom::Current(v)
+            },
+            io::SeekFrom::End(v) => {
+                let end = self.inner.seek(io::SeekFrom::End(0))?;
+                self.relative(end.checked_add_signed(v).unwrap_or(0))?;
+                io::SeekFrom::End(v)
+            }
+        };
+        let pos = self.inner.seek(pos)?;
+        return self.relative(pos);
+    }
+}
+
+/// The BPX decoder.
+pub struct Decoder<TBackend: IoBackend>
+{
+    main_header: MainHeader,
+    sections: Vec<SectionHeader>,
+    sections_data: Vec<Option<Box<dyn SectionData>>>,
+    budget: MemoryBudget,
+    instrument: Option<Arc<dyn Instrumentation>>,
+    storage: TempStorage,
+    consumed: u64,
+    trailing: u64,
+    // IoBackend is implemented for every Read + Seek, so a shared buffer cannot be
+    // recognized from the backend type alone: from_shared keeps it here.
+    shared: Option<Arc<[u8]>>,
+    cache: CachePolicy,
+    last_use: Vec<u64>,
+    clock: u64,
+    file: TBackend
+}
+
+/// How a [Decoder](self::Decoder) keeps the sections it loaded, see
+/// [with_cache_policy](self::Decoder::with_cache_policy).
+///
+/// *Compressed sections are inflated each time they are loaded again.*
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CachePolicy
+{
+    /// Keeps every loaded section until the decoder is dropped or the section is evicted.
+    #[default]
+    KeepAll,
+
+    /// Keeps the most recently opened sections as long as they hold at most the given number
+    /// of bytes of memory, the section being opened is always kept.
+    KeepLru(usize),
+
+    /// Keeps only the section opened last: opening another section drops it.
+    DropAfterUse
+}
+
+impl<TBackend: IoBackend> Decoder<TBackend>
+{
+    /// Creates a new BPX decoder.
+    ///
+    /// *Unbuffered backends such as a raw [File](std::fs::File) issue one
+    /// system call per section header, use [open_file](Decoder::open_file)
+    /// to read a BPX from disk.*
+    ///
+    /// *Each section is read from the pointer in its header: the data of the
+    /// sections may be stored in any order and with gaps between them, unlike
+    /// what the [Encoder](crate::encoder::Encoder) writes.*
+    ///
+    /// # Arguments
+    ///
+    /// * `file`: An [IoBackend](self::IoBackend) to use for reading the data.
+    ///
+    /// returns: Result<Decoder<TBackend>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if some headers
+    /// could not be read or if the header data is corrupted, including
+    /// a section header table which does not fit in `file_size` and
+    /// sections ending after `file_size` or partially overlapping each other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// encoder.save().unwrap();
+    /// let decoder = Decoder::new(Cursor::new(encoder.into_inner())).unwrap();
+    /// assert_eq!(decoder.get_main_header().section_num, 0);
+    /// ```
+    pub fn new(mut file: TBackend) -> Result<Decoder<TBackend>>
+    {
+        let (checksum, header) = MainHeader::read(&mut file)?;
+        let sections = read_section_header_table(&mut file, &header, checksum)?;
+        let (consumed, trailing) = measure_stream(&mut file, header.file_size)?;
+        return Ok(Decoder::from_parts(file, header, sections, consumed, trailing));
+    }
+
+    /// Creates a new BPX decoder which rejects anything unexpected in the headers.
+    ///
+    /// *Unlike [new](Self::new), which ignores what it does not understand,
+    /// this checks that:*
+    /// - *no section has unknown flags or a nonzero reserved byte,*
+    /// - *the size of each uncompressed section matches its stored size,*
+    /// - *the Extended Type Information of known variants is zero outside of the
+    ///   bytes reserved by the variant (this rejects custom data written with
+    ///   [with_type_ext_range](crate::builder::MainHeaderBuilder::with_type_ext_range)),*
+    /// - *the data of each section starts after the section header table,*
+    /// - *`section_num` matches the section header table: the table is not
+    ///   directly followed by another section header,*
+    /// - *`file_size` is the end of the last section,*
+    /// - *the stream ends with the BPX, see [trailing_bytes](Self::trailing_bytes).*
+    ///
+    /// # Arguments
+    ///
+    /// * `file`: An [IoBackend](self::IoBackend) to use for reading the data.
+    ///
+    /// returns: Result<Decoder<TBackend>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned in the same cases as [new](Self::new)
+    /// and a [Corruption](crate::error::Error::Corruption) error describing the first
+    /// failed check otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    ///
+    /// let mut buf = Vec::new();
+    /// Encoder::new(&mut buf).unwrap().save().unwrap();
+    /// assert!(Decoder::new_strict(Cursor::new(buf)).is_ok());
+    /// ```
+    pub fn new_strict(mut file: TBackend) -> Result<Decoder<TBackend>>
+    {
+        let (checksum, header) = MainHeader::read(&mut file)?;
+        check_strict_main_header(&header)?;
+        let sections = read_section_header_table(&mut file, &header, checksum)?;
+        check_strict_sections(&mut file, &header, &sections)?;
+        let (consumed, trailing) = measure_stream(&mut file, header.file_size)?;
+        if trailing > 0 {
+            return Err(Error::Corruption(format!(
+                "{} trailing byte(s) after the end of the BPX ({})",
+                trailing, consumed
+            )));
+        }
+        return Ok(Decoder::from_parts(file, header, sections, consumed, trailing));
+    }
+
+    /// Builds a decoder with the default settings from an already read section header table.
+    fn from_parts(
+        file: TBackend,
+        main_header: MainHeader,
+        sections: Vec<SectionHeader>,
+        consumed: u64,
+        trailing: u64
+    ) -> Decoder<TBackend>
+    {
+        return Decoder {
+            file,
+            main_header,
+            sections_data: std::iter::repeat_with(|| None).take(sections.len()).collect(),
+            last_use: vec![0; sections.len()],
+            sections,
+            budget: MemoryBudget::unlimited(),
+            instrument: None,
+            storage: TempStorage::new(),
+            consumed,
+            trailing,
+            shared: None,
+            cache: CachePolicy::KeepAll,
+            clock: 0
+        };
+    }
+
+    /// Limits the number of bytes of section data this decoder holds in memory.
+    ///
+    /// *Sections which do not fit in the budget are loaded into a temporary
+    /// file instead; without the `tempfile` feature loading them fails.
+    /// [load_in_memory](crate::section::SectionData::load_in_memory) and
+    /// Structured Data read by variant decoders are checked against the
+    /// remaining budget before allocating. Sections loaded before calling this
+    /// function are not accounted.*
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: the maximum number of bytes to hold in memory.
+    ///
+    /// returns: Decoder<TBackend>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::builder::SectionHeaderBuilder;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// for _ in 0..2 {
+    ///     let handle = encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+    ///     encoder.open_section(handle).unwrap().write_all(&[1; 1024]).unwrap();
+    /// }
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(encoder.into_inner())).unwrap().with_memory_budget(1536);
+    /// let handles: Vec<_> = decoder.iter_sections().collect();
+    /// decoder.open_section(handles[0]).unwrap();
+    /// assert_eq!(decoder.memory_usage(), 1024);
+    /// // The second section is stored in a temporary file.
+    /// assert_eq!(decoder.open_section(handles[1]).unwrap().size(), 1024);
+    /// assert_eq!(decoder.memory_usage(), 1024);
+    /// ```
+    pub fn with_memory_budget(mut self, bytes: usize) -> Self
+    {
+        self.budget = MemoryBudget::new(bytes);
+        return self;
+    }
+
+    /// Sets where this decoder stores sections which do not fit in memory or in its memory budget.
+    ///
+    /// *If no temporary file can be created, sections are kept in memory, charged to the
+    /// memory budget of this decoder if one is set and to the fallback of the
+    /// [TempStorage](crate::section::TempStorage) otherwise.*
+    ///
+    /// # Arguments
+    ///
+    /// * `storage`: the storage to use.
+    ///
+    /// returns: Decoder<TBackend>
+    pub fn with_temp_storage(mut self, storage: TempStorage) -> Self
+    {
+        self.storage = storage;
+        return self;
+    }
+
+    /// Returns the number of bytes of section data this decoder currently holds in memory.
+    pub fn memory_usage(&self) -> usize
+    {
+        return self.budget.used();
+    }
+
+    /// Sets how this decoder keeps the sections it loaded, see [CachePolicy](self::CachePolicy).
+    ///
+    /// *The policy is applied each time a section is loaded by
+    /// [open_section](crate::Interface::open_section); sections loaded by
+    /// [load_sections](Self::load_sections) are only dropped by the next call to
+    /// [open_section](crate::Interface::open_section). Changes written to a
+    /// section are lost when it is dropped.*
+    ///
+    /// # Arguments
+    ///
+    /// * `policy`: the cache policy, [KeepAll](self::CachePolicy::KeepAll) by default.
+    ///
+    /// returns: Decoder<TBackend>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::builder::SectionHeaderBuilder;
+    /// use bpx::decoder::{CachePolicy, Decoder};
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// for _ in 0..2 {
+    ///     let handle = encoder.create_section(SectionHeaderBuilder::bulk_data().build()).unwrap();
+    ///     encoder.open_section(handle).unwrap().write_all(&[1; 1024]).unwrap();
+    /// }
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(encoder.into_inner()))
+    ///     .unwrap()
+    ///     .with_cache_policy(CachePolicy::DropAfterUse);
+    /// let handles: Vec<_> = decoder.iter_sections().collect();
+    /// decoder.open_section(handles[0]).unwrap();
+    /// decoder.open_section(handles[1]).unwrap();
+    /// let report = decoder.storage_report();
+    /// assert_eq!(report.sections.len(), 1);
+    /// assert_eq!(report.sections[0].index, 1);
+    /// ```
+    pub fn with_cache_policy(mut self, policy: CachePolicy) -> Self
+    {
+        self.cache = policy;
+        return self;
+    }
+
+    /// Drops the loaded content of a section, it is read again the next time the section is opened.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: bool; true if the section was loaded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::builder::SectionHeaderBuilder;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let handle = encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+    /// encoder.open_section(handle).unwrap().write_all(b"test").unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(encoder.into_inner())).unwrap();
+    /// let handle = decoder.find_section_by_index(0).unwrap();
+    /// decoder.open_section(handle).unwrap();
+    /// assert!(decoder.evict(handle));
+    /// assert!(!decoder.evict(handle));
+    /// assert!(decoder.storage_report().sections.is_empty());
+    /// ```
+    pub fn evict(&mut self, handle: SectionHandle) -> bool
+    {
+        return match self.sections_data.get_mut(handle.0) {
+            Some(data) => data.take().is_some(),
+            None => false
+        };
+    }
+
+    /// Drops the loaded content of all sections, see [evict](Self::evict).
+    pub fn evict_all(&mut self)
+    {
+        for data in &mut self.sections_data {
+            *data = None;
+        }
+    }
+
+    /// Drops loaded sections other than `handle` according to the cache policy before `needed`
+    /// more bytes are loaded.
+    fn make_room(&mut self, handle: SectionHandle, needed: usize)
+    {
+        let limit = match self.cache {
+            CachePolicy::KeepAll => return,
+            CachePolicy::KeepLru(bytes) => bytes,
+            CachePolicy::DropAfterUse => {
+                for (i, data) in self.sections_data.iter_mut().enumerate() {
+                    if i != handle.0 {
+                        *data = None;
+                    }
+                }
+                return;
+            }
+        };
+        let mut used: usize = self.sections_data.iter().flatten().map(|v| v.allocated_bytes()).sum();
+        while used.saturating_add(needed) > limit {
+            let oldest = (0..self.sections_data.len())
+                .filter(|i| *i != handle.0 && self.sections_data[*i].is_some())
+                .min_by_key(|i| self.last_use[*i]);
+            match oldest {
+                Some(i) => {
+                    if let Some(data) = self.sections_data[i].take() {
+                        used = used.saturating_sub(data.allocated_bytes());
+                    }
+                },
+                None => break
+            }
+        }
+    }
+
+    /// Installs or removes an [Instrumentation](crate::instrument::Instrumentation).
+    ///
+    /// *While installed, loading a section reports its decompression, its
+    /// checksum, the number of bytes read and whether it is stored in a
+    /// temporary file; with [load_sections](Self::load_sections) events are
+    /// reported from the worker threads.*
+    ///
+    /// # Arguments
+    ///
+    /// * `instrument`: the instrumentation to report events to, None to stop reporting.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use std::sync::Arc;
+    /// use bpx::builder::SectionHeaderBuilder;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::instrument::BasicStats;
+    /// use bpx::Interface;
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let handle = encoder.create_section(SectionHeaderBuilder::bulk_data().build()).unwrap();
+    /// encoder.open_section(handle).unwrap().write_all(&[0; 4096]).unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let stats = Arc::new(BasicStats::new());
+    /// let mut decoder = Decoder::new(Cursor::new(encoder.into_inner())).unwrap();
+    /// decoder.set_instrumentation(Some(stats.clone()));
+    /// decoder.open_section(handle).unwrap();
+    /// let stats = stats.get();
+    /// assert_eq!(stats.decompressed_sections, 1);
+    /// assert_eq!(stats.decompress_output_bytes, 4096);
+    /// assert_eq!(stats.backend_read_bytes, stats.decompress_input_bytes);
+    /// ```
+    pub fn set_instrumentation(&mut self, instrument: Option<Arc<dyn Instrumentation>>)
+    {
+        self.instrument = instrument;
+    }
+
+    /// Returns the BPX version of the file, one of
+    /// [KNOWN_VERSIONS](crate::header::MainHeader::KNOWN_VERSIONS).
+    ///
+    /// *Tools can use this to branch on the version before handing the
+    /// decoder to a variant decoder.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::header::BPX_CURRENT_VERSION;
+    ///
+    /// let mut buf = Vec::new();
+    /// Encoder::new(&mut buf).unwrap().save().unwrap();
+    /// let decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    /// assert_eq!(decoder.version(), BPX_CURRENT_VERSION);
+    /// ```
+    pub fn version(&self) -> u32
+    {
+        return self.main_header.version;
+    }
+
+    /// Returns the number of bytes of the stream this BPX occupies.
+    ///
+    /// *This is `file_size` plus the integrity footer written by
+    /// [set_file_digest](crate::encoder::Encoder::set_file_digest), if present.
+    /// When several BPX are concatenated, the next one starts at this offset
+    /// (see [new_embedded](Decoder::new_embedded)).*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    ///
+    /// let mut buf = Vec::new();
+    /// Encoder::new(&mut buf).unwrap().save().unwrap();
+    /// let size = buf.len() as u64;
+    /// buf.extend_from_slice(b"garbage");
+    /// let decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    /// assert_eq!(decoder.bytes_consumed(), size);
+    /// assert_eq!(decoder.trailing_bytes(), 7);
+    /// ```
+    pub fn bytes_consumed(&self) -> u64
+    {
+        return self.consumed;
+    }
+
+    /// Returns the number of bytes following this BPX in the stream.
+    ///
+    /// *Trailing bytes are accepted by [new](Self::new) and rejected by
+    /// [new_strict](Self::new_strict).*
+    pub fn trailing_bytes(&self) -> u64
+    {
+        return self.trailing;
+    }
+
+    /// Verifies the optional integrity footer of the BPX.
+    ///
+    /// *The footer is written by [Encoder](crate::encoder::Encoder) when
+    /// [set_file_digest](crate::encoder::Encoder::set_file_digest) is enabled.*
+    ///
+    /// returns: Result<bool, Error>; true if the footer is present and matches
+    /// the file content, false if the file has no footer.
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the footer is present but
+    /// the CRC32 of the file does not match or if the file could not be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    ///
+    /// let mut buf = Vec::new();
+    /// Encoder::new(Cursor::new(&mut buf)).unwrap().save().unwrap();
+    /// let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    /// assert!(!decoder.verify_file_digest().unwrap());
+    /// ```
+    pub fn verify_file_digest(&mut self) -> Result<bool>
+    {
+        let file_size = self.main_header.file_size;
+        let mut footer: [u8; DIGEST_FOOTER_SIZE] = [0; DIGEST_FOOTER_SIZE];
+
+        self.file.seek(io::SeekFrom::Start(file_size))?;
+        if read_fill(&mut self.file, &mut footer)? < DIGEST_FOOTER_SIZE {
+            return Ok(false);
+        }
+        if footer[..4] != DIGEST_FOOTER_SIGNATURE {
+            return Ok(false);
+        }
+        let expected = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]);
+        let mut chksum = Crc32Checksum::new();
+        let mut buf: [u8; READ_BLOCK_SIZE] = [0; READ_BLOCK_SIZE];
+        let mut count: u64 = 0;
+        self.file.seek(io::SeekFrom::Start(0))?;
+        while count < file_size {
+            let size = std::cmp::min(READ_BLOCK_SIZE as u64, file_size - count) as usize;
+            self.file.read_exact(&mut buf[..size])?;
+            chksum.push(&buf[..size]);
+            count += size as u64;
+        }
+        let actual = chksum.finish();
+        if actual != expected {
+            return Err(Error::Checksum(actual, expected));
+        }
+        return Ok(true);
+    }
+
+    /// Loads multiple sections at once, inflating them on multiple threads.
+    ///
+    /// *The stored bytes of all requested sections are first read in file order,
+    /// then decompressed and verified by `threads` workers. Sections which are
+    /// already loaded are skipped. Sections too large to be kept in memory are
+    /// loaded one by one on the calling thread.*
+    ///
+    /// # Arguments
+    ///
+    /// * `handles`: the sections to load.
+    /// * `threads`: the number of worker threads, 0 to use the available parallelism.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if a section handle is invalid,
+    /// if a section could not be read, is corrupted or does not match its checksum.
+    /// Sections loaded before the error stay loaded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::builder::{CompressionMethod, SectionHeaderBuilder};
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut encoder = Encoder::new(&mut buf).unwrap();
+    /// for i in 0..4u8 {
+    ///     let header = SectionHeaderBuilder::new().with_compression(CompressionMethod::Xz).build();
+    ///     let handle = encoder.create_section(header).unwrap();
+    ///     encoder.open_section(handle).unwrap().write_all(&[i; 64]).unwrap();
+    /// }
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    /// let handles: Vec<_> = decoder.iter_sections().collect();
+    /// decoder.load_sections(&handles, 2).unwrap();
+    /// assert_eq!(decoder.open_section(handles[3]).unwrap().load_in_memory().unwrap(), [3; 64]);
+    /// ```
+    pub fn load_sections(&mut self, handles: &[SectionHandle], threads: usize) -> Result<()>
+    {
+        let mut jobs: Vec<(usize, Vec<u8>)> = Vec::new();
+        if let Some(h) = handles.iter().find(|h| h.0 >= self.sections.len()) {
+            return Err(Error::InvalidHandle(h.0));
+        }
+        let mut ordered: Vec<usize> = handles
+            .iter()
+            .map(|h| h.0)
+            .filter(|i| self.sections_data[*i].is_none())
+            .collect();
+        ordered.sort_unstable();
+        ordered.dedup();
+        ordered.sort_by_key(|i| (self.sections[*i].pointer, *i));
+        for i in ordered {
+            let header = self.sections[i];
+            let context = move |e: Error| e.with_section(i as u32, header.btype);
+            // The stored and inflated bytes are both held in memory until the job completes.
+            let charge = header.csize as usize + header.size as usize;
+            if let Some(bytes) = self.shared.as_ref().filter(|_| header.compression().is_none()) {
+                match shared_section(bytes, &header) {
+                    Ok(data) => self.sections_data[i] = Some(data),
+                    Err(e) => {
+                        self.release_jobs(&jobs);
+                        return Err(context(e));
+                    }
+                }
+                continue;
+            }
+            let probe = Probe::new(self.instrument.as_deref(), i);
+            if header.size > MEMORY_THRESHOLD || header.csize > MEMORY_THRESHOLD || !self.budget.try_reserve(charge) {
+                match load_section_budgeted(&mut self.file, &header, &self.budget, &self.storage, probe) {
+                    Ok(data) => self.sections_data[i] = Some(data),
+                    Err(e) => {
+                        self.release_jobs(&jobs);
+                        return Err(context(e));
+                    }
+                }
+                continue;
+            }
+            let mut blob = vec![0; header.csize as usize];
+            if let Err(e) = read_stored_section(&mut self.file, &header, &mut blob) {
+                self.budget.release(charge);
+                self.release_jobs(&jobs);
+                return Err(context(e));
+            }
+            if let Some(probe) = probe {
+                probe.instrument.on_event(Event::BackendRead {
+                    bytes: header.csize as u64
+                });
+            }
+            jobs.push((i, blob));
+        }
+        if jobs.is_empty() {
+            return Ok(());
+        }
+        let threads = match threads {
+            0 => std::thread::available_parallelism().map(|v| v.get()).unwrap_or(1),
+            v => v
+        };
+        let threads = std::cmp::min(threads, jobs.len());
+        let next = AtomicUsize::new(0);
+        let sections = &self.sections;
+        let instrument = self.instrument.as_deref();
+        let results: Vec<(usize, Result<Vec<u8>>)> = std::thread::scope(|s| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let job = next.fetch_add(1, Ordering::Relaxed);
+                            let (i, blob) = match jobs.get(job) {
+                                Some(v) => v,
+                                None => break
+                            };
+                            let header = &sections[*i];
+                            let res = load_section_from_blob(blob, header, Probe::new(instrument, *i))
+                                .map_err(|e| e.with_section(*i as u32, header.btype));
+                            done.push((*i, res));
+                        }
+                        done
+                    })
+                })
+                .collect();
+            workers.into_iter().flat_map(|w| w.join().unwrap_or_else(|e| std::panic::resume_unwind(e))).collect()
+        });
+        self.release_jobs(&jobs);
+        let mut error = None;
+        for (i, res) in results {
+            match res.and_then(|data| Ok(budgeted_section_from_content(data, &self.budget)?)) {
+                Ok(data) => self.sections_data[i] = Some(data),
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        return match error {
+            Some(e) => Err(e),
+            None => Ok(())
+        };
+    }
+
+    fn release_jobs(&self, jobs: &[(usize, Vec<u8>)])
+    {
+        for (i, _) in jobs {
+            let header = &self.sections[*i];
+            self.budget.release(header.csize as usize + header.size as usize);
+        }
+    }
+
+    /// Formats a range of a section as hex and ASCII for debugging purposes.
+    ///
+    /// *See [hexdump](crate::utils::hexdump) for the output format; the range is
+    /// clamped to the size of the section.*
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    /// * `range`: the range of bytes to display.
+    ///
+    /// returns: Result<String, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the handle is invalid or
+    /// the section could not be loaded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::header::SectionHeader;
+    /// use bpx::Interface;
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut encoder = Encoder::new(&mut buf).unwrap();
+    /// let handle = encoder.create_section(SectionHeader::new()).unwrap();
+    /// encoder.open_section(handle).unwrap().write_all(b"BPX").unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    /// let handle = decoder.find_section_by_index(0).unwrap();
+    /// assert!(decoder.dump_section(handle, 0..256).unwrap().ends_with("|BPX|\n"));
+    /// ```
+    pub fn dump_section(&mut self, handle: SectionHandle, range: Range<u64>) -> Result<String>
+    {
+        let len = usize::try_from(range.end.saturating_sub(range.start)).unwrap_or(usize::MAX);
+        let mut section = self.open_section(handle)?;
+        return hexdump(&mut *section, range.start, len);
+    }
+
+    /// Returns a reader over the stored bytes of a section, exactly as they
+    /// appear in the file: still compressed and without checksum verification.
+    ///
+    /// Combined with [create_section_raw](crate::encoder::Encoder::create_section_raw)
+    /// this copies sections between files without decompressing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: Result<Take<&mut TBackend>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the handle is invalid or
+    /// the backend could not be seeked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{Cursor, Read};
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::header::SectionHeader;
+    /// use bpx::Interface;
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut encoder = Encoder::new(&mut buf).unwrap();
+    /// let handle = encoder.create_section(SectionHeader::new()).unwrap();
+    /// encoder.open_section(handle).unwrap().write_all(b"BPX").unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    /// let handle = decoder.find_section_by_index(0).unwrap();
+    /// let mut raw = Vec::new();
+    /// decoder.raw_section_reader(handle).unwrap().read_to_end(&mut raw).unwrap();
+    /// assert_eq!(raw, b"BPX");
+    /// ```
+    pub fn raw_section_reader(&mut self, handle: SectionHandle) -> Result<Take<&mut TBackend>>
+    {
+        let header = self.sections.get(handle.0).ok_or(Error::InvalidHandle(handle.0))?;
+        let csize = header.csize as u64;
+        self.file.seek(io::SeekFrom::Start(header.pointer))?;
+        return Ok((&mut self.file).take(csize));
+    }
+
+    /// Returns a reader inflating a section as it is read, the checksum of the
+    /// section is verified once its last byte was read.
+    ///
+    /// *Unlike [open_section](crate::Interface::open_section), the section is never
+    /// loaded in memory. When the BPX has checkpoints (see
+    /// [set_checkpoints](crate::encoder::Encoder::set_checkpoints)), each chunk is
+    /// verified as soon as its last byte is read, so a corruption is reported
+    /// within one chunk of where it lies. Bytes are returned before the chunk
+    /// containing them is verified.*
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: Result<SectionStream<TBackend>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the handle is invalid, the
+    /// checkpoints section is corrupted or the backend could not be seeked.
+    /// Reading fails with an [io::Error](std::io::Error) wrapping the
+    /// [Error](crate::error::Error) if the section is corrupted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{Cursor, Read};
+    /// use bpx::builder::{Checksum, CompressionMethod, SectionHeaderBuilder};
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut encoder = Encoder::new(&mut buf).unwrap();
+    /// let header = SectionHeaderBuilder::new()
+    ///     .with_checksum(Checksum::Crc32)
+    ///     .with_compression(CompressionMethod::Zlib)
+    ///     .build();
+    /// let handle = encoder.create_section(header).unwrap();
+    /// encoder.open_section(handle).unwrap().write_all(&[42; 1000]).unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    /// let expected = decoder.get_section_header(handle).chksum;
+    /// let mut stream = decoder.stream_section(handle).unwrap();
+    /// let mut data = Vec::new();
+    /// stream.read_to_end(&mut data).unwrap();
+    /// assert_eq!(data, [42; 1000]);
+    /// assert_eq!(stream.checksum_so_far(), expected);
+    /// ```
+    pub fn stream_section(&mut self, handle: SectionHandle) -> Result<SectionStream<'_, TBackend>>
+    {
+        let header = *self.sections.get(handle.0).ok_or(Error::InvalidHandle(handle.0))?;
+        let index = handle.0 as u32;
+        let checkpoints = read_checkpoints(self, index)?.map(|v| ChunkVerifier::new(v.interval, v.chunks));
+        let (size, csize) = (header.size as usize, header.csize as usize);
+        let (inflater, len) = match header.compression() {
+            // Nothing to inflate, even if the writer ran a compressor over an empty section.
+            _ if size == 0 => (None, 0),
+            Some(builder::CompressionMethod::Xz) => (Some(XzCompressionMethod::stream(csize, size)?), csize),
+            Some(builder::CompressionMethod::Zlib) => (Some(ZlibCompressionMethod::stream(csize, size)?), csize),
+            None => (None, size)
+        };
+        let chksum: Box<dyn Checksum> = match header.checksum() {
+            Some(builder::Checksum::Weak) => Box::new(WeakChecksum::new()),
+            Some(builder::Checksum::Crc32) => Box::new(Crc32Checksum::new()),
+            None => Box::new(NoChecksum)
+        };
+        self.file.seek(io::SeekFrom::Start(header.pointer))?;
+        return Ok(SectionStream {
+            input: (&mut self.file).take(len as u64),
+            inflater,
+            header,
+            index,
+            chksum,
+            checkpoints,
+            ended: false
+        });
+    }
+
+    /// Returns the underlying IO backend, used to access the raw bytes of the file.
+    pub(crate) fn get_backend_mut(&mut self) -> &mut TBackend
+    {
+        return &mut self.file;
+    }
+
+    /// Converts this decoder into a [ConcurrentDecoder](self::ConcurrentDecoder)
+    /// which can be shared between threads.
+    ///
+    /// *Sections which were already loaded are moved to memory.*
+    ///
+    /// returns: Result<ConcurrentDecoder<TBackend>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if a loaded section could not be read.
+    pub fn into_concurrent(self) -> Result<ConcurrentDecoder<TBackend>>
+    {
+        let mut sections_data = Vec::with_capacity(self.sections.len());
+        for section in self.sections_data {
+            match section {
+                Some(mut data) => {
+                    data.seek(io::SeekFrom::Start(0))?;
+                    let mut buf = data.load_in_memory()?;
+                    buf.truncate(data.size());
+                    sections_data.push(OnceLock::from(buf));
+                },
+                None => sections_data.push(OnceLock::new())
+            }
+        }
+        return Ok(ConcurrentDecoder {
+            main_header: self.main_header,
+            sections: self.sections,
+            sections_data,
+            file: Mutex::new(self.file)
+        });
+    }
+}
+
+impl Decoder<Spool>
+{
+    /// Creates a new BPX decoder from a non-seekable stream such as a pipe.
+    ///
+    /// *The stream is first copied into a [Spool](crate::section::Spool): in
+    /// memory up to `spool_threshold` bytes, in a temporary file beyond.
+    /// [DEFAULT_SPOOL_THRESHOLD](crate::section::DEFAULT_SPOOL_THRESHOLD)
+    /// matches the size above which sections are stored in temporary files.
+    /// The spooled copy is seekable, so sections stored out of order are read
+    /// like with [new](Decoder::new).*
+    ///
+    /// # Arguments
+    ///
+    /// * `read`: the stream to read the whole BPX from.
+    /// * `spool_threshold`: the maximum number of bytes to keep in memory.
+    ///
+    /// returns: Result<Decoder<Spool>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the stream could not be
+    /// spooled, if some headers could not be read or if the header data is corrupted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::section::DEFAULT_SPOOL_THRESHOLD;
+    /// use bpx::Interface;
+    ///
+    /// let mut buf = Vec::new();
+    /// Encoder::new(&mut buf).unwrap().save().unwrap();
+    /// let decoder = Decoder::new_spooled(&buf[..], DEFAULT_SPOOL_THRESHOLD).unwrap();
+    /// assert_eq!(decoder.get_main_header().section_num, 0);
+    /// ```
+    pub fn new_spooled<TRead: io::Read>(read: TRead, spool_threshold: usize) -> Result<Decoder<Spool>>
+    {
+        let spool = Spool::new(read, spool_threshold)?;
+        return Decoder::new(spool);
+    }
+}
+
+impl<TBackend: IoBackend> Decoder<Embedded<TBackend>>
+{
+    /// Creates a new BPX decoder for a BPX starting at an arbitrary offset of a stream.
+    ///
+    /// *The BPX does not need to own the rest of the stream: bytes following it
+    /// are reported by [trailing_bytes](Decoder::trailing_bytes) and the next BPX
+    /// of a concatenation starts [bytes_consumed](Decoder::bytes_consumed) bytes
+    /// after `start_offset`.*
+    ///
+    /// # Arguments
+    ///
+    /// * `file`: An [IoBackend](self::IoBackend) to use for reading the data.
+    /// * `start_offset`: the offset in `file` of the first byte of the BPX.
+    ///
+    /// returns: Result<Decoder<Embedded<TBackend>>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned in the same cases as [new](Decoder::new).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    ///
+    /// let mut buf = b"header".to_vec();
+    /// Encoder::new(&mut buf).unwrap().save().unwrap();
+    /// let decoder = Decoder::new_embedded(Cursor::new(&buf), 6).unwrap();
+    /// assert_eq!(decoder.get_main_header().section_num, 0);
+    /// assert_eq!(decoder.bytes_consumed(), buf.len() as u64 - 6);
+    /// ```
+    pub fn new_embedded(file: TBackend, start_offset: u64) -> Result<Decoder<Embedded<TBackend>>>
+    {
+        return Decoder::new(Embedded::new(file, start_offset)?);
+    }
+}
+
+impl Decoder<BufReader<File>>
+{
+    /// Creates a new BPX decoder reading from a file.
+    ///
+    /// This is the recommended way to read a BPX from disk: the section
+    /// header table is read through a buffer instead of issuing one system
+    /// call per header, which makes opening files with many sections several
+    /// times faster (see `cargo bench`).
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: the path of the file to read.
+    ///
+    /// returns: Result<Decoder<BufReader<File>>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the file could not be
+    /// opened or if it is not a valid BPX (see [new](Decoder::new)).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::header::SectionHeader;
+    /// use bpx::Interface;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("file.bpx");
+    /// let mut encoder = Encoder::create_file(&path).unwrap();
+    /// let handle = encoder.create_section(SectionHeader::new()).unwrap();
+    /// encoder.open_section(handle).unwrap().write_all(b"test").unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::open_file(&path).unwrap();
+    /// let handle = decoder.find_section_by_index(0).unwrap();
+    /// assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), b"test");
+    /// ```
+    pub fn open_file<TPath: AsRef<Path>>(path: TPath) -> Result<Decoder<BufReader<File>>>
+    {
+        let file = File::open(path)?;
+        return Decoder::new(BufReader::with_capacity(FILE_BUFFER_SIZE, file));
+    }
+}
+
+impl Decoder<Cursor<Arc<[u8]>>>
+{
+    /// Creates a new BPX decoder over a buffer shared with other decoders.
+    ///
+    /// *Each decoder has its own cursor, so any number of them can read the same
+    /// buffer at once, from different threads. Uncompressed sections are views over
+    /// the buffer: opening them does not copy their content until they are written to.
+    /// Only compressed sections are inflated into new section data.*
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: the content of the BPX.
+    ///
+    /// returns: Result<Decoder<Cursor<Arc<[u8]>>>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned in the same cases as [new](Decoder::new).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::header::SectionHeader;
+    /// use bpx::Interface;
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let handle = encoder.create_section(SectionHeader::new()).unwrap();
+    /// encoder.open_section(handle).unwrap().write_all(b"test").unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let bytes: Arc<[u8]> = encoder.into_inner().into();
+    /// let mut first = Decoder::from_shared(bytes.clone()).unwrap();
+    /// let mut second = Decoder::from_shared(bytes).unwrap();
+    /// assert_eq!(first.open_section(handle).unwrap().load_in_memory().unwrap(), b"test");
+    /// assert_eq!(second.open_section(handle).unwrap().load_in_memory().unwrap(), b"test");
+    /// assert_eq!(first.storage_report().memory_bytes(), 0);
+    /// ```
+    pub fn from_shared(bytes: Arc<[u8]>) -> Result<Decoder<Cursor<Arc<[u8]>>>>
+    {
+        let mut decoder = Decoder::new(Cursor::new(bytes.clone()))?;
+        decoder.shared = Some(bytes);
+        return Ok(decoder);
+    }
+}
+
+/// A reader inflating a section as it is read (see [stream_section](Decoder::stream_section)).
+pub struct SectionStream<'a, TBackend: IoBackend>
+{
+    input: Take<&'a mut TBackend>,
+    // None for uncompressed sections.
+    inflater: Option<Box<dyn InflateStream>>,
+    header: SectionHeader,
+    index: u32,
+    chksum: Box<dyn Checksum>,
+    checkpoints: Option<ChunkVerifier>,
+    ended: bool
+}
+
+impl<'a, TBackend: IoBackend> SectionStream<'a, TBackend>
+{
+    /// Returns the checksum of the bytes read so far, computed with the
+    /// checksum algorithm of the section (always 0 for sections without checksum).
+    ///
+    /// *Once the whole section was read, this is the checksum stored in its header.*
+    pub fn checksum_so_far(&self) -> u32
+    {
+        return self.chksum.value();
+    }
+
+    fn read_checked(&mut self, buf: &mut [u8]) -> Result<usize>
+    {
+        if self.ended || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = match self.inflater.as_mut() {
+            Some(inflater) => inflater.inflate_some(&mut self.input, buf)?,
+            None => {
+                let wanted = std::cmp::min(buf.len(), self.input.limit() as usize);
+                let len = self.input.read(&mut buf[..wanted])?;
+                if len == 0 && wanted > 0 {
+                    return Err(Error::Truncation("load section"));
+                }
+                len
+            }
+        };
+        self.chksum.push(&buf[..len]);
+        if let Some(checkpoints) = self.checkpoints.as_mut() {
+            checkpoints.push(&buf[..len])?;
+        }
+        if len > 0 {
+            return Ok(len);
+        }
+        if let Some(checkpoints) = self.checkpoints.as_mut() {
+            checkpoints.finish()?;
+        }
+        let v = self.chksum.value();
+        if self.header.checksum().is_some() && v != self.header.chksum {
+            return Err(Error::Checksum(v, self.header.chksum));
+        }
+        self.ended = true;
+        return Ok(0);
+    }
+}
+
+impl<'a, TBackend: IoBackend> Read for SectionStream<'a, TBackend>
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        let (index, btype) = (self.index, self.header.btype);
+        return self.read_checked(buf).map_err(|e| e.with_section(index, btype).into());
+    }
+}
+
+/// A BPX decoder which can be shared between threads.
+///
+/// *Sections are loaded in memory the first time they are requested. Only
+/// loading a section locks the IO backend, sections which are already
+/// loaded are read without any locking.*
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use bpx::decoder::Decoder;
+/// use bpx::encoder::Encoder;
+/// use bpx::header::SectionHeader;
+/// use bpx::Interface;
+///
+/// let mut buf = Vec::new();
+/// let mut encoder = Encoder::new(&mut buf).unwrap();
+/// let handle = encoder.create_section(SectionHeader::new()).unwrap();
+/// encoder.open_section(handle).unwrap().write_all(b"test").unwrap();
+/// encoder.save().unwrap();
+///
+/// let decoder = Decoder::new(Cursor::new(buf)).unwrap().into_concurrent().unwrap();
+/// let handle = decoder.find_section_by_index(0).unwrap();
+/// std::thread::scope(|s| {
+///     s.spawn(|| assert_eq!(decoder.load_section(handle).unwrap(), b"test"));
+///     s.spawn(|| assert_eq!(decoder.get_section_header(handle).size, 4));
+/// });
+/// ```
+pub struct ConcurrentDecoder<TBackend: IoBackend>
+{
+    main_header: MainHeader,
+    sections: Vec<SectionHeader>,
+    sections_data: Vec<OnceLock<Vec<u8>>>,
+    file: Mutex<TBackend>
+}
+
+impl<TBackend: IoBackend> ConcurrentDecoder<TBackend>
+{
+    /// Searches for the first section of a given type.
+    /// Returns None if no section could be found.
+    ///
+    /// # Arguments
+    ///
+    /// * `btype`: section type byte.
+    ///
+    /// returns: Option<SectionHandle>
+    pub fn find_section_by_type(&self, btype: u8) -> Option<SectionHandle>
+    {
+        return find_sections_with_type(&self.sections, btype).next();
+    }
+
+    /// Searches for all sections of a given type.
+    ///
+    /// # Arguments
+    ///
+    /// * `btype`: section type byte.
+    ///
+    /// returns: Vec<SectionHandle, Global>
+    pub fn find_all_sections_of_type(&self, btype: u8) -> Vec<SectionHandle>
+    {
+        return find_sections_with_type(&self.sections, btype).collect();
+    }
+
+    /// Locates a section by its index in the file.
+    /// Returns None if the section does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: the section index to search for.
+    ///
+    /// returns: Option<SectionHandle>
+    pub fn find_section_by_index(&self, index: u32) -> Option<SectionHandle>
+    {
+        return find_section_index(&self.sections, index);
+    }
 
-use crate::{
-    compression::{Checksum, Crc32Checksum, Inflater, WeakChecksum, XzCompressionMethod, ZlibCompressionMethod},
-    error::Error,
-    header::{MainHeader, SectionHeader, FLAG_CHECK_CRC32, FLAG_CHECK_WEAK, FLAG_COMPRESS_XZ, FLAG_COMPRESS_ZLIB},
-    section::{new_section_data, SectionData},
-    utils::OptionExtension,
-    Interface,
-    Result,
-    SectionHandle
-};
+    /// Returns the BPX section header of a section.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: &SectionHeader
+    ///
+    /// # Panics
+    ///
+    /// Panics if the given section handle is invalid, use
+    /// [try_get_section_header](Self::try_get_section_header) for handles
+    /// which may not belong to this BPX.
+    pub fn get_section_header(&self, handle: SectionHandle) -> &SectionHeader
+    {
+        return &self.sections[handle.0];
+    }
 
-const READ_BLOCK_SIZE: usize = 8192;
+    /// Returns the BPX section header of a section.
+    /// Returns None if the handle does not point to a section of this BPX.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: Option<&SectionHeader>
+    pub fn try_get_section_header(&self, handle: SectionHandle) -> Option<&SectionHeader>
+    {
+        return self.sections.get(handle.0);
+    }
 
-/// Represents the IO backend for a BPX decoder.
-pub trait IoBackend: io::Seek + io::Read
-{
+    /// Returns the section index from a section handle.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: u32
+    pub fn get_section_index(&self, handle: SectionHandle) -> u32
+    {
+        return handle.0 as u32;
+    }
+
+    /// Returns a read-only reference to the BPX main header.
+    pub fn get_main_header(&self) -> &MainHeader
+    {
+        return &self.main_header;
+    }
+
+    /// Returns the content of a section, loading it from the IO backend if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: Result<&[u8], Error>
+    ///
+    /// # Errors
+    ///
+    /// A BPX [Error](crate::error::Error) if the handle does not point to a
+    /// section of this BPX or if an IO or any other file error occurs while
+    /// reading the section from the file.
+    pub fn load_section(&self, handle: SectionHandle) -> Result<&[u8]>
+    {
+        let slot = self.sections_data.get(handle.0).ok_or(Error::InvalidHandle(handle.0))?;
+        if let Some(data) = slot.get() {
+            return Ok(data);
+        }
+        // The backend only holds a cursor which is always moved before reading,
+        // a panic in another thread does not leave it in an invalid state.
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(data) = slot.get() {
+            return Ok(data);
+        }
+        let header = &self.sections[handle.0];
+        let mut section =
+            load_section(&mut *file, header).map_err(|e| e.with_section(handle.0 as u32, header.btype))?;
+        let mut buf = section.load_in_memory()?;
+        buf.truncate(section.size());
+        return Ok(slot.get_or_init(|| buf));
+    }
+
+    /// Reads a string from a loaded string section.
+    ///
+    /// *The string is borrowed from the section content, no string cache is needed.*
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the string section.
+    /// * `address`: the offset to the start of the string.
+    ///
+    /// returns: Result<&str, Error>
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](crate::error::Error) if the section could not be loaded
+    /// or the string is corrupted/truncated.
+    pub fn get_string(&self, handle: SectionHandle, address: u32) -> Result<&str>
+    {
+        let data = self.load_section(handle)?;
+        let header = &self.sections[handle.0];
+        return read_string_in_slice(address, data).map_err(|e| e.with_section(handle.0 as u32, header.btype));
+    }
+
+    /// Consumes this decoder and returns the underlying IO backend.
+    ///
+    /// returns: TBackend
+    pub fn into_inner(self) -> TBackend
+    {
+        return self.file.into_inner().unwrap_or_else(|e| e.into_inner());
+    }
 }
-impl<T: io::Seek + io::Read> IoBackend for T {}
 
-/// The BPX decoder.
-pub struct Decoder<TBackend: IoBackend>
+/// A BPX decoder reading from a byte slice already in memory.
+///
+/// *Uncompressed sections are views over the slice: opening them does not
+/// copy their content until they are written to. Only compressed sections
+/// are inflated into new section data.*
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use bpx::decoder::SliceDecoder;
+/// use bpx::encoder::Encoder;
+/// use bpx::header::SectionHeader;
+/// use bpx::Interface;
+///
+/// let mut buf = Vec::new();
+/// let mut encoder = Encoder::new(&mut buf).unwrap();
+/// let handle = encoder.create_section(SectionHeader::new()).unwrap();
+/// encoder.open_section(handle).unwrap().write_all(b"test").unwrap();
+/// encoder.save().unwrap();
+///
+/// let mut decoder = SliceDecoder::new(&buf).unwrap();
+/// let handle = decoder.find_section_by_index(0).unwrap();
+/// assert_eq!(decoder.section_bytes(handle).unwrap(), Some(&b"test"[..]));
+/// assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), b"test");
+/// ```
+pub struct SliceDecoder<'a>
 {
     main_header: MainHeader,
     sections: Vec<SectionHeader>,
-    sections_data: Vec<Option<Box<dyn SectionData>>>,
-    file: TBackend
+    sections_data: Vec<Option<Box<dyn SectionData + 'a>>>,
+    data: &'a [u8]
 }
 
-impl<TBackend: IoBackend> Decoder<TBackend>
+impl<'a> SliceDecoder<'a>
 {
-    fn read_section_header_table(&mut self, checksum: u32) -> Result<()>
-    {
-        let mut final_checksum = checksum;
-
-        for _ in 0..self.main_header.section_num {
-            let (checksum, header) = SectionHeader::read(&mut self.file)?;
-            final_checksum += checksum;
-            self.sections.push(header);
-        }
-        if final_checksum != self.main_header.chksum {
-            return Err(Error::Checksum(final_checksum, self.main_header.chksum));
-        }
-        return Ok(());
-    }
-
-    /// Creates a new BPX decoder.
+    /// Creates a new BPX decoder over a byte slice.
     ///
     /// # Arguments
     ///
-    /// * `file`: An [IoBackend](self::IoBackend) to use for reading the data.
+    /// * `data`: the content of the BPX.
     ///
-    /// returns: Result<Decoder<TBackend>, Error>
+    /// returns: Result<SliceDecoder, Error>
     ///
     /// # Errors
     ///
     /// An [Error](crate::error::Error) is returned if some headers
     /// could not be read or if the header data is corrupted.
+    pub fn new(data: &'a [u8]) -> Result<SliceDecoder<'a>>
+    {
+        let mut file = Cursor::new(data);
+        let (checksum, main_header) = MainHeader::read(&mut file)?;
+        let sections = read_section_header_table(&mut file, &main_header, checksum)?;
+        let sections_data = std::iter::repeat_with(|| None).take(sections.len()).collect();
+        return Ok(SliceDecoder {
+            main_header,
+            sections,
+            sections_data,
+            data
+        });
+    }
+
+    /// Returns the content of an uncompressed section without copying it.
     ///
-    /// # Examples
+    /// *The checksum of the section is verified on each call.*
     ///
-    /// ```
-    /// use bpx::encoder::Encoder;
+    /// # Arguments
     ///
-    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
-    /// encoder.save();
-    /// //TODO: Finish once Encoder can be consumed back into its IO Backend
-    /// ```
-    pub fn new(mut file: TBackend) -> Result<Decoder<TBackend>>
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: Result<Option<&[u8]>, Error>; None if the section is compressed.
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the handle does not point
+    /// to a section of this BPX, if the section is truncated or its checksum
+    /// does not match.
+    pub fn section_bytes(&self, handle: SectionHandle) -> Result<Option<&'a [u8]>>
     {
-        let (checksum, header) = MainHeader::read(&mut file)?;
-        let num = header.section_num;
-        let mut decoder = Decoder {
-            file,
-            main_header: header,
-            sections: Vec::with_capacity(num as usize),
-            sections_data: std::iter::repeat_with(|| None).take(num as usize).collect()
-        };
-        decoder.read_section_header_table(checksum)?;
-        return Ok(decoder);
+        let header = self.sections.get(handle.0).ok_or(Error::InvalidHandle(handle.0))?;
+        if header.compression().is_some() {
+            return Ok(None);
+        }
+        let bytes = section_slice(self.data, header).map_err(|e| e.with_section(handle.0 as u32, header.btype))?;
+        return Ok(Some(bytes));
     }
 }
 
-impl<TBackend: IoBackend> Interface for Decoder<TBackend>
+impl<'a> Interface for SliceDecoder<'a>
 {
-    fn find_section_by_type(&self, btype: u8) -> Option<SectionHandle>
+    fn find_section_by_index(&self, index: u32) -> Option<SectionHandle>
     {
-        for i in 0..self.sections.len() {
-            if self.sections[i].btype == btype {
-                return Some(SectionHandle(i));
-            }
-        }
-        return None;
+        return find_section_index(&self.sections, index);
     }
 
-    fn find_all_sections_of_type(&self, btype: u8) -> Vec<SectionHandle>
+    fn get_section_header(&self, handle: SectionHandle) -> &SectionHeader
     {
-        let mut v = Vec::new();
+        return &self.sections[handle.0];
+    }
 
-        for i in 0..self.sections.len() {
-            if self.sections[i].btype == btype {
-                v.push(SectionHandle(i));
-            }
-        }
-        return v;
+    fn try_get_section_header(&self, handle: SectionHandle) -> Option<&SectionHeader>
+    {
+        return self.sections.get(handle.0);
+    }
+
+    fn open_section(&mut self, handle: SectionHandle) -> Result<SectionGuard<'_>>
+    {
+        let header = self.sections.get(handle.0).ok_or(Error::InvalidHandle(handle.0))?;
+        let data = self.data;
+        let object = self.sections_data[handle.0].get_or_insert_with_err(|| {
+            let res: Result<Box<dyn SectionData + 'a>> = match header.compression() {
+                Some(_) => load_section(&mut Cursor::new(data), header),
+                None => section_slice(data, header).map(|v| Box::new(SliceSection::new(v)) as _)
+            };
+            res.map_err(|e| e.with_section(handle.0 as u32, header.btype))
+        })?;
+        return Ok(SectionGuard::new(object.as_mut())?);
+    }
+
+    fn get_main_header(&self) -> &MainHeader
+    {
+        return &self.main_header;
+    }
+
+    fn get_section_index(&self, handle: SectionHandle) -> u32
+    {
+        return handle.0 as u32;
+    }
+
+    fn storage_report(&self) -> StorageReport
+    {
+        return storage_report(self.sections_data.iter().map(|v| v.as_deref().map(|data| data as &dyn SectionData)));
     }
+}
 
+impl<TBackend: IoBackend> Interface for Decoder<TBackend>
+{
     fn find_section_by_index(&self, index: u32) -> Option<SectionHandle>
     {
-        if let Some(_) = self.sections.get(index as usize) {
-            return Some(SectionHandle(index as _));
-        }
-        return None;
+        return find_section_index(&self.sections, index);
     }
 
     fn get_section_header(&self, handle: SectionHandle) -> &SectionHeader
@@ -149,12 +1640,33 @@ impl<TBackend: IoBackend> Interface for Decoder<TBackend>
         return &self.sections[handle.0];
     }
 
-    fn open_section(&mut self, handle: SectionHandle) -> Result<&mut dyn SectionData>
+    fn try_get_section_header(&self, handle: SectionHandle) -> Option<&SectionHeader>
+    {
+        return self.sections.get(handle.0);
+    }
+
+    fn open_section(&mut self, handle: SectionHandle) -> Result<SectionGuard<'_>>
     {
+        let size = self.sections.get(handle.0).ok_or(Error::InvalidHandle(handle.0))?.size;
+        self.clock += 1;
+        self.last_use[handle.0] = self.clock;
+        if self.sections_data[handle.0].is_none() {
+            self.make_room(handle, size as usize);
+        }
         let header = &self.sections[handle.0];
         let file = &mut self.file;
-        let object = self.sections_data[handle.0].get_or_insert_with_err(|| load_section(file, header))?;
-        return Ok(object.as_mut());
+        let budget = &self.budget;
+        let storage = &self.storage;
+        let shared = self.shared.as_ref().filter(|_| header.compression().is_none());
+        let probe = Probe::new(self.instrument.as_deref(), handle.0);
+        let object = self.sections_data[handle.0].get_or_insert_with_err(|| {
+            let res = match shared {
+                Some(bytes) => shared_section(bytes, header),
+                None => load_section_budgeted(file, header, budget, storage, probe)
+            };
+            res.map_err(|e| e.with_section(handle.0 as u32, header.btype))
+        })?;
+        return Ok(SectionGuard::new(object.as_mut())?);
     }
 
     fn get_main_header(&self) -> &MainHeader
@@ -166,32 +1678,399 @@ impl<TBackend: IoBackend> Interface for Decoder<TBackend>
     {
         return handle.0 as u32;
     }
+
+    fn memory_budget(&self) -> Option<&MemoryBudget>
+    {
+        return Some(&self.budget);
+    }
+
+    fn storage_report(&self) -> StorageReport
+    {
+        return storage_report(self.sections_data.iter().map(|v| v.as_deref()));
+    }
+
+    fn evict_section(&mut self, handle: SectionHandle) -> bool
+    {
+        return self.evict(handle);
+    }
+
+    fn section_remaining_capacity(&mut self, handle: SectionHandle) -> Result<u64>
+    {
+        // Sections which are not loaded are not inflated just to read their size.
+        let header = self.sections.get(handle.0).ok_or(Error::InvalidHandle(handle.0))?;
+        let size = match &self.sections_data[handle.0] {
+            Some(data) => data.size() as u64,
+            None => header.size as u64
+        };
+        return Ok(MAX_SECTION_SIZE.saturating_sub(size));
+    }
 }
 
-fn load_section<TBackend: IoBackend>(file: &mut TBackend, section: &SectionHeader) -> Result<Box<dyn SectionData>>
+/// Reads the section header table following a main header, verifies the main header checksum
+/// and checks that the data of each section lies within the file without overlapping.
+///
+/// *The table must fit in `file_size`; this is checked before reading anything so that an
+/// absurd `section_num` fails without allocating.*
+pub(crate) fn read_section_header_table<TBackend: IoBackend>(
+    file: &mut TBackend,
+    main_header: &MainHeader,
+    checksum: u32
+) -> Result<Vec<SectionHeader>>
 {
-    let mut data = new_section_data(Some(section.size))?;
-    data.seek(io::SeekFrom::Start(0))?;
-    if section.flags & FLAG_CHECK_WEAK != 0 {
-        let mut chksum = WeakChecksum::new();
-        load_section_checked(file, &section, &mut data, &mut chksum)?;
-        let v = chksum.finish();
-        if v != section.chksum {
-            return Err(Error::Checksum(v, section.chksum));
-        }
-    } else if section.flags & FLAG_CHECK_CRC32 != 0 {
-        let mut chksum = Crc32Checksum::new();
-        load_section_checked(file, &section, &mut data, &mut chksum)?;
-        let v = chksum.finish();
-        if v != section.chksum {
-            return Err(Error::Checksum(v, section.chksum));
+    let table_size = main_header.section_num as u64 * SECTION_HEADER_SIZE as u64;
+    if MAIN_HEADER_SIZE as u64 + table_size > main_header.file_size {
+        return Err(Error::Corruption(format!(
+            "section_num ({}) does not fit in file_size ({})",
+            main_header.section_num, main_header.file_size
+        )));
+    }
+    let mut final_checksum = checksum;
+    let mut sections = Vec::new();
+
+    for _ in 0..main_header.section_num {
+        let (checksum, header) = SectionHeader::read(file)?;
+        final_checksum = final_checksum.wrapping_add(checksum);
+        sections.push(header);
+    }
+    if final_checksum != main_header.chksum {
+        return Err(Error::Checksum(final_checksum, main_header.chksum));
+    }
+    check_section_ranges(main_header, &sections)?;
+    return Ok(sections);
+}
+
+/// Checks that no section ends after `file_size` and that the data of two sections
+/// never partially overlap; identical ranges are legal as they are produced by
+/// [set_deduplicate](crate::encoder::Encoder::set_deduplicate).
+///
+/// *Uncompressed sections are read for `size` bytes, so their range covers the
+/// larger of `size` and `csize`.*
+fn check_section_ranges(main_header: &MainHeader, sections: &[SectionHeader]) -> Result<()>
+{
+    let mut ranges = Vec::with_capacity(sections.len());
+
+    for (i, header) in sections.iter().enumerate() {
+        let len = stored_extent(header);
+        let end = header.pointer.checked_add(len);
+        if end.map(|v| v > main_header.file_size).unwrap_or(true) {
+            return Err(Error::Corruption(format!(
+                "section {} data ({}+{}) exceeds file_size ({})",
+                i, header.pointer, len, main_header.file_size
+            )));
+        }
+        if len > 0 {
+            ranges.push((header.pointer, header.pointer + len, i));
+        }
+    }
+    ranges.sort_unstable();
+    let mut previous: Option<(u64, u64, usize)> = None;
+    for range in ranges {
+        if let Some(prev) = previous {
+            if range.0 < prev.1 && (range.0, range.1) != (prev.0, prev.1) {
+                return Err(Error::Corruption(format!("data of sections {} and {} overlap", prev.2, range.2)));
+            }
+            if range.1 <= prev.1 {
+                continue;
+            }
+        }
+        previous = Some(range);
+    }
+    return Ok(());
+}
+
+/// Returns the number of bytes a section may read from its pointer.
+fn stored_extent(header: &SectionHeader) -> u64
+{
+    if header.compression().is_none() {
+        return header.csize.max(header.size) as u64;
+    }
+    return header.csize as u64;
+}
+
+fn check_strict_main_header(header: &MainHeader) -> Result<()>
+{
+    if let Some(reserved) = get_type_ext_reserved_range(header.btype) {
+        let extra = header
+            .type_ext
+            .iter()
+            .enumerate()
+            .find(|(i, v)| !reserved.contains(i) && **v != 0);
+        if let Some((i, v)) = extra {
+            return Err(Error::Corruption(format!("nonzero byte ({:#04x}) in type_ext padding at offset {}", v, i)));
+        }
+    }
+    return Ok(());
+}
+
+fn check_strict_sections<TBackend: IoBackend>(
+    file: &mut TBackend,
+    main_header: &MainHeader,
+    sections: &[SectionHeader]
+) -> Result<()>
+{
+    let table_end = (MAIN_HEADER_SIZE + sections.len() * SECTION_HEADER_SIZE) as u64;
+
+    for (i, header) in sections.iter().enumerate() {
+        let unknown = header.flags().unknown_bits();
+        if unknown != 0 {
+            return Err(Error::Corruption(format!("section {} has unknown flags ({:#04x})", i, unknown)));
+        }
+        if header.reserved != 0 {
+            return Err(Error::Corruption(format!(
+                "section {} has a nonzero reserved byte ({:#04x})",
+                i, header.reserved
+            )));
+        }
+        if header.compression().is_none() && header.size != header.csize {
+            return Err(Error::Corruption(format!(
+                "section {} is uncompressed but its size ({}) differs from its stored size ({})",
+                i, header.size, header.csize
+            )));
+        }
+        if header.pointer < table_end {
+            return Err(Error::Corruption(format!(
+                "section {} data ({}) starts inside the section header table (..{})",
+                i, header.pointer, table_end
+            )));
+        }
+    }
+    // A section header right after the table means section_num is too small.
+    let data_start = sections
+        .iter()
+        .filter(|v| v.csize > 0)
+        .map(|v| v.pointer)
+        .min()
+        .unwrap_or(main_header.file_size);
+    if data_start - table_end >= SECTION_HEADER_SIZE as u64 {
+        let mut buf: [u8; SECTION_HEADER_SIZE] = [0; SECTION_HEADER_SIZE];
+        file.seek(io::SeekFrom::Start(table_end))?;
+        file.read_exact(&mut buf)?;
+        if let Ok(header) = SectionHeader::from_bytes(&buf) {
+            let end = header.pointer.checked_add(header.csize as u64);
+            let in_file = end.map(|v| v <= main_header.file_size).unwrap_or(false);
+            if header.csize > 0 && header.pointer >= table_end && in_file {
+                return Err(Error::Corruption(format!(
+                    "section_num ({}) is less than the number of section headers",
+                    main_header.section_num
+                )));
+            }
         }
-    } else {
-        let mut chksum = WeakChecksum::new();
-        load_section_checked(file, &section, &mut data, &mut chksum)?;
     }
+    let data_end = sections
+        .iter()
+        .map(|v| v.pointer + v.csize as u64)
+        .max()
+        .unwrap_or(0)
+        .max(table_end);
+    if data_end != main_header.file_size {
+        return Err(Error::Corruption(format!(
+            "file_size ({}) does not match the end of the section data ({})",
+            main_header.file_size, data_end
+        )));
+    }
+    return Ok(());
+}
+
+/// Returns the number of bytes of the BPX in `file`, including its integrity
+/// footer if any, and the number of bytes following it.
+fn measure_stream<TBackend: IoBackend>(file: &mut TBackend, file_size: u64) -> Result<(u64, u64)>
+{
+    let end = file.seek(io::SeekFrom::End(0))?;
+    let mut consumed = file_size;
+    if end.saturating_sub(file_size) >= DIGEST_FOOTER_SIZE as u64 {
+        let mut signature = [0; 4];
+        file.seek(io::SeekFrom::Start(file_size))?;
+        file.read_exact(&mut signature)?;
+        if signature == DIGEST_FOOTER_SIGNATURE {
+            consumed += DIGEST_FOOTER_SIZE as u64;
+        }
+    }
+    return Ok((consumed, end.saturating_sub(consumed)));
+}
+
+/// Where to report the events of loading one section.
+#[derive(Copy, Clone)]
+struct Probe<'a>
+{
+    instrument: &'a dyn Instrumentation,
+    section: u32
+}
+
+impl<'a> Probe<'a>
+{
+    fn new(instrument: Option<&'a dyn Instrumentation>, index: usize) -> Option<Probe<'a>>
+    {
+        return instrument.map(|instrument| Probe {
+            instrument,
+            section: index as u32
+        });
+    }
+}
+
+/// Runs `load`, which returns the time spent computing the checksum when passed true, and reports
+/// its events if `probe` is set.
+fn measure_load<T, F: FnOnce(bool) -> Result<(T, Duration)>>(
+    probe: Option<Probe>,
+    section: &SectionHeader,
+    backend: bool,
+    load: F
+) -> Result<T>
+{
+    let probe = match probe {
+        Some(v) => v,
+        None => return Ok(load(false)?.0)
+    };
+    probe.instrument.on_event(Event::DecompressStart {
+        section: probe.section,
+        csize: section.csize
+    });
+    let start = Instant::now();
+    let (value, elapsed) = load(true)?;
+    if backend {
+        probe.instrument.on_event(Event::BackendRead {
+            bytes: section.csize as u64
+        });
+    }
+    if section.checksum().is_some() {
+        probe.instrument.on_event(Event::Checksum {
+            section: probe.section,
+            bytes: section.size as usize,
+            elapsed
+        });
+    }
+    probe.instrument.on_event(Event::DecompressEnd {
+        section: probe.section,
+        size: section.size,
+        elapsed: start.elapsed()
+    });
+    return Ok(value);
+}
+
+pub(crate) fn load_section<TBackend: IoBackend>(file: &mut TBackend, section: &SectionHeader) -> Result<Box<dyn SectionData>>
+{
+    let data = new_section_data(Some(section.size))?;
+    return Ok(load_section_into(file, section, data, false)?.0);
+}
+
+fn load_section_budgeted<TBackend: IoBackend>(
+    file: &mut TBackend,
+    section: &SectionHeader,
+    budget: &MemoryBudget,
+    storage: &TempStorage,
+    probe: Option<Probe>
+) -> Result<Box<dyn SectionData>>
+{
+    let (data, spilled) = new_budgeted_section_data(section.size, budget, storage)?;
+    if let Some(probe) = probe.filter(|_| spilled) {
+        probe.instrument.on_event(Event::Spill {
+            section: probe.section,
+            size: Some(section.size)
+        });
+    }
+    return measure_load(probe, section, true, |timed| load_section_into(file, section, data, timed));
+}
+
+fn load_section_into<TBackend: IoBackend>(
+    file: &mut TBackend,
+    section: &SectionHeader,
+    mut data: Box<dyn SectionData>,
+    timed: bool
+) -> Result<(Box<dyn SectionData>, Duration)>
+{
+    data.seek(io::SeekFrom::Start(0))?;
+    let elapsed = load_section_verified(file, section, &mut data, timed)?;
     data.seek(io::SeekFrom::Start(0))?;
-    return Ok(data);
+    return Ok((data, elapsed));
+}
+
+/// Loads and verifies a section, returns the time spent computing its checksum, zero if `timed` is false.
+fn load_section_verified<TBackend: IoBackend, TWrite: Write>(
+    file: &mut TBackend,
+    section: &SectionHeader,
+    out: &mut TWrite,
+    timed: bool
+) -> Result<Duration>
+{
+    match section.checksum() {
+        Some(builder::Checksum::Weak) => {
+            let mut chksum = TimedChecksum::new(WeakChecksum::new(), timed);
+            load_section_checked(file, section, out, &mut chksum)?;
+            let elapsed = chksum.elapsed();
+            let v = chksum.finish();
+            if v != section.chksum {
+                return Err(Error::Checksum(v, section.chksum));
+            }
+            return Ok(elapsed);
+        },
+        Some(builder::Checksum::Crc32) => {
+            let mut chksum = TimedChecksum::new(Crc32Checksum::new(), timed);
+            load_section_checked(file, section, out, &mut chksum)?;
+            let elapsed = chksum.elapsed();
+            let v = chksum.finish();
+            if v != section.chksum {
+                return Err(Error::Checksum(v, section.chksum));
+            }
+            return Ok(elapsed);
+        },
+        None => {
+            load_section_checked(file, section, out, &mut NoChecksum)?;
+            return Ok(Duration::ZERO);
+        }
+    }
+}
+
+/// Reads the stored bytes of a section.
+fn read_stored_section<TBackend: IoBackend>(file: &mut TBackend, section: &SectionHeader, blob: &mut [u8]) -> Result<()>
+{
+    file.seek(io::SeekFrom::Start(section.pointer))?;
+    file.read_exact(blob).map_err(|_| Error::Truncation("load section"))?;
+    return Ok(());
+}
+
+/// Inflates and verifies a section from its stored bytes.
+fn load_section_from_blob(blob: &[u8], section: &SectionHeader, probe: Option<Probe>) -> Result<Vec<u8>>
+{
+    let mut header = *section;
+    header.pointer = 0;
+    return measure_load(probe, section, false, |timed| {
+        let mut data = Vec::with_capacity(section.size as usize);
+        let elapsed = load_section_verified(&mut Cursor::new(blob), &header, &mut data, timed)?;
+        Ok((data, elapsed))
+    });
+}
+
+/// Returns the bytes of an uncompressed section after verifying its checksum.
+fn section_slice<'a>(data: &'a [u8], section: &SectionHeader) -> Result<&'a [u8]>
+{
+    let bytes = usize::try_from(section.pointer)
+        .ok()
+        .and_then(|start| data.get(start..)?.get(..section.size as usize))
+        .ok_or(Error::Truncation("load section"))?;
+    let v = match section.checksum() {
+        Some(builder::Checksum::Weak) => {
+            let mut chksum = WeakChecksum::new();
+            chksum.push(bytes);
+            chksum.finish()
+        },
+        Some(builder::Checksum::Crc32) => {
+            let mut chksum = Crc32Checksum::new();
+            chksum.push(bytes);
+            chksum.finish()
+        },
+        None => return Ok(bytes)
+    };
+    if v != section.chksum {
+        return Err(Error::Checksum(v, section.chksum));
+    }
+    return Ok(bytes);
+}
+
+/// Returns a view over an uncompressed section of a shared buffer after verifying its checksum.
+fn shared_section(bytes: &Arc<[u8]>, section: &SectionHeader) -> Result<Box<dyn SectionData>>
+{
+    let len = section_slice(bytes, section)?.len();
+    let start = section.pointer as usize;
+    return Ok(Box::new(SharedSection::new(bytes.clone(), start..start + len)));
 }
 
 fn load_section_checked<TBackend: io::Read + io::Seek, TWrite: Write, TChecksum: Checksum>(
@@ -201,12 +2080,18 @@ fn load_section_checked<TBackend: io::Read + io::Seek, TWrite: Write, TChecksum:
     chksum: &mut TChecksum
 ) -> Result<()>
 {
-    if section.flags & FLAG_COMPRESS_XZ != 0 {
-        load_section_compressed::<XzCompressionMethod, _, _, _>(file, &section, out, chksum)?;
-    } else if section.flags & FLAG_COMPRESS_ZLIB != 0 {
-        load_section_compressed::<ZlibCompressionMethod, _, _, _>(file, &section, out, chksum)?;
-    } else {
-        load_section_uncompressed(file, &section, out, chksum)?;
+    if section.size == 0 {
+        // Nothing to inflate, even if the writer ran a compressor over an empty section.
+        return Ok(());
+    }
+    match section.compression() {
+        Some(builder::CompressionMethod::Xz) => {
+            load_section_compressed::<XzCompressionMethod, _, _, _>(file, section, out, chksum)?
+        },
+        Some(builder::CompressionMethod::Zlib) => {
+            load_section_compressed::<ZlibCompressionMethod, _, _, _>(file, section, out, chksum)?
+        },
+        None => load_section_uncompressed(file, section, out, chksum)?
     }
     return Ok(());
 }
@@ -216,7 +2101,7 @@ fn load_section_uncompressed<TBackend: io::Read + io::Seek, TWrite: Write, TChec
     header: &SectionHeader,
     output: &mut TWrite,
     chksum: &mut TChecksum
-) -> io::Result<()>
+) -> Result<()>
 {
     let mut idata: [u8; READ_BLOCK_SIZE] = [0; READ_BLOCK_SIZE];
     let mut count: usize = 0;
@@ -225,7 +2110,10 @@ fn load_section_uncompressed<TBackend: io::Read + io::Seek, TWrite: Write, TChec
     bpx.seek(io::SeekFrom::Start(header.pointer))?;
     while count < header.size as usize {
         let res = bpx.read(&mut idata[0..std::cmp::min(READ_BLOCK_SIZE, remaining)])?;
-        output.write(&idata[0..res])?;
+        if res == 0 {
+            return Err(Error::Truncation("load section"));
+        }
+        output.write_all(&idata[0..res])?;
         chksum.push(&idata[0..res]);
         count += res;
         remaining -= res;
@@ -241,6 +2129,6 @@ fn load_section_compressed<TMethod: Inflater, TBackend: io::Read + io::Seek, TWr
 ) -> Result<()>
 {
     bpx.seek(io::SeekFrom::Start(header.pointer))?;
-    XzCompressionMethod::inflate(bpx, output, header.csize as usize, chksum)?;
+    TMethod::inflate(bpx, output, header.csize as usize, header.size as usize, chksum)?;
     return Ok(());
 }
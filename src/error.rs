@@ -27,13 +27,26 @@
 // SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 //! Error declarations.
+//!
+//! Errors specific to a single module are declared in that module
+//! ([sd::Error](crate::sd::Error), [strings::Error](crate::strings::Error) and
+//! [header::Error](crate::header::Error)) and are wrapped by the umbrella
+//! [Error](self::Error) returned by all functions of this library. The
+//! compression module is private, so its error is re-exported here as
+//! [CompressionError].
 
 use std::{
     convert::From,
     fmt::{Display, Formatter},
+    io::ErrorKind,
+    path::PathBuf,
     string::String
 };
 
+use crate::{header, sd, strings, validate::ValidationIssue, Interface, SectionHandle};
+
+pub use crate::compression::Error as CompressionError;
+
 /// Represents a BPX error
 #[derive(Debug)]
 pub enum Error
@@ -51,26 +64,6 @@ pub enum Error
     /// * the error that occured.
     Io(std::io::Error),
 
-    /// Describes a variant conversion error (Structured Data).
-    ///
-    /// # Arguments
-    /// * expected variant name.
-    /// * actual variant name.
-    TypeError(&'static str, &'static str),
-
-    /// Describes too many props or values attempted to be written as part of
-    /// an Object or Array (Structured Data) (ie exceeds 255).
-    ///
-    /// # Arguments
-    /// * actual count of props.
-    PropCountExceeded(usize),
-
-    /// Describes a missing property in an object (Structured Data).
-    ///
-    /// # Arguments
-    /// * name of missing prop.
-    MissingProp(&'static str),
-
     /// Describes a data truncation error, this means a section or
     /// the file itself has been truncated.
     ///
@@ -104,17 +97,107 @@ pub enum Error
     /// * actual size of section.
     Capacity(usize),
 
-    /// Describes a compression error.
+    /// Describes a BPX with more sections than a section header table can
+    /// describe (ie more than 2 pow 32 - 1).
+    ///
+    /// # Arguments
+    /// * number of sections.
+    SectionCount(usize),
+
+    /// Describes an allocation refused by the
+    /// [MemoryBudget](crate::section::MemoryBudget) of a decoder.
+    ///
+    /// # Arguments
+    /// * requested number of bytes.
+    /// * number of bytes still available in the budget.
+    MemoryBudget(usize, usize),
+
+    /// Describes a temporary file which could not be created to store section
+    /// data, when keeping the data in memory is not possible either (see
+    /// [TempStorage](crate::section::TempStorage)).
+    TempFile
+    {
+        /// The directory the temporary file was created in.
+        dir: PathBuf,
+
+        /// The error returned when creating the file.
+        source: std::io::Error
+    },
+
+    /// Describes an error in a BPX Main Header or Section Header.
+    ///
+    /// # Arguments
+    /// * the [header::Error](crate::header::Error) that occured.
+    Header(header::Error),
+
+    /// Describes a compression or decompression error.
+    ///
+    /// # Arguments
+    /// * the [CompressionError] that occured.
+    Compression(CompressionError),
+
+    /// Describes a Structured Data error.
+    ///
+    /// # Arguments
+    /// * the [sd::Error](crate::sd::Error) that occured.
+    Sd(sd::Error),
+
+    /// Describes a string section error.
     ///
     /// # Arguments
-    /// * error description string.
-    Deflate(&'static str),
+    /// * the [strings::Error](crate::strings::Error) that occured.
+    Strings(strings::Error),
+
+    /// Describes a source whose size changed while it was being packed.
+    ///
+    /// # Arguments
+    /// * name of the object being packed.
+    /// * expected size in bytes.
+    /// * number of bytes actually read.
+    SourceChanged(String, u64, u64),
+
+    /// Describes a section handle which does not belong to the BPX it is used with.
+    ///
+    /// # Arguments
+    /// * index of the section the handle points to.
+    InvalidHandle(usize),
+
+    /// Describes a section required by a BPX variant which does not exist.
+    MissingSection
+    {
+        /// Type byte of the missing section.
+        expected: u8,
+
+        /// Type byte and number of the sections which exist, sorted by type byte.
+        found: Vec<(u8, u32)>
+    },
 
-    /// Describes a decompression error.
+    /// Describes a base package referenced by an overlay package which could not be opened,
+    /// see [reference](crate::variant::package::reference).
     ///
     /// # Arguments
-    /// * error description string.
-    Inflate(&'static str),
+    /// * build identifier of the base package.
+    UnresolvedBase(u64),
+
+    /// Describes an error raised while processing a section.
+    Section
+    {
+        /// Index of the section.
+        index: u32,
+
+        /// Type byte of the section.
+        btype: u8,
+
+        /// The error that occured.
+        source: Box<Error>
+    },
+
+    /// Describes a BPX which was not saved because it did not pass validation,
+    /// see [save_checked](crate::encoder::Encoder::save_checked).
+    ///
+    /// # Arguments
+    /// * all issues found, including warnings.
+    Validation(Vec<ValidationIssue>),
 
     /// Describes a generic unknown error.
     ///
@@ -123,6 +206,229 @@ pub enum Error
     Other(String)
 }
 
+impl Error
+{
+    /// Attaches section context to this error.
+    ///
+    /// *If this error already carries section context, it is returned
+    /// unchanged so that the innermost section is reported.*
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: the index of the section.
+    /// * `btype`: the type byte of the section.
+    ///
+    /// returns: Error
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::error::Error;
+    ///
+    /// let err = Error::Truncation("read object table").with_section(3, 0x50);
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "section #3 (type 0x50): unexpected EOF while reading read object table, are you sure the data is not truncated?"
+    /// );
+    /// ```
+    pub fn with_section(self, index: u32, btype: u8) -> Error
+    {
+        if let Error::Section { .. } = self {
+            return self;
+        }
+        return Error::Section {
+            index,
+            btype,
+            source: Box::new(self)
+        };
+    }
+
+    /// Returns the error wrapped by any section context.
+    pub(crate) fn without_context(&self) -> &Error
+    {
+        let mut err = self;
+        while let Error::Section { source, .. } = err {
+            err = source.as_ref();
+        }
+        return err;
+    }
+
+    /// Checks if this error describes malformed or truncated data.
+    ///
+    /// *Checksum mismatches are reported by
+    /// [is_checksum_mismatch](Error::is_checksum_mismatch) instead.*
+    ///
+    /// returns: bool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::error::Error;
+    /// use bpx::header;
+    ///
+    /// assert!(Error::Truncation("read header").is_corruption());
+    /// assert!(Error::from(header::Error::BadSignature).is_corruption());
+    /// assert!(!Error::Capacity(0).is_corruption());
+    /// ```
+    pub fn is_corruption(&self) -> bool
+    {
+        return match self.without_context() {
+            Error::Truncation(_) | Error::Corruption(_) | Error::Utf8(_) | Error::MissingSection { .. } => true,
+            Error::Header(e) => matches!(
+                e,
+                header::Error::BadSignature | header::Error::Truncation(_) | header::Error::BadFlags(_)
+            ),
+            Error::Compression(e) => matches!(e, CompressionError::Inflate(_)),
+            Error::Sd(e) => matches!(
+                e,
+                sd::Error::Truncation(_) | sd::Error::Utf8(_) | sd::Error::Corruption(_) | sd::Error::MaxDepthExceeded(_)
+            ),
+            Error::Strings(e) => matches!(e, strings::Error::Truncation(_) | strings::Error::Utf8(_)),
+            _ => false
+        };
+    }
+
+    /// Checks if this error describes a checksum which does not match the data.
+    ///
+    /// returns: bool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::error::Error;
+    ///
+    /// assert!(Error::Checksum(1, 2).is_checksum_mismatch());
+    /// assert!(Error::Checksum(1, 2).with_section(0, 0xFF).is_checksum_mismatch());
+    /// ```
+    pub fn is_checksum_mismatch(&self) -> bool
+    {
+        return matches!(self.without_context(), Error::Checksum(_, _));
+    }
+
+    /// Checks if this error describes a BPX or BPX variant version which is not supported.
+    ///
+    /// returns: bool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::error::Error;
+    /// use bpx::header;
+    ///
+    /// assert!(Error::from(header::Error::BadVersion(42)).is_unsupported_version());
+    /// assert!(!Error::Unsupported(String::from("test")).is_unsupported_version());
+    /// ```
+    pub fn is_unsupported_version(&self) -> bool
+    {
+        return matches!(
+            self.without_context(),
+            Error::Header(header::Error::BadVersion(_)) | Error::Header(header::Error::BadVariantVersion(_, _, _))
+        );
+    }
+
+    /// Checks if this error describes a section, string, Structured Data value
+    /// or number of sections exceeding the capacity allowed by BPX, or an
+    /// allocation exceeding a memory budget.
+    ///
+    /// returns: bool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::error::Error;
+    /// use bpx::sd;
+    ///
+    /// assert!(Error::Capacity(usize::MAX).is_capacity());
+    /// assert!(Error::SectionCount(usize::MAX).is_capacity());
+    /// assert!(Error::MemoryBudget(64, 0).is_capacity());
+    /// assert!(Error::from(sd::Error::PropCountExceeded(256)).is_capacity());
+    /// ```
+    pub fn is_capacity(&self) -> bool
+    {
+        return matches!(
+            self.without_context(),
+            Error::Capacity(_)
+                | Error::SectionCount(_)
+                | Error::MemoryBudget(_, _)
+                | Error::Sd(sd::Error::PropCountExceeded(_))
+                | Error::Strings(strings::Error::Capacity(_, _))
+        );
+    }
+
+    /// Returns the underlying io error if this error was caused by the io backend.
+    ///
+    /// returns: Option<&std::io::Error>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::ErrorKind;
+    /// use bpx::error::Error;
+    ///
+    /// let err = Error::from(std::io::Error::from(ErrorKind::NotFound)).with_section(0, 0xFF);
+    /// assert_eq!(err.as_io().map(|e| e.kind()), Some(ErrorKind::NotFound));
+    /// assert!(Error::Checksum(1, 2).as_io().is_none());
+    /// ```
+    pub fn as_io(&self) -> Option<&std::io::Error>
+    {
+        return match self.without_context() {
+            Error::Io(e) => Some(e),
+            _ => None
+        };
+    }
+
+    /// Returns the io error kind closest to this error.
+    fn io_kind(&self) -> ErrorKind
+    {
+        if let Some(e) = self.as_io() {
+            return e.kind();
+        }
+        let err = self.without_context();
+        if let Error::Truncation(_)
+        | Error::Header(header::Error::Truncation(_))
+        | Error::Sd(sd::Error::Truncation(_))
+        | Error::Strings(strings::Error::Truncation(_)) = err
+        {
+            return ErrorKind::UnexpectedEof;
+        }
+        if err.is_corruption() || err.is_checksum_mismatch() {
+            return ErrorKind::InvalidData;
+        }
+        if err.is_unsupported_version() {
+            return ErrorKind::Unsupported;
+        }
+        return match err {
+            Error::Unsupported(_) => ErrorKind::Unsupported,
+            Error::MemoryBudget(_, _) => ErrorKind::OutOfMemory,
+            Error::TempFile { source, .. } => source.kind(),
+            Error::UnresolvedBase(_) => ErrorKind::NotFound,
+            Error::Capacity(_)
+            | Error::SectionCount(_)
+            | Error::InvalidHandle(_)
+            | Error::Validation(_)
+            | Error::Header(_)
+            | Error::Sd(_)
+            | Error::Strings(_) => ErrorKind::InvalidInput,
+            _ => ErrorKind::Other
+        };
+    }
+}
+
+/// Returns a function attaching the context of a section to an error.
+pub(crate) fn section_context<TInterface: Interface + ?Sized>(
+    interface: &TInterface,
+    handle: SectionHandle
+) -> impl Fn(Error) -> Error
+{
+    let index = interface.get_section_index(handle);
+    let btype = interface.try_get_section_header(handle).map(|v| v.btype);
+    return move |e| match btype {
+        Some(btype) => e.with_section(index, btype),
+        // Invalid handles are reported by the operation itself.
+        None => e
+    };
+}
+
 impl From<std::io::Error> for Error
 {
     fn from(e: std::io::Error) -> Self
@@ -131,6 +437,38 @@ impl From<std::io::Error> for Error
     }
 }
 
+impl From<header::Error> for Error
+{
+    fn from(e: header::Error) -> Self
+    {
+        return Error::Header(e);
+    }
+}
+
+impl From<CompressionError> for Error
+{
+    fn from(e: CompressionError) -> Self
+    {
+        return Error::Compression(e);
+    }
+}
+
+impl From<sd::Error> for Error
+{
+    fn from(e: sd::Error) -> Self
+    {
+        return Error::Sd(e);
+    }
+}
+
+impl From<strings::Error> for Error
+{
+    fn from(e: strings::Error) -> Self
+    {
+        return Error::Strings(e);
+    }
+}
+
 impl From<&str> for Error
 {
     fn from(e: &str) -> Self
@@ -149,11 +487,6 @@ impl Display for Error
                 expected, actual
             )),
             Error::Io(e) => f.write_str(&format!("io error ({})", e)),
-            Error::TypeError(expected, actual) => {
-                f.write_str(&format!("incompatible types (expected {}, got {})", expected, actual))
-            },
-            Error::PropCountExceeded(v) => f.write_str(&format!("BPXSD - too many props (count {}, max is 256)", v)),
-            Error::MissingProp(v) => f.write_str(&format!("BPXSD - missing property {}", v)),
             Error::Truncation(e) => f.write_str(&format!(
                 "unexpected EOF while reading {}, are you sure the data is not truncated?",
                 e
@@ -165,9 +498,88 @@ impl Display for Error
                 "section capacity exceeded (found {} bytes, max is 2 pow 32 bytes)",
                 e
             )),
-            Error::Deflate(e) => f.write_str(&format!("deflate error ({})", e)),
-            Error::Inflate(e) => f.write_str(&format!("inflate error ({})", e)),
-            Error::Other(e) => f.write_str(&format!("{}", e))
+            Error::SectionCount(e) => f.write_str(&format!(
+                "section count exceeded (found {} sections, max is 2 pow 32 - 1)",
+                e
+            )),
+            Error::MemoryBudget(requested, available) => f.write_str(&format!(
+                "memory budget exceeded (requested {} bytes, {} available)",
+                requested, available
+            )),
+            Error::TempFile { dir, source } => f.write_str(&format!(
+                "could not create a temporary file in '{}' ({}), use TempStorage::in_memory() \
+                to keep section data in memory",
+                dir.display(),
+                source
+            )),
+            Error::SourceChanged(name, expected, actual) => f.write_str(&format!(
+                "source of '{}' changed while packing (expected {} bytes, read {})",
+                name, expected, actual
+            )),
+            Error::InvalidHandle(index) => f.write_str(&format!(
+                "invalid section handle (section #{} does not exist in this BPX)",
+                index
+            )),
+            Error::MissingSection { expected, found } => {
+                let found: Vec<String> = found
+                    .iter()
+                    .map(|(btype, count)| format!("{:#04x} x{}", btype, count))
+                    .collect();
+                let found = if found.is_empty() { String::from("no section") } else { found.join(", ") };
+                f.write_str(&format!(
+                    "missing required section of type {:#04x} (found: {}), was this BPX created \
+                    by the encoder of its variant?",
+                    expected, found
+                ))
+            },
+            Error::UnresolvedBase(base) => f.write_str(&format!(
+                "base package {:016x} could not be resolved, it stores sections referenced by this package",
+                base
+            )),
+            Error::Validation(issues) => {
+                let errors: Vec<String> = issues.iter().filter(|v| v.is_error()).map(|v| v.to_string()).collect();
+                f.write_str(&format!("validation failed ({})", errors.join("; ")))
+            },
+            Error::Header(e) => e.fmt(f),
+            Error::Compression(e) => e.fmt(f),
+            Error::Sd(e) => e.fmt(f),
+            Error::Strings(e) => e.fmt(f),
+            Error::Section { index, btype, source } => {
+                f.write_str(&format!("section #{} (type {:#04x}): {}", index, btype, source))
+            },
+            Error::Other(e) => f.write_str(e)
+        };
+    }
+}
+
+impl std::error::Error for Error
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+    {
+        return match self {
+            Error::Io(e) => Some(e),
+            Error::Header(e) => Some(e),
+            Error::Compression(e) => Some(e),
+            Error::Sd(e) => Some(e),
+            Error::Strings(e) => Some(e),
+            Error::Section { source, .. } => Some(source.as_ref()),
+            Error::TempFile { source, .. } => Some(source),
+            _ => None
         };
     }
 }
+
+/// Converts a BPX error to an io error, mapping it to the closest
+/// [ErrorKind](std::io::ErrorKind).
+///
+/// *Io errors without section context are returned unchanged.*
+impl From<Error> for std::io::Error
+{
+    fn from(e: Error) -> Self
+    {
+        if let Error::Io(e) = e {
+            return e;
+        }
+        return std::io::Error::new(e.io_kind(), e);
+    }
+}
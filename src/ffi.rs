@@ -0,0 +1,523 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! C bindings for decoding BPX files and BPX packages.
+//!
+//! All functions return one of the `BPX_*` status codes. When a function
+//! fails, the code and a description of the error can be retrieved with
+//! [bpx_last_error_code](self::bpx_last_error_code) and
+//! [bpx_last_error_message](self::bpx_last_error_message) on the same thread.
+//!
+//! Decoders and packages are opaque pointers which must be released with
+//! [bpx_decoder_free](self::bpx_decoder_free) and
+//! [bpx_package_free](self::bpx_package_free) respectively. The matching C
+//! declarations are available in `include/bpx.h`.
+//!
+//! *To build a shared library, use `cargo rustc --release --features ffi --crate-type cdylib`.*
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    fs::File,
+    io::{BufReader, Cursor, Read, Seek},
+    os::raw::{c_char, c_int},
+    path::Path,
+    ptr,
+    slice
+};
+
+use crate::{
+    decoder::Decoder,
+    error::Error,
+    variant::package::{object::ObjectHeader, utils::unpack_file, PackageDecoder},
+    Interface
+};
+
+/// The operation succeeded.
+pub const BPX_OK: c_int = 0;
+
+/// A null pointer, an invalid string or an out of range index was given.
+pub const BPX_ERR_INVALID_ARGUMENT: c_int = 1;
+
+/// An IO error occured.
+pub const BPX_ERR_IO: c_int = 2;
+
+/// The data is malformed or truncated.
+pub const BPX_ERR_CORRUPTION: c_int = 3;
+
+/// A checksum does not match the data.
+pub const BPX_ERR_CHECKSUM: c_int = 4;
+
+/// The file uses a version or feature which is not supported.
+pub const BPX_ERR_UNSUPPORTED: c_int = 5;
+
+/// A section or value exceeds the capacity allowed by BPX.
+pub const BPX_ERR_CAPACITY: c_int = 6;
+
+/// No package entry exists with the given name.
+pub const BPX_ERR_NOT_FOUND: c_int = 7;
+
+/// The buffer given by the caller is too small, the required size has been written.
+pub const BPX_ERR_BUFFER_TOO_SMALL: c_int = 8;
+
+/// Any other error.
+pub const BPX_ERR_OTHER: c_int = 255;
+
+thread_local! {
+    static LAST_ERROR: RefCell<(c_int, CString)> = RefCell::new((BPX_OK, CString::default()));
+}
+
+trait Source: Read + Seek {}
+impl<T: Read + Seek> Source for T {}
+
+type Backend = Box<dyn Source>;
+
+/// Opaque BPX decoder.
+pub struct BpxDecoder(Decoder<Backend>);
+
+/// Opaque BPX package decoder.
+pub struct BpxPackage
+{
+    decoder: Decoder<Backend>,
+    objects: Vec<ObjectHeader>,
+    names: Vec<CString>
+}
+
+/// Information about a section.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BpxSectionInfo
+{
+    /// Index of the section.
+    pub index: u32,
+
+    /// Type byte of the section.
+    pub btype: u8,
+
+    /// Flags of the section.
+    pub flags: u8,
+
+    /// Size of the section once decompressed.
+    pub size: u32,
+
+    /// Size of the section as stored in the file.
+    pub csize: u32
+}
+
+fn set_last_error(code: c_int, message: String) -> c_int
+{
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = (code, message));
+    return code;
+}
+
+fn error_code(e: &Error) -> c_int
+{
+    if e.as_io().is_some() {
+        return BPX_ERR_IO;
+    }
+    if e.is_checksum_mismatch() {
+        return BPX_ERR_CHECKSUM;
+    }
+    if e.is_corruption() {
+        return BPX_ERR_CORRUPTION;
+    }
+    if e.is_capacity() {
+        return BPX_ERR_CAPACITY;
+    }
+    if e.is_unsupported_version() {
+        return BPX_ERR_UNSUPPORTED;
+    }
+    if let Error::Unsupported(_) = e {
+        return BPX_ERR_UNSUPPORTED;
+    }
+    if let Error::InvalidHandle(_) = e {
+        return BPX_ERR_INVALID_ARGUMENT;
+    }
+    return BPX_ERR_OTHER;
+}
+
+fn report(e: Error) -> c_int
+{
+    return set_last_error(error_code(&e), e.to_string());
+}
+
+fn invalid_argument(message: &str) -> c_int
+{
+    return set_last_error(BPX_ERR_INVALID_ARGUMENT, String::from(message));
+}
+
+fn open_decoder(backend: Backend, out: *mut *mut BpxDecoder) -> c_int
+{
+    return match Decoder::new(backend) {
+        Ok(decoder) => {
+            // SAFETY: the caller guarantees that `out` is valid for writes.
+            unsafe { *out = Box::into_raw(Box::new(BpxDecoder(decoder))) };
+            BPX_OK
+        },
+        Err(e) => report(e)
+    };
+}
+
+/// Returns the code of the last error that occured on the calling thread.
+#[no_mangle]
+pub extern "C" fn bpx_last_error_code() -> c_int
+{
+    return LAST_ERROR.with(|e| e.borrow().0);
+}
+
+/// Returns the message of the last error that occured on the calling thread.
+///
+/// *The returned string is valid until the next failing call on the calling thread.*
+#[no_mangle]
+pub extern "C" fn bpx_last_error_message() -> *const c_char
+{
+    return LAST_ERROR.with(|e| e.borrow().1.as_ptr());
+}
+
+/// Opens a BPX decoder on a file.
+///
+/// # Safety
+///
+/// `path` must be a valid null-terminated string and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bpx_decoder_open_file(path: *const c_char, out: *mut *mut BpxDecoder) -> c_int
+{
+    if path.is_null() || out.is_null() {
+        return invalid_argument("null pointer");
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(v) => v,
+        Err(_) => return invalid_argument("path is not valid UTF-8")
+    };
+    return match File::open(Path::new(path)) {
+        Ok(file) => open_decoder(Box::new(BufReader::new(file)), out),
+        Err(e) => report(e.into())
+    };
+}
+
+/// Opens a BPX decoder on a memory buffer.
+///
+/// *The buffer is copied, it can be released as soon as this function returns.*
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bpx_decoder_open_memory(data: *const u8, len: usize, out: *mut *mut BpxDecoder) -> c_int
+{
+    if data.is_null() || out.is_null() {
+        return invalid_argument("null pointer");
+    }
+    let buf = slice::from_raw_parts(data, len).to_vec();
+    return open_decoder(Box::new(Cursor::new(buf)), out);
+}
+
+/// Releases a BPX decoder.
+///
+/// # Safety
+///
+/// `decoder` must be null or a pointer returned by a `bpx_decoder_open_*` function
+/// which has not already been released.
+#[no_mangle]
+pub unsafe extern "C" fn bpx_decoder_free(decoder: *mut BpxDecoder)
+{
+    if !decoder.is_null() {
+        drop(Box::from_raw(decoder));
+    }
+}
+
+/// Returns the number of sections in a BPX.
+///
+/// # Safety
+///
+/// `decoder` must be a valid decoder.
+#[no_mangle]
+pub unsafe extern "C" fn bpx_decoder_section_count(decoder: *const BpxDecoder) -> u32
+{
+    if decoder.is_null() {
+        return 0;
+    }
+    return (*decoder).0.get_main_header().section_num;
+}
+
+/// Reads information about a section.
+///
+/// # Safety
+///
+/// `decoder` must be a valid decoder and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bpx_decoder_section_info(
+    decoder: *const BpxDecoder,
+    index: u32,
+    out: *mut BpxSectionInfo
+) -> c_int
+{
+    if decoder.is_null() || out.is_null() {
+        return invalid_argument("null pointer");
+    }
+    let decoder = &(*decoder).0;
+    let handle = match decoder.find_section_by_index(index) {
+        Some(v) => v,
+        None => return invalid_argument("section index out of range")
+    };
+    let header = decoder.get_section_header(handle);
+    *out = BpxSectionInfo {
+        index,
+        btype: header.btype,
+        flags: header.flags,
+        size: header.size,
+        csize: header.csize
+    };
+    return BPX_OK;
+}
+
+/// Reads the content of a section into a buffer.
+///
+/// *If the buffer is too small, the size of the section is written to `written`
+/// and [BPX_ERR_BUFFER_TOO_SMALL](self::BPX_ERR_BUFFER_TOO_SMALL) is returned.*
+///
+/// # Safety
+///
+/// `decoder` must be a valid decoder, `buf` must be valid for writes of `len`
+/// bytes and `written` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bpx_decoder_read_section(
+    decoder: *mut BpxDecoder,
+    index: u32,
+    buf: *mut u8,
+    len: usize,
+    written: *mut usize
+) -> c_int
+{
+    if decoder.is_null() || written.is_null() || (buf.is_null() && len > 0) {
+        return invalid_argument("null pointer");
+    }
+    let decoder = &mut (*decoder).0;
+    let handle = match decoder.find_section_by_index(index) {
+        Some(v) => v,
+        None => return invalid_argument("section index out of range")
+    };
+    let size = decoder.get_section_header(handle).size as usize;
+    *written = size;
+    if len < size {
+        return set_last_error(BPX_ERR_BUFFER_TOO_SMALL, format!("section needs {} bytes", size));
+    }
+    let data = match decoder.open_section(handle).and_then(|mut v| Ok(v.load_in_memory()?)) {
+        Ok(v) => v,
+        Err(e) => return report(e)
+    };
+    if !data.is_empty() {
+        ptr::copy_nonoverlapping(data.as_ptr(), buf, data.len());
+    }
+    *written = data.len();
+    return BPX_OK;
+}
+
+/// Opens a BPX package from a decoder.
+///
+/// *On success the decoder is owned by the package and must not be used or
+/// released anymore; on failure the decoder is left untouched.*
+///
+/// # Safety
+///
+/// `decoder` must be a valid decoder and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bpx_package_open(decoder: *mut BpxDecoder, out: *mut *mut BpxPackage) -> c_int
+{
+    if decoder.is_null() || out.is_null() {
+        return invalid_argument("null pointer");
+    }
+    let (objects, names) = {
+        let mut package = match PackageDecoder::read(&mut (*decoder).0) {
+            Ok(v) => v,
+            Err(e) => return report(e)
+        };
+        let table = match package.read_object_table() {
+            Ok(v) => v,
+            Err(e) => return report(e)
+        };
+        let mut names = Vec::with_capacity(table.get_objects().len());
+        for obj in table.get_objects() {
+            match package.get_object_name(obj) {
+                Ok(v) => names.push(CString::new(v).unwrap_or_default()),
+                Err(e) => return report(e)
+            }
+        }
+        (table.get_objects().clone(), names)
+    };
+    let decoder = Box::from_raw(decoder).0;
+    *out = Box::into_raw(Box::new(BpxPackage {
+        decoder,
+        objects,
+        names
+    }));
+    return BPX_OK;
+}
+
+/// Releases a BPX package.
+///
+/// # Safety
+///
+/// `package` must be null or a pointer returned by [bpx_package_open](self::bpx_package_open)
+/// which has not already been released.
+#[no_mangle]
+pub unsafe extern "C" fn bpx_package_free(package: *mut BpxPackage)
+{
+    if !package.is_null() {
+        drop(Box::from_raw(package));
+    }
+}
+
+/// Returns the number of entries in a BPX package.
+///
+/// # Safety
+///
+/// `package` must be a valid package.
+#[no_mangle]
+pub unsafe extern "C" fn bpx_package_entry_count(package: *const BpxPackage) -> u32
+{
+    if package.is_null() {
+        return 0;
+    }
+    return (*package).objects.len() as u32;
+}
+
+/// Reads the name and size of a package entry.
+///
+/// *The name is valid until the package is released.*
+///
+/// # Safety
+///
+/// `package` must be a valid package, `name` and `size` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bpx_package_entry(
+    package: *const BpxPackage,
+    index: u32,
+    name: *mut *const c_char,
+    size: *mut u64
+) -> c_int
+{
+    if package.is_null() || name.is_null() || size.is_null() {
+        return invalid_argument("null pointer");
+    }
+    let package = &*package;
+    if index as usize >= package.objects.len() {
+        return invalid_argument("entry index out of range");
+    }
+    *name = package.names[index as usize].as_ptr();
+    *size = package.objects[index as usize].size;
+    return BPX_OK;
+}
+
+unsafe fn find_entry(package: &BpxPackage, name: *const c_char) -> Result<ObjectHeader, c_int>
+{
+    if name.is_null() {
+        return Err(invalid_argument("null pointer"));
+    }
+    let name = CStr::from_ptr(name);
+    return match package.names.iter().position(|v| v.as_c_str() == name) {
+        Some(i) => Ok(package.objects[i]),
+        None => Err(set_last_error(
+            BPX_ERR_NOT_FOUND,
+            format!("no entry named '{}'", name.to_string_lossy())
+        ))
+    };
+}
+
+/// Extracts a package entry into a buffer.
+///
+/// *If the buffer is too small, the size of the entry is written to `written`
+/// and [BPX_ERR_BUFFER_TOO_SMALL](self::BPX_ERR_BUFFER_TOO_SMALL) is returned.*
+///
+/// # Safety
+///
+/// `package` must be a valid package, `name` a valid null-terminated string,
+/// `buf` must be valid for writes of `len` bytes and `written` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bpx_package_extract(
+    package: *mut BpxPackage,
+    name: *const c_char,
+    buf: *mut u8,
+    len: usize,
+    written: *mut usize
+) -> c_int
+{
+    if package.is_null() || written.is_null() || (buf.is_null() && len > 0) {
+        return invalid_argument("null pointer");
+    }
+    let package = &mut *package;
+    let obj = match find_entry(package, name) {
+        Ok(v) => v,
+        Err(code) => return code
+    };
+    *written = obj.size as usize;
+    if (len as u64) < obj.size {
+        return set_last_error(BPX_ERR_BUFFER_TOO_SMALL, format!("entry needs {} bytes", obj.size));
+    }
+    let mut out: &mut [u8] = if len > 0 { slice::from_raw_parts_mut(buf, len) } else { &mut [] };
+    let res = PackageDecoder::read(&mut package.decoder).and_then(|mut v| v.unpack_object(&obj, &mut out));
+    return match res {
+        Ok(count) => {
+            *written = count as usize;
+            BPX_OK
+        },
+        Err(e) => report(e)
+    };
+}
+
+/// Extracts a package entry to a file.
+///
+/// # Safety
+///
+/// `package` must be a valid package, `name` and `path` must be valid null-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn bpx_package_extract_file(
+    package: *mut BpxPackage,
+    name: *const c_char,
+    path: *const c_char
+) -> c_int
+{
+    if package.is_null() || path.is_null() {
+        return invalid_argument("null pointer");
+    }
+    let package = &mut *package;
+    let obj = match find_entry(package, name) {
+        Ok(v) => v,
+        Err(code) => return code
+    };
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(v) => v,
+        Err(_) => return invalid_argument("path is not valid UTF-8")
+    };
+    let res = PackageDecoder::read(&mut package.decoder).and_then(|mut v| unpack_file(&mut v, &obj, Path::new(path)));
+    return match res {
+        Ok(_) => BPX_OK,
+        Err(e) => report(e)
+    };
+}
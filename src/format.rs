@@ -0,0 +1,135 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Sizes and limits of the BPX format.
+//!
+//! *These constants are the ones used by the encoders and decoders of this
+//! crate; they can be used to validate inputs before building a BPX.*
+//!
+//! # Examples
+//!
+//! ```
+//! use bpx::encoder::Encoder;
+//! use bpx::format::{MAIN_HEADER_SIZE, SECTION_HEADER_SIZE};
+//! use bpx::header::SectionHeader;
+//!
+//! let mut buf = Vec::new();
+//! let mut encoder = Encoder::new(&mut buf).unwrap();
+//! encoder.create_section(SectionHeader::new()).unwrap();
+//! encoder.save().unwrap();
+//! assert_eq!(buf.len(), MAIN_HEADER_SIZE + SECTION_HEADER_SIZE);
+//! ```
+
+use crate::header::DIGEST_FOOTER_SIGNATURE;
+
+/// The size in bytes of the BPX Main Header.
+pub const MAIN_HEADER_SIZE: usize = 40;
+
+/// The size in bytes of a BPX Section Header.
+pub const SECTION_HEADER_SIZE: usize = 24;
+
+/// The size in bytes of the Extended Type Information field of the BPX Main Header.
+pub const TYPE_EXT_SIZE: usize = 16;
+
+/// The size in bytes of the optional integrity footer.
+pub const DIGEST_FOOTER_SIZE: usize = 8;
+
+/// The maximum size in bytes of the content of a section.
+pub const MAX_SECTION_SIZE: u64 = u32::MAX as u64;
+
+// signature, type, checksum, file size, section count, version and type ext.
+const _: () = assert!(MAIN_HEADER_SIZE == 3 + 1 + 4 + 8 + 4 + 4 + TYPE_EXT_SIZE);
+// pointer, csize, size, checksum, type, flags, user tag and 1 reserved byte.
+const _: () = assert!(SECTION_HEADER_SIZE == 8 + 4 + 4 + 4 + 1 + 1 + 1 + 1);
+const _: () = assert!(DIGEST_FOOTER_SIZE == DIGEST_FOOTER_SIGNATURE.len() + 4);
+
+/// Limits of BPXSD objects.
+pub mod sd
+{
+    /// The maximum number of properties in an object.
+    pub const MAX_PROPS: usize = u8::MAX as usize;
+
+    /// The maximum number of values in an array.
+    pub const MAX_ARRAY_LEN: usize = u8::MAX as usize;
+
+    /// The maximum nesting depth of objects and arrays accepted by the decoder,
+    /// protects the stack against untrusted data.
+    pub const MAX_DEPTH: usize = 128;
+}
+
+/// Sizes and limits of the key-value store variant (BPXK).
+pub mod kvstore
+{
+    /// The size in bytes an encoder fills a data section up to before starting a new one.
+    pub const DEFAULT_SPLIT_SIZE: usize = 200000000;
+
+    /// The size in bytes of an entry of the index.
+    pub const INDEX_ENTRY_SIZE: usize = 24;
+
+    /// The size in bytes of the length prefix of a value in a data section.
+    pub const VALUE_PREFIX_SIZE: usize = 4;
+
+    // key hash, key pointer, section index, offset and value size.
+    const _: () = assert!(INDEX_ENTRY_SIZE == 8 + 4 + 4 + 4 + 4);
+}
+
+/// Sizes and limits of the package variant (BPXP).
+pub mod package
+{
+    /// The size in bytes an encoder fills a data section up to before starting a new one.
+    pub const DEFAULT_SPLIT_SIZE: usize = 200000000;
+
+    /// The size in bytes of an entry of the object table.
+    pub const OBJECT_ENTRY_SIZE: usize = 20;
+
+    /// The size in bytes of the content of the summary section.
+    pub const SUMMARY_SIZE: usize = 16;
+
+    /// The size in bytes of the content of a reference section.
+    pub const REFERENCE_SIZE: usize = 16;
+
+    // size, name pointer, start section and offset.
+    const _: () = assert!(OBJECT_ENTRY_SIZE == 8 + 4 + 4 + 4);
+    // file count and total uncompressed size.
+    const _: () = assert!(SUMMARY_SIZE == 8 + 8);
+    // base build identifier, section index and checksum.
+    const _: () = assert!(REFERENCE_SIZE == 8 + 4 + 4);
+}
+
+/// Sizes and limits of the texture variant (BPXT).
+pub mod texture
+{
+    /// The size in bytes an encoder fills a data section up to before starting a new one.
+    pub const DEFAULT_SPLIT_SIZE: usize = 200000000;
+
+    /// The size in bytes of an entry of the mip table.
+    pub const MIP_ENTRY_SIZE: usize = 20;
+
+    // size, level, start section and offset.
+    const _: () = assert!(MIP_ENTRY_SIZE == 8 + 4 + 4 + 4);
+}
@@ -0,0 +1,362 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Panic-free entry points for fuzzers and seed inputs for their corpus.
+//!
+//! *Each entry point walks a full parse path over in-memory input and only
+//! reports malformed input through its result; a panic is always a bug. Use
+//! [write_corpus](self::write_corpus) to seed the targets under `fuzz/`.*
+//!
+//! *This module is only available with the `fuzz` feature.*
+
+use std::{
+    fs::{create_dir_all, File},
+    io::{Cursor, Write},
+    path::Path
+};
+
+use crate::{
+    builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+    decoder::{Decoder, SliceDecoder},
+    encoder::Encoder,
+    error::Error,
+    inspect,
+    sd::{Array, Object, Value},
+    variant::package::{PackageBuilder, PackageDecoder},
+    Interface,
+    Result
+};
+
+/// Sections and objects larger than this are skipped to keep iterations fast.
+pub const MAX_FUZZ_DATA_SIZE: u32 = 1 << 20;
+
+fn keep_first(error: &mut Option<Error>, res: Result<()>)
+{
+    if let Err(e) = res {
+        error.get_or_insert(e);
+    }
+}
+
+fn finish(error: Option<Error>) -> Result<()>
+{
+    return match error {
+        Some(e) => Err(e),
+        None => Ok(())
+    };
+}
+
+/// Decodes a BPX and loads all its sections.
+///
+/// *The input goes through [Decoder](crate::decoder::Decoder), its strict mode,
+/// [SliceDecoder](crate::decoder::SliceDecoder) and [inspect](crate::inspect).*
+///
+/// # Arguments
+///
+/// * `data`: the raw bytes of the BPX.
+///
+/// returns: Result<(), Error>
+///
+/// # Errors
+///
+/// The first [Error](crate::error::Error) encountered, if any.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::fuzz::{decode_seeds, fuzz_decode};
+///
+/// assert!(fuzz_decode(b"BPX").is_err());
+/// for (_, data) in decode_seeds() {
+///     fuzz_decode(&data).unwrap();
+/// }
+/// ```
+pub fn fuzz_decode(data: &[u8]) -> Result<()>
+{
+    let mut error = None;
+    keep_first(&mut error, Decoder::new_strict(Cursor::new(data)).map(|_| ()));
+    let mut decoder = Decoder::new(Cursor::new(data))?;
+    keep_first(&mut error, decoder.verify_file_digest().map(|_| ()));
+    let handles: Vec<_> = decoder.iter_sections().collect();
+    for handle in &handles {
+        let header = decoder.get_section_header(*handle);
+        if header.size > MAX_FUZZ_DATA_SIZE {
+            continue;
+        }
+        let res = decoder.open_section(*handle).and_then(|mut v| Ok(v.load_in_memory()?));
+        keep_first(&mut error, res.map(|_| ()));
+    }
+    keep_first(&mut error, inspect::dump(&mut decoder).map(|_| ()));
+    match SliceDecoder::new(data) {
+        Ok(slice) => {
+            for handle in handles {
+                if slice.get_section_header(handle).size <= MAX_FUZZ_DATA_SIZE {
+                    keep_first(&mut error, slice.section_bytes(handle).map(|_| ()));
+                }
+            }
+        },
+        Err(e) => keep_first(&mut error, Err(e))
+    }
+    return finish(error);
+}
+
+/// Parses a Structured Data object and encodes it back.
+///
+/// # Arguments
+///
+/// * `data`: the raw bytes of the object.
+///
+/// returns: Result<(), Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned if the object could not be parsed.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::fuzz::{fuzz_sd, sd_seeds};
+///
+/// assert!(fuzz_sd(&[1]).is_err());
+/// for (_, data) in sd_seeds() {
+///     fuzz_sd(&data).unwrap();
+/// }
+/// ```
+pub fn fuzz_sd(data: &[u8]) -> Result<()>
+{
+    let obj = Object::read(&mut Cursor::new(data))?;
+    let mut buf = Vec::new();
+    obj.write(&mut buf)?;
+    return Ok(());
+}
+
+/// Decodes a BPX Package and unpacks all its objects.
+///
+/// # Arguments
+///
+/// * `data`: the raw bytes of the package.
+///
+/// returns: Result<(), Error>
+///
+/// # Errors
+///
+/// The first [Error](crate::error::Error) encountered, if any.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::fuzz::{fuzz_package_entries, package_seeds};
+///
+/// assert!(fuzz_package_entries(&[]).is_err());
+/// for (_, data) in package_seeds() {
+///     fuzz_package_entries(&data).unwrap();
+/// }
+/// ```
+pub fn fuzz_package_entries(data: &[u8]) -> Result<()>
+{
+    let mut error = None;
+    let mut decoder = Decoder::new(Cursor::new(data))?;
+    let mut package = PackageDecoder::read(&mut decoder)?;
+    keep_first(&mut error, package.read_metadata().map(|_| ()));
+    keep_first(&mut error, package.summary().map(|_| ()));
+    let mut table = package.read_object_table()?;
+    for obj in table.get_objects().clone() {
+        keep_first(&mut error, package.get_object_name(&obj).map(|_| ()));
+        if obj.size <= MAX_FUZZ_DATA_SIZE as u64 {
+            keep_first(&mut error, package.unpack_object(&obj, &mut std::io::sink()).map(|_| ()));
+        }
+    }
+    keep_first(&mut error, table.build_lookup_table(&mut package));
+    return finish(error);
+}
+
+fn sd_seed_object() -> Object
+{
+    let mut nested = Object::new();
+    nested.set("flag", Value::from(true));
+    let mut arr = Array::new();
+    arr.add(Value::from(-1i8));
+    arr.add(Value::from(2.5f32));
+    arr.add(Value::Null);
+    arr.add(Value::Object(nested.clone()));
+    let mut obj = Object::new();
+    obj.set("u8", Value::from(1u8));
+    obj.set("u16", Value::from(2u16));
+    obj.set("u32", Value::from(3u32));
+    obj.set("u64", Value::from(4u64));
+    obj.set("i8", Value::from(-5i8));
+    obj.set("i16", Value::from(-6i16));
+    obj.set("i32", Value::from(-7i32));
+    obj.set("i64", Value::from(-8i64));
+    obj.set("f32", Value::from(9.5f32));
+    obj.set("f64", Value::from(10.5f64));
+    obj.set("string", Value::from("BPX"));
+    obj.set("array", Value::Array(arr));
+    obj.set("object", Value::Object(nested));
+    return obj;
+}
+
+/// Returns small valid inputs for [fuzz_sd](self::fuzz_sd), named after what they contain.
+pub fn sd_seeds() -> Vec<(String, Vec<u8>)>
+{
+    let mut seeds = Vec::new();
+    for (name, obj) in &[("empty", Object::new()), ("all_types", sd_seed_object())] {
+        let mut buf = Vec::new();
+        // Writing to memory only fails on objects which are too large.
+        if obj.write(&mut buf).is_ok() {
+            seeds.push((name.to_string(), buf));
+        }
+    }
+    return seeds;
+}
+
+fn build_seed(f: impl FnOnce(&mut Encoder<&mut Vec<u8>>) -> Result<()>) -> Result<Vec<u8>>
+{
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf)?;
+        f(&mut encoder)?;
+        encoder.save()?;
+    }
+    return Ok(buf);
+}
+
+fn package_seed(with_metadata: bool) -> Result<Vec<u8>>
+{
+    return build_seed(|encoder| {
+        let mut builder = PackageBuilder::new();
+        if with_metadata {
+            builder = builder.with_metadata(sd_seed_object());
+        }
+        let mut package = builder.build(encoder)?;
+        package.pack_object("first", &mut b"first object".as_ref())?;
+        package.pack_object("second", &mut [0xAB; 300].as_ref())?;
+        return Ok(());
+    });
+}
+
+/// Returns small valid inputs for [fuzz_package_entries](self::fuzz_package_entries),
+/// named after what they contain.
+pub fn package_seeds() -> Vec<(String, Vec<u8>)>
+{
+    let mut seeds = Vec::new();
+    for (name, with_metadata) in &[("package", false), ("package_metadata", true)] {
+        // Building seeds in memory cannot fail.
+        if let Ok(data) = package_seed(*with_metadata) {
+            seeds.push((name.to_string(), data));
+        }
+    }
+    return seeds;
+}
+
+/// Returns small valid inputs for [fuzz_decode](self::fuzz_decode) covering every
+/// combination of section compression and checksum, named after what they contain.
+pub fn decode_seeds() -> Vec<(String, Vec<u8>)>
+{
+    let compressions = [
+        ("raw", None),
+        ("xz", Some(CompressionMethod::Xz)),
+        ("zlib", Some(CompressionMethod::Zlib))
+    ];
+    let checksums = [("nocheck", None), ("weak", Some(Checksum::Weak)), ("crc32", Some(Checksum::Crc32))];
+    let mut seeds = Vec::new();
+    for (compression_name, compression) in &compressions {
+        for (checksum_name, checksum) in &checksums {
+            let seed = build_seed(|encoder| {
+                let mut builder = SectionHeaderBuilder::new();
+                if let Some(v) = compression {
+                    builder = builder.with_compression(*v);
+                }
+                if let Some(v) = checksum {
+                    builder = builder.with_checksum(*v);
+                }
+                let mut header = builder.build();
+                // Compress the data even if it is smaller than the compression threshold.
+                header.csize = 0;
+                let handle = encoder.create_section(header)?;
+                let mut section = encoder.open_section(handle)?;
+                for i in 0..64u8 {
+                    section.write_all(&[i; 4])?;
+                }
+                return Ok(());
+            });
+            if let Ok(data) = seed {
+                seeds.push((format!("{}_{}", compression_name, checksum_name), data));
+            }
+        }
+    }
+    let extra: [(&str, Result<Vec<u8>>); 3] = [
+        ("empty", build_seed(|_| Ok(()))),
+        (
+            "digest",
+            build_seed(|encoder| {
+                encoder.set_file_digest(true);
+                let handle = encoder.create_section(SectionHeaderBuilder::new().build())?;
+                encoder.open_section(handle)?.write_all(b"digest")?;
+                return Ok(());
+            })
+        ),
+        ("package", package_seed(true))
+    ];
+    for (name, seed) in extra {
+        if let Ok(data) = seed {
+            seeds.push((name.to_string(), data));
+        }
+    }
+    return seeds;
+}
+
+/// Writes the seed inputs of every fuzz target to a corpus directory.
+///
+/// *Seeds are written to `<root>/<target>/<name>` for the targets `decode`,
+/// `sd` and `package_entries`.*
+///
+/// # Arguments
+///
+/// * `root`: the corpus directory.
+///
+/// returns: Result<(), Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned if a directory or file could not be created.
+pub fn write_corpus(root: &Path) -> Result<()>
+{
+    let targets = [
+        ("decode", decode_seeds()),
+        ("sd", sd_seeds()),
+        ("package_entries", package_seeds())
+    ];
+    for (target, seeds) in &targets {
+        let dir = root.join(target);
+        create_dir_all(&dir)?;
+        for (name, data) in seeds {
+            File::create(dir.join(name))?.write_all(data)?;
+        }
+    }
+    return Ok(());
+}
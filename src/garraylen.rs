@@ -26,8 +26,6 @@
 // NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
 // SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use std::convert::TryInto;
-
 pub trait GenericArrayLen
 {
     const SIZE: usize;
@@ -45,7 +43,9 @@ impl GenericArrayLen for T3
 
     fn from_array(buf: &[u8]) -> Self::TArray
     {
-        return buf.try_into().unwrap();
+        let mut arr = [0; Self::SIZE];
+        arr.copy_from_slice(buf);
+        return arr;
     }
 }
 
@@ -58,7 +58,9 @@ impl GenericArrayLen for T16
 
     fn from_array(buf: &[u8]) -> Self::TArray
     {
-        return buf.try_into().unwrap();
+        let mut arr = [0; Self::SIZE];
+        arr.copy_from_slice(buf);
+        return arr;
     }
 }
 
@@ -28,18 +28,54 @@
 
 //! Declarations for basic constants and low-level file headers.
 
-use std::io;
+use std::{
+    fmt::{Display, Formatter},
+    io,
+    ops::{BitAnd, BitOr, BitOrAssign, Range, RangeInclusive}
+};
 
 use byteorder::{ByteOrder, LittleEndian};
 
 use super::garraylen::*;
-use crate::{error::Error, Result};
+use crate::{
+    builder::{Checksum, CompressionMethod},
+    format,
+    utils::read_fill,
+    Result
+};
 
-/// The size in bytes of the BPX Main Header.
-pub const SIZE_MAIN_HEADER: usize = 40;
+/// The size in bytes of the BPX Main Header, same as [MAIN_HEADER_SIZE](crate::format::MAIN_HEADER_SIZE).
+pub const SIZE_MAIN_HEADER: usize = format::MAIN_HEADER_SIZE;
 
-/// The size in bytes of a BPX Section Header.
-pub const SIZE_SECTION_HEADER: usize = 24;
+/// The size in bytes of a BPX Section Header, same as [SECTION_HEADER_SIZE](crate::format::SECTION_HEADER_SIZE).
+pub const SIZE_SECTION_HEADER: usize = format::SECTION_HEADER_SIZE;
+
+/// The size in bytes of the Extended Type Information field of the BPX Main Header,
+/// same as [TYPE_EXT_SIZE](crate::format::TYPE_EXT_SIZE).
+pub const SIZE_TYPE_EXT: usize = format::TYPE_EXT_SIZE;
+
+/// The size in bytes of the optional integrity footer.
+///
+/// *The footer is placed right after the last byte accounted for by the
+/// `file_size` field of the BPX Main Header, so decoders which do not know
+/// about it never read it. It is made of [DIGEST_FOOTER_SIGNATURE](self::DIGEST_FOOTER_SIGNATURE)
+/// followed by the little endian CRC32 of all preceding bytes.*
+pub const SIZE_DIGEST_FOOTER: usize = format::DIGEST_FOOTER_SIZE;
+
+/// The signature of the optional integrity footer.
+pub const DIGEST_FOOTER_SIGNATURE: [u8; 4] = *b"BPXD";
+
+/// Bytes of Extended Type Information reserved by the BPX Key-Value store variant (type K).
+pub const TYPE_EXT_RESERVED_KVSTORE: Range<usize> = 0..1;
+
+/// Bytes of Extended Type Information reserved by the BPX Package variant (type P).
+pub const TYPE_EXT_RESERVED_PACKAGE: Range<usize> = 0..4;
+
+/// Bytes of Extended Type Information reserved by the BPX Shader Package variant (type S).
+pub const TYPE_EXT_RESERVED_SHADER: Range<usize> = 0..12;
+
+/// Bytes of Extended Type Information reserved by the BPX Texture variant (type T).
+pub const TYPE_EXT_RESERVED_TEXTURE: Range<usize> = 0..12;
 
 /// XZ section compression enable flag.
 pub const FLAG_COMPRESS_XZ: u8 = 0x2;
@@ -53,20 +89,374 @@ pub const FLAG_COMPRESS_ZLIB: u8 = 0x1;
 /// Section CRC32 checksum enable flag.
 pub const FLAG_CHECK_CRC32: u8 = 0x4;
 
+/// Represents an error in a BPX Main Header or Section Header.
+#[derive(Debug)]
+pub enum Error
+{
+    /// Describes data which does not start with the BPX signature.
+    BadSignature,
+
+    /// Describes a BPX version which is not supported by this implementation.
+    ///
+    /// # Arguments
+    /// * the version found in the main header.
+    BadVersion(u32),
+
+    /// Describes a truncated header.
+    ///
+    /// # Arguments
+    /// * last operation name before failure.
+    Truncation(&'static str),
+
+    /// Describes an impossible combination of section flags.
+    ///
+    /// # Arguments
+    /// * the flags byte.
+    BadFlags(u8),
+
+    /// Describes a range which does not fit in the Extended Type Information field.
+    ///
+    /// # Arguments
+    /// * offset of the range.
+    /// * length of the range.
+    TypeExtOutOfBounds(usize, usize),
+
+    /// Describes a range which overlaps bytes of Extended Type Information
+    /// reserved by the variant.
+    ///
+    /// # Arguments
+    /// * the requested range.
+    /// * the reserved range.
+    /// * the BPX type byte.
+    TypeExtReserved(Range<usize>, Range<usize>, u8),
+
+    /// Describes a BPX version which is not supported by the decoder of a variant.
+    ///
+    /// # Arguments
+    /// * the BPX type byte.
+    /// * the version found in the main header.
+    /// * the versions supported by the variant.
+    BadVariantVersion(u8, u32, RangeInclusive<u32>)
+}
+
+impl Display for Error
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        return match self {
+            Error::BadSignature => f.write_str("illegal bytes found (not a BPX file (bad signature))"),
+            Error::BadVersion(v) => f.write_str(&format!(
+                "unsupported operation BPX version {} (this implementation supports versions {} to {})",
+                v,
+                KNOWN_VERSIONS[0],
+                KNOWN_VERSIONS[KNOWN_VERSIONS.len() - 1]
+            )),
+            Error::Truncation(e) => f.write_str(&format!(
+                "unexpected EOF while reading {}, are you sure the data is not truncated?",
+                e
+            )),
+            Error::BadFlags(_) => f.write_str("illegal bytes found (section flags request both XZ and ZLIB compression)"),
+            Error::TypeExtOutOfBounds(offset, len) => f.write_str(&format!(
+                "type_ext range {}+{} is out of bounds (max is {})",
+                offset, len, SIZE_TYPE_EXT
+            )),
+            Error::TypeExtReserved(range, reserved, btype) => f.write_str(&format!(
+                "type_ext range {}..{} overlaps bytes {}..{} reserved by variant '{}'",
+                range.start, range.end, reserved.start, reserved.end, *btype as char
+            )),
+            Error::BadVariantVersion(btype, v, supported) => f.write_str(&format!(
+                "unsupported operation BPX{} version {} (this implementation supports versions {} to {} of BPX{})",
+                *btype as char,
+                v,
+                supported.start(),
+                supported.end(),
+                *btype as char
+            ))
+        };
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Typed representation of the flags byte of a BPX Section Header.
+///
+/// *Unknown bits are preserved as-is so that reading then writing a header is bit-exact.*
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub struct SectionFlags(u8);
+
+impl SectionFlags
+{
+    /// ZLIB section compression enable flag.
+    pub const COMPRESS_ZLIB: SectionFlags = SectionFlags(FLAG_COMPRESS_ZLIB);
+
+    /// XZ section compression enable flag.
+    pub const COMPRESS_XZ: SectionFlags = SectionFlags(FLAG_COMPRESS_XZ);
+
+    /// Section CRC32 checksum enable flag.
+    pub const CHECK_CRC32: SectionFlags = SectionFlags(FLAG_CHECK_CRC32);
+
+    /// Section weak checksum enable flag.
+    pub const CHECK_WEAK: SectionFlags = SectionFlags(FLAG_CHECK_WEAK);
+
+    /// Creates an empty set of flags.
+    pub const fn empty() -> SectionFlags
+    {
+        return SectionFlags(0);
+    }
+
+    /// Creates a set of flags from the raw flags byte.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits`: the raw flags byte (see FLAG_* constants).
+    ///
+    /// returns: SectionFlags
+    pub const fn from_bits(bits: u8) -> SectionFlags
+    {
+        return SectionFlags(bits);
+    }
+
+    /// Returns the raw flags byte.
+    pub const fn bits(self) -> u8
+    {
+        return self.0;
+    }
+
+    /// Returns the bits of these flags which do not correspond to any known flag.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::header::SectionFlags;
+    ///
+    /// assert_eq!(SectionFlags::COMPRESS_XZ.unknown_bits(), 0);
+    /// assert_eq!(SectionFlags::from_bits(0x12).unknown_bits(), 0x10);
+    /// ```
+    pub const fn unknown_bits(self) -> u8
+    {
+        return self.0 & !(FLAG_COMPRESS_XZ | FLAG_COMPRESS_ZLIB | FLAG_CHECK_WEAK | FLAG_CHECK_CRC32);
+    }
+
+    /// Checks if all flags in `other` are set.
+    ///
+    /// # Arguments
+    ///
+    /// * `other`: the flags to check for.
+    ///
+    /// returns: bool
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::header::SectionFlags;
+    ///
+    /// let flags = SectionFlags::COMPRESS_XZ | SectionFlags::CHECK_CRC32;
+    /// assert!(flags.contains(SectionFlags::COMPRESS_XZ));
+    /// assert!(!flags.contains(SectionFlags::COMPRESS_ZLIB));
+    /// ```
+    pub const fn contains(self, other: SectionFlags) -> bool
+    {
+        return self.0 & other.0 == other.0;
+    }
+
+    /// Sets all flags in `other`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other`: the flags to set.
+    pub fn insert(&mut self, other: SectionFlags)
+    {
+        self.0 |= other.0;
+    }
+
+    /// Clears all flags in `other`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other`: the flags to clear.
+    pub fn remove(&mut self, other: SectionFlags)
+    {
+        self.0 &= !other.0;
+    }
+
+    /// Checks if a compression flag is set.
+    pub fn is_compressed(self) -> bool
+    {
+        return self.compression().is_some();
+    }
+
+    /// Returns the compression method selected by these flags.
+    /// Returns None if no compression flag is set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::CompressionMethod;
+    /// use bpx::header::SectionFlags;
+    ///
+    /// assert_eq!(SectionFlags::COMPRESS_ZLIB.compression(), Some(CompressionMethod::Zlib));
+    /// assert_eq!(SectionFlags::CHECK_WEAK.compression(), None);
+    /// ```
+    pub fn compression(self) -> Option<CompressionMethod>
+    {
+        if self.contains(SectionFlags::COMPRESS_XZ) {
+            return Some(CompressionMethod::Xz);
+        } else if self.contains(SectionFlags::COMPRESS_ZLIB) {
+            return Some(CompressionMethod::Zlib);
+        }
+        return None;
+    }
+
+    /// Returns the checksum algorithm selected by these flags.
+    /// Returns None if no checksum flag is set.
+    ///
+    /// *This returns an Option rather than a plain [Checksum] because Checksum has no variant for
+    /// sections without a checksum; this mirrors [compression](Self::compression).*
+    ///
+    /// *When both checksum flags are set, the weak checksum takes precedence.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::Checksum;
+    /// use bpx::header::SectionFlags;
+    ///
+    /// assert_eq!(SectionFlags::CHECK_CRC32.checksum(), Some(Checksum::Crc32));
+    /// assert_eq!(SectionFlags::COMPRESS_XZ.checksum(), None);
+    /// ```
+    pub fn checksum(self) -> Option<Checksum>
+    {
+        if self.contains(SectionFlags::CHECK_WEAK) {
+            return Some(Checksum::Weak);
+        } else if self.contains(SectionFlags::CHECK_CRC32) {
+            return Some(Checksum::Crc32);
+        }
+        return None;
+    }
+
+    /// Checks that these flags do not contain an illegal combination.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](crate::error::Error) if both XZ and ZLIB compression flags are set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::header::SectionFlags;
+    ///
+    /// assert!(SectionFlags::COMPRESS_XZ.validate().is_ok());
+    /// assert!((SectionFlags::COMPRESS_XZ | SectionFlags::COMPRESS_ZLIB).validate().is_err());
+    /// ```
+    pub fn validate(self) -> Result<()>
+    {
+        if self.contains(SectionFlags::COMPRESS_XZ | SectionFlags::COMPRESS_ZLIB) {
+            return Err(Error::BadFlags(self.0).into());
+        }
+        return Ok(());
+    }
+}
+
+impl BitOr for SectionFlags
+{
+    type Output = SectionFlags;
+
+    fn bitor(self, rhs: SectionFlags) -> SectionFlags
+    {
+        return SectionFlags(self.0 | rhs.0);
+    }
+}
+
+impl BitOrAssign for SectionFlags
+{
+    fn bitor_assign(&mut self, rhs: SectionFlags)
+    {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for SectionFlags
+{
+    type Output = SectionFlags;
+
+    fn bitand(self, rhs: SectionFlags) -> SectionFlags
+    {
+        return SectionFlags(self.0 & rhs.0);
+    }
+}
+
 /// The standard variant for a BPX Strings section.
 pub const SECTION_TYPE_STRING: u8 = 0xFF;
 
 /// The standard variant for a BPX Structured Data section.
 pub const SECTION_TYPE_SD: u8 = 0xFE;
 
+/// The standard variant for a BPX section names table
+/// (see [SectionNames](crate::names::SectionNames)).
+pub const SECTION_TYPE_NAMES: u8 = 0xFD;
+
+/// The standard variant for a BPX build identifier section, holding a single
+/// little endian u64 (see [build_id](crate::Interface::build_id)).
+pub const SECTION_TYPE_BUILD_ID: u8 = 0xFC;
+
+/// The standard variant for a BPX checksum checkpoints section, holding the CRC32
+/// of every fixed size chunk of the other sections
+/// (see [set_checkpoints](crate::encoder::Encoder::set_checkpoints)).
+pub const SECTION_TYPE_CHECKPOINTS: u8 = 0xFB;
+
 /// The BPX version this crate supports.
 pub const BPX_CURRENT_VERSION: u32 = 0x2;
 
 /// The values allowed for the version field in BPX main header.
 pub const KNOWN_VERSIONS: &[u32] = &[0x1, 0x2];
 
+/// The BPX signature found at the start of every BPX file.
+pub const BPX_SIGNATURE: [u8; 3] = [b'B', b'P', b'X'];
+
+/// Returns the range of Extended Type Information bytes reserved by a
+/// known BPX variant.
+///
+/// # Arguments
+///
+/// * `btype`: the BPX type byte.
+///
+/// returns: Option<Range<usize>>
+///
+/// # Examples
+///
+/// ```
+/// use bpx::header::{get_type_ext_reserved_range, TYPE_EXT_RESERVED_PACKAGE};
+///
+/// assert_eq!(get_type_ext_reserved_range(b'P'), Some(TYPE_EXT_RESERVED_PACKAGE));
+/// assert_eq!(get_type_ext_reserved_range(b'M'), None);
+/// ```
+pub fn get_type_ext_reserved_range(btype: u8) -> Option<Range<usize>>
+{
+    return match btype {
+        b'K' => Some(TYPE_EXT_RESERVED_KVSTORE),
+        b'P' => Some(TYPE_EXT_RESERVED_PACKAGE),
+        b'S' => Some(TYPE_EXT_RESERVED_SHADER),
+        b'T' => Some(TYPE_EXT_RESERVED_TEXTURE),
+        _ => None
+    };
+}
+
+pub(crate) fn check_type_ext_range(btype: u8, offset: usize, len: usize) -> Result<Range<usize>>
+{
+    let end = match offset.checked_add(len) {
+        Some(v) if v <= SIZE_TYPE_EXT => v,
+        _ => return Err(Error::TypeExtOutOfBounds(offset, len).into())
+    };
+    if let Some(reserved) = get_type_ext_reserved_range(btype) {
+        if len > 0 && offset < reserved.end && reserved.start < end {
+            return Err(Error::TypeExtReserved(offset..end, reserved, btype).into());
+        }
+    }
+    return Ok(offset..end);
+}
+
 /// The BPX Main Header.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct MainHeader
 {
     /// BPX signature.
@@ -81,6 +471,9 @@ pub struct MainHeader
 
     /// Weak checksum of all headers.
     ///
+    /// *This is the sum, wrapping at 2 pow 32, of all bytes of the main header
+    /// (with this field set to zero) and of all section headers.*
+    ///
     /// Offset: +4
     pub chksum: u32,
 
@@ -107,8 +500,14 @@ pub struct MainHeader
 
 impl MainHeader
 {
+    /// The values allowed for the version field in BPX main header.
+    pub const KNOWN_VERSIONS: &'static [u32] = KNOWN_VERSIONS;
+
     /// Attempts to read a BPX Main Header from an IO backend.
     ///
+    /// *The signature is checked first so that non-BPX data is rejected
+    /// before anything else is interpreted.*
+    ///
     /// # Arguments
     ///
     /// * `reader`: the IO backend to read from.
@@ -118,7 +517,8 @@ impl MainHeader
     /// # Errors
     ///
     /// Returns an [Error](crate::error::Error) if the data could not be
-    /// read from the IO backend or if the header is corrupted.
+    /// read from the IO backend, if the data is not a BPX (bad signature),
+    /// if the header is truncated or if the version is not supported.
     ///
     /// # Examples
     ///
@@ -132,49 +532,120 @@ impl MainHeader
     {
         let mut buf: [u8; SIZE_MAIN_HEADER] = [0; SIZE_MAIN_HEADER];
         let mut checksum: u32 = 0;
-
-        reader.read(&mut buf)?;
-        for i in 0..SIZE_MAIN_HEADER {
-            if i < 4 || i > 7 {
-                checksum += buf[i] as u32;
+        let len = read_fill(reader, &mut buf)?;
+        if len < BPX_SIGNATURE.len() || buf[0..3] != BPX_SIGNATURE {
+            return Err(Error::BadSignature.into());
+        }
+        if len < SIZE_MAIN_HEADER {
+            return Err(Error::Truncation("read main header").into());
+        }
+        for (i, byte) in buf.iter().enumerate() {
+            if !(4..=7).contains(&i) {
+                checksum = checksum.wrapping_add(*byte as u32);
             }
         }
+        let head = MainHeader::from_bytes(&buf)?;
+        return Ok((checksum, head));
+    }
+
+    /// Decodes a BPX Main Header from its on-disk representation.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf`: the raw bytes of the header.
+    ///
+    /// returns: Result<MainHeader, Error>
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](crate::error::Error) if the signature is incorrect
+    /// or if the version is not supported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::header::MainHeader;
+    ///
+    /// let header = MainHeader::new();
+    /// let decoded = MainHeader::from_bytes(&header.to_bytes()).unwrap();
+    /// assert_eq!(decoded.version, header.version);
+    /// ```
+    pub fn from_bytes(buf: &[u8; SIZE_MAIN_HEADER]) -> Result<MainHeader>
+    {
+        if buf[0..3] != BPX_SIGNATURE {
+            return Err(Error::BadSignature.into());
+        }
         let head = MainHeader {
-            signature: extract_slice::<T3>(&buf, 0),
+            signature: extract_slice::<T3>(buf, 0),
             btype: buf[3],
             chksum: LittleEndian::read_u32(&buf[4..8]),
             file_size: LittleEndian::read_u64(&buf[8..16]),
             section_num: LittleEndian::read_u32(&buf[16..20]),
             version: LittleEndian::read_u32(&buf[20..24]),
-            type_ext: extract_slice::<T16>(&buf, 24)
+            type_ext: extract_slice::<T16>(buf, 24)
         };
-        if head.signature[0] != 'B' as u8 || head.signature[1] != 'P' as u8 || head.signature[2] != 'X' as u8 {
-            return Err(Error::Corruption(format!(
-                "incorrect signature, expected {}{}{}, got {}{}{}",
-                'B' as u8, 'P' as u8, 'X' as u8, head.signature[0], head.signature[1], head.signature[2]
-            )));
-        }
         if !KNOWN_VERSIONS.contains(&head.version) {
-            return Err(Error::Unsupported(format!("unsupported version {}", head.version)));
+            return Err(Error::BadVersion(head.version).into());
         }
-        return Ok((checksum, head));
+        return Ok(head);
+    }
+
+    /// Returns a range of bytes from the Extended Type Information field.
+    ///
+    /// *Unlike [with_type_ext_range](crate::builder::MainHeaderBuilder::with_type_ext_range),
+    /// reading bytes reserved by the variant is allowed.*
+    ///
+    /// # Arguments
+    ///
+    /// * `offset`: the offset of the first byte in the Extended Type Information field.
+    /// * `len`: the number of bytes to return.
+    ///
+    /// returns: Option<&[u8]>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::MainHeaderBuilder;
+    ///
+    /// let header = MainHeaderBuilder::new()
+    ///     .with_type_ext_range(12, &[1, 2, 3, 4])
+    ///     .unwrap()
+    ///     .build();
+    /// assert_eq!(header.type_ext_range(12, 4), Some(&[1, 2, 3, 4][..]));
+    /// assert_eq!(header.type_ext_range(14, 4), None);
+    /// ```
+    pub fn type_ext_range(&self, offset: usize, len: usize) -> Option<&[u8]>
+    {
+        let end = offset.checked_add(len)?;
+        return self.type_ext.get(offset..end);
     }
 
     /// Creates a new empty BPX Main Header.
     pub fn new() -> MainHeader
     {
         return MainHeader {
-            signature: ['B' as u8, 'P' as u8, 'X' as u8], //+0
-            btype: 'P' as u8,                             //+3
-            chksum: 0,                                    //+4
-            file_size: SIZE_MAIN_HEADER as u64,           //+8
-            section_num: 0,                               //+16
-            version: BPX_CURRENT_VERSION,                 //+20
+            signature: BPX_SIGNATURE,           //+0
+            btype: b'P',                        //+3
+            chksum: 0,                          //+4
+            file_size: SIZE_MAIN_HEADER as u64, //+8
+            section_num: 0,                     //+16
+            version: BPX_CURRENT_VERSION,       //+20
             type_ext: [0; 16]
         };
     }
 
-    fn to_bytes(&self) -> [u8; SIZE_MAIN_HEADER]
+    /// Encodes this header to its on-disk representation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::header::{MainHeader, SIZE_MAIN_HEADER};
+    ///
+    /// let buf = MainHeader::new().to_bytes();
+    /// assert_eq!(buf.len(), SIZE_MAIN_HEADER);
+    /// assert_eq!(&buf[0..3], b"BPX");
+    /// ```
+    pub fn to_bytes(self) -> [u8; SIZE_MAIN_HEADER]
     {
         let mut block: [u8; SIZE_MAIN_HEADER] = [0; SIZE_MAIN_HEADER];
         block[0] = self.signature[0];
@@ -185,19 +656,19 @@ impl MainHeader
         LittleEndian::write_u64(&mut block[8..16], self.file_size);
         LittleEndian::write_u32(&mut block[16..20], self.section_num);
         LittleEndian::write_u32(&mut block[20..24], self.version);
-        for i in 24..40 {
-            block[i] = self.type_ext[i - 24];
-        }
+        block[24..40].copy_from_slice(&self.type_ext);
         return block;
     }
 
     /// Computes the checksum for this header.
+    ///
+    /// *The checksum is the sum of all bytes of the header, wrapping at 2 pow 32.*
     pub fn get_checksum(&self) -> u32
     {
         let mut checksum: u32 = 0;
         let buf = self.to_bytes();
-        for i in 0..SIZE_MAIN_HEADER {
-            checksum += buf[i] as u32;
+        for byte in &buf {
+            checksum = checksum.wrapping_add(*byte as u32);
         }
         return checksum;
     }
@@ -217,14 +688,22 @@ impl MainHeader
     pub fn write<TWriter: io::Write>(&self, writer: &mut TWriter) -> io::Result<()>
     {
         let buf = self.to_bytes();
-        writer.write(&buf)?;
+        writer.write_all(&buf)?;
         writer.flush()?;
         return Ok(());
     }
 }
 
+impl Default for MainHeader
+{
+    fn default() -> Self
+    {
+        return Self::new();
+    }
+}
+
 /// The BPX Section Header.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct SectionHeader
 {
     /// Data pointer.
@@ -252,10 +731,20 @@ pub struct SectionHeader
     /// Offset: +20
     pub btype: u8,
 
-    /// Flags (see FLAG_* constants).
+    /// Flags (see FLAG_* constants and [SectionFlags](self::SectionFlags)).
     ///
     /// Offset: +21
-    pub flags: u8
+    pub flags: u8,
+
+    /// Application defined tag byte, never interpreted by this crate.
+    ///
+    /// Offset: +22
+    pub user_tag: u8,
+
+    /// Reserved byte, zero in headers built by this crate and kept as read otherwise.
+    ///
+    /// Offset: +23
+    pub reserved: u8
 }
 
 impl SectionHeader
@@ -271,39 +760,71 @@ impl SectionHeader
     /// # Errors
     ///
     /// Returns an [Error](crate::error::Error) if the data could not be
-    /// read from the IO backend.
-    pub fn read<TReader: io::Read>(reader: &mut TReader) -> io::Result<(u32, SectionHeader)>
+    /// read from the IO backend or if the flags contain an illegal combination.
+    pub fn read<TReader: io::Read>(reader: &mut TReader) -> Result<(u32, SectionHeader)>
     {
         let mut buf: [u8; SIZE_SECTION_HEADER] = [0; SIZE_SECTION_HEADER];
         let mut checksum: u32 = 0;
 
-        reader.read(&mut buf)?;
-        for i in 0..SIZE_SECTION_HEADER {
-            checksum += buf[i] as u32;
+        reader.read_exact(&mut buf)?;
+        for byte in &buf {
+            checksum = checksum.wrapping_add(*byte as u32);
         }
-        return Ok((
-            checksum,
-            SectionHeader {
-                pointer: LittleEndian::read_u64(&buf[0..8]),
-                csize: LittleEndian::read_u32(&buf[8..12]),
-                size: LittleEndian::read_u32(&buf[12..16]),
-                chksum: LittleEndian::read_u32(&buf[16..20]),
-                btype: buf[20],
-                flags: buf[21]
-            }
-        ));
+        let header = SectionHeader::from_bytes(&buf)?;
+        return Ok((checksum, header));
+    }
+
+    /// Decodes a BPX Section Header from its on-disk representation.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf`: the raw bytes of the header.
+    ///
+    /// returns: Result<SectionHeader, Error>
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](crate::error::Error) if the flags contain an illegal combination.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::SectionHeaderBuilder;
+    /// use bpx::header::SectionHeader;
+    ///
+    /// let header = SectionHeaderBuilder::new().with_type(1).with_size(128).build();
+    /// let decoded = SectionHeader::from_bytes(&header.to_bytes()).unwrap();
+    /// assert_eq!(decoded.btype, 1);
+    /// assert_eq!(decoded.size, 128);
+    /// ```
+    pub fn from_bytes(buf: &[u8; SIZE_SECTION_HEADER]) -> Result<SectionHeader>
+    {
+        let header = SectionHeader {
+            pointer: LittleEndian::read_u64(&buf[0..8]),
+            csize: LittleEndian::read_u32(&buf[8..12]),
+            size: LittleEndian::read_u32(&buf[12..16]),
+            chksum: LittleEndian::read_u32(&buf[16..20]),
+            btype: buf[20],
+            flags: buf[21],
+            user_tag: buf[22],
+            reserved: buf[23]
+        };
+        header.flags().validate()?;
+        return Ok(header);
     }
 
     /// Creates a new empty BPX Section Header.
     pub fn new() -> SectionHeader
     {
         return SectionHeader {
-            pointer: 0, //+0
-            csize: 0,   //+8
-            size: 0,    //+12
-            chksum: 0,  //+16
-            btype: 0,   //+20
-            flags: 0    //+21
+            pointer: 0,  //+0
+            csize: 0,    //+8
+            size: 0,     //+12
+            chksum: 0,   //+16
+            btype: 0,    //+20
+            flags: 0,    //+21
+            user_tag: 0, //+22
+            reserved: 0  //+23
         };
     }
 
@@ -313,7 +834,96 @@ impl SectionHeader
         return self.size > 100000000;
     }
 
-    fn to_bytes(&self) -> [u8; SIZE_SECTION_HEADER]
+    /// Returns the typed [SectionFlags](self::SectionFlags) of this section.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::{Checksum, SectionHeaderBuilder};
+    /// use bpx::header::SectionFlags;
+    ///
+    /// let header = SectionHeaderBuilder::new().with_checksum(Checksum::Crc32).build();
+    /// assert_eq!(header.flags(), SectionFlags::CHECK_CRC32);
+    /// ```
+    pub fn flags(&self) -> SectionFlags
+    {
+        return SectionFlags::from_bits(self.flags);
+    }
+
+    /// Returns the application defined tag byte of this section.
+    ///
+    /// *The tag is stored in its own byte of the header, separate from the
+    /// flags, so it can never be mistaken for a compression or checksum flag.
+    /// The default tag is 0.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::{Checksum, SectionHeaderBuilder};
+    ///
+    /// let header = SectionHeaderBuilder::new().with_user_tag(0xFF).with_checksum(Checksum::Weak).build();
+    /// assert_eq!(header.user_tag(), 0xFF);
+    /// assert_eq!(header.checksum(), Some(Checksum::Weak));
+    /// assert!(!header.is_compressed());
+    /// ```
+    pub fn user_tag(&self) -> u8
+    {
+        return self.user_tag;
+    }
+
+    /// Checks if a compression flag is set on this section.
+    pub fn is_compressed(&self) -> bool
+    {
+        return self.flags().is_compressed();
+    }
+
+    /// Returns the compression method requested by this section.
+    /// Returns None if the section is not compressed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::{CompressionMethod, SectionHeaderBuilder};
+    ///
+    /// let header = SectionHeaderBuilder::new().with_compression(CompressionMethod::Xz).build();
+    /// assert_eq!(header.compression(), Some(CompressionMethod::Xz));
+    /// ```
+    pub fn compression(&self) -> Option<CompressionMethod>
+    {
+        return self.flags().compression();
+    }
+
+    /// Returns the checksum algorithm requested by this section.
+    /// Returns None if the section has no checksum.
+    ///
+    /// *See [SectionFlags::checksum] for why this returns an Option.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::{Checksum, SectionHeaderBuilder};
+    ///
+    /// let header = SectionHeaderBuilder::new().with_checksum(Checksum::Weak).build();
+    /// assert_eq!(header.checksum(), Some(Checksum::Weak));
+    /// ```
+    pub fn checksum(&self) -> Option<Checksum>
+    {
+        return self.flags().checksum();
+    }
+
+    /// Encodes this header to its on-disk representation.
+    ///
+    /// *The last byte is reserved and always written as zero.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::header::{SectionHeader, SIZE_SECTION_HEADER};
+    ///
+    /// let buf = SectionHeader::new().to_bytes();
+    /// assert_eq!(buf, [0; SIZE_SECTION_HEADER]);
+    /// ```
+    pub fn to_bytes(self) -> [u8; SIZE_SECTION_HEADER]
     {
         let mut block: [u8; SIZE_SECTION_HEADER] = [0; SIZE_SECTION_HEADER];
         LittleEndian::write_u64(&mut block[0..8], self.pointer);
@@ -322,16 +932,20 @@ impl SectionHeader
         LittleEndian::write_u32(&mut block[16..20], self.chksum);
         block[20] = self.btype;
         block[21] = self.flags;
+        block[22] = self.user_tag;
+        block[23] = self.reserved;
         return block;
     }
 
     /// Computes the checksum for this header.
+    ///
+    /// *The checksum is the sum of all bytes of the header, wrapping at 2 pow 32.*
     pub fn get_checksum(&self) -> u32
     {
         let mut checksum: u32 = 0;
         let buf = self.to_bytes();
-        for i in 0..SIZE_SECTION_HEADER {
-            checksum += buf[i] as u32;
+        for byte in &buf {
+            checksum = checksum.wrapping_add(*byte as u32);
         }
         return checksum;
     }
@@ -351,8 +965,16 @@ impl SectionHeader
     pub fn write<TWriter: io::Write>(&self, writer: &mut TWriter) -> io::Result<()>
     {
         let buf = self.to_bytes();
-        writer.write(&buf)?;
+        writer.write_all(&buf)?;
         writer.flush()?;
         return Ok(());
     }
 }
+
+impl Default for SectionHeader
+{
+    fn default() -> Self
+    {
+        return Self::new();
+    }
+}
@@ -0,0 +1,330 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Human-readable description of the structure of a BPX.
+//!
+//! *The text rendering of [FileReport](self::FileReport) is stable: it only changes
+//! if the described file changes, which makes it suitable for snapshot tests and
+//! support tickets.*
+
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Formatter}
+};
+
+use crate::{
+    error::Error,
+    header::{MainHeader, SectionHeader, SECTION_TYPE_NAMES, SECTION_TYPE_SD, SECTION_TYPE_STRING},
+    names::parse_names,
+    sd::Object,
+    section::StorageReport,
+    format::package::{OBJECT_ENTRY_SIZE, SUMMARY_SIZE},
+    variant::package::{SECTION_TYPE_OBJECT_TABLE, SECTION_TYPE_SUMMARY},
+    Interface,
+    Result
+};
+
+/// The result of verifying the data of a section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SectionStatus
+{
+    /// The section has no checksum and could be loaded.
+    Loaded,
+
+    /// The section checksum matches the data.
+    Valid,
+
+    /// The section checksum does not match the data.
+    ///
+    /// *Contains the actual and the expected checksum.*
+    ChecksumMismatch(u32, u32),
+
+    /// The section could not be loaded.
+    ///
+    /// *Contains the error message.*
+    Error(String)
+}
+
+/// A short summary of the content of a section of a known type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SectionSummary
+{
+    /// A strings section.
+    ///
+    /// *Contains the number of strings.*
+    Strings(usize),
+
+    /// A BPXSD object section.
+    ///
+    /// *Contains the sorted hashes of the top-level keys.*
+    StructuredData(Vec<u64>),
+
+    /// A section names table.
+    ///
+    /// *Contains the number of named sections.*
+    Names(usize),
+
+    /// A BPX Package (type P) object table.
+    ///
+    /// *Contains the number of package entries.*
+    ObjectTable(usize),
+
+    /// A BPX Package (type P) summary section.
+    ///
+    /// *Contains the stored file count and total size.*
+    PackageSummary(u64, u64)
+}
+
+/// Description of a single section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SectionReport
+{
+    /// The index of the section.
+    pub index: u32,
+
+    /// The section header.
+    pub header: SectionHeader,
+
+    /// The result of loading the section.
+    pub status: SectionStatus,
+
+    /// A summary of the content if the section type is known and the section could be loaded.
+    pub summary: Option<SectionSummary>,
+
+    /// The index of the first section whose stored payload is shared with this
+    /// section, if the file was written with section deduplication.
+    pub shared_with: Option<u32>
+}
+
+/// Description of a BPX, as returned by [dump](self::dump).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileReport
+{
+    /// The main header.
+    pub main_header: MainHeader,
+
+    /// The description of each section, in index order.
+    pub sections: Vec<SectionReport>,
+
+    /// How the sections loaded by the dump are held in memory.
+    ///
+    /// *This depends on allocation sizes and memory budgets rather than on the described
+    /// file, so it is not part of the text rendering.*
+    pub storage: StorageReport
+}
+
+impl FileReport
+{
+    /// Returns true if all sections could be loaded and their checksums verify.
+    pub fn is_valid(&self) -> bool
+    {
+        return self
+            .sections
+            .iter()
+            .all(|v| matches!(v.status, SectionStatus::Loaded | SectionStatus::Valid));
+    }
+
+    /// Returns the headers of all sections, in index order.
+    ///
+    /// *This is the same snapshot as
+    /// [section_headers_snapshot](crate::Interface::section_headers_snapshot)
+    /// taken at the time of the dump.*
+    pub fn section_headers(&self) -> Vec<SectionHeader>
+    {
+        return self.sections.iter().map(|v| v.header).collect();
+    }
+}
+
+fn summarize(main_header: &MainHeader, btype: u8, data: &[u8]) -> Option<SectionSummary>
+{
+    return match btype {
+        SECTION_TYPE_STRING => Some(SectionSummary::Strings(data.iter().filter(|v| **v == 0).count())),
+        SECTION_TYPE_SD => {
+            let obj = Object::read(&mut &*data).ok()?;
+            let mut keys: Vec<u64> = obj.get_keys().copied().collect();
+            keys.sort_unstable();
+            Some(SectionSummary::StructuredData(keys))
+        },
+        SECTION_TYPE_NAMES => Some(SectionSummary::Names(parse_names(data).ok()?.len())),
+        SECTION_TYPE_OBJECT_TABLE if main_header.btype == b'P' => {
+            Some(SectionSummary::ObjectTable(data.len() / OBJECT_ENTRY_SIZE))
+        },
+        SECTION_TYPE_SUMMARY if main_header.btype == b'P' && data.len() == SUMMARY_SIZE => {
+            let count = u64::from_le_bytes(<[u8; 8]>::try_from(&data[0..8]).ok()?);
+            let size = u64::from_le_bytes(<[u8; 8]>::try_from(&data[8..16]).ok()?);
+            Some(SectionSummary::PackageSummary(count, size))
+        },
+        _ => None
+    };
+}
+
+/// Describes the structure of a BPX.
+///
+/// *Every section is loaded in order to verify its checksum; a section which
+/// fails to load is reported instead of aborting the dump.*
+///
+/// # Arguments
+///
+/// * `file`: the BPX [Decoder](crate::decoder::Decoder) or
+///   [Container](crate::container::Container) to describe.
+///
+/// returns: Result<FileReport, Error>
+///
+/// # Errors
+///
+/// Currently no error is returned, the Result is kept to allow reporting
+/// failures which prevent describing the file as a whole.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use bpx::builder::SectionHeaderBuilder;
+/// use bpx::decoder::Decoder;
+/// use bpx::encoder::Encoder;
+/// use bpx::inspect::dump;
+///
+/// let mut buf = Vec::new();
+/// let mut encoder = Encoder::new(Cursor::new(&mut buf)).unwrap();
+/// encoder.create_section(SectionHeaderBuilder::new().with_type(1).build()).unwrap();
+/// encoder.save().unwrap();
+/// let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+/// let report = dump(&mut decoder).unwrap();
+/// assert_eq!(report.sections.len(), 1);
+/// assert!(report.is_valid());
+/// ```
+pub fn dump<TInterface: Interface>(file: &mut TInterface) -> Result<FileReport>
+{
+    let main_header = *file.get_main_header();
+    let handles: Vec<_> = file.iter_sections().collect();
+    let mut sections = Vec::with_capacity(handles.len());
+
+    for handle in handles {
+        let header = *file.get_section_header(handle);
+        let index = file.get_section_index(handle);
+        let loaded = file.open_section(handle).and_then(|mut v| Ok(v.load_in_memory()?));
+        let (status, summary) = match loaded {
+            Ok(data) => {
+                let data = &data[..std::cmp::min(data.len(), header.size as usize)];
+                let status = match header.checksum() {
+                    Some(_) => SectionStatus::Valid,
+                    None => SectionStatus::Loaded
+                };
+                (status, summarize(&main_header, header.btype, data))
+            },
+            Err(e) => match e.without_context() {
+                Error::Checksum(actual, expected) => {
+                    (SectionStatus::ChecksumMismatch(*actual, *expected), None)
+                },
+                _ => (SectionStatus::Error(e.to_string()), None)
+            }
+        };
+        let shared_with = sections
+            .iter()
+            .find(|v: &&SectionReport| {
+                header.csize > 0 && v.header.pointer == header.pointer && v.header.csize == header.csize
+            })
+            .map(|v| v.index);
+        sections.push(SectionReport {
+            index,
+            header,
+            status,
+            summary,
+            shared_with
+        });
+    }
+    return Ok(FileReport {
+        main_header,
+        sections,
+        storage: file.storage_report()
+    });
+}
+
+impl Display for SectionStatus
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        return match self {
+            SectionStatus::Loaded => f.write_str("ok"),
+            SectionStatus::Valid => f.write_str("checksum ok"),
+            SectionStatus::ChecksumMismatch(actual, expected) => f.write_str(&format!(
+                "checksum mismatch (actual {:#010x}, expected {:#010x})",
+                actual, expected
+            )),
+            SectionStatus::Error(e) => f.write_str(&format!("error: {}", e))
+        };
+    }
+}
+
+impl Display for SectionSummary
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        return match self {
+            SectionSummary::Strings(count) => f.write_str(&format!("strings: {}", count)),
+            SectionSummary::StructuredData(keys) => {
+                let keys: Vec<String> = keys.iter().map(|v| format!("{:#018x}", v)).collect();
+                f.write_str(&format!("structured data keys: [{}]", keys.join(", ")))
+            },
+            SectionSummary::Names(count) => f.write_str(&format!("named sections: {}", count)),
+            SectionSummary::ObjectTable(count) => f.write_str(&format!("package entries: {}", count)),
+            SectionSummary::PackageSummary(count, size) => {
+                f.write_str(&format!("package summary: {} file(s), {} byte(s)", count, size))
+            }
+        };
+    }
+}
+
+impl Display for FileReport
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        let header = &self.main_header;
+        let type_ext: Vec<String> = header.type_ext.iter().map(|v| format!("{:02x}", v)).collect();
+        writeln!(f, "BPX type '{}' ({:#04x}) version {}", header.btype as char, header.btype, header.version)?;
+        writeln!(f, "file size: {} bytes", header.file_size)?;
+        writeln!(f, "header checksum: {:#010x}", header.chksum)?;
+        writeln!(f, "type ext: {}", type_ext.join(" "))?;
+        writeln!(f, "sections: {}", header.section_num)?;
+        for section in &self.sections {
+            let h = &section.header;
+            writeln!(
+                f,
+                "[{}] type {:#04x} flags {:#04x} size {} csize {} pointer {:#x} checksum {:#010x}: {}",
+                section.index, h.btype, h.flags, h.size, h.csize, h.pointer, h.chksum, section.status
+            )?;
+            if let Some(index) = section.shared_with {
+                writeln!(f, "    shares payload with [{}]", index)?;
+            }
+            if let Some(summary) = &section.summary {
+                writeln!(f, "    {}", summary)?;
+            }
+        }
+        return Ok(());
+    }
+}
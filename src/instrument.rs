@@ -0,0 +1,262 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Hooks to measure where time goes while saving and loading a BPX.
+//!
+//! *An [Instrumentation](self::Instrumentation) installed on an
+//! [Encoder](crate::encoder::Encoder) or a [Decoder](crate::decoder::Decoder)
+//! receives one [Event](self::Event) per section and per save; nothing is
+//! measured when no instrumentation is installed.*
+
+use std::{sync::Mutex, time::Duration};
+
+/// An event reported to an [Instrumentation](self::Instrumentation).
+///
+/// *Section indices are the indices in the section header table. Sizes are in bytes.*
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event
+{
+    /// A section is about to be compressed (or stored uncompressed) by a save.
+    CompressStart
+    {
+        /// The index of the section.
+        section: u32,
+
+        /// The size of the section data.
+        size: usize
+    },
+
+    /// A section was compressed, including checksum time.
+    CompressEnd
+    {
+        /// The index of the section.
+        section: u32,
+
+        /// The size of the section data.
+        size: usize,
+
+        /// The size of the stored section.
+        csize: usize,
+
+        /// The time spent compressing the section.
+        elapsed: Duration
+    },
+
+    /// A section is about to be loaded and decompressed.
+    DecompressStart
+    {
+        /// The index of the section.
+        section: u32,
+
+        /// The size of the stored section.
+        csize: u32
+    },
+
+    /// A section was loaded and decompressed, including checksum time.
+    DecompressEnd
+    {
+        /// The index of the section.
+        section: u32,
+
+        /// The size of the section data.
+        size: u32,
+
+        /// The time spent loading the section.
+        elapsed: Duration
+    },
+
+    /// The checksum of a section was computed, while compressing or decompressing it.
+    Checksum
+    {
+        /// The index of the section.
+        section: u32,
+
+        /// The number of bytes checked.
+        bytes: usize,
+
+        /// The time spent computing the checksum.
+        elapsed: Duration
+    },
+
+    /// Bytes were read from the IO backend.
+    BackendRead
+    {
+        /// The number of bytes.
+        bytes: u64
+    },
+
+    /// Bytes were written to the IO backend.
+    BackendWrite
+    {
+        /// The number of bytes.
+        bytes: u64
+    },
+
+    /// Section data is stored in a temporary file instead of memory.
+    Spill
+    {
+        /// The index of the section.
+        section: u32,
+
+        /// The expected size of the section, if known.
+        size: Option<u32>
+    }
+}
+
+/// Receives [Event](self::Event)s from an encoder or a decoder.
+///
+/// *Events may be reported from worker threads, see
+/// [load_sections](crate::decoder::Decoder::load_sections).*
+pub trait Instrumentation: Send + Sync
+{
+    /// Called for each event.
+    ///
+    /// # Arguments
+    ///
+    /// * `event`: the event.
+    fn on_event(&self, event: Event);
+}
+
+/// Totals collected by [BasicStats](self::BasicStats).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats
+{
+    /// The number of sections compressed.
+    pub compressed_sections: u64,
+
+    /// The size of the data of the compressed sections.
+    pub compress_input_bytes: u64,
+
+    /// The size of the compressed sections once stored.
+    pub compress_output_bytes: u64,
+
+    /// The time spent compressing sections.
+    pub compress_time: Duration,
+
+    /// The number of sections decompressed.
+    pub decompressed_sections: u64,
+
+    /// The size of the decompressed sections as stored.
+    pub decompress_input_bytes: u64,
+
+    /// The size of the data of the decompressed sections.
+    pub decompress_output_bytes: u64,
+
+    /// The time spent decompressing sections.
+    pub decompress_time: Duration,
+
+    /// The number of bytes checksummed.
+    pub checksum_bytes: u64,
+
+    /// The time spent computing checksums, part of the compression and decompression time.
+    pub checksum_time: Duration,
+
+    /// The number of bytes read from the IO backend.
+    pub backend_read_bytes: u64,
+
+    /// The number of bytes written to the IO backend.
+    pub backend_write_bytes: u64,
+
+    /// The number of sections stored in temporary files.
+    pub spills: u64
+}
+
+/// An [Instrumentation](self::Instrumentation) adding up all events into [Stats](self::Stats).
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use bpx::builder::SectionHeaderBuilder;
+/// use bpx::encoder::Encoder;
+/// use bpx::instrument::BasicStats;
+/// use bpx::Interface;
+///
+/// let stats = Arc::new(BasicStats::new());
+/// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+/// encoder.set_instrumentation(Some(stats.clone()));
+/// let handle = encoder.create_section(SectionHeaderBuilder::bulk_data().build()).unwrap();
+/// encoder.open_section(handle).unwrap().write_all(&[0; 4096]).unwrap();
+/// encoder.save().unwrap();
+/// let stats = stats.get();
+/// assert_eq!(stats.compressed_sections, 1);
+/// assert_eq!(stats.compress_input_bytes, 4096);
+/// assert_eq!(stats.backend_write_bytes, encoder.get_main_header().file_size);
+/// ```
+#[derive(Debug, Default)]
+pub struct BasicStats
+{
+    stats: Mutex<Stats>
+}
+
+impl BasicStats
+{
+    /// Creates new empty statistics.
+    pub fn new() -> BasicStats
+    {
+        return BasicStats::default();
+    }
+
+    /// Returns the totals collected so far.
+    pub fn get(&self) -> Stats
+    {
+        return self.stats.lock().map(|v| *v).unwrap_or_else(|e| *e.into_inner());
+    }
+}
+
+impl Instrumentation for BasicStats
+{
+    fn on_event(&self, event: Event)
+    {
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        match event {
+            Event::CompressStart { .. } => (),
+            Event::DecompressStart { csize, .. } => stats.decompress_input_bytes += csize as u64,
+            Event::CompressEnd {
+                size, csize, elapsed, ..
+            } => {
+                stats.compressed_sections += 1;
+                stats.compress_input_bytes += size as u64;
+                stats.compress_output_bytes += csize as u64;
+                stats.compress_time += elapsed;
+            },
+            Event::DecompressEnd { size, elapsed, .. } => {
+                stats.decompressed_sections += 1;
+                stats.decompress_output_bytes += size as u64;
+                stats.decompress_time += elapsed;
+            },
+            Event::Checksum { bytes, elapsed, .. } => {
+                stats.checksum_bytes += bytes as u64;
+                stats.checksum_time += elapsed;
+            },
+            Event::BackendRead { bytes } => stats.backend_read_bytes += bytes,
+            Event::BackendWrite { bytes } => stats.backend_write_bytes += bytes,
+            Event::Spill { .. } => stats.spills += 1
+        }
+    }
+}
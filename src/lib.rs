@@ -28,20 +28,46 @@
 
 //! This library is the official implementation for the [BPX](https://gitlab.com/bp3d/bpx/bpx/-/blob/master/BPX_Format.pdf) container format.
 
-use std::vec::Vec;
+#![allow(clippy::needless_return)]
+#![deny(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::{
+    convert::TryFrom,
+    io::{Read, Seek, SeekFrom},
+    vec::Vec
+};
 
 pub mod variant;
 pub mod builder;
+mod build_id;
+mod checkpoint;
 mod compression;
+pub mod container;
 pub mod decoder;
 pub mod encoder;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod format;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 mod garraylen;
 pub mod header;
+pub mod inspect;
+pub mod instrument;
+pub mod names;
+pub mod patch;
+pub mod policy;
+pub mod raw;
 pub mod sd;
 pub mod section;
 pub mod strings;
+#[cfg(feature = "test-util")]
+pub mod testing;
 pub mod utils;
+pub mod validate;
+
+pub use error::Error;
 
 /// Represents a pointer to a section.
 ///
@@ -49,6 +75,73 @@ pub mod utils;
 #[derive(Copy, Clone, Debug)]
 pub struct SectionHandle(usize);
 
+/// Iterator over the handles of all sections in a BPX, in index order.
+///
+/// *Created by [iter_sections](crate::Interface::iter_sections).*
+pub struct SectionIter<'a, T: ?Sized>
+{
+    interface: &'a T,
+    index: u32
+}
+
+impl<'a, T: Interface + ?Sized> Iterator for SectionIter<'a, T>
+{
+    type Item = SectionHandle;
+
+    fn next(&mut self) -> Option<SectionHandle>
+    {
+        let handle = self.interface.find_section_by_index(self.index)?;
+        self.index += 1;
+        return Some(handle);
+    }
+}
+
+/// Iterator over the handles of all sections of a given type in a BPX, in index order.
+///
+/// *Created by [sections_of_type](crate::Interface::sections_of_type).*
+pub struct SectionsOfType<'a, T: ?Sized>
+{
+    iter: SectionIter<'a, T>,
+    btype: u8
+}
+
+impl<'a, T: Interface + ?Sized> Iterator for SectionsOfType<'a, T>
+{
+    type Item = SectionHandle;
+
+    fn next(&mut self) -> Option<SectionHandle>
+    {
+        let interface = self.iter.interface;
+        let btype = self.btype;
+        return self
+            .iter
+            .find(|handle| interface.get_section_header(*handle).btype == btype);
+    }
+}
+
+/// Locates a section by its index in a section header table.
+pub(crate) fn find_section_index(sections: &[header::SectionHeader], index: u32) -> Option<SectionHandle>
+{
+    let index = usize::try_from(index).ok()?;
+    if index < sections.len() {
+        return Some(SectionHandle(index));
+    }
+    return None;
+}
+
+/// Returns an iterator over the handles of all sections of a given type in a section header table.
+pub(crate) fn find_sections_with_type(
+    sections: &[header::SectionHeader],
+    btype: u8
+) -> impl Iterator<Item = SectionHandle> + '_
+{
+    return sections
+        .iter()
+        .enumerate()
+        .filter(move |(_, header)| header.btype == btype)
+        .map(|(i, _)| SectionHandle(i));
+}
+
 /// The interface implemented by both the BPX encoder and decoder.
 pub trait Interface
 {
@@ -70,7 +163,10 @@ pub trait Interface
     /// let file = Encoder::new(Vec::<u8>::new()).unwrap();
     /// assert!(file.find_section_by_type(0).is_none());
     /// ```
-    fn find_section_by_type(&self, btype: u8) -> Option<SectionHandle>;
+    fn find_section_by_type(&self, btype: u8) -> Option<SectionHandle>
+    {
+        return self.sections_of_type(btype).next();
+    }
 
     /// Searches for all sections of a given type.
     /// Returns None if no section could be found.
@@ -90,7 +186,220 @@ pub trait Interface
     /// let file = Encoder::new(Vec::<u8>::new()).unwrap();
     /// assert_eq!(file.find_all_sections_of_type(0).len(), 0);
     /// ```
-    fn find_all_sections_of_type(&self, btype: u8) -> Vec<SectionHandle>;
+    fn find_all_sections_of_type(&self, btype: u8) -> Vec<SectionHandle>
+    {
+        return self.sections_of_type(btype).collect();
+    }
+
+    /// Returns an iterator over the handles of all sections of a given type.
+    ///
+    /// *Unlike [find_all_sections_of_type](crate::Interface::find_all_sections_of_type)
+    /// this does not allocate.*
+    ///
+    /// # Arguments
+    ///
+    /// * `btype`: section type byte.
+    ///
+    /// returns: SectionsOfType<Self>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    /// use bpx::builder::SectionHeaderBuilder;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().with_type(1).build()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().with_type(2).build()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().with_type(1).build()).unwrap();
+    /// assert_eq!(file.sections_of_type(1).count(), 2);
+    /// ```
+    fn sections_of_type(&self, btype: u8) -> SectionsOfType<'_, Self>
+    {
+        return SectionsOfType {
+            iter: self.iter_sections(),
+            btype
+        };
+    }
+
+    /// Returns an iterator over the handles of all sections, in index order.
+    ///
+    /// returns: SectionIter<Self>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    /// use bpx::builder::SectionHeaderBuilder;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().build()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().build()).unwrap();
+    /// let indices: Vec<u32> = file.iter_sections().map(|v| file.get_section_index(v)).collect();
+    /// assert_eq!(indices, [0, 1]);
+    /// ```
+    fn iter_sections(&self) -> SectionIter<'_, Self>
+    {
+        return SectionIter {
+            interface: self,
+            index: 0
+        };
+    }
+
+    /// Describes the compression and checksum used by a section.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: SectionInfo
+    ///
+    /// # Panics
+    ///
+    /// Panics if the given section handle is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::{Checksum, CompressionMethod, SectionHeaderBuilder};
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let header = SectionHeaderBuilder::new().with_compression(CompressionMethod::Zlib).with_threshold(0).build();
+    /// let handle = file.create_section(header).unwrap();
+    /// let info = file.section_info(handle);
+    /// assert_eq!(info.compression, Some(CompressionMethod::Zlib));
+    /// assert_eq!(info.checksum, None);
+    /// ```
+    fn section_info(&self, handle: SectionHandle) -> policy::SectionInfo
+    {
+        return policy::SectionInfo::new(self.get_section_header(handle));
+    }
+
+    /// Checks the compression and checksum of every section against a policy.
+    /// Returns an empty list if all sections meet the policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy`: the [SectionPolicy](crate::policy::SectionPolicy) to check.
+    ///
+    /// returns: Vec<PolicyViolation, Global>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::{Checksum, SectionHeaderBuilder};
+    /// use bpx::encoder::Encoder;
+    /// use bpx::policy::{SectionPolicy, SectionRule};
+    /// use bpx::Interface;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().with_checksum(Checksum::Weak).build()).unwrap();
+    /// let policy = SectionPolicy::new(SectionRule {
+    ///     min_checksum: Some(Checksum::Crc32),
+    ///     allowed_compression: None
+    /// });
+    /// let violations = file.policy_check(&policy);
+    /// assert_eq!(violations[0].to_string(), "section 0 (type 0x00): checksum weak is weaker than crc32");
+    /// ```
+    fn policy_check(&self, policy: &policy::SectionPolicy) -> Vec<policy::PolicyViolation>
+    {
+        return self
+            .iter_sections()
+            .flat_map(|handle| policy.check(self.get_section_index(handle), self.get_section_header(handle)))
+            .collect();
+    }
+
+    /// Returns a copy of the headers of all sections, in index order.
+    ///
+    /// *The snapshot does not borrow the BPX, so it can be sent to another
+    /// thread, for example to plan which sections to load while this
+    /// interface keeps being used.*
+    ///
+    /// returns: Vec<SectionHeader, Global>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    /// use bpx::builder::SectionHeaderBuilder;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().with_type(1).build()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().with_type(2).build()).unwrap();
+    /// let headers = file.section_headers_snapshot();
+    /// let types = std::thread::spawn(move || headers.iter().map(|v| v.btype).collect::<Vec<u8>>());
+    /// assert_eq!(types.join().unwrap(), [1, 2]);
+    /// ```
+    fn section_headers_snapshot(&self) -> Vec<header::SectionHeader>
+    {
+        return self.iter_sections().map(|v| *self.get_section_header(v)).collect();
+    }
+
+    /// Searches for the first section whose header matches a predicate.
+    /// Returns None if no section could be found.
+    ///
+    /// # Arguments
+    ///
+    /// * `pred`: the predicate to test each [SectionHeader](header::SectionHeader) against.
+    ///
+    /// returns: Option<SectionHandle>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    /// use bpx::builder::SectionHeaderBuilder;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().with_size(4).build()).unwrap();
+    /// let handle = file.create_section(SectionHeaderBuilder::new().with_size(16).build()).unwrap();
+    /// let found = file.find_section(|header| header.size > 8).unwrap();
+    /// assert_eq!(file.get_section_index(found), file.get_section_index(handle));
+    /// ```
+    fn find_section(&self, pred: impl Fn(&header::SectionHeader) -> bool) -> Option<SectionHandle>
+    where
+        Self: Sized
+    {
+        return self
+            .iter_sections()
+            .find(|handle| pred(self.get_section_header(*handle)));
+    }
+
+    /// Searches for all sections whose header matches a predicate.
+    ///
+    /// # Arguments
+    ///
+    /// * `pred`: the predicate to test each [SectionHeader](header::SectionHeader) against.
+    ///
+    /// returns: Vec<SectionHandle, Global>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    /// use bpx::builder::SectionHeaderBuilder;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().with_size(4).build()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().with_size(16).build()).unwrap();
+    /// assert_eq!(file.find_sections(|header| header.size > 0).len(), 2);
+    /// ```
+    fn find_sections(&self, pred: impl Fn(&header::SectionHeader) -> bool) -> Vec<SectionHandle>
+    where
+        Self: Sized
+    {
+        return self
+            .iter_sections()
+            .filter(|handle| pred(self.get_section_header(*handle)))
+            .collect();
+    }
 
     /// Locates a section by its index in the file.
     /// Returns None if the section does not exist.
@@ -112,6 +421,73 @@ pub trait Interface
     /// ```
     fn find_section_by_index(&self, index: u32) -> Option<SectionHandle>;
 
+    /// Searches for a section by the name attached to it in the section
+    /// names table (see [SectionNames](names::SectionNames)).
+    /// Returns None if the file has no section names table or no existing
+    /// section has this name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the name of the section to search for.
+    ///
+    /// returns: Result<Option<SectionHandle>, Error>
+    ///
+    /// # Errors
+    ///
+    /// A BPX [Error](error::Error) if the section names table could not be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// assert!(file.find_section_by_name("metadata").unwrap().is_none());
+    /// ```
+    fn find_section_by_name(&mut self, name: &str) -> Result<Option<SectionHandle>>
+    where
+        Self: Sized
+    {
+        return match self.find_section_by_type(header::SECTION_TYPE_NAMES) {
+            Some(table) => names::SectionNames::new(table).find(self, name),
+            None => Ok(None)
+        };
+    }
+
+    /// Reads the build identifier stored in the build identifier section
+    /// (see [SECTION_TYPE_BUILD_ID](header::SECTION_TYPE_BUILD_ID)).
+    /// Returns None if the file has no build identifier.
+    ///
+    /// *The identifier is an ordinary section: the BPX Main Header checksum
+    /// covers its section header and the section carries a CRC32 of the
+    /// identifier, checked when it is loaded.*
+    ///
+    /// returns: Result<Option<u64>, Error>
+    ///
+    /// # Errors
+    ///
+    /// A BPX [Error](error::Error) if the build identifier section could not
+    /// be loaded or does not contain exactly 8 bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// assert_eq!(file.build_id().unwrap(), None);
+    /// file.set_build_id(0x0123456789ABCDEF).unwrap();
+    /// assert_eq!(file.build_id().unwrap(), Some(0x0123456789ABCDEF));
+    /// ```
+    fn build_id(&mut self) -> Result<Option<u64>>
+    where
+        Self: Sized
+    {
+        return build_id::read_build_id(self);
+    }
+
     /// Returns the BPX section header of a section.
     ///
     /// # Arguments
@@ -122,7 +498,9 @@ pub trait Interface
     ///
     /// # Panics
     ///
-    /// Panics if the given section handle is invalid.
+    /// Panics if the given section handle is invalid, use
+    /// [try_get_section_header](Interface::try_get_section_header) for
+    /// handles which may not belong to this BPX.
     ///
     /// # Examples
     ///
@@ -138,6 +516,32 @@ pub trait Interface
     /// ```
     fn get_section_header(&self, handle: SectionHandle) -> &header::SectionHeader;
 
+    /// Returns the BPX section header of a section.
+    /// Returns None if the handle does not point to a section of this BPX.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: Option<&SectionHeader>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    /// use bpx::builder::SectionHeaderBuilder;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let mut other = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().build()).unwrap();
+    /// let handle = file.create_section(SectionHeaderBuilder::new().build()).unwrap();
+    /// assert!(file.try_get_section_header(handle).is_some());
+    /// assert!(other.try_get_section_header(handle).is_none());
+    /// assert!(other.open_section(handle).is_err());
+    /// ```
+    fn try_get_section_header(&self, handle: SectionHandle) -> Option<&header::SectionHeader>;
+
     /// Returns the section index from a section handle.
     ///
     /// # Arguments
@@ -165,20 +569,23 @@ pub trait Interface
 
     /// Opens a section for read and/or write.
     ///
+    /// *The cursor position of the section is restored when the returned
+    /// [SectionGuard](section::SectionGuard) is dropped. As a consequence a
+    /// section is always opened at the position it had when it was created or
+    /// loaded, that is the start of its data, and code writing to the end of a
+    /// section must seek there first.*
+    ///
     /// # Arguments
     ///
     /// * `handle`: a handle to the section.
     ///
-    /// returns: Result<&mut dyn SectionData, Error>
+    /// returns: Result<SectionGuard, Error>
     ///
     /// # Errors
     ///
-    /// A BPX [Error](error::Error) if an IO or any other file error occurs
-    /// while reading the section from the file.
-    ///
-    /// # Panics
-    ///
-    /// Panics if the given section handle is invalid.
+    /// A BPX [Error](error::Error) if the handle does not point to a section
+    /// of this BPX or if an IO or any other file error occurs while reading the
+    /// section from the file.
     ///
     /// # Examples
     ///
@@ -189,11 +596,98 @@ pub trait Interface
     ///
     /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
     /// let handle = file.create_section(SectionHeaderBuilder::new().build()).unwrap();
-    /// let section = file.open_section(handle).unwrap();
+    /// let mut section = file.open_section(handle).unwrap();
     /// let data = section.load_in_memory().unwrap();
     /// assert_eq!(data.len(), 0);
     /// ```
-    fn open_section(&mut self, handle: SectionHandle) -> Result<&mut dyn section::SectionData>;
+    fn open_section(&mut self, handle: SectionHandle) -> Result<section::SectionGuard<'_>>;
+
+    /// Reads the bytes of a section starting at `offset` into `buf`.
+    ///
+    /// *The cursor position of the section is left untouched. The section is
+    /// opened with [open_section](Interface::open_section) which, for a
+    /// [Decoder](decoder::Decoder), loads it fully the first time.*
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    /// * `offset`: the offset in the section of the first byte to read.
+    /// * `buf`: the buffer to fill.
+    ///
+    /// returns: Result<usize, Error>
+    ///
+    /// # Errors
+    ///
+    /// A BPX [Error](error::Error) if the section could not be opened, or
+    /// [Truncation](error::Error::Truncation) if the range does not lie
+    /// within the section.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    /// use bpx::builder::SectionHeaderBuilder;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let handle = file.create_section(SectionHeaderBuilder::new().build()).unwrap();
+    /// file.open_section(handle).unwrap().write_all(b"hello world").unwrap();
+    /// let mut buf = [0; 5];
+    /// assert_eq!(file.read_section_range(handle, 6, &mut buf).unwrap(), 5);
+    /// assert_eq!(&buf, b"world");
+    /// assert!(file.read_section_range(handle, 7, &mut buf).is_err());
+    /// ```
+    fn read_section_range(&mut self, handle: SectionHandle, offset: u64, buf: &mut [u8]) -> Result<usize>
+    {
+        let context = error::section_context(self, handle);
+        let mut data = self.open_section(handle)?;
+        match offset.checked_add(buf.len() as u64) {
+            Some(end) if end <= data.size() as u64 => (),
+            _ => return Err(context(Error::Truncation("read section range")))
+        }
+        data.seek(SeekFrom::Start(offset)).map_err(|e| context(e.into()))?;
+        data.read_exact(buf).map_err(|e| context(e.into()))?;
+        return Ok(buf.len());
+    }
+
+    /// Returns the number of bytes which can still be added to a section before
+    /// it exceeds [MAX_SECTION_SIZE](format::MAX_SECTION_SIZE).
+    ///
+    /// *Writes past the format maximum fail (see [SectionData](section::SectionData)) and
+    /// saving a larger section fails with a [Capacity](error::Error::Capacity) error;
+    /// the returned value is 0 for such a section.*
+    ///
+    /// *Decoders and containers read the size of a section which is not loaded from
+    /// its header instead of opening it.*
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: Result<u64, Error>
+    ///
+    /// # Errors
+    ///
+    /// A BPX [Error](error::Error) if the section could not be opened.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::format::MAX_SECTION_SIZE;
+    /// use bpx::Interface;
+    /// use bpx::builder::SectionHeaderBuilder;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let handle = file.create_section(SectionHeaderBuilder::new().build()).unwrap();
+    /// file.open_section(handle).unwrap().write_all(b"hello").unwrap();
+    /// assert_eq!(file.section_remaining_capacity(handle).unwrap(), MAX_SECTION_SIZE - 5);
+    /// ```
+    fn section_remaining_capacity(&mut self, handle: SectionHandle) -> Result<u64>
+    {
+        let size = self.open_section(handle)?.size() as u64;
+        return Ok(format::MAX_SECTION_SIZE.saturating_sub(size));
+    }
 
     /// Returns a read-only reference to the BPX main header.
     ///
@@ -210,6 +704,133 @@ pub trait Interface
     /// assert_eq!(header.btype, 'P' as u8);
     /// ```
     fn get_main_header(&self) -> &header::MainHeader;
+
+    /// Returns the [MemoryBudget](section::MemoryBudget) allocations made while
+    /// reading this BPX are checked against, if any.
+    ///
+    /// *Only [Decoder](decoder::Decoder) has a budget, see
+    /// [with_memory_budget](decoder::Decoder::with_memory_budget).*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::Interface;
+    ///
+    /// let file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// assert!(file.memory_budget().is_none());
+    /// ```
+    fn memory_budget(&self) -> Option<&section::MemoryBudget>
+    {
+        return None;
+    }
+
+    /// Returns the memory and temporary file usage of the loaded sections, see
+    /// [StorageReport](section::StorageReport).
+    ///
+    /// *Sections which were not loaded yet are not listed. The default
+    /// implementation reports no section; [Decoder](decoder::Decoder),
+    /// [SliceDecoder](decoder::SliceDecoder), [Encoder](encoder::Encoder) and
+    /// [Container](container::Container) report all their loaded sections.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::SectionHeaderBuilder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::section::StorageKind;
+    /// use bpx::Interface;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// file.create_section(SectionHeaderBuilder::new().with_size(64).build()).unwrap();
+    /// let report = file.storage_report();
+    /// assert_eq!(report.sections.len(), 1);
+    /// assert_eq!(report.sections[0].kind, StorageKind::Memory);
+    /// assert_eq!(report.temp_file_bytes(), 0);
+    /// ```
+    fn storage_report(&self) -> section::StorageReport
+    {
+        return section::StorageReport::default();
+    }
+
+    /// Drops the loaded content of a section, it is read again the next time the section is opened.
+    ///
+    /// *This is a hint for readers which are done with a section. The default
+    /// implementation keeps the section and returns false; only
+    /// [Decoder](decoder::Decoder) drops sections, see
+    /// [evict](decoder::Decoder::evict).*
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: a handle to the section.
+    ///
+    /// returns: bool; true if the section was loaded and has been dropped.
+    fn evict_section(&mut self, _handle: SectionHandle) -> bool
+    {
+        return false;
+    }
+}
+
+/// The interface implemented by both the BPX encoder and container to create sections.
+///
+/// *This allows variant encoders to write either to a new file through an
+/// [Encoder](encoder::Encoder) or to an existing file through a
+/// [Container](container::Container).*
+pub trait WriteInterface: Interface
+{
+    /// Sets the BPX Main Header.
+    ///
+    /// # Arguments
+    ///
+    /// * `main_header`: the new [MainHeader](header::MainHeader).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::MainHeaderBuilder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::{Interface, WriteInterface};
+    ///
+    /// fn set_type<T: WriteInterface>(file: &mut T)
+    /// {
+    ///     file.set_main_header(MainHeaderBuilder::new().with_type(1).build());
+    /// }
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// set_type(&mut file);
+    /// assert_eq!(file.get_main_header().btype, 1);
+    /// ```
+    fn set_main_header(&mut self, main_header: header::MainHeader);
+
+    /// Creates a new section.
+    ///
+    /// # Arguments
+    ///
+    /// * `header`: the [SectionHeader](header::SectionHeader) of the new section.
+    ///
+    /// returns: Result<SectionHandle, Error>
+    ///
+    /// # Errors
+    ///
+    /// A BPX [Error](error::Error) if the section data could not be allocated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::header::SectionHeader;
+    /// use bpx::{Interface, WriteInterface};
+    ///
+    /// fn add_section<T: WriteInterface>(file: &mut T) -> bpx::Result<bpx::SectionHandle>
+    /// {
+    ///     return file.create_section(SectionHeader::new());
+    /// }
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let handle = add_section(&mut file).unwrap();
+    /// assert_eq!(file.get_section_index(handle), 0);
+    /// ```
+    fn create_section(&mut self, header: header::SectionHeader) -> Result<SectionHandle>;
 }
 
 /// Represents a result from this library.
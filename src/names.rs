@@ -0,0 +1,264 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! A set of helpers to attach names to sections.
+//!
+//! Names are stored in a dedicated section of type
+//! [SECTION_TYPE_NAMES](crate::header::SECTION_TYPE_NAMES) as a list of
+//! records, each made of a little endian u32 section index followed by
+//! a null-terminated UTF-8 name. When a section is named more than once
+//! the last record wins.
+
+use std::{collections::HashMap, io::SeekFrom, string::String};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    builder::{Checksum, SectionHeaderBuilder},
+    error::{section_context, Error},
+    header::SECTION_TYPE_NAMES,
+    section::SectionData,
+    Interface,
+    Result,
+    SectionHandle,
+    WriteInterface
+};
+
+/// Helper class to manage a BPX section names table.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::builder::SectionHeaderBuilder;
+/// use bpx::encoder::Encoder;
+/// use bpx::header::{SectionHeader, SECTION_TYPE_NAMES};
+/// use bpx::names::SectionNames;
+///
+/// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+/// let section = file.create_section(SectionHeader::new()).unwrap();
+/// let table = file.create_section(SectionHeaderBuilder::new().with_type(SECTION_TYPE_NAMES).build()).unwrap();
+/// let mut names = SectionNames::new(table);
+/// names.put(&mut file, section, "metadata").unwrap();
+/// assert_eq!(names.get(&mut file, section).unwrap(), Some("metadata"));
+/// assert!(names.find(&mut file, "metadata").unwrap().is_some());
+/// ```
+pub struct SectionNames
+{
+    handle: SectionHandle,
+    names: Option<HashMap<u32, String>>
+}
+
+impl SectionNames
+{
+    /// Create a new section names table from a handle.
+    ///
+    /// # Arguments
+    ///
+    /// * `hdl`: handle to the section names table.
+    ///
+    /// returns: SectionNames
+    pub fn new(hdl: SectionHandle) -> SectionNames
+    {
+        return SectionNames {
+            handle: hdl,
+            names: None
+        };
+    }
+
+    fn load<TInterface: Interface>(&mut self, interface: &mut TInterface) -> Result<&mut HashMap<u32, String>>
+    {
+        if self.names.is_none() {
+            let context = section_context(interface, self.handle);
+            let mut data = interface.open_section(self.handle)?;
+            self.names = Some(low_level_read_names(&mut *data).map_err(context)?);
+        }
+        return Ok(self.names.get_or_insert_with(HashMap::new));
+    }
+
+    /// Reads the name of a section.
+    /// Returns None if the section has no name.
+    ///
+    /// # Arguments
+    ///
+    /// * `interface`: the BPX IO interface.
+    /// * `section`: a handle to the section to get the name of.
+    ///
+    /// returns: Result<Option<&str>, Error>
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](crate::error::Error) if the table could not be read or the
+    /// section is corrupted/truncated.
+    pub fn get<TInterface: Interface>(
+        &mut self,
+        interface: &mut TInterface,
+        section: SectionHandle
+    ) -> Result<Option<&str>>
+    {
+        let index = interface.get_section_index(section);
+        let names = self.load(interface)?;
+        return Ok(names.get(&index).map(|v| v.as_str()));
+    }
+
+    /// Searches for a section by name.
+    /// Returns None if no existing section has this name.
+    ///
+    /// *A name which refers to a section index that no longer exists is ignored.*
+    ///
+    /// # Arguments
+    ///
+    /// * `interface`: the BPX IO interface.
+    /// * `name`: the name of the section to search for.
+    ///
+    /// returns: Result<Option<SectionHandle>, Error>
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](crate::error::Error) if the table could not be read or the
+    /// section is corrupted/truncated.
+    pub fn find<TInterface: Interface>(
+        &mut self,
+        interface: &mut TInterface,
+        name: &str
+    ) -> Result<Option<SectionHandle>>
+    {
+        let names = self.load(interface)?;
+        let mut indices: Vec<u32> = names.iter().filter(|(_, v)| *v == name).map(|(k, _)| *k).collect();
+        indices.sort_unstable();
+        for index in indices {
+            if let Some(handle) = interface.find_section_by_index(index) {
+                return Ok(Some(handle));
+            }
+        }
+        return Ok(None);
+    }
+
+    /// Writes a new name for a section into the table.
+    ///
+    /// # Arguments
+    ///
+    /// * `interface`: the BPX IO interface.
+    /// * `section`: a handle to the section to name.
+    /// * `name`: the name of the section.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](crate::error::Error) if the name is empty, contains a null
+    /// byte, is already used by another section or could not be written.
+    pub fn put<TInterface: Interface>(
+        &mut self,
+        interface: &mut TInterface,
+        section: SectionHandle,
+        name: &str
+    ) -> Result<()>
+    {
+        if name.is_empty() || name.contains('\0') {
+            return Err(Error::Other(format!("invalid section name '{}'", name.escape_default())));
+        }
+        let index = interface.get_section_index(section);
+        let names = self.load(interface)?;
+        if names.iter().any(|(k, v)| *k != index && v == name) {
+            return Err(Error::Other(format!("section name '{}' is already in use", name)));
+        }
+        {
+            let context = section_context(interface, self.handle);
+            let mut data = interface.open_section(self.handle)?;
+            low_level_write_name(index, name, &mut *data).map_err(context)?;
+        }
+        self.load(interface)?.insert(index, String::from(name));
+        return Ok(());
+    }
+}
+
+/// Attaches a name to a section, creating the section names table if needed.
+pub(crate) fn name_section<TInterface: WriteInterface>(
+    interface: &mut TInterface,
+    handle: SectionHandle,
+    name: &str
+) -> Result<()>
+{
+    let table = match interface.find_section_by_type(SECTION_TYPE_NAMES) {
+        Some(v) => v,
+        None => interface.create_section(
+            SectionHeaderBuilder::new()
+                .with_type(SECTION_TYPE_NAMES)
+                .with_checksum(Checksum::Weak)
+                .build()
+        )?
+    };
+    return SectionNames::new(table).put(interface, handle, name);
+}
+
+pub(crate) fn low_level_read_names(section: &mut dyn SectionData) -> Result<HashMap<u32, String>>
+{
+    let mut buf = vec![0; section.size()];
+
+    section.seek(SeekFrom::Start(0))?;
+    section.read_exact(&mut buf)?;
+    return parse_names(&buf);
+}
+
+pub(crate) fn parse_names(buf: &[u8]) -> Result<HashMap<u32, String>>
+{
+    let mut names = HashMap::new();
+    let mut pos = 0;
+
+    while pos < buf.len() {
+        if buf.len() - pos < 4 {
+            return Err(Error::Truncation("section names read"));
+        }
+        let index = LittleEndian::read_u32(&buf[pos..pos + 4]);
+        pos += 4;
+        let len = match buf[pos..].iter().position(|v| *v == 0x0) {
+            Some(v) => v,
+            None => return Err(Error::Truncation("section names read"))
+        };
+        let name = match std::str::from_utf8(&buf[pos..pos + len]) {
+            Ok(v) => String::from(v),
+            Err(_) => return Err(Error::Utf8("section names read"))
+        };
+        names.insert(index, name);
+        pos += len + 1;
+    }
+    return Ok(names);
+}
+
+pub(crate) fn low_level_write_name(index: u32, name: &str, section: &mut dyn SectionData) -> Result<()>
+{
+    let mut buf: [u8; 4] = [0; 4];
+
+    LittleEndian::write_u32(&mut buf, index);
+    section.seek(SeekFrom::End(0))?;
+    section.write_all(&buf)?;
+    section.write_all(name.as_bytes())?;
+    section.write_all(&[0x0])?;
+    return Ok(());
+}
+
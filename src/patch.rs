@@ -0,0 +1,397 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Section-aware binary patches between two BPX files.
+//!
+//! A patch rebuilds the new file byte by byte from a list of operations: copy a
+//! range of bytes from the old file, or insert literal bytes stored in the patch.
+//! Sections of the new file whose stored bytes exist as a section of the old file
+//! are copied, everything else (headers, changed and added sections, trailing data)
+//! is stored literally.
+//!
+//! Patch format (version 1, all integers little endian):
+//!
+//! * [PATCH_SIGNATURE](self::PATCH_SIGNATURE) followed by the u32 [PATCH_VERSION](self::PATCH_VERSION).
+//! * u64 `file_size` and u32 `chksum` of the old BPX Main Header, to reject patches applied to the wrong file.
+//! * u8 operation code followed by the operation:
+//!     * [OP_COPY](self::OP_COPY): u64 offset in the old file, u64 length.
+//!     * [OP_DATA](self::OP_DATA): u64 length, followed by the literal bytes.
+//!     * [OP_END](self::OP_END): u64 size of the new file and u32 CRC32 of the new file; ends the patch.
+
+use std::{
+    collections::HashMap,
+    io::{Read, SeekFrom, Write}
+};
+
+use crate::{
+    compression::{Checksum, Crc32Checksum},
+    decoder::{Decoder, IoBackend},
+    error::Error,
+    Interface,
+    Result
+};
+
+/// The signature of a BPX patch.
+pub const PATCH_SIGNATURE: [u8; 4] = *b"BPXT";
+
+/// The version of the patch format written by [create](self::create).
+pub const PATCH_VERSION: u32 = 1;
+
+/// Patch operation ending the patch.
+pub const OP_END: u8 = 0x0;
+
+/// Patch operation copying bytes from the old file.
+pub const OP_COPY: u8 = 0x1;
+
+/// Patch operation inserting literal bytes.
+pub const OP_DATA: u8 = 0x2;
+
+const COPY_BUFFER_SIZE: usize = 8192;
+
+/// Statistics about a patch, as returned by [create](self::create).
+///
+/// *Sections are compared by index: a section present at the same index in both
+/// files is either unchanged or changed.*
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatchStats
+{
+    /// The number of sections whose stored bytes are identical in both files.
+    pub unchanged: usize,
+
+    /// The number of sections whose stored bytes differ.
+    pub changed: usize,
+
+    /// The number of sections only present in the new file.
+    pub added: usize,
+
+    /// The number of sections only present in the old file.
+    pub removed: usize,
+
+    /// The number of bytes copied from the old file.
+    pub copied_bytes: u64,
+
+    /// The number of literal bytes stored in the patch.
+    pub literal_bytes: u64
+}
+
+struct PatchWriter<'a, TWrite: Write>
+{
+    out: &'a mut TWrite,
+    chksum: Crc32Checksum,
+    size: u64
+}
+
+impl<'a, TWrite: Write> PatchWriter<'a, TWrite>
+{
+    fn copy(&mut self, offset: u64, data: &[u8]) -> Result<()>
+    {
+        self.out.write_all(&[OP_COPY])?;
+        self.out.write_all(&offset.to_le_bytes())?;
+        self.out.write_all(&(data.len() as u64).to_le_bytes())?;
+        self.chksum.push(data);
+        self.size += data.len() as u64;
+        return Ok(());
+    }
+
+    fn data(&mut self, data: &[u8]) -> Result<()>
+    {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.out.write_all(&[OP_DATA])?;
+        self.out.write_all(&(data.len() as u64).to_le_bytes())?;
+        self.out.write_all(data)?;
+        self.chksum.push(data);
+        self.size += data.len() as u64;
+        return Ok(());
+    }
+
+    fn data_from<TRead: Read>(&mut self, source: &mut TRead, len: u64) -> Result<()>
+    {
+        let mut buf: [u8; COPY_BUFFER_SIZE] = [0; COPY_BUFFER_SIZE];
+        let mut count = 0;
+
+        while count < len {
+            let size = std::cmp::min(COPY_BUFFER_SIZE as u64, len - count) as usize;
+            source.read_exact(&mut buf[..size])?;
+            self.data(&buf[..size])?;
+            count += size as u64;
+        }
+        return Ok(());
+    }
+}
+
+fn read_raw<TBackend: IoBackend>(file: &mut TBackend, pointer: u64, size: u32) -> Result<Vec<u8>>
+{
+    let mut buf = vec![0; size as usize];
+    file.seek(SeekFrom::Start(pointer))?;
+    file.read_exact(&mut buf)?;
+    return Ok(buf);
+}
+
+/// Creates a patch rebuilding `new` from `old`.
+///
+/// # Arguments
+///
+/// * `old`: the BPX [Decoder](crate::decoder::Decoder) of the file the patch applies to.
+/// * `new`: the BPX [Decoder](crate::decoder::Decoder) of the file the patch produces.
+/// * `out`: the destination of the patch.
+///
+/// returns: Result<PatchStats, Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned if one of the files could not be
+/// read or the patch could not be written.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{Cursor, Write};
+/// use bpx::builder::SectionHeaderBuilder;
+/// use bpx::decoder::Decoder;
+/// use bpx::encoder::Encoder;
+/// use bpx::patch;
+///
+/// let mut old = Vec::new();
+/// let mut encoder = Encoder::new(Cursor::new(&mut old)).unwrap();
+/// encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+/// encoder.save().unwrap();
+/// let mut new = Vec::new();
+/// let mut encoder = Encoder::new(Cursor::new(&mut new)).unwrap();
+/// encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+/// encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+/// encoder.save().unwrap();
+///
+/// let mut old = Decoder::new(Cursor::new(old)).unwrap();
+/// let mut data = Vec::new();
+/// let stats = patch::create(&mut old, &mut Decoder::new(Cursor::new(new.clone())).unwrap(), &mut data).unwrap();
+/// assert_eq!(stats.added, 1);
+/// let mut rebuilt = Vec::new();
+/// patch::apply(&mut old, &mut data.as_slice(), &mut rebuilt).unwrap();
+/// assert_eq!(rebuilt, new);
+/// ```
+pub fn create<TOld: IoBackend, TNew: IoBackend, TWrite: Write>(
+    old: &mut Decoder<TOld>,
+    new: &mut Decoder<TNew>,
+    out: &mut TWrite
+) -> Result<PatchStats>
+{
+    let old_header = *old.get_main_header();
+    let mut stats = PatchStats::default();
+    let mut candidates: HashMap<(u32, u32), Vec<u32>> = HashMap::new();
+    let old_sections: Vec<_> = old.iter_sections().map(|v| *old.get_section_header(v)).collect();
+    let new_sections: Vec<_> = new.iter_sections().map(|v| *new.get_section_header(v)).collect();
+
+    for (i, header) in old_sections.iter().enumerate() {
+        candidates
+            .entry((header.csize, header.chksum))
+            .or_default()
+            .push(i as u32);
+    }
+    out.write_all(&PATCH_SIGNATURE)?;
+    out.write_all(&PATCH_VERSION.to_le_bytes())?;
+    out.write_all(&old_header.file_size.to_le_bytes())?;
+    out.write_all(&old_header.chksum.to_le_bytes())?;
+    let mut writer = PatchWriter {
+        out,
+        chksum: Crc32Checksum::new(),
+        size: 0
+    };
+    let mut order: Vec<usize> = (0..new_sections.len()).collect();
+    order.sort_by_key(|i| new_sections[*i].pointer);
+    let new_len = new.get_backend_mut().seek(SeekFrom::End(0))?;
+    let mut pos: u64 = 0;
+    for i in order {
+        let header = &new_sections[i];
+        let end = header.pointer + header.csize as u64;
+        // Empty or overlapping sections are covered by the literal ranges.
+        let emit = header.csize > 0 && header.pointer >= pos && end <= new_len;
+        let data = if header.csize > 0 && end <= new_len {
+            read_raw(new.get_backend_mut(), header.pointer, header.csize)?
+        } else {
+            Vec::new()
+        };
+        let mut source = None;
+        if let Some(list) = candidates.get(&(header.csize, header.chksum)) {
+            // Prefer the section at the same index so that it is counted as unchanged.
+            let same_index = list.iter().filter(|v| **v as usize == i);
+            for index in same_index.chain(list.iter().filter(|v| **v as usize != i)) {
+                let old_header = &old_sections[*index as usize];
+                if read_raw(old.get_backend_mut(), old_header.pointer, old_header.csize)? == data {
+                    source = Some(*index);
+                    break;
+                }
+            }
+        }
+        if i < old_sections.len() {
+            if source == Some(i as u32) {
+                stats.unchanged += 1;
+            } else {
+                stats.changed += 1;
+            }
+        } else {
+            stats.added += 1;
+        }
+        if !emit {
+            continue;
+        }
+        let backend = new.get_backend_mut();
+        backend.seek(SeekFrom::Start(pos))?;
+        writer.data_from(backend, header.pointer - pos)?;
+        stats.literal_bytes += header.pointer - pos;
+        match source {
+            Some(index) => {
+                writer.copy(old_sections[index as usize].pointer, &data)?;
+                stats.copied_bytes += data.len() as u64;
+            },
+            None => {
+                writer.data(&data)?;
+                stats.literal_bytes += data.len() as u64;
+            }
+        }
+        pos = end;
+    }
+    let backend = new.get_backend_mut();
+    backend.seek(SeekFrom::Start(pos))?;
+    writer.data_from(backend, new_len - pos)?;
+    stats.literal_bytes += new_len - pos;
+    stats.removed = old_sections.len().saturating_sub(new_sections.len());
+    writer.out.write_all(&[OP_END])?;
+    writer.out.write_all(&writer.size.to_le_bytes())?;
+    writer.out.write_all(&writer.chksum.finish().to_le_bytes())?;
+    return Ok(stats);
+}
+
+fn read_u64<TRead: Read>(patch: &mut TRead) -> Result<u64>
+{
+    let mut buf: [u8; 8] = [0; 8];
+    patch.read_exact(&mut buf).map_err(|_| Error::Truncation("read patch"))?;
+    return Ok(u64::from_le_bytes(buf));
+}
+
+fn read_u32<TRead: Read>(patch: &mut TRead) -> Result<u32>
+{
+    let mut buf: [u8; 4] = [0; 4];
+    patch.read_exact(&mut buf).map_err(|_| Error::Truncation("read patch"))?;
+    return Ok(u32::from_le_bytes(buf));
+}
+
+fn copy_bytes<TRead: Read, TWrite: Write>(
+    source: &mut TRead,
+    out: &mut TWrite,
+    chksum: &mut Crc32Checksum,
+    len: u64
+) -> Result<()>
+{
+    let mut buf: [u8; COPY_BUFFER_SIZE] = [0; COPY_BUFFER_SIZE];
+    let mut count = 0;
+
+    while count < len {
+        let size = std::cmp::min(COPY_BUFFER_SIZE as u64, len - count) as usize;
+        source
+            .read_exact(&mut buf[..size])
+            .map_err(|_| Error::Truncation("apply patch"))?;
+        chksum.push(&buf[..size]);
+        out.write_all(&buf[..size])?;
+        count += size as u64;
+    }
+    return Ok(());
+}
+
+/// Applies a patch created by [create](self::create) to rebuild the new file.
+///
+/// # Arguments
+///
+/// * `old`: the BPX [Decoder](crate::decoder::Decoder) of the file the patch applies to.
+/// * `patch`: the patch to apply.
+/// * `out`: the destination of the new file.
+///
+/// returns: Result<(), Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned if the patch is malformed,
+/// does not apply to `old`, if the rebuilt file does not match the CRC32
+/// recorded in the patch or in case of IO error.
+pub fn apply<TOld: IoBackend, TRead: Read, TWrite: Write>(
+    old: &mut Decoder<TOld>,
+    patch: &mut TRead,
+    out: &mut TWrite
+) -> Result<()>
+{
+    let mut signature: [u8; 4] = [0; 4];
+    patch
+        .read_exact(&mut signature)
+        .map_err(|_| Error::Truncation("read patch"))?;
+    if signature != PATCH_SIGNATURE {
+        return Err(Error::Corruption(String::from("Not a BPX patch")));
+    }
+    let version = read_u32(patch)?;
+    if version != PATCH_VERSION {
+        return Err(Error::Unsupported(format!("BPX patch version {}", version)));
+    }
+    let file_size = read_u64(patch)?;
+    let chksum = read_u32(patch)?;
+    if file_size != old.get_main_header().file_size || chksum != old.get_main_header().chksum {
+        return Err(Error::Corruption(String::from("BPX patch does not apply to this file")));
+    }
+    let mut actual = Crc32Checksum::new();
+    let mut size: u64 = 0;
+    loop {
+        let mut op: [u8; 1] = [0; 1];
+        patch.read_exact(&mut op).map_err(|_| Error::Truncation("read patch"))?;
+        match op[0] {
+            OP_COPY => {
+                let offset = read_u64(patch)?;
+                let len = read_u64(patch)?;
+                let backend = old.get_backend_mut();
+                backend.seek(SeekFrom::Start(offset))?;
+                copy_bytes(backend, out, &mut actual, len)?;
+                size += len;
+            },
+            OP_DATA => {
+                let len = read_u64(patch)?;
+                copy_bytes(patch, out, &mut actual, len)?;
+                size += len;
+            },
+            OP_END => break,
+            op => return Err(Error::Corruption(format!("Unknown BPX patch operation {:#x}", op)))
+        }
+    }
+    let expected_size = read_u64(patch)?;
+    let expected = read_u32(patch)?;
+    if size != expected_size {
+        return Err(Error::Truncation("apply patch"));
+    }
+    let actual = actual.finish();
+    if actual != expected {
+        return Err(Error::Checksum(actual, expected));
+    }
+    return Ok(());
+}
@@ -0,0 +1,298 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Checks of the compression and checksum used by each section of a BPX.
+//!
+//! *A [SectionPolicy](self::SectionPolicy) describes, for each section type,
+//! the weakest accepted checksum and the accepted compression methods; see
+//! [policy_check](crate::Interface::policy_check).*
+
+use std::fmt::{Display, Formatter};
+
+use crate::{
+    builder::{Checksum, CompressionMethod},
+    header::SectionHeader
+};
+
+/// The compression and checksum used by a section, as returned by
+/// [section_info](crate::Interface::section_info).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SectionInfo
+{
+    /// The compression method, None if the section is stored uncompressed.
+    pub compression: Option<CompressionMethod>,
+
+    /// The checksum algorithm, None if the section has no checksum.
+    pub checksum: Option<Checksum>,
+
+    /// The size of the section data.
+    pub size: u32,
+
+    /// The size of the section data as stored in the file.
+    pub csize: u32,
+
+    /// The stored size divided by the size; 1 for empty sections.
+    pub ratio: f64
+}
+
+impl SectionInfo
+{
+    /// Describes a section from its header.
+    ///
+    /// # Arguments
+    ///
+    /// * `header`: the [SectionHeader](crate::header::SectionHeader) to describe.
+    ///
+    /// returns: SectionInfo
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::{Checksum, SectionHeaderBuilder};
+    /// use bpx::policy::SectionInfo;
+    ///
+    /// let mut header = SectionHeaderBuilder::new().with_checksum(Checksum::Crc32).build();
+    /// header.size = 200;
+    /// header.csize = 50;
+    /// let info = SectionInfo::new(&header);
+    /// assert_eq!(info.compression, None);
+    /// assert_eq!(info.checksum, Some(Checksum::Crc32));
+    /// assert_eq!(info.ratio, 0.25);
+    /// ```
+    pub fn new(header: &SectionHeader) -> SectionInfo
+    {
+        let ratio = match header.size {
+            0 => 1.0,
+            size => header.csize as f64 / size as f64
+        };
+        return SectionInfo {
+            compression: header.compression(),
+            checksum: header.checksum(),
+            size: header.size,
+            csize: header.csize,
+            ratio
+        };
+    }
+}
+
+/// The requirements on the sections of a given type.
+///
+/// *The default rule accepts every section.*
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SectionRule
+{
+    /// The weakest accepted checksum, None to accept sections without checksum.
+    ///
+    /// *A CRC32 checksum is stronger than a weak checksum.*
+    pub min_checksum: Option<Checksum>,
+
+    /// The accepted compression methods, None in the list standing for
+    /// uncompressed sections; None to accept any compression.
+    pub allowed_compression: Option<Vec<Option<CompressionMethod>>>
+}
+
+/// The requirements on the sections of a BPX.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::builder::{Checksum, CompressionMethod};
+/// use bpx::policy::{SectionPolicy, SectionRule};
+///
+/// let policy = SectionPolicy::new(SectionRule {
+///     min_checksum: Some(Checksum::Crc32),
+///     allowed_compression: Some(vec![None, Some(CompressionMethod::Xz)])
+/// })
+/// .with_type(0xFF, SectionRule::default());
+/// assert_eq!(policy.rule(0xFF), &SectionRule::default());
+/// assert_eq!(policy.rule(1).min_checksum, Some(Checksum::Crc32));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SectionPolicy
+{
+    default: SectionRule,
+    types: Vec<(u8, SectionRule)>
+}
+
+impl SectionPolicy
+{
+    /// Creates a new policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `default`: the rule for section types without a specific rule.
+    ///
+    /// returns: SectionPolicy
+    pub fn new(default: SectionRule) -> SectionPolicy
+    {
+        return SectionPolicy {
+            default,
+            types: Vec::new()
+        };
+    }
+
+    /// Sets the rule for a section type, replacing any previous rule for this type.
+    ///
+    /// # Arguments
+    ///
+    /// * `btype`: the section type byte.
+    /// * `rule`: the [SectionRule](self::SectionRule) of sections of this type.
+    ///
+    /// returns: SectionPolicy
+    pub fn with_type(mut self, btype: u8, rule: SectionRule) -> Self
+    {
+        self.types.retain(|(v, _)| *v != btype);
+        self.types.push((btype, rule));
+        return self;
+    }
+
+    /// Returns the rule applying to a section type.
+    ///
+    /// # Arguments
+    ///
+    /// * `btype`: the section type byte.
+    ///
+    /// returns: &SectionRule
+    pub fn rule(&self, btype: u8) -> &SectionRule
+    {
+        return self
+            .types
+            .iter()
+            .find(|(v, _)| *v == btype)
+            .map(|(_, rule)| rule)
+            .unwrap_or(&self.default);
+    }
+
+    /// Checks a single section against this policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: the index of the section.
+    /// * `header`: the [SectionHeader](crate::header::SectionHeader) of the section.
+    ///
+    /// returns: Vec<PolicyViolation, Global>
+    pub fn check(&self, index: u32, header: &SectionHeader) -> Vec<PolicyViolation>
+    {
+        let rule = self.rule(header.btype);
+        let info = SectionInfo::new(header);
+        let mut violations = Vec::new();
+        let violation = |kind| PolicyViolation {
+            index,
+            btype: header.btype,
+            kind
+        };
+
+        if let Some(required) = rule.min_checksum {
+            if checksum_strength(info.checksum) < checksum_strength(Some(required)) {
+                violations.push(violation(ViolationKind::Checksum(info.checksum, required)));
+            }
+        }
+        if let Some(allowed) = &rule.allowed_compression {
+            if !allowed.contains(&info.compression) {
+                violations.push(violation(ViolationKind::Compression(info.compression)));
+            }
+        }
+        return violations;
+    }
+}
+
+fn checksum_strength(checksum: Option<Checksum>) -> u8
+{
+    return match checksum {
+        None => 0,
+        Some(Checksum::Weak) => 1,
+        Some(Checksum::Crc32) => 2
+    };
+}
+
+fn checksum_name(checksum: Option<Checksum>) -> &'static str
+{
+    return match checksum {
+        None => "none",
+        Some(Checksum::Weak) => "weak",
+        Some(Checksum::Crc32) => "crc32"
+    };
+}
+
+fn compression_name(compression: Option<CompressionMethod>) -> &'static str
+{
+    return match compression {
+        None => "none",
+        Some(CompressionMethod::Xz) => "xz",
+        Some(CompressionMethod::Zlib) => "zlib"
+    };
+}
+
+/// The requirement a section does not meet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViolationKind
+{
+    /// The section checksum is weaker than required.
+    ///
+    /// *Contains the checksum of the section and the weakest accepted checksum.*
+    Checksum(Option<Checksum>, Checksum),
+
+    /// The section compression is not allowed.
+    ///
+    /// *Contains the compression of the section.*
+    Compression(Option<CompressionMethod>)
+}
+
+/// A section which does not meet a [SectionPolicy](self::SectionPolicy).
+///
+/// *The text rendering is stable, for example
+/// `section 2 (type 0x01): checksum weak is weaker than crc32`.*
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PolicyViolation
+{
+    /// The index of the section.
+    pub index: u32,
+
+    /// The type of the section.
+    pub btype: u8,
+
+    /// The requirement which is not met.
+    pub kind: ViolationKind
+}
+
+impl Display for PolicyViolation
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "section {} (type {:#04x}): ", self.index, self.btype)?;
+        return match self.kind {
+            ViolationKind::Checksum(found, required) => write!(
+                f,
+                "checksum {} is weaker than {}",
+                checksum_name(found),
+                checksum_name(Some(required))
+            ),
+            ViolationKind::Compression(found) => write!(f, "compression {} is not allowed", compression_name(found))
+        };
+    }
+}
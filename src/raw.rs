@@ -0,0 +1,269 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Low-level building blocks of the BPX encoder and decoder.
+//!
+//! These functions are the stages used internally by [Encoder](crate::encoder::Encoder)
+//! and [Decoder](crate::decoder::Decoder). They are exposed to benchmark or profile
+//! individual stages and to build alternative high-level layers; unlike the
+//! high-level types they perform no bookkeeping (no file size, no header table).
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::{
+    decoder,
+    encoder,
+    header::{MainHeader, SectionFlags, SectionHeader},
+    sd::Object,
+    section::SectionData,
+    strings,
+    Result
+};
+
+/// Computes the flags a section gets when it is written.
+///
+/// *The compression requested by the header only applies when the section
+/// is larger than the header `csize` field, which is the compression threshold.
+/// [Encoder](crate::encoder::Encoder) also stores sections smaller than its
+/// [minimum compression size](crate::encoder::Encoder::set_min_compress_size) uncompressed.*
+///
+/// # Arguments
+///
+/// * `header`: the [SectionHeader](crate::header::SectionHeader) as given to the encoder.
+/// * `size`: the size of the section data.
+///
+/// returns: SectionFlags
+pub fn section_flags(header: &SectionHeader, size: u32) -> SectionFlags
+{
+    return encoder::get_flags(header, size);
+}
+
+/// Writes the whole content of a section, compressing and computing its checksum
+/// according to `flags`.
+///
+/// # Arguments
+///
+/// * `flags`: the [SectionFlags](crate::header::SectionFlags) of the section.
+/// * `section`: the section data to write.
+/// * `out`: the destination.
+///
+/// returns: Result<(usize, u32), Error>; the number of bytes written and the
+/// checksum of the section (0 if no checksum is requested).
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned in case of IO or compression error.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+/// use bpx::header::SectionFlags;
+/// use bpx::raw::write_section_to;
+/// use bpx::section::new_section_data;
+///
+/// let mut section = new_section_data(None).unwrap();
+/// section.write_all(b"raw").unwrap();
+/// let mut out = Vec::new();
+/// let (size, _) = write_section_to(SectionFlags::CHECK_CRC32, section.as_mut(), &mut out).unwrap();
+/// assert_eq!(size, 3);
+/// assert_eq!(out, b"raw");
+/// ```
+pub fn write_section_to<TWrite: Write>(
+    flags: SectionFlags,
+    section: &mut dyn SectionData,
+    out: &mut TWrite
+) -> Result<(usize, u32)>
+{
+    section.seek(SeekFrom::Start(0))?;
+    return encoder::write_section(flags, section, out);
+}
+
+/// Copies exactly `len` bytes from `input` to `out`.
+///
+/// This is the stage used by the encoder to move the staged section data
+/// into the IO backend.
+///
+/// # Arguments
+///
+/// * `input`: the source.
+/// * `out`: the destination.
+/// * `len`: the number of bytes to copy.
+///
+/// returns: Result<(), Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned in case of IO error or if
+/// `input` ends before `len` bytes could be copied.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::raw::copy_exact;
+///
+/// let mut out = Vec::new();
+/// copy_exact(&b"section data"[..], &mut out, 7).unwrap();
+/// assert_eq!(out, b"section");
+/// assert!(copy_exact(&b"short"[..], &mut out, 7).is_err());
+/// ```
+pub fn copy_exact<TRead: Read, TWrite: Write>(input: TRead, out: &mut TWrite, len: u64) -> Result<()>
+{
+    return encoder::copy_exact(input, out, len);
+}
+
+/// Reads, decompresses and verifies the content of a section.
+///
+/// # Arguments
+///
+/// * `file`: the BPX to read from.
+/// * `header`: the [SectionHeader](crate::header::SectionHeader) of the section to read.
+///
+/// returns: Result<Box<dyn SectionData>, Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned in case of IO error, corruption or
+/// checksum mismatch.
+pub fn read_section_from<TBackend: Read + Seek>(
+    file: &mut TBackend,
+    header: &SectionHeader
+) -> Result<Box<dyn SectionData>>
+{
+    return decoder::load_section(file, header);
+}
+
+/// Reads and verifies the BPX Main Header and the section header table.
+///
+/// # Arguments
+///
+/// * `file`: the BPX to read from, positioned at the start of the main header.
+///
+/// returns: Result<(MainHeader, Vec<SectionHeader>), Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned if the headers could not be read,
+/// are corrupted or if the main header checksum does not match.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use bpx::builder::SectionHeaderBuilder;
+/// use bpx::encoder::Encoder;
+/// use bpx::raw::read_headers;
+///
+/// let mut buf = Vec::new();
+/// let mut encoder = Encoder::new(Cursor::new(&mut buf)).unwrap();
+/// encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+/// encoder.save().unwrap();
+/// let (main_header, sections) = read_headers(&mut Cursor::new(buf)).unwrap();
+/// assert_eq!(main_header.section_num, 1);
+/// assert_eq!(sections.len(), 1);
+/// ```
+pub fn read_headers<TBackend: Read + Seek>(file: &mut TBackend) -> Result<(MainHeader, Vec<SectionHeader>)>
+{
+    let (checksum, main_header) = MainHeader::read(file)?;
+    let sections = decoder::read_section_header_table(file, &main_header, checksum)?;
+    return Ok((main_header, sections));
+}
+
+/// Checks that a stream contains a valid BPXSD object.
+///
+/// # Arguments
+///
+/// * `source`: the encoded BPXSD object.
+///
+/// returns: Result<(), Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned if the object is truncated or corrupted.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::raw::sd_validate;
+/// use bpx::sd::Object;
+///
+/// let mut buf = Vec::new();
+/// Object::new().write(&mut buf).unwrap();
+/// assert!(sd_validate(&mut buf.as_slice()).is_ok());
+/// assert!(sd_validate(&mut &buf[1..]).is_err());
+/// ```
+pub fn sd_validate<TRead: Read>(source: &mut TRead) -> Result<()>
+{
+    Object::read(source)?;
+    return Ok(());
+}
+
+/// Reads a null-terminated string from a strings section.
+///
+/// # Arguments
+///
+/// * `ptr`: the offset to the start of the string.
+/// * `section`: the strings section.
+///
+/// returns: Result<String, Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned if the string is truncated or is not UTF-8.
+pub fn read_string(ptr: u32, section: &mut dyn SectionData) -> Result<String>
+{
+    return strings::low_level_read_string(ptr, section);
+}
+
+/// Appends a null-terminated string to a strings section.
+///
+/// # Arguments
+///
+/// * `s`: the string to write.
+/// * `section`: the strings section.
+///
+/// returns: Result<u32, Error>; the offset to the start of the string.
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned in case of IO error.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::raw::{read_string, write_string};
+/// use bpx::section::new_section_data;
+///
+/// let mut section = new_section_data(None).unwrap();
+/// let ptr = write_string("hello", section.as_mut()).unwrap();
+/// assert_eq!(read_string(ptr, section.as_mut()).unwrap(), "hello");
+/// ```
+pub fn write_string(s: &str, section: &mut dyn SectionData) -> Result<u32>
+{
+    return strings::low_level_write_string(s, section);
+}
@@ -31,10 +31,10 @@ use std::{
     vec::Vec
 };
 
-use crate::sd::Value;
+use crate::sd::{value::NULL, Value};
 
 /// Represents a BPX Structured Data Array.
-#[derive(PartialEq, Clone)]
+#[derive(PartialEq, Clone, Debug)]
 pub struct Array
 {
     data: Vec<Value>
@@ -153,21 +153,38 @@ impl Array
     {
         return self.data.len();
     }
+
+    /// Checks if the array is empty.
+    pub fn is_empty(&self) -> bool
+    {
+        return self.data.is_empty();
+    }
+}
+
+impl Default for Array
+{
+    fn default() -> Self
+    {
+        return Self::new();
+    }
 }
 
+/// Indexes a value in the array.
+///
+/// *Returns [Value::Null](crate::sd::Value::Null) if the index is out of bounds.*
 impl Index<usize> for Array
 {
     type Output = Value;
 
-    fn index<'a>(&'a self, i: usize) -> &'a Value
+    fn index(&self, i: usize) -> &Value
     {
-        return &self.data[i];
+        return self.data.get(i).unwrap_or(&NULL);
     }
 }
 
 impl IndexMut<usize> for Array
 {
-    fn index_mut<'a>(&'a mut self, i: usize) -> &'a mut Value
+    fn index_mut(&mut self, i: usize) -> &mut Value
     {
         return &mut self.data[i];
     }
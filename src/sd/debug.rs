@@ -28,15 +28,15 @@
 
 use std::{collections::HashMap, convert::TryInto};
 
+use super::Error;
 use crate::{
-    error::Error,
     sd::{Array, Object},
     utils::hash,
     Result
 };
 
 /// Provides support for debug symbols to BPXSD object.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct DebugSymbols
 {
     symbols_map: HashMap<u64, String>,
@@ -75,7 +75,7 @@ impl DebugSymbols
     pub fn lookup(&self, hash: u64) -> Option<&str>
     {
         if let Some(v) = self.symbols_map.get(&hash) {
-            return Some(&v);
+            return Some(v);
         }
         return None;
     }
@@ -174,6 +174,14 @@ impl DebugSymbols
                 symbols_map: symbols
             });
         }
-        return Err(Error::MissingProp("__debug__"));
+        return Err(Error::MissingProp("__debug__").into());
+    }
+}
+
+impl Default for DebugSymbols
+{
+    fn default() -> Self
+    {
+        return Self::new();
     }
 }
@@ -30,9 +30,11 @@ use std::io::Read;
 
 use byteorder::{ByteOrder, LittleEndian};
 
+use super::Error;
 use crate::{
-    error::Error,
-    sd::{Array, Object, Value},
+    format::sd::MAX_DEPTH,
+    sd::{Array, HashAlgo, Object, Value},
+    utils::read_fill,
     Result
 };
 
@@ -40,8 +42,8 @@ fn read_bool<TRead: Read>(stream: &mut TRead) -> Result<Value>
 {
     let mut flag: [u8; 1] = [0; 1];
 
-    if stream.read(&mut flag)? != 1 {
-        return Err(Error::Truncation("Read Structured Data Value (bool)"));
+    if read_fill(stream, &mut flag)? != 1 {
+        return Err(Error::Truncation("Read Structured Data Value (bool)").into());
     }
     return Ok(Value::Bool(flag[0] == 1));
 }
@@ -50,8 +52,8 @@ fn read_uint8<TRead: Read>(stream: &mut TRead) -> Result<Value>
 {
     let mut val: [u8; 1] = [0; 1];
 
-    if stream.read(&mut val)? != 1 {
-        return Err(Error::Truncation("Read Structured Data Value (uint8)"));
+    if read_fill(stream, &mut val)? != 1 {
+        return Err(Error::Truncation("Read Structured Data Value (uint8)").into());
     }
     return Ok(Value::Uint8(val[0]));
 }
@@ -60,8 +62,8 @@ fn read_int8<TRead: Read>(stream: &mut TRead) -> Result<Value>
 {
     let mut val: [u8; 1] = [0; 1];
 
-    if stream.read(&mut val)? != 1 {
-        return Err(Error::Truncation("Read Structured Data Value (int8)"));
+    if read_fill(stream, &mut val)? != 1 {
+        return Err(Error::Truncation("Read Structured Data Value (int8)").into());
     }
     return Ok(Value::Int8(val[0] as i8));
 }
@@ -70,8 +72,8 @@ fn read_uint16<TRead: Read>(stream: &mut TRead) -> Result<Value>
 {
     let mut val: [u8; 2] = [0; 2];
 
-    if stream.read(&mut val)? != 2 {
-        return Err(Error::Truncation("Read Structured Data Value (uint16)"));
+    if read_fill(stream, &mut val)? != 2 {
+        return Err(Error::Truncation("Read Structured Data Value (uint16)").into());
     }
     return Ok(Value::Uint16(LittleEndian::read_u16(&val)));
 }
@@ -80,8 +82,8 @@ fn read_int16<TRead: Read>(stream: &mut TRead) -> Result<Value>
 {
     let mut val: [u8; 2] = [0; 2];
 
-    if stream.read(&mut val)? != 2 {
-        return Err(Error::Truncation("Read Structured Data Value (int16)"));
+    if read_fill(stream, &mut val)? != 2 {
+        return Err(Error::Truncation("Read Structured Data Value (int16)").into());
     }
     return Ok(Value::Int16(LittleEndian::read_i16(&val)));
 }
@@ -90,8 +92,8 @@ fn read_uint32<TRead: Read>(stream: &mut TRead) -> Result<Value>
 {
     let mut val: [u8; 4] = [0; 4];
 
-    if stream.read(&mut val)? != 4 {
-        return Err(Error::Truncation("Read Structured Data Value (uint32)"));
+    if read_fill(stream, &mut val)? != 4 {
+        return Err(Error::Truncation("Read Structured Data Value (uint32)").into());
     }
     return Ok(Value::Uint32(LittleEndian::read_u32(&val)));
 }
@@ -100,8 +102,8 @@ fn read_int32<TRead: Read>(stream: &mut TRead) -> Result<Value>
 {
     let mut val: [u8; 4] = [0; 4];
 
-    if stream.read(&mut val)? != 4 {
-        return Err(Error::Truncation("Read Structured Data Value (int32)"));
+    if read_fill(stream, &mut val)? != 4 {
+        return Err(Error::Truncation("Read Structured Data Value (int32)").into());
     }
     return Ok(Value::Int32(LittleEndian::read_i32(&val)));
 }
@@ -110,8 +112,8 @@ fn read_uint64<TRead: Read>(stream: &mut TRead) -> Result<Value>
 {
     let mut val: [u8; 8] = [0; 8];
 
-    if stream.read(&mut val)? != 8 {
-        return Err(Error::Truncation("Read Structured Data Value (uint64)"));
+    if read_fill(stream, &mut val)? != 8 {
+        return Err(Error::Truncation("Read Structured Data Value (uint64)").into());
     }
     return Ok(Value::Uint64(LittleEndian::read_u64(&val)));
 }
@@ -120,8 +122,8 @@ fn read_int64<TRead: Read>(stream: &mut TRead) -> Result<Value>
 {
     let mut val: [u8; 8] = [0; 8];
 
-    if stream.read(&mut val)? != 8 {
-        return Err(Error::Truncation("Read Structured Data Value (int64)"));
+    if read_fill(stream, &mut val)? != 8 {
+        return Err(Error::Truncation("Read Structured Data Value (int64)").into());
     }
     return Ok(Value::Int64(LittleEndian::read_i64(&val)));
 }
@@ -130,8 +132,8 @@ fn read_float<TRead: Read>(stream: &mut TRead) -> Result<Value>
 {
     let mut val: [u8; 4] = [0; 4];
 
-    if stream.read(&mut val)? != 4 {
-        return Err(Error::Truncation("Read Structured Data Value (float)"));
+    if read_fill(stream, &mut val)? != 4 {
+        return Err(Error::Truncation("Read Structured Data Value (float)").into());
     }
     return Ok(Value::Float(LittleEndian::read_f32(&val)));
 }
@@ -140,8 +142,8 @@ fn read_double<TRead: Read>(stream: &mut TRead) -> Result<Value>
 {
     let mut val: [u8; 8] = [0; 8];
 
-    if stream.read(&mut val)? != 8 {
-        return Err(Error::Truncation("Read Structured Data Value (double)"));
+    if read_fill(stream, &mut val)? != 8 {
+        return Err(Error::Truncation("Read Structured Data Value (double)").into());
     }
     return Ok(Value::Double(LittleEndian::read_f64(&val)));
 }
@@ -151,45 +153,68 @@ fn read_string<TRead: Read>(stream: &mut TRead) -> Result<Value>
     let mut curs: Vec<u8> = Vec::new();
     let mut chr: [u8; 1] = [0; 1]; //read char by char with a buffer
 
-    stream.read(&mut chr)?;
+    if read_fill(stream, &mut chr)? != 1 {
+        return Err(Error::Truncation("Read Structured Data Value (string)").into());
+    }
     while chr[0] != 0x0 {
         curs.push(chr[0]);
-        let res = stream.read(&mut chr)?;
+        let res = read_fill(stream, &mut chr)?;
         if res != 1 {
-            return Err(Error::Truncation("Read Structured Data Value (string)"));
+            return Err(Error::Truncation("Read Structured Data Value (string)").into());
         }
     }
     match String::from_utf8(curs) {
-        Err(_) => return Err(Error::Utf8("Read Structured Data Value (string)")),
+        Err(_) => return Err(Error::Utf8("Read Structured Data Value (string)").into()),
         Ok(v) => return Ok(Value::String(v))
     }
 }
 
-fn parse_object<TRead: Read>(stream: &mut TRead) -> Result<Object>
+fn parse_value<TRead: Read>(
+    stream: &mut TRead,
+    type_code: u8,
+    algo: HashAlgo,
+    depth: usize
+) -> Result<Option<Value>>
+{
+    return match type_code {
+        0xD => Ok(Some(Value::Array(parse_array(stream, algo, depth + 1)?))),
+        0xE => Ok(Some(Value::Object(parse_object(stream, algo, depth + 1)?))),
+        _ => match get_value_parser(type_code) {
+            Some(func) => Ok(Some(func(stream)?)),
+            None => Ok(None)
+        }
+    };
+}
+
+fn parse_object<TRead: Read>(stream: &mut TRead, algo: HashAlgo, depth: usize) -> Result<Object>
 {
-    let mut obj = Object::new();
+    if depth > MAX_DEPTH {
+        return Err(Error::MaxDepthExceeded(MAX_DEPTH).into());
+    }
+    let mut obj = Object::with_hash_algo(algo);
     let mut count = {
         let mut buf: [u8; 1] = [0; 1];
-        if stream.read(&mut buf)? != 1 {
-            return Err(Error::Truncation("Read Structured Data Value (object)"));
+        if read_fill(stream, &mut buf)? != 1 {
+            return Err(Error::Truncation("Read Structured Data Value (object)").into());
         }
         buf[0]
     };
 
     while count > 0 {
         let mut prop: [u8; 9] = [0; 9];
-        if stream.read(&mut prop)? != 9 {
-            return Err(Error::Truncation("Read Structured Data Value (object)"));
+        if read_fill(stream, &mut prop)? != 9 {
+            return Err(Error::Truncation("Read Structured Data Value (object)").into());
         }
         let hash = LittleEndian::read_u64(&prop[0..8]);
         let type_code = prop[8];
-        match get_value_parser(type_code) {
-            Some(func) => obj.raw_set(hash, func(stream)?),
+        match parse_value(stream, type_code, algo, depth)? {
+            Some(v) => obj.raw_set(hash, v),
             None => {
                 return Err(Error::Corruption(format!(
                     "Got unexpected unknown variant code ({}) while reading Structured Data Object",
                     type_code
-                )))
+                ))
+                .into())
             },
         }
         count -= 1;
@@ -197,29 +222,33 @@ fn parse_object<TRead: Read>(stream: &mut TRead) -> Result<Object>
     return Ok(obj);
 }
 
-fn parse_array<TRead: Read>(stream: &mut TRead) -> Result<Array>
+fn parse_array<TRead: Read>(stream: &mut TRead, algo: HashAlgo, depth: usize) -> Result<Array>
 {
+    if depth > MAX_DEPTH {
+        return Err(Error::MaxDepthExceeded(MAX_DEPTH).into());
+    }
     let mut arr = Array::new();
     let mut count = {
         let mut buf: [u8; 1] = [0; 1];
-        if stream.read(&mut buf)? != 1 {
-            return Err(Error::Truncation("Read Structured Data Value (array)"));
+        if read_fill(stream, &mut buf)? != 1 {
+            return Err(Error::Truncation("Read Structured Data Value (array)").into());
         }
         buf[0]
     };
 
     while count > 0 {
         let mut type_code: [u8; 1] = [0; 1];
-        if stream.read(&mut type_code)? != 1 {
-            return Err(Error::Truncation("Read Structured Data Value (array)"));
+        if read_fill(stream, &mut type_code)? != 1 {
+            return Err(Error::Truncation("Read Structured Data Value (array)").into());
         }
-        match get_value_parser(type_code[0]) {
-            Some(func) => arr.add(func(stream)?),
+        match parse_value(stream, type_code[0], algo, depth)? {
+            Some(v) => arr.add(v),
             None => {
                 return Err(Error::Corruption(format!(
                     "Got unexpected unknown variant code ({}) while reading Structured Data Array",
                     type_code[0]
-                )))
+                ))
+                .into())
             },
         }
         count -= 1;
@@ -245,17 +274,11 @@ fn get_value_parser<TRead: Read>(type_code: u8) -> Option<fn(stream: &mut TRead)
         0xA => Some(read_float),
         0xB => Some(read_double),
         0xC => Some(read_string),
-        0xD => Some(|stream| {
-            return Ok(Value::Array(parse_array(stream)?));
-        }),
-        0xE => Some(|stream| {
-            return Ok(Value::Object(parse_object(stream)?));
-        }),
         _ => None
     }
 }
 
-pub fn read_structured_data<TRead: Read>(source: &mut TRead) -> Result<Object>
+pub fn read_structured_data<TRead: Read>(source: &mut TRead, algo: HashAlgo) -> Result<Object>
 {
-    return parse_object(source);
+    return parse_object(source, algo, 0);
 }
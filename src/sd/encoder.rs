@@ -30,9 +30,10 @@ use std::io::Write;
 
 use byteorder::{ByteOrder, LittleEndian};
 
+use super::Error;
 use crate::{
-    error::Error,
-    sd::{Array, Object, Value},
+    format::sd::{MAX_ARRAY_LEN, MAX_PROPS},
+    sd::{Array, HashAlgo, Object, Value},
     Result
 };
 
@@ -57,10 +58,8 @@ fn get_value_type_code(val: &Value) -> u8
     }
 }
 
-fn write_value(val: &Value) -> Result<Vec<u8>>
+fn write_value(val: &Value, algo: HashAlgo, buf: &mut Vec<u8>) -> Result<()>
 {
-    let mut buf = Vec::new();
-
     match val {
         Value::Null => (),
         Value::Bool(b) => {
@@ -116,52 +115,59 @@ fn write_value(val: &Value) -> Result<Vec<u8>>
             buf.extend_from_slice(s.as_bytes());
             buf.push(0x0); //Add null byte terminator
         },
-        Value::Array(arr) => buf.append(&mut write_array(arr)?),
-        Value::Object(obj) => buf.append(&mut write_object(obj)?)
+        Value::Array(arr) => write_array(arr, algo, buf)?,
+        Value::Object(obj) => write_object(obj, algo, buf)?
     }
-    return Ok(buf);
+    return Ok(());
 }
 
-fn write_object(obj: &Object) -> Result<Vec<u8>>
+fn write_object(obj: &Object, algo: HashAlgo, v: &mut Vec<u8>) -> Result<()>
 {
-    let mut v: Vec<u8> = Vec::new();
     let count = obj.prop_count();
 
-    if count > 255 {
-        return Err(Error::PropCountExceeded(count));
+    // Only one algorithm is recorded for the whole tree so nested objects must agree with the root.
+    if obj.hash_algo() != algo {
+        return Err(Error::HashAlgoMismatch(obj.hash_algo().id(), algo.id()).into());
+    }
+    if count > MAX_PROPS {
+        return Err(Error::PropCountExceeded(count).into());
     }
     v.push(count as u8);
-    for hash in obj.get_keys() {
+    // Properties are sorted so that the encoding does not depend on the iteration order of the map.
+    let mut keys: Vec<u64> = obj.get_keys().copied().collect();
+    keys.sort_unstable();
+    for hash in &keys {
         let val = &obj[*hash];
         let mut head: [u8; 9] = [0; 9];
         LittleEndian::write_u64(&mut head[0..8], *hash);
         head[8] = get_value_type_code(val);
         v.extend_from_slice(&head);
-        v.append(&mut write_value(val)?);
+        write_value(val, algo, v)?;
     }
-    return Ok(v);
+    return Ok(());
 }
 
-fn write_array(arr: &Array) -> Result<Vec<u8>>
+fn write_array(arr: &Array, algo: HashAlgo, v: &mut Vec<u8>) -> Result<()>
 {
-    let mut v: Vec<u8> = Vec::new();
     let count = arr.len();
 
-    if count > 255 {
-        return Err(Error::PropCountExceeded(count));
+    if count > MAX_ARRAY_LEN {
+        return Err(Error::PropCountExceeded(count).into());
     }
     v.push(count as u8);
     for i in 0..count {
         let val = &arr[i];
         v.push(get_value_type_code(val));
-        v.append(&mut write_value(val)?);
+        write_value(val, algo, v)?;
     }
-    return Ok(v);
+    return Ok(());
 }
 
 pub fn write_structured_data<TWrite: Write>(dest: &mut TWrite, obj: &Object) -> Result<()>
 {
-    let bytes = write_object(obj)?;
-    dest.write(&bytes)?;
+    // Everything is encoded in a single buffer first so that nothing is written on error.
+    let mut bytes = Vec::new();
+    write_object(obj, obj.hash_algo(), &mut bytes)?;
+    dest.write_all(&bytes)?;
     return Ok(());
 }
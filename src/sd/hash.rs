@@ -0,0 +1,148 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::fmt::{Debug, Formatter};
+
+use crate::utils;
+
+/// The first identifier available to application defined hash algorithms.
+pub const HASH_ALGO_CUSTOM_MIN: u8 = 0x80;
+
+/// The algorithm used to hash the names of the properties of an
+/// [Object](crate::sd::Object).
+///
+/// *Each algorithm has an identifier byte which is stored next to the
+/// data (for example in the user tag of a section), so that decoders use
+/// the same algorithm for lookups. Identifiers below
+/// [HASH_ALGO_CUSTOM_MIN](self::HASH_ALGO_CUSTOM_MIN) are reserved for
+/// algorithms defined by this crate; version 1 has identifier 0 so that
+/// data written with it is unchanged.*
+///
+/// # Examples
+///
+/// ```
+/// use bpx::sd::HashAlgo;
+/// use bpx::utils::hash;
+///
+/// assert_eq!(HashAlgo::V1.hash("Test"), hash("Test"));
+/// assert_eq!(HashAlgo::from_id(0), Some(HashAlgo::V1));
+/// let custom = HashAlgo::custom(0x80, |name| name.len() as u64).unwrap();
+/// assert_eq!(custom.hash("Test"), 4);
+/// assert!(HashAlgo::custom(1, |name| name.len() as u64).is_none());
+/// ```
+#[derive(Clone, Copy)]
+pub struct HashAlgo
+{
+    id: u8,
+    func: fn(&str) -> u64
+}
+
+impl HashAlgo
+{
+    /// The original BPX name hash, see [hash](crate::utils::hash).
+    pub const V1: HashAlgo = HashAlgo {
+        id: 0,
+        func: utils::hash
+    };
+
+    /// Creates an application defined hash algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: the identifier of the algorithm, at least [HASH_ALGO_CUSTOM_MIN](self::HASH_ALGO_CUSTOM_MIN).
+    /// * `func`: the hash function.
+    ///
+    /// returns: Option<HashAlgo>; None if the identifier is reserved.
+    pub fn custom(id: u8, func: fn(&str) -> u64) -> Option<HashAlgo>
+    {
+        if id < HASH_ALGO_CUSTOM_MIN {
+            return None;
+        }
+        return Some(HashAlgo { id, func });
+    }
+
+    /// Returns the algorithm defined by this crate with the given identifier.
+    ///
+    /// # Arguments
+    ///
+    /// * `id`: the identifier of the algorithm.
+    ///
+    /// returns: Option<HashAlgo>; None for unknown and application defined identifiers.
+    pub fn from_id(id: u8) -> Option<HashAlgo>
+    {
+        return match id {
+            0 => Some(HashAlgo::V1),
+            _ => None
+        };
+    }
+
+    /// Returns the identifier of this algorithm.
+    pub fn id(self) -> u8
+    {
+        return self.id;
+    }
+
+    /// Hashes a property name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the property name.
+    ///
+    /// returns: u64
+    pub fn hash(self, name: &str) -> u64
+    {
+        return (self.func)(name);
+    }
+}
+
+impl Default for HashAlgo
+{
+    fn default() -> Self
+    {
+        return HashAlgo::V1;
+    }
+}
+
+/// Algorithms are identified by their identifier only.
+impl PartialEq for HashAlgo
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        return self.id == other.id;
+    }
+}
+
+impl Eq for HashAlgo {}
+
+impl Debug for HashAlgo
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        return f.debug_tuple("HashAlgo").field(&self.id).finish();
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Declarative construction of BPXSD objects and arrays.
+//!
+//! Values are converted with [From](std::convert::From) into a
+//! [Value](crate::sd::Value), so a value of an unsupported type is a compile
+//! error. Braces nest an object and brackets nest an array. The 255 entries
+//! limit of BPXSD is only checked when the object is written.
+
+/// Builds an [Object](crate::sd::Object) from `"key": value` pairs.
+///
+/// *Values may be any expression convertible into a [Value](crate::sd::Value),
+/// `{ ... }` for a nested object or `[ ... ]` for a nested array.*
+///
+/// # Examples
+///
+/// ```
+/// use std::convert::TryInto;
+///
+/// use bpx::sd::{Array, Value};
+/// use bpx::sd_object;
+///
+/// let version = 2;
+/// let obj = sd_object! {
+///     "name": "demo",
+///     "version": format!("1.{}", version),
+///     "tags": ["a", "b"],
+///     "build": { "id": 42u32, "offset": -1i8 }
+/// };
+/// assert_eq!(obj.get("name"), Some(&Value::from("demo")));
+/// assert_eq!(obj.get("version"), Some(&Value::from("1.2")));
+/// let tags: &Array = obj.get("tags").unwrap().try_into().unwrap();
+/// assert_eq!(tags.len(), 2);
+/// let build: &bpx::sd::Object = obj.get("build").unwrap().try_into().unwrap();
+/// assert_eq!(build.get("offset"), Some(&Value::Int8(-1)));
+/// ```
+///
+/// Values which have no BPXSD representation are rejected at compile time:
+///
+/// ```compile_fail
+/// use bpx::sd_object;
+///
+/// let obj = sd_object! { "path": std::path::PathBuf::new() };
+/// ```
+#[macro_export]
+macro_rules! sd_object {
+    ($($body:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut obj = $crate::sd::Object::new();
+        $crate::__sd_entries!(sd_object, obj, (); $($body)*);
+        obj
+    }};
+}
+
+/// Builds an [Object](crate::sd::Object) like [sd_object](crate::sd_object) and
+/// attaches [DebugSymbols](crate::sd::DebugSymbols) naming its keys.
+///
+/// *Nested objects get their own debug symbols.*
+///
+/// # Examples
+///
+/// ```
+/// use std::convert::TryInto;
+///
+/// use bpx::sd::DebugSymbols;
+/// use bpx::sd_object_with_symbols;
+/// use bpx::utils::hash;
+///
+/// let obj = sd_object_with_symbols! { "name": "demo", "build": { "id": 42u32 } };
+/// let symbols = DebugSymbols::read(&obj).unwrap();
+/// assert_eq!(symbols.lookup(hash("name")), Some("name"));
+/// let build = obj.get("build").unwrap().try_into().unwrap();
+/// assert_eq!(DebugSymbols::read(build).unwrap().lookup(hash("id")), Some("id"));
+/// ```
+#[macro_export]
+macro_rules! sd_object_with_symbols {
+    ($($body:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut obj = $crate::sd::Object::new();
+        let mut symbols = $crate::sd::DebugSymbols::new();
+        $crate::__sd_entries!(sd_object_with_symbols, obj, (symbols); $($body)*);
+        symbols.write(&mut obj);
+        obj
+    }};
+}
+
+/// Builds an [Array](crate::sd::Array) from a list of values.
+///
+/// *Values follow the same rules as in [sd_object](crate::sd_object).*
+///
+/// # Examples
+///
+/// ```
+/// use bpx::sd::Value;
+/// use bpx::sd_array;
+///
+/// let arr = sd_array![1u8, "two", [3.0f32], { "four": 4u64 }];
+/// assert_eq!(arr.len(), 4);
+/// assert_eq!(arr.get(1), Some(&Value::from("two")));
+/// ```
+#[macro_export]
+macro_rules! sd_array {
+    ($($body:tt)*) => {
+        $crate::__sd_array!(sd_object; $($body)*)
+    };
+}
+
+// The first rule of each muncher handles lists of single token values without
+// recursion, so that long literal lists do not hit the macro recursion limit.
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sd_entries {
+    ($mac:ident, $obj:ident, $symbols:tt; $($key:literal : $value:tt),* $(,)?) => {
+        $(
+            $crate::__sd_symbol!($symbols, $key);
+            $obj.set($key, $crate::__sd_value!($mac; $value));
+        )*
+    };
+    ($mac:ident, $obj:ident, $symbols:tt; $key:literal : $value:tt $(, $($rest:tt)*)?) => {
+        $crate::__sd_symbol!($symbols, $key);
+        $obj.set($key, $crate::__sd_value!($mac; $value));
+        $crate::__sd_entries!($mac, $obj, $symbols; $($($rest)*)?);
+    };
+    ($mac:ident, $obj:ident, $symbols:tt; $key:literal : $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__sd_symbol!($symbols, $key);
+        $obj.set($key, $crate::sd::Value::from($value));
+        $crate::__sd_entries!($mac, $obj, $symbols; $($($rest)*)?);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sd_symbol {
+    ((), $key:literal) => {};
+    (($symbols:ident), $key:literal) => {
+        $symbols.push($key);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sd_array {
+    ($mac:ident; $($body:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut arr = $crate::sd::Array::new();
+        $crate::__sd_items!($mac, arr; $($body)*);
+        arr
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sd_items {
+    ($mac:ident, $arr:ident; $($value:tt),* $(,)?) => {
+        $($arr.add($crate::__sd_value!($mac; $value));)*
+    };
+    ($mac:ident, $arr:ident; $value:tt $(, $($rest:tt)*)?) => {
+        $arr.add($crate::__sd_value!($mac; $value));
+        $crate::__sd_items!($mac, $arr; $($($rest)*)?);
+    };
+    ($mac:ident, $arr:ident; $value:expr $(, $($rest:tt)*)?) => {
+        $arr.add($crate::sd::Value::from($value));
+        $crate::__sd_items!($mac, $arr; $($($rest)*)?);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sd_value {
+    ($mac:ident; { $($body:tt)* }) => {
+        $crate::sd::Value::from($crate::$mac!($($body)*))
+    };
+    ($mac:ident; [ $($body:tt)* ]) => {
+        $crate::sd::Value::from($crate::__sd_array!($mac; $($body)*))
+    };
+    ($mac:ident; $value:expr) => {
+        $crate::sd::Value::from($value)
+    };
+}
@@ -31,11 +31,106 @@
 mod array;
 mod decoder;
 mod encoder;
+mod hash;
+mod macros;
 mod object;
+mod stream;
 mod value;
 mod debug;
 
 pub use array::Array;
+pub use hash::{HashAlgo, HASH_ALGO_CUSTOM_MIN};
 pub use object::Object;
+pub use stream::{ObjectStreamReader, ObjectStreamWriter};
 pub use value::Value;
 pub use debug::DebugSymbols;
+
+use std::fmt::{Display, Formatter};
+
+/// Represents a Structured Data error.
+#[derive(Debug)]
+pub enum Error
+{
+    /// Describes a variant conversion error.
+    ///
+    /// # Arguments
+    /// * expected variant name.
+    /// * actual variant name.
+    TypeError(&'static str, &'static str),
+
+    /// Describes too many props or values attempted to be written as part of
+    /// an Object or Array (ie exceeds 255).
+    ///
+    /// # Arguments
+    /// * actual count of props.
+    PropCountExceeded(usize),
+
+    /// Describes a missing property in an object.
+    ///
+    /// # Arguments
+    /// * name of missing prop.
+    MissingProp(&'static str),
+
+    /// Describes a truncated Structured Data object.
+    ///
+    /// # Arguments
+    /// * last operation name before failure.
+    Truncation(&'static str),
+
+    /// Describes an utf8 decoding error in a string value.
+    ///
+    /// # Arguments
+    /// * last operation name before failure.
+    Utf8(&'static str),
+
+    /// Describes an impossible byte or sequence of bytes in a Structured Data object.
+    ///
+    /// # Arguments
+    /// * message.
+    Corruption(String),
+
+    /// Describes objects or arrays nested deeper than supported.
+    ///
+    /// # Arguments
+    /// * the maximum nesting level.
+    MaxDepthExceeded(usize),
+
+    /// Describes a nested object using another name hash algorithm than the
+    /// object it is written in.
+    ///
+    /// # Arguments
+    /// * identifier of the algorithm of the nested object.
+    /// * identifier of the algorithm of the root object.
+    HashAlgoMismatch(u8, u8)
+}
+
+impl Display for Error
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        return match self {
+            Error::TypeError(expected, actual) => {
+                f.write_str(&format!("incompatible types (expected {}, got {})", expected, actual))
+            },
+            Error::PropCountExceeded(v) => f.write_str(&format!(
+                "BPXSD - too many props (count {}, max is {})",
+                v,
+                crate::format::sd::MAX_PROPS
+            )),
+            Error::MissingProp(v) => f.write_str(&format!("BPXSD - missing property {}", v)),
+            Error::Truncation(e) => f.write_str(&format!(
+                "unexpected EOF while reading {}, are you sure the data is not truncated?",
+                e
+            )),
+            Error::Utf8(e) => f.write_str(&format!("utf8 decoding/encoding error in {}", e)),
+            Error::Corruption(e) => f.write_str(&format!("illegal bytes found ({})", e)),
+            Error::MaxDepthExceeded(v) => f.write_str(&format!("BPXSD - too deeply nested (max is {})", v)),
+            Error::HashAlgoMismatch(nested, root) => f.write_str(&format!(
+                "BPXSD - nested object hashes names with algorithm {} but its root uses {}",
+                nested, root
+            ))
+        };
+    }
+}
+
+impl std::error::Error for Error {}
@@ -31,21 +31,62 @@ use std::{
     ops::Index
 };
 
-use crate::{sd::Value, utils, Result};
+use crate::{
+    sd::{value::NULL, HashAlgo, Value},
+    Result
+};
 
 /// Represents a BPX Structured Data Object.
-#[derive(PartialEq, Clone)]
+///
+/// *Property names are hashed with the [HashAlgo](crate::sd::HashAlgo) the
+/// object was constructed with; objects nested in it must use the same
+/// algorithm.*
+#[derive(PartialEq, Clone, Debug)]
 pub struct Object
 {
-    props: HashMap<u64, Value>
+    props: HashMap<u64, Value>,
+    hash_algo: HashAlgo
 }
 
 impl Object
 {
-    /// Creates a new object.
+    /// Creates a new object hashing property names with [HashAlgo::V1](crate::sd::HashAlgo::V1).
     pub fn new() -> Object
     {
-        return Object { props: HashMap::new() };
+        return Object::with_hash_algo(HashAlgo::V1);
+    }
+
+    /// Creates a new object hashing property names with the given algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `hash_algo`: the [HashAlgo](crate::sd::HashAlgo) to use for property names.
+    ///
+    /// returns: Object
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::sd::{HashAlgo, Object};
+    ///
+    /// let algo = HashAlgo::custom(0x80, |name| name.len() as u64).unwrap();
+    /// let mut obj = Object::with_hash_algo(algo);
+    /// obj.set("Test", 12.into());
+    /// assert!(obj.raw_get(4).is_some());
+    /// assert_eq!(obj.hash_algo(), algo);
+    /// ```
+    pub fn with_hash_algo(hash_algo: HashAlgo) -> Object
+    {
+        return Object {
+            props: HashMap::new(),
+            hash_algo
+        };
+    }
+
+    /// Returns the algorithm used to hash property names.
+    pub fn hash_algo(&self) -> HashAlgo
+    {
+        return self.hash_algo;
     }
 
     /// Sets a property in the object using a raw property hash.
@@ -89,7 +130,7 @@ impl Object
     /// ```
     pub fn set(&mut self, name: &str, value: Value)
     {
-        self.raw_set(utils::hash(name), value);
+        self.raw_set(self.hash_algo.hash(name), value);
     }
 
     /// Gets a property in the object by its hash.
@@ -139,7 +180,7 @@ impl Object
     /// ```
     pub fn get(&self, name: &str) -> Option<&Value>
     {
-        return self.raw_get(utils::hash(name));
+        return self.raw_get(self.hash_algo.hash(name));
     }
 
     /// Returns the number of properties in the object.
@@ -156,12 +197,22 @@ impl Object
 
     /// Attempts to write the object to the given IO backend.
     ///
+    /// *The hash algorithm is not part of the encoded bytes: callers record
+    /// it next to the data (see [read_with](Object::read_with)). Properties
+    /// are written in ascending hash order, so equal objects always produce
+    /// the same bytes.*
+    ///
     /// # Arguments
     ///
     /// * `dest`: the destination [Write](std::io::Write).
     ///
     /// returns: Result<(), Error>
     ///
+    /// # Errors
+    ///
+    /// Returns [HashAlgoMismatch](crate::sd::Error::HashAlgoMismatch) when a
+    /// nested object uses another hash algorithm than this object.
+    ///
     /// # Examples
     ///
     /// ```
@@ -202,26 +253,69 @@ impl Object
     /// ```
     pub fn read<TRead: std::io::Read>(source: &mut TRead) -> Result<Object>
     {
-        return super::decoder::read_structured_data(source);
+        return Object::read_with(source, HashAlgo::V1);
+    }
+
+    /// Attempts to read a BPXSD object whose property names were hashed with
+    /// the given algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `source`: the source [Read](std::io::Read).
+    /// * `hash_algo`: the [HashAlgo](crate::sd::HashAlgo) the object was written with.
+    ///
+    /// returns: Result<Object, Error>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::sd::{HashAlgo, Object};
+    ///
+    /// let algo = HashAlgo::custom(0x80, |name| name.len() as u64).unwrap();
+    /// let mut obj = Object::with_hash_algo(algo);
+    /// obj.set("Test", 12.into());
+    /// let mut buf = Vec::<u8>::new();
+    /// obj.write(&mut buf).unwrap();
+    /// assert!(Object::read(&mut buf.as_slice()).unwrap().get("Test").is_none());
+    /// let obj1 = Object::read_with(&mut buf.as_slice(), algo).unwrap();
+    /// assert!(obj1.get("Test").is_some());
+    /// ```
+    pub fn read_with<TRead: std::io::Read>(source: &mut TRead, hash_algo: HashAlgo) -> Result<Object>
+    {
+        return super::decoder::read_structured_data(source, hash_algo);
+    }
+}
+
+impl Default for Object
+{
+    fn default() -> Self
+    {
+        return Self::new();
     }
 }
 
+/// Indexes a property by name.
+///
+/// *Returns [Value::Null](crate::sd::Value::Null) if the property does not exist.*
 impl Index<&str> for Object
 {
     type Output = Value;
 
     fn index(&self, name: &str) -> &Value
     {
-        return &self.props.index(&utils::hash(name));
+        return self.get(name).unwrap_or(&NULL);
     }
 }
 
+/// Indexes a property by its raw BPX hash.
+///
+/// *Returns [Value::Null](crate::sd::Value::Null) if the property does not exist.*
 impl Index<u64> for Object
 {
     type Output = Value;
 
     fn index(&self, hash: u64) -> &Value
     {
-        return &self.props.index(&hash);
+        return self.raw_get(hash).unwrap_or(&NULL);
     }
 }
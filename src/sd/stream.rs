@@ -0,0 +1,217 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Streams of BPXSD objects stored back to back in a single section.
+
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+use crate::{
+    error::Error,
+    sd::{self, HashAlgo, Object},
+    section::SectionData,
+    Result
+};
+
+/// Counts the bytes read through it, so that the offset of the next object is known
+/// without seeking the buffered section.
+struct Counter<'a, TRead: Read>
+{
+    inner: &'a mut TRead,
+    count: u64
+}
+
+impl<'a, TRead: Read> Read for Counter<'a, TRead>
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize>
+    {
+        let len = self.inner.read(buf)?;
+        self.count += len as u64;
+        return Ok(len);
+    }
+}
+
+/// Reads the BPXSD objects stored back to back in a section, from its start to its end.
+///
+/// *Each item is the offset of an object in the section and the object itself.
+/// Iteration stops after the first error.*
+///
+/// # Examples
+///
+/// ```
+/// use bpx::encoder::Encoder;
+/// use bpx::header::SectionHeader;
+/// use bpx::sd::{Object, ObjectStreamReader, ObjectStreamWriter};
+/// use bpx::Interface;
+///
+/// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+/// let handle = encoder.create_section(SectionHeader::new()).unwrap();
+/// let mut section = encoder.open_section(handle).unwrap();
+/// let mut obj = Object::new();
+/// obj.set("name", "first".into());
+/// let mut writer = ObjectStreamWriter::new(&mut *section);
+/// assert_eq!(writer.append(&obj).unwrap(), 0);
+/// obj.set("name", "second".into());
+/// let second = writer.append(&obj).unwrap();
+/// let objects: Vec<(u64, Object)> = ObjectStreamReader::new(&mut *section).map(|v| v.unwrap()).collect();
+/// assert_eq!(objects.len(), 2);
+/// assert_eq!(objects[1].0, second);
+/// assert_eq!(objects[1].1.get("name").unwrap(), &"second".into());
+/// ```
+pub struct ObjectStreamReader<'a>
+{
+    reader: BufReader<&'a mut dyn SectionData>,
+    hash_algo: HashAlgo,
+    size: u64,
+    offset: u64,
+    started: bool,
+    done: bool
+}
+
+impl<'a> ObjectStreamReader<'a>
+{
+    /// Creates a reader of all objects stored in a section.
+    ///
+    /// *The section is read from its start, regardless of its current cursor.*
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: the section to read.
+    ///
+    /// returns: ObjectStreamReader
+    pub fn new(data: &'a mut dyn SectionData) -> ObjectStreamReader<'a>
+    {
+        let size = data.size() as u64;
+        return ObjectStreamReader {
+            reader: BufReader::new(data),
+            hash_algo: HashAlgo::V1,
+            size,
+            offset: 0,
+            started: false,
+            done: false
+        };
+    }
+
+    /// Reads the objects as written with the given hash algorithm, see
+    /// [read_with](crate::sd::Object::read_with).
+    ///
+    /// # Arguments
+    ///
+    /// * `hash_algo`: the [HashAlgo](crate::sd::HashAlgo) the objects were written with.
+    ///
+    /// returns: ObjectStreamReader
+    pub fn with_hash_algo(mut self, hash_algo: HashAlgo) -> Self
+    {
+        self.hash_algo = hash_algo;
+        return self;
+    }
+
+    fn read_next(&mut self) -> Result<Object>
+    {
+        if !self.started {
+            self.reader.seek(SeekFrom::Start(0))?;
+            self.started = true;
+        }
+        let mut counter = Counter {
+            inner: &mut self.reader,
+            count: 0
+        };
+        let res = Object::read_with(&mut counter, self.hash_algo);
+        let offset = self.offset;
+        self.offset += counter.count;
+        return res.map_err(|e| match e {
+            Error::Sd(sd::Error::Truncation(_)) => Error::Corruption(format!(
+                "object at offset {} is truncated, the section ends {} byte(s) after its start",
+                offset,
+                self.size - offset
+            )),
+            Error::Sd(e) => Error::Corruption(format!("object at offset {} is invalid ({})", offset, e)),
+            e => e
+        });
+    }
+}
+
+impl<'a> Iterator for ObjectStreamReader<'a>
+{
+    type Item = Result<(u64, Object)>;
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        if self.done || self.offset >= self.size {
+            return None;
+        }
+        let offset = self.offset;
+        let res = self.read_next();
+        self.done = res.is_err();
+        return Some(res.map(|obj| (offset, obj)));
+    }
+}
+
+/// Appends BPXSD objects to the end of a section, one after the other.
+///
+/// *Objects are encoded before anything is written so that an object which
+/// cannot be encoded leaves the section untouched.*
+pub struct ObjectStreamWriter<'a>
+{
+    data: &'a mut dyn SectionData
+}
+
+impl<'a> ObjectStreamWriter<'a>
+{
+    /// Creates a writer appending objects to a section.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: the section to append to.
+    ///
+    /// returns: ObjectStreamWriter
+    pub fn new(data: &'a mut dyn SectionData) -> ObjectStreamWriter<'a>
+    {
+        return ObjectStreamWriter { data };
+    }
+
+    /// Appends an object to the end of the section.
+    ///
+    /// # Arguments
+    ///
+    /// * `obj`: the object to write.
+    ///
+    /// returns: Result<u64, Error>; the offset of the object in the section.
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the object could not be
+    /// encoded or the section could not be written.
+    pub fn append(&mut self, obj: &Object) -> Result<u64>
+    {
+        let mut buf = Vec::new();
+        obj.write(&mut buf)?;
+        let offset = self.data.seek(SeekFrom::End(0))?;
+        self.data.write_all(&buf)?;
+        return Ok(offset);
+    }
+}
@@ -31,14 +31,11 @@ use std::{
     string::String
 };
 
-use crate::{
-    error::Error,
-    sd::{Array, Object},
-    Result
-};
+use super::Error;
+use crate::sd::{Array, Object};
 
 /// Represents a BPXSD value
-#[derive(PartialEq, Clone)]
+#[derive(PartialEq, Clone, Debug)]
 pub enum Value
 {
     /// NULL (0x0)
@@ -116,6 +113,9 @@ impl Value
     }
 }
 
+/// The value returned when indexing a missing property or array element.
+pub(crate) static NULL: Value = Value::Null;
+
 impl From<bool> for Value
 {
     fn from(v: bool) -> Self
@@ -263,7 +263,7 @@ impl TryFrom<Value> for bool
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         if let Value::Bool(v) = v {
             return Ok(v);
@@ -276,7 +276,7 @@ impl TryFrom<Value> for u8
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         if let Value::Uint8(v) = v {
             return Ok(v);
@@ -289,7 +289,7 @@ impl TryFrom<Value> for u16
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         return match v {
             Value::Uint16(v) => Ok(v),
@@ -303,7 +303,7 @@ impl TryFrom<Value> for u32
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         return match v {
             Value::Uint32(v) => Ok(v),
@@ -318,7 +318,7 @@ impl TryFrom<Value> for u64
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         return match v {
             Value::Uint64(v) => Ok(v),
@@ -334,7 +334,7 @@ impl TryFrom<Value> for i8
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         if let Value::Int8(v) = v {
             return Ok(v);
@@ -347,7 +347,7 @@ impl TryFrom<Value> for i16
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         return match v {
             Value::Int16(v) => Ok(v),
@@ -361,7 +361,7 @@ impl TryFrom<Value> for i32
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         return match v {
             Value::Int32(v) => Ok(v),
@@ -376,7 +376,7 @@ impl TryFrom<Value> for i64
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         return match v {
             Value::Int64(v) => Ok(v),
@@ -392,7 +392,7 @@ impl TryFrom<Value> for f32
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         if let Value::Float(v) = v {
             return Ok(v);
@@ -405,7 +405,7 @@ impl TryFrom<Value> for f64
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         return match v {
             Value::Double(v) => Ok(v),
@@ -419,7 +419,7 @@ impl TryFrom<Value> for String
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         if let Value::String(v) = v {
             return Ok(v);
@@ -432,7 +432,7 @@ impl TryFrom<Value> for Array
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         if let Value::Array(v) = v {
             return Ok(v);
@@ -445,7 +445,7 @@ impl TryFrom<Value> for Object
 {
     type Error = Error;
 
-    fn try_from(v: Value) -> Result<Self>
+    fn try_from(v: Value) -> Result<Self, Error>
     {
         if let Value::Object(v) = v {
             return Ok(v);
@@ -458,7 +458,7 @@ impl TryFrom<&Value> for bool
 {
     type Error = Error;
 
-    fn try_from(v: &Value) -> Result<Self>
+    fn try_from(v: &Value) -> Result<Self, Error>
     {
         if let Value::Bool(v) = v {
             return Ok(*v);
@@ -471,7 +471,7 @@ impl TryFrom<&Value> for u8
 {
     type Error = Error;
 
-    fn try_from(v: &Value) -> Result<Self>
+    fn try_from(v: &Value) -> Result<Self, Error>
     {
         if let Value::Uint8(v) = v {
             return Ok(*v);
@@ -484,7 +484,7 @@ impl TryFrom<&Value> for u16
 {
     type Error = Error;
 
-    fn try_from(v: &Value) -> Result<Self>
+    fn try_from(v: &Value) -> Result<Self, Error>
     {
         return match v {
             Value::Uint16(v) => Ok(*v),
@@ -498,7 +498,7 @@ impl TryFrom<&Value> for u32
 {
     type Error = Error;
 
-    fn try_from(v: &Value) -> Result<Self>
+    fn try_from(v: &Value) -> Result<Self, Error>
     {
         return match v {
             Value::Uint32(v) => Ok(*v),
@@ -513,7 +513,7 @@ impl TryFrom<&Value> for u64
 {
     type Error = Error;
 
-    fn try_from(v: &Value) -> Result<Self>
+    fn try_from(v: &Value) -> Result<Self, Error>
     {
         return match v {
             Value::Uint64(v) => Ok(*v),
@@ -529,7 +529,7 @@ impl TryFrom<&Value> for i8
 {
     type Error = Error;
 
-    fn try_from(v: &Value) -> Result<Self>
+    fn try_from(v: &Value) -> Result<Self, Error>
     {
         if let Value::Int8(v) = v {
             return Ok(*v);
@@ -542,7 +542,7 @@ impl TryFrom<&Value> for i16
 {
     type Error = Error;
 
-    fn try_from(v: &Value) -> Result<Self>
+    fn try_from(v: &Value) -> Result<Self, Error>
     {
         return match v {
             Value::Int16(v) => Ok(*v),
@@ -556,7 +556,7 @@ impl TryFrom<&Value> for i32
 {
     type Error = Error;
 
-    fn try_from(v: &Value) -> Result<Self>
+    fn try_from(v: &Value) -> Result<Self, Error>
     {
         return match v {
             Value::Int32(v) => Ok(*v),
@@ -571,7 +571,7 @@ impl TryFrom<&Value> for i64
 {
     type Error = Error;
 
-    fn try_from(v: &Value) -> Result<Self>
+    fn try_from(v: &Value) -> Result<Self, Error>
     {
         return match v {
             Value::Int64(v) => Ok(*v),
@@ -587,7 +587,7 @@ impl TryFrom<&Value> for f32
 {
     type Error = Error;
 
-    fn try_from(v: &Value) -> Result<Self>
+    fn try_from(v: &Value) -> Result<Self, Error>
     {
         if let Value::Float(v) = v {
             return Ok(*v);
@@ -600,7 +600,7 @@ impl TryFrom<&Value> for f64
 {
     type Error = Error;
 
-    fn try_from(v: &Value) -> Result<Self>
+    fn try_from(v: &Value) -> Result<Self, Error>
     {
         return match v {
             Value::Double(v) => Ok(*v),
@@ -614,10 +614,10 @@ impl<'a> TryFrom<&'a Value> for &'a str
 {
     type Error = Error;
 
-    fn try_from(v: &'a Value) -> Result<Self>
+    fn try_from(v: &'a Value) -> Result<Self, Error>
     {
         if let Value::String(v) = v {
-            return Ok(&v);
+            return Ok(v);
         }
         return Err(Error::TypeError("string", v.get_type_name()));
     }
@@ -627,10 +627,10 @@ impl<'a> TryFrom<&'a Value> for &'a Array
 {
     type Error = Error;
 
-    fn try_from(v: &'a Value) -> Result<Self>
+    fn try_from(v: &'a Value) -> Result<Self, Error>
     {
         if let Value::Array(v) = v {
-            return Ok(&v);
+            return Ok(v);
         }
         return Err(Error::TypeError("array", v.get_type_name()));
     }
@@ -640,7 +640,7 @@ impl<'a> TryFrom<&'a Value> for &'a Object
 {
     type Error = Error;
 
-    fn try_from(v: &'a Value) -> Result<Self>
+    fn try_from(v: &'a Value) -> Result<Self, Error>
     {
         if let Value::Object(v) = v {
             return Ok(v);
@@ -656,7 +656,7 @@ macro_rules! generate_option_try_from {
             {
                 type Error = Error;
 
-                fn try_from(v: Value) -> Result<Self>
+                fn try_from(v: Value) -> Result<Self, Error>
                 {
                     if let Value::Null = v
                     {
@@ -677,7 +677,7 @@ macro_rules! generate_option_try_from_ref {
             {
                 type Error = Error;
 
-                fn try_from(v: &'a Value) -> Result<Self>
+                fn try_from(v: &'a Value) -> Result<Self, Error>
                 {
                     if let Value::Null = v
                     {
@@ -698,7 +698,7 @@ macro_rules! generate_option_try_from_ref_scalar {
             {
                 type Error = Error;
 
-                fn try_from(v: &'a Value) -> Result<Self>
+                fn try_from(v: &'a Value) -> Result<Self, Error>
                 {
                     if let Value::Null = v
                     {
@@ -0,0 +1,157 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc
+};
+
+use crate::error::Error;
+
+/// A shared limit on the number of bytes of section data held in memory.
+///
+/// *Sections charge their buffer to the budget when allocated and release it
+/// when dropped. Cloning a budget gives another handle to the same counter,
+/// which allows multiple decoders to share one limit.*
+///
+/// # Examples
+///
+/// ```
+/// use bpx::section::MemoryBudget;
+///
+/// let budget = MemoryBudget::new(100);
+/// assert!(budget.try_reserve(60));
+/// assert!(!budget.clone().try_reserve(60));
+/// budget.release(60);
+/// assert_eq!(budget.used(), 0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MemoryBudget
+{
+    used: Arc<AtomicUsize>,
+    limit: usize
+}
+
+impl MemoryBudget
+{
+    /// Creates a new budget.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit`: the maximum number of bytes which may be held at once.
+    ///
+    /// returns: MemoryBudget
+    pub fn new(limit: usize) -> MemoryBudget
+    {
+        return MemoryBudget {
+            used: Arc::new(AtomicUsize::new(0)),
+            limit
+        };
+    }
+
+    /// Creates a budget which only counts allocations.
+    pub fn unlimited() -> MemoryBudget
+    {
+        return MemoryBudget::new(usize::MAX);
+    }
+
+    /// Returns the maximum number of bytes which may be held at once.
+    pub fn limit(&self) -> usize
+    {
+        return self.limit;
+    }
+
+    /// Returns the number of bytes currently held.
+    pub fn used(&self) -> usize
+    {
+        return self.used.load(Ordering::Relaxed);
+    }
+
+    /// Returns the number of bytes which may still be reserved.
+    pub fn available(&self) -> usize
+    {
+        return self.limit.saturating_sub(self.used());
+    }
+
+    /// Reserves bytes if they fit in the budget.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: the number of bytes to reserve.
+    ///
+    /// returns: bool; false if the budget is left unchanged because it is too small.
+    pub fn try_reserve(&self, bytes: usize) -> bool
+    {
+        let res = self.used.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            used.checked_add(bytes).filter(|v| *v <= self.limit)
+        });
+        return res.is_ok();
+    }
+
+    /// Releases bytes previously reserved with [try_reserve](Self::try_reserve).
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: the number of bytes to release.
+    pub fn release(&self, bytes: usize)
+    {
+        // Saturate instead of wrapping if more is released than reserved.
+        let _ = self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| Some(used.saturating_sub(bytes)));
+    }
+
+    /// Checks that a temporary allocation fits in the budget, without reserving it.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: the size of the allocation.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// A [MemoryBudget](crate::error::Error::MemoryBudget) error is returned if
+    /// the allocation does not fit.
+    pub fn check(&self, bytes: usize) -> Result<(), Error>
+    {
+        let available = self.available();
+        if bytes > available {
+            return Err(Error::MemoryBudget(bytes, available));
+        }
+        return Ok(());
+    }
+}
+
+impl Default for MemoryBudget
+{
+    fn default() -> Self
+    {
+        return MemoryBudget::unlimited();
+    }
+}
@@ -28,21 +28,53 @@
 
 use std::{
     fs::File,
-    io::{Read, Result, Seek, SeekFrom, Write}
+    io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write}
 };
 
-use crate::section::SectionData;
+use crate::section::{
+    memory::{checked_seek, checked_write_end},
+    MemoryBudget,
+    SectionData,
+    StorageKind
+};
+
+const PAGE_SIZE: usize = 4096;
+const PAGE_COUNT: usize = 2;
+
+/// A cached page of the file, `offset` is always a multiple of PAGE_SIZE.
+struct Page
+{
+    offset: u64,
+    len: usize,
+    data: [u8; PAGE_SIZE]
+}
 
-const READ_BLOCK_SIZE: usize = 8192;
+impl Page
+{
+    fn new() -> Page
+    {
+        return Page {
+            offset: u64::MAX,
+            len: 0,
+            data: [0; PAGE_SIZE]
+        };
+    }
+
+    fn overlaps(&self, start: u64, end: u64) -> bool
+    {
+        return self.offset != u64::MAX && self.offset < end && start < self.offset + PAGE_SIZE as u64;
+    }
+}
 
 pub struct FileBasedSection
 {
     data: File,
-    buffer: [u8; READ_BLOCK_SIZE],
-    written: usize,
-    cursor: usize,
+    pages: [Page; PAGE_COUNT],
+    next_page: usize,
+    file_ptr: u64,
     cur_size: usize,
-    seek_ptr: u64
+    seek_ptr: u64,
+    budget: Option<MemoryBudget>
 }
 
 impl FileBasedSection
@@ -50,32 +82,94 @@ impl FileBasedSection
     pub fn new(data: File) -> FileBasedSection
     {
         return FileBasedSection {
-            data: data,
-            buffer: [0; READ_BLOCK_SIZE],
-            written: 0,
-            cursor: usize::MAX,
+            data,
+            pages: [Page::new(), Page::new()],
+            next_page: 0,
+            file_ptr: 0,
             cur_size: 0,
-            seek_ptr: 0
+            seek_ptr: 0,
+            budget: None
+        };
+    }
+
+    pub fn with_budget(data: File, budget: MemoryBudget) -> FileBasedSection
+    {
+        return FileBasedSection {
+            budget: Some(budget),
+            ..FileBasedSection::new(data)
         };
     }
+
+    fn move_file_ptr(&mut self, pos: u64) -> Result<()>
+    {
+        if self.file_ptr != pos {
+            self.file_ptr = self.data.seek(SeekFrom::Start(pos))?;
+        }
+        return Ok(());
+    }
+
+    fn load_page(&mut self, offset: u64) -> Result<usize>
+    {
+        if let Some(index) = self.pages.iter().position(|p| p.offset == offset) {
+            return Ok(index);
+        }
+        let index = self.next_page;
+        self.next_page = (self.next_page + 1) % PAGE_COUNT;
+        self.move_file_ptr(offset)?;
+        let page = &mut self.pages[index];
+        // Invalidate first, the page is left unusable if the read fails.
+        page.offset = u64::MAX;
+        page.len = 0;
+        while page.len < PAGE_SIZE {
+            let res = self.data.read(&mut page.data[page.len..]);
+            match res {
+                Ok(0) => break,
+                Ok(len) => {
+                    page.len += len;
+                    self.file_ptr += len as u64;
+                },
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    // The file position is unknown after an error.
+                    self.file_ptr = u64::MAX;
+                    return Err(e);
+                }
+            }
+        }
+        page.offset = offset;
+        return Ok(index);
+    }
 }
 
 impl Read for FileBasedSection
 {
     fn read(&mut self, data: &mut [u8]) -> Result<usize>
     {
+        if self.seek_ptr >= self.cur_size as u64 {
+            return Ok(0);
+        }
+        let len = std::cmp::min(data.len() as u64, self.cur_size as u64 - self.seek_ptr) as usize;
+        if len >= PAGE_SIZE {
+            // Large reads gain nothing from the cache.
+            self.move_file_ptr(self.seek_ptr)?;
+            let res = self.data.read(&mut data[..len]).inspect_err(|_| self.file_ptr = u64::MAX)?;
+            self.file_ptr += res as u64;
+            self.seek_ptr += res as u64;
+            return Ok(res);
+        }
         let mut cnt: usize = 0;
-
-        for i in 0..data.len() {
-            if self.cursor >= self.written {
-                self.cursor = 0;
-                self.written = self.data.read(&mut self.buffer)?;
-            }
-            if self.cursor < self.written {
-                data[i] = self.buffer[self.cursor];
-                self.cursor += 1;
-                cnt += 1;
+        while cnt < len {
+            let offset = self.seek_ptr - self.seek_ptr % PAGE_SIZE as u64;
+            let index = self.load_page(offset)?;
+            let page = &self.pages[index];
+            let start = (self.seek_ptr - offset) as usize;
+            if start >= page.len {
+                break;
             }
+            let size = std::cmp::min(len - cnt, page.len - start);
+            data[cnt..cnt + size].copy_from_slice(&page.data[start..start + size]);
+            cnt += size;
+            self.seek_ptr += size as u64;
         }
         return Ok(cnt);
     }
@@ -85,18 +179,25 @@ impl Write for FileBasedSection
 {
     fn write(&mut self, data: &[u8]) -> Result<usize>
     {
-        let len = self.data.write(data)?;
-        if self.seek_ptr >= self.cur_size as u64 {
-            self.cur_size += len;
-            self.seek_ptr += len as u64;
+        let end = checked_write_end(self.seek_ptr, data.len())?;
+        for page in &mut self.pages {
+            // A short page ends at the end of the file which this write may move.
+            if page.overlaps(self.seek_ptr, end) || page.len < PAGE_SIZE {
+                page.offset = u64::MAX;
+            }
+        }
+        self.move_file_ptr(self.seek_ptr)?;
+        let len = self.data.write(data).inspect_err(|_| self.file_ptr = u64::MAX)?;
+        self.file_ptr += len as u64;
+        self.seek_ptr += len as u64;
+        if self.seek_ptr > self.cur_size as u64 {
+            self.cur_size = self.seek_ptr as usize;
         }
         return Ok(len);
     }
 
     fn flush(&mut self) -> Result<()>
     {
-        self.data.seek(SeekFrom::Current(self.cursor as i64))?;
-        self.cursor = usize::MAX;
         return self.data.flush();
     }
 }
@@ -105,7 +206,12 @@ impl Seek for FileBasedSection
 {
     fn seek(&mut self, state: SeekFrom) -> Result<u64>
     {
-        self.seek_ptr = self.data.seek(state)?;
+        let pos = match state {
+            SeekFrom::Start(pos) => pos as usize,
+            SeekFrom::End(pos) => checked_seek(self.cur_size, pos)?,
+            SeekFrom::Current(pos) => checked_seek(self.seek_ptr as usize, pos)?
+        };
+        self.seek_ptr = pos as u64;
         return Ok(self.seek_ptr);
     }
 }
@@ -114,8 +220,17 @@ impl SectionData for FileBasedSection
 {
     fn load_in_memory(&mut self) -> Result<Vec<u8>>
     {
-        let mut data: Vec<u8> = Vec::new();
-        self.data.read_to_end(&mut data)?;
+        if let Some(budget) = &self.budget {
+            budget.check(self.cur_size)?;
+        }
+        self.move_file_ptr(0)?;
+        let mut data: Vec<u8> = Vec::with_capacity(self.cur_size);
+        let res = (&mut self.data).take(self.cur_size as u64).read_to_end(&mut data);
+        self.file_ptr = if res.is_ok() { data.len() as u64 } else { u64::MAX };
+        res?;
+        if data.len() < self.cur_size {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "section data is truncated"));
+        }
         return Ok(data);
     }
 
@@ -123,4 +238,14 @@ impl SectionData for FileBasedSection
     {
         return self.cur_size;
     }
+
+    fn storage_kind(&self) -> StorageKind
+    {
+        return StorageKind::TempFile;
+    }
+
+    fn allocated_bytes(&self) -> usize
+    {
+        return PAGE_COUNT * PAGE_SIZE;
+    }
 }
@@ -26,15 +26,20 @@
 // NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
 // SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use std::io::{Read, Result, Seek, SeekFrom, Write};
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
 
-use crate::section::SectionData;
+use crate::{
+    format::MAX_SECTION_SIZE,
+    section::{BufferPool, MemoryBudget, SectionData}
+};
 
 pub struct InMemorySection
 {
     data: Vec<u8>,
     cursor: usize,
-    cur_size: usize
+    cur_size: usize,
+    pool: Option<BufferPool>,
+    budget: Option<MemoryBudget>
 }
 
 impl InMemorySection
@@ -42,25 +47,83 @@ impl InMemorySection
     pub fn new(data: Vec<u8>) -> InMemorySection
     {
         return InMemorySection {
-            data: data,
+            data,
             cursor: 0,
-            cur_size: 0
+            cur_size: 0,
+            pool: None,
+            budget: None
+        };
+    }
+
+    pub fn new_charged(data: Vec<u8>, budget: MemoryBudget) -> InMemorySection
+    {
+        return InMemorySection {
+            data,
+            cursor: 0,
+            cur_size: 0,
+            pool: None,
+            budget: Some(budget)
+        };
+    }
+
+    pub fn from_charged_content(data: Vec<u8>, budget: MemoryBudget) -> InMemorySection
+    {
+        return InMemorySection {
+            cur_size: data.len(),
+            data,
+            cursor: 0,
+            pool: None,
+            budget: Some(budget)
+        };
+    }
+
+    pub fn from_content(data: Vec<u8>) -> InMemorySection
+    {
+        return InMemorySection {
+            cur_size: data.len(),
+            data,
+            cursor: 0,
+            pool: None,
+            budget: None
+        };
+    }
+
+    pub fn new_pooled(size: usize, pool: &BufferPool) -> InMemorySection
+    {
+        return InMemorySection {
+            data: pool.get(size),
+            cursor: 0,
+            cur_size: 0,
+            pool: Some(pool.clone()),
+            budget: None
         };
     }
 }
 
+impl Drop for InMemorySection
+{
+    fn drop(&mut self)
+    {
+        if let Some(budget) = &self.budget {
+            budget.release(self.data.len());
+        }
+        if let Some(pool) = &self.pool {
+            pool.put(std::mem::take(&mut self.data));
+        }
+    }
+}
+
 impl Read for InMemorySection
 {
     fn read(&mut self, data: &mut [u8]) -> Result<usize>
     {
-        for i in 0..data.len() {
-            if self.cursor >= self.data.len() {
-                return Ok(i);
-            }
-            data[i] = self.data[self.cursor];
-            self.cursor += 1;
+        if self.cursor >= self.cur_size {
+            return Ok(0);
         }
-        return Ok(data.len());
+        let len = std::cmp::min(data.len(), self.cur_size - self.cursor);
+        data[..len].copy_from_slice(&self.data[self.cursor..self.cursor + len]);
+        self.cursor += len;
+        return Ok(len);
     }
 }
 
@@ -68,15 +131,21 @@ impl Write for InMemorySection
 {
     fn write(&mut self, data: &[u8]) -> Result<usize>
     {
-        for i in 0..data.len() {
-            if self.cursor >= self.data.len() {
-                return Ok(i);
-            }
-            self.data[self.cursor] = data[i];
-            self.cursor += 1;
-            if self.cursor >= self.cur_size {
-                self.cur_size += 1
+        let end = checked_write_end(self.cursor as u64, data.len())? as usize;
+        // Grow the buffer when writing past the preallocated size, filling any gap with zeros.
+        if end > self.data.len() {
+            if let Some(budget) = &self.budget {
+                let extra = end - self.data.len();
+                if !budget.try_reserve(extra) {
+                    return Err(crate::error::Error::MemoryBudget(extra, budget.available()).into());
+                }
             }
+            self.data.resize(end, 0);
+        }
+        self.data[self.cursor..end].copy_from_slice(data);
+        self.cursor = end;
+        if self.cursor > self.cur_size {
+            self.cur_size = self.cursor;
         }
         return Ok(data.len());
     }
@@ -87,16 +156,33 @@ impl Write for InMemorySection
     }
 }
 
-fn slow_but_correct_add(value: usize, offset: isize) -> usize
-//Unfortunatly rust requires much slower add operation (another reason to not use rust for large scale projects)
+pub(super) fn checked_seek(value: usize, offset: i64) -> Result<usize>
 {
-    if offset < 0 {
-        return value - -offset as usize;
-    } else if offset > 0 {
-        return value + offset as usize;
+    let res = if offset < 0 {
+        value.checked_sub(offset.unsigned_abs() as usize)
     } else {
-        return value;
-    }
+        value.checked_add(offset as usize)
+    };
+    return match res {
+        Some(v) => Ok(v),
+        None => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position"
+        ))
+    };
+}
+
+/// Returns the end of a write of `len` bytes at `cursor`, sections may not grow
+/// past [MAX_SECTION_SIZE](crate::format::MAX_SECTION_SIZE).
+pub(super) fn checked_write_end(cursor: u64, len: usize) -> Result<u64>
+{
+    return match cursor.checked_add(len as u64) {
+        Some(end) if end <= MAX_SECTION_SIZE => Ok(end),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "write past the maximum size of a section"
+        ))
+    };
 }
 
 impl Seek for InMemorySection
@@ -105,8 +191,8 @@ impl Seek for InMemorySection
     {
         match state {
             SeekFrom::Start(pos) => self.cursor = pos as usize,
-            SeekFrom::End(pos) => self.cursor = slow_but_correct_add(self.data.len(), pos as isize),
-            SeekFrom::Current(pos) => self.cursor = slow_but_correct_add(self.cursor, pos as isize)
+            SeekFrom::End(pos) => self.cursor = checked_seek(self.cur_size, pos)?,
+            SeekFrom::Current(pos) => self.cursor = checked_seek(self.cursor, pos)?
         }
         return Ok(self.cursor as u64);
     }
@@ -116,11 +202,19 @@ impl SectionData for InMemorySection
 {
     fn load_in_memory(&mut self) -> Result<Vec<u8>>
     {
-        return Ok(self.data.clone());
+        if let Some(budget) = &self.budget {
+            budget.check(self.cur_size)?;
+        }
+        return Ok(self.data[..self.cur_size].to_vec());
     }
 
     fn size(&self) -> usize
     {
         return self.cur_size;
     }
+
+    fn allocated_bytes(&self) -> usize
+    {
+        return self.data.capacity();
+    }
 }
@@ -30,23 +30,72 @@
 
 use std::{
     boxed::Box,
-    io::{Read, Result, Seek, Write},
+    io::{Read, Result, Seek, SeekFrom, Write},
+    ops::{Deref, DerefMut},
     vec::Vec
 };
 
+mod budget;
+#[cfg(feature = "tempfile")]
 mod file;
 mod memory;
+mod pool;
+mod report;
+mod slice;
+mod spool;
+mod storage;
 
-const MEMORY_THRESHOLD: u32 = 100000000;
+pub use budget::MemoryBudget;
+pub use pool::{BufferPool, DEFAULT_MAX_BUFFERS};
+pub(crate) use report::storage_report;
+pub use report::{SectionStorage, StorageKind, StorageReport};
+pub(crate) use slice::{SharedSection, SliceSection};
+pub use spool::{Spool, DEFAULT_SPOOL_THRESHOLD};
+pub use storage::{TempStorage, DEFAULT_MEMORY_FALLBACK};
+
+pub(crate) const MEMORY_THRESHOLD: u32 = 100000000;
 
 /// Opaque variant intended to manipulate section data in the form of standard IO operations.
+///
+/// *All implementations behave the same when the cursor is past the end of the section:
+/// seeking there is allowed, reads return 0 bytes and writes first extend the section
+/// with zeros up to the cursor. A write which would make the section larger than
+/// [MAX_SECTION_SIZE](crate::format::MAX_SECTION_SIZE) fails with
+/// [InvalidInput](std::io::ErrorKind::InvalidInput) and leaves the section unchanged.*
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{ErrorKind, Read, Seek, SeekFrom};
+/// use bpx::encoder::Encoder;
+/// use bpx::format::MAX_SECTION_SIZE;
+/// use bpx::header::SectionHeader;
+/// use bpx::Interface;
+///
+/// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+/// let handle = file.create_section(SectionHeader::new()).unwrap();
+/// let mut section = file.open_section(handle).unwrap();
+/// section.write_all(b"ab").unwrap();
+/// section.seek(SeekFrom::Start(4)).unwrap();
+/// assert_eq!(section.read(&mut [0; 4]).unwrap(), 0);
+/// section.write_all(b"c").unwrap();
+/// assert_eq!(section.load_in_memory().unwrap(), b"ab\0\0c");
+/// section.seek(SeekFrom::Start(MAX_SECTION_SIZE)).unwrap();
+/// assert_eq!(section.write(b"d").unwrap_err().kind(), ErrorKind::InvalidInput);
+/// assert_eq!(section.size(), 5);
+/// ```
 pub trait SectionData: Read + Write + Seek
 {
     /// Loads this section into memory.
     ///
+    /// *The whole content of the section is returned, regardless of the cursor
+    /// position which is left unchanged.*
+    ///
     /// # Errors
     ///
-    /// An [Error](crate::error::Error) is returned if the section could not be loaded.
+    /// An [Error](std::io::Error) is returned if the section could not be loaded;
+    /// the kind is [UnexpectedEof](std::io::ErrorKind::UnexpectedEof) if less than
+    /// [size](Self::size) bytes could be read.
     ///
     /// # Examples
     ///
@@ -57,7 +106,7 @@ pub trait SectionData: Read + Write + Seek
     ///
     /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
     /// let handle = file.create_section(SectionHeader::new()).unwrap();
-    /// let section = file.open_section(handle).unwrap();
+    /// let mut section = file.open_section(handle).unwrap();
     /// let data = section.load_in_memory().unwrap();
     /// assert_eq!(data.len(), 0);
     /// ```
@@ -78,12 +127,154 @@ pub trait SectionData: Read + Write + Seek
     /// assert_eq!(section.size(), 0);
     /// ```
     fn size(&self) -> usize;
+
+    /// Returns where the content of this section is stored.
+    ///
+    /// *The default implementation reports [Memory](self::StorageKind::Memory).*
+    fn storage_kind(&self) -> StorageKind
+    {
+        return StorageKind::Memory;
+    }
+
+    /// Returns the number of bytes of memory held by this section, buffer overheads included.
+    ///
+    /// *The default implementation reports the [size](Self::size) of the section.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::SectionHeaderBuilder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::section::StorageKind;
+    /// use bpx::Interface;
+    ///
+    /// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let handle = file.create_section(SectionHeaderBuilder::new().with_size(64).build()).unwrap();
+    /// let section = file.open_section(handle).unwrap();
+    /// assert_eq!(section.storage_kind(), StorageKind::Memory);
+    /// assert!(section.allocated_bytes() >= 64);
+    /// ```
+    fn allocated_bytes(&self) -> usize
+    {
+        return self.size();
+    }
+}
+
+/// A guard to an opened section which restores the cursor position of
+/// the section when dropped.
+///
+/// *Code receiving a section can therefore seek freely without affecting
+/// whoever opens the section next. A section which has just been created
+/// or loaded is positioned at the start of its data.*
+///
+/// # Examples
+///
+/// ```
+/// use std::io::SeekFrom;
+/// use bpx::encoder::Encoder;
+/// use bpx::header::SectionHeader;
+/// use bpx::Interface;
+///
+/// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+/// let handle = file.create_section(SectionHeader::new()).unwrap();
+/// {
+///     let mut section = file.open_section(handle).unwrap();
+///     section.write_all(b"test").unwrap();
+///     section.seek(SeekFrom::Start(2)).unwrap();
+/// }
+/// let mut section = file.open_section(handle).unwrap();
+/// assert_eq!(section.stream_position().unwrap(), 0);
+/// ```
+pub struct SectionGuard<'a>
+{
+    data: &'a mut dyn SectionData,
+    pos: u64
+}
+
+impl<'a> SectionGuard<'a>
+{
+    /// Creates a new guard saving the current cursor position of a section.
+    ///
+    /// *This function is not intended for direct use.*
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: the section data to guard.
+    ///
+    /// returns: Result<SectionGuard, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](std::io::Error) is returned if the cursor position could not be read.
+    pub fn new(data: &'a mut dyn SectionData) -> Result<SectionGuard<'a>>
+    {
+        let pos = data.stream_position()?;
+        return Ok(SectionGuard { data, pos });
+    }
+}
+
+impl<'a> Deref for SectionGuard<'a>
+{
+    type Target = dyn SectionData + 'a;
+
+    fn deref(&self) -> &Self::Target
+    {
+        return self.data;
+    }
+}
+
+impl<'a> DerefMut for SectionGuard<'a>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target
+    {
+        return self.data;
+    }
+}
+
+impl<'a> Read for SectionGuard<'a>
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>
+    {
+        return self.data.read(buf);
+    }
+}
+
+impl<'a> Write for SectionGuard<'a>
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize>
+    {
+        return self.data.write(buf);
+    }
+
+    fn flush(&mut self) -> Result<()>
+    {
+        return self.data.flush();
+    }
+}
+
+impl<'a> Seek for SectionGuard<'a>
+{
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64>
+    {
+        return self.data.seek(pos);
+    }
+}
+
+impl<'a> Drop for SectionGuard<'a>
+{
+    fn drop(&mut self)
+    {
+        // Errors cannot be reported from drop, the next seek on the section would fail anyway.
+        let _ = self.data.seek(SeekFrom::Start(self.pos));
+    }
 }
 
 /// Creates new section data by automatically choosing the right container given a section size.
 ///
 /// *This function is not intended for direct use.*
 ///
+/// *Without the `tempfile` feature, all sections are stored in memory.*
+///
 /// # Arguments
 ///
 /// * `size`: optional size of section, if None the section will automatically reallocate to fit its content.
@@ -92,15 +283,269 @@ pub trait SectionData: Read + Write + Seek
 ///
 /// # Errors
 ///
-/// An [Error](std::io::Error) is returned in case the temporary file could not be created.
+/// An [Error](std::io::Error) is returned in case the temporary file could not be created
+/// and the section does not fit in the memory fallback of the default
+/// [TempStorage](self::TempStorage).
+#[cfg(feature = "tempfile")]
+pub fn new_section_data(size: Option<u32>) -> Result<Box<dyn SectionData>>
+{
+    return Ok(new_section_data_in(size, &TempStorage::new())?.0);
+}
+
+/// Creates new section data like [new_section_data](self::new_section_data) using
+/// a [TempStorage](self::TempStorage), the returned flag is true if the section is
+/// stored in a temporary file.
+#[cfg(feature = "tempfile")]
+pub(crate) fn new_section_data_in(size: Option<u32>, storage: &TempStorage) -> Result<(Box<dyn SectionData>, bool)>
+{
+    if let Some(s) = size.filter(|s| *s <= MEMORY_THRESHOLD) {
+        return Ok((Box::new(memory::InMemorySection::new(vec![0; s as usize])), false));
+    }
+    return spill(size, storage, None);
+}
+
+/// Creates section data stored in a temporary file, or in memory if the file cannot be created.
+///
+/// *Data kept in memory is charged to `budget` if it is limited and to the fallback of
+/// `storage` otherwise; a [TempFile](crate::error::Error::TempFile) error is returned if it
+/// does not fit. The returned flag is true if the section is stored in a temporary file.*
+#[cfg(feature = "tempfile")]
+fn spill(
+    size: Option<u32>,
+    storage: &TempStorage,
+    budget: Option<&MemoryBudget>
+) -> Result<(Box<dyn SectionData>, bool)>
+{
+    let error = match storage.create_file() {
+        Ok(Some(data)) => {
+            let section = match budget {
+                Some(budget) => file::FileBasedSection::with_budget(data, budget.clone()),
+                None => file::FileBasedSection::new(data)
+            };
+            return Ok((Box::new(section), true));
+        },
+        Ok(None) => None,
+        Err(e) => Some(e)
+    };
+    let charged = storage.fallback(budget);
+    // Sections above the threshold are not preallocated, they grow as they are written.
+    let len = size.filter(|s| *s <= MEMORY_THRESHOLD).unwrap_or(0) as usize;
+    if charged.try_reserve(len) {
+        return Ok((Box::new(memory::InMemorySection::new_charged(vec![0; len], charged.clone())), false));
+    }
+    let error = match error {
+        Some(source) => crate::error::Error::TempFile {
+            dir: storage.dir(),
+            source
+        },
+        None => crate::error::Error::MemoryBudget(len, charged.available())
+    };
+    return Err(error.into());
+}
+
+/// Creates new section data by automatically choosing the right container given a section size.
+///
+/// *This function is not intended for direct use.*
+///
+/// *Without the `tempfile` feature, all sections are stored in memory.*
+///
+/// # Arguments
+///
+/// * `size`: optional size of section, if None the section will automatically reallocate to fit its content.
+///
+/// returns: Result<Box<dyn SectionData, Global>, Error>
+///
+/// # Errors
+///
+/// This function never fails when the `tempfile` feature is disabled.
+#[cfg(not(feature = "tempfile"))]
 pub fn new_section_data(size: Option<u32>) -> Result<Box<dyn SectionData>>
 {
-    if let Some(s) = size {
-        if s > MEMORY_THRESHOLD {
-            return Ok(Box::new(file::FileBasedSection::new(tempfile::tempfile()?)));
-        } else {
-            return Ok(Box::new(memory::InMemorySection::new(vec![0; s as usize])));
-        }
+    // Sections larger than the threshold grow as they are written instead of
+    // being preallocated from a possibly untrusted size.
+    let capacity = std::cmp::min(size.unwrap_or(0), MEMORY_THRESHOLD) as usize;
+    return Ok(Box::new(memory::InMemorySection::new(Vec::with_capacity(capacity))));
+}
+
+/// Creates new section data like [new_section_data](self::new_section_data) using
+/// a [TempStorage](self::TempStorage), the returned flag is true if the section is
+/// stored in a temporary file.
+#[cfg(not(feature = "tempfile"))]
+pub(crate) fn new_section_data_in(size: Option<u32>, _: &TempStorage) -> Result<(Box<dyn SectionData>, bool)>
+{
+    return Ok((new_section_data(size)?, false));
+}
+
+/// Creates new section data like [new_section_data](self::new_section_data) but takes
+/// in-memory buffers from a [BufferPool](self::BufferPool).
+///
+/// *This function is not intended for direct use.*
+///
+/// *Only sections with a known size stored in memory use the pool; the buffer
+/// is returned to the pool when the section is dropped.*
+///
+/// # Arguments
+///
+/// * `size`: optional size of section, if None the section will automatically reallocate to fit its content.
+/// * `pool`: the pool to take buffers from.
+///
+/// returns: Result<Box<dyn SectionData, Global>, Error>
+///
+/// # Errors
+///
+/// An [Error](std::io::Error) is returned in case the temporary file could not be created.
+pub fn new_pooled_section_data(size: Option<u32>, pool: &BufferPool) -> Result<Box<dyn SectionData>>
+{
+    if let Some(s) = size.filter(|s| *s <= MEMORY_THRESHOLD) {
+        return Ok(Box::new(memory::InMemorySection::new_pooled(s as usize, pool)));
+    }
+    #[cfg(feature = "tempfile")]
+    return new_section_data(size);
+    #[cfg(not(feature = "tempfile"))]
+    return Ok(Box::new(memory::InMemorySection::new_pooled(0, pool)));
+}
+
+/// Creates new section data like [new_section_data](self::new_section_data) but charges
+/// in-memory buffers to a [MemoryBudget](self::MemoryBudget).
+///
+/// *Sections which do not fit in the budget are stored in a temporary file; without the
+/// `tempfile` feature a [MemoryBudget](crate::error::Error::MemoryBudget) error is returned instead.
+/// The returned flag is true if the section is stored in a temporary file.*
+pub(crate) fn new_budgeted_section_data(
+    size: u32,
+    budget: &MemoryBudget,
+    storage: &TempStorage
+) -> Result<(Box<dyn SectionData>, bool)>
+{
+    if size <= MEMORY_THRESHOLD && budget.try_reserve(size as usize) {
+        let data = vec![0; size as usize];
+        return Ok((Box::new(memory::InMemorySection::new_charged(data, budget.clone())), false));
     }
-    return Ok(Box::new(file::FileBasedSection::new(tempfile::tempfile()?)));
+    #[cfg(feature = "tempfile")]
+    return spill(Some(size), storage, Some(budget));
+    #[cfg(not(feature = "tempfile"))]
+    let _ = storage;
+    #[cfg(not(feature = "tempfile"))]
+    if size <= MEMORY_THRESHOLD {
+        return Err(crate::error::Error::MemoryBudget(size as usize, budget.available()).into());
+    }
+    // Grows as it is written, each reallocation being charged to the budget.
+    #[cfg(not(feature = "tempfile"))]
+    return Ok((Box::new(memory::InMemorySection::new_charged(Vec::new(), budget.clone())), false));
+}
+
+/// Wraps already loaded content into in-memory section data charged to a
+/// [MemoryBudget](self::MemoryBudget).
+///
+/// # Errors
+///
+/// A [MemoryBudget](crate::error::Error::MemoryBudget) error is returned if the content does not fit.
+pub(crate) fn budgeted_section_from_content(data: Vec<u8>, budget: &MemoryBudget) -> Result<Box<dyn SectionData>>
+{
+    if !budget.try_reserve(data.len()) {
+        return Err(crate::error::Error::MemoryBudget(data.len(), budget.available()).into());
+    }
+    return Ok(Box::new(memory::InMemorySection::from_charged_content(data, budget.clone())));
+}
+
+/// Wraps already loaded content into in-memory section data positioned at the start.
+pub(crate) fn section_data_from_content(data: Vec<u8>) -> Box<dyn SectionData>
+{
+    return Box::new(memory::InMemorySection::from_content(data));
+}
+
+/// The buffer used to stage section data while saving a BPX.
+#[cfg(feature = "tempfile")]
+pub(crate) enum StagingBuffer
+{
+    File(std::fs::File),
+    Memory(memory::InMemorySection)
+}
+
+#[cfg(feature = "tempfile")]
+impl Read for StagingBuffer
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>
+    {
+        return match self {
+            StagingBuffer::File(v) => v.read(buf),
+            StagingBuffer::Memory(v) => v.read(buf)
+        };
+    }
+}
+
+#[cfg(feature = "tempfile")]
+impl Write for StagingBuffer
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize>
+    {
+        return match self {
+            StagingBuffer::File(v) => v.write(buf),
+            StagingBuffer::Memory(v) => v.write(buf)
+        };
+    }
+
+    fn flush(&mut self) -> Result<()>
+    {
+        return match self {
+            StagingBuffer::File(v) => v.flush(),
+            StagingBuffer::Memory(v) => v.flush()
+        };
+    }
+}
+
+#[cfg(feature = "tempfile")]
+impl Seek for StagingBuffer
+{
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64>
+    {
+        return match self {
+            StagingBuffer::File(v) => v.seek(pos),
+            StagingBuffer::Memory(v) => v.seek(pos)
+        };
+    }
+}
+
+/// The buffer used to stage section data while saving a BPX.
+#[cfg(not(feature = "tempfile"))]
+pub(crate) type StagingBuffer = std::io::Cursor<Vec<u8>>;
+
+/// Creates a new [StagingBuffer](self::StagingBuffer) using the default [TempStorage](self::TempStorage).
+pub(crate) fn new_staging_buffer() -> Result<StagingBuffer>
+{
+    return new_staging_buffer_in(&TempStorage::new(), 0);
+}
+
+/// Creates a new [StagingBuffer](self::StagingBuffer) for about `size` bytes.
+///
+/// *If no temporary file can be created, the buffer is kept in memory when `size`
+/// fits in the fallback of `storage`; writes beyond the fallback fail.*
+#[cfg(feature = "tempfile")]
+pub(crate) fn new_staging_buffer_in(storage: &TempStorage, size: u64) -> Result<StagingBuffer>
+{
+    let error = match storage.create_file() {
+        Ok(Some(data)) => return Ok(StagingBuffer::File(data)),
+        Ok(None) => None,
+        Err(e) => Some(e)
+    };
+    let charged = storage.fallback(None);
+    if size <= charged.available() as u64 {
+        let data = memory::InMemorySection::new_charged(Vec::new(), charged.clone());
+        return Ok(StagingBuffer::Memory(data));
+    }
+    let error = match error {
+        Some(source) => crate::error::Error::TempFile {
+            dir: storage.dir(),
+            source
+        },
+        None => crate::error::Error::MemoryBudget(size as usize, charged.available())
+    };
+    return Err(error.into());
+}
+
+/// Creates a new [StagingBuffer](self::StagingBuffer) for about `size` bytes.
+#[cfg(not(feature = "tempfile"))]
+pub(crate) fn new_staging_buffer_in(_: &TempStorage, _: u64) -> Result<StagingBuffer>
+{
+    return Ok(std::io::Cursor::new(Vec::new()));
 }
@@ -0,0 +1,141 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::sync::{Arc, Mutex};
+
+/// Default number of buffers kept by a [BufferPool](self::BufferPool).
+pub const DEFAULT_MAX_BUFFERS: usize = 65536;
+
+/// A shared pool of reusable byte buffers.
+///
+/// *In-memory sections created through a pool return their buffer to the pool
+/// when dropped, so the next sections can reuse the allocation. Cloning a pool
+/// gives another handle to the same buffers, which allows multiple encoders
+/// to share one pool.*
+///
+/// # Examples
+///
+/// ```
+/// use bpx::section::BufferPool;
+///
+/// let pool = BufferPool::new();
+/// let mut buf = pool.get(0);
+/// buf.extend_from_slice(b"test");
+/// pool.put(buf);
+/// assert_eq!(pool.len(), 1);
+/// let buf = pool.get(2);
+/// assert_eq!(buf, vec![0; 2]);
+/// assert!(pool.is_empty());
+/// ```
+#[derive(Clone)]
+pub struct BufferPool
+{
+    buffers: Arc<Mutex<Vec<Vec<u8>>>>,
+    max_buffers: usize
+}
+
+impl BufferPool
+{
+    /// Creates a new empty pool keeping at most [DEFAULT_MAX_BUFFERS](self::DEFAULT_MAX_BUFFERS) buffers.
+    pub fn new() -> BufferPool
+    {
+        return BufferPool::with_max_buffers(DEFAULT_MAX_BUFFERS);
+    }
+
+    /// Creates a new empty pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_buffers`: the maximum number of buffers to keep, extra buffers are freed.
+    ///
+    /// returns: BufferPool
+    pub fn with_max_buffers(max_buffers: usize) -> BufferPool
+    {
+        return BufferPool {
+            buffers: Arc::new(Mutex::new(Vec::new())),
+            max_buffers
+        };
+    }
+
+    /// Takes a buffer out of the pool, allocating a new one if the pool is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `len`: the length of the returned buffer, all bytes are set to 0.
+    ///
+    /// returns: Vec<u8>
+    pub fn get(&self, len: usize) -> Vec<u8>
+    {
+        let buffer = self.buffers.lock().ok().and_then(|mut buffers| buffers.pop());
+        return match buffer {
+            Some(mut buf) => {
+                buf.resize(len, 0);
+                buf
+            },
+            None => vec![0; len]
+        };
+    }
+
+    /// Gives a buffer back to the pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf`: the buffer to recycle.
+    pub fn put(&self, mut buf: Vec<u8>)
+    {
+        if buf.capacity() == 0 {
+            return;
+        }
+        if let Ok(mut buffers) = self.buffers.lock() {
+            if buffers.len() < self.max_buffers {
+                buf.clear();
+                buffers.push(buf);
+            }
+        }
+    }
+
+    /// Returns the number of buffers currently available in the pool.
+    pub fn len(&self) -> usize
+    {
+        return self.buffers.lock().map(|buffers| buffers.len()).unwrap_or(0);
+    }
+
+    /// Returns true if no buffer is currently available in the pool.
+    pub fn is_empty(&self) -> bool
+    {
+        return self.len() == 0;
+    }
+}
+
+impl Default for BufferPool
+{
+    fn default() -> Self
+    {
+        return BufferPool::new();
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::fmt::{Display, Formatter};
+
+use crate::section::SectionData;
+
+/// Where the content of a section is stored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StorageKind
+{
+    /// The content is held in memory.
+    Memory,
+
+    /// The content is held in a temporary file, only a small page cache is in memory.
+    TempFile
+}
+
+/// Storage of a single loaded section, see [StorageReport](self::StorageReport).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SectionStorage
+{
+    /// The index of the section.
+    pub index: u32,
+
+    /// Where the content of the section is stored.
+    pub kind: StorageKind,
+
+    /// The size in bytes of the content of the section.
+    pub size: usize,
+
+    /// The number of bytes of memory held by the section, buffer overheads included.
+    pub allocated_bytes: usize
+}
+
+/// Memory and temporary file usage of the loaded sections of a BPX, as returned by
+/// [storage_report](crate::Interface::storage_report).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageReport
+{
+    /// The storage of each loaded section, in index order.
+    ///
+    /// *Sections which were not loaded yet hold no storage and are not listed.*
+    pub sections: Vec<SectionStorage>
+}
+
+impl StorageReport
+{
+    /// Returns the total number of bytes of memory held by the loaded sections.
+    pub fn memory_bytes(&self) -> usize
+    {
+        return self.sections.iter().map(|v| v.allocated_bytes).sum();
+    }
+
+    /// Returns the total number of bytes of section content stored in temporary files.
+    pub fn temp_file_bytes(&self) -> usize
+    {
+        return self
+            .sections
+            .iter()
+            .filter(|v| v.kind == StorageKind::TempFile)
+            .map(|v| v.size)
+            .sum();
+    }
+}
+
+impl Display for StorageKind
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        return match self {
+            StorageKind::Memory => f.write_str("memory"),
+            StorageKind::TempFile => f.write_str("temporary file")
+        };
+    }
+}
+
+impl Display for StorageReport
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        writeln!(
+            f,
+            "loaded sections: {}, memory: {} byte(s), temporary files: {} byte(s)",
+            self.sections.len(),
+            self.memory_bytes(),
+            self.temp_file_bytes()
+        )?;
+        for section in &self.sections {
+            writeln!(
+                f,
+                "[{}] {}: size {} allocated {}",
+                section.index, section.kind, section.size, section.allocated_bytes
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+/// Builds the report of the loaded sections out of a list of optional section data in index order.
+pub(crate) fn storage_report<'a, TIter: Iterator<Item = Option<&'a dyn SectionData>>>(sections: TIter) -> StorageReport
+{
+    let sections = sections
+        .enumerate()
+        .filter_map(|(index, data)| {
+            data.map(|data| SectionStorage {
+                index: index as u32,
+                kind: data.storage_kind(),
+                size: data.size(),
+                allocated_bytes: data.allocated_bytes()
+            })
+        })
+        .collect();
+    return StorageReport { sections };
+}
@@ -0,0 +1,227 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::{
+    borrow::Cow,
+    io::{Read, Result, Seek, SeekFrom, Write},
+    ops::Range,
+    sync::Arc
+};
+
+use crate::section::{
+    memory::{checked_seek, checked_write_end},
+    SectionData
+};
+
+/// Section data viewing a borrowed slice, copied only when first written to.
+pub struct SliceSection<'a>
+{
+    data: Cow<'a, [u8]>,
+    cursor: usize
+}
+
+impl<'a> SliceSection<'a>
+{
+    pub fn new(data: &'a [u8]) -> SliceSection<'a>
+    {
+        return SliceSection {
+            data: Cow::Borrowed(data),
+            cursor: 0
+        };
+    }
+}
+
+impl<'a> Read for SliceSection<'a>
+{
+    fn read(&mut self, data: &mut [u8]) -> Result<usize>
+    {
+        if self.cursor >= self.data.len() {
+            return Ok(0);
+        }
+        let len = std::cmp::min(data.len(), self.data.len() - self.cursor);
+        data[..len].copy_from_slice(&self.data[self.cursor..self.cursor + len]);
+        self.cursor += len;
+        return Ok(len);
+    }
+}
+
+impl<'a> Write for SliceSection<'a>
+{
+    fn write(&mut self, data: &[u8]) -> Result<usize>
+    {
+        let end = checked_write_end(self.cursor as u64, data.len())? as usize;
+        let buf = self.data.to_mut();
+        if end > buf.len() {
+            buf.resize(end, 0);
+        }
+        buf[self.cursor..end].copy_from_slice(data);
+        self.cursor = end;
+        return Ok(data.len());
+    }
+
+    fn flush(&mut self) -> Result<()>
+    {
+        return Ok(());
+    }
+}
+
+impl<'a> Seek for SliceSection<'a>
+{
+    fn seek(&mut self, state: SeekFrom) -> Result<u64>
+    {
+        match state {
+            SeekFrom::Start(pos) => self.cursor = pos as usize,
+            SeekFrom::End(pos) => self.cursor = checked_seek(self.data.len(), pos)?,
+            SeekFrom::Current(pos) => self.cursor = checked_seek(self.cursor, pos)?
+        }
+        return Ok(self.cursor as u64);
+    }
+}
+
+impl<'a> SectionData for SliceSection<'a>
+{
+    fn load_in_memory(&mut self) -> Result<Vec<u8>>
+    {
+        return Ok(self.data.to_vec());
+    }
+
+    fn size(&self) -> usize
+    {
+        return self.data.len();
+    }
+
+    fn allocated_bytes(&self) -> usize
+    {
+        // A borrowed view holds no memory of its own.
+        return match &self.data {
+            Cow::Borrowed(_) => 0,
+            Cow::Owned(v) => v.capacity()
+        };
+    }
+}
+
+/// Section data viewing a range of a shared buffer, copied only when first written to.
+pub struct SharedSection
+{
+    shared: Arc<[u8]>,
+    range: Range<usize>,
+    owned: Option<Vec<u8>>,
+    cursor: usize
+}
+
+impl SharedSection
+{
+    pub fn new(shared: Arc<[u8]>, range: Range<usize>) -> SharedSection
+    {
+        return SharedSection {
+            shared,
+            range,
+            owned: None,
+            cursor: 0
+        };
+    }
+
+    fn data(&self) -> &[u8]
+    {
+        return match &self.owned {
+            Some(v) => v,
+            None => &self.shared[self.range.clone()]
+        };
+    }
+}
+
+impl Read for SharedSection
+{
+    fn read(&mut self, data: &mut [u8]) -> Result<usize>
+    {
+        let src = self.data();
+        if self.cursor >= src.len() {
+            return Ok(0);
+        }
+        let len = std::cmp::min(data.len(), src.len() - self.cursor);
+        data[..len].copy_from_slice(&src[self.cursor..self.cursor + len]);
+        self.cursor += len;
+        return Ok(len);
+    }
+}
+
+impl Write for SharedSection
+{
+    fn write(&mut self, data: &[u8]) -> Result<usize>
+    {
+        let end = checked_write_end(self.cursor as u64, data.len())? as usize;
+        let (shared, range) = (&self.shared, &self.range);
+        let buf = self.owned.get_or_insert_with(|| shared[range.clone()].to_vec());
+        if end > buf.len() {
+            buf.resize(end, 0);
+        }
+        buf[self.cursor..end].copy_from_slice(data);
+        self.cursor = end;
+        return Ok(data.len());
+    }
+
+    fn flush(&mut self) -> Result<()>
+    {
+        return Ok(());
+    }
+}
+
+impl Seek for SharedSection
+{
+    fn seek(&mut self, state: SeekFrom) -> Result<u64>
+    {
+        match state {
+            SeekFrom::Start(pos) => self.cursor = pos as usize,
+            SeekFrom::End(pos) => self.cursor = checked_seek(self.data().len(), pos)?,
+            SeekFrom::Current(pos) => self.cursor = checked_seek(self.cursor, pos)?
+        }
+        return Ok(self.cursor as u64);
+    }
+}
+
+impl SectionData for SharedSection
+{
+    fn load_in_memory(&mut self) -> Result<Vec<u8>>
+    {
+        return Ok(self.data().to_vec());
+    }
+
+    fn size(&self) -> usize
+    {
+        return self.data().len();
+    }
+
+    fn allocated_bytes(&self) -> usize
+    {
+        // The shared buffer is owned by whoever created the decoders.
+        return match &self.owned {
+            Some(v) => v.capacity(),
+            None => 0
+        };
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::io::{Cursor, Read, Result, Seek, SeekFrom};
+
+use super::{new_staging_buffer, StagingBuffer, MEMORY_THRESHOLD};
+
+/// Default number of bytes a [Spool](self::Spool) keeps in memory, same as the
+/// size above which sections are stored in temporary files.
+pub const DEFAULT_SPOOL_THRESHOLD: usize = MEMORY_THRESHOLD as usize;
+
+enum SpoolData
+{
+    Memory(Cursor<Vec<u8>>),
+    Staged(StagingBuffer)
+}
+
+/// A seekable copy of a non-seekable stream.
+///
+/// *Streams up to a threshold are kept in memory, larger streams are spilled
+/// to a temporary file. Without the `tempfile` feature, all streams are kept
+/// in memory.*
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{Read, Seek, SeekFrom};
+/// use bpx::section::Spool;
+///
+/// let mut spool = Spool::new(&b"piped data"[..], 4).unwrap();
+/// spool.seek(SeekFrom::Start(6)).unwrap();
+/// let mut buf = String::new();
+/// spool.read_to_string(&mut buf).unwrap();
+/// assert_eq!(buf, "data");
+/// ```
+pub struct Spool
+{
+    data: SpoolData
+}
+
+impl Spool
+{
+    /// Copies a stream into a new spool positioned at the start.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader`: the stream to copy until its end.
+    /// * `threshold`: the maximum number of bytes to keep in memory.
+    ///
+    /// returns: Result<Spool, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](std::io::Error) is returned if the stream could not be read
+    /// or the temporary file could not be created or written.
+    pub fn new<TRead: Read>(mut reader: TRead, threshold: usize) -> Result<Spool>
+    {
+        let mut head = Vec::new();
+        (&mut reader).take(threshold as u64 + 1).read_to_end(&mut head)?;
+        if cfg!(not(feature = "tempfile")) {
+            reader.read_to_end(&mut head)?;
+        }
+        if head.len() <= threshold || cfg!(not(feature = "tempfile")) {
+            return Ok(Spool {
+                data: SpoolData::Memory(Cursor::new(head))
+            });
+        }
+        let mut staged = new_staging_buffer()?;
+        std::io::Write::write_all(&mut staged, &head)?;
+        std::mem::drop(head);
+        std::io::copy(&mut reader, &mut staged)?;
+        staged.seek(SeekFrom::Start(0))?;
+        return Ok(Spool {
+            data: SpoolData::Staged(staged)
+        });
+    }
+
+    /// Returns true if the spooled stream is kept in memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::section::Spool;
+    ///
+    /// assert!(Spool::new(&b"BPX"[..], 3).unwrap().is_in_memory());
+    /// ```
+    pub fn is_in_memory(&self) -> bool
+    {
+        return matches!(self.data, SpoolData::Memory(_));
+    }
+}
+
+impl Read for Spool
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>
+    {
+        return match &mut self.data {
+            SpoolData::Memory(v) => v.read(buf),
+            SpoolData::Staged(v) => v.read(buf)
+        };
+    }
+}
+
+impl Seek for Spool
+{
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64>
+    {
+        return match &mut self.data {
+            SpoolData::Memory(v) => v.seek(pos),
+            SpoolData::Staged(v) => v.seek(pos)
+        };
+    }
+}
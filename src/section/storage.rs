@@ -0,0 +1,159 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::path::PathBuf;
+
+use crate::section::MemoryBudget;
+
+/// Default number of bytes a [TempStorage](self::TempStorage) keeps in memory
+/// when temporary files cannot be created.
+pub const DEFAULT_MEMORY_FALLBACK: usize = 1 << 28;
+
+/// Describes where section data which does not fit in memory is stored.
+///
+/// *Large sections, sections of unknown size and the data staged while saving
+/// are stored in temporary files. If a temporary file cannot be created, for
+/// example on sandboxed platforms, the data is kept in memory up to a limit
+/// shared by all users of the storage; only when that limit is exceeded does
+/// the operation fail with a [TempFile](crate::error::Error::TempFile) error.
+/// Cloning a storage gives another handle to the same limit. Without the
+/// `tempfile` feature, all data is stored in memory.*
+///
+/// # Examples
+///
+/// ```
+/// use bpx::section::TempStorage;
+///
+/// let storage = TempStorage::new().with_dir("/path/to/scratch").with_memory_fallback(1 << 20);
+/// assert_eq!(storage.dir(), std::path::Path::new("/path/to/scratch"));
+/// assert!(!storage.is_in_memory());
+/// assert!(TempStorage::in_memory().is_in_memory());
+/// ```
+#[derive(Clone, Debug)]
+pub struct TempStorage
+{
+    dir: Option<PathBuf>,
+    in_memory: bool,
+    fallback: MemoryBudget
+}
+
+impl TempStorage
+{
+    /// Creates a new storage using the system temporary directory with a
+    /// fallback of [DEFAULT_MEMORY_FALLBACK](self::DEFAULT_MEMORY_FALLBACK) bytes.
+    pub fn new() -> TempStorage
+    {
+        return TempStorage {
+            dir: None,
+            in_memory: false,
+            fallback: MemoryBudget::new(DEFAULT_MEMORY_FALLBACK)
+        };
+    }
+
+    /// Creates a storage which never creates temporary files and keeps all
+    /// section data in memory, without limit.
+    pub fn in_memory() -> TempStorage
+    {
+        return TempStorage {
+            dir: None,
+            in_memory: true,
+            fallback: MemoryBudget::unlimited()
+        };
+    }
+
+    /// Sets the directory temporary files are created in.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir`: the directory.
+    ///
+    /// returns: TempStorage
+    pub fn with_dir<TPath: Into<PathBuf>>(mut self, dir: TPath) -> Self
+    {
+        self.dir = Some(dir.into());
+        return self;
+    }
+
+    /// Sets the number of bytes kept in memory when temporary files cannot be created.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes`: the maximum number of bytes, 0 to fail as soon as a temporary file cannot be created.
+    ///
+    /// returns: TempStorage
+    pub fn with_memory_fallback(mut self, bytes: usize) -> Self
+    {
+        self.fallback = MemoryBudget::new(bytes);
+        return self;
+    }
+
+    /// Returns the directory temporary files are created in.
+    pub fn dir(&self) -> PathBuf
+    {
+        return self.dir.clone().unwrap_or_else(std::env::temp_dir);
+    }
+
+    /// Returns true if this storage never creates temporary files.
+    pub fn is_in_memory(&self) -> bool
+    {
+        return self.in_memory;
+    }
+
+    /// Returns the number of bytes currently kept in memory in place of temporary files.
+    pub fn fallback_usage(&self) -> usize
+    {
+        return self.fallback.used();
+    }
+
+    /// Creates a temporary file, returns None if the data should be kept in memory.
+    #[cfg(feature = "tempfile")]
+    pub(crate) fn create_file(&self) -> std::io::Result<Option<std::fs::File>>
+    {
+        if self.in_memory {
+            return Ok(None);
+        }
+        return tempfile::tempfile_in(self.dir()).map(Some);
+    }
+
+    /// Returns the budget to charge data kept in memory to, `budget` unless it is unlimited.
+    pub(crate) fn fallback<'a>(&'a self, budget: Option<&'a MemoryBudget>) -> &'a MemoryBudget
+    {
+        return match budget {
+            Some(budget) if budget.limit() != usize::MAX => budget,
+            _ => &self.fallback
+        };
+    }
+}
+
+impl Default for TempStorage
+{
+    fn default() -> Self
+    {
+        return TempStorage::new();
+    }
+}
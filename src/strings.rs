@@ -28,13 +28,100 @@
 
 //! A set of helpers to manipulate BPX string sections.
 
-use std::{collections::HashMap, fs::DirEntry, io::SeekFrom, path::Path, string::String};
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    fs::DirEntry,
+    io::SeekFrom,
+    path::Path,
+    string::String
+};
 
-use crate::{error::Error, section::SectionData, Interface, Result, SectionHandle};
+use crate::{
+    error::section_context,
+    format::MAX_SECTION_SIZE,
+    header::SECTION_TYPE_STRING,
+    section::SectionData,
+    Interface,
+    Result,
+    SectionHandle
+};
 use std::collections::hash_map::Entry;
 
+const STRING_READ_CHUNK_SIZE: usize = 64;
+
+/// Represents a string section error.
+#[derive(Debug)]
+pub enum Error
+{
+    /// Describes a string which is not null-terminated before the end of the section.
+    ///
+    /// # Arguments
+    /// * last operation name before failure.
+    Truncation(&'static str),
+
+    /// Describes a string which is not valid UTF-8.
+    ///
+    /// # Arguments
+    /// * last operation name before failure.
+    Utf8(&'static str),
+
+    /// Describes a path which does not end with a file name.
+    InvalidPath,
+
+    /// Describes a path which is not unicode compatible (BPX only supports UTF-8).
+    NonUnicodePath,
+
+    /// Describes a cached string which was overwritten in its section.
+    ///
+    /// *Only detected with the `section-debug` feature.*
+    ///
+    /// # Arguments
+    /// * offset of the string in the section.
+    Aliased(u32),
+
+    /// Describes a string which does not fit in its section because string
+    /// offsets and section sizes are limited to 32 bits.
+    ///
+    /// # Arguments
+    /// * the string which could not be written.
+    /// * size in bytes the section would have reached.
+    Capacity(String, u64)
+}
+
+impl Display for Error
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        return match self {
+            Error::Truncation(e) => f.write_str(&format!(
+                "unexpected EOF while reading {}, are you sure the data is not truncated?",
+                e
+            )),
+            Error::Utf8(e) => f.write_str(&format!("utf8 decoding/encoding error in {}", e)),
+            Error::InvalidPath => f.write_str("incorrect path format"),
+            Error::NonUnicodePath => f.write_str("non unicode path (BPX only supports UTF-8)"),
+            Error::Aliased(address) => f.write_str(&format!(
+                "string at offset {} was overwritten while it was cached (the section is aliased)",
+                address
+            )),
+            Error::Capacity(s, size) => f.write_str(&format!(
+                "string '{}' does not fit in its section (found {} bytes, max is 2 pow 32 bytes)",
+                s, size
+            ))
+        };
+    }
+}
+
+impl std::error::Error for Error {}
+
 /// Helper class to manage a BPX string section.
 ///
+/// *Strings are cached once read or written. With the `section-debug` feature,
+/// each cache hit is checked against the section content so that code
+/// overwriting the section through another open is reported as
+/// [Aliased](self::Error::Aliased) instead of returning stale strings.*
+///
 /// # Examples
 ///
 /// ```
@@ -72,6 +159,12 @@ impl StringSection
         };
     }
 
+    /// Returns the handle to the string section.
+    pub fn handle(&self) -> SectionHandle
+    {
+        return self.handle;
+    }
+
     /// Reads a string from the section.
     ///
     /// # Arguments
@@ -88,10 +181,13 @@ impl StringSection
     pub fn get<TInterface: Interface>(&mut self, interface: &mut TInterface, address: u32) -> Result<&str>
     {
         let res = match self.cache.entry(address) {
-            Entry::Occupied(o) => o.into_mut(),
+            Entry::Occupied(o) => {
+                #[cfg(feature = "section-debug")]
+                check_cached(interface, self.handle, address, o.get())?;
+                o.into_mut()
+            },
             Entry::Vacant(o) => {
-                let data = interface.open_section(self.handle)?;
-                let s = low_level_read_string(address, data)?;
+                let s = read_string_range(interface, self.handle, address)?;
                 o.insert(s)
             }
         };
@@ -112,39 +208,356 @@ impl StringSection
     /// Returns an [Error](crate::error::Error) if the string could not be written.
     pub fn put<TInterface: Interface>(&mut self, interface: &mut TInterface, s: &str) -> Result<u32>
     {
-        let data = interface.open_section(self.handle)?;
-        let address = low_level_write_string(s, data)?;
+        let context = section_context(interface, self.handle);
+        let mut data = interface.open_section(self.handle)?;
+        let address = low_level_write_string(s, &mut *data).map_err(&context)?;
+        #[cfg(feature = "section-debug")]
+        if self.cache.get(&address).map(|v| v != s).unwrap_or(false) {
+            // The section shrank since the string at this address was cached.
+            return Err(context(Error::Aliased(address).into()));
+        }
         self.cache.insert(address, String::from(s));
         return Ok(address);
     }
 }
 
-fn low_level_read_string(ptr: u32, string_section: &mut dyn SectionData) -> Result<String>
+/// Statistics collected by a [StringInterner](self::StringInterner).
+///
+/// *Sizes include the null terminator of each string.*
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct InternerStats
+{
+    /// Number of strings interned, including duplicates.
+    pub strings: u64,
+
+    /// Number of bytes written to string sections.
+    pub written_bytes: u64,
+
+    /// Number of bytes not written because the string was already in the same section.
+    pub saved_bytes: u64,
+
+    /// Number of bytes written for strings already present in another section.
+    pub cross_section_bytes: u64
+}
+
+/// Deduplicates strings written to one or more string sections.
+///
+/// *A string is written once per section: interning it again in the same
+/// section returns its first address. Strings can only be referenced from the
+/// section they are stored in, so a string already present in another section
+/// is written again and counted in
+/// [cross_section_bytes](self::InternerStats::cross_section_bytes).*
+///
+/// # Examples
+///
+/// ```
+/// use bpx::builder::SectionHeaderBuilder;
+/// use bpx::encoder::Encoder;
+/// use bpx::strings::StringInterner;
+/// use bpx::Interface;
+///
+/// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+/// let names = file.create_section(SectionHeaderBuilder::strings().build()).unwrap();
+/// let symbols = file.create_section(SectionHeaderBuilder::strings().build()).unwrap();
+/// let mut interner = StringInterner::new();
+/// let address = interner.intern(&mut file, names, "main").unwrap();
+/// assert_eq!(interner.intern(&mut file, names, "main").unwrap(), address);
+/// interner.intern(&mut file, symbols, "main").unwrap();
+/// let stats = interner.stats();
+/// assert_eq!(stats.written_bytes, 10);
+/// assert_eq!(stats.saved_bytes, 5);
+/// assert_eq!(stats.cross_section_bytes, 5);
+/// assert_eq!(file.open_section(names).unwrap().size(), 5);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StringInterner
+{
+    sections: HashMap<usize, HashMap<String, u32>>,
+    stats: InternerStats
+}
+
+impl StringInterner
+{
+    /// Creates a new empty interner.
+    pub fn new() -> StringInterner
+    {
+        return StringInterner::default();
+    }
+
+    /// Registers the strings already stored in a section, so that they are reused.
+    ///
+    /// # Arguments
+    ///
+    /// * `interface`: the BPX IO interface.
+    /// * `handle`: handle to the string section.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](crate::error::Error) if the section could not be loaded
+    /// or contains a string which is not valid UTF-8.
+    pub fn load<TInterface: Interface>(&mut self, interface: &mut TInterface, handle: SectionHandle) -> Result<()>
+    {
+        let context = section_context(interface, handle);
+        let data = interface.open_section(handle)?.load_in_memory()?;
+        let strings = self.sections.entry(handle.0).or_default();
+        // An unterminated tail cannot be referenced.
+        let mut address = 0;
+        for bytes in data.split(|v| *v == 0).take(data.iter().filter(|v| **v == 0).count()) {
+            let s = std::str::from_utf8(bytes).map_err(|_| context(Error::Utf8("string section read").into()))?;
+            strings.entry(String::from(s)).or_insert(address as u32);
+            address += bytes.len() + 1;
+        }
+        return Ok(());
+    }
+
+    /// Writes a string into a section unless the section already contains it.
+    ///
+    /// # Arguments
+    ///
+    /// * `interface`: the BPX IO interface.
+    /// * `handle`: handle to the string section.
+    /// * `s`: the string to write.
+    ///
+    /// returns: Result<u32, Error>; the address of the string in the section.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](crate::error::Error) if the string could not be written.
+    pub fn intern<TInterface: Interface>(
+        &mut self,
+        interface: &mut TInterface,
+        handle: SectionHandle,
+        s: &str
+    ) -> Result<u32>
+    {
+        let size = s.len() as u64 + 1;
+        self.stats.strings += 1;
+        if let Some(address) = self.address(handle, s) {
+            self.stats.saved_bytes += size;
+            return Ok(address);
+        }
+        let context = section_context(interface, handle);
+        let mut data = interface.open_section(handle)?;
+        let address = low_level_write_string(s, &mut *data).map_err(context)?;
+        if self.sections.iter().any(|(k, v)| *k != handle.0 && v.contains_key(s)) {
+            self.stats.cross_section_bytes += size;
+        }
+        self.stats.written_bytes += size;
+        self.sections.entry(handle.0).or_default().insert(String::from(s), address);
+        return Ok(address);
+    }
+
+    /// Returns the address of a string already interned in a section.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: handle to the string section.
+    /// * `s`: the string to look for.
+    ///
+    /// returns: Option<u32>
+    pub fn address(&self, handle: SectionHandle, s: &str) -> Option<u32>
+    {
+        return self.sections.get(&handle.0).and_then(|v| v.get(s)).copied();
+    }
+
+    /// Returns the statistics of this interner.
+    pub fn stats(&self) -> InternerStats
+    {
+        return self.stats;
+    }
+}
+
+/// Duplicated content found in the string sections of a BPX by
+/// [string_duplication_report](self::string_duplication_report).
+///
+/// *Sizes include the null terminator of each string.*
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StringReport
+{
+    /// Number of string sections analyzed.
+    pub sections: u32,
+
+    /// Number of strings found, including duplicates.
+    pub strings: u64,
+
+    /// Total size of the strings.
+    pub total_bytes: u64,
+
+    /// Size of the strings stored more than once in the same section.
+    pub duplicate_bytes: u64,
+
+    /// Size of the strings already stored in a previous string section.
+    pub cross_section_bytes: u64
+}
+
+impl StringReport
+{
+    /// Returns the number of bytes which could be saved by storing each string once per file.
+    pub fn wasted_bytes(&self) -> u64
+    {
+        return self.duplicate_bytes + self.cross_section_bytes;
+    }
+}
+
+/// Measures the duplicated content of all [SECTION_TYPE_STRING](crate::header::SECTION_TYPE_STRING)
+/// sections of a BPX.
+///
+/// *Duplicates within a section can be removed with a [StringInterner](self::StringInterner);
+/// duplicates across sections could only be removed by merging the sections.*
+///
+/// # Arguments
+///
+/// * `interface`: the BPX IO interface.
+///
+/// returns: Result<StringReport, Error>
+///
+/// # Errors
+///
+/// Returns an [Error](crate::error::Error) if a string section could not be loaded.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::builder::SectionHeaderBuilder;
+/// use bpx::encoder::Encoder;
+/// use bpx::strings::{string_duplication_report, StringSection};
+///
+/// let mut file = Encoder::new(Vec::<u8>::new()).unwrap();
+/// let handle = file.create_section(SectionHeaderBuilder::strings().build()).unwrap();
+/// let mut strings = StringSection::new(handle);
+/// strings.put(&mut file, "name").unwrap();
+/// strings.put(&mut file, "name").unwrap();
+/// let report = string_duplication_report(&mut file).unwrap();
+/// assert_eq!(report.strings, 2);
+/// assert_eq!(report.wasted_bytes(), 5);
+/// ```
+pub fn string_duplication_report<TInterface: Interface>(interface: &mut TInterface) -> Result<StringReport>
+{
+    let mut report = StringReport::default();
+    let mut seen: HashMap<Vec<u8>, usize> = HashMap::new();
+    for (index, handle) in interface.find_all_sections_of_type(SECTION_TYPE_STRING).into_iter().enumerate() {
+        let data = interface.open_section(handle)?.load_in_memory()?;
+        report.sections += 1;
+        // An unterminated tail is not a string.
+        for bytes in data.split(|v| *v == 0).take(data.iter().filter(|v| **v == 0).count()) {
+            let size = bytes.len() as u64 + 1;
+            report.strings += 1;
+            report.total_bytes += size;
+            match seen.entry(bytes.to_vec()) {
+                Entry::Occupied(o) if *o.get() == index => report.duplicate_bytes += size,
+                Entry::Occupied(mut o) => {
+                    report.cross_section_bytes += size;
+                    // The next copy in this section is a duplicate within the section.
+                    o.insert(index);
+                },
+                Entry::Vacant(o) => {
+                    o.insert(index);
+                }
+            }
+        }
+    }
+    return Ok(report);
+}
+
+/// Checks that a cached string still matches the content of its section.
+#[cfg(feature = "section-debug")]
+fn check_cached<TInterface: Interface>(
+    interface: &mut TInterface,
+    handle: SectionHandle,
+    address: u32,
+    cached: &str
+) -> Result<()>
+{
+    match read_string_range(interface, handle, address) {
+        Ok(v) if v == cached => return Ok(()),
+        _ => return Err(section_context(interface, handle)(Error::Aliased(address).into()))
+    }
+}
+
+/// Reads a null-terminated string from a string section in chunks of
+/// [STRING_READ_CHUNK_SIZE] bytes through [read_section_range](Interface::read_section_range).
+fn read_string_range<TInterface: Interface>(
+    interface: &mut TInterface,
+    handle: SectionHandle,
+    ptr: u32
+) -> Result<String>
+{
+    let context = section_context(interface, handle);
+    let size = interface.open_section(handle)?.size() as u64;
+    let mut curs: Vec<u8> = Vec::new();
+    let mut chunk: [u8; STRING_READ_CHUNK_SIZE] = [0; STRING_READ_CHUNK_SIZE];
+    let mut offset = ptr as u64;
+    loop {
+        let len = size.saturating_sub(offset).min(STRING_READ_CHUNK_SIZE as u64) as usize;
+        if len == 0 {
+            return Err(context(Error::Truncation("string secton read").into()));
+        }
+        interface.read_section_range(handle, offset, &mut chunk[0..len])?;
+        if let Some(end) = chunk[0..len].iter().position(|v| *v == 0x0) {
+            curs.extend_from_slice(&chunk[0..end]);
+            break;
+        }
+        curs.extend_from_slice(&chunk[0..len]);
+        offset += len as u64;
+    }
+    return match String::from_utf8(curs) {
+        Err(_) => Err(context(Error::Utf8("string section read").into())),
+        Ok(v) => Ok(v)
+    };
+}
+
+pub(crate) fn low_level_read_string(ptr: u32, string_section: &mut dyn SectionData) -> Result<String>
 {
     let mut curs: Vec<u8> = Vec::new();
     let mut chr: [u8; 1] = [0; 1]; //read char by char with a buffer
 
     string_section.seek(SeekFrom::Start(ptr as u64))?;
-    string_section.read(&mut chr)?;
+    if string_section.read(&mut chr)? != 1 {
+        return Err(Error::Truncation("string secton read").into());
+    }
     while chr[0] != 0x0 {
         curs.push(chr[0]);
         let res = string_section.read(&mut chr)?;
         if res != 1 {
-            return Err(Error::Truncation("string secton read"));
+            return Err(Error::Truncation("string secton read").into());
         }
     }
     return match String::from_utf8(curs) {
-        Err(_) => Err(Error::Utf8("string section read")),
+        Err(_) => Err(Error::Utf8("string section read").into()),
         Ok(v) => Ok(v)
     }
 }
 
-fn low_level_write_string(s: &str, string_section: &mut dyn SectionData) -> Result<u32>
+/// Reads a null-terminated string directly from the content of a string section.
+pub(crate) fn read_string_in_slice(ptr: u32, data: &[u8]) -> Result<&str>
 {
-    let ptr = string_section.size() as u32;
-    string_section.write(s.as_bytes())?;
-    string_section.write(&[0x0])?;
-    return Ok(ptr);
+    let start = ptr as usize;
+    if start >= data.len() {
+        return Err(Error::Truncation("string section read").into());
+    }
+    let len = match data[start..].iter().position(|v| *v == 0x0) {
+        Some(v) => v,
+        None => return Err(Error::Truncation("string section read").into())
+    };
+    return match std::str::from_utf8(&data[start..start + len]) {
+        Err(_) => Err(Error::Utf8("string section read").into()),
+        Ok(v) => Ok(v)
+    };
+}
+
+pub(crate) fn low_level_write_string(s: &str, string_section: &mut dyn SectionData) -> Result<u32>
+{
+    let ptr = string_section.size() as u64;
+    let end = ptr + s.len() as u64 + 1;
+    if end > MAX_SECTION_SIZE {
+        return Err(Error::Capacity(String::from(s), end).into());
+    }
+    string_section.seek(SeekFrom::End(0))?;
+    string_section.write_all(s.as_bytes())?;
+    string_section.write_all(&[0x0])?;
+    return Ok(ptr as u32);
 }
 
 /// Returns the file name as a UTF-8 string from a rust Path.
@@ -157,11 +570,8 @@ fn low_level_write_string(s: &str, string_section: &mut dyn SectionData) -> Resu
 ///
 /// # Errors
 ///
-/// Returns an [Error](crate::error::Error) if the path does not have a file name.
-///
-/// # Panics
-///
-/// Panics in case `path` is not unicode compatible (BPX only supports UTF-8).
+/// Returns an [Error](crate::error::Error) if the path does not have a file name
+/// or if the file name is not unicode compatible (BPX only supports UTF-8).
 ///
 /// # Examples
 ///
@@ -177,11 +587,10 @@ pub fn get_name_from_path(path: &Path) -> Result<String>
     match path.file_name() {
         Some(v) => match v.to_str() {
             Some(v) => return Ok(String::from(v)),
-            // Panic here as a non Unicode system in all cases could just throw a bunch of broken unicode strings in a BPXP
             // The reason BPXP cannot support non-unicode strings in paths is simply because this would be incompatible with unicode systems
-            None => panic!("Non unicode paths operating systems cannot run BPXP")
+            None => return Err(Error::NonUnicodePath.into())
         },
-        None => return Err(Error::from("incorrect path format"))
+        None => return Err(Error::InvalidPath.into())
     }
 }
 
@@ -191,15 +600,16 @@ pub fn get_name_from_path(path: &Path) -> Result<String>
 ///
 /// * `entry`: the rust DirEntry.
 ///
-/// returns: String
+/// returns: Result<String, Error>
 ///
-/// # Panics
+/// # Errors
 ///
-/// Panics in case `entry` is not unicode compatible (BPX only supports UTF-8).
-pub fn get_name_from_dir_entry(entry: &DirEntry) -> String
+/// Returns an [Error](crate::error::Error) if the file name is not unicode
+/// compatible (BPX only supports UTF-8).
+pub fn get_name_from_dir_entry(entry: &DirEntry) -> Result<String>
 {
     match entry.file_name().to_str() {
-        Some(v) => return String::from(v),
-        None => panic!("Non unicode paths operating systems cannot run BPXP")
+        Some(v) => return Ok(String::from(v)),
+        None => return Err(Error::NonUnicodePath.into())
     }
 }
@@ -0,0 +1,541 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Helpers to test BPX variants, enabled by the `test-util` feature.
+//!
+//! *All helpers panic on failure, they are meant to be called from tests only.
+//! Random content is generated by a seeded generator, so that a failing test
+//! always fails the same way.*
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::{
+    fs::File,
+    io::{Cursor, SeekFrom, Write},
+    path::Path
+};
+
+use crate::{
+    builder::{Checksum, CompressionMethod, MainHeaderBuilder, SectionHeaderBuilder},
+    decoder::Decoder,
+    encoder::Encoder,
+    header::{SIZE_MAIN_HEADER, SIZE_SECTION_HEADER},
+    raw::read_headers,
+    sd::{Array, Object, Value},
+    strings::StringSection,
+    variant::package::PackageBuilder,
+    Interface
+};
+
+/// Deterministic xorshift generator.
+struct Rng(u64);
+
+impl Rng
+{
+    fn new(seed: u64) -> Rng
+    {
+        // xorshift is stuck at 0.
+        return Rng(seed ^ 0x9E3779B97F4A7C15);
+    }
+
+    fn next(&mut self) -> u64
+    {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        return self.0;
+    }
+
+    fn below(&mut self, max: u64) -> u64
+    {
+        return self.next() % max;
+    }
+}
+
+/// Builds a BPX in memory, saves it and opens it with a [Decoder](crate::decoder::Decoder).
+///
+/// # Arguments
+///
+/// * `build`: a function creating the content of the BPX, the encoder is saved after it returns.
+///
+/// returns: Decoder<Cursor<Vec<u8>>>
+///
+/// # Panics
+///
+/// Panics if the BPX could not be saved or decoded.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::header::SectionHeader;
+/// use bpx::testing::roundtrip_container;
+/// use bpx::Interface;
+///
+/// let mut decoder = roundtrip_container(|encoder| {
+///     let handle = encoder.create_section(SectionHeader::new()).unwrap();
+///     encoder.open_section(handle).unwrap().write_all(b"BPX").unwrap();
+/// });
+/// let handle = decoder.find_section_by_index(0).unwrap();
+/// assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), b"BPX");
+/// ```
+pub fn roundtrip_container<F: FnOnce(&mut Encoder<Vec<u8>>)>(build: F) -> Decoder<Cursor<Vec<u8>>>
+{
+    let mut encoder = Encoder::new(Vec::new()).expect("failed to create the encoder");
+    build(&mut encoder);
+    encoder.save().expect("failed to save the BPX");
+    return Decoder::new(Cursor::new(encoder.into_inner())).expect("failed to decode the saved BPX");
+}
+
+/// Asserts that two BPX have the same logical content.
+///
+/// *The type, version and Extended Type Information of the main headers must match,
+/// as well as the type byte, user tag, checksum algorithm and content of each section.
+/// Compression and the location of the sections in the file are not compared.*
+///
+/// # Arguments
+///
+/// * `a`: the first BPX.
+/// * `b`: the second BPX.
+///
+/// # Panics
+///
+/// Panics if the BPX differ or a section could not be loaded.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::builder::{CompressionMethod, SectionHeaderBuilder};
+/// use bpx::testing::{assert_sections_equal, roundtrip_container};
+/// use bpx::Interface;
+///
+/// let build = |compression| {
+///     move |encoder: &mut bpx::encoder::Encoder<Vec<u8>>| {
+///         let mut builder = SectionHeaderBuilder::new();
+///         if let Some(method) = compression {
+///             builder = builder.with_compression(method);
+///         }
+///         let handle = encoder.create_section(builder.build()).unwrap();
+///         encoder.open_section(handle).unwrap().write_all(&[0; 1024]).unwrap();
+///     }
+/// };
+/// let mut stored = roundtrip_container(build(None));
+/// let mut compressed = roundtrip_container(build(Some(CompressionMethod::Zlib)));
+/// assert_sections_equal(&mut stored, &mut compressed);
+/// ```
+pub fn assert_sections_equal<A: Interface, B: Interface>(a: &mut A, b: &mut B)
+{
+    let (main_a, main_b) = (a.get_main_header(), b.get_main_header());
+    assert_eq!(main_a.btype, main_b.btype, "BPX types differ");
+    assert_eq!(main_a.version, main_b.version, "BPX versions differ");
+    assert_eq!(main_a.type_ext, main_b.type_ext, "BPX Extended Type Information differ");
+    let handles_a: Vec<_> = a.iter_sections().collect();
+    let handles_b: Vec<_> = b.iter_sections().collect();
+    assert_eq!(handles_a.len(), handles_b.len(), "BPX section counts differ");
+    for (index, (ha, hb)) in handles_a.into_iter().zip(handles_b).enumerate() {
+        let (sa, sb) = (*a.get_section_header(ha), *b.get_section_header(hb));
+        assert_eq!(sa.btype, sb.btype, "section #{} types differ", index);
+        assert_eq!(sa.user_tag(), sb.user_tag(), "section #{} user tags differ", index);
+        assert_eq!(sa.checksum(), sb.checksum(), "section #{} checksums differ", index);
+        let data_a = load(a, ha, index);
+        let data_b = load(b, hb, index);
+        assert!(data_a == data_b, "section #{} contents differ", index);
+    }
+}
+
+fn load<TInterface: Interface>(interface: &mut TInterface, handle: crate::SectionHandle, index: usize) -> Vec<u8>
+{
+    let mut section = interface
+        .open_section(handle)
+        .unwrap_or_else(|e| panic!("failed to load section #{}: {}", index, e));
+    section.seek(SeekFrom::Start(0)).unwrap();
+    return section.load_in_memory().unwrap();
+}
+
+fn random_value(rng: &mut Rng, depth: u32) -> Value
+{
+    let kinds = if depth > 0 { 15 } else { 13 };
+    return match rng.below(kinds) {
+        0 => Value::Null,
+        1 => Value::Bool(rng.next() & 1 == 1),
+        2 => Value::Uint8(rng.next() as u8),
+        3 => Value::Uint16(rng.next() as u16),
+        4 => Value::Uint32(rng.next() as u32),
+        5 => Value::Uint64(rng.next()),
+        6 => Value::Int8(rng.next() as i8),
+        7 => Value::Int16(rng.next() as i16),
+        8 => Value::Int32(rng.next() as i32),
+        9 => Value::Int64(rng.next() as i64),
+        // Integral values, so that the objects can be compared.
+        10 => Value::Float(rng.below(1 << 24) as f32),
+        11 => Value::Double(rng.below(1 << 53) as f64),
+        12 => Value::String(format!("s{}", rng.below(1000))),
+        13 => {
+            let mut array = Array::new();
+            for _ in 0..rng.below(4) {
+                array.add(random_value(rng, depth - 1));
+            }
+            Value::Array(array)
+        },
+        _ => Value::Object(random_object_with(rng, depth - 1))
+    };
+}
+
+fn random_object_with(rng: &mut Rng, depth: u32) -> Object
+{
+    let mut obj = Object::new();
+    for i in 0..rng.below(5) {
+        obj.set(&format!("p{}", i), random_value(rng, depth));
+    }
+    return obj;
+}
+
+/// Generates a Structured Data object with random properties.
+///
+/// *The same seed always generates the same object.*
+///
+/// # Arguments
+///
+/// * `depth`: the maximum number of nested objects and arrays.
+/// * `seed`: the seed of the generator.
+///
+/// returns: Object
+///
+/// # Examples
+///
+/// ```
+/// use bpx::sd::Object;
+/// use bpx::testing::random_object;
+///
+/// let obj = random_object(3, 42);
+/// assert_eq!(obj, random_object(3, 42));
+/// let mut buf = Vec::new();
+/// obj.write(&mut buf).unwrap();
+/// assert_eq!(Object::read(&mut buf.as_slice()).unwrap(), obj);
+/// ```
+pub fn random_object(depth: u32, seed: u64) -> Object
+{
+    return random_object_with(&mut Rng::new(seed), depth);
+}
+
+/// Creates files with random names and content in a directory.
+///
+/// *Files are spread over nested sub-directories; the same arguments always
+/// generate the same tree.*
+///
+/// # Arguments
+///
+/// * `dir`: the directory to create the files in, created if it does not exist.
+/// * `files`: the number of files to create.
+/// * `max_size`: the maximum size of a file in bytes.
+///
+/// returns: Vec<(String, Vec<u8>)>; the path of each file relative to `dir`,
+/// with `/` separators, and its content, sorted by path.
+///
+/// # Panics
+///
+/// Panics if a file could not be written.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::testing::random_tree;
+///
+/// let tmp = tempfile::tempdir().unwrap();
+/// let dir = tmp.path().join("tree");
+/// let files = random_tree(&dir, 8, 64);
+/// assert_eq!(files.len(), 8);
+/// for (path, data) in &files {
+///     assert_eq!(&std::fs::read(dir.join(path)).unwrap(), data);
+/// }
+/// ```
+pub fn random_tree(dir: &Path, files: usize, max_size: usize) -> Vec<(String, Vec<u8>)>
+{
+    let mut rng = Rng::new(files as u64 ^ ((max_size as u64) << 32));
+    let mut tree = Vec::with_capacity(files);
+    for i in 0..files {
+        let mut path = String::new();
+        for _ in 0..rng.below(3) {
+            path.push_str(&format!("d{}/", rng.below(3)));
+        }
+        // The index keeps names unique.
+        path.push_str(&format!("f{}.bin", i));
+        let size = rng.below(max_size as u64 + 1) as usize;
+        let data: Vec<u8> = (0..size).map(|_| rng.next() as u8).collect();
+        let file = dir.join(&path);
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent).unwrap_or_else(|e| panic!("failed to create {:?}: {}", parent, e));
+        }
+        File::create(&file)
+            .and_then(|mut f| f.write_all(&data))
+            .unwrap_or_else(|e| panic!("failed to write {:?}: {}", file, e));
+        tree.push((path, data));
+    }
+    tree.sort();
+    return tree;
+}
+
+/// Rewrites a BPX with the data of its sections stored in another physical order.
+///
+/// *The encoder always stores sections contiguously in creation order; this
+/// produces the layouts other writers may leave behind. Section headers keep
+/// their order and only their pointers change; `gaps[i]` filler bytes are
+/// inserted before the data of section `order[i]`. Bytes following `file_size`,
+/// such as a file digest footer, are dropped.*
+///
+/// # Arguments
+///
+/// * `bpx`: the BPX to rewrite.
+/// * `order`: the index of each section in the order its data is stored.
+/// * `gaps`: the number of filler bytes stored before the data of each section of `order`.
+///
+/// returns: Vec<u8>
+///
+/// # Panics
+///
+/// Panics if the headers of `bpx` could not be read, if `order` does not contain
+/// each section index exactly once or if `gaps` is not as long as `order`.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use bpx::decoder::Decoder;
+/// use bpx::encoder::Encoder;
+/// use bpx::header::SectionHeader;
+/// use bpx::testing::relayout_sections;
+/// use bpx::Interface;
+///
+/// let mut encoder = Encoder::new(Vec::new()).unwrap();
+/// for data in [b"first".as_ref(), b"second".as_ref()] {
+///     let handle = encoder.create_section(SectionHeader::new()).unwrap();
+///     encoder.open_section(handle).unwrap().write_all(data).unwrap();
+/// }
+/// encoder.save().unwrap();
+/// let buf = relayout_sections(&encoder.into_inner(), &[1, 0], &[16, 3]);
+/// let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+/// let (first, second) = (decoder.find_section_by_index(0).unwrap(), decoder.find_section_by_index(1).unwrap());
+/// assert!(decoder.get_section_header(first).pointer > decoder.get_section_header(second).pointer);
+/// assert_eq!(decoder.open_section(first).unwrap().load_in_memory().unwrap(), b"first");
+/// assert_eq!(decoder.open_section(second).unwrap().load_in_memory().unwrap(), b"second");
+/// ```
+pub fn relayout_sections(bpx: &[u8], order: &[usize], gaps: &[usize]) -> Vec<u8>
+{
+    let (mut main_header, mut sections) = read_headers(&mut Cursor::new(bpx)).unwrap();
+    let mut sorted = order.to_vec();
+    sorted.sort_unstable();
+    assert!(sorted.into_iter().eq(0..sections.len()), "order must contain each section index once");
+    assert_eq!(gaps.len(), order.len(), "one gap is needed per section");
+    let mut pointer = (SIZE_MAIN_HEADER + sections.len() * SIZE_SECTION_HEADER) as u64;
+    let mut data = Vec::new();
+    for (index, gap) in order.iter().zip(gaps) {
+        let header = &mut sections[*index];
+        let start = header.pointer as usize;
+        // Filler which is neither zeros nor a copy of the section data.
+        data.extend((0..*gap).map(|v| 0xA0 | (v % 16) as u8));
+        pointer += *gap as u64;
+        data.extend_from_slice(&bpx[start..start + header.csize as usize]);
+        header.pointer = pointer;
+        pointer += header.csize as u64;
+    }
+    main_header.file_size = pointer;
+    main_header.chksum = 0;
+    main_header.chksum = sections
+        .iter()
+        .fold(main_header.get_checksum(), |acc, v| acc.wrapping_add(v.get_checksum()));
+    let mut out = Vec::with_capacity(pointer as usize);
+    main_header.write(&mut out).unwrap();
+    for header in &sections {
+        header.write(&mut out).unwrap();
+    }
+    out.extend(data);
+    return out;
+}
+
+/// A reference BPX of the backward compatibility suite, committed in `tests/fixtures`.
+///
+/// *The encoder is deterministic: the same calls always produce the same
+/// bytes. The fixtures are regenerated with `cargo run --example regen_fixtures`,
+/// which must only be done when the format is intentionally changed.*
+pub struct Fixture
+{
+    /// The file name of the fixture in `tests/fixtures`.
+    pub name: &'static str,
+
+    /// Creates the content of the fixture, the encoder is saved after it returns.
+    pub build: fn(&mut Encoder<Vec<u8>>),
+
+    /// Rewrites the saved BPX, for layouts the encoder never produces.
+    pub relayout: fn(Vec<u8>) -> Vec<u8>
+}
+
+impl Fixture
+{
+    /// Generates the bytes of this fixture with the current encoder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the BPX could not be saved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::testing::FIXTURES;
+    ///
+    /// for fixture in FIXTURES {
+    ///     assert_eq!(fixture.generate(), fixture.generate());
+    /// }
+    /// ```
+    pub fn generate(&self) -> Vec<u8>
+    {
+        let mut encoder = Encoder::new(Vec::new()).expect("failed to create the encoder");
+        (self.build)(&mut encoder);
+        encoder.save().expect("failed to save the BPX");
+        return (self.relayout)(encoder.into_inner());
+    }
+}
+
+/// The fixtures of the backward compatibility suite.
+pub const FIXTURES: &[Fixture] = &[
+    Fixture {
+        name: "sections.bpx",
+        build: build_sections_fixture,
+        relayout: encoder_layout
+    },
+    Fixture {
+        name: "structured.bpx",
+        build: build_structured_fixture,
+        relayout: encoder_layout
+    },
+    Fixture {
+        name: "package.bpxp",
+        build: build_package_fixture,
+        relayout: encoder_layout
+    },
+    Fixture {
+        name: "shuffled.bpx",
+        build: build_sections_fixture,
+        relayout: shuffle_sections_fixture
+    }
+];
+
+fn encoder_layout(bpx: Vec<u8>) -> Vec<u8>
+{
+    return bpx;
+}
+
+/// The sections fixture stored in reverse order, far after the section header
+/// table and with filler between the sections.
+fn shuffle_sections_fixture(bpx: Vec<u8>) -> Vec<u8>
+{
+    let (_, sections) = read_headers(&mut Cursor::new(&bpx)).unwrap();
+    let order: Vec<usize> = (0..sections.len()).rev().collect();
+    let gaps: Vec<usize> = (0..sections.len()).map(|i| if i == 0 { 4096 } else { 7 * i }).collect();
+    return relayout_sections(&bpx, &order, &gaps);
+}
+
+/// Sections with each compression method and checksum, a section name and a build identifier.
+fn build_sections_fixture(encoder: &mut Encoder<Vec<u8>>)
+{
+    let mut type_ext = [0; 16];
+    for (i, v) in type_ext.iter_mut().enumerate() {
+        *v = i as u8 + 1;
+    }
+    encoder.set_main_header(MainHeaderBuilder::new().with_type(b'X').with_type_ext(type_ext).build());
+    let sections: [(SectionHeaderBuilder, Vec<u8>); 4] = [
+        (SectionHeaderBuilder::new().with_type(1), b"uncompressed".to_vec()),
+        (
+            SectionHeaderBuilder::new()
+                .with_type(2)
+                .with_compression(CompressionMethod::Xz)
+                .with_threshold(0)
+                .with_checksum(Checksum::Crc32),
+            (0..8192u32).map(|v| (v % 251) as u8).collect()
+        ),
+        (
+            SectionHeaderBuilder::new()
+                .with_type(3)
+                .with_compression(CompressionMethod::Zlib)
+                .with_threshold(0)
+                .with_checksum(Checksum::Weak),
+            (0..8192u32).map(|v| (v * 7 % 256) as u8).collect()
+        ),
+        (
+            SectionHeaderBuilder::new().with_type(4).with_user_tag(0x42).with_checksum(Checksum::Crc32),
+            b"checksum".to_vec()
+        )
+    ];
+    for (builder, data) in sections {
+        let handle = encoder.create_section(builder.build()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&data).unwrap();
+    }
+    let first = encoder.find_section_by_type(1).unwrap();
+    encoder.name_section(first, "first").unwrap();
+    encoder.set_build_id(0x0123456789ABCDEF).unwrap();
+}
+
+/// A string section and a structured data section holding every kind of value.
+fn build_structured_fixture(encoder: &mut Encoder<Vec<u8>>)
+{
+    let strings = encoder.create_section(SectionHeaderBuilder::strings().build()).unwrap();
+    let mut strings = StringSection::new(strings);
+    strings.put(encoder, "first string").unwrap();
+    strings.put(encoder, "second string").unwrap();
+    let mut nested = Object::new();
+    nested.set("negative", (-1i32).into());
+    let mut array = Array::new();
+    array.add(true.into());
+    array.add(Value::Null);
+    array.add("item".into());
+    let mut obj = Object::new();
+    obj.set("name", "fixture".into());
+    obj.set("count", 42u32.into());
+    obj.set("big", 0x0123456789ABCDEFu64.into());
+    obj.set("ratio", 0.5f64.into());
+    obj.set("scale", 2.5f32.into());
+    obj.set("byte", 0xFFu8.into());
+    obj.set("array", array.into());
+    obj.set("nested", nested.into());
+    let handle = encoder.create_section(SectionHeaderBuilder::structured_data().build()).unwrap();
+    obj.write(&mut encoder.open_section(handle).unwrap()).unwrap();
+}
+
+/// A package with metadata, an empty object and an object spanning several data sections.
+fn build_package_fixture(encoder: &mut Encoder<Vec<u8>>)
+{
+    let mut metadata = Object::new();
+    metadata.set("version", 1u32.into());
+    let mut package = PackageBuilder::new()
+        .with_split_size(16384)
+        .with_metadata(metadata)
+        .build(encoder)
+        .unwrap();
+    let large: Vec<u8> = (0..20000u32).map(|v| (v % 13) as u8).collect();
+    package.pack_object("empty", &mut b"".as_ref()).unwrap();
+    package.pack_object("dir/small.txt", &mut b"hello bpx".as_ref()).unwrap();
+    package.pack_object("dir/large.bin", &mut large.as_slice()).unwrap();
+}
@@ -28,7 +28,20 @@
 
 //! Contains various utilities to be used by other modules.
 
-use std::num::Wrapping;
+use std::{
+    convert::TryFrom,
+    fmt::Write,
+    fs::{File, OpenOptions},
+    io::{BufWriter, ErrorKind, Read, Result as IoResult, SeekFrom, Write as IoWrite},
+    num::Wrapping,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering}
+};
+
+use crate::{error::Error, section::SectionData};
+
+/// The number of bytes displayed on each line of a [hexdump](self::hexdump).
+const HEXDUMP_LINE_SIZE: usize = 16;
 
 /// Hash text using the hash function defined in the BPX specification for strings.
 ///
@@ -58,17 +71,323 @@ pub fn hash(s: &str) -> u64
     return val.0;
 }
 
-/// Extension to include get_or_insert_with but with support for Result and errors.
+/// Reads from a stream until a buffer is full or the end of the stream is reached.
+///
+/// *Unlike [read](std::io::Read::read), a short count always means the end of
+/// the stream was reached; unlike [read_exact](std::io::Read::read_exact), the
+/// caller decides whether this is an error.*
+///
+/// # Arguments
+///
+/// * `reader`: the stream to read from.
+/// * `buf`: the buffer to fill.
+///
+/// returns: Result<usize, Error>; the number of bytes read.
+///
+/// # Errors
+///
+/// An [Error](std::io::Error) is returned if the stream fails with anything
+/// else than [Interrupted](std::io::ErrorKind::Interrupted).
+///
+/// # Examples
+///
+/// ```
+/// use bpx::utils::read_fill;
+///
+/// let mut buf = [0; 4];
+/// assert_eq!(read_fill(&mut &b"abcdef"[..], &mut buf).unwrap(), 4);
+/// assert_eq!(read_fill(&mut &b"ab"[..], &mut buf).unwrap(), 2);
+/// ```
+pub fn read_fill<TRead: Read + ?Sized>(reader: &mut TRead, buf: &mut [u8]) -> IoResult<usize>
+{
+    let mut len = 0;
+
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(res) => len += res,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e)
+        }
+    }
+    return Ok(len);
+}
+
+/// Formats a range of a section as hex and ASCII, 16 bytes per line.
+///
+/// *Each line starts with the offset of its first byte in the section; bytes
+/// outside of the printable ASCII range are shown as `.`. The range is clamped
+/// to the size of the section and the cursor position is left unchanged.*
+///
+/// # Arguments
+///
+/// * `data`: the section to read from.
+/// * `offset`: the offset of the first byte to display.
+/// * `len`: the maximum number of bytes to display.
+///
+/// returns: Result<String, Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned if the section could not be read.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+/// use bpx::section::new_section_data;
+/// use bpx::utils::hexdump;
+///
+/// let mut section = new_section_data(None).unwrap();
+/// section.write_all(b"Hello BPX\n").unwrap();
+/// assert_eq!(
+///     hexdump(section.as_mut(), 0, 256).unwrap(),
+///     "00000000  48 65 6c 6c 6f 20 42 50  58 0a                    |Hello BPX.|\n"
+/// );
+/// ```
+pub fn hexdump(data: &mut dyn SectionData, offset: u64, len: usize) -> Result<String, Error>
+{
+    let mut res = String::new();
+    let mut buf: [u8; HEXDUMP_LINE_SIZE] = [0; HEXDUMP_LINE_SIZE];
+    let size = data.size() as u64;
+    let mut remaining = std::cmp::min(len as u64, size.saturating_sub(offset)) as usize;
+    let mut line_offset = offset;
+    let pos = data.stream_position()?;
+
+    data.seek(SeekFrom::Start(offset))?;
+    while remaining > 0 {
+        let wanted = std::cmp::min(HEXDUMP_LINE_SIZE, remaining);
+        let count = read_fill(data, &mut buf[..wanted])?;
+        if count == 0 {
+            break;
+        }
+        // Writing to a String never fails.
+        let _ = write!(res, "{:08x} ", line_offset);
+        for (i, byte) in buf[..count].iter().enumerate() {
+            if i % 8 == 0 {
+                res.push(' ');
+            }
+            let _ = write!(res, "{:02x} ", byte);
+        }
+        for i in count..HEXDUMP_LINE_SIZE {
+            if i % 8 == 0 {
+                res.push(' ');
+            }
+            res.push_str("   ");
+        }
+        res.push_str(" |");
+        res.extend(buf[..count].iter().map(|v| match v {
+            0x20..=0x7E => *v as char,
+            _ => '.'
+        }));
+        res.push_str("|\n");
+        line_offset += count as u64;
+        remaining -= count;
+    }
+    data.seek(SeekFrom::Start(pos))?;
+    return Ok(res);
+}
+
+fn glob_class(pattern: &[char], c: char) -> Option<(bool, usize)>
+{
+    let negate = matches!(pattern.get(1), Some('!') | Some('^'));
+    let start = if negate { 2 } else { 1 };
+    // A ']' right after the opening bracket is a literal.
+    let end = start + 1 + pattern.get(start + 1..)?.iter().position(|v| *v == ']')?;
+    let class = &pattern[start..end];
+    let mut matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            matched |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+    return Some((matched != negate, end + 1));
+}
+
+fn glob_match_chars(pattern: &[char], name: &[char]) -> bool
+{
+    match pattern.first() {
+        None => return name.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            if pattern.get(2) == Some(&'/') {
+                // '**/' matches zero or more whole directories.
+                let rest = &pattern[3..];
+                return glob_match_chars(rest, name)
+                    || (0..name.len()).any(|i| name[i] == '/' && glob_match_chars(rest, &name[i + 1..]));
+            }
+            return (0..=name.len()).any(|i| glob_match_chars(&pattern[2..], &name[i..]));
+        },
+        Some('*') => {
+            for i in 0..=name.len() {
+                if glob_match_chars(&pattern[1..], &name[i..]) {
+                    return true;
+                }
+                if i < name.len() && name[i] == '/' {
+                    break;
+                }
+            }
+            return false;
+        },
+        Some('?') => return !name.is_empty() && name[0] != '/' && glob_match_chars(&pattern[1..], &name[1..]),
+        Some('[') if !name.is_empty() => {
+            if let Some((matched, len)) = glob_class(pattern, name[0]) {
+                return matched && name[0] != '/' && glob_match_chars(&pattern[len..], &name[1..]);
+            }
+        },
+        _ => ()
+    }
+    return name.first() == pattern.first() && glob_match_chars(&pattern[1..], &name[1..]);
+}
+
+/// Matches a `/` separated name against a glob pattern.
+///
+/// *`?` matches any character but `/`, `*` matches any sequence of characters
+/// without `/`, `**` matches any sequence of characters and `**/` matches zero
+/// or more directories. `[abc]`, `[a-z]` and `[!abc]` match one character of
+/// (or not of) a set; an unclosed `[` is a literal.*
+///
+/// # Arguments
+///
+/// * `pattern`: the glob pattern.
+/// * `name`: the name to match.
+///
+/// returns: bool
+///
+/// # Examples
+///
+/// ```
+/// use bpx::utils::glob_match;
+///
+/// assert!(glob_match("textures/**/*.png", "textures/a.png"));
+/// assert!(glob_match("textures/**/*.png", "textures/ui/icons/b.png"));
+/// assert!(!glob_match("textures/*.png", "textures/ui/b.png"));
+/// assert!(glob_match("sound[0-9].ogg", "sound4.ogg"));
+/// ```
+pub fn glob_match(pattern: &str, name: &str) -> bool
+{
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    return glob_match_chars(&pattern, &name);
+}
+
+/// Fallible lazy initialization helpers for [Option](std::option::Option).
+///
+/// *These are the fallible counterparts of [get_or_insert_with](std::option::Option::get_or_insert_with):
+/// the value is only created if the option is [None](std::option::Option::None) and any error is
+/// propagated to the caller, leaving the option untouched.*
 pub trait OptionExtension<T>
 {
+    /// Inserts a value computed from a fallible function if the option is
+    /// [None](std::option::Option::None), then returns a mutable reference to the contained value.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: the function to compute the value with.
+    ///
+    /// returns: Result<&mut T, TError>
+    ///
+    /// # Errors
+    ///
+    /// The error returned by `f`, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::utils::OptionExtension;
+    ///
+    /// let mut value: Option<u32> = None;
+    /// assert_eq!(value.get_or_insert_with_err(|| "42".parse::<u32>()), Ok(&mut 42));
+    /// assert_eq!(value.get_or_insert_with_err(|| "12".parse::<u32>()), Ok(&mut 42));
+    /// let mut value: Option<u32> = None;
+    /// assert!(value.get_or_insert_with_err(|| "BPX".parse::<u32>()).is_err());
+    /// assert_eq!(value, None);
+    /// ```
     fn get_or_insert_with_err<TError, F: FnOnce() -> Result<T, TError>>(&mut self, f: F) -> Result<&mut T, TError>;
+
+    /// Same as [get_or_insert_with_err](Self::get_or_insert_with_err) but
+    /// specialized for BPX [Error](crate::error::Error).
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: the function to compute the value with.
+    ///
+    /// returns: Result<&mut T, Error>
+    ///
+    /// # Errors
+    ///
+    /// The [Error](crate::error::Error) returned by `f`, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::error::Error;
+    /// use bpx::utils::OptionExtension;
+    ///
+    /// let mut value: Option<u32> = None;
+    /// assert_eq!(*value.try_get_or_insert_with(|| Ok(42)).unwrap(), 42);
+    /// let mut value: Option<u32> = None;
+    /// assert!(value.try_get_or_insert_with(|| Err(Error::Truncation("test"))).is_err());
+    /// assert_eq!(value, None);
+    /// ```
+    fn try_get_or_insert_with<F: FnOnce() -> Result<T, Error>>(&mut self, f: F) -> Result<&mut T, Error>
+    {
+        return self.get_or_insert_with_err(f);
+    }
+
+    /// Same as [get_or_insert_with_err](Self::get_or_insert_with_err) but
+    /// explicitly passes `data` to `f`.
+    ///
+    /// *This allows computing the value from another field of the structure
+    /// owning the option without borrowing the entire structure in the closure.*
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: the data to pass to `f` when the value needs to be computed.
+    /// * `f`: the function to compute the value with.
+    ///
+    /// returns: Result<&mut T, TError>
+    ///
+    /// # Errors
+    ///
+    /// The error returned by `f`, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::utils::OptionExtension;
+    ///
+    /// struct Cache
+    /// {
+    ///     source: Vec<u8>,
+    ///     first: Option<u8>
+    /// }
+    ///
+    /// let mut cache = Cache { source: vec![4, 2], first: None };
+    /// let first = cache
+    ///     .first
+    ///     .get_or_insert_with_data(&mut cache.source, |s| s.first().copied().ok_or("empty"));
+    /// assert_eq!(first, Ok(&mut 4));
+    /// ```
+    fn get_or_insert_with_data<D, TError, F: FnOnce(D) -> Result<T, TError>>(
+        &mut self,
+        data: D,
+        f: F
+    ) -> Result<&mut T, TError>
+    {
+        return self.get_or_insert_with_err(|| f(data));
+    }
 }
 
 impl<T> OptionExtension<T> for Option<T>
 {
     fn get_or_insert_with_err<TError, F: FnOnce() -> Result<T, TError>>(&mut self, f: F) -> Result<&mut T, TError>
     {
-        if let None = *self {
+        if self.is_none() {
             *self = Some(f()?);
         }
 
@@ -80,3 +399,140 @@ impl<T> OptionExtension<T> for Option<T>
         }
     }
 }
+
+/// Size of the buffer of the temporary file used by [write_atomically](self::write_atomically).
+const ATOMIC_BUFFER_SIZE: usize = 8192;
+
+/// Number of attempts to rename a temporary file over a destination which is busy (Windows only).
+#[cfg(windows)]
+const RENAME_ATTEMPTS: u32 = 10;
+
+/// Distinguishes temporary files created by concurrent calls from the same process.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes a file without ever leaving a partially written file at its path.
+///
+/// *The content is written to a temporary file in the same directory, flushed
+/// to disk and renamed over the destination once `write` succeeds; on failure
+/// the temporary file is removed and the destination is left untouched. On
+/// Windows renaming fails while the destination is open; the rename is retried
+/// for a short time in case another process (such as an indexer or an
+/// antivirus) only holds it briefly.*
+///
+/// # Arguments
+///
+/// * `path`: the path of the file to write.
+/// * `write`: a function writing the content of the file.
+///
+/// returns: Result<(), Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned if the temporary file could not be
+/// created, written or renamed, or if `write` fails.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+/// use bpx::utils::write_atomically;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("file.txt");
+/// write_atomically(&path, |file| Ok(file.write_all(b"first")?)).unwrap();
+/// let res = write_atomically(&path, |file| {
+///     file.write_all(b"partial")?;
+///     Err(bpx::error::Error::Truncation("example"))
+/// });
+/// assert!(res.is_err());
+/// assert_eq!(std::fs::read(&path).unwrap(), b"first");
+/// ```
+pub fn write_atomically<TPath: AsRef<Path>, F: FnOnce(&mut BufWriter<File>) -> Result<(), Error>>(
+    path: TPath,
+    write: F
+) -> Result<(), Error>
+{
+    let path = path.as_ref();
+    let (temp, file) = create_temp_file(path)?;
+    let res = write_temp_file(file, write).and_then(|_| Ok(rename_over(&temp, path)?));
+    if res.is_err() {
+        // The write error is more useful than an error removing the temporary file.
+        let _ = std::fs::remove_file(&temp);
+    }
+    res?;
+    // Persist the rename itself, directories cannot be opened on Windows.
+    #[cfg(unix)]
+    File::open(parent_dir(path))?.sync_all()?;
+    return Ok(());
+}
+
+fn parent_dir(path: &Path) -> &Path
+{
+    return match path.parent() {
+        Some(v) if !v.as_os_str().is_empty() => v,
+        _ => Path::new(".")
+    };
+}
+
+fn create_temp_file(path: &Path) -> Result<(PathBuf, File), Error>
+{
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::Unsupported(format!("atomic write to {:?} which is not a file path", path)))?;
+    loop {
+        let id = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(name);
+        temp_name.push(format!(".{}.{}.tmp", std::process::id(), id));
+        let temp = parent_dir(path).join(temp_name);
+        match OpenOptions::new().write(true).create_new(true).open(&temp) {
+            Ok(file) => return Ok((temp, file)),
+            // Left over by a process which crashed while writing.
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into())
+        }
+    }
+}
+
+fn write_temp_file<F: FnOnce(&mut BufWriter<File>) -> Result<(), Error>>(file: File, write: F) -> Result<(), Error>
+{
+    let mut out = BufWriter::with_capacity(ATOMIC_BUFFER_SIZE, file);
+    write(&mut out)?;
+    out.flush()?;
+    let file = out.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    return Ok(());
+}
+
+#[cfg(not(windows))]
+fn rename_over(from: &Path, to: &Path) -> IoResult<()>
+{
+    return std::fs::rename(from, to);
+}
+
+#[cfg(windows)]
+fn rename_over(from: &Path, to: &Path) -> IoResult<()>
+{
+    // MoveFileEx replaces existing files but fails with a sharing violation
+    // while another handle to the destination is open.
+    let mut attempt = 1;
+    loop {
+        match std::fs::rename(from, to) {
+            Err(e) if e.kind() == ErrorKind::PermissionDenied && attempt < RENAME_ATTEMPTS => {
+                std::thread::sleep(std::time::Duration::from_millis(10 * attempt as u64));
+                attempt += 1;
+            },
+            res => return res
+        }
+    }
+}
+
+/// Converts a number of sections to the `section_num` of a main header.
+///
+/// # Errors
+///
+/// Returns [SectionCount](crate::error::Error::SectionCount) if the number does not fit in 32 bits.
+pub(crate) fn section_count(len: usize) -> Result<u32, Error>
+{
+    return u32::try_from(len).map_err(|_| Error::SectionCount(len));
+}
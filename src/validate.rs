@@ -0,0 +1,274 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Checks of a BPX which is about to be saved.
+//!
+//! *See [validate](crate::encoder::Encoder::validate). Each
+//! [ValidationIssue](self::ValidationIssue) is either an error, which makes
+//! [save_checked](crate::encoder::Encoder::save_checked) refuse to save, or a
+//! warning about data which would be written differently than requested.*
+
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter}
+};
+
+use crate::{
+    format::MAX_SECTION_SIZE,
+    header::{SectionFlags, SectionHeader},
+    variant::{
+        kvstore::{KvReader, KvStore},
+        package::{Package, PackageDecoder, SECTION_TYPE_OBJECT_TABLE},
+        texture::{Texture, TexturePackDecoder},
+        Variant
+    },
+    Interface
+};
+
+/// How serious a [ValidationIssue](self::ValidationIssue) is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity
+{
+    /// The BPX can be saved but some data is not written as requested.
+    Warning,
+
+    /// Saving fails or writes a BPX which cannot be read back.
+    Error
+}
+
+/// The problem found by a validation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IssueKind
+{
+    /// The section is larger than [MAX_SECTION_SIZE](crate::format::MAX_SECTION_SIZE).
+    ///
+    /// *Contains the size of the section.*
+    TooLarge(usize),
+
+    /// The section requests several compression methods or several checksums.
+    ///
+    /// *Contains the requested flags.*
+    ConflictingFlags(u8),
+
+    /// The section requests flags unknown to this crate, which are not written.
+    ///
+    /// *Contains the unknown flags.*
+    UnknownFlags(u8),
+
+    /// A section required by the variant of the BPX is empty.
+    EmptySection,
+
+    /// The BPX cannot be read by the decoder of the variant given by its type byte.
+    ///
+    /// *Contains the error returned by the decoder.*
+    Variant(String),
+
+    /// Several objects of a BPX Package have the same name.
+    ///
+    /// *Contains the name and the number of objects with this name.*
+    DuplicateName(String, usize),
+
+    /// The metadata of a BPX Package is not a valid
+    /// [PackageMetadata](crate::variant::package::PackageMetadata).
+    ///
+    /// *Contains the error returned when reading the metadata.*
+    Metadata(String)
+}
+
+/// A problem found by [validate](crate::encoder::Encoder::validate).
+///
+/// *The text rendering is stable, for example
+/// `error: section 2 (type 0x01): conflicting flags 0x03`.*
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationIssue
+{
+    /// How serious the problem is.
+    pub severity: Severity,
+
+    /// The index and type of the section the problem is about, None if it is about the whole BPX.
+    pub section: Option<(u32, u8)>,
+
+    /// The problem.
+    pub kind: IssueKind
+}
+
+impl ValidationIssue
+{
+    /// Returns true if this issue is an [Error](self::Severity::Error).
+    pub fn is_error(&self) -> bool
+    {
+        return self.severity == Severity::Error;
+    }
+}
+
+impl Display for Severity
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        return match self {
+            Severity::Warning => f.write_str("warning"),
+            Severity::Error => f.write_str("error")
+        };
+    }
+}
+
+impl Display for ValidationIssue
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "{}: ", self.severity)?;
+        if let Some((index, btype)) = self.section {
+            write!(f, "section {} (type {:#04x}): ", index, btype)?;
+        }
+        return match &self.kind {
+            IssueKind::TooLarge(size) => write!(f, "size {} exceeds the maximum section size", size),
+            IssueKind::ConflictingFlags(flags) => write!(f, "conflicting flags {:#04x}", flags),
+            IssueKind::UnknownFlags(flags) => write!(f, "unknown flags {:#04x} are not written", flags),
+            IssueKind::EmptySection => f.write_str("required section is empty"),
+            IssueKind::Variant(e) => write!(f, "invalid variant ({})", e),
+            IssueKind::DuplicateName(name, count) => write!(f, "{} objects are named '{}'", count, name),
+            IssueKind::Metadata(e) => write!(f, "invalid package metadata ({})", e)
+        };
+    }
+}
+
+/// Checks the header requested for a section and the size of its data.
+pub(crate) fn check_section(index: u32, requested: &SectionHeader, size: usize) -> Vec<ValidationIssue>
+{
+    let mut issues = Vec::new();
+    let mut issue = |severity, kind| {
+        issues.push(ValidationIssue {
+            severity,
+            section: Some((index, requested.btype)),
+            kind
+        })
+    };
+    if size as u64 > MAX_SECTION_SIZE {
+        issue(Severity::Error, IssueKind::TooLarge(size));
+    }
+    let flags = requested.flags();
+    let compressions = SectionFlags::COMPRESS_XZ | SectionFlags::COMPRESS_ZLIB;
+    let checksums = SectionFlags::CHECK_WEAK | SectionFlags::CHECK_CRC32;
+    if flags.contains(compressions) || flags.contains(checksums) {
+        issue(Severity::Error, IssueKind::ConflictingFlags(flags.bits()));
+    }
+    if flags.unknown_bits() != 0 {
+        issue(Severity::Warning, IssueKind::UnknownFlags(flags.unknown_bits()));
+    }
+    return issues;
+}
+
+/// Checks a BPX against the decoder of the variant given by its type byte.
+///
+/// *BPX of other types and BPX without any of the sections required by the
+/// variant, such as a BPX written without the encoder of the variant, are not checked.*
+pub(crate) fn check_variant<TInterface: Interface>(file: &mut TInterface) -> Vec<ValidationIssue>
+{
+    let btype = file.get_main_header().btype;
+    let required = match btype {
+        Package::TYPE_BYTE => Package::required_sections(),
+        KvStore::TYPE_BYTE => KvStore::required_sections(),
+        Texture::TYPE_BYTE => Texture::required_sections(),
+        _ => return Vec::new()
+    };
+    let handles: Vec<_> = required.iter().filter_map(|v| file.find_section_by_type(*v)).collect();
+    if handles.is_empty() {
+        return Vec::new();
+    }
+    let mut issues = Vec::new();
+    for handle in handles {
+        let section = (file.get_section_index(handle), file.get_section_header(handle).btype);
+        match file.open_section(handle) {
+            Ok(data) if data.size() == 0 => issues.push(ValidationIssue {
+                severity: Severity::Warning,
+                section: Some(section),
+                kind: IssueKind::EmptySection
+            }),
+            Ok(_) => (),
+            Err(e) => issues.push(variant_issue(e))
+        }
+    }
+    let res = match btype {
+        Package::TYPE_BYTE => {
+            issues.extend(check_package(file));
+            Ok(())
+        },
+        KvStore::TYPE_BYTE => KvReader::read(file).map(|_| ()),
+        _ => TexturePackDecoder::read(file).map(|_| ())
+    };
+    if let Err(e) = res {
+        issues.push(variant_issue(e));
+    }
+    return issues;
+}
+
+fn variant_issue(e: crate::error::Error) -> ValidationIssue
+{
+    return ValidationIssue {
+        severity: Severity::Error,
+        section: None,
+        kind: IssueKind::Variant(e.to_string())
+    };
+}
+
+fn check_package<TInterface: Interface>(file: &mut TInterface) -> Vec<ValidationIssue>
+{
+    let table = file
+        .find_section_by_type(SECTION_TYPE_OBJECT_TABLE)
+        .map(|handle| (file.get_section_index(handle), SECTION_TYPE_OBJECT_TABLE));
+    let mut package = match PackageDecoder::read(file) {
+        Ok(v) => v,
+        Err(e) => return vec![variant_issue(e)]
+    };
+    let mut issues = Vec::new();
+    if let Err(e) = package.read_typed_metadata() {
+        issues.push(ValidationIssue {
+            severity: Severity::Warning,
+            section: None,
+            kind: IssueKind::Metadata(e.to_string())
+        });
+    }
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let res = package.read_object_table().and_then(|objects| {
+        for obj in objects.get_objects() {
+            *counts.entry(package.get_object_name(obj)?.into()).or_default() += 1;
+        }
+        Ok(())
+    });
+    if let Err(e) = res {
+        issues.push(variant_issue(e));
+    }
+    let mut duplicates: Vec<(String, usize)> = counts.into_iter().filter(|(_, count)| *count > 1).collect();
+    duplicates.sort_unstable();
+    issues.extend(duplicates.into_iter().map(|(name, count)| ValidationIssue {
+        severity: Severity::Error,
+        section: table,
+        kind: IssueKind::DuplicateName(name, count)
+    }));
+    return issues;
+}
@@ -0,0 +1,204 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::io::SeekFrom;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    error::{section_context, Error},
+    format::kvstore::VALUE_PREFIX_SIZE,
+    strings::StringSection,
+    variant::{
+        kvstore::{key_hash, IndexEntry, KvStore, SECTION_TYPE_DATA, SIZE_INDEX_ENTRY},
+        VariantDecoder
+    },
+    Interface,
+    Result,
+    SectionHandle
+};
+
+/// Represents a BPX Key-Value store decoder.
+///
+/// *The index is not loaded: each lookup binary searches the index section
+/// and reads only the matching keys and value.*
+pub struct KvReader<'a, TInterface: Interface>
+{
+    decoder: &'a mut TInterface,
+    index: SectionHandle,
+    strings: StringSection,
+    count: usize
+}
+
+impl<'a, TInterface: Interface> KvReader<'a, TInterface>
+{
+    /// Creates a new KvReader by reading from a BPX decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoder`: the BPX [Decoder](crate::decoder::Decoder) or
+    ///   [Container](crate::container::Container) backend to use.
+    ///
+    /// returns: Result<KvReader<TInterface>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the BPX is not a valid
+    /// key-value store or if the index is truncated.
+    pub fn read(decoder: &'a mut TInterface) -> Result<KvReader<'a, TInterface>>
+    {
+        let (decoder, _, required) = VariantDecoder::<KvStore, _>::read(decoder)?.into_parts();
+        let index = required[0];
+        let context = section_context(decoder, index);
+        let size = decoder.open_section(index)?.size();
+        if size % SIZE_INDEX_ENTRY != 0 {
+            return Err(context(Error::Truncation("read index")));
+        }
+        return Ok(KvReader {
+            decoder,
+            index,
+            strings: StringSection::new(required[1]),
+            count: size / SIZE_INDEX_ENTRY
+        });
+    }
+
+    /// Returns the number of keys in this store.
+    pub fn len(&self) -> usize
+    {
+        return self.count;
+    }
+
+    /// Returns true if this store contains no key.
+    pub fn is_empty(&self) -> bool
+    {
+        return self.count == 0;
+    }
+
+    fn read_entry(&mut self, i: usize) -> Result<IndexEntry>
+    {
+        let context = section_context(self.decoder, self.index);
+        let mut data = self.decoder.open_section(self.index)?;
+        let mut buf: [u8; SIZE_INDEX_ENTRY] = [0; SIZE_INDEX_ENTRY];
+        data.seek(SeekFrom::Start((i * SIZE_INDEX_ENTRY) as u64))
+            .map_err(|e| context(e.into()))?;
+        data.read_exact(&mut buf)
+            .map_err(|_| context(Error::Truncation("read index")))?;
+        return Ok(IndexEntry::from_bytes(&buf));
+    }
+
+    /// Returns the index entry of a key.
+    /// Returns None if the key is not stored in this store.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: the key.
+    ///
+    /// returns: Result<Option<IndexEntry>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the index or the keys could not be read.
+    pub fn get_entry(&mut self, key: &str) -> Result<Option<IndexEntry>>
+    {
+        let hash = key_hash(key);
+        let mut low = 0;
+        let mut high = self.count;
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.read_entry(mid)?.hash < hash {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        // Keys with the same hash are next to each other.
+        for i in low..self.count {
+            let entry = self.read_entry(i)?;
+            if entry.hash != hash {
+                break;
+            }
+            if self.strings.get(self.decoder, entry.key)? == key {
+                return Ok(Some(entry));
+            }
+        }
+        return Ok(None);
+    }
+
+    /// Reads the value of a key.
+    /// Returns None if the key is not stored in this store.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: the key.
+    ///
+    /// returns: Result<Option<Vec<u8>>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the index, the keys or the value
+    /// could not be read or if the value does not match its index entry.
+    pub fn get(&mut self, key: &str) -> Result<Option<Vec<u8>>>
+    {
+        let entry = match self.get_entry(key)? {
+            Some(v) => v,
+            None => return Ok(None)
+        };
+        let handle = match self.decoder.find_section_by_index(entry.section) {
+            Some(v) if self.decoder.get_section_header(v).btype == SECTION_TYPE_DATA => v,
+            _ => {
+                return Err(Error::Corruption(format!(
+                    "Value of '{}' points to section #{} which is not a data section",
+                    key, entry.section
+                )))
+            },
+        };
+        let context = section_context(self.decoder, handle);
+        let mut data = self.decoder.open_section(handle)?;
+        let end = entry.offset as u64 + VALUE_PREFIX_SIZE as u64 + entry.size as u64;
+        if end > data.size() as u64 {
+            return Err(context(Error::Truncation("read value")));
+        }
+        let mut prefix: [u8; VALUE_PREFIX_SIZE] = [0; VALUE_PREFIX_SIZE];
+        data.seek(SeekFrom::Start(entry.offset as u64))
+            .map_err(|e| context(e.into()))?;
+        data.read_exact(&mut prefix)
+            .map_err(|_| context(Error::Truncation("read value")))?;
+        if LittleEndian::read_u32(&prefix) != entry.size {
+            return Err(context(Error::Corruption(format!(
+                "Value of '{}' has {} bytes but the index says {}",
+                key,
+                LittleEndian::read_u32(&prefix),
+                entry.size
+            ))));
+        }
+        let mut value = vec![0; entry.size as usize];
+        data.read_exact(&mut value)
+            .map_err(|_| context(Error::Truncation("read value")))?;
+        return Ok(Some(value));
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::{collections::HashMap, convert::TryFrom, io::SeekFrom};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    builder::SectionHeaderBuilder,
+    error::{section_context, Error},
+    format::kvstore::{DEFAULT_SPLIT_SIZE, VALUE_PREFIX_SIZE},
+    header::SectionHeader,
+    strings::StringSection,
+    variant::{
+        kvstore::{key_hash, IndexEntry, KvDescriptor, KvStore, HASH_FNV1A64, SECTION_TYPE_DATA},
+        VariantEncoder
+    },
+    Result,
+    SectionHandle,
+    WriteInterface
+};
+
+fn create_data_section_header() -> SectionHeader
+{
+    return SectionHeaderBuilder::bulk_data().with_type(SECTION_TYPE_DATA).build();
+}
+
+/// Represents a BPX Key-Value store encoder.
+///
+/// *Values are written as soon as they are [put](Self::put); the index is only
+/// written by [finish](Self::finish), a store which is not finished contains no key.*
+///
+/// # Examples
+///
+/// ```
+/// use bpx::encoder::Encoder;
+/// use bpx::variant::kvstore::{KvReader, KvWriter};
+///
+/// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+/// let mut store = KvWriter::create(&mut encoder).unwrap();
+/// store.put("answer", b"42").unwrap();
+/// store.put("question", b"unknown").unwrap();
+/// store.put("answer", b"forty-two").unwrap();
+/// assert_eq!(store.len(), 2);
+/// store.finish().unwrap();
+///
+/// let mut store = KvReader::read(&mut encoder).unwrap();
+/// assert_eq!(store.get("answer").unwrap().unwrap(), b"forty-two");
+/// assert!(store.get("missing").unwrap().is_none());
+/// ```
+pub struct KvWriter<'a, TInterface: WriteInterface>
+{
+    encoder: &'a mut TInterface,
+    index: SectionHandle,
+    strings: StringSection,
+    last_data_section: Option<SectionHandle>,
+    entries: Vec<IndexEntry>,
+    keys: HashMap<String, usize>,
+    split_size: usize
+}
+
+impl<'a, TInterface: WriteInterface> KvWriter<'a, TInterface>
+{
+    /// Creates a new BPX Key-Value store.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder`: the BPX [Encoder](crate::encoder::Encoder) or
+    ///   [Container](crate::container::Container) to write to.
+    ///
+    /// returns: Result<KvWriter<TInterface>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned in case some sections could not be created.
+    pub fn create(encoder: &'a mut TInterface) -> Result<KvWriter<'a, TInterface>>
+    {
+        let desc = KvDescriptor { hash: HASH_FNV1A64 };
+        let (encoder, _, required) = VariantEncoder::<KvStore, _>::create(encoder, desc)?.into_parts();
+        return Ok(KvWriter {
+            encoder,
+            index: required[0],
+            strings: StringSection::new(required[1]),
+            last_data_section: None,
+            entries: Vec::new(),
+            keys: HashMap::new(),
+            split_size: DEFAULT_SPLIT_SIZE
+        });
+    }
+
+    /// Defines the size in bytes a data section is filled up to before starting a new one.
+    ///
+    /// *Values are never split: a value larger than the split size gets a data section of its own.
+    /// By default, [DEFAULT_SPLIT_SIZE](crate::format::kvstore::DEFAULT_SPLIT_SIZE) is used.*
+    ///
+    /// # Arguments
+    ///
+    /// * `split_size`: the maximum size of a data section.
+    ///
+    /// returns: KvWriter<TInterface>
+    pub fn with_split_size(mut self, split_size: usize) -> Self
+    {
+        self.split_size = split_size;
+        return self;
+    }
+
+    /// Returns the number of keys in this store.
+    pub fn len(&self) -> usize
+    {
+        return self.entries.len();
+    }
+
+    /// Returns true if this store contains no key.
+    pub fn is_empty(&self) -> bool
+    {
+        return self.entries.is_empty();
+    }
+
+    fn data_section(&mut self, len: usize) -> Result<SectionHandle>
+    {
+        if let Some(handle) = self.last_data_section {
+            let size = self.encoder.open_section(handle)?.size();
+            if size == 0 || size.saturating_add(len) <= self.split_size {
+                return Ok(handle);
+            }
+        }
+        let handle = self.encoder.create_section(create_data_section_header())?;
+        self.last_data_section = Some(handle);
+        return Ok(handle);
+    }
+
+    /// Stores a value in this BPXK.
+    ///
+    /// *If the key already exists, the new value replaces it; the previous value
+    /// is left unreferenced in its data section.*
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: the key.
+    /// * `value`: the value.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// A [Capacity](crate::error::Error::Capacity) error is returned if the value
+    /// does not fit in a section and an [Error](crate::error::Error) if the value
+    /// or the key could not be written.
+    pub fn put(&mut self, key: &str, value: &[u8]) -> Result<()>
+    {
+        let len = VALUE_PREFIX_SIZE + value.len();
+        let size = u32::try_from(value.len())
+            .ok()
+            .filter(|_| u32::try_from(len).is_ok())
+            .ok_or(Error::Capacity(value.len()))?;
+        let section = self.data_section(len)?;
+        let context = section_context(self.encoder, section);
+        let mut data = self.encoder.open_section(section)?;
+        let offset = u32::try_from(data.size()).map_err(|_| context(Error::Capacity(data.size())))?;
+        let mut prefix: [u8; VALUE_PREFIX_SIZE] = [0; VALUE_PREFIX_SIZE];
+        LittleEndian::write_u32(&mut prefix, size);
+        data.seek(SeekFrom::End(0)).map_err(|e| context(e.into()))?;
+        data.write_all(&prefix).map_err(|e| context(e.into()))?;
+        data.write_all(value).map_err(|e| context(e.into()))?;
+        drop(data);
+        let section = self.encoder.get_section_index(section);
+        if let Some(i) = self.keys.get(key) {
+            let entry = &mut self.entries[*i];
+            entry.section = section;
+            entry.offset = offset;
+            entry.size = size;
+            return Ok(());
+        }
+        let entry = IndexEntry {
+            hash: key_hash(key),
+            key: self.strings.put(self.encoder, key)?,
+            section,
+            offset,
+            size
+        };
+        self.keys.insert(String::from(key), self.entries.len());
+        self.entries.push(entry);
+        return Ok(());
+    }
+
+    /// Writes the index of this BPXK.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the index could not be written.
+    pub fn finish(mut self) -> Result<()>
+    {
+        // Keys with the same hash keep their insertion order.
+        self.entries.sort_by_key(|v| v.hash);
+        let mut buf = Vec::with_capacity(self.entries.len() * super::SIZE_INDEX_ENTRY);
+        for entry in &self.entries {
+            buf.extend_from_slice(&entry.to_bytes());
+        }
+        let context = section_context(self.encoder, self.index);
+        let mut index = self.encoder.open_section(self.index)?;
+        index.seek(SeekFrom::Start(0)).map_err(|e| context(e.into()))?;
+        index.write_all(&buf).map_err(|e| context(e.into()))?;
+        return Ok(());
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! An implementation of a BPX type K (Key-Value store) variant.
+//!
+//! A BPXK maps string keys to binary values. Keys are stored in a strings
+//! section, values are appended with a length prefix to data sections and an
+//! index section, sorted by key hash, maps each key to the location of its
+//! value. Lookups binary search the index and read a single value.
+
+mod decoder;
+mod encoder;
+
+use std::ops::RangeInclusive;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    builder::SectionHeaderBuilder,
+    error::Error,
+    header::{SectionHeader, SECTION_TYPE_STRING},
+    variant::{FromTypeExt, ToTypeExt, Variant},
+    Result
+};
+
+pub use decoder::KvReader;
+pub use encoder::KvWriter;
+
+/// The standard type for a data section in a BPX Key-Value store (type K).
+pub const SECTION_TYPE_DATA: u8 = 0x1;
+
+/// The standard type for the index section in a BPX Key-Value store (type K).
+pub const SECTION_TYPE_INDEX: u8 = 0x2;
+
+/// The supported BPX version for this key-value store variant decoder/encoder.
+pub const SUPPORTED_VERSION: u32 = 0x2;
+
+/// The BPX versions accepted by the key-value store variant decoder.
+pub const SUPPORTED_VERSIONS: RangeInclusive<u32> = SUPPORTED_VERSION..=SUPPORTED_VERSION;
+
+/// The code of the 64 bits FNV-1a key hash, the only one currently defined.
+pub const HASH_FNV1A64: u8 = 0x0;
+
+/// The size in bytes of an entry of the index.
+///
+/// Layout:
+///
+/// * bytes 0-7: hash of the key (see [key_hash](self::key_hash)).
+/// * bytes 8-11: pointer to the key in the strings section.
+/// * bytes 12-15: index of the data section containing the value.
+/// * bytes 16-19: offset to the length prefix of the value in the data section.
+/// * bytes 20-23: size of the value in bytes.
+pub const SIZE_INDEX_ENTRY: usize = crate::format::kvstore::INDEX_ENTRY_SIZE;
+
+/// Typed view of the Extended Type Information field of a BPX Key-Value store (type K).
+///
+/// Layout:
+///
+/// * byte 0: code of the key hash, [HASH_FNV1A64](self::HASH_FNV1A64).
+/// * bytes 1-15: unused (zero).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KvDescriptor
+{
+    /// The code of the hash used to sort the index.
+    pub hash: u8
+}
+
+impl ToTypeExt for KvDescriptor
+{
+    fn to_type_ext(&self) -> [u8; 16]
+    {
+        let mut type_ext: [u8; 16] = [0; 16];
+        type_ext[0] = self.hash;
+        return type_ext;
+    }
+}
+
+impl FromTypeExt for KvDescriptor
+{
+    fn from_type_ext(type_ext: &[u8; 16]) -> Result<Self>
+    {
+        if type_ext[0] != HASH_FNV1A64 {
+            return Err(Error::Corruption(format!("Unknown key hash code {}", type_ext[0])));
+        }
+        return Ok(KvDescriptor { hash: type_ext[0] });
+    }
+}
+
+/// The [Variant](crate::variant::Variant) description of a BPX Key-Value store (type K).
+pub struct KvStore;
+
+impl Variant for KvStore
+{
+    const TYPE_BYTE: u8 = b'K';
+    const VERSION: u32 = SUPPORTED_VERSION;
+    const SUPPORTED_VERSIONS: RangeInclusive<u32> = SUPPORTED_VERSIONS;
+    type TypeExt = KvDescriptor;
+
+    fn required_sections() -> &'static [u8]
+    {
+        return &[SECTION_TYPE_INDEX, SECTION_TYPE_STRING];
+    }
+
+    fn required_section_header(btype: u8) -> SectionHeader
+    {
+        // The index is a table of references, stored like strings.
+        return SectionHeaderBuilder::strings().with_type(btype).build();
+    }
+}
+
+/// Computes the hash of a key as stored in the index (64 bits FNV-1a of the UTF-8 bytes).
+///
+/// # Arguments
+///
+/// * `key`: the key.
+///
+/// returns: u64
+///
+/// # Examples
+///
+/// ```
+/// use bpx::variant::kvstore::key_hash;
+///
+/// assert_eq!(key_hash(""), 0xcbf29ce484222325);
+/// assert_eq!(key_hash("a"), 0xaf63dc4c8601ec8c);
+/// ```
+pub fn key_hash(key: &str) -> u64
+{
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in key.bytes() {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    return hash;
+}
+
+/// Represents an index entry as read from the store.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IndexEntry
+{
+    /// The hash of the key.
+    pub hash: u64,
+
+    /// The pointer to the key in the strings section.
+    pub key: u32,
+
+    /// The index of the data section containing the value.
+    pub section: u32,
+
+    /// The offset to the length prefix of the value in the data section.
+    pub offset: u32,
+
+    /// The size of the value.
+    pub size: u32
+}
+
+impl IndexEntry
+{
+    fn to_bytes(self) -> [u8; SIZE_INDEX_ENTRY]
+    {
+        let mut buf: [u8; SIZE_INDEX_ENTRY] = [0; SIZE_INDEX_ENTRY];
+        LittleEndian::write_u64(&mut buf[0..8], self.hash);
+        LittleEndian::write_u32(&mut buf[8..12], self.key);
+        LittleEndian::write_u32(&mut buf[12..16], self.section);
+        LittleEndian::write_u32(&mut buf[16..20], self.offset);
+        LittleEndian::write_u32(&mut buf[20..24], self.size);
+        return buf;
+    }
+
+    fn from_bytes(buf: &[u8; SIZE_INDEX_ENTRY]) -> IndexEntry
+    {
+        return IndexEntry {
+            hash: LittleEndian::read_u64(&buf[0..8]),
+            key: LittleEndian::read_u32(&buf[8..12]),
+            section: LittleEndian::read_u32(&buf[12..16]),
+            offset: LittleEndian::read_u32(&buf[16..20]),
+            size: LittleEndian::read_u32(&buf[20..24])
+        };
+    }
+}
@@ -27,5 +27,303 @@
 // SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 //! This module contains implementations for the standard BPX variants/types.
+//!
+//! It also contains the [Variant](self::Variant) trait, shared by all variants, which can be
+//! used to implement custom variants on top of [VariantEncoder](self::VariantEncoder) and
+//! [VariantDecoder](self::VariantDecoder).
 
+pub mod kvstore;
 pub mod package;
+pub mod texture;
+
+use std::ops::RangeInclusive;
+
+use crate::{
+    builder::{MainHeaderBuilder, SectionHeaderBuilder},
+    error::Error,
+    header::{self, SectionHeader},
+    Interface,
+    Result,
+    SectionHandle,
+    WriteInterface
+};
+
+/// Trait for types which can be encoded into a BPX Extended Type Information block.
+pub trait ToTypeExt
+{
+    /// Encodes this into a BPX Extended Type Information block.
+    fn to_type_ext(&self) -> [u8; 16];
+}
+
+/// Trait for types which can be decoded from a BPX Extended Type Information block.
+pub trait FromTypeExt: Sized
+{
+    /// Decodes a BPX Extended Type Information block.
+    ///
+    /// # Arguments
+    ///
+    /// * `type_ext`: the Extended Type Information block to decode.
+    ///
+    /// returns: Result<Self, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the block is invalid for this variant.
+    fn from_type_ext(type_ext: &[u8; 16]) -> Result<Self>;
+}
+
+/// Describes a BPX variant.
+///
+/// # Examples
+///
+/// ```
+/// use bpx::encoder::Encoder;
+/// use bpx::variant::{FromTypeExt, ToTypeExt, Variant, VariantDecoder, VariantEncoder};
+/// use bpx::Result;
+///
+/// struct Animation;
+///
+/// struct AnimationTypeExt(u8);
+///
+/// impl ToTypeExt for AnimationTypeExt
+/// {
+///     fn to_type_ext(&self) -> [u8; 16]
+///     {
+///         let mut type_ext = [0; 16];
+///         type_ext[0] = self.0;
+///         type_ext
+///     }
+/// }
+///
+/// impl FromTypeExt for AnimationTypeExt
+/// {
+///     fn from_type_ext(type_ext: &[u8; 16]) -> Result<Self>
+///     {
+///         Ok(AnimationTypeExt(type_ext[0]))
+///     }
+/// }
+///
+/// impl Variant for Animation
+/// {
+///     const TYPE_BYTE: u8 = b'A';
+///     const VERSION: u32 = 0x2;
+///     type TypeExt = AnimationTypeExt;
+///
+///     fn required_sections() -> &'static [u8]
+///     {
+///         &[0x1]
+///     }
+/// }
+///
+/// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+/// let variant = VariantEncoder::<Animation, _>::create(&mut encoder, AnimationTypeExt(60)).unwrap();
+/// assert_eq!(variant.get_required_sections().len(), 1);
+/// let decoder = VariantDecoder::<Animation, _>::read(&mut encoder).unwrap();
+/// assert_eq!(decoder.get_type_ext().0, 60);
+/// ```
+pub trait Variant
+{
+    /// The BPX type byte of this variant.
+    const TYPE_BYTE: u8;
+
+    /// The BPX version written by the encoder of this variant.
+    const VERSION: u32;
+
+    /// The BPX versions accepted by the decoder of this variant.
+    ///
+    /// *By default, only [VERSION](Self::VERSION) is accepted.*
+    const SUPPORTED_VERSIONS: RangeInclusive<u32> = Self::VERSION..=Self::VERSION;
+
+    /// The typed view of the Extended Type Information block of this variant.
+    type TypeExt: ToTypeExt + FromTypeExt;
+
+    /// Returns the types of the sections which must exist in any BPX of this variant.
+    fn required_sections() -> &'static [u8];
+
+    /// Returns the header to use when creating a required section of this variant.
+    ///
+    /// *By default, the section is created uncompressed and without checksum.*
+    ///
+    /// # Arguments
+    ///
+    /// * `btype`: the type of the required section.
+    ///
+    /// returns: SectionHeader
+    fn required_section_header(btype: u8) -> SectionHeader
+    {
+        return SectionHeaderBuilder::new().with_type(btype).build();
+    }
+}
+
+/// Shared encoder for BPX variants.
+///
+/// *Writes the main header and creates the required sections of the variant.*
+pub struct VariantEncoder<'a, V: Variant, TInterface: WriteInterface>
+{
+    interface: &'a mut TInterface,
+    type_ext: V::TypeExt,
+    required: Vec<SectionHandle>
+}
+
+impl<'a, V: Variant, TInterface: WriteInterface> VariantEncoder<'a, V, TInterface>
+{
+    /// Creates a new VariantEncoder by initializing a BPX encoder for the variant `V`.
+    ///
+    /// # Arguments
+    ///
+    /// * `interface`: the BPX [Encoder](crate::encoder::Encoder) or
+    ///   [Container](crate::container::Container) to write to.
+    /// * `type_ext`: the Extended Type Information of the variant.
+    ///
+    /// returns: Result<VariantEncoder<V, TInterface>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned in case some sections could not be created.
+    pub fn create(interface: &'a mut TInterface, type_ext: V::TypeExt) -> Result<Self>
+    {
+        let header = MainHeaderBuilder::new()
+            .with_type(V::TYPE_BYTE)
+            .with_type_ext(type_ext.to_type_ext())
+            .with_version(V::VERSION)
+            .build();
+        interface.set_main_header(header);
+        let mut required = Vec::with_capacity(V::required_sections().len());
+        for btype in V::required_sections() {
+            required.push(interface.create_section(V::required_section_header(*btype))?);
+        }
+        return Ok(VariantEncoder {
+            interface,
+            type_ext,
+            required
+        });
+    }
+
+    /// Returns the Extended Type Information of this variant.
+    pub fn get_type_ext(&self) -> &V::TypeExt
+    {
+        return &self.type_ext;
+    }
+
+    /// Returns the handles to the required sections, in the order of
+    /// [required_sections](self::Variant::required_sections).
+    pub fn get_required_sections(&self) -> &[SectionHandle]
+    {
+        return &self.required;
+    }
+
+    /// Returns the underlying BPX encoder.
+    pub fn get_interface(&mut self) -> &mut TInterface
+    {
+        return self.interface;
+    }
+
+    /// Consumes this VariantEncoder and returns the underlying BPX encoder,
+    /// the Extended Type Information and the handles to the required sections.
+    pub fn into_parts(self) -> (&'a mut TInterface, V::TypeExt, Vec<SectionHandle>)
+    {
+        return (self.interface, self.type_ext, self.required);
+    }
+}
+
+/// Shared decoder for BPX variants.
+///
+/// *Checks the type byte, the version and the presence of the required sections of the variant.*
+pub struct VariantDecoder<'a, V: Variant, TInterface: Interface>
+{
+    interface: &'a mut TInterface,
+    type_ext: V::TypeExt,
+    required: Vec<SectionHandle>
+}
+
+impl<'a, V: Variant, TInterface: Interface> VariantDecoder<'a, V, TInterface>
+{
+    /// Creates a new VariantDecoder by validating a BPX decoder against the variant `V`.
+    ///
+    /// # Arguments
+    ///
+    /// * `interface`: the BPX [Decoder](crate::decoder::Decoder) or
+    ///   [Container](crate::container::Container) backend to use.
+    ///
+    /// returns: Result<VariantDecoder<V, TInterface>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the type byte does not match,
+    /// the version is not supported, the Extended Type Information is invalid or a
+    /// required section is missing ([MissingSection](crate::error::Error::MissingSection),
+    /// listing the sections found instead).
+    pub fn read(interface: &'a mut TInterface) -> Result<Self>
+    {
+        let main_header = interface.get_main_header();
+        if main_header.btype != V::TYPE_BYTE {
+            return Err(Error::Corruption(format!(
+                "Unknown variant of BPX: {}",
+                main_header.btype as char
+            )));
+        }
+        if !V::SUPPORTED_VERSIONS.contains(&main_header.version) {
+            return Err(
+                header::Error::BadVariantVersion(V::TYPE_BYTE, main_header.version, V::SUPPORTED_VERSIONS).into()
+            );
+        }
+        let type_ext = V::TypeExt::from_type_ext(&main_header.type_ext)?;
+        let mut required = Vec::with_capacity(V::required_sections().len());
+        for btype in V::required_sections() {
+            match interface.find_section_by_type(*btype) {
+                Some(v) => required.push(v),
+                None => {
+                    return Err(Error::MissingSection {
+                        expected: *btype,
+                        found: count_section_types(interface)
+                    })
+                },
+            }
+        }
+        return Ok(VariantDecoder {
+            interface,
+            type_ext,
+            required
+        });
+    }
+
+    /// Returns the Extended Type Information of this variant.
+    pub fn get_type_ext(&self) -> &V::TypeExt
+    {
+        return &self.type_ext;
+    }
+
+    /// Returns the handles to the required sections, in the order of
+    /// [required_sections](self::Variant::required_sections).
+    pub fn get_required_sections(&self) -> &[SectionHandle]
+    {
+        return &self.required;
+    }
+
+    /// Returns the underlying BPX decoder.
+    pub fn get_interface(&mut self) -> &mut TInterface
+    {
+        return self.interface;
+    }
+
+    /// Consumes this VariantDecoder and returns the underlying BPX decoder,
+    /// the Extended Type Information and the handles to the required sections.
+    pub fn into_parts(self) -> (&'a mut TInterface, V::TypeExt, Vec<SectionHandle>)
+    {
+        return (self.interface, self.type_ext, self.required);
+    }
+}
+
+/// Returns the type byte and number of the sections of a BPX, sorted by type byte.
+fn count_section_types<TInterface: Interface>(interface: &TInterface) -> Vec<(u8, u32)>
+{
+    let mut counts: Vec<(u8, u32)> = Vec::new();
+    for handle in interface.iter_sections() {
+        let btype = interface.get_section_header(handle).btype;
+        match counts.binary_search_by_key(&btype, |v| v.0) {
+            Ok(i) => counts[i].1 += 1,
+            Err(i) => counts.insert(i, (btype, 1))
+        }
+    }
+    return counts;
+}
@@ -26,22 +26,42 @@
 // NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
 // SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use std::io::{SeekFrom, Write};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs::File,
+    io::{SeekFrom, Write},
+    path::{Component, Path}
+};
 
 use byteorder::{ByteOrder, LittleEndian};
 
 use crate::{
-    decoder::{Decoder, IoBackend},
-    error::Error,
-    header::{SECTION_TYPE_SD, SECTION_TYPE_STRING},
-    sd::Object,
+    decoder::{CachePolicy, Decoder, IoBackend},
+    error::{section_context, Error},
+    format::package::{OBJECT_ENTRY_SIZE, SUMMARY_SIZE},
+    header::SECTION_TYPE_SD,
+    sd::{HashAlgo, Object},
     strings::StringSection,
-    variant::package::{
-        object::{ObjectHeader, ObjectTable},
-        Architecture,
-        Platform,
-        SECTION_TYPE_OBJECT_TABLE,
-        SUPPORTED_VERSION
+    utils::{glob_match, read_fill},
+    variant::{
+        package::{
+            object::{ObjectHeader, ObjectTable},
+            reference::{read_reference, section_checksum, BaseSection, Resolver},
+            utils::{UnpackOptions, UnpackReport},
+            Architecture,
+            EntryLayout,
+            Manifest,
+            ManifestEntry,
+            Package,
+            PackageMetadata,
+            PackageSummary,
+            Platform,
+            SummarySource,
+            SECTION_TYPE_DATA,
+            SECTION_TYPE_REFERENCE,
+            SECTION_TYPE_SUMMARY
+        },
+        VariantDecoder
     },
     Interface,
     Result,
@@ -51,49 +71,31 @@ use crate::{
 const DATA_READ_BUFFER_SIZE: usize = 8192;
 
 /// Represents a BPX Package decoder.
-pub struct PackageDecoder<'a, TBackend: IoBackend>
+pub struct PackageDecoder<'a, TInterface: Interface>
 {
     type_code: [u8; 2],
     architecture: Architecture,
     platform: Platform,
     strings: StringSection,
-    decoder: &'a mut Decoder<TBackend>,
-    object_table: SectionHandle
-}
-
-fn get_arch_platform_from_code(acode: u8, pcode: u8) -> Result<(Architecture, Platform)>
-{
-    let arch;
-    let platform;
-
-    match acode {
-        0x0 => arch = Architecture::X86_64,
-        0x1 => arch = Architecture::Aarch64,
-        0x2 => arch = Architecture::X86,
-        0x3 => arch = Architecture::Armv7hl,
-        0x4 => arch = Architecture::Any,
-        _ => return Err(Error::Corruption(String::from("Architecture code does not exist")))
-    }
-    match pcode {
-        0x0 => platform = Platform::Linux,
-        0x1 => platform = Platform::Mac,
-        0x2 => platform = Platform::Windows,
-        0x3 => platform = Platform::Android,
-        0x4 => platform = Platform::Any,
-        _ => return Err(Error::Corruption(String::from("Platform code does not exist")))
-    }
-    return Ok((arch, platform));
+    decoder: &'a mut TInterface,
+    object_table: SectionHandle,
+    resolver: Option<Box<dyn Resolver>>,
+    bases: HashMap<u64, Decoder<Box<dyn IoBackend>>>,
+    verified: HashSet<(u64, u32)>,
+    cache: CachePolicy,
+    current: Option<u32>
 }
 
-impl<'a, TBackend: IoBackend> PackageDecoder<'a, TBackend>
+impl<'a, TInterface: Interface> PackageDecoder<'a, TInterface>
 {
     /// Creates a new PackageDecoder by reading from a BPX decoder.
     ///
     /// # Arguments
     ///
-    /// * `decoder`: the BPX [Decoder](crate::decoder::Decoder) backend to use.
+    /// * `decoder`: the BPX [Decoder](crate::decoder::Decoder) or
+    ///   [Container](crate::container::Container) backend to use.
     ///
-    /// returns: Result<PackageDecoder<TBackend>, Error>
+    /// returns: Result<PackageDecoder<TInterface>, Error>
     ///
     /// # Errors
     ///
@@ -104,46 +106,166 @@ impl<'a, TBackend: IoBackend> PackageDecoder<'a, TBackend>
     /// ```
     /// //TODO: Implement
     /// ```
-    pub fn read(decoder: &mut Decoder<TBackend>) -> Result<PackageDecoder<TBackend>>
+    pub fn read(decoder: &mut TInterface) -> Result<PackageDecoder<'_, TInterface>>
     {
-        if decoder.get_main_header().btype != 'P' as u8 {
-            return Err(Error::Corruption(format!(
-                "Unknown variant of BPX: {}",
-                decoder.get_main_header().btype as char
-            )));
-        }
-        if decoder.get_main_header().version != SUPPORTED_VERSION {
-            return Err(Error::Unsupported(format!(
-                "This version of the BPX SDK only supports BPXP version {}, you are trying to decode version {} BPXP",
-                SUPPORTED_VERSION,
-                decoder.get_main_header().version
-            )));
-        }
-        let (a, p) = get_arch_platform_from_code(
-            decoder.get_main_header().type_ext[0],
-            decoder.get_main_header().type_ext[1]
-        )?;
-        let strings = match decoder.find_section_by_type(SECTION_TYPE_STRING) {
-            Some(v) => v,
-            None => return Err(Error::Corruption(String::from("Unable to locate strings section")))
-        };
-        let object_table = match decoder.find_section_by_type(SECTION_TYPE_OBJECT_TABLE) {
-            Some(v) => v,
-            None => return Err(Error::Corruption(String::from("Unable to locate BPXP object table")))
-        };
+        let (decoder, type_ext, required) = VariantDecoder::<Package, _>::read(decoder)?.into_parts();
+        let strings = required[0];
+        let object_table = required[1];
         return Ok(PackageDecoder {
-            architecture: a,
-            platform: p,
+            architecture: type_ext.architecture,
+            platform: type_ext.platform,
             strings: StringSection::new(strings),
-            type_code: [
-                decoder.get_main_header().type_ext[2],
-                decoder.get_main_header().type_ext[3]
-            ],
+            type_code: type_ext.variant_code,
             decoder,
-            object_table
+            object_table,
+            resolver: None,
+            bases: HashMap::new(),
+            verified: HashSet::new(),
+            cache: CachePolicy::DropAfterUse,
+            current: None
         });
     }
 
+    /// Sets how the data sections read by [unpack_object](Self::unpack_object) are kept.
+    ///
+    /// *With [DropAfterUse](crate::decoder::CachePolicy::DropAfterUse), the default,
+    /// a data section is evicted from the BPX decoder as soon as an object from
+    /// another section is read, and [extract_matching](Self::extract_matching)
+    /// also evicts the object table, the strings and its last data section: a full
+    /// unpack never holds more than one inflated data section. With other policies,
+    /// data sections are kept according to the policy of the BPX decoder. Base
+    /// packages are opened with this policy.*
+    ///
+    /// # Arguments
+    ///
+    /// * `policy`: the [CachePolicy](crate::decoder::CachePolicy) of data sections.
+    ///
+    /// returns: PackageDecoder<TInterface>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::decoder::{CachePolicy, Decoder};
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::package::{PackageBuilder, PackageDecoder};
+    /// use bpx::Interface;
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut encoder = Encoder::new(&mut buf).unwrap();
+    /// let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    /// package.pack_object("a.txt", &mut b"first".as_ref()).unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(std::io::Cursor::new(buf)).unwrap();
+    /// let mut package = PackageDecoder::read(&mut decoder).unwrap().with_cache_policy(CachePolicy::KeepAll);
+    /// let obj = package.find_object("a.txt").unwrap().unwrap();
+    /// package.unpack_object(&obj, &mut Vec::new()).unwrap();
+    /// // The strings, the object table and the data section stay loaded.
+    /// assert_eq!(decoder.storage_report().sections.len(), 3);
+    /// ```
+    pub fn with_cache_policy(mut self, policy: CachePolicy) -> Self
+    {
+        self.cache = policy;
+        return self;
+    }
+
+    /// Evicts the data section read last, and the sections read from base packages.
+    fn release_current(&mut self)
+    {
+        if let Some(handle) = self.current.take().and_then(|v| self.decoder.find_section_by_index(v)) {
+            self.decoder.evict_section(handle);
+        }
+        for base in self.bases.values_mut() {
+            base.evict_all();
+        }
+    }
+
+    /// Sets the [Resolver](crate::variant::package::reference::Resolver) opening the
+    /// base packages of this overlay package.
+    ///
+    /// *Each base package is opened once, the first time one of its sections is
+    /// needed; the checksum of a referenced section is checked the first time its
+    /// data is read.*
+    ///
+    /// # Arguments
+    ///
+    /// * `resolver`: the resolver to open base packages with.
+    ///
+    /// returns: PackageDecoder<TInterface>
+    pub fn with_resolver<TResolver: Resolver + 'static>(mut self, resolver: TResolver) -> Self
+    {
+        self.resolver = Some(Box::new(resolver));
+        return self;
+    }
+
+    /// Lists the build identifiers of the base packages referenced by this
+    /// package which the resolver could not open.
+    ///
+    /// returns: Result<Vec<u64>, Error>; in the order they are first referenced.
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if a reference section could
+    /// not be read or the resolver failed to open a base package.
+    pub fn missing_bases(&mut self) -> Result<Vec<u64>>
+    {
+        let mut missing = Vec::new();
+        for handle in self.decoder.find_all_sections_of_type(SECTION_TYPE_REFERENCE) {
+            let (base, _) = read_reference(self.decoder, handle)?;
+            if missing.contains(&base) {
+                continue;
+            }
+            match self.open_base(base) {
+                Err(Error::UnresolvedBase(_)) => missing.push(base),
+                Err(e) => return Err(e),
+                Ok(_) => ()
+            }
+        }
+        return Ok(missing);
+    }
+
+    fn open_base(&mut self, base: u64) -> Result<&mut Decoder<Box<dyn IoBackend>>>
+    {
+        return match self.bases.entry(base) {
+            Entry::Occupied(o) => Ok(o.into_mut()),
+            Entry::Vacant(o) => {
+                let decoder = match self.resolver.as_mut() {
+                    Some(resolver) => resolver.open(base)?,
+                    None => None
+                };
+                let mut decoder = decoder.ok_or(Error::UnresolvedBase(base))?.with_cache_policy(self.cache);
+                if decoder.build_id()? != Some(base) {
+                    return Err(Error::Corruption(format!(
+                        "the resolver did not open base package {:016x}",
+                        base
+                    )));
+                }
+                Ok(o.insert(decoder))
+            }
+        };
+    }
+
+    fn resolve(
+        &mut self,
+        base: u64,
+        section: BaseSection
+    ) -> Result<(&mut Decoder<Box<dyn IoBackend>>, SectionHandle)>
+    {
+        self.open_base(base)?;
+        let decoder = self.bases.get_mut(&base).ok_or(Error::UnresolvedBase(base))?;
+        let handle = decoder.find_section_by_index(section.index).ok_or_else(|| {
+            Error::Corruption(format!("base package {:016x} has no section #{}", base, section.index))
+        })?;
+        if !self.verified.contains(&(base, section.index)) {
+            let actual = section_checksum(decoder, handle)?;
+            if actual != section.checksum {
+                return Err(section_context(decoder, handle)(Error::Checksum(actual, section.checksum)));
+            }
+            self.verified.insert((base, section.index));
+        }
+        return Ok((decoder, handle));
+    }
+
     /// Gets the two bytes of BPXP variant.
     pub fn get_variant(&self) -> [u8; 2]
     {
@@ -177,13 +299,150 @@ impl<'a, TBackend: IoBackend> PackageDecoder<'a, TBackend>
     pub fn read_metadata(&mut self) -> Result<Option<Object>>
     {
         if let Some(handle) = self.decoder.find_section_by_type(SECTION_TYPE_SD) {
+            let id = self.decoder.get_section_header(handle).user_tag();
+            return match HashAlgo::from_id(id) {
+                Some(algo) => self.read_metadata_with(algo),
+                None => Err(Error::Unsupported(format!("metadata name hash algorithm {}", id)))
+            };
+        }
+        return Ok(None);
+    }
+
+    /// Reads the metadata section of this BPXP using the given name hash algorithm.
+    /// Returns None if there is no metadata in this BPXP.
+    ///
+    /// *Use this to read metadata written with an application defined
+    /// [HashAlgo](crate::sd::HashAlgo); [read_metadata](PackageDecoder::read_metadata)
+    /// only knows the algorithms defined by this crate.*
+    ///
+    /// # Arguments
+    ///
+    /// * `algo`: the [HashAlgo](crate::sd::HashAlgo) the metadata was written with.
+    ///
+    /// returns: Result<Option<Object>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned in case of corruption or system error
+    /// or if the metadata was written with another algorithm.
+    pub fn read_metadata_with(&mut self, algo: HashAlgo) -> Result<Option<Object>>
+    {
+        if let Some(handle) = self.decoder.find_section_by_type(SECTION_TYPE_SD) {
+            let id = self.decoder.get_section_header(handle).user_tag();
+            if id != algo.id() {
+                return Err(Error::Unsupported(format!(
+                    "metadata name hash algorithm {} (expected {})",
+                    id,
+                    algo.id()
+                )));
+            }
+            let context = section_context(self.decoder, handle);
+            let budget = self.decoder.memory_budget().cloned();
             let mut data = self.decoder.open_section(handle)?;
-            let obj = Object::read(&mut data)?;
+            if let Some(budget) = budget {
+                // The decoded tree is at least as large as its encoding.
+                budget.check(data.size()).map_err(&context)?;
+            }
+            let obj = Object::read_with(&mut data, algo).map_err(context)?;
             return Ok(Some(obj));
         }
         return Ok(None);
     }
 
+    /// Reads the metadata section of this BPXP as standard
+    /// [PackageMetadata](crate::variant::package::PackageMetadata).
+    /// Returns None if there is no metadata in this BPXP.
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned in case of corruption or system error
+    /// or if the metadata lacks a name or a version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::package::{PackageBuilder, PackageDecoder, PackageMetadata};
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut encoder = Encoder::new(&mut buf).unwrap();
+    /// let metadata = PackageMetadata::new("game", "1.0.0");
+    /// PackageBuilder::new().with_typed_metadata(metadata.clone()).build(&mut encoder).unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    /// let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    /// assert_eq!(package.read_typed_metadata().unwrap(), Some(metadata));
+    /// ```
+    pub fn read_typed_metadata(&mut self) -> Result<Option<PackageMetadata>>
+    {
+        let handle = match self.decoder.find_section_by_type(SECTION_TYPE_SD) {
+            Some(v) => v,
+            None => return Ok(None)
+        };
+        let context = section_context(self.decoder, handle);
+        return match self.read_metadata()? {
+            Some(obj) => Ok(Some(PackageMetadata::from_object(&obj).map_err(context)?)),
+            None => Ok(None)
+        };
+    }
+
+    /// Returns the number of objects and their total size.
+    ///
+    /// *The totals are read from the summary section when present; packages
+    /// without one are scanned through their object table, which is reported
+    /// as [Scanned](crate::variant::package::SummarySource::Scanned).*
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned in case of corruption or system error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::package::{PackageBuilder, PackageDecoder, SummarySource};
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut encoder = Encoder::new(&mut buf).unwrap();
+    /// let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    /// package.pack_object("a", &mut b"hello".as_ref()).unwrap();
+    /// package.pack_object("b", &mut b"world!".as_ref()).unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    /// let summary = PackageDecoder::read(&mut decoder).unwrap().summary().unwrap();
+    /// assert_eq!((summary.file_count, summary.total_size), (2, 11));
+    /// assert_eq!(summary.source, SummarySource::Stored);
+    /// ```
+    pub fn summary(&mut self) -> Result<PackageSummary>
+    {
+        if let Some(handle) = self.decoder.find_section_by_type(SECTION_TYPE_SUMMARY) {
+            let mut buf: [u8; SUMMARY_SIZE] = [0; SUMMARY_SIZE];
+            let context = section_context(self.decoder, handle);
+            let mut data = self.decoder.open_section(handle)?;
+            if read_fill(&mut *data, &mut buf).map_err(|e| context(e.into()))? != SUMMARY_SIZE {
+                return Err(context(Error::Truncation("read summary")));
+            }
+            return Ok(PackageSummary {
+                file_count: LittleEndian::read_u64(&buf[0..8]),
+                total_size: LittleEndian::read_u64(&buf[8..16]),
+                source: SummarySource::Stored
+            });
+        }
+        let table = self.read_object_table()?;
+        let objects = table.get_objects();
+        return Ok(PackageSummary {
+            file_count: objects.len() as u64,
+            total_size: objects.iter().fold(0, |acc, v| acc.saturating_add(v.size)),
+            source: SummarySource::Scanned
+        });
+    }
+
     /// Reads the object table of this BPXP.
     ///
     /// # Errors
@@ -198,24 +457,20 @@ impl<'a, TBackend: IoBackend> PackageDecoder<'a, TBackend>
     pub fn read_object_table(&mut self) -> Result<ObjectTable>
     {
         let mut v = Vec::new();
-        let count = self.decoder.get_section_header(self.object_table).size / 20;
-        let object_table = self.decoder.open_section(self.object_table)?;
+        let count = self.decoder.get_section_header(self.object_table).size / OBJECT_ENTRY_SIZE as u32;
+        let strings_size = self.decoder.get_section_header(self.strings.handle()).size;
+        let context = section_context(self.decoder, self.object_table);
+        let mut object_table = self.decoder.open_section(self.object_table)?;
 
-        for _ in 0..count {
-            let mut buf: [u8; 20] = [0; 20];
-            if object_table.read(&mut buf)? != 20 {
-                return Err(Error::Truncation("read object table"));
+        for i in 0..count {
+            let header = ObjectHeader::read(&mut *object_table).map_err(&context)?;
+            if header.name >= strings_size {
+                return Err(context(Error::Corruption(format!(
+                    "object #{} name offset {} is past the end of the strings section ({} bytes)",
+                    i, header.name, strings_size
+                ))));
             }
-            let size = LittleEndian::read_u64(&buf[0..8]);
-            let name_ptr = LittleEndian::read_u32(&buf[8..12]);
-            let start = LittleEndian::read_u32(&buf[12..16]);
-            let offset = LittleEndian::read_u32(&buf[16..20]);
-            v.push(ObjectHeader {
-                size,
-                name: name_ptr,
-                start,
-                offset
-            })
+            v.push(header)
         }
         return Ok(ObjectTable::new(v));
     }
@@ -242,26 +497,54 @@ impl<'a, TBackend: IoBackend> PackageDecoder<'a, TBackend>
         return self.strings.get(self.decoder, obj.name);
     }
 
-    fn load_from_section<TWrite: Write>(
-        &mut self,
-        handle: SectionHandle,
-        offset: u32,
-        size: u32,
-        out: &mut TWrite
-    ) -> Result<u32>
+    /// Finds a single object by its name without building a lookup table.
+    /// Returns None if the object does not exist.
+    ///
+    /// *When many objects are looked up, prefer
+    /// [build_lookup_table](crate::variant::package::object::ObjectTable::build_lookup_table)
+    /// which reads every name only once.*
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the name of the object to search for.
+    ///
+    /// returns: Result<Option<ObjectHeader>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the object table or
+    /// a name could not be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::package::{PackageBuilder, PackageDecoder};
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut encoder = Encoder::new(&mut buf).unwrap();
+    /// let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    /// package.pack_object("a.txt", &mut b"first".as_ref()).unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(std::io::Cursor::new(buf)).unwrap();
+    /// let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    /// let obj = package.find_object("a.txt").unwrap().unwrap();
+    /// let mut data = Vec::new();
+    /// package.unpack_object(&obj, &mut data).unwrap();
+    /// assert_eq!(data, b"first");
+    /// assert!(package.find_object("b.txt").unwrap().is_none());
+    /// ```
+    pub fn find_object(&mut self, name: &str) -> Result<Option<ObjectHeader>>
     {
-        let mut len = 0;
-        let mut buf: [u8; DATA_READ_BUFFER_SIZE] = [0; DATA_READ_BUFFER_SIZE];
-        let data = self.decoder.open_section(handle)?;
-
-        data.seek(SeekFrom::Start(offset as u64))?;
-        while len < size {
-            let s = std::cmp::min(size - len, DATA_READ_BUFFER_SIZE as u32);
-            let val = data.read(&mut buf[0..s as usize])?;
-            len += val as u32;
-            out.write(&buf[0..val])?;
+        let table = self.read_object_table()?;
+        for obj in table.get_objects() {
+            if self.get_object_name(obj)? == name {
+                return Ok(Some(*obj));
+            }
         }
-        return Ok(len);
+        return Ok(None);
     }
 
     /// Unpacks an object to a raw stream.
@@ -290,21 +573,337 @@ impl<'a, TBackend: IoBackend> PackageDecoder<'a, TBackend>
         let mut len = obj.size;
 
         while len > 0 {
+            if self.cache == CachePolicy::DropAfterUse && self.current != Some(section_id) {
+                self.release_current();
+                self.current = Some(section_id);
+            }
             let handle = match self.decoder.find_section_by_index(section_id) {
                 Some(i) => i,
                 None => break
             };
-            let remaining_section_size = self.decoder.get_section_header(handle).size - offset;
-            let val = self.load_from_section(
-                handle,
-                offset,
-                std::cmp::min(remaining_section_size as u64, len) as u32,
-                out
-            )?;
+            let val = if self.decoder.get_section_header(handle).btype == SECTION_TYPE_REFERENCE {
+                let (base, section) = read_reference(self.decoder, handle)?;
+                let (decoder, handle) = self.resolve(base, section)?;
+                copy_from_section(decoder, handle, offset, len, out)?
+            } else {
+                copy_from_section(self.decoder, handle, offset, len, out)?
+            };
             len -= val as u64;
             offset = 0;
             section_id += 1;
         }
-        return Ok(obj.size);
+        return Ok(obj.size - len);
+    }
+
+    /// Computes where the content of each object is stored in the file.
+    ///
+    /// This is intended for readers which fetch parts of a package without
+    /// going through a [Decoder](crate::decoder::Decoder), for example with
+    /// HTTP range requests: fetch `section_csize` bytes at
+    /// `section_file_offset`, decompress them with `compression` and read
+    /// `size` bytes at `intra_offset`. Objects are listed in object table
+    /// order and each object produces one entry per data section it spans.
+    ///
+    /// *The offsets are the ones of the file the package was read from; they
+    /// do not reflect unsaved edits made through a [Container](crate::container::Container).*
+    ///
+    /// returns: Result<Vec<EntryLayout>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the object table or
+    /// the object names could not be read, or if an object points outside of
+    /// the data sections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::package::{PackageBuilder, PackageDecoder};
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut encoder = Encoder::new(&mut buf).unwrap();
+    /// let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    /// package.pack_object("a", &mut b"hello".as_ref()).unwrap();
+    /// package.pack_object("b", &mut b"world!".as_ref()).unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    /// let layout = PackageDecoder::read(&mut decoder).unwrap().layout().unwrap();
+    /// assert_eq!(layout.len(), 2);
+    /// assert_eq!(layout[1].section_index, layout[0].section_index);
+    /// assert_eq!((layout[1].intra_offset, layout[1].size), (5, 6));
+    /// ```
+    pub fn layout(&mut self) -> Result<Vec<EntryLayout>>
+    {
+        let table = self.read_object_table()?;
+        let mut layout = Vec::with_capacity(table.get_objects().len());
+        for obj in table.get_objects() {
+            let name = String::from(self.get_object_name(obj)?);
+            let mut section_index = obj.start;
+            let mut offset = obj.offset;
+            let mut object_offset = 0;
+            loop {
+                let header = match self.decoder.find_section_by_index(section_index) {
+                    Some(handle) => self.decoder.get_section_header(handle),
+                    None => {
+                        return Err(Error::Corruption(format!(
+                            "object '{}' points to missing data section #{}",
+                            name, section_index
+                        )))
+                    },
+                };
+                if header.btype == SECTION_TYPE_REFERENCE {
+                    return Err(Error::Unsupported(format!(
+                        "object '{}' is stored in a base package (section #{})",
+                        name, section_index
+                    )));
+                }
+                let remaining_section_size = match header.size.checked_sub(offset) {
+                    Some(v) => v,
+                    None => {
+                        return Err(Error::Corruption(format!(
+                            "object offset {} is past the end of section #{} ({} bytes)",
+                            offset, section_index, header.size
+                        )))
+                    },
+                };
+                let size = std::cmp::min(remaining_section_size as u64, obj.size - object_offset);
+                // Objects starting at the very end of a section have nothing to fetch there.
+                if size > 0 || obj.size == 0 {
+                    layout.push(EntryLayout {
+                        name: name.clone(),
+                        section_index,
+                        section_file_offset: header.pointer,
+                        section_csize: header.csize as u64,
+                        intra_offset: offset as u64,
+                        object_offset,
+                        size,
+                        compression: header.compression()
+                    });
+                }
+                object_offset += size;
+                if object_offset >= obj.size {
+                    break;
+                }
+                offset = 0;
+                section_index = match section_index.checked_add(1) {
+                    Some(v) => v,
+                    None => return Err(Error::Truncation("package layout"))
+                };
+            }
+        }
+        return Ok(layout);
+    }
+
+    /// Lists the objects of this BPXP with their sizes and the package totals.
+    ///
+    /// *The totals come from the summary section when present and are
+    /// computed from the object table otherwise, see [summary](Self::summary).
+    /// This version of the format stores no per-object checksum, so
+    /// [checksum](crate::variant::package::ManifestEntry::checksum) is always
+    /// None. No object data is read.*
+    ///
+    /// returns: Result<Manifest, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the summary, the object
+    /// table or the object names could not be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::package::{PackageBuilder, PackageDecoder};
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut encoder = Encoder::new(&mut buf).unwrap();
+    /// let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    /// package.pack_object("a", &mut b"hello".as_ref()).unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    /// let manifest = PackageDecoder::read(&mut decoder).unwrap().manifest().unwrap();
+    /// assert_eq!(manifest.to_string(), "# files\t1\n# size\t5\n# csize\t5\na\t5\t-\n");
+    /// ```
+    pub fn manifest(&mut self) -> Result<Manifest>
+    {
+        let summary = self.summary()?;
+        let table = self.read_object_table()?;
+        let mut entries = Vec::with_capacity(table.get_objects().len());
+        for obj in table.get_objects() {
+            entries.push(ManifestEntry {
+                name: String::from(self.get_object_name(obj)?),
+                size: obj.size,
+                checksum: None
+            });
+        }
+        let total_csize = self
+            .decoder
+            .sections_of_type(SECTION_TYPE_DATA)
+            .map(|handle| self.decoder.get_section_header(handle).csize as u64)
+            .sum();
+        return Ok(Manifest {
+            entries,
+            summary,
+            total_csize
+        });
+    }
+
+    /// Extracts all objects whose name matches a glob pattern to a directory.
+    ///
+    /// The pattern is matched against the virtual object names using
+    /// [glob_match](crate::utils::glob_match), so `textures/**/*.png` selects
+    /// every PNG under `textures`. Matching objects are streamed to their
+    /// destination file in storage order; objects that do not match are never
+    /// copied. A pattern matching nothing produces an empty report.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern`: the glob pattern to match object names against.
+    /// * `dest`: the destination directory.
+    /// * `options`: the extraction [UnpackOptions](crate::variant::package::utils::UnpackOptions).
+    ///
+    /// returns: Result<UnpackReport, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the object table could
+    /// not be read, if a matching object has an empty, absolute or parent
+    /// referencing name, or if an object could not be written.
+    pub fn extract_matching(&mut self, pattern: &str, dest: &Path, options: UnpackOptions) -> Result<UnpackReport>
+    {
+        let table = self.read_object_table()?;
+        let mut matches = Vec::new();
+        for obj in table.get_objects() {
+            let name = self.get_object_name(obj)?;
+            if glob_match(pattern, name) {
+                matches.push((String::from(name), *obj));
+            }
+        }
+        matches.sort_by_key(|(_, obj)| (obj.start, obj.offset));
+        if self.cache == CachePolicy::DropAfterUse {
+            self.decoder.evict_section(self.object_table);
+            self.decoder.evict_section(self.strings.handle());
+        }
+        let mut report = UnpackReport::default();
+        for (name, obj) in matches {
+            let path = Path::new(&name);
+            if name.is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+                return Err(Error::Corruption(format!(
+                    "unsafe object name '{}', aborting to prevent damage on host files",
+                    name
+                )));
+            }
+            let target = dest.join(path);
+            if options.skip_existing && target.exists() {
+                report.skipped.push(name);
+                continue;
+            }
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut file = File::create(&target)?;
+            let len = self.unpack_object(&obj, &mut file)?;
+            if len != obj.size {
+                return Err(Error::Truncation("extract matching"));
+            }
+            report.bytes += len;
+            report.extracted.push(name);
+        }
+        if self.cache == CachePolicy::DropAfterUse {
+            self.release_current();
+        }
+        return Ok(report);
+    }
+
+    /// Extracts all objects of this package to a directory.
+    ///
+    /// This is a shortcut to [extract_matching](Self::extract_matching) with
+    /// the `**` pattern; object names are turned into paths relative to `dest`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dest`: the destination directory.
+    /// * `options`: the extraction [UnpackOptions](crate::variant::package::utils::UnpackOptions).
+    ///
+    /// returns: Result<UnpackReport, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the object table could
+    /// not be read, if an object has an empty, absolute or parent referencing
+    /// name, or if an object could not be written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::decoder::Decoder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::package::{utils::UnpackOptions, PackageBuilder, PackageDecoder};
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut encoder = Encoder::new(&mut buf).unwrap();
+    /// let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    /// package.pack_object("docs/a.txt", &mut b"first".as_ref()).unwrap();
+    /// package.pack_object("b.txt", &mut b"second".as_ref()).unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let tmp = tempfile::tempdir().unwrap();
+    /// let dest = tmp.path().join("out");
+    /// let mut decoder = Decoder::new(std::io::Cursor::new(buf)).unwrap();
+    /// let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    /// let report = package.extract_all(&dest, UnpackOptions::default()).unwrap();
+    /// assert_eq!(report.extracted.len(), 2);
+    /// assert_eq!(std::fs::read(dest.join("docs/a.txt")).unwrap(), b"first");
+    /// ```
+    pub fn extract_all(&mut self, dest: &Path, options: UnpackOptions) -> Result<UnpackReport>
+    {
+        return self.extract_matching("**", dest, options);
+    }
+}
+
+/// Copies at most `len` bytes of a section starting at `offset`, up to the end of the section.
+fn copy_from_section<TInterface: Interface, TWrite: Write>(
+    interface: &mut TInterface,
+    handle: SectionHandle,
+    offset: u32,
+    len: u64,
+    out: &mut TWrite
+) -> Result<u32>
+{
+    let section_size = interface.get_section_header(handle).size;
+    let remaining_section_size = match section_size.checked_sub(offset) {
+        Some(v) => v,
+        None => {
+            return Err(Error::Corruption(format!(
+                "object offset {} is past the end of section #{} ({} bytes)",
+                offset,
+                interface.get_section_index(handle),
+                section_size
+            )))
+        },
+    };
+    let size = std::cmp::min(remaining_section_size as u64, len) as u32;
+    let mut copied = 0;
+    let mut buf: [u8; DATA_READ_BUFFER_SIZE] = [0; DATA_READ_BUFFER_SIZE];
+    let context = section_context(interface, handle);
+    let mut data = interface.open_section(handle)?;
+
+    data.seek(SeekFrom::Start(offset as u64)).map_err(|e| context(e.into()))?;
+    while copied < size {
+        let s = std::cmp::min(size - copied, DATA_READ_BUFFER_SIZE as u32);
+        let val = data.read(&mut buf[0..s as usize]).map_err(|e| context(e.into()))?;
+        if val == 0 {
+            return Err(context(Error::Truncation("unpack object")));
+        }
+        copied += val as u32;
+        out.write_all(&buf[0..val])?;
     }
+    return Ok(copied);
 }
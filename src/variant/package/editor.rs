@@ -0,0 +1,136 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use crate::{
+    container::{Container, IoBackend},
+    error::{section_context, Error},
+    header::SECTION_TYPE_SD,
+    sd::{HashAlgo, Object},
+    variant::{
+        package::{metadata_section_header, Package},
+        VariantDecoder
+    },
+    Interface,
+    Result
+};
+
+/// Represents a BPX Package editor.
+///
+/// *The editor changes the package in its [Container](crate::container::Container),
+/// which must then be saved. Data sections are never loaded.*
+pub struct PackageEditor<'a, TBackend: IoBackend>
+{
+    container: &'a mut Container<TBackend>
+}
+
+impl<'a, TBackend: IoBackend> PackageEditor<'a, TBackend>
+{
+    /// Creates a new PackageEditor from a BPX container.
+    ///
+    /// # Arguments
+    ///
+    /// * `container`: the BPX [Container](crate::container::Container) to edit.
+    ///
+    /// returns: Result<PackageEditor<TBackend>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the BPX is not a package
+    /// or is missing a required section.
+    pub fn open(container: &mut Container<TBackend>) -> Result<PackageEditor<'_, TBackend>>
+    {
+        let (container, _, _) = VariantDecoder::<Package, _>::read(container)?.into_parts();
+        return Ok(PackageEditor { container });
+    }
+
+    /// Updates the metadata object of the package.
+    ///
+    /// *The metadata is decoded, passed to `f` and encoded again. A package
+    /// without metadata gets a new metadata section starting from an empty
+    /// object. When saving the container, only the metadata section and the
+    /// headers are written if the new metadata fits in place of the old one
+    /// once compressed, see [save](crate::container::Container::save).*
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: the function modifying the metadata object.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the metadata could not be
+    /// decoded or encoded or if its name hash algorithm is unknown to this crate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use bpx::container::Container;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::package::{PackageBuilder, PackageDecoder, PackageEditor, PackageMetadata, METADATA_VERSION};
+    ///
+    /// let mut buf = Vec::new();
+    /// let mut encoder = Encoder::new(&mut buf).unwrap();
+    /// let metadata = PackageMetadata::new("game", "1.0.0");
+    /// PackageBuilder::new().with_typed_metadata(metadata).build(&mut encoder).unwrap();
+    /// encoder.save().unwrap();
+    ///
+    /// let mut container = Container::open(Cursor::new(buf)).unwrap();
+    /// let mut editor = PackageEditor::open(&mut container).unwrap();
+    /// editor.update_metadata(|obj| obj.set(METADATA_VERSION, "1.0.1".into())).unwrap();
+    /// container.save().unwrap();
+    ///
+    /// let mut container = Container::open(container.into_inner()).unwrap();
+    /// let metadata = PackageDecoder::read(&mut container).unwrap().read_typed_metadata().unwrap().unwrap();
+    /// assert_eq!(metadata.version, "1.0.1");
+    /// ```
+    pub fn update_metadata<F: FnOnce(&mut Object)>(&mut self, f: F) -> Result<()>
+    {
+        let handle = match self.container.find_section_by_type(SECTION_TYPE_SD) {
+            Some(handle) => handle,
+            None => self.container.sections().create(metadata_section_header(HashAlgo::V1))?
+        };
+        let context = section_context(self.container, handle);
+        let id = self.container.get_section_header(handle).user_tag();
+        let algo = HashAlgo::from_id(id)
+            .ok_or_else(|| Error::Unsupported(format!("metadata name hash algorithm {}", id)))?;
+        let mut obj = {
+            let mut data = self.container.sections().open(handle)?;
+            if data.size() == 0 {
+                Object::with_hash_algo(algo)
+            } else {
+                Object::read_with(&mut data, algo).map_err(&context)?
+            }
+        };
+        f(&mut obj);
+        let mut content = Vec::new();
+        obj.write(&mut content).map_err(&context)?;
+        return self.container.sections().replace(handle, content);
+    }
+}
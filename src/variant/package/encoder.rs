@@ -26,35 +26,60 @@
 // NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
 // SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use std::io::Read;
+use std::io::{Read, SeekFrom, Write};
 
 use byteorder::{ByteOrder, LittleEndian};
 
 use crate::{
-    builder::{Checksum, CompressionMethod, MainHeaderBuilder, SectionHeaderBuilder},
-    encoder::{Encoder, IoBackend},
-    header::{SectionHeader, SECTION_TYPE_SD, SECTION_TYPE_STRING},
+    builder::{Checksum, SectionHeaderBuilder},
+    encoder::EncoderProfile,
+    error::{section_context, Error},
+    format::{
+        package::{DEFAULT_SPLIT_SIZE, SUMMARY_SIZE},
+        MAX_SECTION_SIZE
+    },
+    header::SectionHeader,
     sd::Object,
-    strings::StringSection,
-    utils::OptionExtension,
-    variant::package::{Architecture, Platform, SECTION_TYPE_DATA, SECTION_TYPE_OBJECT_TABLE},
-    Interface,
+    strings::StringInterner,
+    utils::read_fill,
+    variant::{
+        package::{
+            metadata_section_header,
+            object::ObjectHeader,
+            reference::{write_reference, BaseSection},
+            source::{PackEntry, PackSource, PackSourceTree},
+            utils::PackOptions,
+            Architecture,
+            Package,
+            PackageMetadata,
+            PackageTypeExt,
+            Platform,
+            SECTION_TYPE_DATA,
+            SECTION_TYPE_REFERENCE,
+            SECTION_TYPE_SUMMARY
+        },
+        VariantEncoder
+    },
     Result,
-    SectionHandle
+    SectionHandle,
+    WriteInterface
 };
-use crate::variant::package::SUPPORTED_VERSION;
 
 const DATA_WRITE_BUFFER_SIZE: usize = 8192;
 const MIN_DATA_REMAINING_SIZE: usize = DATA_WRITE_BUFFER_SIZE;
-const MAX_DATA_SECTION_SIZE: usize = 200000000 - MIN_DATA_REMAINING_SIZE; //200MB
+const MIN_SPLIT_SIZE: u32 = 2 * DATA_WRITE_BUFFER_SIZE as u32;
 
 /// Utility to easily generate a [PackageEncoder](crate::variant::package::PackageEncoder).
+#[derive(Clone, Debug, PartialEq)]
 pub struct PackageBuilder
 {
     architecture: Architecture,
     platform: Platform,
     metadata: Option<Object>,
-    type_code: [u8; 2]
+    type_code: [u8; 2],
+    split_size: u32,
+    interner: StringInterner,
+    profile: EncoderProfile
 }
 
 impl PackageBuilder
@@ -66,7 +91,10 @@ impl PackageBuilder
             architecture: Architecture::Any,
             platform: Platform::Any,
             metadata: None,
-            type_code: [0x50, 0x48]
+            type_code: [0x50, 0x48],
+            split_size: DEFAULT_SPLIT_SIZE as u32,
+            interner: StringInterner::new(),
+            profile: EncoderProfile::Balanced
         };
     }
 
@@ -102,7 +130,8 @@ impl PackageBuilder
 
     /// Defines the metadata for the package.
     ///
-    /// *By default, no metadata object is set.*
+    /// *By default, no metadata object is set. The name hash algorithm of
+    /// the object is recorded in the user tag of the metadata section.*
     ///
     /// # Arguments
     ///
@@ -115,6 +144,30 @@ impl PackageBuilder
         return self;
     }
 
+    /// Defines the metadata of the package from standard
+    /// [PackageMetadata](crate::variant::package::PackageMetadata).
+    ///
+    /// *This replaces any metadata defined with [with_metadata](Self::with_metadata).*
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata`: the package metadata.
+    ///
+    /// returns: PackageBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::package::{PackageBuilder, PackageMetadata};
+    ///
+    /// let builder = PackageBuilder::new().with_typed_metadata(PackageMetadata::new("game", "1.0.0"));
+    /// assert!(builder.get_metadata().unwrap().get("name").is_some());
+    /// ```
+    pub fn with_typed_metadata(self, metadata: PackageMetadata) -> Self
+    {
+        return self.with_metadata(metadata.to_object());
+    }
+
     /// Defines the type of the package.
     ///
     /// *By default, the package variant is 'PK' to identify
@@ -131,13 +184,139 @@ impl PackageBuilder
         return self;
     }
 
+    /// Defines the maximum size of the data sections of the package.
+    ///
+    /// *By default, data sections are split at
+    /// [DEFAULT_SPLIT_SIZE](crate::format::package::DEFAULT_SPLIT_SIZE) bytes.
+    /// Smaller data sections are cheaper to load one at a time but compress
+    /// less. Sizes smaller than 16 KiB are raised to 16 KiB.*
+    ///
+    /// # Arguments
+    ///
+    /// * `size`: the maximum size in bytes of a data section.
+    ///
+    /// returns: PackageBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::package::PackageBuilder;
+    ///
+    /// assert_eq!(PackageBuilder::new().with_split_size(1 << 20).get_split_size(), 1 << 20);
+    /// assert_eq!(PackageBuilder::new().with_split_size(0).get_split_size(), 16384);
+    /// ```
+    pub fn with_split_size(mut self, size: u32) -> Self
+    {
+        self.split_size = std::cmp::max(size, MIN_SPLIT_SIZE);
+        return self;
+    }
+
+    /// Defines the [EncoderProfile](crate::encoder::EncoderProfile) of the data sections.
+    ///
+    /// *Data sections are created with the header returned by
+    /// [bulk_data](crate::encoder::EncoderProfile::bulk_data). The profile
+    /// does not change the settings of the encoder itself, apply it with
+    /// [apply_profile](crate::encoder::Encoder::apply_profile). By default,
+    /// [Balanced](crate::encoder::EncoderProfile::Balanced) is used.*
+    ///
+    /// # Arguments
+    ///
+    /// * `profile`: the profile of the data sections.
+    ///
+    /// returns: PackageBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::CompressionMethod;
+    /// use bpx::encoder::{Encoder, EncoderProfile};
+    /// use bpx::variant::package::{PackageBuilder, SECTION_TYPE_DATA};
+    /// use bpx::Interface;
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// encoder.apply_profile(EncoderProfile::Fast);
+    /// let mut package = PackageBuilder::new().with_profile(EncoderProfile::Fast).build(&mut encoder).unwrap();
+    /// package.pack_object("a", &mut b"data".as_ref()).unwrap();
+    /// let data = encoder.find_section_by_type(SECTION_TYPE_DATA).unwrap();
+    /// assert_eq!(encoder.get_section_header(data).compression(), Some(CompressionMethod::Zlib));
+    /// ```
+    pub fn with_profile(mut self, profile: EncoderProfile) -> Self
+    {
+        self.profile = profile;
+        return self;
+    }
+
+    /// Defines the [StringInterner](crate::strings::StringInterner) object names are written with.
+    ///
+    /// *Object names are always deduplicated within the package. Passing the
+    /// interner of another variant encoder of the same BPX, see
+    /// [into_interner](crate::variant::package::PackageEncoder::into_interner),
+    /// keeps its statistics across all string sections of the file.*
+    ///
+    /// # Arguments
+    ///
+    /// * `interner`: the interner to use.
+    ///
+    /// returns: PackageBuilder
+    pub fn with_interner(mut self, interner: StringInterner) -> Self
+    {
+        self.interner = interner;
+        return self;
+    }
+
+    /// Returns the CPU architecture that the package is targeting.
+    pub fn get_architecture(&self) -> Architecture
+    {
+        return self.architecture;
+    }
+
+    /// Returns the platform that the package is targeting.
+    pub fn get_platform(&self) -> Platform
+    {
+        return self.platform;
+    }
+
+    /// Returns the metadata object of the package if any.
+    pub fn get_metadata(&self) -> Option<&Object>
+    {
+        return self.metadata.as_ref();
+    }
+
+    /// Returns the maximum size of the data sections of the package.
+    pub fn get_split_size(&self) -> u32
+    {
+        return self.split_size;
+    }
+
+    /// Returns the profile of the data sections.
+    pub fn get_profile(&self) -> EncoderProfile
+    {
+        return self.profile;
+    }
+
+    /// Returns the type of the package.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::package::PackageBuilder;
+    ///
+    /// let builder = PackageBuilder::new().with_type(*b"PK");
+    /// assert_eq!(builder.get_type(), *b"PK");
+    /// ```
+    pub fn get_type(&self) -> [u8; 2]
+    {
+        return self.type_code;
+    }
+
     /// Builds the corresponding [PackageEncoder](crate::variant::package::PackageEncoder).
     ///
     /// # Arguments
     ///
-    /// * `encoder`:
+    /// * `encoder`: the BPX [Encoder](crate::encoder::Encoder) or
+    ///   [Container](crate::container::Container) to write to.
     ///
-    /// returns: Result<PackageEncoder<TBackend>, Error>
+    /// returns: Result<PackageEncoder<TInterface>, Error>
     ///
     /// # Errors
     ///
@@ -154,107 +333,103 @@ impl PackageBuilder
     /// encoder.save();
     /// //TODO: Finish
     /// ```
-    pub fn build<TBackend: IoBackend>(self, encoder: &mut Encoder<TBackend>) -> Result<PackageEncoder<TBackend>>
-    {
-        let mut type_ext: [u8; 16] = [0; 16];
-        match self.architecture {
-            Architecture::X86_64 => type_ext[0] = 0x0,
-            Architecture::Aarch64 => type_ext[0] = 0x1,
-            Architecture::X86 => type_ext[0] = 0x2,
-            Architecture::Armv7hl => type_ext[0] = 0x3,
-            Architecture::Any => type_ext[0] = 0x4
-        }
-        match self.platform {
-            Platform::Linux => type_ext[1] = 0x0,
-            Platform::Mac => type_ext[1] = 0x1,
-            Platform::Windows => type_ext[1] = 0x2,
-            Platform::Android => type_ext[1] = 0x3,
-            Platform::Any => type_ext[1] = 0x4
-        }
-        type_ext[2] = self.type_code[0];
-        type_ext[3] = self.type_code[1];
-        let header = MainHeaderBuilder::new()
-            .with_type('P' as u8)
-            .with_type_ext(type_ext)
-            .with_version(SUPPORTED_VERSION)
-            .build();
-        encoder.set_main_header(header);
-        let strings_header = SectionHeaderBuilder::new()
-            .with_checksum(Checksum::Weak)
-            .with_compression(CompressionMethod::Zlib)
-            .with_type(SECTION_TYPE_STRING)
-            .build();
-        let object_table_header = SectionHeaderBuilder::new()
-            .with_checksum(Checksum::Weak)
-            .with_compression(CompressionMethod::Zlib)
-            .with_type(SECTION_TYPE_OBJECT_TABLE)
-            .build();
-        let strings = encoder.create_section(strings_header)?;
-        let object_table = encoder.create_section(object_table_header)?;
+    pub fn build<TInterface: WriteInterface>(self, encoder: &mut TInterface) -> Result<PackageEncoder<'_, TInterface>>
+    {
+        let type_ext = PackageTypeExt {
+            architecture: self.architecture,
+            platform: self.platform,
+            variant_code: self.type_code
+        };
+        let (encoder, _, required) = VariantEncoder::<Package, _>::create(encoder, type_ext)?.into_parts();
+        let strings = required[0];
+        let object_table = required[1];
         if let Some(obj) = self.metadata {
-            let metadata_header = SectionHeaderBuilder::new()
-                .with_checksum(Checksum::Weak)
-                .with_compression(CompressionMethod::Zlib)
-                .with_type(SECTION_TYPE_SD)
-                .build();
-            let metadata = encoder.create_section(metadata_header)?;
+            let metadata = encoder.create_section(metadata_section_header(obj.hash_algo()))?;
             obj.write(&mut encoder.open_section(metadata)?)?;
         }
-        return Ok(PackageEncoder {
+        let summary_header = SectionHeaderBuilder::new()
+            .with_type(SECTION_TYPE_SUMMARY)
+            .with_size(SUMMARY_SIZE as u32)
+            .with_checksum(Checksum::Weak)
+            .build();
+        let summary = encoder.create_section(summary_header)?;
+        let mut package = PackageEncoder {
             strings,
+            interner: self.interner,
             encoder,
             last_data_section: None,
-            object_table
-        });
+            object_table,
+            summary,
+            file_count: 0,
+            total_size: 0,
+            split_size: self.split_size as u64,
+            profile: self.profile
+        };
+        package.write_summary()?;
+        return Ok(package);
+    }
+}
+
+impl Default for PackageBuilder
+{
+    fn default() -> Self
+    {
+        return Self::new();
     }
 }
 
 /// Represents a BPX Package encoder
-pub struct PackageEncoder<'a, TBackend: IoBackend>
+pub struct PackageEncoder<'a, TInterface: WriteInterface>
 {
     strings: SectionHandle,
+    interner: StringInterner,
     last_data_section: Option<SectionHandle>,
     object_table: SectionHandle,
-    encoder: &'a mut Encoder<TBackend>
+    summary: SectionHandle,
+    file_count: u64,
+    total_size: u64,
+    split_size: u64,
+    profile: EncoderProfile,
+    encoder: &'a mut TInterface
 }
 
-fn create_data_section_header() -> SectionHeader
+fn create_data_section_header(profile: EncoderProfile) -> SectionHeader
 {
-    let header = SectionHeaderBuilder::new()
-        .with_type(SECTION_TYPE_DATA)
-        .with_compression(CompressionMethod::Xz)
-        .with_checksum(Checksum::Crc32)
-        .build();
-    return header;
+    return profile.bulk_data().with_type(SECTION_TYPE_DATA).build();
 }
 
-impl<'a, TBackend: IoBackend> PackageEncoder<'a, TBackend>
+impl<'a, TInterface: WriteInterface> PackageEncoder<'a, TInterface>
 {
-    fn write_object<TRead: Read>(&mut self, source: &mut TRead, data_id: SectionHandle) -> Result<(usize, bool)>
-    {
-        let data = self.encoder.open_section(data_id)?;
-        let mut buf: [u8; DATA_WRITE_BUFFER_SIZE] = [0; DATA_WRITE_BUFFER_SIZE];
-        let mut res = source.read(&mut buf)?;
-        let mut count = res;
-
-        while res > 0 {
-            data.write(&buf[0..res])?;
-            if data.size() >= MAX_DATA_SECTION_SIZE
-            //Split sections (this is to avoid reaching the 4Gb max)
-            {
-                return Ok((count, true));
-            }
-            res = source.read(&mut buf)?;
-            count += res;
-        }
-        return Ok((count, false));
+    /// Returns the number of bytes a data section can still take before reaching the split size.
+    fn data_section_room(&mut self, handle: SectionHandle) -> Result<u64>
+    {
+        let remaining = self.encoder.section_remaining_capacity(handle)?;
+        let used = MAX_SECTION_SIZE - remaining;
+        return Ok(std::cmp::min(remaining, self.split_size.saturating_sub(used)));
+    }
+
+    fn write_summary(&mut self) -> Result<()>
+    {
+        let mut buf: [u8; SUMMARY_SIZE] = [0; SUMMARY_SIZE];
+        LittleEndian::write_u64(&mut buf[0..8], self.file_count);
+        LittleEndian::write_u64(&mut buf[8..16], self.total_size);
+        let context = section_context(self.encoder, self.summary);
+        let mut data = self.encoder.open_section(self.summary)?;
+        data.seek(SeekFrom::Start(0)).map_err(|e| context(e.into()))?;
+        data.write_all(&buf).map_err(|e| context(e.into()))?;
+        return Ok(());
     }
 
     /// Stores an object in this BPXP with the given name.
     ///
+    /// *The file count and total size of the summary section are updated
+    /// accordingly. The recorded size is the number of bytes read from
+    /// `source`, use [pack_object_sized](Self::pack_object_sized) to reject
+    /// sources which changed since their size was known.*
+    ///
     /// **This function prints some information to standard output as a way
-    /// to debug data compression issues unless the `debug-log` feature
-    /// is disabled.**
+    /// to debug data compression issues when the `debug-log` feature
+    /// is enabled.**
     ///
     /// # Arguments
     ///
@@ -270,40 +445,444 @@ impl<'a, TBackend: IoBackend> PackageEncoder<'a, TBackend>
     /// ```
     pub fn pack_object<TRead: Read>(&mut self, name: &str, source: &mut TRead) -> Result<()>
     {
-        let mut object_size = 0;
-        let useless = &mut self.encoder;
-        let mut data_section = *Option::get_or_insert_with_err(&mut self.last_data_section, || {
-            useless.create_section(create_data_section_header())
-        })?;
-        let start = self.encoder.get_section_index(data_section);
-        let offset = self.encoder.open_section(data_section)?.size() as u32;
+        return self.pack_object_checked(name, source, None);
+    }
+
+    /// Stores an object of a known size in this BPXP with the given name.
+    ///
+    /// *At most `size` + 1 bytes are read from `source`, so a source which
+    /// keeps growing does not stall packing.*
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the name of the object.
+    /// * `source`: the source object data as a [Read](std::io::Read).
+    /// * `size`: the number of bytes `source` is expected to produce.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the object could not be
+    /// packed. If `source` did not produce exactly `size` bytes,
+    /// [SourceChanged](crate::error::Error::SourceChanged) is returned and no
+    /// object is recorded; the bytes already copied stay unreferenced in the
+    /// data section.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::error::Error;
+    /// use bpx::variant::package::PackageBuilder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    /// package.pack_object_sized("a", &mut b"data".as_ref(), 4).unwrap();
+    /// let e = package.pack_object_sized("b", &mut b"more data".as_ref(), 4).unwrap_err();
+    /// assert!(matches!(e, Error::SourceChanged(_, 4, 5)));
+    /// ```
+    pub fn pack_object_sized<TRead: Read>(&mut self, name: &str, source: &mut TRead, size: u64) -> Result<()>
+    {
+        let mut source = source.take(size.saturating_add(1));
+        return self.pack_object_checked(name, &mut source, Some(size));
+    }
+
+    /// Stores an object read from a [PackSource](crate::variant::package::source::PackSource).
+    ///
+    /// # Arguments
+    ///
+    /// * `src`: the source of the object.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the source could not be
+    /// opened or the object could not be packed.
+    pub fn pack_source(&mut self, src: &mut dyn PackSource) -> Result<()>
+    {
+        return self.pack_source_with(src, PackOptions::default());
+    }
+
+    /// Stores an object read from a [PackSource](crate::variant::package::source::PackSource)
+    /// with the given [PackOptions](crate::variant::package::utils::PackOptions).
+    ///
+    /// # Arguments
+    ///
+    /// * `src`: the source of the object.
+    /// * `options`: the packing options.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the source could not be
+    /// opened or the object could not be packed, or
+    /// [SourceChanged](crate::error::Error::SourceChanged) if `fail_on_size_change`
+    /// is set and the source did not produce its size.
+    pub fn pack_source_with(&mut self, src: &mut dyn PackSource, options: PackOptions) -> Result<()>
+    {
+        let name = String::from(src.name());
+        return self.pack_source_named(&name, src, options);
+    }
+
+    fn pack_source_named(&mut self, name: &str, src: &mut dyn PackSource, options: PackOptions) -> Result<()>
+    {
+        let size = src.size();
+        #[cfg(feature = "debug-log")]
+        println!("Writing file {} with {} byte(s)", name, size);
+        let mut reader = src.open()?;
+        if options.fail_on_size_change {
+            return self.pack_object_sized(name, &mut reader, size);
+        }
+        return self.pack_object(name, &mut reader);
+    }
+
+    /// Stores all objects of a [PackSourceTree](crate::variant::package::source::PackSourceTree).
+    ///
+    /// *Objects are named after their path in the tree, starting with the
+    /// name of the tree and separated by '/'.*
+    ///
+    /// # Arguments
+    ///
+    /// * `tree`: the tree of objects.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if a directory could not be
+    /// listed or an object could not be packed.
+    pub fn pack_tree(&mut self, tree: &mut dyn PackSourceTree) -> Result<()>
+    {
+        return self.pack_tree_with(tree, PackOptions::default());
+    }
+
+    /// Stores all objects of a [PackSourceTree](crate::variant::package::source::PackSourceTree)
+    /// with the given [PackOptions](crate::variant::package::utils::PackOptions).
+    ///
+    /// # Arguments
+    ///
+    /// * `tree`: the tree of objects.
+    /// * `options`: the packing options.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if a directory could not be
+    /// listed or an object could not be packed, see [pack_source_with](Self::pack_source_with).
+    pub fn pack_tree_with(&mut self, tree: &mut dyn PackSourceTree, options: PackOptions) -> Result<()>
+    {
+        let name = String::from(tree.name());
+        return self.pack_tree_named(&name, tree, options);
+    }
+
+    /// Returns the [StringInterner](crate::strings::StringInterner) object names are written with.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::package::PackageBuilder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    /// package.pack_object("a.txt", &mut b"first".as_ref()).unwrap();
+    /// package.pack_object("a.txt", &mut b"second".as_ref()).unwrap();
+    /// assert_eq!(package.interner().stats().saved_bytes, 6);
+    /// ```
+    pub fn interner(&self) -> &StringInterner
+    {
+        return &self.interner;
+    }
+
+    /// Consumes this encoder and returns its [StringInterner](crate::strings::StringInterner),
+    /// to be passed to the next variant encoder of the same BPX.
+    pub fn into_interner(self) -> StringInterner
+    {
+        return self.interner;
+    }
 
+    fn pack_tree_named(&mut self, name: &str, tree: &mut dyn PackSourceTree, options: PackOptions) -> Result<()>
+    {
+        for entry in tree.entries()? {
+            match entry {
+                PackEntry::Source(mut src) => {
+                    let path = format!("{}/{}", name, src.name());
+                    self.pack_source_named(&path, src.as_mut(), options)?;
+                },
+                PackEntry::Tree(mut tree) => {
+                    let path = format!("{}/{}", name, tree.name());
+                    self.pack_tree_named(&path, tree.as_mut(), options)?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn pack_object_checked<TRead: Read>(&mut self, name: &str, source: &mut TRead, size: Option<u64>) -> Result<()>
+    {
+        let mut writer = self.begin_object(name, size);
         loop {
-            let (count, need_section) = self.write_object(source, data_section)?;
-            object_size += count;
-            if need_section {
-                data_section = self.encoder.create_section(create_data_section_header())?;
-            } else {
+            writer.pending = read_fill(source, &mut writer.buf)?;
+            if writer.pending == 0 {
                 break;
             }
+            writer.write_pending()?;
         }
+        return writer.finish();
+    }
+
+    /// Starts storing an object in this BPXP with the given name, its data
+    /// is then fed incrementally to the returned [ObjectWriter].
+    ///
+    /// *This is the building block of all pack functions: it lets a caller
+    /// driven by its own event loop write the object as data becomes available
+    /// instead of handing over a blocking [Read](std::io::Read).*
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the name of the object.
+    /// * `expected_size`: the number of bytes the object is expected to contain,
+    ///   checked by [finish](ObjectWriter::finish), or None to accept any size.
+    ///
+    /// returns: ObjectWriter<TInterface>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Write;
+    ///
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::package::PackageBuilder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    /// let mut writer = package.begin_object("a.txt", Some(11));
+    /// writer.write_all(b"hello").unwrap();
+    /// writer.write_all(b" world").unwrap();
+    /// assert_eq!(writer.size(), 11);
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn begin_object(&mut self, name: &str, expected_size: Option<u64>) -> ObjectWriter<'_, 'a, TInterface>
+    {
+        return ObjectWriter {
+            package: self,
+            name: String::from(name),
+            expected_size,
+            start: None,
+            size: 0,
+            buf: [0; DATA_WRITE_BUFFER_SIZE],
+            pending: 0
+        };
+    }
+
+    /// Adds a section standing for a section of a base package, see
+    /// [reference](crate::variant::package::reference).
+    ///
+    /// *Objects stored in the base section are then recorded with
+    /// [reference_object](Self::reference_object). Sections referenced one
+    /// after the other get consecutive indices, so an object spanning several
+    /// base sections can be referenced by referencing all of them in order.*
+    ///
+    /// # Arguments
+    ///
+    /// * `base`: the build identifier of the base package.
+    /// * `section`: the section of the base package to reference.
+    ///
+    /// returns: Result<u32, Error>; the index of the new section in this package.
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the section could not be created.
+    pub fn reference_section(&mut self, base: u64, section: BaseSection) -> Result<u32>
+    {
+        let header = SectionHeaderBuilder::new()
+            .with_type(SECTION_TYPE_REFERENCE)
+            .with_checksum(Checksum::Weak)
+            .build();
+        let handle = self.encoder.create_section(header)?;
+        {
+            let context = section_context(self.encoder, handle);
+            let mut data = self.encoder.open_section(handle)?;
+            data.write_all(&write_reference(base, section)).map_err(|e| context(e.into()))?;
+        }
+        // Objects spanning several data sections need them to be consecutive.
+        self.last_data_section = None;
+        return Ok(self.encoder.get_section_index(handle));
+    }
+
+    /// Records an object whose data is already stored in this package, usually
+    /// in a section added by [reference_section](Self::reference_section).
+    ///
+    /// *The file count and total size of the summary section are updated
+    /// accordingly.*
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the name of the object.
+    /// * `start`: the index of the first section storing the object.
+    /// * `offset`: the offset of the object in its first section.
+    /// * `size`: the size of the object in bytes.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the object could not be recorded.
+    pub fn reference_object(&mut self, name: &str, start: u32, offset: u32, size: u64) -> Result<()>
+    {
+        return self.record_object(name, size, start, offset);
+    }
+
+    fn record_object(&mut self, name: &str, size: u64, start: u32, offset: u32) -> Result<()>
+    {
         {
-            // Fill and write the object header
-            let mut buf: [u8; 20] = [0; 20];
-            let mut strings = StringSection::new(self.strings);
-            LittleEndian::write_u64(&mut buf[0..8], object_size as u64);
-            LittleEndian::write_u32(&mut buf[8..12], strings.put(self.encoder, &name)?);
-            LittleEndian::write_u32(&mut buf[12..16], start);
-            LittleEndian::write_u32(&mut buf[16..20], offset);
-            // Write the object header
-            let object_table = self.encoder.open_section(self.object_table)?;
-            object_table.write(&buf)?;
+            let header = ObjectHeader {
+                size,
+                name: self.interner.intern(self.encoder, self.strings, name)?,
+                start,
+                offset
+            };
+            let context = section_context(self.encoder, self.object_table);
+            let mut object_table = self.encoder.open_section(self.object_table)?;
+            object_table.seek(SeekFrom::End(0)).map_err(|e| context(e.into()))?;
+            header.write(&mut *object_table).map_err(|e| context(e.into()))?;
         }
-        if self.encoder.open_section(data_section)?.size() > MAX_DATA_SECTION_SIZE {
-            self.last_data_section = None;
-        } else {
-            self.last_data_section = Some(data_section);
+        self.file_count += 1;
+        self.total_size += size;
+        self.write_summary()?;
+        return Ok(());
+    }
+}
+
+/// Incremental writer for a single object of a [PackageEncoder], created by
+/// [begin_object](PackageEncoder::begin_object).
+///
+/// *Data is written to data sections in chunks of 8 KiB, a new data section is
+/// created whenever the current one reaches the split size. If the writer is
+/// dropped without calling [finish](Self::finish) no object is recorded and the
+/// bytes already written stay unreferenced in the data sections.*
+pub struct ObjectWriter<'a, 'b, TInterface: WriteInterface>
+{
+    package: &'a mut PackageEncoder<'b, TInterface>,
+    name: String,
+    expected_size: Option<u64>,
+    start: Option<(u32, u32)>,
+    size: u64,
+    buf: [u8; DATA_WRITE_BUFFER_SIZE],
+    pending: usize
+}
+
+impl<'a, 'b, TInterface: WriteInterface> ObjectWriter<'a, 'b, TInterface>
+{
+    /// Writes the pending chunk to the last data section or to a new one if it is full.
+    ///
+    /// *A new section is only requested when data remains after the section is full,
+    /// an object ending exactly at the split size does not create an empty section.*
+    fn write_pending(&mut self) -> Result<()>
+    {
+        if self.pending == 0 {
+            return Ok(());
         }
+        let package = &mut *self.package;
+        let last_data_section = package.last_data_section;
+        let data_section = match last_data_section {
+            // Leave room for the pending chunk so that sections never exceed the split size.
+            Some(v) if package.data_section_room(v)? > MIN_DATA_REMAINING_SIZE as u64 => v,
+            _ => package.encoder.create_section(create_data_section_header(package.profile))?
+        };
+        package.last_data_section = Some(data_section);
+        let index = package.encoder.get_section_index(data_section);
+        let context = section_context(package.encoder, data_section);
+        let mut data = package.encoder.open_section(data_section)?;
+        let offset = data.seek(SeekFrom::End(0)).map_err(|e| context(e.into()))?;
+        self.start.get_or_insert((index, offset as u32));
+        data.write_all(&self.buf[0..self.pending]).map_err(|e| context(e.into()))?;
+        self.size += self.pending as u64;
+        self.pending = 0;
+        return Ok(());
+    }
+
+    /// Returns the number of bytes written to this object so far.
+    pub fn size(&self) -> u64
+    {
+        return self.size + self.pending as u64;
+    }
+
+    /// Writes the remaining data and records the object in the object table.
+    ///
+    /// *The file count and total size of the summary section are updated
+    /// accordingly.*
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the object could not be
+    /// written. If an expected size was given and the number of bytes written
+    /// differs, [SourceChanged](crate::error::Error::SourceChanged) is returned
+    /// and no object is recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Write;
+    ///
+    /// use bpx::encoder::Encoder;
+    /// use bpx::error::Error;
+    /// use bpx::variant::package::PackageBuilder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    /// let mut writer = package.begin_object("a.txt", Some(8));
+    /// writer.write_all(b"data").unwrap();
+    /// assert!(matches!(writer.finish().unwrap_err(), Error::SourceChanged(_, 8, 4)));
+    /// ```
+    pub fn finish(mut self) -> Result<()>
+    {
+        self.write_pending()?;
+        let (start, offset) = match self.start {
+            Some(v) => v,
+            // Empty objects may point at the end of a full section, they never need a new one.
+            None => {
+                let package = &mut *self.package;
+                let data_section = match package.last_data_section {
+                    Some(v) => v,
+                    None => package.encoder.create_section(create_data_section_header(package.profile))?
+                };
+                package.last_data_section = Some(data_section);
+                let offset = package.encoder.open_section(data_section)?.size() as u32;
+                (package.encoder.get_section_index(data_section), offset)
+            }
+        };
+        if let Some(expected) = self.expected_size {
+            if self.size != expected {
+                return Err(Error::SourceChanged(self.name, expected, self.size));
+            }
+        }
+        return self.package.record_object(&self.name, self.size, start, offset);
+    }
+}
+
+impl<'a, 'b, TInterface: WriteInterface> Write for ObjectWriter<'a, 'b, TInterface>
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>
+    {
+        if self.pending == DATA_WRITE_BUFFER_SIZE {
+            self.write_pending()?;
+        }
+        let len = buf.len().min(DATA_WRITE_BUFFER_SIZE - self.pending);
+        self.buf[self.pending..self.pending + len].copy_from_slice(&buf[0..len]);
+        self.pending += len;
+        return Ok(len);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()>
+    {
+        self.write_pending()?;
         return Ok(());
     }
 }
@@ -0,0 +1,233 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Typed metadata for BPX Packages.
+
+use std::{
+    convert::TryInto,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+use crate::{
+    sd::{Error, Object},
+    utils::hash,
+    Result
+};
+
+/// Name of the property storing the package name; its hash is `hash("name")`.
+pub const METADATA_NAME: &str = "name";
+
+/// Name of the property storing the package version; its hash is `hash("version")`.
+pub const METADATA_VERSION: &str = "version";
+
+/// Name of the property storing the package description; its hash is `hash("description")`.
+pub const METADATA_DESCRIPTION: &str = "description";
+
+/// Name of the property storing the package author; its hash is `hash("author")`.
+pub const METADATA_AUTHOR: &str = "author";
+
+/// Name of the property storing the package build date, in seconds since the
+/// UNIX epoch; its hash is `hash("build_date")`.
+pub const METADATA_BUILD_DATE: &str = "build_date";
+
+const KEYS: [&str; 5] = [
+    METADATA_NAME,
+    METADATA_VERSION,
+    METADATA_DESCRIPTION,
+    METADATA_AUTHOR,
+    METADATA_BUILD_DATE
+];
+
+/// The standard metadata of a BPX Package.
+///
+/// *The metadata is stored as a Structured Data object using the METADATA_*
+/// property names; any other property is kept in `custom`.*
+///
+/// # Examples
+///
+/// ```
+/// use bpx::variant::package::PackageMetadata;
+///
+/// let mut metadata = PackageMetadata::new("game", "1.0.0");
+/// metadata.author = Some("someone".into());
+/// let obj = metadata.to_object();
+/// assert_eq!(PackageMetadata::from_object(&obj).unwrap(), metadata);
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub struct PackageMetadata
+{
+    /// The name of the package.
+    pub name: String,
+
+    /// The version of the package.
+    pub version: String,
+
+    /// An optional description of the package.
+    pub description: Option<String>,
+
+    /// The optional author of the package.
+    pub author: Option<String>,
+
+    /// The optional build date of the package, in seconds since the UNIX epoch.
+    pub build_date: Option<u64>,
+
+    /// Any other property of the metadata object.
+    pub custom: Object
+}
+
+impl PackageMetadata
+{
+    /// Creates new package metadata with only a name and a version.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the name of the package.
+    /// * `version`: the version of the package.
+    ///
+    /// returns: PackageMetadata
+    pub fn new(name: &str, version: &str) -> PackageMetadata
+    {
+        return PackageMetadata {
+            name: name.into(),
+            version: version.into(),
+            description: None,
+            author: None,
+            build_date: None,
+            custom: Object::new()
+        };
+    }
+
+    /// Sets the build date to the current system time.
+    ///
+    /// *The build date is left unset if the system clock is before the UNIX epoch.*
+    ///
+    /// returns: PackageMetadata
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::package::PackageMetadata;
+    ///
+    /// let metadata = PackageMetadata::new("game", "1.0.0").with_current_build_date();
+    /// assert!(metadata.build_date.is_some());
+    /// ```
+    pub fn with_current_build_date(mut self) -> Self
+    {
+        self.build_date = SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|v| v.as_secs());
+        return self;
+    }
+
+    /// Converts this metadata to a Structured Data object.
+    ///
+    /// *Unset optional fields are not written; properties of `custom` using one
+    /// of the METADATA_* names are replaced.*
+    ///
+    /// returns: Object
+    pub fn to_object(&self) -> Object
+    {
+        let mut obj = self.custom.clone();
+        obj.set(METADATA_NAME, self.name.as_str().into());
+        obj.set(METADATA_VERSION, self.version.as_str().into());
+        if let Some(v) = &self.description {
+            obj.set(METADATA_DESCRIPTION, v.as_str().into());
+        }
+        if let Some(v) = &self.author {
+            obj.set(METADATA_AUTHOR, v.as_str().into());
+        }
+        if let Some(v) = self.build_date {
+            obj.set(METADATA_BUILD_DATE, v.into());
+        }
+        return obj;
+    }
+
+    /// Reads metadata from a Structured Data object.
+    ///
+    /// *Missing or null optional fields are read as None.*
+    ///
+    /// # Arguments
+    ///
+    /// * `obj`: the object to read from.
+    ///
+    /// returns: Result<PackageMetadata, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the name or the version
+    /// is missing or if a field has the wrong type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::sd::Object;
+    /// use bpx::variant::package::PackageMetadata;
+    ///
+    /// let mut obj = Object::new();
+    /// obj.set("name", "game".into());
+    /// assert!(PackageMetadata::from_object(&obj).is_err());
+    /// obj.set("version", "1.0.0".into());
+    /// obj.set("engine", "bp3d".into());
+    /// let metadata = PackageMetadata::from_object(&obj).unwrap();
+    /// assert_eq!(metadata.author, None);
+    /// assert!(metadata.custom.get("engine").is_some());
+    /// ```
+    pub fn from_object(obj: &Object) -> Result<PackageMetadata>
+    {
+        let required = |name: &'static str| -> Result<String> {
+            let value: &str = obj.get(name).ok_or(Error::MissingProp(name))?.try_into()?;
+            return Ok(value.into());
+        };
+        let optional = |name: &str| -> Result<Option<String>> {
+            let value: Option<&str> = match obj.get(name) {
+                Some(v) => v.try_into()?,
+                None => None
+            };
+            return Ok(value.map(String::from));
+        };
+        let build_date: Option<u64> = match obj.get(METADATA_BUILD_DATE) {
+            Some(v) => v.try_into()?,
+            None => None
+        };
+        let keys: Vec<u64> = KEYS.iter().map(|v| hash(v)).collect();
+        let mut custom = Object::new();
+        for key in obj.get_keys() {
+            if !keys.contains(key) {
+                if let Some(v) = obj.raw_get(*key) {
+                    custom.raw_set(*key, v.clone());
+                }
+            }
+        }
+        return Ok(PackageMetadata {
+            name: required(METADATA_NAME)?,
+            version: required(METADATA_VERSION)?,
+            description: optional(METADATA_DESCRIPTION)?,
+            author: optional(METADATA_AUTHOR)?,
+            build_date,
+            custom
+        });
+    }
+}
@@ -29,13 +29,47 @@
 //! An implementation of the BPX type P (Package) specification.
 
 mod decoder;
+mod editor;
 mod encoder;
+mod metadata;
 pub mod utils;
 pub mod object;
+pub mod reference;
+pub mod source;
+#[cfg(feature = "zip")]
+mod zip;
+
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Formatter},
+    ops::RangeInclusive,
+    str::FromStr
+};
+
+use crate::{
+    builder::{CompressionMethod, SectionHeaderBuilder},
+    error::Error,
+    header::{SectionHeader, SECTION_TYPE_STRING},
+    sd::HashAlgo,
+    variant::{FromTypeExt, ToTypeExt, Variant},
+    Result
+};
 
 pub use decoder::PackageDecoder;
+pub use editor::PackageEditor;
 pub use encoder::PackageEncoder;
 pub use encoder::PackageBuilder;
+pub use encoder::ObjectWriter;
+#[cfg(feature = "zip")]
+pub use zip::{to_zip, to_zip_with, ZipMethod};
+pub use metadata::{
+    PackageMetadata,
+    METADATA_AUTHOR,
+    METADATA_BUILD_DATE,
+    METADATA_DESCRIPTION,
+    METADATA_NAME,
+    METADATA_VERSION
+};
 
 /// The standard type for a data section in a BPX Package (type P).
 pub const SECTION_TYPE_DATA: u8 = 0x1;
@@ -43,11 +77,54 @@ pub const SECTION_TYPE_DATA: u8 = 0x1;
 /// The standard type for the object table section in a BPX Package (type P).
 pub const SECTION_TYPE_OBJECT_TABLE: u8 = 0x2;
 
+/// The standard type for the summary section in a BPX Package (type P).
+///
+/// *The section stores the number of objects and their total size as two
+/// little endian u64; packages written before it existed do not have one.*
+pub const SECTION_TYPE_SUMMARY: u8 = 0x3;
+
+/// The standard type for a section of a BPX Package (type P) which stands for
+/// a section of a base package, see [reference](crate::variant::package::reference).
+pub const SECTION_TYPE_REFERENCE: u8 = 0x4;
+
 /// The supported BPX version for this package variant decoder/encoder.
 pub const SUPPORTED_VERSION: u32 = 0x2;
 
+/// The BPX versions accepted by the package variant decoder.
+pub const SUPPORTED_VERSIONS: RangeInclusive<u32> = SUPPORTED_VERSION..=SUPPORTED_VERSION;
+
+/// The [Variant](crate::variant::Variant) description of a BPX Package (type P).
+///
+/// *The required sections are the strings section followed by the object table.*
+pub struct Package;
+
+impl Variant for Package
+{
+    const TYPE_BYTE: u8 = b'P';
+    const VERSION: u32 = SUPPORTED_VERSION;
+    const SUPPORTED_VERSIONS: RangeInclusive<u32> = SUPPORTED_VERSIONS;
+    type TypeExt = PackageTypeExt;
+
+    fn required_sections() -> &'static [u8]
+    {
+        return &[SECTION_TYPE_STRING, SECTION_TYPE_OBJECT_TABLE];
+    }
+
+    fn required_section_header(btype: u8) -> SectionHeader
+    {
+        // The object table is a small table of references, stored like strings.
+        return SectionHeaderBuilder::strings().with_type(btype).build();
+    }
+}
+
+/// Returns the header of a metadata section whose property names are hashed with `algo`.
+fn metadata_section_header(algo: HashAlgo) -> SectionHeader
+{
+    return SectionHeaderBuilder::structured_data().with_user_tag(algo.id()).build();
+}
+
 /// Enum of all supported processor architectures by BPXP.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Architecture
 {
     /// x86_64
@@ -82,8 +159,121 @@ pub enum Architecture
     Any
 }
 
+// Code point and canonical name of each architecture; the order is the order of the codes.
+const ARCHITECTURES: [(Architecture, &str); 5] = [
+    (Architecture::X86_64, "x86_64"),
+    (Architecture::Aarch64, "aarch64"),
+    (Architecture::X86, "x86"),
+    (Architecture::Armv7hl, "armv7hl"),
+    (Architecture::Any, "any")
+];
+
+impl Architecture
+{
+    /// Returns the architecture of the running host.
+    ///
+    /// *Returns [Any](Self::Any) if the host architecture is not supported by BPXP.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::package::Architecture;
+    ///
+    /// #[cfg(target_arch = "x86_64")]
+    /// assert_eq!(Architecture::current(), Architecture::X86_64);
+    /// ```
+    pub fn current() -> Architecture
+    {
+        if cfg!(target_arch = "x86_64") {
+            return Architecture::X86_64;
+        } else if cfg!(target_arch = "aarch64") {
+            return Architecture::Aarch64;
+        } else if cfg!(target_arch = "x86") {
+            return Architecture::X86;
+        } else if cfg!(target_arch = "arm") {
+            return Architecture::Armv7hl;
+        }
+        return Architecture::Any;
+    }
+
+    /// Returns the canonical lowercase name of this architecture.
+    pub fn name(self) -> &'static str
+    {
+        return ARCHITECTURES[u8::from(self) as usize].1;
+    }
+}
+
+impl From<Architecture> for u8
+{
+    fn from(arch: Architecture) -> Self
+    {
+        return match arch {
+            Architecture::X86_64 => 0x0,
+            Architecture::Aarch64 => 0x1,
+            Architecture::X86 => 0x2,
+            Architecture::Armv7hl => 0x3,
+            Architecture::Any => 0x4
+        };
+    }
+}
+
+impl TryFrom<u8> for Architecture
+{
+    type Error = Error;
+
+    fn try_from(code: u8) -> Result<Self>
+    {
+        return match ARCHITECTURES.get(code as usize) {
+            Some((arch, _)) => Ok(*arch),
+            None => Err(Error::Corruption(String::from("Architecture code does not exist")))
+        };
+    }
+}
+
+impl FromStr for Architecture
+{
+    type Err = Error;
+
+    /// Parses the canonical lowercase name of an architecture.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::package::Architecture;
+    ///
+    /// assert_eq!("aarch64".parse::<Architecture>().unwrap(), Architecture::Aarch64);
+    /// assert_eq!(Architecture::Aarch64.to_string(), "aarch64");
+    /// assert!("arm64".parse::<Architecture>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self>
+    {
+        return match ARCHITECTURES.iter().find(|(_, name)| *name == s) {
+            Some((arch, _)) => Ok(*arch),
+            None => Err(Error::Unsupported(format!("architecture '{}'", s)))
+        };
+    }
+}
+
+impl Display for Architecture
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        return f.write_str(self.name());
+    }
+}
+
+impl Default for Architecture
+{
+    /// Returns [Architecture::Any](crate::variant::package::Architecture::Any), matching the default of
+    /// [PackageBuilder](crate::variant::package::PackageBuilder).
+    fn default() -> Self
+    {
+        return Architecture::Any;
+    }
+}
+
 /// Enum of all supported platforms by BPXP.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Platform
 {
     /// GNU / Linux
@@ -111,3 +301,338 @@ pub enum Platform
     /// The package does not have a target platform and by extension can be loaded on any platform.
     Any
 }
+
+// Code point and canonical name of each platform; the order is the order of the codes.
+const PLATFORMS: [(Platform, &str); 5] = [
+    (Platform::Linux, "linux"),
+    (Platform::Mac, "mac"),
+    (Platform::Windows, "windows"),
+    (Platform::Android, "android"),
+    (Platform::Any, "any")
+];
+
+impl Platform
+{
+    /// Returns the platform of the running host.
+    ///
+    /// *Returns [Any](Self::Any) if the host platform is not supported by BPXP.*
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::package::Platform;
+    ///
+    /// #[cfg(target_os = "linux")]
+    /// assert_eq!(Platform::current(), Platform::Linux);
+    /// ```
+    pub fn current() -> Platform
+    {
+        if cfg!(target_os = "linux") {
+            return Platform::Linux;
+        } else if cfg!(target_os = "macos") {
+            return Platform::Mac;
+        } else if cfg!(target_os = "windows") {
+            return Platform::Windows;
+        } else if cfg!(target_os = "android") {
+            return Platform::Android;
+        }
+        return Platform::Any;
+    }
+
+    /// Returns the canonical lowercase name of this platform.
+    pub fn name(self) -> &'static str
+    {
+        return PLATFORMS[u8::from(self) as usize].1;
+    }
+}
+
+impl From<Platform> for u8
+{
+    fn from(platform: Platform) -> Self
+    {
+        return match platform {
+            Platform::Linux => 0x0,
+            Platform::Mac => 0x1,
+            Platform::Windows => 0x2,
+            Platform::Android => 0x3,
+            Platform::Any => 0x4
+        };
+    }
+}
+
+impl TryFrom<u8> for Platform
+{
+    type Error = Error;
+
+    fn try_from(code: u8) -> Result<Self>
+    {
+        return match PLATFORMS.get(code as usize) {
+            Some((platform, _)) => Ok(*platform),
+            None => Err(Error::Corruption(String::from("Platform code does not exist")))
+        };
+    }
+}
+
+impl FromStr for Platform
+{
+    type Err = Error;
+
+    /// Parses the canonical lowercase name of a platform.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::package::Platform;
+    ///
+    /// assert_eq!("windows".parse::<Platform>().unwrap(), Platform::Windows);
+    /// assert_eq!(Platform::Windows.to_string(), "windows");
+    /// assert!("macos".parse::<Platform>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self>
+    {
+        return match PLATFORMS.iter().find(|(_, name)| *name == s) {
+            Some((platform, _)) => Ok(*platform),
+            None => Err(Error::Unsupported(format!("platform '{}'", s)))
+        };
+    }
+}
+
+impl Display for Platform
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        return f.write_str(self.name());
+    }
+}
+
+impl Default for Platform
+{
+    /// Returns [Platform::Any](crate::variant::package::Platform::Any), matching the default of
+    /// [PackageBuilder](crate::variant::package::PackageBuilder).
+    fn default() -> Self
+    {
+        return Platform::Any;
+    }
+}
+
+/// Where a [PackageSummary](crate::variant::package::PackageSummary) was obtained from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SummarySource
+{
+    /// The totals were read from the summary section written by the encoder.
+    Stored,
+
+    /// The package has no summary section, the totals were computed from the object table.
+    Scanned
+}
+
+/// The number of objects and the total uncompressed size of a BPX Package.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackageSummary
+{
+    /// The number of objects in the package.
+    pub file_count: u64,
+
+    /// The sum of the sizes of all objects, in bytes.
+    pub total_size: u64,
+
+    /// Whether the totals were stored or had to be computed.
+    pub source: SummarySource
+}
+
+/// Location of one contiguous piece of an object inside the file of a BPX Package.
+///
+/// An object which spans several data sections is described by one entry per
+/// section, in object order. Empty objects are described by a single entry
+/// with a size of 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryLayout
+{
+    /// The name of the object this piece belongs to.
+    pub name: String,
+
+    /// The index of the data section containing this piece.
+    pub section_index: u32,
+
+    /// The absolute offset in bytes of the data section in the file.
+    pub section_file_offset: u64,
+
+    /// The size in bytes of the data section as stored in the file (after compression).
+    pub section_csize: u64,
+
+    /// The offset in bytes of this piece in the decompressed data section.
+    pub intra_offset: u64,
+
+    /// The offset in bytes of this piece in the object.
+    pub object_offset: u64,
+
+    /// The size in bytes of this piece.
+    pub size: u64,
+
+    /// The compression method of the data section, None if the section is stored uncompressed.
+    pub compression: Option<CompressionMethod>
+}
+
+/// One object listed in a [Manifest](crate::variant::package::Manifest).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestEntry
+{
+    /// The name of the object.
+    pub name: String,
+
+    /// The size in bytes of the object.
+    pub size: u64,
+
+    /// The checksum of the object, None if the package does not store per-object checksums.
+    pub checksum: Option<u32>
+}
+
+/// Machine-readable listing of the content of a BPX Package.
+///
+/// The [Display](std::fmt::Display) rendering is stable and meant to be
+/// diffed: three `# key<TAB>value` lines with the totals, then one
+/// `name<TAB>size<TAB>checksum` line per object in storage order.
+/// Unavailable fields are rendered as `-`, checksums as 8 lowercase hex
+/// digits. Tabs, new lines and backslashes in names are escaped as `\t`,
+/// `\n` and `\\`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Manifest
+{
+    /// The objects of the package, in storage order.
+    pub entries: Vec<ManifestEntry>,
+
+    /// The number of objects and their total size.
+    pub summary: PackageSummary,
+
+    /// The total size in bytes of the data sections as stored in the file (after compression).
+    pub total_csize: u64
+}
+
+impl Display for Manifest
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        writeln!(f, "# files\t{}", self.summary.file_count)?;
+        writeln!(f, "# size\t{}", self.summary.total_size)?;
+        writeln!(f, "# csize\t{}", self.total_csize)?;
+        for entry in &self.entries {
+            for c in entry.name.chars() {
+                match c {
+                    '\t' => f.write_str("\\t")?,
+                    '\n' => f.write_str("\\n")?,
+                    '\\' => f.write_str("\\\\")?,
+                    c => write!(f, "{}", c)?
+                }
+            }
+            match entry.checksum {
+                Some(v) => writeln!(f, "\t{}\t{:08x}", entry.size, v)?,
+                None => writeln!(f, "\t{}\t-", entry.size)?
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Typed view of the Extended Type Information field of a BPX Package (type P).
+///
+/// Layout:
+///
+/// * byte 0: architecture code.
+/// * byte 1: platform code.
+/// * bytes 2-3: package variant code.
+/// * bytes 4-15: unused (zero).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackageTypeExt
+{
+    /// The target CPU architecture.
+    pub architecture: Architecture,
+
+    /// The target platform.
+    pub platform: Platform,
+
+    /// The two bytes of package variant.
+    pub variant_code: [u8; 2]
+}
+
+impl PackageTypeExt
+{
+    /// Encodes this into a BPX Extended Type Information block.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::package::{Architecture, PackageTypeExt, Platform};
+    ///
+    /// let ext = PackageTypeExt {
+    ///     architecture: Architecture::Aarch64,
+    ///     platform: Platform::Windows,
+    ///     variant_code: *b"PK"
+    /// };
+    /// assert_eq!(&ext.to_type_ext()[0..4], &[0x1, 0x2, b'P', b'K']);
+    /// ```
+    pub fn to_type_ext(&self) -> [u8; 16]
+    {
+        let mut type_ext: [u8; 16] = [0; 16];
+        type_ext[0] = self.architecture.into();
+        type_ext[1] = self.platform.into();
+        type_ext[2] = self.variant_code[0];
+        type_ext[3] = self.variant_code[1];
+        return type_ext;
+    }
+
+    /// Decodes a BPX Extended Type Information block.
+    ///
+    /// # Arguments
+    ///
+    /// * `type_ext`: the Extended Type Information block to decode.
+    ///
+    /// returns: Result<PackageTypeExt, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the architecture or the
+    /// platform code does not exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::package::PackageTypeExt;
+    ///
+    /// let mut type_ext = [0; 16];
+    /// type_ext[0] = 0x4;
+    /// type_ext[1] = 0x4;
+    /// type_ext[2] = b'P';
+    /// type_ext[3] = b'K';
+    /// let ext = PackageTypeExt::from_type_ext(&type_ext).unwrap();
+    /// assert_eq!(ext.variant_code, *b"PK");
+    /// assert_eq!(ext.to_type_ext(), type_ext);
+    /// type_ext[0] = 0xFF;
+    /// assert!(PackageTypeExt::from_type_ext(&type_ext).is_err());
+    /// ```
+    pub fn from_type_ext(type_ext: &[u8; 16]) -> Result<PackageTypeExt>
+    {
+        let architecture = Architecture::try_from(type_ext[0])?;
+        let platform = Platform::try_from(type_ext[1])?;
+        return Ok(PackageTypeExt {
+            architecture,
+            platform,
+            variant_code: [type_ext[2], type_ext[3]]
+        });
+    }
+}
+
+impl ToTypeExt for PackageTypeExt
+{
+    fn to_type_ext(&self) -> [u8; 16]
+    {
+        return PackageTypeExt::to_type_ext(self);
+    }
+}
+
+impl FromTypeExt for PackageTypeExt
+{
+    fn from_type_ext(type_ext: &[u8; 16]) -> Result<Self>
+    {
+        return PackageTypeExt::from_type_ext(type_ext);
+    }
+}
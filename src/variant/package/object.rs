@@ -26,27 +26,122 @@
 // NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
 // SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, io};
 
-use crate::{decoder::IoBackend, variant::package::PackageDecoder, Result};
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    error::Error,
+    format::package::OBJECT_ENTRY_SIZE,
+    utils::read_fill,
+    variant::package::PackageDecoder,
+    Interface,
+    Result
+};
 
 /// Represents an object header as read from the package.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct ObjectHeader
 {
     /// The size of the object.
+    ///
+    /// Offset: +0
     pub size: u64,
 
     /// The pointer to the name of the object.
+    ///
+    /// Offset: +8
     pub name: u32,
 
     /// The start section index to the content.
+    ///
+    /// Offset: +12
     pub start: u32,
 
     /// The offset to the content in the start section.
+    ///
+    /// Offset: +16
     pub offset: u32
 }
 
+impl ObjectHeader
+{
+    /// Attempts to read an object header from an object table.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader`: the object table to read from.
+    ///
+    /// returns: Result<ObjectHeader, Error>
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](crate::error::Error) if the data could not be read
+    /// or a [Truncation](crate::error::Error::Truncation) if the object table
+    /// ends in the middle of the header.
+    pub fn read<TReader: io::Read + ?Sized>(reader: &mut TReader) -> Result<ObjectHeader>
+    {
+        let mut buf: [u8; OBJECT_ENTRY_SIZE] = [0; OBJECT_ENTRY_SIZE];
+        if read_fill(reader, &mut buf)? != OBJECT_ENTRY_SIZE {
+            return Err(Error::Truncation("read object table"));
+        }
+        return Ok(ObjectHeader::from_bytes(&buf));
+    }
+
+    /// Decodes an object header from its on-disk representation.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf`: the raw bytes of the header.
+    ///
+    /// returns: ObjectHeader
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::package::object::ObjectHeader;
+    ///
+    /// let header = ObjectHeader { size: 42, name: 1, start: 2, offset: 3 };
+    /// assert_eq!(ObjectHeader::from_bytes(&header.to_bytes()), header);
+    /// ```
+    pub fn from_bytes(buf: &[u8; OBJECT_ENTRY_SIZE]) -> ObjectHeader
+    {
+        return ObjectHeader {
+            size: LittleEndian::read_u64(&buf[0..8]),
+            name: LittleEndian::read_u32(&buf[8..12]),
+            start: LittleEndian::read_u32(&buf[12..16]),
+            offset: LittleEndian::read_u32(&buf[16..20])
+        };
+    }
+
+    /// Encodes this header to its on-disk representation.
+    pub fn to_bytes(self) -> [u8; OBJECT_ENTRY_SIZE]
+    {
+        let mut buf: [u8; OBJECT_ENTRY_SIZE] = [0; OBJECT_ENTRY_SIZE];
+        LittleEndian::write_u64(&mut buf[0..8], self.size);
+        LittleEndian::write_u32(&mut buf[8..12], self.name);
+        LittleEndian::write_u32(&mut buf[12..16], self.start);
+        LittleEndian::write_u32(&mut buf[16..20], self.offset);
+        return buf;
+    }
+
+    /// Attempts to write this header to an object table.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer`: the object table to write to.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// Returns an [Error](std::io::Error) if the data could not be written.
+    pub fn write<TWriter: io::Write + ?Sized>(&self, writer: &mut TWriter) -> io::Result<()>
+    {
+        return writer.write_all(&self.to_bytes());
+    }
+}
+
 pub struct ObjectTable
 {
     list: Vec<ObjectHeader>,
@@ -88,7 +183,7 @@ impl ObjectTable
     /// ```
     /// //TODO: Implement
     /// ```
-    pub fn build_lookup_table<TBackend: IoBackend>(&mut self, package: &mut PackageDecoder<TBackend>) -> Result<()>
+    pub fn build_lookup_table<TInterface: Interface>(&mut self, package: &mut PackageDecoder<TInterface>) -> Result<()>
     {
         let mut map = HashMap::new();
         for v in &self.list {
@@ -112,19 +207,28 @@ impl ObjectTable
     ///
     /// * `name`: the name of the object to search for.
     ///
-    /// returns: Option<&ObjectHeader>
+    /// returns: Result<Option<&ObjectHeader>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the lookup table
+    /// has not yet been built with [build_lookup_table](ObjectTable::build_lookup_table).
     ///
     /// # Examples
     ///
     /// ```
-    /// //TODO: Implement
+    /// use bpx::variant::package::object::ObjectTable;
+    ///
+    /// let table = ObjectTable::new(Vec::new());
+    /// assert!(table.find_object("test").is_err());
     /// ```
-    pub fn find_object(&self, name: &str) -> Option<&ObjectHeader>
+    pub fn find_object(&self, name: &str) -> Result<Option<&ObjectHeader>>
     {
         if let Some(map) = &self.map {
-            return map.get(name);
-        } else {
-            panic!("ObjectTable lookup table has not yet been initialized, please call build_lookup_table");
+            return Ok(map.get(name));
         }
+        return Err(Error::Other(String::from(
+            "ObjectTable lookup table has not yet been initialized, please call build_lookup_table"
+        )));
     }
 }
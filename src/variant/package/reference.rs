@@ -0,0 +1,168 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Sections of a BPX Package stored in another package.
+//!
+//! An overlay package can refer to the data sections of a base package instead
+//! of duplicating them: each [SECTION_TYPE_REFERENCE](crate::variant::package::SECTION_TYPE_REFERENCE)
+//! section of the overlay stands for one section of the base and holds the
+//! build identifier of the base (see [set_build_id](crate::encoder::Encoder::set_build_id)),
+//! the index of the section in the base and the CRC32 of its content. Objects
+//! stored in referenced sections are read through a [Resolver] opening the base.
+
+use std::io::{Read, SeekFrom};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    compression::{Checksum, Crc32Checksum},
+    decoder::{Decoder, IoBackend},
+    error::section_context,
+    format::package::REFERENCE_SIZE,
+    Interface,
+    Result,
+    SectionHandle
+};
+
+const CHECKSUM_BUFFER_SIZE: usize = 8192;
+
+/// A section of a base package referenced by an overlay package.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BaseSection
+{
+    /// The index of the section in the base package.
+    pub index: u32,
+
+    /// The CRC32 of the content of the section, checked when it is resolved.
+    pub checksum: u32
+}
+
+impl BaseSection
+{
+    /// Describes a section of a base package to reference it from an overlay.
+    ///
+    /// # Arguments
+    ///
+    /// * `base`: the base package.
+    /// * `handle`: a handle to the section to reference.
+    ///
+    /// returns: Result<BaseSection, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the section could not be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::builder::SectionHeaderBuilder;
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::package::reference::BaseSection;
+    /// use bpx::Interface;
+    ///
+    /// let mut base = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let handle = base.create_section(SectionHeaderBuilder::new().build()).unwrap();
+    /// base.open_section(handle).unwrap().write_all(b"shared").unwrap();
+    /// let section = BaseSection::of(&mut base, handle).unwrap();
+    /// assert_eq!(section.index, 0);
+    /// ```
+    pub fn of<TInterface: Interface>(base: &mut TInterface, handle: SectionHandle) -> Result<BaseSection>
+    {
+        return Ok(BaseSection {
+            index: base.get_section_index(handle),
+            checksum: section_checksum(base, handle)?
+        });
+    }
+}
+
+/// Opens the base packages referenced by an overlay package.
+///
+/// *Any `FnMut(u64) -> Result<Option<Decoder<Box<dyn IoBackend>>>>` is a resolver.*
+pub trait Resolver
+{
+    /// Opens the base package with the given build identifier.
+    ///
+    /// # Arguments
+    ///
+    /// * `base`: the build identifier of the base package.
+    ///
+    /// returns: Result<Option<Decoder<Box<dyn IoBackend>>>, Error>; None if the base is not available.
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the base package exists but could not be opened.
+    fn open(&mut self, base: u64) -> Result<Option<Decoder<Box<dyn IoBackend>>>>;
+}
+
+impl<F: FnMut(u64) -> Result<Option<Decoder<Box<dyn IoBackend>>>>> Resolver for F
+{
+    fn open(&mut self, base: u64) -> Result<Option<Decoder<Box<dyn IoBackend>>>>
+    {
+        return self(base);
+    }
+}
+
+pub(crate) fn write_reference(base: u64, section: BaseSection) -> [u8; REFERENCE_SIZE]
+{
+    let mut buf: [u8; REFERENCE_SIZE] = [0; REFERENCE_SIZE];
+    LittleEndian::write_u64(&mut buf[0..8], base);
+    LittleEndian::write_u32(&mut buf[8..12], section.index);
+    LittleEndian::write_u32(&mut buf[12..16], section.checksum);
+    return buf;
+}
+
+pub(crate) fn read_reference<TInterface: Interface>(
+    interface: &mut TInterface,
+    handle: SectionHandle
+) -> Result<(u64, BaseSection)>
+{
+    let mut buf: [u8; REFERENCE_SIZE] = [0; REFERENCE_SIZE];
+    interface.read_section_range(handle, 0, &mut buf)?;
+    let section = BaseSection {
+        index: LittleEndian::read_u32(&buf[8..12]),
+        checksum: LittleEndian::read_u32(&buf[12..16])
+    };
+    return Ok((LittleEndian::read_u64(&buf[0..8]), section));
+}
+
+pub(crate) fn section_checksum<TInterface: Interface>(interface: &mut TInterface, handle: SectionHandle) -> Result<u32>
+{
+    let mut chksum = Crc32Checksum::new();
+    let mut buf: [u8; CHECKSUM_BUFFER_SIZE] = [0; CHECKSUM_BUFFER_SIZE];
+    let context = section_context(interface, handle);
+    let mut data = interface.open_section(handle)?;
+    data.seek(SeekFrom::Start(0)).map_err(|e| context(e.into()))?;
+    loop {
+        let len = data.read(&mut buf).map_err(|e| context(e.into()))?;
+        if len == 0 {
+            break;
+        }
+        chksum.push(&buf[0..len]);
+    }
+    return Ok(chksum.finish());
+}
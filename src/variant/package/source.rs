@@ -0,0 +1,213 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Sources of the objects packed in a BPXP.
+//!
+//! *A [PackSource](self::PackSource) provides the content of a single object
+//! and a [PackSourceTree](self::PackSourceTree) the content of a directory,
+//! which allows packing objects that do not come from the file system, such
+//! as files inside other archives or generated content. [PathSource](self::PathSource)
+//! and [PathTree](self::PathTree) read from the file system.*
+
+use std::{
+    fs::{metadata, read_dir, File},
+    io::Read,
+    path::{Path, PathBuf}
+};
+
+use crate::{
+    strings::{get_name_from_dir_entry, get_name_from_path},
+    Result
+};
+
+/// The source of an object to pack.
+pub trait PackSource
+{
+    /// Returns the name of the object.
+    ///
+    /// *Inside a [PackSourceTree](self::PackSourceTree) this is the name of
+    /// the object relative to its directory.*
+    fn name(&self) -> &str;
+
+    /// Returns the number of bytes the object is expected to contain.
+    fn size(&self) -> u64;
+
+    /// Opens the content of the object.
+    ///
+    /// returns: Result<Box<dyn Read>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the content could not be opened.
+    fn open(&mut self) -> Result<Box<dyn Read + '_>>;
+}
+
+/// An entry of a [PackSourceTree](self::PackSourceTree).
+pub enum PackEntry<'a>
+{
+    /// An object.
+    Source(Box<dyn PackSource + 'a>),
+
+    /// A sub-directory.
+    Tree(Box<dyn PackSourceTree + 'a>)
+}
+
+/// A directory of objects to pack.
+pub trait PackSourceTree
+{
+    /// Returns the name of the directory.
+    ///
+    /// *Inside another tree this is the name of the directory relative to its parent.*
+    fn name(&self) -> &str;
+
+    /// Lists the entries of the directory.
+    ///
+    /// returns: Result<Vec<PackEntry>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the directory could not be listed.
+    fn entries(&mut self) -> Result<Vec<PackEntry<'_>>>;
+}
+
+/// A [PackSource](self::PackSource) reading a file.
+pub struct PathSource
+{
+    name: String,
+    path: PathBuf,
+    size: u64
+}
+
+impl PathSource
+{
+    /// Creates a source reading a file, named after the file name.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: the [Path](std::path::Path) of the file.
+    ///
+    /// returns: Result<PathSource, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the file name is not
+    /// unicode or if the file size could not be read.
+    pub fn new(path: &Path) -> Result<PathSource>
+    {
+        return PathSource::with_name(&get_name_from_path(path)?, path);
+    }
+
+    /// Creates a source reading a file with the given name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the name of the object.
+    /// * `path`: the [Path](std::path::Path) of the file.
+    ///
+    /// returns: Result<PathSource, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the file size could not be read.
+    pub fn with_name(name: &str, path: &Path) -> Result<PathSource>
+    {
+        return Ok(PathSource {
+            name: String::from(name),
+            path: path.to_path_buf(),
+            size: metadata(path)?.len()
+        });
+    }
+}
+
+impl PackSource for PathSource
+{
+    fn name(&self) -> &str
+    {
+        return &self.name;
+    }
+
+    fn size(&self) -> u64
+    {
+        return self.size;
+    }
+
+    fn open(&mut self) -> Result<Box<dyn Read + '_>>
+    {
+        return Ok(Box::new(File::open(&self.path)?));
+    }
+}
+
+/// A [PackSourceTree](self::PackSourceTree) reading a directory recursively.
+pub struct PathTree
+{
+    name: String,
+    path: PathBuf
+}
+
+impl PathTree
+{
+    /// Creates a tree reading a directory with the given name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the name of the directory.
+    /// * `path`: the [Path](std::path::Path) of the directory.
+    ///
+    /// returns: PathTree
+    pub fn with_name(name: &str, path: &Path) -> PathTree
+    {
+        return PathTree {
+            name: String::from(name),
+            path: path.to_path_buf()
+        };
+    }
+}
+
+impl PackSourceTree for PathTree
+{
+    fn name(&self) -> &str
+    {
+        return &self.name;
+    }
+
+    fn entries(&mut self) -> Result<Vec<PackEntry<'_>>>
+    {
+        let mut entries = Vec::new();
+        for entry in read_dir(&self.path)? {
+            let entry = entry?;
+            let name = get_name_from_dir_entry(&entry)?;
+            let path = entry.path();
+            if metadata(&path)?.is_file() {
+                entries.push(PackEntry::Source(Box::new(PathSource::with_name(&name, &path)?)));
+            } else {
+                entries.push(PackEntry::Tree(Box::new(PathTree::with_name(&name, &path))));
+            }
+        }
+        return Ok(entries);
+    }
+}
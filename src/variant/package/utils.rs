@@ -27,22 +27,31 @@
 // SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 use std::{
-    fs::{metadata, read_dir, File},
+    fs::{metadata, File},
     path::{Path, PathBuf}
 };
 
 use crate::{
     error::Error,
-    strings::{get_name_from_dir_entry, get_name_from_path},
-    variant::package::{object::ObjectHeader, PackageDecoder, PackageEncoder},
-    Result
+    strings::get_name_from_path,
+    variant::package::{
+        object::ObjectHeader,
+        source::{PathSource, PathTree},
+        PackageDecoder,
+        PackageEncoder
+    },
+    Interface,
+    Result,
+    WriteInterface
 };
 
+const MAX_PREALLOC_SIZE: u64 = 200000000;
+
 /// Packs a file or folder in a BPXP with the given virtual name.
 ///
 /// **This function prints some information to standard output as a way
-/// to debug data compression issues unless the `debug-log` feature
-/// is disabled.**
+/// to debug data compression issues when the `debug-log` feature
+/// is enabled.**
 ///
 /// # Arguments
 ///
@@ -55,37 +64,54 @@ use crate::{
 /// # Errors
 ///
 /// An [Error](crate::error::Error) is returned if some objects could not be packed.
-pub fn pack_file_vname<TBackend: crate::encoder::IoBackend>(
-    package: &mut PackageEncoder<TBackend>,
+pub fn pack_file_vname<TInterface: WriteInterface>(
+    package: &mut PackageEncoder<TInterface>,
     vname: &str,
     source: &Path
 ) -> Result<()>
 {
-    let md = metadata(source)?;
-    if md.is_file() {
-        #[cfg(feature = "debug-log")]
-        println!("Writing file {} with {} byte(s)", vname, md.len());
-        let mut fle = File::open(source)?;
-        package.pack_object(&vname, &mut fle)?;
-    } else {
-        let entries = read_dir(source)?;
-        for rentry in entries {
-            let entry = rentry?;
-            let mut s = String::from(vname);
-            s.push('/');
-            s.push_str(&get_name_from_dir_entry(&entry));
-            pack_file_vname(package, &s, &entry.path())?;
-        }
+    return pack_file_vname_with(package, vname, source, PackOptions::default());
+}
+
+/// Packs a file or folder in a BPXP with the given virtual name and [PackOptions](self::PackOptions).
+///
+/// **This function prints some information to standard output as a way
+/// to debug data compression issues when the `debug-log` feature
+/// is enabled.**
+///
+/// # Arguments
+///
+/// * `package`: the BPXP [PackageEncoder](crate::variant::package::PackageEncoder) to use.
+/// * `vname`: the virtual name for the root source path.
+/// * `source`: the source [Path](std::path::Path) to pack.
+/// * `options`: the packing options.
+///
+/// returns: Result<(), Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned if some objects could not be
+/// packed, or [SourceChanged](crate::error::Error::SourceChanged) if
+/// `fail_on_size_change` is set and a file changed size while it was read.
+pub fn pack_file_vname_with<TInterface: WriteInterface>(
+    package: &mut PackageEncoder<TInterface>,
+    vname: &str,
+    source: &Path,
+    options: PackOptions
+) -> Result<()>
+{
+    if metadata(source)?.is_file() {
+        return package.pack_source_with(&mut PathSource::with_name(vname, source)?, options);
     }
-    return Ok(());
+    return package.pack_tree_with(&mut PathTree::with_name(vname, source), options);
 }
 
 /// Packs a file or folder in a BPXP, automatically computing
 /// the virtual name from the source path file name.
 ///
 /// **This function prints some information to standard output as a way
-/// to debug data compression issues unless the `debug-log` feature
-/// is disabled.**
+/// to debug data compression issues when the `debug-log` feature
+/// is enabled.**
 ///
 /// # Arguments
 ///
@@ -97,8 +123,8 @@ pub fn pack_file_vname<TBackend: crate::encoder::IoBackend>(
 /// # Errors
 ///
 /// An [Error](crate::error::Error) is returned if some objects could not be packed.
-pub fn pack_file<TBackend: crate::encoder::IoBackend>(
-    package: &mut PackageEncoder<TBackend>,
+pub fn pack_file<TInterface: WriteInterface>(
+    package: &mut PackageEncoder<TInterface>,
     source: &Path
 ) -> Result<()>
 {
@@ -117,12 +143,13 @@ pub fn pack_file<TBackend: crate::encoder::IoBackend>(
 /// # Errors
 ///
 /// An [Error](crate::error::Error) is returned if the object could not be unpacked.
-pub fn unpack_memory<TBackend: crate::decoder::IoBackend>(
-    package: &mut PackageDecoder<TBackend>,
+pub fn unpack_memory<TInterface: Interface>(
+    package: &mut PackageDecoder<TInterface>,
     obj: &ObjectHeader
 ) -> Result<Vec<u8>>
 {
-    let mut v = Vec::with_capacity(obj.size as usize);
+    // The object size comes from the file, do not trust it for a single allocation.
+    let mut v = Vec::with_capacity(std::cmp::min(obj.size, MAX_PREALLOC_SIZE) as usize);
     let len = package.unpack_object(obj, &mut v)?;
     if len != obj.size {
         return Err(Error::Truncation("object unpack memory"));
@@ -143,8 +170,8 @@ pub fn unpack_memory<TBackend: crate::decoder::IoBackend>(
 /// # Errors
 ///
 /// An [Error](crate::error::Error) is returned if the object could not be unpacked.
-pub fn unpack_file<TBackend: crate::decoder::IoBackend>(
-    package: &mut PackageDecoder<TBackend>,
+pub fn unpack_file<TInterface: Interface>(
+    package: &mut PackageDecoder<TInterface>,
     obj: &ObjectHeader,
     out: &Path
 ) -> Result<File>
@@ -160,8 +187,8 @@ pub fn unpack_file<TBackend: crate::decoder::IoBackend>(
 /// Unpacks a BPXP.
 ///
 /// **This function prints some information to standard output as a way
-/// to debug a broken or incorrectly packed BPXP unless the `debug-log`
-/// feature is disabled.**
+/// to debug a broken or incorrectly packed BPXP when the `debug-log`
+/// feature is enabled.**
 ///
 /// # Arguments
 ///
@@ -173,13 +200,13 @@ pub fn unpack_file<TBackend: crate::decoder::IoBackend>(
 /// # Errors
 ///
 /// An [Error](crate::error::Error) is returned if some objects could not be unpacked.
-pub fn unpack<TBackend: crate::decoder::IoBackend>(package: &mut PackageDecoder<TBackend>, target: &Path)
+pub fn unpack<TInterface: Interface>(package: &mut PackageDecoder<TInterface>, target: &Path)
     -> Result<()>
 {
     let table = package.read_object_table()?;
     for v in table.get_objects() {
         let path = package.get_object_name(v)?;
-        if path == "" {
+        if path.is_empty() {
             return Err(Error::Corruption(String::from(
                 "Empty path string detected, aborting to prevent damage on host files"
             )));
@@ -196,3 +223,38 @@ pub fn unpack<TBackend: crate::decoder::IoBackend>(package: &mut PackageDecoder<
     }
     return Ok(());
 }
+
+/// Options for packing files with
+/// [pack_file_vname_with](crate::variant::package::utils::pack_file_vname_with).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PackOptions
+{
+    /// Fails with [SourceChanged](crate::error::Error::SourceChanged) when a
+    /// file does not have the size reported by its metadata once read, instead
+    /// of recording the bytes which were actually read.
+    pub fail_on_size_change: bool
+}
+
+/// Options for selective extraction with
+/// [extract_matching](crate::variant::package::PackageDecoder::extract_matching) and
+/// [extract_all](crate::variant::package::PackageDecoder::extract_all).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct UnpackOptions
+{
+    /// Leaves files which already exist in the destination untouched instead of overwriting them.
+    pub skip_existing: bool
+}
+
+/// Summary of a selective extraction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnpackReport
+{
+    /// The names of the extracted objects, in extraction order.
+    pub extracted: Vec<String>,
+
+    /// The names of the matching objects which were not extracted because the file already existed.
+    pub skipped: Vec<String>,
+
+    /// The total number of bytes written.
+    pub bytes: u64
+}
@@ -0,0 +1,307 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Export of BPX Packages as zip archives.
+
+use std::io::{Seek, SeekFrom, Write};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    compression::{crc32_update, RawDeflateWriter},
+    error::Error,
+    variant::package::{object::ObjectHeader, PackageDecoder},
+    Interface,
+    Result
+};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+const LOCAL_HEADER_SIZE: usize = 30;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const VERSION_NEEDED: u16 = 20;
+// Names are stored as UTF-8.
+const FLAG_UTF8: u16 = 0x0800;
+// 1980-01-01 00:00, the first date representable by zip.
+const DOS_DATE: u16 = 0x21;
+
+/// The compression method of the entries of an exported zip archive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZipMethod
+{
+    /// Entries are stored uncompressed.
+    Stored,
+
+    /// Entries are compressed with deflate.
+    Deflate
+}
+
+impl ZipMethod
+{
+    fn code(self) -> u16
+    {
+        return match self {
+            ZipMethod::Stored => 0,
+            ZipMethod::Deflate => 8
+        };
+    }
+}
+
+struct Entry
+{
+    name: String,
+    crc: u32,
+    csize: u32,
+    size: u32,
+    offset: u32
+}
+
+struct CrcWriter<TWrite: Write>
+{
+    inner: TWrite,
+    crc: u32,
+    size: u64
+}
+
+impl<TWrite: Write> Write for CrcWriter<TWrite>
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>
+    {
+        let len = self.inner.write(buf)?;
+        self.crc = crc32_update(self.crc, &buf[0..len]);
+        self.size += len as u64;
+        return Ok(len);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()>
+    {
+        return self.inner.flush();
+    }
+}
+
+fn zip32(value: u64, what: &str) -> Result<u32>
+{
+    if value >= u32::MAX as u64 {
+        return Err(Error::Unsupported(format!(
+            "{} ({}) requires zip64 which is not supported",
+            what, value
+        )));
+    }
+    return Ok(value as u32);
+}
+
+fn write_local_header<TWrite: Write>(out: &mut TWrite, entry: &Entry, method: ZipMethod) -> Result<()>
+{
+    let mut buf: [u8; LOCAL_HEADER_SIZE] = [0; LOCAL_HEADER_SIZE];
+    LittleEndian::write_u32(&mut buf[0..4], LOCAL_HEADER_SIGNATURE);
+    LittleEndian::write_u16(&mut buf[4..6], VERSION_NEEDED);
+    LittleEndian::write_u16(&mut buf[6..8], FLAG_UTF8);
+    LittleEndian::write_u16(&mut buf[8..10], method.code());
+    LittleEndian::write_u16(&mut buf[12..14], DOS_DATE);
+    LittleEndian::write_u32(&mut buf[14..18], entry.crc);
+    LittleEndian::write_u32(&mut buf[18..22], entry.csize);
+    LittleEndian::write_u32(&mut buf[22..26], entry.size);
+    LittleEndian::write_u16(&mut buf[26..28], entry.name.len() as u16);
+    out.write_all(&buf)?;
+    out.write_all(entry.name.as_bytes())?;
+    return Ok(());
+}
+
+fn write_central_header<TWrite: Write>(out: &mut TWrite, entry: &Entry, method: ZipMethod) -> Result<()>
+{
+    let mut buf: [u8; CENTRAL_HEADER_SIZE] = [0; CENTRAL_HEADER_SIZE];
+    LittleEndian::write_u32(&mut buf[0..4], CENTRAL_HEADER_SIGNATURE);
+    LittleEndian::write_u16(&mut buf[4..6], VERSION_NEEDED);
+    LittleEndian::write_u16(&mut buf[6..8], VERSION_NEEDED);
+    LittleEndian::write_u16(&mut buf[8..10], FLAG_UTF8);
+    LittleEndian::write_u16(&mut buf[10..12], method.code());
+    LittleEndian::write_u16(&mut buf[14..16], DOS_DATE);
+    LittleEndian::write_u32(&mut buf[16..20], entry.crc);
+    LittleEndian::write_u32(&mut buf[20..24], entry.csize);
+    LittleEndian::write_u32(&mut buf[24..28], entry.size);
+    LittleEndian::write_u16(&mut buf[28..30], entry.name.len() as u16);
+    LittleEndian::write_u32(&mut buf[42..46], entry.offset);
+    out.write_all(&buf)?;
+    out.write_all(entry.name.as_bytes())?;
+    return Ok(());
+}
+
+fn write_entry<TInterface: Interface, TWrite: Write + Seek>(
+    package: &mut PackageDecoder<TInterface>,
+    out: &mut TWrite,
+    obj: &ObjectHeader,
+    name: String,
+    method: ZipMethod
+) -> Result<Entry>
+{
+    if name.len() > u16::MAX as usize {
+        return Err(Error::Unsupported(format!("zip entry name of {} bytes is too long", name.len())));
+    }
+    let start = out.stream_position()?;
+    let mut entry = Entry {
+        name,
+        crc: 0,
+        csize: 0,
+        size: zip32(obj.size, "zip entry size")?,
+        offset: zip32(start, "zip entry offset")?
+    };
+    write_local_header(out, &entry, method)?;
+    let data_start = out.stream_position()?;
+    let (crc, size) = match method {
+        ZipMethod::Stored => {
+            let mut writer = CrcWriter { inner: &mut *out, crc: 0, size: 0 };
+            package.unpack_object(obj, &mut writer)?;
+            (writer.crc, writer.size)
+        },
+        ZipMethod::Deflate => {
+            let mut writer = CrcWriter {
+                inner: RawDeflateWriter::new(&mut *out)?,
+                crc: 0,
+                size: 0
+            };
+            package.unpack_object(obj, &mut writer)?;
+            writer.inner.finish()?;
+            (writer.crc, writer.size)
+        }
+    };
+    if size != obj.size {
+        return Err(Error::Truncation("zip entry"));
+    }
+    let end = out.stream_position()?;
+    entry.crc = crc;
+    entry.csize = zip32(end - data_start, "zip entry compressed size")?;
+    // Patch the local header now that the sizes and the CRC are known.
+    out.seek(SeekFrom::Start(start))?;
+    write_local_header(out, &entry, method)?;
+    out.seek(SeekFrom::Start(end))?;
+    return Ok(entry);
+}
+
+/// Exports all objects of a BPXP as a deflate compressed zip archive.
+///
+/// *See [to_zip_with](self::to_zip_with).*
+///
+/// # Arguments
+///
+/// * `package`: the BPXP [PackageDecoder](crate::variant::package::PackageDecoder) to export.
+/// * `out`: the destination [Write](std::io::Write) + [Seek](std::io::Seek).
+///
+/// returns: Result<(), Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned if an object could not be
+/// unpacked, if the archive could not be written or if it would need zip64.
+pub fn to_zip<TInterface: Interface, TWrite: Write + Seek>(
+    package: &mut PackageDecoder<TInterface>,
+    out: TWrite
+) -> Result<()>
+{
+    return to_zip_with(package, out, ZipMethod::Deflate);
+}
+
+/// Exports all objects of a BPXP as a zip archive.
+///
+/// *Each object is streamed from the package into its zip entry, so memory
+/// use does not depend on the size of the objects. Entries keep the object
+/// names and order; since packages do not store file attributes, entries
+/// have no permissions and are dated 1980-01-01. Archives needing zip64
+/// (entries or archives of 4 GiB or more, more than 65535 entries) are not
+/// supported.*
+///
+/// # Arguments
+///
+/// * `package`: the BPXP [PackageDecoder](crate::variant::package::PackageDecoder) to export.
+/// * `out`: the destination [Write](std::io::Write) + [Seek](std::io::Seek).
+/// * `method`: the [ZipMethod](self::ZipMethod) used for all entries.
+///
+/// returns: Result<(), Error>
+///
+/// # Errors
+///
+/// An [Error](crate::error::Error) is returned if an object could not be
+/// unpacked, if the archive could not be written or if it would need zip64.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use bpx::decoder::Decoder;
+/// use bpx::encoder::Encoder;
+/// use bpx::variant::package::{to_zip_with, PackageBuilder, PackageDecoder, ZipMethod};
+///
+/// let mut buf = Vec::new();
+/// let mut encoder = Encoder::new(&mut buf).unwrap();
+/// let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+/// package.pack_object("hello.txt", &mut b"Hello".as_ref()).unwrap();
+/// encoder.save().unwrap();
+///
+/// let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+/// let mut package = PackageDecoder::read(&mut decoder).unwrap();
+/// let mut zip = Cursor::new(Vec::new());
+/// to_zip_with(&mut package, &mut zip, ZipMethod::Stored).unwrap();
+/// let zip = zip.into_inner();
+/// assert_eq!(&zip[0..4], b"PK\x03\x04");
+/// assert_eq!(&zip[30..44], b"hello.txtHello");
+/// ```
+pub fn to_zip_with<TInterface: Interface, TWrite: Write + Seek>(
+    package: &mut PackageDecoder<TInterface>,
+    mut out: TWrite,
+    method: ZipMethod
+) -> Result<()>
+{
+    let table = package.read_object_table()?;
+    let objects = table.get_objects();
+    if objects.len() > u16::MAX as usize {
+        return Err(Error::Unsupported(format!(
+            "{} zip entries requires zip64 which is not supported",
+            objects.len()
+        )));
+    }
+    let mut entries = Vec::with_capacity(objects.len());
+    for obj in objects {
+        let name = String::from(package.get_object_name(obj)?);
+        entries.push(write_entry(package, &mut out, obj, name, method)?);
+    }
+    let directory_start = out.stream_position()?;
+    for entry in &entries {
+        write_central_header(&mut out, entry, method)?;
+    }
+    let directory_end = out.stream_position()?;
+    let mut buf: [u8; END_OF_CENTRAL_DIRECTORY_SIZE] = [0; END_OF_CENTRAL_DIRECTORY_SIZE];
+    LittleEndian::write_u32(&mut buf[0..4], END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+    LittleEndian::write_u16(&mut buf[8..10], entries.len() as u16);
+    LittleEndian::write_u16(&mut buf[10..12], entries.len() as u16);
+    LittleEndian::write_u32(&mut buf[12..16], zip32(directory_end - directory_start, "zip directory size")?);
+    LittleEndian::write_u32(&mut buf[16..20], zip32(directory_start, "zip directory offset")?);
+    out.write_all(&buf)?;
+    out.flush()?;
+    return Ok(());
+}
@@ -0,0 +1,198 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::{
+    convert::TryFrom,
+    io::{SeekFrom, Write}
+};
+
+use crate::{
+    error::{section_context, Error},
+    variant::{
+        texture::{MipHeader, Texture, TextureDescriptor, SIZE_MIP_ENTRY},
+        VariantDecoder
+    },
+    Interface,
+    Result
+};
+
+const DATA_READ_BUFFER_SIZE: usize = 8192;
+
+/// Represents a BPX Texture decoder.
+pub struct TexturePackDecoder<'a, TInterface: Interface>
+{
+    decoder: &'a mut TInterface,
+    desc: TextureDescriptor,
+    mips: Vec<MipHeader>
+}
+
+impl<'a, TInterface: Interface> TexturePackDecoder<'a, TInterface>
+{
+    /// Creates a new TexturePackDecoder by reading from a BPX decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoder`: the BPX [Decoder](crate::decoder::Decoder) or
+    ///   [Container](crate::container::Container) backend to use.
+    ///
+    /// returns: Result<TexturePackDecoder<TInterface>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the BPX is not a valid
+    /// texture, if the mip table is truncated or if it does not match the
+    /// texture descriptor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::texture::{PixelFormat, TextureBuilder, TexturePackDecoder};
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let mut texture = TextureBuilder::new(PixelFormat::Rg8, 1, 1).build(&mut encoder).unwrap();
+    /// texture.add_mip(0, &[1, 2]).unwrap();
+    /// let mut texture = TexturePackDecoder::read(&mut encoder).unwrap();
+    /// assert_eq!(texture.mip_count(), 1);
+    /// let mut pixels = Vec::new();
+    /// texture.read_mip(0, &mut pixels).unwrap();
+    /// assert_eq!(pixels, [1, 2]);
+    /// ```
+    pub fn read(decoder: &mut TInterface) -> Result<TexturePackDecoder<'_, TInterface>>
+    {
+        let (decoder, desc, required) = VariantDecoder::<Texture, _>::read(decoder)?.into_parts();
+        let mip_table = required[0];
+        let context = section_context(decoder, mip_table);
+        let mut mips = Vec::new();
+        {
+            let mut data = decoder.open_section(mip_table)?;
+            if data.size() % SIZE_MIP_ENTRY != 0 {
+                return Err(context(Error::Truncation("read mip table")));
+            }
+            let count = data.size() / SIZE_MIP_ENTRY;
+            data.seek(SeekFrom::Start(0)).map_err(|e| context(e.into()))?;
+            for _ in 0..count {
+                let mut buf: [u8; SIZE_MIP_ENTRY] = [0; SIZE_MIP_ENTRY];
+                data.read_exact(&mut buf)
+                    .map_err(|_| context(Error::Truncation("read mip table")))?;
+                let mip = MipHeader::from_bytes(&buf);
+                let expected = match u8::try_from(mip.level).ok().and_then(|v| desc.mip_size(v)) {
+                    Some(v) => v,
+                    None => {
+                        return Err(context(Error::Corruption(format!(
+                            "Mip level {} does not exist",
+                            mip.level
+                        ))))
+                    },
+                };
+                if mip.size != expected {
+                    return Err(context(Error::Corruption(format!(
+                        "Mip level {} has {} bytes but the descriptor needs {}",
+                        mip.level, mip.size, expected
+                    ))));
+                }
+                mips.push(mip);
+            }
+        }
+        return Ok(TexturePackDecoder { decoder, desc, mips });
+    }
+
+    /// Returns the number of mip levels declared by the texture descriptor.
+    pub fn mip_count(&self) -> u8
+    {
+        return self.desc.mip_count;
+    }
+
+    /// Returns the texture descriptor.
+    pub fn descriptor(&self) -> &TextureDescriptor
+    {
+        return &self.desc;
+    }
+
+    /// Returns the mip table entry of a mip level.
+    /// Returns None if the mip level is not stored in this texture.
+    ///
+    /// # Arguments
+    ///
+    /// * `level`: the mip level.
+    ///
+    /// returns: Option<&MipHeader>
+    pub fn get_mip(&self, level: u8) -> Option<&MipHeader>
+    {
+        return self.mips.iter().find(|v| v.level == level as u32);
+    }
+
+    /// Reads the pixel data of a mip level.
+    /// Returns the number of bytes read.
+    ///
+    /// # Arguments
+    ///
+    /// * `level`: the mip level.
+    /// * `out`: the raw [Write](std::io::Write) to use as destination.
+    ///
+    /// returns: Result<u64, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the mip level is not stored
+    /// in this texture or if the data could not be read.
+    pub fn read_mip<TWrite: Write>(&mut self, level: u8, out: &mut TWrite) -> Result<u64>
+    {
+        let mip = match self.get_mip(level) {
+            Some(v) => *v,
+            None => return Err(Error::Corruption(format!("Mip level {} is not stored", level)))
+        };
+        let mut section_id = mip.start;
+        let mut offset = mip.offset as u64;
+        let mut len = mip.size;
+        let mut buf: [u8; DATA_READ_BUFFER_SIZE] = [0; DATA_READ_BUFFER_SIZE];
+
+        while len > 0 {
+            let handle = match self.decoder.find_section_by_index(section_id) {
+                Some(v) => v,
+                None => return Err(Error::Truncation("read mip"))
+            };
+            let context = section_context(self.decoder, handle);
+            let mut data = self.decoder.open_section(handle)?;
+            let remaining = (data.size() as u64).saturating_sub(offset);
+            let mut count = std::cmp::min(remaining, len);
+            data.seek(SeekFrom::Start(offset)).map_err(|e| context(e.into()))?;
+            len -= count;
+            while count > 0 {
+                let s = std::cmp::min(count, DATA_READ_BUFFER_SIZE as u64) as usize;
+                data.read_exact(&mut buf[..s])
+                    .map_err(|_| context(Error::Truncation("read mip")))?;
+                out.write_all(&buf[..s])?;
+                count -= s as u64;
+            }
+            offset = 0;
+            section_id += 1;
+        }
+        return Ok(mip.size);
+    }
+}
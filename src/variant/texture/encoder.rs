@@ -0,0 +1,259 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::io::SeekFrom;
+
+use crate::{
+    builder::SectionHeaderBuilder,
+    error::{section_context, Error},
+    format::texture::DEFAULT_SPLIT_SIZE,
+    header::SectionHeader,
+    variant::{
+        texture::{MipHeader, PixelFormat, Texture, TextureDescriptor, SECTION_TYPE_DATA, SIZE_MIP_ENTRY},
+        VariantEncoder
+    },
+    Result,
+    SectionHandle,
+    WriteInterface
+};
+
+const MAX_DATA_SECTION_SIZE: usize = DEFAULT_SPLIT_SIZE;
+
+/// Utility to easily generate a [TexturePackEncoder](crate::variant::texture::TexturePackEncoder).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureBuilder
+{
+    desc: TextureDescriptor
+}
+
+impl TextureBuilder
+{
+    /// Creates a new BPX Texture builder.
+    ///
+    /// *By default, the texture has a single mip level and a single array layer.*
+    ///
+    /// # Arguments
+    ///
+    /// * `format`: the [PixelFormat](crate::variant::texture::PixelFormat) of the texture.
+    /// * `width`: the width in pixels of the base mip level.
+    /// * `height`: the height in pixels of the base mip level.
+    ///
+    /// returns: TextureBuilder
+    pub fn new(format: PixelFormat, width: u32, height: u32) -> TextureBuilder
+    {
+        return TextureBuilder {
+            desc: TextureDescriptor {
+                format,
+                width,
+                height,
+                mip_count: 1,
+                layers: 1
+            }
+        };
+    }
+
+    /// Defines the number of mip levels of the texture.
+    ///
+    /// # Arguments
+    ///
+    /// * `mip_count`: the number of mip levels.
+    ///
+    /// returns: TextureBuilder
+    pub fn with_mip_count(mut self, mip_count: u8) -> Self
+    {
+        self.desc.mip_count = mip_count;
+        return self;
+    }
+
+    /// Defines the number of array layers of the texture.
+    ///
+    /// # Arguments
+    ///
+    /// * `layers`: the number of array layers.
+    ///
+    /// returns: TextureBuilder
+    pub fn with_layers(mut self, layers: u16) -> Self
+    {
+        self.desc.layers = layers;
+        return self;
+    }
+
+    /// Returns the descriptor of the texture.
+    pub fn get_descriptor(&self) -> &TextureDescriptor
+    {
+        return &self.desc;
+    }
+
+    /// Builds the corresponding [TexturePackEncoder](crate::variant::texture::TexturePackEncoder).
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder`: the BPX [Encoder](crate::encoder::Encoder) or
+    ///   [Container](crate::container::Container) to write to.
+    ///
+    /// returns: Result<TexturePackEncoder<TInterface>, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the descriptor is invalid
+    /// or in case some sections could not be created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::encoder::Encoder;
+    /// use bpx::variant::texture::{PixelFormat, TextureBuilder};
+    ///
+    /// let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    /// let mut texture = TextureBuilder::new(PixelFormat::R8, 2, 2)
+    ///     .with_mip_count(2)
+    ///     .build(&mut encoder)
+    ///     .unwrap();
+    /// texture.add_mip(0, &[1, 2, 3, 4]).unwrap();
+    /// texture.add_mip(1, &[5]).unwrap();
+    /// assert!(texture.add_mip(1, &[5]).is_err());
+    /// ```
+    pub fn build<TInterface: WriteInterface>(
+        self,
+        encoder: &mut TInterface
+    ) -> Result<TexturePackEncoder<'_, TInterface>>
+    {
+        self.desc.validate()?;
+        let (encoder, desc, required) = VariantEncoder::<Texture, _>::create(encoder, self.desc)?.into_parts();
+        return Ok(TexturePackEncoder {
+            encoder,
+            desc,
+            mip_table: required[0],
+            last_data_section: None,
+            written: vec![false; desc.mip_count as usize]
+        });
+    }
+}
+
+/// Represents a BPX Texture encoder.
+pub struct TexturePackEncoder<'a, TInterface: WriteInterface>
+{
+    encoder: &'a mut TInterface,
+    desc: TextureDescriptor,
+    mip_table: SectionHandle,
+    last_data_section: Option<SectionHandle>,
+    written: Vec<bool>
+}
+
+fn create_data_section_header() -> SectionHeader
+{
+    return SectionHeaderBuilder::bulk_data().with_type(SECTION_TYPE_DATA).build();
+}
+
+impl<'a, TInterface: WriteInterface> TexturePackEncoder<'a, TInterface>
+{
+    /// Returns the descriptor of the texture.
+    pub fn descriptor(&self) -> &TextureDescriptor
+    {
+        return &self.desc;
+    }
+
+    fn data_section(&mut self) -> Result<SectionHandle>
+    {
+        if let Some(handle) = self.last_data_section {
+            if self.encoder.open_section(handle)?.size() < MAX_DATA_SECTION_SIZE {
+                return Ok(handle);
+            }
+        }
+        let handle = self.encoder.create_section(create_data_section_header())?;
+        self.last_data_section = Some(handle);
+        return Ok(handle);
+    }
+
+    /// Stores a mip level in this BPXT.
+    ///
+    /// *Large mip levels are split across several data sections.*
+    ///
+    /// # Arguments
+    ///
+    /// * `level`: the mip level.
+    /// * `data`: the pixel data of the mip level, all array layers included.
+    ///
+    /// returns: Result<(), Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the level does not exist or
+    /// was already added, if the size of the data does not match the texture
+    /// descriptor or if the data could not be written.
+    pub fn add_mip(&mut self, level: u8, data: &[u8]) -> Result<()>
+    {
+        let expected = match self.desc.mip_size(level) {
+            Some(v) => v,
+            None => return Err(Error::Corruption(format!("Mip level {} does not exist", level)))
+        };
+        if self.written[level as usize] {
+            return Err(Error::Corruption(format!("Mip level {} was already added", level)));
+        }
+        if data.len() as u64 != expected {
+            return Err(Error::Corruption(format!(
+                "Mip level {} needs {} bytes but {} were given",
+                level,
+                expected,
+                data.len()
+            )));
+        }
+        let mut section = self.data_section()?;
+        let start = self.encoder.get_section_index(section);
+        let offset = self.encoder.open_section(section)?.size() as u32;
+        let mut remaining = data;
+        loop {
+            let context = section_context(self.encoder, section);
+            let mut out = self.encoder.open_section(section)?;
+            //Split sections (this is to avoid reaching the 4Gb max)
+            let len = std::cmp::min(remaining.len(), MAX_DATA_SECTION_SIZE.saturating_sub(out.size()));
+            out.seek(SeekFrom::End(0)).map_err(|e| context(e.into()))?;
+            out.write_all(&remaining[..len]).map_err(|e| context(e.into()))?;
+            remaining = &remaining[len..];
+            drop(out);
+            if remaining.is_empty() {
+                break;
+            }
+            section = self.encoder.create_section(create_data_section_header())?;
+            self.last_data_section = Some(section);
+        }
+        let header = MipHeader {
+            size: expected,
+            level: level as u32,
+            start,
+            offset
+        };
+        let context = section_context(self.encoder, self.mip_table);
+        let mut mip_table = self.encoder.open_section(self.mip_table)?;
+        mip_table.seek(SeekFrom::End(0)).map_err(|e| context(e.into()))?;
+        let buf: [u8; SIZE_MIP_ENTRY] = header.to_bytes();
+        mip_table.write_all(&buf).map_err(|e| context(e.into()))?;
+        self.written[level as usize] = true;
+        return Ok(());
+    }
+}
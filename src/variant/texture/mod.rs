@@ -0,0 +1,392 @@
+// Copyright (c) 2021, BlockProject 3D
+//
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+//
+//     * Redistributions of source code must retain the above copyright notice,
+//       this list of conditions and the following disclaimer.
+//     * Redistributions in binary form must reproduce the above copyright notice,
+//       this list of conditions and the following disclaimer in the documentation
+//       and/or other materials provided with the distribution.
+//     * Neither the name of BlockProject 3D nor the names of its contributors
+//       may be used to endorse or promote products derived from this software
+//       without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT OWNER OR
+// CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL,
+// EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO,
+// PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR
+// PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF
+// LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING
+// NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE OF THIS
+// SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! An implementation of a BPX type T (Texture) variant.
+//!
+//! A BPXT stores the mip levels of a texture as raw pixel data in data sections
+//! and a mip table describing where each level starts. The texture descriptor
+//! (pixel format, dimensions, mip count and array layers) is stored in the
+//! Extended Type Information block of the BPX Main Header.
+
+mod decoder;
+mod encoder;
+
+use std::ops::RangeInclusive;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{
+    builder::SectionHeaderBuilder,
+    error::Error,
+    header::SectionHeader,
+    variant::{FromTypeExt, ToTypeExt, Variant},
+    Result
+};
+
+pub use decoder::TexturePackDecoder;
+pub use encoder::TextureBuilder;
+pub use encoder::TexturePackEncoder;
+
+/// The standard type for a data section in a BPX Texture (type T).
+pub const SECTION_TYPE_DATA: u8 = 0x1;
+
+/// The standard type for the mip table section in a BPX Texture (type T).
+pub const SECTION_TYPE_MIP_TABLE: u8 = 0x2;
+
+/// The supported BPX version for this texture variant decoder/encoder.
+pub const SUPPORTED_VERSION: u32 = 0x2;
+
+/// The BPX versions accepted by the texture variant decoder.
+pub const SUPPORTED_VERSIONS: RangeInclusive<u32> = SUPPORTED_VERSION..=SUPPORTED_VERSION;
+
+/// The size in bytes of an entry of the mip table.
+///
+/// Layout:
+///
+/// * bytes 0-7: size of the mip level in bytes.
+/// * bytes 8-11: mip level.
+/// * bytes 12-15: index of the data section containing the start of the mip level.
+/// * bytes 16-19: offset to the start of the mip level in the data section.
+pub const SIZE_MIP_ENTRY: usize = crate::format::texture::MIP_ENTRY_SIZE;
+
+/// Enum of all supported pixel formats by BPXT.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat
+{
+    /// One 8 bits unsigned normalized channel.
+    R8,
+
+    /// Two 8 bits unsigned normalized channels.
+    Rg8,
+
+    /// Three 8 bits unsigned normalized channels.
+    Rgb8,
+
+    /// Four 8 bits unsigned normalized channels.
+    Rgba8,
+
+    /// Four 8 bits unsigned normalized channels in BGRA order.
+    Bgra8,
+
+    /// Four 16 bits floating point channels.
+    Rgba16F,
+
+    /// Four 32 bits floating point channels.
+    Rgba32F
+}
+
+impl PixelFormat
+{
+    /// Returns the size in bytes of a single pixel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::texture::PixelFormat;
+    ///
+    /// assert_eq!(PixelFormat::Rgba8.bytes_per_pixel(), 4);
+    /// assert_eq!(PixelFormat::Rgba32F.bytes_per_pixel(), 16);
+    /// ```
+    pub fn bytes_per_pixel(self) -> u64
+    {
+        return match self {
+            PixelFormat::R8 => 1,
+            PixelFormat::Rg8 => 2,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8 => 4,
+            PixelFormat::Bgra8 => 4,
+            PixelFormat::Rgba16F => 8,
+            PixelFormat::Rgba32F => 16
+        };
+    }
+
+    fn code(self) -> u8
+    {
+        return match self {
+            PixelFormat::R8 => 0x0,
+            PixelFormat::Rg8 => 0x1,
+            PixelFormat::Rgb8 => 0x2,
+            PixelFormat::Rgba8 => 0x3,
+            PixelFormat::Bgra8 => 0x4,
+            PixelFormat::Rgba16F => 0x5,
+            PixelFormat::Rgba32F => 0x6
+        };
+    }
+
+    fn from_code(code: u8) -> Result<PixelFormat>
+    {
+        return match code {
+            0x0 => Ok(PixelFormat::R8),
+            0x1 => Ok(PixelFormat::Rg8),
+            0x2 => Ok(PixelFormat::Rgb8),
+            0x3 => Ok(PixelFormat::Rgba8),
+            0x4 => Ok(PixelFormat::Bgra8),
+            0x5 => Ok(PixelFormat::Rgba16F),
+            0x6 => Ok(PixelFormat::Rgba32F),
+            _ => Err(Error::Corruption(String::from("Pixel format code does not exist")))
+        };
+    }
+}
+
+/// Typed view of the Extended Type Information field of a BPX Texture (type T).
+///
+/// Layout:
+///
+/// * byte 0: pixel format code.
+/// * bytes 1-4: width in pixels.
+/// * bytes 5-8: height in pixels.
+/// * byte 9: number of mip levels.
+/// * bytes 10-11: number of array layers.
+/// * bytes 12-15: unused (zero).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureDescriptor
+{
+    /// The pixel format.
+    pub format: PixelFormat,
+
+    /// The width in pixels of the base mip level.
+    pub width: u32,
+
+    /// The height in pixels of the base mip level.
+    pub height: u32,
+
+    /// The number of mip levels.
+    pub mip_count: u8,
+
+    /// The number of array layers.
+    pub layers: u16
+}
+
+impl TextureDescriptor
+{
+    /// Returns the maximum number of mip levels allowed by the dimensions of the texture.
+    pub fn max_mip_count(&self) -> u8
+    {
+        let largest = std::cmp::max(self.width, self.height);
+        if largest == 0 {
+            return 0;
+        }
+        return (32 - largest.leading_zeros()) as u8;
+    }
+
+    /// Returns the size in bytes of a mip level, including all array layers.
+    /// Returns None if the level does not exist or if its size overflows u64.
+    ///
+    /// # Arguments
+    ///
+    /// * `level`: the mip level.
+    ///
+    /// returns: Option<u64>
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::texture::{PixelFormat, TextureDescriptor};
+    ///
+    /// let desc = TextureDescriptor {
+    ///     format: PixelFormat::Rgba8,
+    ///     width: 4,
+    ///     height: 2,
+    ///     mip_count: 3,
+    ///     layers: 1
+    /// };
+    /// assert_eq!(desc.mip_size(0), Some(32));
+    /// assert_eq!(desc.mip_size(1), Some(8));
+    /// assert_eq!(desc.mip_size(2), Some(4));
+    /// assert_eq!(desc.mip_size(3), None);
+    /// ```
+    pub fn mip_size(&self, level: u8) -> Option<u64>
+    {
+        if level >= self.mip_count {
+            return None;
+        }
+        let width = std::cmp::max(1, self.width.checked_shr(level as u32).unwrap_or(0)) as u64;
+        let height = std::cmp::max(1, self.height.checked_shr(level as u32).unwrap_or(0)) as u64;
+        return width
+            .checked_mul(height)?
+            .checked_mul(self.format.bytes_per_pixel())?
+            .checked_mul(self.layers as u64);
+    }
+
+    /// Checks that the dimensions, mip count and layer count are consistent.
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if a dimension or the layer
+    /// count is zero, if the mip count is zero or exceeds
+    /// [max_mip_count](Self::max_mip_count) or if the size of the base mip
+    /// level overflows u64.
+    pub fn validate(&self) -> Result<()>
+    {
+        if self.width == 0 || self.height == 0 || self.layers == 0 {
+            return Err(Error::Corruption(String::from("Texture dimensions must not be zero")));
+        }
+        if self.mip_count == 0 || self.mip_count > self.max_mip_count() {
+            return Err(Error::Corruption(format!(
+                "Invalid mip count {} (max is {})",
+                self.mip_count,
+                self.max_mip_count()
+            )));
+        }
+        if self.mip_size(0).is_none() {
+            return Err(Error::Corruption(String::from("Texture base mip level size overflows u64")));
+        }
+        return Ok(());
+    }
+
+    /// Encodes this into a BPX Extended Type Information block.
+    pub fn to_type_ext(&self) -> [u8; 16]
+    {
+        let mut type_ext: [u8; 16] = [0; 16];
+        type_ext[0] = self.format.code();
+        LittleEndian::write_u32(&mut type_ext[1..5], self.width);
+        LittleEndian::write_u32(&mut type_ext[5..9], self.height);
+        type_ext[9] = self.mip_count;
+        LittleEndian::write_u16(&mut type_ext[10..12], self.layers);
+        return type_ext;
+    }
+
+    /// Decodes a BPX Extended Type Information block.
+    ///
+    /// # Arguments
+    ///
+    /// * `type_ext`: the Extended Type Information block to decode.
+    ///
+    /// returns: Result<TextureDescriptor, Error>
+    ///
+    /// # Errors
+    ///
+    /// An [Error](crate::error::Error) is returned if the pixel format code does
+    /// not exist or the descriptor fails [validate](Self::validate).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bpx::variant::texture::{PixelFormat, TextureDescriptor};
+    ///
+    /// let desc = TextureDescriptor {
+    ///     format: PixelFormat::Rg8,
+    ///     width: 256,
+    ///     height: 64,
+    ///     mip_count: 9,
+    ///     layers: 6
+    /// };
+    /// assert_eq!(TextureDescriptor::from_type_ext(&desc.to_type_ext()).unwrap(), desc);
+    /// assert!(TextureDescriptor::from_type_ext(&[0xFF; 16]).is_err());
+    /// ```
+    pub fn from_type_ext(type_ext: &[u8; 16]) -> Result<TextureDescriptor>
+    {
+        let desc = TextureDescriptor {
+            format: PixelFormat::from_code(type_ext[0])?,
+            width: LittleEndian::read_u32(&type_ext[1..5]),
+            height: LittleEndian::read_u32(&type_ext[5..9]),
+            mip_count: type_ext[9],
+            layers: LittleEndian::read_u16(&type_ext[10..12])
+        };
+        desc.validate()?;
+        return Ok(desc);
+    }
+}
+
+impl ToTypeExt for TextureDescriptor
+{
+    fn to_type_ext(&self) -> [u8; 16]
+    {
+        return TextureDescriptor::to_type_ext(self);
+    }
+}
+
+impl FromTypeExt for TextureDescriptor
+{
+    fn from_type_ext(type_ext: &[u8; 16]) -> Result<Self>
+    {
+        return TextureDescriptor::from_type_ext(type_ext);
+    }
+}
+
+/// The [Variant](crate::variant::Variant) description of a BPX Texture (type T).
+pub struct Texture;
+
+impl Variant for Texture
+{
+    const TYPE_BYTE: u8 = b'T';
+    const VERSION: u32 = SUPPORTED_VERSION;
+    const SUPPORTED_VERSIONS: RangeInclusive<u32> = SUPPORTED_VERSIONS;
+    type TypeExt = TextureDescriptor;
+
+    fn required_sections() -> &'static [u8]
+    {
+        return &[SECTION_TYPE_MIP_TABLE];
+    }
+
+    fn required_section_header(btype: u8) -> SectionHeader
+    {
+        // The mip table is a small table of references, stored like strings.
+        return SectionHeaderBuilder::strings().with_type(btype).build();
+    }
+}
+
+/// Represents a mip table entry as read from the texture.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MipHeader
+{
+    /// The size of the mip level.
+    pub size: u64,
+
+    /// The mip level.
+    pub level: u32,
+
+    /// The start section index to the content.
+    pub start: u32,
+
+    /// The offset to the content in the start section.
+    pub offset: u32
+}
+
+impl MipHeader
+{
+    fn to_bytes(self) -> [u8; SIZE_MIP_ENTRY]
+    {
+        let mut buf: [u8; SIZE_MIP_ENTRY] = [0; SIZE_MIP_ENTRY];
+        LittleEndian::write_u64(&mut buf[0..8], self.size);
+        LittleEndian::write_u32(&mut buf[8..12], self.level);
+        LittleEndian::write_u32(&mut buf[12..16], self.start);
+        LittleEndian::write_u32(&mut buf[16..20], self.offset);
+        return buf;
+    }
+
+    fn from_bytes(buf: &[u8; SIZE_MIP_ENTRY]) -> MipHeader
+    {
+        return MipHeader {
+            size: LittleEndian::read_u64(&buf[0..8]),
+            level: LittleEndian::read_u32(&buf[8..12]),
+            start: LittleEndian::read_u32(&buf[12..16]),
+            offset: LittleEndian::read_u32(&buf[16..20])
+        };
+    }
+}
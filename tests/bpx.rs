@@ -1,6 +1,6 @@
 use std::{fs::File, path::Path};
 
-use bpx::{decoder::Decoder, encoder::Encoder, header::BPX_CURRENT_VERSION, Interface};
+use bpx::{decoder::Decoder, encoder::Encoder, header::BPX_CURRENT_VERSION, testing::roundtrip_container, Interface};
 
 #[test]
 fn attempt_write_empty_bpxp()
@@ -37,3 +37,6228 @@ fn sd_api_test()
     assert_eq!(v1u, String::from("test"));
     assert_eq!(v2u, Some(0));
 }
+
+#[test]
+fn section_header_flags_round_trip()
+{
+    use bpx::header::{SectionFlags, SectionHeader, SIZE_SECTION_HEADER};
+
+    let mut header = SectionHeader::new();
+    header.pointer = 0x0102030405060708;
+    header.csize = 42;
+    header.size = 84;
+    header.chksum = 0xDEADBEEF;
+    header.btype = 0xFE;
+    // XZ + CRC32 plus an unknown high bit which must be preserved.
+    header.flags = 0x80 | (SectionFlags::COMPRESS_XZ | SectionFlags::CHECK_CRC32).bits();
+    let mut buf = Vec::new();
+    header.write(&mut buf).unwrap();
+    assert_eq!(buf.len(), SIZE_SECTION_HEADER);
+    let (_, decoded) = SectionHeader::read(&mut buf.as_slice()).unwrap();
+    let mut buf1 = Vec::new();
+    decoded.write(&mut buf1).unwrap();
+    assert_eq!(buf, buf1);
+    assert_eq!(decoded.flags, header.flags);
+    assert!(decoded.is_compressed());
+    assert_eq!(decoded.compression(), Some(bpx::builder::CompressionMethod::Xz));
+    assert_eq!(decoded.checksum(), Some(bpx::builder::Checksum::Crc32));
+}
+
+#[test]
+fn section_header_rejects_xz_and_zlib()
+{
+    use bpx::{
+        error::Error,
+        header::{self, SectionFlags, SectionHeader}
+    };
+
+    let mut header = SectionHeader::new();
+    header.flags = (SectionFlags::COMPRESS_XZ | SectionFlags::COMPRESS_ZLIB).bits();
+    let mut buf = Vec::new();
+    header.write(&mut buf).unwrap();
+    match SectionHeader::read(&mut buf.as_slice()) {
+        Err(Error::Header(header::Error::BadFlags(flags))) => assert_eq!(flags, header.flags),
+        _ => panic!("expected a bad flags error")
+    }
+}
+
+#[test]
+fn builder_compression_replaces_previous()
+{
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        header::SectionFlags
+    };
+
+    let header = SectionHeaderBuilder::new()
+        .with_compression(CompressionMethod::Xz)
+        .with_compression(CompressionMethod::Zlib)
+        .build();
+    assert_eq!(header.flags(), SectionFlags::COMPRESS_ZLIB);
+    assert!(header.flags().validate().is_ok());
+}
+
+fn assert_not_bpx(data: &[u8])
+{
+    use std::io::Cursor;
+
+    use bpx::{error::Error, header};
+
+    match Decoder::new(Cursor::new(data.to_vec())) {
+        Err(e @ Error::Header(header::Error::BadSignature)) => {
+            assert_eq!(e.to_string(), "illegal bytes found (not a BPX file (bad signature))")
+        },
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("non-BPX data was accepted")
+    }
+}
+
+#[test]
+fn decoder_rejects_png()
+{
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    png.resize(64, 0);
+    assert_not_bpx(&png);
+}
+
+#[test]
+fn decoder_rejects_zip()
+{
+    let mut zip = vec![b'P', b'K', 0x03, 0x04];
+    zip.resize(64, 0);
+    assert_not_bpx(&zip);
+}
+
+#[test]
+fn decoder_rejects_empty_file()
+{
+    assert_not_bpx(&[]);
+}
+
+#[test]
+fn decoder_rejects_unknown_version()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        error::Error,
+        header::{self, MainHeader}
+    };
+
+    let mut header = MainHeader::new();
+    header.version = 42;
+    let mut buf = Vec::new();
+    header.write(&mut buf).unwrap();
+    match Decoder::new(Cursor::new(buf)) {
+        Err(e @ Error::Header(header::Error::BadVersion(42))) => {
+            let msg = e.to_string();
+            assert!(msg.starts_with("unsupported operation BPX version 42"));
+            assert!(msg.contains(&MainHeader::KNOWN_VERSIONS.last().unwrap().to_string()));
+        },
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("unknown version was accepted")
+    }
+}
+
+#[test]
+fn main_header_golden_layout()
+{
+    use bpx::header::MainHeader;
+
+    let mut header = MainHeader::new();
+    header.btype = b'P';
+    header.chksum = 0x11223344;
+    header.file_size = 0x0102030405060708;
+    header.section_num = 3;
+    header.version = 2;
+    header.type_ext = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+    let expected: [u8; 40] = [
+        b'B', b'P', b'X', b'P', // signature + type
+        0x44, 0x33, 0x22, 0x11, // chksum
+        0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, // file_size
+        0x03, 0x00, 0x00, 0x00, // section_num
+        0x02, 0x00, 0x00, 0x00, // version
+        1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16 // type_ext
+    ];
+    assert_eq!(header.to_bytes(), expected);
+    let mut buf = Vec::new();
+    header.write(&mut buf).unwrap();
+    assert_eq!(buf, expected);
+    let decoded = MainHeader::from_bytes(&expected).unwrap();
+    assert_eq!(decoded.to_bytes(), expected);
+    let (_, read) = MainHeader::read(&mut expected.as_ref()).unwrap();
+    assert_eq!(read.to_bytes(), expected);
+}
+
+#[test]
+fn section_header_golden_layout()
+{
+    use bpx::header::SectionHeader;
+
+    let mut header = SectionHeader::new();
+    header.pointer = 0x0102030405060708;
+    header.csize = 0x11223344;
+    header.size = 0x55667788;
+    header.chksum = 0x99AABBCC;
+    header.btype = 0xFE;
+    header.flags = 0x06;
+    let expected: [u8; 24] = [
+        0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, // pointer
+        0x44, 0x33, 0x22, 0x11, // csize
+        0x88, 0x77, 0x66, 0x55, // size
+        0xCC, 0xBB, 0xAA, 0x99, // chksum
+        0xFE, 0x06, // type + flags
+        0x00, 0x00 // reserved
+    ];
+    assert_eq!(header.to_bytes(), expected);
+    let mut buf = Vec::new();
+    header.write(&mut buf).unwrap();
+    assert_eq!(buf, expected);
+    let decoded = SectionHeader::from_bytes(&expected).unwrap();
+    assert_eq!(decoded.to_bytes(), expected);
+    let (_, read) = SectionHeader::read(&mut expected.as_ref()).unwrap();
+    assert_eq!(read.to_bytes(), expected);
+
+    // The tag and reserved bytes are kept as read.
+    let mut tagged = expected;
+    tagged[22] = 0x42;
+    tagged[23] = 0x01;
+    let decoded = SectionHeader::from_bytes(&tagged).unwrap();
+    assert_eq!((decoded.user_tag, decoded.reserved), (0x42, 0x01));
+    assert_eq!(decoded.to_bytes(), tagged);
+}
+
+#[test]
+fn package_type_ext_golden_layout()
+{
+    use bpx::variant::package::{Architecture, PackageBuilder, PackageDecoder, PackageTypeExt, Platform};
+
+    let mut decoder = roundtrip_container(|encoder| {
+        PackageBuilder::new()
+            .with_architecture(Architecture::Armv7hl)
+            .with_platform(Platform::Android)
+            .with_type(*b"PK")
+            .build(encoder)
+            .unwrap();
+        // Byte layout produced by the historical hand-packed implementation.
+        let expected: [u8; 16] = [0x3, 0x3, b'P', b'K', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(encoder.get_main_header().type_ext, expected);
+    });
+    let ext = PackageTypeExt::from_type_ext(&decoder.get_main_header().type_ext).unwrap();
+    assert!(matches!(ext.architecture, Architecture::Armv7hl));
+    assert!(matches!(ext.platform, Platform::Android));
+    let package = PackageDecoder::read(&mut decoder).unwrap();
+    assert!(matches!(package.get_architecture(), Architecture::Armv7hl));
+    assert!(matches!(package.get_platform(), Platform::Android));
+    assert_eq!(package.get_variant(), *b"PK");
+}
+
+#[test]
+fn default_matches_new()
+{
+    use bpx::{
+        builder::{MainHeaderBuilder, SectionHeaderBuilder},
+        header::{MainHeader, SectionHeader},
+        sd::{Array, Object},
+        variant::package::{Architecture, PackageBuilder, Platform}
+    };
+
+    assert_eq!(SectionHeaderBuilder::default(), SectionHeaderBuilder::new());
+    assert_eq!(MainHeaderBuilder::default(), MainHeaderBuilder::new());
+    assert_eq!(PackageBuilder::default(), PackageBuilder::new());
+    assert_eq!(SectionHeader::default(), SectionHeader::new());
+    assert_eq!(MainHeader::default(), MainHeader::new());
+    assert_eq!(Object::default(), Object::new());
+    assert_eq!(Array::default(), Array::new());
+    assert_eq!(Architecture::default(), PackageBuilder::new().get_architecture());
+    assert_eq!(Platform::default(), PackageBuilder::new().get_platform());
+}
+
+#[test]
+fn builder_getters_round_trip()
+{
+    use bpx::{
+        builder::{Checksum, CompressionMethod, MainHeaderBuilder, SectionHeaderBuilder},
+        sd::Object,
+        variant::package::{Architecture, PackageBuilder, Platform}
+    };
+
+    let builder = SectionHeaderBuilder::new()
+        .with_size(128)
+        .with_type(7)
+        .with_compression(CompressionMethod::Zlib)
+        .with_threshold(64)
+        .with_checksum(Checksum::Weak);
+    assert_eq!(builder.get_size(), 128);
+    assert_eq!(builder.get_type(), 7);
+    assert_eq!(builder.get_compression(), Some(CompressionMethod::Zlib));
+    assert_eq!(builder.get_threshold(), 64);
+    assert_eq!(builder.get_checksum(), Some(Checksum::Weak));
+    assert_eq!(builder.build().size, 128);
+
+    let builder = MainHeaderBuilder::new()
+        .with_type(b'M')
+        .with_type_ext([2; 16])
+        .with_version(1);
+    assert_eq!(builder.get_type(), b'M');
+    assert_eq!(builder.get_type_ext(), [2; 16]);
+    assert_eq!(builder.get_version(), 1);
+
+    let mut metadata = Object::new();
+    metadata.set("Name", "test".into());
+    let builder = PackageBuilder::new()
+        .with_architecture(Architecture::Aarch64)
+        .with_platform(Platform::Mac)
+        .with_metadata(metadata.clone())
+        .with_type(*b"PK");
+    assert_eq!(builder.get_architecture(), Architecture::Aarch64);
+    assert_eq!(builder.get_platform(), Platform::Mac);
+    assert_eq!(builder.get_metadata(), Some(&metadata));
+    assert_eq!(builder.get_type(), *b"PK");
+}
+
+#[test]
+fn type_ext_range_respects_reserved_bytes()
+{
+    use bpx::{
+        builder::MainHeaderBuilder,
+        header::{TYPE_EXT_RESERVED_PACKAGE, TYPE_EXT_RESERVED_SHADER}
+    };
+
+    let package = MainHeaderBuilder::new().with_type(b'P');
+    assert!(package.with_type_ext_range(TYPE_EXT_RESERVED_PACKAGE.end - 1, &[1]).is_err());
+    let header = package
+        .with_type_ext_range(TYPE_EXT_RESERVED_PACKAGE.end, &[0xEF, 0xBE, 0xAD, 0xDE])
+        .unwrap()
+        .build();
+    assert_eq!(header.type_ext_range(4, 4), Some(&[0xEF, 0xBE, 0xAD, 0xDE][..]));
+    assert_eq!(header.type_ext_range(0, 4), Some(&[0; 4][..]));
+
+    let shader = MainHeaderBuilder::new().with_type(b'S');
+    assert!(shader.with_type_ext_range(8, &[1; 4]).is_err());
+    assert!(shader.with_type_ext_range(TYPE_EXT_RESERVED_SHADER.end, &[1; 4]).is_ok());
+    assert!(shader.with_type_ext_range(usize::MAX, &[1]).is_err());
+
+    // Unknown variants do not reserve anything.
+    let other = MainHeaderBuilder::new().with_type(b'M');
+    assert!(other.with_type_ext_range(0, &[1; 16]).is_ok());
+    assert!(other.with_type_ext_range(1, &[1; 16]).is_err());
+}
+
+#[test]
+fn section_names_round_trip()
+{
+    use bpx::{builder::SectionHeaderBuilder, header::SECTION_TYPE_SD, Interface};
+
+    let mut decoder = roundtrip_container(|encoder| {
+        let header = SectionHeaderBuilder::new().with_type(SECTION_TYPE_SD).build();
+        let metadata = encoder.create_section(header).unwrap();
+        let locale = encoder.create_section(header).unwrap();
+        encoder.open_section(locale).unwrap().write_all(b"locale").unwrap();
+        encoder.name_section(metadata, "metadata").unwrap();
+        encoder.name_section(locale, "locale").unwrap();
+        assert!(encoder.name_section(metadata, "locale").is_err());
+        assert!(encoder.name_section(metadata, "").is_err());
+        // Renaming a section replaces its previous name.
+        encoder.name_section(locale, "locale table").unwrap();
+    });
+    assert_eq!(decoder.get_main_header().section_num, 3);
+    assert!(decoder.find_section_by_name("locale").unwrap().is_none());
+    let locale = decoder.find_section_by_name("locale table").unwrap().unwrap();
+    assert_eq!(decoder.get_section_index(locale), 1);
+    {
+        let mut data = decoder.open_section(locale).unwrap();
+        let mut content = vec![0; data.size()];
+        data.read_exact(&mut content).unwrap();
+        assert_eq!(content, b"locale");
+    }
+    let metadata = decoder.find_section_by_name("metadata").unwrap().unwrap();
+    assert_eq!(decoder.get_section_index(metadata), 0);
+}
+
+#[test]
+fn section_names_ignore_missing_sections()
+{
+    use bpx::{builder::SectionHeaderBuilder, header::SECTION_TYPE_NAMES, Interface};
+
+    // A names table still referencing a section which was dropped from the file.
+    let mut decoder = roundtrip_container(|encoder| {
+        let table = encoder
+            .create_section(SectionHeaderBuilder::new().with_type(SECTION_TYPE_NAMES).build())
+            .unwrap();
+        {
+            let mut data = encoder.open_section(table).unwrap();
+            data.write_all(&[5, 0, 0, 0]).unwrap();
+            data.write_all(b"removed\0").unwrap();
+            data.write_all(&[0, 0, 0, 0]).unwrap();
+            data.write_all(b"table\0").unwrap();
+        }
+    });
+    assert!(decoder.find_section_by_name("removed").unwrap().is_none());
+    assert!(decoder.find_section_by_name("table").unwrap().is_some());
+}
+
+#[test]
+fn module_errors_convert_and_chain()
+{
+    use std::{error::Error as StdError, io};
+
+    use bpx::{
+        error::{CompressionError, Error},
+        header,
+        sd,
+        strings
+    };
+
+    let e: Error = sd::Error::TypeError("uint8", "string").into();
+    assert_eq!(e.to_string(), "incompatible types (expected uint8, got string)");
+    assert!(e.source().unwrap().downcast_ref::<sd::Error>().is_some());
+
+    let e: Error = sd::Error::PropCountExceeded(300).into();
+    assert_eq!(e.to_string(), "BPXSD - too many props (count 300, max is 255)");
+
+    let e: Error = CompressionError::Inflate("LZMA data error").into();
+    assert_eq!(e.to_string(), "inflate error (LZMA data error)");
+    assert!(e.source().unwrap().downcast_ref::<CompressionError>().is_some());
+
+    let e: Error = strings::Error::Truncation("string secton read").into();
+    assert_eq!(
+        e.to_string(),
+        "unexpected EOF while reading string secton read, are you sure the data is not truncated?"
+    );
+    assert!(e.source().unwrap().downcast_ref::<strings::Error>().is_some());
+
+    let e: Error = header::Error::Truncation("read main header").into();
+    assert!(e.source().unwrap().downcast_ref::<header::Error>().is_some());
+
+    let e: Error = io::Error::other("disk on fire").into();
+    assert_eq!(e.to_string(), "io error (disk on fire)");
+    assert!(e.source().unwrap().downcast_ref::<io::Error>().is_some());
+
+    assert!(Error::Truncation("test").source().is_none());
+}
+
+#[test]
+fn sd_errors_are_sd_errors()
+{
+    use std::convert::TryInto;
+
+    use bpx::{
+        error::Error,
+        sd::{self, DebugSymbols, Object, Value}
+    };
+
+    let res: Result<u8, sd::Error> = Value::from("test").try_into();
+    assert!(matches!(res, Err(sd::Error::TypeError("uint8", "string"))));
+    match DebugSymbols::read(&Object::new()) {
+        Err(Error::Sd(sd::Error::MissingProp("__debug__"))) => (),
+        _ => panic!("expected a missing prop error")
+    }
+    match Object::read(&mut [1u8, 0, 0, 0, 0, 0, 0, 0].as_ref()) {
+        Err(Error::Sd(sd::Error::Truncation(_))) => (),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("truncated object was accepted")
+    }
+}
+
+#[test]
+fn section_errors_carry_section_context()
+{
+    use std::{error::Error as StdError, io::Cursor};
+
+    use bpx::{
+        builder::{Checksum, SectionHeaderBuilder},
+        error::Error,
+        Interface
+    };
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+        let handle = encoder
+            .create_section(SectionHeaderBuilder::new().with_type(0x50).with_checksum(Checksum::Weak).build())
+            .unwrap();
+        encoder.open_section(handle).unwrap().write_all(b"hello").unwrap();
+        encoder.save().unwrap();
+    }
+    *buf.last_mut().unwrap() ^= 0xFF;
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let handle = decoder.find_section_by_index(1).unwrap();
+    let err = match decoder.open_section(handle) {
+        Err(e) => e,
+        Ok(_) => panic!("corrupted section was accepted")
+    };
+    assert!(err.to_string().starts_with("section #1 (type 0x50): checksum validation failed"));
+    match &err {
+        Error::Section { index, btype, source } => {
+            assert_eq!(*index, 1);
+            assert_eq!(*btype, 0x50);
+            assert!(matches!(**source, Error::Checksum(_, _)));
+        },
+        _ => panic!("expected a section error")
+    }
+    assert!(matches!(
+        err.source().unwrap().downcast_ref::<Error>(),
+        Some(Error::Checksum(_, _))
+    ));
+    // Context is only attached once, by the innermost section.
+    assert!(matches!(err.with_section(0, 0), Error::Section { index: 1, .. }));
+}
+
+#[cfg(unix)]
+#[test]
+fn non_unicode_path_is_an_error()
+{
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use bpx::{error::Error, strings};
+
+    let path = Path::new(OsStr::from_bytes(b"dir/\xFF\xFE.bin"));
+    match strings::get_name_from_path(path) {
+        Err(Error::Strings(strings::Error::NonUnicodePath)) => (),
+        _ => panic!("expected a non unicode path error")
+    }
+}
+
+#[test]
+fn sd_index_missing_is_null()
+{
+    use bpx::sd::{Array, Object, Value};
+
+    let mut obj = Object::new();
+    obj.set("Test", 12.into());
+    assert_eq!(obj["Test"], Value::from(12));
+    assert_eq!(obj["Missing"], Value::Null);
+    assert_eq!(obj[42u64], Value::Null);
+    let mut arr = Array::new();
+    arr.add(1.into());
+    assert_eq!(arr[0], Value::from(1));
+    assert_eq!(arr[1], Value::Null);
+}
+
+#[test]
+fn sd_rejects_deep_nesting()
+{
+    use bpx::{error::Error, sd, sd::Object};
+
+    // One object holding an array, holding an array, ... far deeper than supported.
+    let mut data = vec![1u8, 0, 0, 0, 0, 0, 0, 0, 0, 0xD];
+    for _ in 0..100000 {
+        data.extend_from_slice(&[1, 0xD]);
+    }
+    data.push(0);
+    match Object::read(&mut data.as_slice()) {
+        Err(Error::Sd(sd::Error::MaxDepthExceeded(_))) => (),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("deeply nested object was accepted")
+    }
+}
+
+#[test]
+fn memory_section_rejects_negative_seek()
+{
+    use std::io::SeekFrom;
+
+    use bpx::{builder::SectionHeaderBuilder, Interface};
+
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    let handle = encoder.create_section(SectionHeaderBuilder::new().with_size(16).build()).unwrap();
+    let mut data = encoder.open_section(handle).unwrap();
+    assert!(data.seek(SeekFrom::Current(-1)).is_err());
+    assert!(data.seek(SeekFrom::End(-1)).is_err());
+    data.write_all(b"abcd").unwrap();
+    assert_eq!(data.seek(SeekFrom::End(-1)).unwrap(), 3);
+}
+
+#[test]
+fn decoder_rejects_huge_section_count()
+{
+    use std::io::Cursor;
+
+    use bpx::{container::Container, decoder::SliceDecoder, error::Error, header::MainHeader};
+
+    // The table is checked against file_size before any section header is read.
+    let mut header = MainHeader::new();
+    header.section_num = u32::MAX;
+    let mut buf = Vec::new();
+    header.write(&mut buf).unwrap();
+    match Decoder::new(Cursor::new(buf.clone())) {
+        Err(Error::Corruption(e)) => assert_eq!(e, "section_num (4294967295) does not fit in file_size (40)"),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("truncated section header table was accepted")
+    }
+    assert!(SliceDecoder::new(&buf).err().unwrap().is_corruption());
+    assert!(Container::open(Cursor::new(buf)).err().unwrap().is_corruption());
+
+    // A file_size large enough for the table still fails on the missing headers.
+    header.file_size = u64::MAX;
+    let mut buf = Vec::new();
+    header.write(&mut buf).unwrap();
+    match Decoder::new(Cursor::new(buf)) {
+        Err(Error::Header(_)) | Err(Error::Io(_)) | Err(Error::Truncation(_)) => (),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("truncated section header table was accepted")
+    }
+}
+
+#[test]
+fn decoder_rejects_truncated_sections()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        error::Error,
+        Interface
+    };
+
+    for compression in [None, Some(CompressionMethod::Xz)] {
+        let mut buf = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut buf).unwrap();
+            let mut builder = SectionHeaderBuilder::new().with_size(200000);
+            if let Some(method) = compression {
+                builder = builder.with_compression(method);
+            }
+            let handle = encoder.create_section(builder.build()).unwrap();
+            {
+                let mut data = encoder.open_section(handle).unwrap();
+                for i in 0..200000u32 {
+                    data.write_all(&[(i % 251) as u8]).unwrap();
+                }
+            }
+            encoder.save().unwrap();
+        }
+        let len = buf.len();
+        buf.truncate(len - 64);
+        let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+        let handle = decoder.find_section_by_index(0).unwrap();
+        match decoder.open_section(handle) {
+            Err(Error::Section { .. }) => (),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("truncated section was accepted")
+        };
+    }
+}
+
+#[test]
+fn package_decoder_rejects_bad_object_headers()
+{
+    use bpx::{
+        error::Error,
+        variant::package::{
+            object::ObjectHeader,
+            utils::unpack_memory,
+            PackageBuilder,
+            PackageDecoder
+        }
+    };
+
+    let mut decoder = roundtrip_container(|encoder| {
+        let mut package = PackageBuilder::new().build(encoder).unwrap();
+        package.pack_object("test", &mut b"content".as_ref()).unwrap();
+    });
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    let mut table = package.read_object_table().unwrap();
+    assert!(table.find_object("test").is_err());
+    table.build_lookup_table(&mut package).unwrap();
+    let obj = *table.find_object("test").unwrap().unwrap();
+    assert_eq!(unpack_memory(&mut package, &obj).unwrap(), b"content");
+
+    let past_end = ObjectHeader { offset: 1000, ..obj };
+    assert!(matches!(unpack_memory(&mut package, &past_end), Err(Error::Corruption(_))));
+    let missing_section = ObjectHeader { start: 1000, ..obj };
+    assert!(matches!(unpack_memory(&mut package, &missing_section), Err(Error::Truncation(_))));
+    let too_large = ObjectHeader { size: u64::MAX, ..obj };
+    assert!(unpack_memory(&mut package, &too_large).is_err());
+}
+
+#[test]
+fn error_predicates_classify_decode_errors()
+{
+    use std::io::{Cursor, ErrorKind};
+
+    use bpx::{builder::MainHeaderBuilder, variant::package::PackageDecoder};
+
+    fn open_package(buf: Vec<u8>) -> std::io::Result<()>
+    {
+        let mut decoder = Decoder::new(Cursor::new(buf))?;
+        PackageDecoder::read(&mut decoder)?;
+        Ok(())
+    }
+
+    let mut bad = vec![0; 40];
+    bad[..3].copy_from_slice(b"BPZ");
+    let err = Decoder::new(Cursor::new(bad)).err().unwrap();
+    assert!(err.is_corruption());
+    assert_eq!(std::io::Error::from(err).kind(), ErrorKind::InvalidData);
+
+    let mut buf = Vec::new();
+    {
+        let header = MainHeaderBuilder::new().with_type(b'P').with_version(1).build();
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        encoder.set_main_header(header);
+        encoder.save().unwrap();
+    }
+    let mut decoder = Decoder::new(Cursor::new(buf.clone())).unwrap();
+    let err = PackageDecoder::read(&mut decoder).err().unwrap();
+    assert!(err.is_unsupported_version());
+    assert!(!err.is_corruption());
+    assert_eq!(open_package(buf).unwrap_err().kind(), ErrorKind::Unsupported);
+}
+
+#[test]
+fn container_round_trip_and_edit()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        container::Container,
+        header::SectionHeader
+    };
+
+    let mut file = Container::create(Cursor::new(Vec::new()));
+    let first = file.sections().create(SectionHeader::new()).unwrap();
+    let big = file
+        .sections()
+        .create(SectionHeaderBuilder::new().with_compression(CompressionMethod::Xz).build())
+        .unwrap();
+    file.sections().open(first).unwrap().write_all(b"first").unwrap();
+    {
+        let mut data = file.sections().open(big).unwrap();
+        for i in 0..200000u32 {
+            data.write_all(&[(i % 251) as u8]).unwrap();
+        }
+    }
+    file.name_section(big, "big").unwrap();
+    file.save().unwrap();
+    let saved = file.into_inner().into_inner();
+
+    // Reading without modifying only rewrites identical headers.
+    let mut file = Container::open(Cursor::new(saved.clone())).unwrap();
+    let handle = file.find_section_by_name("big").unwrap().unwrap();
+    assert_eq!(file.sections().open(handle).unwrap().size(), 200000);
+    file.save().unwrap();
+    assert_eq!(file.into_inner().into_inner(), saved);
+
+    // Removing a section shifts the following sections and their names.
+    let mut file = Container::open(Cursor::new(saved)).unwrap();
+    let handle = file.find_section_by_index(0).unwrap();
+    file.sections().remove(handle).unwrap();
+    let added = file.sections().create(SectionHeader::new()).unwrap();
+    file.sections().open(added).unwrap().write_all(b"added").unwrap();
+    file.save().unwrap();
+    assert_eq!(file.sections().len(), 3);
+
+    let mut decoder = Decoder::new(Cursor::new(file.into_inner().into_inner())).unwrap();
+    let handle = decoder.find_section_by_name("big").unwrap().unwrap();
+    assert_eq!(decoder.get_section_index(handle), 0);
+    let data = decoder.open_section(handle).unwrap().load_in_memory().unwrap();
+    assert_eq!(data.len(), 200000);
+    assert!(data.iter().enumerate().all(|(i, v)| *v == (i % 251) as u8));
+    let handle = decoder.find_section_by_index(2).unwrap();
+    assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), b"added");
+}
+
+#[test]
+fn container_reads_and_writes_packages()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        container::Container,
+        variant::package::{utils::unpack_memory, PackageBuilder, PackageDecoder}
+    };
+
+    let mut file = Container::create(Cursor::new(Vec::new()));
+    {
+        let mut package = PackageBuilder::new().build(&mut file).unwrap();
+        package.pack_object("a", &mut b"first".as_ref()).unwrap();
+    }
+    file.save().unwrap();
+
+    let mut file = Container::open(file.into_inner()).unwrap();
+    let mut package = PackageDecoder::read(&mut file).unwrap();
+    let mut table = package.read_object_table().unwrap();
+    table.build_lookup_table(&mut package).unwrap();
+    let obj = *table.find_object("a").unwrap().unwrap();
+    assert_eq!(unpack_memory(&mut package, &obj).unwrap(), b"first");
+}
+
+#[test]
+fn section_guard_restores_cursor()
+{
+    use std::io::{Cursor, SeekFrom};
+
+    use bpx::{builder::SectionHeaderBuilder, header::SECTION_TYPE_STRING, strings::StringSection, Interface};
+
+    let mut buf = Vec::new();
+    let (first, second) = {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let handle = encoder
+            .create_section(SectionHeaderBuilder::new().with_type(SECTION_TYPE_STRING).build())
+            .unwrap();
+        let mut strings = StringSection::new(handle);
+        let first = strings.put(&mut encoder, "first").unwrap();
+        {
+            // A raw read in the middle of writes does not move the append point.
+            let mut data = encoder.open_section(handle).unwrap();
+            data.seek(SeekFrom::Start(1)).unwrap();
+        }
+        let second = strings.put(&mut encoder, "second").unwrap();
+        encoder.save().unwrap();
+        (first, second)
+    };
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let handle = decoder.find_section_by_type(SECTION_TYPE_STRING).unwrap();
+    let mut strings = StringSection::new(handle);
+    let mut raw = [0; 3];
+    {
+        let mut data = decoder.open_section(handle).unwrap();
+        data.read_exact(&mut raw[0..1]).unwrap();
+    }
+    assert_eq!(strings.get(&mut decoder, second).unwrap(), "second");
+    {
+        // Neither the previous read nor the string lookup moved the cursor.
+        let mut data = decoder.open_section(handle).unwrap();
+        assert_eq!(data.stream_position().unwrap(), 0);
+        data.read_exact(&mut raw).unwrap();
+    }
+    assert_eq!(&raw, b"fir");
+    assert_eq!(strings.get(&mut decoder, first).unwrap(), "first");
+    let mut data = decoder.open_section(handle).unwrap();
+    assert_eq!(data.load_in_memory().unwrap(), b"first\0second\0");
+}
+
+#[test]
+fn concurrent_decoder_reads_from_threads()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        header::SECTION_TYPE_STRING,
+        strings::StringSection,
+        Interface
+    };
+
+    let mut buf = Vec::new();
+    let address = {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        for i in 0..8u8 {
+            let header = SectionHeaderBuilder::new()
+                .with_type(i)
+                .with_compression(CompressionMethod::Xz)
+                .build();
+            let handle = encoder.create_section(header).unwrap();
+            let mut data = encoder.open_section(handle).unwrap();
+            for _ in 0..100000 {
+                data.write_all(&[i]).unwrap();
+            }
+        }
+        let strings = encoder
+            .create_section(SectionHeaderBuilder::new().with_type(SECTION_TYPE_STRING).build())
+            .unwrap();
+        let address = StringSection::new(strings).put(&mut encoder, "texture").unwrap();
+        encoder.save().unwrap();
+        address
+    };
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    // Already loaded sections are carried over.
+    let first = decoder.find_section_by_index(0).unwrap();
+    decoder.open_section(first).unwrap();
+    let decoder = decoder.into_concurrent().unwrap();
+    let strings = decoder.find_section_by_type(SECTION_TYPE_STRING).unwrap();
+
+    std::thread::scope(|s| {
+        for i in 0..8u8 {
+            let decoder = &decoder;
+            s.spawn(move || {
+                let handle = decoder.find_section_by_type(i).unwrap();
+                let data = decoder.load_section(handle).unwrap();
+                assert_eq!(data.len(), 100000);
+                assert!(data.iter().all(|v| *v == i));
+                assert_eq!(decoder.get_string(strings, address).unwrap(), "texture");
+            });
+        }
+    });
+    assert!(decoder.get_string(strings, 1000).is_err());
+}
+
+// Run by CI with --no-default-features to check that no temporary file is needed.
+#[test]
+fn in_memory_round_trip()
+{
+    use std::io::Cursor;
+
+    use bpx::{builder::SectionHeaderBuilder, container::Container, Interface};
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let unsized_section = encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+        let sized = encoder.create_section(SectionHeaderBuilder::new().with_size(16).build()).unwrap();
+        {
+            let mut data = encoder.open_section(unsized_section).unwrap();
+            for i in 0..100000u32 {
+                data.write_all(&i.to_le_bytes()).unwrap();
+            }
+        }
+        // Writing past the preallocated size grows the section.
+        encoder.open_section(sized).unwrap().write_all(&[1; 20]).unwrap();
+        encoder.save().unwrap();
+    }
+    let mut file = Container::open(Cursor::new(buf)).unwrap();
+    let handle = file.find_section_by_index(1).unwrap();
+    file.sections().open(handle).unwrap().write_all(&[2; 4]).unwrap();
+    file.save().unwrap();
+
+    let mut decoder = Decoder::new(Cursor::new(file.into_inner().into_inner())).unwrap();
+    let handle = decoder.find_section_by_index(0).unwrap();
+    let data = decoder.open_section(handle).unwrap().load_in_memory().unwrap();
+    assert_eq!(data.len(), 400000);
+    assert_eq!(&data[399996..], &99999u32.to_le_bytes());
+    let handle = decoder.find_section_by_index(1).unwrap();
+    let data = decoder.open_section(handle).unwrap().load_in_memory().unwrap();
+    assert_eq!(data, [&[2; 4][..], &[1; 16][..]].concat());
+}
+
+#[cfg(feature = "ffi")]
+#[test]
+fn ffi_round_trip()
+{
+    use std::{
+        ffi::{CStr, CString},
+        io::Cursor,
+        ptr
+    };
+
+    use bpx::{ffi::*, variant::package::PackageBuilder};
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(Cursor::new(&mut buf)).unwrap();
+        {
+            let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+            package.pack_object("hello.txt", &mut b"Hello world".as_ref()).unwrap();
+        }
+        encoder.save().unwrap();
+    }
+
+    unsafe {
+        let mut decoder = ptr::null_mut();
+        assert_eq!(bpx_decoder_open_memory(b"nope".as_ptr(), 4, &mut decoder), BPX_ERR_CORRUPTION);
+        assert_eq!(bpx_last_error_code(), BPX_ERR_CORRUPTION);
+        assert!(!CStr::from_ptr(bpx_last_error_message()).to_bytes().is_empty());
+
+        assert_eq!(bpx_decoder_open_memory(buf.as_ptr(), buf.len(), &mut decoder), BPX_OK);
+        let count = bpx_decoder_section_count(decoder);
+        assert!(count >= 2);
+        let mut info = BpxSectionInfo::default();
+        assert_eq!(bpx_decoder_section_info(decoder, count, &mut info), BPX_ERR_INVALID_ARGUMENT);
+        assert_eq!(bpx_decoder_section_info(decoder, 0, &mut info), BPX_OK);
+        let mut written = 0;
+        assert_eq!(
+            bpx_decoder_read_section(decoder, 0, ptr::null_mut(), 0, &mut written),
+            BPX_ERR_BUFFER_TOO_SMALL
+        );
+        assert_eq!(written, info.size as usize);
+        let mut section = vec![0; written];
+        assert_eq!(
+            bpx_decoder_read_section(decoder, 0, section.as_mut_ptr(), section.len(), &mut written),
+            BPX_OK
+        );
+        assert_eq!(written, section.len());
+
+        let mut package = ptr::null_mut();
+        assert_eq!(bpx_package_open(decoder, &mut package), BPX_OK);
+        assert_eq!(bpx_package_entry_count(package), 1);
+        let mut name = ptr::null();
+        let mut size = 0;
+        assert_eq!(bpx_package_entry(package, 0, &mut name, &mut size), BPX_OK);
+        assert_eq!(CStr::from_ptr(name).to_str().unwrap(), "hello.txt");
+        assert_eq!(size, 11);
+        let missing = CString::new("missing").unwrap();
+        let mut out = [0; 32];
+        assert_eq!(
+            bpx_package_extract(package, missing.as_ptr(), out.as_mut_ptr(), out.len(), &mut written),
+            BPX_ERR_NOT_FOUND
+        );
+        assert_eq!(
+            bpx_package_extract(package, name, out.as_mut_ptr(), out.len(), &mut written),
+            BPX_OK
+        );
+        assert_eq!(&out[..written], b"Hello world");
+        bpx_package_free(package);
+    }
+}
+
+#[test]
+fn find_helpers_match_type_lookups()
+{
+    use std::io::Cursor;
+
+    use bpx::{builder::SectionHeaderBuilder, SectionHandle};
+
+    fn indices<T: Interface>(file: &T, handles: Vec<SectionHandle>) -> Vec<u32>
+    {
+        handles.into_iter().map(|v| file.get_section_index(v)).collect()
+    }
+
+    fn check<T: Interface>(file: &T)
+    {
+        for btype in 0..4 {
+            let by_type = file.find_section_by_type(btype).map(|v| file.get_section_index(v));
+            let by_pred = file.find_section(|h| h.btype == btype).map(|v| file.get_section_index(v));
+            assert_eq!(by_type, by_pred);
+            let all = indices(file, file.find_all_sections_of_type(btype));
+            assert_eq!(all, indices(file, file.find_sections(|h| h.btype == btype)));
+            assert_eq!(all, indices(file, file.sections_of_type(btype).collect()));
+        }
+        assert_eq!(indices(file, file.find_sections(|h| h.btype == 2)), [1, 3]);
+        assert_eq!(file.iter_sections().count(), 4);
+    }
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(Cursor::new(&mut buf)).unwrap();
+        for btype in [1, 2, 3, 2] {
+            encoder
+                .create_section(SectionHeaderBuilder::new().with_type(btype).build())
+                .unwrap();
+        }
+        check(&encoder);
+        encoder.save().unwrap();
+    }
+    check(&Decoder::new(Cursor::new(buf)).unwrap());
+}
+
+#[test]
+fn variant_decoder_validates_package()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        builder::{MainHeaderBuilder, SectionHeaderBuilder},
+        header::SECTION_TYPE_STRING,
+        variant::{
+            package::{Package, PackageBuilder, PackageTypeExt, SUPPORTED_VERSION},
+            ToTypeExt,
+            VariantDecoder
+        }
+    };
+
+    let mut encoder = Encoder::new(Cursor::new(Vec::new())).unwrap();
+    PackageBuilder::new().with_type(*b"PK").build(&mut encoder).unwrap();
+    let decoder = VariantDecoder::<Package, _>::read(&mut encoder).unwrap();
+    assert_eq!(decoder.get_type_ext().variant_code, *b"PK");
+    assert_eq!(decoder.get_required_sections().len(), 2);
+
+    // A package without object table is rejected.
+    let mut encoder = Encoder::new(Cursor::new(Vec::new())).unwrap();
+    let type_ext = PackageTypeExt::from_type_ext(&[4; 16]).unwrap();
+    encoder.set_main_header(
+        MainHeaderBuilder::new()
+            .with_type(b'P')
+            .with_version(SUPPORTED_VERSION)
+            .with_type_ext(ToTypeExt::to_type_ext(&type_ext))
+            .build()
+    );
+    encoder
+        .create_section(SectionHeaderBuilder::new().with_type(SECTION_TYPE_STRING).build())
+        .unwrap();
+    let err = VariantDecoder::<Package, _>::read(&mut encoder).err().unwrap();
+    assert!(err.is_corruption());
+
+    // Wrong type byte and unsupported version.
+    encoder.set_main_header(MainHeaderBuilder::new().with_type(b'S').build());
+    assert!(VariantDecoder::<Package, _>::read(&mut encoder).is_err());
+    encoder.set_main_header(MainHeaderBuilder::new().with_type(b'P').with_version(1).build());
+    let err = VariantDecoder::<Package, _>::read(&mut encoder).err().unwrap();
+    assert!(err.is_unsupported_version());
+}
+
+#[test]
+fn inspect_dump_is_stable()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        inspect::{dump, SectionStatus},
+        sd::{Object, Value},
+        variant::package::PackageBuilder
+    };
+
+    let mut metadata = Object::new();
+    metadata.set("name", Value::from("test"));
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(Cursor::new(&mut buf)).unwrap();
+        {
+            let mut package = PackageBuilder::new()
+                .with_type(*b"PK")
+                .with_metadata(metadata)
+                .build(&mut encoder)
+                .unwrap();
+            package.pack_object("a.txt", &mut b"first".as_ref()).unwrap();
+            package.pack_object("b.txt", &mut b"second".as_ref()).unwrap();
+        }
+        encoder.save().unwrap();
+    }
+    let mut decoder = Decoder::new(Cursor::new(buf.clone())).unwrap();
+    let report = dump(&mut decoder).unwrap();
+    assert!(report.is_valid());
+    let expected = "\
+BPX type 'P' (0x50) version 2
+file size: 254 bytes
+header checksum: 0x00000cf9
+type ext: 04 04 50 4b 00 00 00 00 00 00 00 00 00 00 00 00
+sections: 5
+[0] type 0xff flags 0x08 size 12 csize 12 pointer 0xa0 checksum 0x000003df: checksum ok
+    strings: 2
+[1] type 0x02 flags 0x08 size 40 csize 40 pointer 0xac checksum 0x0000001e: checksum ok
+    package entries: 2
+[2] type 0xfe flags 0x08 size 15 csize 15 pointer 0xd4 checksum 0x00000337: checksum ok
+    structured data keys: [0x000000017c9b0c46]
+[3] type 0x03 flags 0x08 size 16 csize 16 pointer 0xe3 checksum 0x0000000d: checksum ok
+    package summary: 2 file(s), 11 byte(s)
+[4] type 0x01 flags 0x04 size 11 csize 11 pointer 0xf3 checksum 0xff12479f: checksum ok
+";
+    assert_eq!(report.to_string(), expected);
+
+    // Corrupt the last byte of the CRC32 protected data section.
+    let data = report.sections.iter().find(|v| v.header.btype == 1).unwrap();
+    let pos = (data.header.pointer + data.header.csize as u64 - 1) as usize;
+    buf[pos] ^= 0xFF;
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let report = dump(&mut decoder).unwrap();
+    assert!(!report.is_valid());
+    let data = report.sections.iter().find(|v| v.header.btype == 1).unwrap();
+    assert!(matches!(data.status, SectionStatus::ChecksumMismatch(_, _)));
+}
+
+#[test]
+fn file_digest_footer()
+{
+    use std::io::Cursor;
+
+    use bpx::{builder::SectionHeaderBuilder, header::SIZE_DIGEST_FOOTER};
+
+    fn encode(digest: bool) -> Vec<u8>
+    {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(Cursor::new(&mut buf)).unwrap();
+        encoder.set_file_digest(digest);
+        let handle = encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(b"digest me").unwrap();
+        encoder.save().unwrap();
+        buf
+    }
+
+    let plain = encode(false);
+    let with_footer = encode(true);
+    assert_eq!(with_footer.len(), plain.len() + SIZE_DIGEST_FOOTER);
+    // The footer is outside file_size: everything before it is unchanged.
+    assert_eq!(&with_footer[..plain.len()], &plain[..]);
+
+    let mut decoder = Decoder::new(Cursor::new(plain)).unwrap();
+    assert!(!decoder.verify_file_digest().unwrap());
+
+    let mut decoder = Decoder::new(Cursor::new(with_footer.clone())).unwrap();
+    assert!(decoder.verify_file_digest().unwrap());
+    let handle = decoder.find_section_by_index(0).unwrap();
+    assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), b"digest me");
+
+    let mut corrupted = with_footer;
+    let pos = corrupted.len() - SIZE_DIGEST_FOOTER - 1;
+    corrupted[pos] ^= 0xFF;
+    let mut decoder = Decoder::new(Cursor::new(corrupted)).unwrap();
+    assert!(decoder.verify_file_digest().unwrap_err().is_checksum_mismatch());
+}
+
+#[test]
+fn patch_round_trip()
+{
+    use std::io::Cursor;
+
+    use bpx::{builder::SectionHeaderBuilder, patch};
+
+    fn encode(sections: &[&[u8]], digest: bool) -> Vec<u8>
+    {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(Cursor::new(&mut buf)).unwrap();
+        encoder.set_file_digest(digest);
+        for data in sections {
+            let handle = encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+            encoder.open_section(handle).unwrap().write_all(data).unwrap();
+        }
+        encoder.save().unwrap();
+        buf
+    }
+
+    fn round_trip(old: &[u8], new: &[u8]) -> patch::PatchStats
+    {
+        let mut old = Decoder::new(Cursor::new(old.to_vec())).unwrap();
+        let mut data = Vec::new();
+        let stats = patch::create(&mut old, &mut Decoder::new(Cursor::new(new.to_vec())).unwrap(), &mut data).unwrap();
+        let mut rebuilt = Vec::new();
+        patch::apply(&mut old, &mut data.as_slice(), &mut rebuilt).unwrap();
+        assert_eq!(rebuilt, new);
+        stats
+    }
+
+    let big = vec![42; 4096];
+    let base = encode(&[&big, b"second", b"third"], false);
+
+    let stats = round_trip(&base, &base);
+    assert_eq!((stats.unchanged, stats.changed, stats.added, stats.removed), (3, 0, 0, 0));
+
+    let stats = round_trip(&base, &encode(&[&big, b"changed", b"third"], false));
+    assert_eq!((stats.unchanged, stats.changed, stats.added, stats.removed), (2, 1, 0, 0));
+    assert!(stats.copied_bytes >= 4096);
+
+    let stats = round_trip(&base, &encode(&[&big, b"second", b"third", b"fourth"], true));
+    assert_eq!((stats.unchanged, stats.changed, stats.added, stats.removed), (3, 0, 1, 0));
+
+    let stats = round_trip(&base, &encode(&[b"second", &big], false));
+    assert_eq!((stats.unchanged, stats.changed, stats.added, stats.removed), (0, 2, 0, 1));
+    assert!(stats.copied_bytes >= 4096);
+
+    // A patch only applies to the file it was created from.
+    let other = encode(&[b"other"], false);
+    let mut data = Vec::new();
+    patch::create(
+        &mut Decoder::new(Cursor::new(base.clone())).unwrap(),
+        &mut Decoder::new(Cursor::new(other.clone())).unwrap(),
+        &mut data
+    )
+    .unwrap();
+    let mut target = Decoder::new(Cursor::new(other)).unwrap();
+    assert!(patch::apply(&mut target, &mut data.as_slice(), &mut Vec::new()).is_err());
+    let mut target = Decoder::new(Cursor::new(base)).unwrap();
+    let last = data.len() - 1;
+    data[last] ^= 0xFF;
+    let err = patch::apply(&mut target, &mut data.as_slice(), &mut Vec::new()).unwrap_err();
+    assert!(err.is_checksum_mismatch());
+}
+
+#[test]
+fn deduplicated_sections_round_trip()
+{
+    use std::io::Cursor;
+
+    use bpx::{builder::SectionHeaderBuilder, inspect::dump, patch};
+
+    fn encode(deduplicate: bool) -> Vec<u8>
+    {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(Cursor::new(&mut buf)).unwrap();
+        encoder.set_deduplicate(deduplicate);
+        for data in [&[7; 1000][..], b"unique", &[7; 1000][..]] {
+            let handle = encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+            encoder.open_section(handle).unwrap().write_all(data).unwrap();
+        }
+        encoder.save().unwrap();
+        buf
+    }
+
+    let plain = encode(false);
+    let deduplicated = encode(true);
+    assert_eq!(plain.len(), deduplicated.len() + 1000);
+
+    let mut decoder = Decoder::new(Cursor::new(deduplicated.clone())).unwrap();
+    let first = decoder.find_section_by_index(0).unwrap();
+    let last = decoder.find_section_by_index(2).unwrap();
+    let a = decoder.open_section(first).unwrap().load_in_memory().unwrap();
+    let b = decoder.open_section(last).unwrap().load_in_memory().unwrap();
+    assert_eq!(a, vec![7; 1000]);
+    assert_eq!(a, b);
+
+    let report = dump(&mut decoder).unwrap();
+    assert!(report.is_valid());
+    let shared: Vec<_> = report.sections.iter().map(|v| v.shared_with).collect();
+    assert_eq!(shared, [None, None, Some(0)]);
+    assert!(report.to_string().contains("shares payload with [0]"));
+
+    // Patches reproduce deduplicated files exactly.
+    let mut old = Decoder::new(Cursor::new(plain)).unwrap();
+    let mut data = Vec::new();
+    patch::create(&mut old, &mut Decoder::new(Cursor::new(deduplicated.clone())).unwrap(), &mut data).unwrap();
+    let mut rebuilt = Vec::new();
+    patch::apply(&mut old, &mut data.as_slice(), &mut rebuilt).unwrap();
+    assert_eq!(rebuilt, deduplicated);
+}
+
+#[test]
+fn texture_round_trip()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        builder::MainHeaderBuilder,
+        variant::texture::{PixelFormat, TextureBuilder, TextureDescriptor, TexturePackDecoder, SUPPORTED_VERSION}
+    };
+
+    let base: Vec<u8> = (0..8 * 4 * 4 * 2).map(|v| v as u8).collect();
+    let mip1 = vec![1; 4 * 2 * 4 * 2];
+    let mip2 = vec![2; 2 * 4 * 2];
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(Cursor::new(&mut buf)).unwrap();
+        {
+            let mut texture = TextureBuilder::new(PixelFormat::Rgba8, 8, 4)
+                .with_mip_count(3)
+                .with_layers(2)
+                .build(&mut encoder)
+                .unwrap();
+            assert!(texture.add_mip(0, &base[1..]).is_err());
+            assert!(texture.add_mip(3, &mip2).is_err());
+            texture.add_mip(2, &mip2).unwrap();
+            texture.add_mip(0, &base).unwrap();
+            texture.add_mip(1, &mip1).unwrap();
+        }
+        encoder.save().unwrap();
+    }
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let mut texture = TexturePackDecoder::read(&mut decoder).unwrap();
+    assert_eq!(texture.mip_count(), 3);
+    assert_eq!(
+        *texture.descriptor(),
+        TextureDescriptor {
+            format: PixelFormat::Rgba8,
+            width: 8,
+            height: 4,
+            mip_count: 3,
+            layers: 2
+        }
+    );
+    for (level, expected) in [(0, &base), (1, &mip1), (2, &mip2)] {
+        let mut pixels = Vec::new();
+        assert_eq!(texture.read_mip(level, &mut pixels).unwrap(), expected.len() as u64);
+        assert_eq!(&pixels, expected);
+    }
+
+    // Invalid descriptors are rejected on both sides.
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    assert!(TextureBuilder::new(PixelFormat::R8, 4, 4)
+        .with_mip_count(4)
+        .build(&mut encoder)
+        .is_err());
+    assert!(TextureBuilder::new(PixelFormat::R8, 0, 4).build(&mut encoder).is_err());
+    encoder.set_main_header(
+        MainHeaderBuilder::new()
+            .with_type(b'T')
+            .with_version(SUPPORTED_VERSION)
+            .build()
+    );
+    assert!(TexturePackDecoder::read(&mut encoder).is_err());
+}
+
+#[test]
+fn header_checksum_wraps()
+{
+    use std::io::Cursor;
+
+    use bpx::header::{MainHeader, SectionHeader};
+
+    // Each header sums to 17 * 255, enough of them overflow a u32.
+    let count = (u32::MAX / (17 * 255)) + 16;
+    let section = SectionHeader {
+        pointer: u64::MAX - u32::MAX as u64,
+        csize: u32::MAX,
+        size: u32::MAX,
+        chksum: u32::MAX,
+        btype: 0xFF,
+        flags: 0,
+        user_tag: 0,
+        reserved: 0
+    };
+    let mut header = MainHeader::new();
+    header.section_num = count;
+    header.file_size = u64::MAX;
+    let mut chksum = header.get_checksum();
+    for _ in 0..count {
+        chksum = chksum.wrapping_add(section.get_checksum());
+    }
+    assert!((header.get_checksum() as u64 + count as u64 * section.get_checksum() as u64) > u32::MAX as u64);
+    header.chksum = chksum;
+    let mut buf = Vec::with_capacity(40 + count as usize * 24);
+    header.write(&mut buf).unwrap();
+    let bytes = section.to_bytes();
+    for _ in 0..count {
+        buf.extend_from_slice(&bytes);
+    }
+    let decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    assert_eq!(decoder.get_main_header().section_num, count);
+}
+
+#[test]
+fn copy_staged_data_short_file()
+{
+    use std::io::{Seek, SeekFrom, Write};
+
+    use bpx::{error::Error, raw::copy_exact};
+
+    let path = std::env::temp_dir().join("bpx_short_staging.tmp");
+    let mut staging = File::options().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+    staging.write_all(&[0xAB; 10000]).unwrap();
+    staging.seek(SeekFrom::Start(0)).unwrap();
+    let mut out = Vec::new();
+    assert!(matches!(
+        copy_exact(&mut staging, &mut out, 20000),
+        Err(Error::Truncation(_))
+    ));
+    assert_eq!(out.len(), 10000);
+    staging.seek(SeekFrom::Start(0)).unwrap();
+    out.clear();
+    copy_exact(&mut staging, &mut out, 4096).unwrap();
+    assert_eq!(out, vec![0xAB; 4096]);
+    drop(staging);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn pooled_sections_do_not_leak_previous_data()
+{
+    use std::io::{Cursor, Write};
+
+    use bpx::{builder::SectionHeaderBuilder, section::BufferPool};
+
+    let pool = BufferPool::new();
+    let mut files = Vec::new();
+    for content in [&b"first section content"[..], &b"second"[..]] {
+        let mut buf = Vec::new();
+        {
+            let mut encoder = Encoder::new(Cursor::new(&mut buf)).unwrap().with_buffer_pool(pool.clone());
+            let handle = encoder
+                .create_section(SectionHeaderBuilder::new().with_size(32).build())
+                .unwrap();
+            encoder.open_section(handle).unwrap().write_all(content).unwrap();
+            encoder.save().unwrap();
+        }
+        assert_eq!(pool.len(), 1);
+        files.push(buf);
+    }
+    let mut decoder = Decoder::new(Cursor::new(files.pop().unwrap())).unwrap();
+    let handle = decoder.find_section_by_index(0).unwrap();
+    let data = decoder.open_section(handle).unwrap().load_in_memory().unwrap();
+    assert_eq!(data, b"second");
+}
+
+#[test]
+fn load_in_memory_returns_whole_section()
+{
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    use bpx::section::new_section_data;
+
+    let content: Vec<u8> = (0..20000u32).map(|v| v as u8).collect();
+    for size in [None, Some(content.len() as u32)] {
+        let mut section = new_section_data(size).unwrap();
+        section.write_all(&content).unwrap();
+        assert_eq!(section.load_in_memory().unwrap().len(), section.size());
+        section.seek(SeekFrom::Start(100)).unwrap();
+        let mut buf = [0; 10];
+        section.read_exact(&mut buf).unwrap();
+        assert_eq!(section.load_in_memory().unwrap(), content);
+        section.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, content[110..120]);
+    }
+}
+
+#[test]
+fn incremental_save_recompresses_dirty_sections_only()
+{
+    use std::{
+        cell::RefCell,
+        io::{Cursor, Read, Seek, SeekFrom, Write},
+        rc::Rc
+    };
+
+    use bpx::{
+        builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+        encoder::SaveStats
+    };
+
+    #[derive(Clone)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer
+    {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>
+        {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()>
+        {
+            Ok(())
+        }
+    }
+
+    let out = SharedBuffer(Rc::new(RefCell::new(Vec::new())));
+    let mut encoder = Encoder::new(out.clone()).unwrap();
+    encoder.set_incremental(true);
+    let handles: Vec<_> = (0..10u8)
+        .map(|i| {
+            let handle = encoder
+                .create_section(
+                    SectionHeaderBuilder::new()
+                        .with_type(i)
+                        .with_compression(CompressionMethod::Xz)
+                        .with_checksum(Checksum::Crc32)
+                        .build()
+                )
+                .unwrap();
+            encoder.open_section(handle).unwrap().write_all(&[i; 4096]).unwrap();
+            handle
+        })
+        .collect();
+    encoder.save().unwrap();
+    let first = out.0.replace(Vec::new());
+    assert_eq!(encoder.get_save_stats(), SaveStats { written: 10, reused: 0, deduplicated: 0, compression_skipped: 0 });
+    let flags = encoder.get_section_header(handles[3]).flags;
+
+    let mut buf = Vec::new();
+    encoder.open_section(handles[5]).unwrap().read_to_end(&mut buf).unwrap();
+    {
+        let mut section = encoder.open_section(handles[3]).unwrap();
+        section.seek(SeekFrom::Start(0)).unwrap();
+        section.write_all(&[0xFF; 8192]).unwrap();
+    }
+    encoder.save().unwrap();
+    let second = out.0.replace(Vec::new());
+    assert_eq!(encoder.get_save_stats(), SaveStats { written: 1, reused: 9, deduplicated: 0, compression_skipped: 0 });
+    assert_eq!(encoder.get_section_header(handles[3]).flags, flags);
+    assert_ne!(first, second);
+
+    let mut decoder = Decoder::new(Cursor::new(second)).unwrap();
+    for (i, handle) in handles.iter().enumerate() {
+        let handle = decoder.find_section_by_index(decoder.get_section_index(*handle)).unwrap();
+        let data = decoder.open_section(handle).unwrap().load_in_memory().unwrap();
+        if i == 3 {
+            assert_eq!(data.len(), 8192);
+            assert!(data.iter().all(|v| *v == 0xFF));
+        } else {
+            assert_eq!(data, vec![i as u8; 4096]);
+        }
+    }
+
+    encoder.save().unwrap();
+    assert_eq!(encoder.get_save_stats().reused, 10);
+}
+
+#[test]
+fn slice_decoder_matches_decoder()
+{
+    use std::io::{Cursor, Seek, SeekFrom, Write};
+
+    use bpx::{
+        builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+        decoder::SliceDecoder,
+        error::Error
+    };
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        for i in 0..8u8 {
+            let mut builder = SectionHeaderBuilder::new().with_type(i).with_checksum(Checksum::Crc32);
+            if i % 2 == 1 {
+                builder = builder.with_compression(CompressionMethod::Xz);
+            }
+            let handle = encoder.create_section(builder.build()).unwrap();
+            encoder.open_section(handle).unwrap().write_all(&[i; 3000]).unwrap();
+        }
+        encoder.save().unwrap();
+    }
+    let mut decoder = Decoder::new(Cursor::new(buf.clone())).unwrap();
+    let mut slice = SliceDecoder::new(&buf).unwrap();
+    for i in 0..8 {
+        let handle = slice.find_section_by_index(i).unwrap();
+        let expected = decoder.open_section(handle).unwrap().load_in_memory().unwrap();
+        assert_eq!(slice.open_section(handle).unwrap().load_in_memory().unwrap(), expected);
+        match slice.section_bytes(handle).unwrap() {
+            Some(bytes) => assert_eq!(bytes, &expected[..]),
+            None => assert_eq!(i % 2, 1)
+        }
+    }
+
+    let handle = slice.find_section_by_index(0).unwrap();
+    {
+        let mut section = slice.open_section(handle).unwrap();
+        section.seek(SeekFrom::Start(2998)).unwrap();
+        section.write_all(b"end").unwrap();
+    }
+    assert_eq!(slice.open_section(handle).unwrap().size(), 3001);
+    assert_eq!(slice.section_bytes(handle).unwrap().unwrap(), &[0; 3000][..]);
+
+    let pointer = slice.get_section_header(handle).pointer as usize;
+    let mut corrupted = buf.clone();
+    corrupted[pointer] = 0xFF;
+    let corrupted = SliceDecoder::new(&corrupted).unwrap();
+    match corrupted.section_bytes(handle) {
+        Err(Error::Section { source, .. }) => assert!(matches!(*source, Error::Checksum(_, _))),
+        _ => panic!("corrupted section should fail checksum verification")
+    }
+}
+
+#[test]
+fn sd_encoding_is_stable()
+{
+    use bpx::sd::{Array, Object, Value};
+
+    let mut arr = Array::new();
+    arr.add(Value::from(0x0102u16));
+    arr.add(Value::from("x"));
+    arr.add(Value::from(Object::new()));
+    let mut obj = Object::new();
+    obj.set("a", Value::from(arr));
+    let mut buf = Vec::new();
+    obj.write(&mut buf).unwrap();
+    assert_eq!(
+        buf,
+        [1, 0x06, 0xB6, 0x02, 0, 0, 0, 0, 0, 0x0D, 3, 0x03, 0x02, 0x01, 0x0C, b'x', 0, 0x0E, 0]
+    );
+}
+
+#[test]
+fn file_section_reads_see_writes_across_pages()
+{
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    use bpx::section::new_section_data;
+
+    let mut section = new_section_data(None).unwrap();
+    let mut expected: Vec<u8> = (0..10000u32).map(|v| (v % 253) as u8).collect();
+    section.write_all(&expected).unwrap();
+    let mut buf = [0; 16];
+    for (pos, patch) in [(4090u64, &b"page boundary"[..]), (100, b"start"), (8190, b"second boundary")] {
+        section.seek(SeekFrom::Start(pos - 10)).unwrap();
+        section.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, expected[pos as usize - 10..pos as usize + 6]);
+        section.seek(SeekFrom::Start(pos)).unwrap();
+        section.write_all(patch).unwrap();
+        expected[pos as usize..pos as usize + patch.len()].copy_from_slice(patch);
+        section.seek(SeekFrom::Start(pos - 10)).unwrap();
+        section.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, expected[pos as usize - 10..pos as usize + 6]);
+    }
+    // Writing past the end fills the gap with zeros, including the cached last page.
+    section.seek(SeekFrom::End(-4)).unwrap();
+    section.read_exact(&mut buf[..4]).unwrap();
+    section.seek(SeekFrom::Start(12000)).unwrap();
+    section.write_all(b"tail").unwrap();
+    expected.resize(12000, 0);
+    expected.extend_from_slice(b"tail");
+    assert_eq!(section.size(), expected.len());
+    section.seek(SeekFrom::Start(0)).unwrap();
+    let mut all = Vec::new();
+    section.read_to_end(&mut all).unwrap();
+    assert_eq!(all, expected);
+    assert_eq!(section.load_in_memory().unwrap(), expected);
+}
+
+#[test]
+fn load_sections_in_parallel()
+{
+    use std::io::{Cursor, Write};
+
+    use bpx::{
+        builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+        error::Error
+    };
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        for i in 0..40u32 {
+            let compression = if i % 3 == 0 { CompressionMethod::Zlib } else { CompressionMethod::Xz };
+            let header = SectionHeaderBuilder::new()
+                .with_compression(compression)
+                .with_checksum(Checksum::Crc32)
+                .build();
+            let handle = encoder.create_section(header).unwrap();
+            let mut section = encoder.open_section(handle).unwrap();
+            for j in 0..500u32 {
+                section.write_all(&(i * j).to_le_bytes()).unwrap();
+            }
+        }
+        encoder.save().unwrap();
+    }
+    let mut serial = Decoder::new(Cursor::new(buf.clone())).unwrap();
+    let mut parallel = Decoder::new(Cursor::new(buf.clone())).unwrap();
+    let handles: Vec<_> = parallel.iter_sections().collect();
+    parallel.load_sections(&handles, 4).unwrap();
+    for handle in &handles {
+        let expected = serial.open_section(*handle).unwrap().load_in_memory().unwrap();
+        assert_eq!(parallel.open_section(*handle).unwrap().load_in_memory().unwrap(), expected);
+    }
+
+    let header = *serial.get_section_header(handles[7]);
+    let mut corrupted = buf;
+    corrupted[header.pointer as usize + header.csize as usize / 2] ^= 0xFF;
+    let mut decoder = Decoder::new(Cursor::new(corrupted)).unwrap();
+    match decoder.load_sections(&handles, 0) {
+        Err(Error::Section { index, .. }) => assert_eq!(index, 7),
+        _ => panic!("corrupted section should fail to load")
+    }
+    let expected = serial.open_section(handles[8]).unwrap().load_in_memory().unwrap();
+    assert_eq!(decoder.open_section(handles[8]).unwrap().load_in_memory().unwrap(), expected);
+}
+
+struct OneByteReader<'a>(&'a [u8]);
+
+impl<'a> std::io::Read for OneByteReader<'a>
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize>
+    {
+        if buf.is_empty() || self.0.is_empty() {
+            return Ok(0);
+        }
+        buf[0] = self.0[0];
+        self.0 = &self.0[1..];
+        Ok(1)
+    }
+}
+
+// A section reporting `extra` more bytes than it holds.
+struct OversizedSection
+{
+    data: std::io::Cursor<Vec<u8>>,
+    extra: usize
+}
+
+impl std::io::Read for OversizedSection
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize>
+    {
+        self.data.read(buf)
+    }
+}
+
+impl std::io::Write for OversizedSection
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>
+    {
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()>
+    {
+        Ok(())
+    }
+}
+
+impl std::io::Seek for OversizedSection
+{
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64>
+    {
+        self.data.seek(pos)
+    }
+}
+
+impl bpx::section::SectionData for OversizedSection
+{
+    fn load_in_memory(&mut self) -> std::io::Result<Vec<u8>>
+    {
+        Ok(self.data.get_ref().clone())
+    }
+
+    fn size(&self) -> usize
+    {
+        self.data.get_ref().len() + self.extra
+    }
+}
+
+#[test]
+fn one_byte_readers_are_read_fully()
+{
+    use bpx::{
+        header::MainHeader,
+        sd::{Object, Value},
+        utils::read_fill
+    };
+
+    let mut obj = Object::new();
+    obj.set("name", Value::from("one byte at a time"));
+    obj.set("value", Value::from(42u64));
+    let mut data = Vec::new();
+    obj.write(&mut data).unwrap();
+    let decoded = Object::read(&mut OneByteReader(&data)).unwrap();
+    assert_eq!(decoded.get("value"), Some(&Value::from(42u64)));
+    assert!(Object::read(&mut OneByteReader(&data[..data.len() - 1])).is_err());
+
+    let mut file = Vec::new();
+    Encoder::new(&mut file).unwrap().save().unwrap();
+    let (_, header) = MainHeader::read(&mut OneByteReader(&file)).unwrap();
+    assert_eq!(header.version, BPX_CURRENT_VERSION);
+
+    let mut buf = [0; 8];
+    assert_eq!(read_fill(&mut OneByteReader(b"abcdefghij"), &mut buf).unwrap(), 8);
+    assert_eq!(read_fill(&mut OneByteReader(b"abc"), &mut buf).unwrap(), 3);
+    assert_eq!(&buf[..3], b"abc");
+}
+
+#[test]
+fn short_sections_are_truncation_errors()
+{
+    use bpx::{error::Error, header::SectionFlags, raw::write_section_to};
+
+    for flags in &[SectionFlags::CHECK_CRC32, SectionFlags::COMPRESS_XZ, SectionFlags::COMPRESS_ZLIB] {
+        let mut section = OversizedSection {
+            data: std::io::Cursor::new(vec![7; 1000]),
+            extra: 100
+        };
+        let mut out = Vec::new();
+        match write_section_to(*flags, &mut section, &mut out) {
+            Err(Error::Truncation(_)) => (),
+            _ => panic!("section shorter than its size should be a truncation error")
+        }
+    }
+}
+
+#[test]
+fn hexdump_snapshot()
+{
+    use std::io::{Seek, SeekFrom, Write};
+
+    use bpx::{header::SectionHeader, section::new_section_data, utils::hexdump};
+
+    let mut section = new_section_data(None).unwrap();
+    let data: Vec<u8> = (0..40u8).map(|v| v.wrapping_mul(7).wrapping_add(0x1C)).collect();
+    section.write_all(&data).unwrap();
+    section.seek(SeekFrom::Start(5)).unwrap();
+    let expected = concat!(
+        "00000000  1c 23 2a 31 38 3f 46 4d  54 5b 62 69 70 77 7e 85  |.#*18?FMT[bipw~.|\n",
+        "00000010  8c 93 9a a1 a8 af b6 bd  c4 cb d2 d9 e0 e7 ee f5  |................|\n",
+        "00000020  fc 03 0a 11 18 1f 26 2d                           |......&-|\n"
+    );
+    assert_eq!(hexdump(section.as_mut(), 0, 256).unwrap(), expected);
+    assert_eq!(section.stream_position().unwrap(), 5);
+    assert_eq!(
+        hexdump(section.as_mut(), 37, 16).unwrap(),
+        "00000025  1f 26 2d                                          |.&-|\n"
+    );
+    assert_eq!(hexdump(section.as_mut(), 40, 16).unwrap(), "");
+    assert_eq!(hexdump(section.as_mut(), 1000, 16).unwrap(), "");
+
+    let mut decoder = roundtrip_container(|encoder| {
+        let handle = encoder.create_section(SectionHeader::new()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&data).unwrap();
+    });
+    let handle = decoder.find_section_by_index(0).unwrap();
+    assert_eq!(decoder.dump_section(handle, 0..u64::MAX).unwrap(), expected);
+    assert_eq!(decoder.dump_section(handle, 16..32).unwrap(), expected.lines().nth(1).unwrap().to_owned() + "\n");
+}
+
+#[test]
+fn option_extension_combinators()
+{
+    use bpx::{error::Error, utils::OptionExtension};
+
+    let mut calls = 0;
+    let mut value: Option<Vec<u8>> = None;
+    value
+        .get_or_insert_with_err(|| -> Result<_, ()> {
+            calls += 1;
+            Ok(vec![1])
+        })
+        .unwrap()
+        .push(2);
+    let res = value.get_or_insert_with_err(|| -> Result<_, ()> {
+        calls += 1;
+        Ok(Vec::new())
+    });
+    assert_eq!(res, Ok(&mut vec![1, 2]));
+    assert_eq!(calls, 1);
+
+    let mut value: Option<u32> = None;
+    assert!(matches!(
+        value.try_get_or_insert_with(|| Err(Error::Truncation("lazy init"))),
+        Err(Error::Truncation("lazy init"))
+    ));
+    assert_eq!(value, None);
+    assert_eq!(*value.try_get_or_insert_with(|| Ok(7)).unwrap(), 7);
+    assert_eq!(*value.try_get_or_insert_with(|| Err(Error::Truncation("unused"))).unwrap(), 7);
+
+    let mut source = vec![3u8, 4];
+    let mut value: Option<u8> = None;
+    assert_eq!(value.get_or_insert_with_data(&mut source, |s| s.pop().ok_or(())), Ok(&mut 4));
+    assert_eq!(value.get_or_insert_with_data(&mut source, |s| s.pop().ok_or(())), Ok(&mut 4));
+    assert_eq!(source, [3]);
+}
+
+#[test]
+fn spooled_decoder_under_and_over_threshold()
+{
+    use std::io::{Read, Write};
+
+    use bpx::{header::SectionHeader, section::Spool};
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let handle = encoder.create_section(SectionHeader::new()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&[0xAB; 5000]).unwrap();
+        encoder.save().unwrap();
+    }
+    for threshold in &[buf.len(), buf.len() - 1, 16, 0] {
+        // A byte-at-a-time reader is neither seekable nor in memory.
+        let mut data = Vec::new();
+        Spool::new(OneByteReader(&buf), *threshold).unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, buf);
+        let in_memory = *threshold >= buf.len() || cfg!(not(feature = "tempfile"));
+        assert_eq!(Spool::new(&buf[..], *threshold).unwrap().is_in_memory(), in_memory);
+        let mut decoder = Decoder::new_spooled(OneByteReader(&buf), *threshold).unwrap();
+        let handle = decoder.find_section_by_index(0).unwrap();
+        assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), vec![0xAB; 5000]);
+    }
+}
+
+type Mutation = fn(&mut Vec<u8>);
+
+fn strict_fixture(mutate: impl Fn(&mut Vec<u8>)) -> Vec<u8>
+{
+    use std::io::Write;
+
+    use bpx::{builder::MainHeaderBuilder, header::SectionHeader};
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        encoder.set_main_header(MainHeaderBuilder::new().with_type(b'P').build());
+        for i in 0..2u8 {
+            let handle = encoder.create_section(SectionHeader::new()).unwrap();
+            encoder.open_section(handle).unwrap().write_all(&[0xA0 + i; 100]).unwrap();
+        }
+        encoder.save().unwrap();
+    }
+    mutate(&mut buf);
+    let section_num = u32::from_le_bytes([buf[16], buf[17], buf[18], buf[19]]) as usize;
+    let end = std::cmp::min(buf.len(), 40 + section_num * 24);
+    let chksum = buf[..end]
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !(4..8).contains(i))
+        .fold(0u32, |acc, (_, v)| acc.wrapping_add(*v as u32));
+    buf[4..8].copy_from_slice(&chksum.to_le_bytes());
+    buf
+}
+
+#[test]
+fn strict_decoder_fixtures()
+{
+    use std::io::Cursor;
+
+    use bpx::error::Error;
+
+    assert!(Decoder::new_strict(Cursor::new(strict_fixture(|_| ()))).is_ok());
+    let fixtures: [(&str, Mutation); 9] = [
+        ("unknown flags", |b| b[40 + 21] |= 0x40),
+        ("nonzero reserved byte", |b| b[40 + 23] = 1),
+        ("differs from its stored size", |b| b[40 + 12] = 50),
+        ("type_ext padding", |b| b[24 + 10] = 1),
+        ("inside the section header table", |b| b[40] = 60),
+        ("does not fit in file_size", |b| b[16..20].copy_from_slice(&1000u32.to_le_bytes())),
+        ("less than the number of section headers", |b| b[16] = 1),
+        ("does not match the end of the section data", |b| b[8] += 1),
+        ("trailing byte(s)", |b| b.push(0))
+    ];
+    for (expected, mutate) in fixtures.iter() {
+        let buf = strict_fixture(mutate);
+        if *expected != "does not fit in file_size" {
+            assert!(Decoder::new(Cursor::new(buf.clone())).is_ok(), "{}", expected);
+        }
+        match Decoder::new_strict(Cursor::new(buf)) {
+            Err(Error::Corruption(msg)) => assert!(msg.contains(expected), "expected {}, got {}", expected, msg),
+            Err(e) => panic!("expected {}, got {}", expected, e),
+            Ok(_) => panic!("expected {}, got no error", expected)
+        }
+    }
+}
+
+#[test]
+fn decoder_checks_section_ranges()
+{
+    use std::io::Cursor;
+
+    use bpx::error::Error;
+
+    let pointer = |b: &[u8], offset: usize| {
+        let mut raw = [0; 8];
+        raw.copy_from_slice(&b[offset..offset + 8]);
+        u64::from_le_bytes(raw)
+    };
+    let fixtures: [(&str, Mutation); 5] = [
+        ("section 1 data", |b| b[64 + 8] = 0xFF),
+        ("section 1 data", |b| b[64 + 12] = 0xFF),
+        ("sections 0 and 1 overlap", |b| b[40 + 12] = 200),
+        ("sections 0 and 1 overlap", |b| {
+            let pointer = u64::from_le_bytes([b[40], b[41], b[42], b[43], b[44], b[45], b[46], b[47]]) + 50;
+            b[64..72].copy_from_slice(&pointer.to_le_bytes());
+        }),
+        ("sections 1 and 0 overlap", |b| {
+            let pointer = u64::from_le_bytes([b[40], b[41], b[42], b[43], b[44], b[45], b[46], b[47]]) - 50;
+            b[64..72].copy_from_slice(&pointer.to_le_bytes());
+        })
+    ];
+    for (expected, mutate) in fixtures.iter() {
+        match Decoder::new(Cursor::new(strict_fixture(mutate))) {
+            Err(Error::Corruption(msg)) => assert!(msg.contains(expected), "expected {}, got {}", expected, msg),
+            Err(e) => panic!("expected {}, got {}", expected, e),
+            Ok(_) => panic!("expected {}, got no error", expected)
+        }
+    }
+
+    // Exact duplicates are what deduplication produces and stay legal.
+    let buf = strict_fixture(|b| {
+        let header = b[40..64].to_vec();
+        b[64..88].copy_from_slice(&header);
+    });
+    assert_eq!(pointer(&buf, 40), pointer(&buf, 64));
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let first = decoder.find_section_by_index(0).unwrap();
+    let second = decoder.find_section_by_index(1).unwrap();
+    let expected = decoder.open_section(first).unwrap().load_in_memory().unwrap();
+    assert_eq!(decoder.open_section(second).unwrap().load_in_memory().unwrap(), expected);
+}
+
+#[test]
+fn fuzz_targets_accept_seeds_and_survive_mutations()
+{
+    use bpx::fuzz::{decode_seeds, fuzz_decode, fuzz_package_entries, fuzz_sd, package_seeds, sd_seeds};
+
+    type Target = fn(&[u8]) -> bpx::Result<()>;
+    type Seeds = Vec<(String, Vec<u8>)>;
+
+    let targets: [(Target, Seeds); 3] = [
+        (fuzz_decode, decode_seeds()),
+        (fuzz_sd, sd_seeds()),
+        (fuzz_package_entries, package_seeds())
+    ];
+    // 3 compressions by 3 checksums, plus an empty file, a file digest and a package.
+    assert_eq!(targets[0].1.len(), 12);
+    for (target, seeds) in targets.iter() {
+        assert!(!seeds.is_empty());
+        for (name, data) in seeds {
+            if let Err(e) = target(data) {
+                panic!("seed {} was rejected: {}", name, e);
+            }
+            // Flip, zero and truncate each byte in turn; any result but a panic is fine.
+            for i in 0..data.len() {
+                for mutation in &[0xFFu8, 0x01] {
+                    let mut mutated = data.clone();
+                    mutated[i] ^= mutation;
+                    let _ = target(&mutated);
+                }
+                let _ = target(&data[..i]);
+            }
+        }
+    }
+}
+
+#[test]
+fn zlib_sections_round_trip()
+{
+    use std::io::Write;
+
+    use bpx::builder::{Checksum, CompressionMethod, SectionHeaderBuilder};
+
+    let sizes = [0usize, 1, 300, 70000, 300000];
+    let content = |size: usize| -> Vec<u8> { (0..size).map(|i| (i % 251) as u8 ^ (i / 4096) as u8).collect() };
+    let mut decoder = roundtrip_container(|encoder| {
+        // Tiny streams are part of what is tested here.
+        encoder.set_min_compress_size(0);
+        for size in &sizes {
+            let mut header = SectionHeaderBuilder::new()
+                .with_compression(CompressionMethod::Zlib)
+                .with_checksum(Checksum::Crc32)
+                .build();
+            header.csize = 0;
+            let handle = encoder.create_section(header).unwrap();
+            encoder.open_section(handle).unwrap().write_all(&content(*size)).unwrap();
+        }
+    });
+    for (i, size) in sizes.iter().enumerate() {
+        let handle = decoder.find_section_by_index(i as u32).unwrap();
+        if *size > 0 {
+            assert!(decoder.get_section_header(handle).flags().compression().is_some());
+        }
+        assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), content(*size));
+    }
+}
+
+#[test]
+fn package_typed_metadata_round_trip()
+{
+    use bpx::{
+        sd::{Object, Value},
+        utils::hash,
+        variant::package::{PackageBuilder, PackageDecoder, PackageMetadata}
+    };
+
+    let mut metadata = PackageMetadata::new("game", "2.1.0").with_current_build_date();
+    metadata.description = Some("A game".into());
+    metadata.custom.set("engine", Value::from("bp3d"));
+    let mut decoder = roundtrip_container(|encoder| {
+        let mut package = PackageBuilder::new()
+            .with_typed_metadata(metadata.clone())
+            .build(encoder)
+            .unwrap();
+        package.pack_object("a", &mut b"data".as_ref()).unwrap();
+    });
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    assert_eq!(package.read_typed_metadata().unwrap(), Some(metadata.clone()));
+    let raw = package.read_metadata().unwrap().unwrap();
+    assert!(raw.raw_get(hash("version")).is_some());
+    assert!(raw.get("author").is_none());
+
+    // Raw objects written by other tools: null optional fields and wrong types.
+    let mut obj = Object::new();
+    obj.set("name", "tool".into());
+    obj.set("version", "1".into());
+    obj.set("author", Value::Null);
+    let read = PackageMetadata::from_object(&obj).unwrap();
+    assert_eq!(read.author, None);
+    assert_eq!(read.custom.prop_count(), 0);
+    obj.set("build_date", "yesterday".into());
+    assert!(PackageMetadata::from_object(&obj).is_err());
+
+    let mut decoder = roundtrip_container(|encoder| {
+        PackageBuilder::new().build(encoder).unwrap();
+    });
+    assert_eq!(PackageDecoder::read(&mut decoder).unwrap().read_typed_metadata().unwrap(), None);
+}
+
+#[test]
+fn architecture_and_platform_code_points()
+{
+    use std::convert::TryFrom;
+
+    use bpx::variant::package::{Architecture, PackageBuilder, PackageDecoder, Platform};
+
+    let architectures = [
+        (Architecture::X86_64, 0x0, "x86_64"),
+        (Architecture::Aarch64, 0x1, "aarch64"),
+        (Architecture::X86, 0x2, "x86"),
+        (Architecture::Armv7hl, 0x3, "armv7hl"),
+        (Architecture::Any, 0x4, "any")
+    ];
+    let platforms = [
+        (Platform::Linux, 0x0, "linux"),
+        (Platform::Mac, 0x1, "mac"),
+        (Platform::Windows, 0x2, "windows"),
+        (Platform::Android, 0x3, "android"),
+        (Platform::Any, 0x4, "any")
+    ];
+    for (arch, code, name) in &architectures {
+        assert_eq!(u8::from(*arch), *code);
+        assert_eq!(Architecture::try_from(*code).unwrap(), *arch);
+        assert_eq!(arch.to_string(), *name);
+        assert_eq!(name.parse::<Architecture>().unwrap(), *arch);
+    }
+    for (platform, code, name) in &platforms {
+        assert_eq!(u8::from(*platform), *code);
+        assert_eq!(Platform::try_from(*code).unwrap(), *platform);
+        assert_eq!(platform.to_string(), *name);
+        assert_eq!(name.parse::<Platform>().unwrap(), *platform);
+    }
+    for code in 0x5..=0xFF {
+        assert!(Architecture::try_from(code).is_err());
+        assert!(Platform::try_from(code).is_err());
+    }
+    assert!("X86_64".parse::<Architecture>().is_err());
+    assert!("".parse::<Platform>().is_err());
+
+    for (arch, _, _) in &architectures {
+        for (platform, _, _) in &platforms {
+            let mut decoder = roundtrip_container(|encoder| {
+                PackageBuilder::new()
+                    .with_architecture(*arch)
+                    .with_platform(*platform)
+                    .build(encoder)
+                    .unwrap();
+            });
+            let package = PackageDecoder::read(&mut decoder).unwrap();
+            assert_eq!(package.get_architecture(), *arch);
+            assert_eq!(package.get_platform(), *platform);
+        }
+    }
+    let host = (Architecture::current(), Platform::current());
+    if cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        assert_eq!(host, (Architecture::X86_64, Platform::Linux));
+    }
+}
+
+#[test]
+fn package_extract_matching()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        utils::glob_match,
+        variant::package::{utils::UnpackOptions, PackageBuilder, PackageDecoder}
+    };
+
+    assert!(glob_match("**/*.png", "a.png"));
+    assert!(glob_match("textures/**", "textures/ui/a.png"));
+    assert!(!glob_match("*.png", "textures/a.png"));
+    assert!(glob_match("[!a]?.txt", "bc.txt"));
+    assert!(!glob_match("[!a]?.txt", "ac.txt"));
+    assert!(glob_match("weird[", "weird["));
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+        package.pack_object("textures/stone.png", &mut b"stone".as_ref()).unwrap();
+        package.pack_object("textures/ui/button.png", &mut b"button".as_ref()).unwrap();
+        package.pack_object("textures/readme.txt", &mut b"readme".as_ref()).unwrap();
+        package.pack_object("icon.png", &mut b"icon".as_ref()).unwrap();
+        encoder.save().unwrap();
+    }
+    let dest = std::env::temp_dir().join(format!("bpx_extract_matching_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dest);
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+
+    let report = package.extract_matching("textures/**/*.png", &dest, UnpackOptions::default()).unwrap();
+    assert_eq!(report.extracted, vec!["textures/stone.png", "textures/ui/button.png"]);
+    assert_eq!(report.bytes, 11);
+    assert_eq!(std::fs::read(dest.join("textures/ui/button.png")).unwrap(), b"button");
+    assert!(!dest.join("textures/readme.txt").exists());
+
+    // Overlapping pattern: already extracted files are skipped, new ones written.
+    let options = UnpackOptions { skip_existing: true };
+    let report = package.extract_matching("**/*.png", &dest, options).unwrap();
+    assert_eq!(report.extracted, vec!["icon.png"]);
+    assert_eq!(report.skipped, vec!["textures/stone.png", "textures/ui/button.png"]);
+    assert_eq!(report.bytes, 4);
+
+    let report = package.extract_matching("sounds/**", &dest, options).unwrap();
+    assert_eq!(report, Default::default());
+    std::fs::remove_dir_all(&dest).unwrap();
+
+    // Names escaping the destination are refused.
+    let mut decoder = roundtrip_container(|encoder| {
+        let mut package = PackageBuilder::new().build(encoder).unwrap();
+        package.pack_object("../escape.png", &mut b"x".as_ref()).unwrap();
+    });
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    assert!(package.extract_matching("**", &dest, UnpackOptions::default()).is_err());
+    assert!(!dest.exists());
+}
+
+#[test]
+fn package_summary_stored_and_scanned()
+{
+    use std::io::Write;
+
+    use bpx::{
+        header::SectionHeader,
+        strings::StringSection,
+        variant::{
+            package::{Package, PackageBuilder, PackageDecoder, PackageTypeExt, SummarySource},
+            VariantEncoder
+        }
+    };
+
+    let mut decoder = roundtrip_container(|encoder| {
+        let mut package = PackageBuilder::new().build(encoder).unwrap();
+        package.pack_object("a", &mut [1u8; 1000].as_ref()).unwrap();
+        package.pack_object("b", &mut [2u8; 24].as_ref()).unwrap();
+        package.pack_object("empty", &mut [].as_ref()).unwrap();
+    });
+    let summary = PackageDecoder::read(&mut decoder).unwrap().summary().unwrap();
+    assert_eq!((summary.file_count, summary.total_size), (3, 1024));
+    assert_eq!(summary.source, SummarySource::Stored);
+
+    // Packages written before the summary section existed.
+    let mut decoder = roundtrip_container(|encoder| {
+        let type_ext = PackageTypeExt::from_type_ext(&[0; 16]).unwrap();
+        let (encoder, _, required) = VariantEncoder::<Package, _>::create(encoder, type_ext)
+            .unwrap()
+            .into_parts();
+        let name = StringSection::new(required[0]).put(encoder, "legacy").unwrap();
+        let data = encoder.create_section(SectionHeader::new()).unwrap();
+        encoder.open_section(data).unwrap().write_all(b"old data").unwrap();
+        let mut entry = Vec::new();
+        entry.extend_from_slice(&8u64.to_le_bytes());
+        entry.extend_from_slice(&name.to_le_bytes());
+        entry.extend_from_slice(&encoder.get_section_index(data).to_le_bytes());
+        entry.extend_from_slice(&0u32.to_le_bytes());
+        encoder.open_section(required[1]).unwrap().write_all(&entry).unwrap();
+    });
+    let summary = PackageDecoder::read(&mut decoder).unwrap().summary().unwrap();
+    assert_eq!((summary.file_count, summary.total_size), (1, 8));
+    assert_eq!(summary.source, SummarySource::Scanned);
+}
+
+#[test]
+fn package_layout_split_entries()
+{
+    use std::io::{Cursor, Write};
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        strings::StringSection,
+        variant::{
+            package::{Package, PackageDecoder, PackageTypeExt, SECTION_TYPE_DATA},
+            VariantEncoder
+        }
+    };
+
+    let first: Vec<u8> = (0..100u32).map(|v| v as u8).collect();
+    let second: Vec<u8> = (0..300u32).map(|v| (v % 7) as u8).collect();
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let type_ext = PackageTypeExt::from_type_ext(&[0; 16]).unwrap();
+        let (encoder, _, required) = VariantEncoder::<Package, _>::create(&mut encoder, type_ext)
+            .unwrap()
+            .into_parts();
+        let raw = encoder
+            .create_section(SectionHeaderBuilder::new().with_type(SECTION_TYPE_DATA).build())
+            .unwrap();
+        encoder.open_section(raw).unwrap().write_all(&first).unwrap();
+        let header = SectionHeaderBuilder::new()
+            .with_type(SECTION_TYPE_DATA)
+            .with_compression(CompressionMethod::Zlib)
+            .with_threshold(0)
+            .build();
+        let zlib = encoder.create_section(header).unwrap();
+        encoder.open_section(zlib).unwrap().write_all(&second).unwrap();
+        let (raw, zlib) = (encoder.get_section_index(raw), encoder.get_section_index(zlib));
+        let mut strings = StringSection::new(required[0]);
+        let mut entries = Vec::new();
+        for (name, size, start, offset) in [
+            ("head", 60u64, raw, 0u32),
+            ("split", 240, raw, 60),
+            ("tail", 100, zlib, 200),
+            ("empty", 0, zlib, 300)
+        ] {
+            entries.extend_from_slice(&size.to_le_bytes());
+            entries.extend_from_slice(&strings.put(encoder, name).unwrap().to_le_bytes());
+            entries.extend_from_slice(&start.to_le_bytes());
+            entries.extend_from_slice(&offset.to_le_bytes());
+        }
+        encoder.open_section(required[1]).unwrap().write_all(&entries).unwrap();
+        encoder.save().unwrap();
+    }
+
+    let mut decoder = Decoder::new(Cursor::new(buf.clone())).unwrap();
+    let raw = decoder.find_section_by_type(SECTION_TYPE_DATA).unwrap();
+    let raw_header = *decoder.get_section_header(raw);
+    let zlib = decoder.find_section_by_index(decoder.get_section_index(raw) + 1).unwrap();
+    let zlib_header = *decoder.get_section_header(zlib);
+    let inflated = decoder.open_section(zlib).unwrap().load_in_memory().unwrap();
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    let layout = package.layout().unwrap();
+    let pieces: Vec<(&str, u64, u64, u64)> = layout
+        .iter()
+        .map(|v| (v.name.as_str(), v.intra_offset, v.object_offset, v.size))
+        .collect();
+    assert_eq!(
+        pieces,
+        [
+            ("head", 0, 0, 60),
+            ("split", 60, 0, 40),
+            ("split", 0, 40, 200),
+            ("tail", 200, 0, 100),
+            ("empty", 300, 0, 0)
+        ]
+    );
+    assert_eq!(layout[1].section_index + 1, layout[2].section_index);
+    assert_eq!(layout[1].compression, None);
+    assert_eq!(layout[2].compression, Some(CompressionMethod::Zlib));
+
+    // Rebuild every object from the file bytes alone, as a range reader would.
+    let table = package.read_object_table().unwrap();
+    for obj in table.get_objects() {
+        let name = String::from(package.get_object_name(obj).unwrap());
+        let mut rebuilt = Vec::new();
+        for piece in layout.iter().filter(|v| v.name == name) {
+            assert_eq!(piece.object_offset, rebuilt.len() as u64);
+            let header = if piece.compression.is_some() { zlib_header } else { raw_header };
+            assert_eq!((piece.section_file_offset, piece.section_csize), (header.pointer, header.csize as u64));
+            let stored = &buf[piece.section_file_offset as usize..][..piece.section_csize as usize];
+            let section = if piece.compression.is_some() { &inflated[..] } else { stored };
+            rebuilt.extend_from_slice(&section[piece.intra_offset as usize..][..piece.size as usize]);
+        }
+        let mut expected = Vec::new();
+        package.unpack_object(obj, &mut expected).unwrap();
+        assert_eq!(rebuilt, expected);
+    }
+}
+
+#[cfg(feature = "zip")]
+fn read_zip(zip: &[u8]) -> Vec<(String, Vec<u8>)>
+{
+    let u16_at = |pos: usize| u16::from_le_bytes([zip[pos], zip[pos + 1]]) as usize;
+    let u32_at = |pos: usize| u32::from_le_bytes([zip[pos], zip[pos + 1], zip[pos + 2], zip[pos + 3]]);
+    let eocd = zip.len() - 22;
+    assert_eq!(u32_at(eocd), 0x06054b50);
+    let mut pos = u32_at(eocd + 16) as usize;
+    let mut entries = Vec::new();
+    for _ in 0..u16_at(eocd + 10) {
+        assert_eq!(u32_at(pos), 0x02014b50);
+        let (method, crc, csize, size) = (u16_at(pos + 10), u32_at(pos + 16), u32_at(pos + 20), u32_at(pos + 24));
+        let name_len = u16_at(pos + 28);
+        let name = String::from_utf8(zip[pos + 46..pos + 46 + name_len].to_vec()).unwrap();
+        let local = u32_at(pos + 42) as usize;
+        assert_eq!(u32_at(local), 0x04034b50);
+        assert_eq!((u32_at(local + 14), u32_at(local + 18), u32_at(local + 22)), (crc, csize, size));
+        let start = local + 30 + u16_at(local + 26);
+        let raw = &zip[start..start + csize as usize];
+        let data = match method {
+            0 => raw.to_vec(),
+            8 => unsafe {
+                // zlib expects null allocators, which Rust function pointers cannot hold.
+                let mut out = vec![0u8; size as usize];
+                let mut stream = std::mem::MaybeUninit::<libz_sys::z_stream>::zeroed();
+                let stream = stream.as_mut_ptr();
+                let size_of = std::mem::size_of::<libz_sys::z_stream>() as i32;
+                assert_eq!(libz_sys::inflateInit2_(stream, -15, libz_sys::zlibVersion(), size_of), 0);
+                (*stream).next_in = raw.as_ptr() as *mut _;
+                (*stream).avail_in = raw.len() as _;
+                (*stream).next_out = out.as_mut_ptr();
+                (*stream).avail_out = out.len() as _;
+                assert_eq!(libz_sys::inflate(stream, libz_sys::Z_FINISH), libz_sys::Z_STREAM_END);
+                libz_sys::inflateEnd(stream);
+                out
+            },
+            _ => panic!("unexpected zip method {}", method)
+        };
+        let actual = unsafe { libz_sys::crc32(0, data.as_ptr(), data.len() as _) } as u32;
+        assert_eq!(actual, crc);
+        entries.push((name, data));
+        pos += 46 + name_len;
+    }
+    entries
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn package_to_zip()
+{
+    use std::io::Cursor;
+
+    use bpx::variant::package::{to_zip, to_zip_with, PackageBuilder, PackageDecoder, ZipMethod};
+
+    let big: Vec<u8> = (0..300000u32).map(|v| (v % 251) as u8).collect();
+    let objects: Vec<(&str, &[u8])> = vec![
+        ("textures/stone.png", b"stone"),
+        ("big.bin", &big),
+        ("empty", b""),
+        ("sons/\u{e9}t\u{e9}.ogg", b"ete")
+    ];
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+        for (name, mut data) in objects.iter().copied() {
+            package.pack_object(name, &mut data).unwrap();
+        }
+        encoder.save().unwrap();
+    }
+    let expected: Vec<(String, Vec<u8>)> = objects.iter().map(|(n, d)| (n.to_string(), d.to_vec())).collect();
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+
+    let mut stored = Cursor::new(Vec::new());
+    to_zip_with(&mut package, &mut stored, ZipMethod::Stored).unwrap();
+    assert_eq!(read_zip(stored.get_ref()), expected);
+    let mut deflated = Cursor::new(Vec::new());
+    to_zip(&mut package, &mut deflated).unwrap();
+    assert_eq!(read_zip(deflated.get_ref()), expected);
+    assert!(deflated.get_ref().len() < stored.get_ref().len() / 10);
+}
+
+#[test]
+fn variant_version_ranges()
+{
+    use std::{io::Cursor, ops::RangeInclusive};
+
+    use bpx::{
+        builder::MainHeaderBuilder,
+        error::Error,
+        header,
+        variant::{
+            package::{Package, PackageBuilder, PackageDecoder, SUPPORTED_VERSIONS},
+            FromTypeExt,
+            ToTypeExt,
+            Variant,
+            VariantDecoder
+        }
+    };
+
+    assert_eq!(<Package as Variant>::SUPPORTED_VERSIONS, SUPPORTED_VERSIONS);
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        PackageBuilder::new().build(&mut encoder).unwrap();
+        encoder.save().unwrap();
+    }
+    // Downgrade the package to BPX version 1.
+    buf[20] = 1;
+    let chksum = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]) - 1;
+    buf[4..8].copy_from_slice(&chksum.to_le_bytes());
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    assert_eq!(decoder.version(), 1);
+    match PackageDecoder::read(&mut decoder).err().unwrap() {
+        e @ Error::Header(header::Error::BadVariantVersion(b'P', 1, _)) => {
+            let msg = e.to_string();
+            assert!(msg.contains("BPXP version 1"));
+            assert!(msg.contains("supports versions 2 to 2"));
+        },
+        e => panic!("unexpected error: {}", e)
+    }
+
+    // A variant reading both BPX versions.
+    struct Legacy;
+    struct Ext;
+    impl ToTypeExt for Ext
+    {
+        fn to_type_ext(&self) -> [u8; 16]
+        {
+            [0; 16]
+        }
+    }
+    impl FromTypeExt for Ext
+    {
+        fn from_type_ext(_: &[u8; 16]) -> bpx::Result<Self>
+        {
+            Ok(Ext)
+        }
+    }
+    impl Variant for Legacy
+    {
+        const TYPE_BYTE: u8 = b'L';
+        const VERSION: u32 = 2;
+        const SUPPORTED_VERSIONS: RangeInclusive<u32> = 1..=2;
+        type TypeExt = Ext;
+
+        fn required_sections() -> &'static [u8]
+        {
+            &[]
+        }
+    }
+    let mut encoder = Encoder::new(Cursor::new(Vec::new())).unwrap();
+    for version in 1..=2 {
+        encoder.set_main_header(MainHeaderBuilder::new().with_type(b'L').with_version(version).build());
+        assert!(VariantDecoder::<Legacy, _>::read(&mut encoder).is_ok());
+    }
+}
+
+#[test]
+fn container_preserves_unknown_sections()
+{
+    use std::io::{Cursor, Seek, SeekFrom, Write};
+
+    use bpx::{
+        builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+        container::Container,
+        header::{SectionHeader, SIZE_SECTION_HEADER}
+    };
+
+    // A section of a type this crate does not know with unknown flag bits set.
+    let unknown: Vec<u8> = (0..4000u32).map(|v| (v % 13) as u8).collect();
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let header = SectionHeaderBuilder::new()
+            .with_type(0x7E)
+            .with_compression(CompressionMethod::Zlib)
+            .with_checksum(Checksum::Crc32)
+            .with_threshold(0)
+            .build();
+        let handle = encoder.create_section(header).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&unknown).unwrap();
+        let handle = encoder.create_section(SectionHeader::new()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(b"known").unwrap();
+        encoder.save().unwrap();
+    }
+    let flags = 40 + 21;
+    buf[flags] |= 0x80;
+    let chksum = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]).wrapping_add(0x80);
+    buf[4..8].copy_from_slice(&chksum.to_le_bytes());
+    let (_, original) = SectionHeader::read(&mut &buf[40..40 + SIZE_SECTION_HEADER]).unwrap();
+    let raw = buf[original.pointer as usize..(original.pointer + original.csize as u64) as usize].to_vec();
+
+    let mut file = Container::open(Cursor::new(buf)).unwrap();
+    let known = file.find_section_by_index(1).unwrap();
+    let mut data = file.sections().open(known).unwrap();
+    data.seek(SeekFrom::End(0)).unwrap();
+    data.write_all(b" and edited").unwrap();
+    drop(data);
+    let added = file.sections().create(SectionHeader::new()).unwrap();
+    file.sections().open(added).unwrap().write_all(b"new").unwrap();
+    file.save().unwrap();
+    let buf = file.into_inner().into_inner();
+
+    let (_, saved) = SectionHeader::read(&mut &buf[40..40 + SIZE_SECTION_HEADER]).unwrap();
+    assert_eq!((saved.btype, saved.flags, saved.chksum), (original.btype, original.flags, original.chksum));
+    assert_eq!((saved.csize, saved.size), (original.csize, original.size));
+    let copied = &buf[saved.pointer as usize..(saved.pointer + saved.csize as u64) as usize];
+    assert_eq!(copied, raw.as_slice());
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    assert_eq!(decoder.get_main_header().section_num, 3);
+    let known = decoder.find_section_by_index(1).unwrap();
+    assert_eq!(decoder.open_section(known).unwrap().load_in_memory().unwrap(), b"known and edited");
+}
+
+#[test]
+fn handles_from_other_files_fail_gracefully()
+{
+    use std::io::{Cursor, ErrorKind, Write};
+
+    use bpx::{container::Container, decoder::SliceDecoder, error::Error, header::SectionHeader};
+
+    let mut big = Encoder::new(Cursor::new(Vec::new())).unwrap();
+    let mut handles = Vec::new();
+    for i in 0..3u8 {
+        let handle = big.create_section(SectionHeader::new()).unwrap();
+        big.open_section(handle).unwrap().write_all(&[i; 4]).unwrap();
+        handles.push(handle);
+    }
+    let foreign = handles[2];
+    let mut small = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut small).unwrap();
+        encoder.create_section(SectionHeader::new()).unwrap();
+        assert!(encoder.try_get_section_header(foreign).is_none());
+        assert!(matches!(encoder.open_section(foreign).err(), Some(Error::InvalidHandle(2))));
+        encoder.save().unwrap();
+    }
+
+    let mut decoder = Decoder::new(Cursor::new(small.clone())).unwrap();
+    assert!(decoder.try_get_section_header(foreign).is_none());
+    let err = decoder.open_section(foreign).err().unwrap();
+    assert!(matches!(err, Error::InvalidHandle(2)));
+    assert_eq!(err.to_string(), "invalid section handle (section #2 does not exist in this BPX)");
+    assert_eq!(std::io::Error::from(err).kind(), ErrorKind::InvalidInput);
+    assert!(decoder.load_sections(&handles, 1).is_err());
+    assert!(decoder.dump_section(foreign, 0..16).is_err());
+
+    let mut slice = SliceDecoder::new(&small).unwrap();
+    assert!(slice.section_bytes(foreign).is_err());
+    assert!(slice.open_section(foreign).is_err());
+    let concurrent = Decoder::new(Cursor::new(small.clone())).unwrap().into_concurrent().unwrap();
+    assert!(concurrent.try_get_section_header(foreign).is_none());
+    assert!(concurrent.load_section(foreign).is_err());
+
+    drop(slice);
+    let mut container = Container::open(Cursor::new(small)).unwrap();
+    assert!(container.try_get_section_header(foreign).is_none());
+    assert!(container.sections().open(foreign).is_err());
+    assert!(container.sections().remove(foreign).is_err());
+    assert_eq!(container.sections().len(), 1);
+}
+
+#[test]
+fn checksum_golden_vectors()
+{
+    use std::io::Write;
+
+    use bpx::builder::{Checksum, CompressionMethod, SectionHeaderBuilder};
+
+    // Both algorithms are part of the format: these values must never change.
+    let spanning: Vec<u8> = (0..16385u32).map(|v| (v * 31 + 7) as u8).collect();
+    let vectors: [(&[u8], u32, u32); 3] = [
+        (b"", 0x0, 0x0),
+        (b"a", 0x61, 0xFF00004F),
+        (&spanning, 0x1FE007, 0xFFFFFF2B)
+    ];
+    for (data, weak, crc32) in vectors {
+        for (checksum, expected) in [(Checksum::Weak, weak), (Checksum::Crc32, crc32)] {
+            for compression in [None, Some(CompressionMethod::Zlib), Some(CompressionMethod::Xz)] {
+                for chunk in [1, 7, 8192, data.len().max(1)] {
+                    let mut builder = SectionHeaderBuilder::new().with_checksum(checksum).with_threshold(0);
+                    if let Some(method) = compression {
+                        builder = builder.with_compression(method);
+                    }
+                    let mut decoder = roundtrip_container(|encoder| {
+                        let handle = encoder.create_section(builder.build()).unwrap();
+                        let mut section = encoder.open_section(handle).unwrap();
+                        for part in data.chunks(chunk) {
+                            section.write_all(part).unwrap();
+                        }
+                        drop(section);
+                    });
+                    let handle = decoder.find_section_by_index(0).unwrap();
+                    assert_eq!(decoder.get_section_header(handle).chksum, expected);
+                    assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), data);
+                }
+            }
+        }
+    }
+
+    // Sections without a check flag store no checksum at all.
+    let mut decoder = roundtrip_container(|encoder| {
+        let handle = encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&spanning).unwrap();
+    });
+    let handle = decoder.find_section_by_index(0).unwrap();
+    assert_eq!(decoder.get_section_header(handle).chksum, 0);
+    assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), spanning);
+    for id in 0..=u8::MAX {
+        if let Some(checksum) = Checksum::from_algorithm_id(id) {
+            assert_eq!(checksum.algorithm_id(), id);
+        }
+    }
+}
+
+#[test]
+fn file_constructors_round_trip()
+{
+    use std::io::Write;
+
+    use bpx::{error::Error, header::SectionHeader};
+
+    let path = std::env::temp_dir().join("bpx_file_constructors_round_trip.bpx");
+    {
+        let mut encoder = Encoder::create_file(&path).unwrap();
+        for i in 0..100u32 {
+            let handle = encoder.create_section(SectionHeader::new()).unwrap();
+            encoder.open_section(handle).unwrap().write_all(&i.to_le_bytes()).unwrap();
+        }
+        encoder.save().unwrap();
+        // The file is complete before the encoder is dropped.
+        let decoder = Decoder::open_file(&path).unwrap();
+        assert_eq!(decoder.get_main_header().section_num, 100);
+    }
+    let mut decoder = Decoder::open_file(&path).unwrap();
+    for i in 0..100u32 {
+        let handle = decoder.find_section_by_index(i).unwrap();
+        assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), i.to_le_bytes());
+    }
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(Decoder::open_file(&path), Err(Error::Io(_))));
+}
+
+#[test]
+fn section_headers_snapshot_crosses_threads()
+{
+    use std::io::Write;
+
+    use bpx::{
+        builder::{Checksum, SectionHeaderBuilder},
+        header::{MainHeader, SectionHeader},
+        inspect::{dump, FileReport}
+    };
+
+    fn assert_send_sync<T: Send + Sync + Clone>() {}
+    assert_send_sync::<MainHeader>();
+    assert_send_sync::<SectionHeader>();
+    assert_send_sync::<Vec<SectionHeader>>();
+    assert_send_sync::<FileReport>();
+
+    let mut decoder = roundtrip_container(|encoder| {
+        for i in 0..8u8 {
+            let header = SectionHeaderBuilder::new().with_type(i).with_checksum(Checksum::Crc32).build();
+            let handle = encoder.create_section(header).unwrap();
+            encoder.open_section(handle).unwrap().write_all(&vec![i; i as usize * 10]).unwrap();
+        }
+    });
+    let main_header = *decoder.get_main_header();
+    let headers = decoder.section_headers_snapshot();
+    let planner = std::thread::spawn(move || {
+        let total: u32 = headers.iter().map(|v| v.size).sum();
+        (main_header.section_num, total, headers)
+    });
+    // The decoder stays usable while the snapshot is planned on.
+    let handle = decoder.find_section_by_index(7).unwrap();
+    assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), [7; 70]);
+    let (count, total, headers) = planner.join().unwrap();
+    assert_eq!((count, total), (8, 280));
+    assert_eq!(dump(&mut decoder).unwrap().section_headers(), headers);
+}
+
+#[cfg(feature = "section-debug")]
+#[test]
+fn section_debug_detects_aliased_strings()
+{
+    use std::io::{Cursor, Seek, SeekFrom, Write};
+
+    use bpx::{
+        container::Container,
+        error::Error,
+        header::SectionHeader,
+        strings::{self, StringSection},
+        variant::{
+            package::{Package, PackageBuilder},
+            VariantDecoder
+        }
+    };
+
+    let is_aliased = |e: &Error, address: u32| match e {
+        Error::Section { source, .. } => {
+            matches!(source.as_ref(), Error::Strings(strings::Error::Aliased(v)) if *v == address)
+        },
+        _ => false
+    };
+
+    // Overwriting a cached string through another open of the section.
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    let handle = encoder.create_section(SectionHeader::new()).unwrap();
+    let mut strings = StringSection::new(handle);
+    let address = strings.put(&mut encoder, "first").unwrap();
+    assert_eq!(strings.get(&mut encoder, address).unwrap(), "first");
+    {
+        let mut data = encoder.open_section(handle).unwrap();
+        data.seek(SeekFrom::Start(0)).unwrap();
+        data.write_all(b"other").unwrap();
+    }
+    let e = strings.get(&mut encoder, address).unwrap_err();
+    assert!(is_aliased(&e, address), "{}", e);
+    assert!(e.to_string().starts_with("section #0"), "{}", e);
+
+    // A second StringSection appending to the same section does not conflict.
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    let handle = encoder.create_section(SectionHeader::new()).unwrap();
+    let (mut a, mut b) = (StringSection::new(handle), StringSection::new(handle));
+    let first = a.put(&mut encoder, "a").unwrap();
+    let second = b.put(&mut encoder, "b").unwrap();
+    assert_eq!(a.get(&mut encoder, first).unwrap(), "a");
+    assert_eq!(a.get(&mut encoder, second).unwrap(), "b");
+    assert_eq!(b.get(&mut encoder, first).unwrap(), "a");
+
+    // Package strings edited in place while the package names are cached.
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+        package.pack_object("name", &mut b"data".as_ref()).unwrap();
+        encoder.save().unwrap();
+    }
+    let mut container = Container::open(Cursor::new(buf)).unwrap();
+    let mut package = VariantDecoder::<Package, _>::read(&mut container).unwrap();
+    let handle = package.get_required_sections()[0];
+    let mut strings = StringSection::new(handle);
+    assert_eq!(strings.get(package.get_interface(), 0).unwrap(), "name");
+    package.get_interface().sections().open(handle).unwrap().write_all(b"nope").unwrap();
+    let e = strings.get(package.get_interface(), 0).unwrap_err();
+    assert!(is_aliased(&e, 0), "{}", e);
+}
+
+#[test]
+fn empty_sections_are_stored()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+        header::{MainHeader, SectionFlags, SectionHeader, SIZE_SECTION_HEADER},
+        raw,
+        section::new_section_data
+    };
+
+    for (method, flag) in [
+        (CompressionMethod::Xz, SectionFlags::COMPRESS_XZ),
+        (CompressionMethod::Zlib, SectionFlags::COMPRESS_ZLIB)
+    ] {
+        let mut section = new_section_data(None).unwrap();
+        let flags = SectionFlags::CHECK_CRC32 | flag;
+        let mut out = Vec::new();
+        assert_eq!(raw::write_section_to(flags, section.as_mut(), &mut out).unwrap(), (0, 0));
+        assert!(out.is_empty());
+
+        let mut buf = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut buf).unwrap();
+            let header = SectionHeaderBuilder::new()
+                .with_compression(method)
+                .with_checksum(Checksum::Crc32)
+                .with_threshold(0)
+                .build();
+            encoder.create_section(header).unwrap();
+            encoder.save().unwrap();
+        }
+        let (_, header) = SectionHeader::read(&mut &buf[40..40 + SIZE_SECTION_HEADER]).unwrap();
+        assert_eq!((header.size, header.csize, header.compression()), (0, 0, None));
+
+        // Other writers may still flag an empty section as compressed.
+        buf[40 + 21] = flags.bits();
+        let (_, main_header) = MainHeader::read(&mut &buf[..]).unwrap();
+        let chksum = main_header.chksum.wrapping_sub(header.flags as u32).wrapping_add(flags.bits() as u32);
+        buf[4..8].copy_from_slice(&chksum.to_le_bytes());
+        let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+        let handle = decoder.find_section_by_index(0).unwrap();
+        assert_eq!(decoder.get_section_header(handle).compression(), Some(method));
+        assert!(decoder.open_section(handle).unwrap().load_in_memory().unwrap().is_empty());
+    }
+}
+
+#[test]
+fn package_zero_length_round_trip()
+{
+    use bpx::variant::package::{
+        utils::{unpack_memory, UnpackOptions},
+        PackageBuilder,
+        PackageDecoder,
+        SECTION_TYPE_DATA
+    };
+
+    // A package without any object.
+    let mut decoder = roundtrip_container(|encoder| {
+        PackageBuilder::new().build(encoder).unwrap();
+    });
+    assert!(decoder.find_section_by_type(SECTION_TYPE_DATA).is_none());
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    assert!(package.read_object_table().unwrap().get_objects().is_empty());
+    assert_eq!(package.summary().unwrap().file_count, 0);
+    assert!(package.layout().unwrap().is_empty());
+    let dest = std::env::temp_dir().join("bpx_package_zero_length_round_trip");
+    let report = package.extract_matching("**", &dest, UnpackOptions::default()).unwrap();
+    assert!(report.extracted.is_empty());
+
+    // With a split size of 16 KiB, data sections are full after 8 KiB.
+    let full: Vec<u8> = (0..8192u32).map(|v| (v % 251) as u8).collect();
+    let large: Vec<u8> = (0..20000u32).map(|v| (v % 13) as u8).collect();
+    let objects: [(&str, &[u8]); 6] = [
+        ("first_empty", b""),
+        ("full", &full),
+        ("empty_at_end", b""),
+        ("next", b"next"),
+        ("large", &large),
+        ("last_empty", b"")
+    ];
+    let mut decoder = roundtrip_container(|encoder| {
+        let mut package = PackageBuilder::new().with_split_size(16384).build(encoder).unwrap();
+        for (name, data) in objects {
+            package.pack_object(name, &mut &*data).unwrap();
+        }
+    });
+    let data_sections: Vec<u32> = decoder
+        .find_all_sections_of_type(SECTION_TYPE_DATA)
+        .into_iter()
+        .map(|v| decoder.get_section_header(v).size)
+        .collect();
+    // No empty data section is created, neither for empty objects nor at the boundary.
+    assert_eq!(data_sections, [8192, 8196, 8192, 3616]);
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    let table = package.read_object_table().unwrap();
+    let first = table.get_objects()[0].start;
+    let starts: Vec<(u32, u32, u64)> = table
+        .get_objects()
+        .iter()
+        .map(|v| (v.start - first, v.offset, v.size))
+        .collect();
+    assert_eq!(starts, [(0, 0, 0), (0, 0, 8192), (0, 8192, 0), (1, 0, 4), (1, 4, 20000), (3, 3616, 0)]);
+    for (obj, (_, data)) in table.get_objects().iter().zip(objects) {
+        assert_eq!(unpack_memory(&mut package, obj).unwrap(), data);
+    }
+    let layout = package.layout().unwrap();
+    let empty_at_end = layout.iter().find(|v| v.name == "empty_at_end").unwrap();
+    assert_eq!((empty_at_end.intra_offset, empty_at_end.size), (8192, 0));
+    assert_eq!(layout.iter().filter(|v| v.name == "large").count(), 3);
+    assert_eq!(package.summary().unwrap().total_size, 28196);
+    let report = package.extract_matching("*empty*", &dest, UnpackOptions::default()).unwrap();
+    assert_eq!(report.extracted, ["first_empty", "empty_at_end", "last_empty"]);
+    assert_eq!(std::fs::metadata(dest.join("empty_at_end")).unwrap().len(), 0);
+    std::fs::remove_dir_all(&dest).unwrap();
+}
+
+#[test]
+fn package_source_changed_while_packing()
+{
+    use std::io::{Cursor, Read};
+
+    use bpx::{
+        error::Error,
+        variant::package::{utils::unpack_memory, PackageBuilder, PackageDecoder}
+    };
+
+    // Simulates another process appending to the file once packing started.
+    struct GrowingReader
+    {
+        inner: Cursor<Vec<u8>>,
+        appended: bool
+    }
+
+    impl Read for GrowingReader
+    {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize>
+        {
+            let len = self.inner.read(buf)?;
+            if len == 0 && !self.appended {
+                self.appended = true;
+                self.inner.get_mut().extend_from_slice(&[0xAA; 100000]);
+                return self.inner.read(buf);
+            }
+            Ok(len)
+        }
+    }
+
+    let growing = || GrowingReader {
+        inner: Cursor::new(vec![1; 5000]),
+        appended: false
+    };
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+        match package.pack_object_sized("grown", &mut growing(), 5000) {
+            Err(Error::SourceChanged(name, 5000, 5001)) => assert_eq!(name, "grown"),
+            v => panic!("unexpected result {:?}", v.err())
+        }
+        match package.pack_object_sized("shrunk", &mut [2u8; 10].as_ref(), 20) {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "source of 'shrunk' changed while packing (expected 20 bytes, read 10)"
+            ),
+            Ok(_) => panic!("a truncated source must be rejected")
+        }
+        package.pack_object_sized("stable", &mut [3u8; 10].as_ref(), 10).unwrap();
+        // Without an expected size the bytes actually read are recorded.
+        package.pack_object("recorded", &mut growing()).unwrap();
+        encoder.save().unwrap();
+    }
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    let table = package.read_object_table().unwrap();
+    let mut objects = Vec::new();
+    for obj in table.get_objects() {
+        let name = String::from(package.get_object_name(obj).unwrap());
+        objects.push((name, unpack_memory(&mut package, obj).unwrap().len()));
+    }
+    assert_eq!(objects, [(String::from("stable"), 10), (String::from("recorded"), 105000)]);
+    let summary = package.summary().unwrap();
+    assert_eq!((summary.file_count, summary.total_size), (2, 105010));
+}
+
+#[test]
+fn raw_section_copy_is_byte_identical()
+{
+    use std::io::{Cursor, Read};
+
+    use bpx::{
+        builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+        container::Container,
+        header::SectionHeader
+    };
+
+    let content: Vec<u8> = (0..20000u32).map(|v| (v % 251) as u8).collect();
+    let headers = [
+        SectionHeaderBuilder::new()
+            .with_compression(CompressionMethod::Xz)
+            .with_checksum(Checksum::Crc32)
+            .with_threshold(0)
+            .with_type(1)
+            .build(),
+        SectionHeaderBuilder::new()
+            .with_compression(CompressionMethod::Zlib)
+            .with_checksum(Checksum::Weak)
+            .with_threshold(0)
+            .with_type(2)
+            .build(),
+        SectionHeaderBuilder::new().with_checksum(Checksum::Crc32).with_type(3).build(),
+        SectionHeader::new()
+    ];
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        for (i, header) in headers.iter().enumerate() {
+            let handle = encoder.create_section(*header).unwrap();
+            encoder.open_section(handle).unwrap().write_all(&content[i..]).unwrap();
+        }
+        encoder.save().unwrap();
+    }
+    let stored = |buf: &[u8]| {
+        let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+        let mut sections = Vec::new();
+        for i in 0..decoder.get_main_header().section_num {
+            let handle = decoder.find_section_by_index(i).unwrap();
+            let mut header = *decoder.get_section_header(handle);
+            let mut payload = Vec::new();
+            decoder.raw_section_reader(handle).unwrap().read_to_end(&mut payload).unwrap();
+            assert_eq!(payload.len(), header.csize as usize);
+            header.pointer = 0;
+            sections.push((header, payload));
+        }
+        sections
+    };
+    let original = stored(&buf);
+    assert_ne!(original[0].0.csize, original[0].0.size);
+    assert_ne!(original[1].0.csize, original[1].0.size);
+
+    let mut copy = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut copy).unwrap();
+        for (header, payload) in &original {
+            encoder.create_section_raw(*header, &mut payload.as_slice()).unwrap();
+        }
+        encoder.save().unwrap();
+    }
+    assert_eq!(stored(&copy), original);
+    assert_eq!(copy, buf);
+
+    let mut container = Container::create(Cursor::new(Vec::new()));
+    for (header, payload) in &original {
+        container.sections().create_raw(*header, &mut payload.as_slice()).unwrap();
+    }
+    // Reading a raw section inflates it but the stored bytes are kept.
+    let handle = container.find_section_by_index(0).unwrap();
+    assert_eq!(container.sections().open(handle).unwrap().load_in_memory().unwrap(), content);
+    container.save().unwrap();
+    assert_eq!(stored(container.into_inner().get_ref()), original);
+
+    // A modified raw section is compressed again.
+    let mut copy = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut copy).unwrap();
+        for (header, payload) in &original {
+            let handle = encoder.create_section_raw(*header, &mut payload.as_slice()).unwrap();
+            if header.btype == 2 {
+                encoder.open_section(handle).unwrap().write_all(&[0; 100]).unwrap();
+            }
+        }
+        encoder.save().unwrap();
+    }
+    let modified = stored(&copy);
+    assert_eq!(modified[0], original[0]);
+    assert_ne!(modified[1], original[1]);
+    let mut decoder = Decoder::new(Cursor::new(copy)).unwrap();
+    let handle = decoder.find_section_by_index(1).unwrap();
+    let mut expected = vec![0; 100];
+    expected.extend_from_slice(&content[101..]);
+    assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), expected);
+
+    let mut bad = original[2].0;
+    bad.size += 1;
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    assert!(encoder.create_section_raw(bad, &mut original[2].1.as_slice()).is_err());
+    let mut short = &original[0].1[..10];
+    assert!(encoder.create_section_raw(original[0].0, &mut short).is_err());
+}
+
+#[test]
+fn section_user_tag_round_trip()
+{
+    use std::io::{Cursor, Seek, SeekFrom};
+
+    use bpx::{
+        builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+        container::Container,
+        header::{SectionFlags, SectionHeader}
+    };
+
+    let tags = [0x00, 0x01, 0x80, 0xFF];
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        for (i, tag) in tags.iter().enumerate() {
+            let header = SectionHeaderBuilder::new()
+                .with_user_tag(*tag)
+                .with_compression(CompressionMethod::Zlib)
+                .with_threshold(0)
+                .with_checksum(Checksum::Crc32)
+                .with_type(i as u8)
+                .build();
+            let handle = encoder.create_section(header).unwrap();
+            encoder.open_section(handle).unwrap().write_all(&[i as u8; 1000]).unwrap();
+        }
+        encoder.save().unwrap();
+    }
+    let check = |buf: &[u8], last_size: usize| {
+        let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+        for (i, tag) in tags.iter().enumerate() {
+            let handle = decoder.find_section_by_index(i as u32).unwrap();
+            let header = *decoder.get_section_header(handle);
+            assert_eq!(header.user_tag(), *tag);
+            assert_eq!(header.flags(), SectionFlags::COMPRESS_ZLIB | SectionFlags::CHECK_CRC32);
+            let size = if i == 3 { last_size } else { 1000 };
+            assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), vec![i as u8; size]);
+        }
+    };
+    check(&buf, 1000);
+    assert_eq!(SectionHeader::new().to_bytes()[22], 0);
+
+    // Tags survive an edit of another section, and a header only save.
+    let mut container = Container::open(Cursor::new(buf)).unwrap();
+    let handle = container.find_section_by_index(3).unwrap();
+    let mut section = container.sections().open(handle).unwrap();
+    section.seek(SeekFrom::End(0)).unwrap();
+    section.write_all(&[3; 1000]).unwrap();
+    drop(section);
+    container.save().unwrap();
+    container.save().unwrap();
+    let buf = container.into_inner().into_inner();
+    check(&buf, 2000);
+}
+
+#[test]
+fn inflated_size_must_match_header()
+{
+    use std::io::{Cursor, Read};
+
+    use bpx::{
+        builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+        header::{MainHeader, SectionHeader, SIZE_SECTION_HEADER}
+    };
+
+    let content: Vec<u8> = (0..1000u32).map(|v| (v % 7) as u8).collect();
+    for method in [CompressionMethod::Xz, CompressionMethod::Zlib] {
+        let mut buf = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut buf).unwrap();
+            let header = SectionHeaderBuilder::new()
+                .with_compression(method)
+                .with_checksum(Checksum::Crc32)
+                .with_threshold(0)
+                .build();
+            let handle = encoder.create_section(header).unwrap();
+            encoder.open_section(handle).unwrap().write_all(&content).unwrap();
+            encoder.save().unwrap();
+        }
+        let (_, header) = SectionHeader::read(&mut &buf[40..40 + SIZE_SECTION_HEADER]).unwrap();
+        assert_eq!((header.size, header.compression()), (1000, Some(method)));
+        let with_size = |size: u32| {
+            let mut buf = buf.clone();
+            let mut patched = header;
+            patched.size = size;
+            buf[40..40 + SIZE_SECTION_HEADER].copy_from_slice(&patched.to_bytes());
+            let (_, main_header) = MainHeader::read(&mut &buf[..]).unwrap();
+            let chksum = main_header
+                .chksum
+                .wrapping_sub(header.get_checksum())
+                .wrapping_add(patched.get_checksum());
+            buf[4..8].copy_from_slice(&chksum.to_le_bytes());
+            let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+            let handle = decoder.find_section_by_index(0).unwrap();
+            let err = decoder.open_section(handle).err().unwrap();
+            assert!(err.is_corruption(), "{}", err);
+            err.to_string()
+        };
+        assert!(with_size(1200).contains("section inflates to 1000 bytes but its header declares 1200"));
+        assert!(with_size(800).contains("section inflates to at least"));
+        assert!(with_size(800).contains("but its header declares 800"));
+
+        // Compressed bytes after the end of the stream are rejected too.
+        let mut decoder = Decoder::new(Cursor::new(buf.clone())).unwrap();
+        let handle = decoder.find_section_by_index(0).unwrap();
+        let mut payload = Vec::new();
+        decoder.raw_section_reader(handle).unwrap().read_to_end(&mut payload).unwrap();
+        payload.extend_from_slice(&[1, 2, 3, 4]);
+        let mut trailing = header;
+        trailing.csize += 4;
+        let mut copy = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut copy).unwrap();
+            encoder.create_section_raw(trailing, &mut payload.as_slice()).unwrap();
+            encoder.save().unwrap();
+        }
+        let mut decoder = Decoder::new(Cursor::new(copy)).unwrap();
+        let handle = decoder.find_section_by_index(0).unwrap();
+        let err = decoder.open_section(handle).err().unwrap();
+        if method == CompressionMethod::Zlib {
+            assert!(err.to_string().contains("4 compressed bytes left after the end of the section stream"));
+        }
+
+        let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+        let handle = decoder.find_section_by_index(0).unwrap();
+        assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), content);
+    }
+}
+
+#[test]
+fn package_manifest_text_snapshot()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        container::Container,
+        variant::package::{PackageBuilder, PackageDecoder, SummarySource, SECTION_TYPE_SUMMARY}
+    };
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+        package.pack_object("readme.txt", &mut b"hello".as_ref()).unwrap();
+        package.pack_object("dir/empty", &mut b"".as_ref()).unwrap();
+        package.pack_object("odd\tname\\with\nbreaks", &mut [7u8; 300].as_ref()).unwrap();
+        encoder.save().unwrap();
+    }
+    let expected = concat!(
+        "# files\t3\n",
+        "# size\t305\n",
+        "# csize\t305\n",
+        "readme.txt\t5\t-\n",
+        "dir/empty\t0\t-\n",
+        "odd\\tname\\\\with\\nbreaks\t300\t-\n"
+    );
+    let mut decoder = Decoder::new(Cursor::new(buf.clone())).unwrap();
+    let manifest = PackageDecoder::read(&mut decoder).unwrap().manifest().unwrap();
+    assert_eq!(manifest.summary.source, SummarySource::Stored);
+    assert_eq!(manifest.entries[2].name, "odd\tname\\with\nbreaks");
+    assert_eq!(manifest.to_string(), expected);
+
+    // Without a summary section the totals are computed from the object table.
+    let mut container = Container::open(Cursor::new(buf)).unwrap();
+    let summary = container.find_section_by_type(SECTION_TYPE_SUMMARY).unwrap();
+    container.sections().remove(summary).unwrap();
+    container.save().unwrap();
+    let mut container = Container::open(container.into_inner()).unwrap();
+    let scanned = PackageDecoder::read(&mut container).unwrap().manifest().unwrap();
+    assert_eq!(scanned.summary.source, SummarySource::Scanned);
+    assert_eq!(scanned.to_string(), expected);
+}
+
+#[test]
+fn build_id_round_trip()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        container::Container,
+        decoder::SliceDecoder,
+        header::{SectionHeader, SECTION_TYPE_BUILD_ID}
+    };
+
+    let encode = |id: Option<u64>| {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let handle = encoder.create_section(SectionHeader::new()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(b"content").unwrap();
+        if let Some(id) = id {
+            encoder.set_build_id(id).unwrap();
+        }
+        encoder.save().unwrap();
+        buf
+    };
+    let buf = encode(Some(0xDEADBEEF00C0FFEE));
+    assert_eq!(buf, encode(Some(0xDEADBEEF00C0FFEE)));
+    assert_ne!(buf, encode(Some(1)));
+
+    let mut decoder = Decoder::new(Cursor::new(buf.clone())).unwrap();
+    assert_eq!(decoder.build_id().unwrap(), Some(0xDEADBEEF00C0FFEE));
+    let mut decoder = SliceDecoder::new(&buf).unwrap();
+    assert_eq!(decoder.build_id().unwrap(), Some(0xDEADBEEF00C0FFEE));
+    let mut decoder = Decoder::new(Cursor::new(encode(None))).unwrap();
+    assert_eq!(decoder.build_id().unwrap(), None);
+
+    // The container keeps the identifier when other sections change.
+    let mut container = Container::open(Cursor::new(buf.clone())).unwrap();
+    let handle = container.find_section_by_index(0).unwrap();
+    container.sections().open(handle).unwrap().write_all(b"changed").unwrap();
+    container.save().unwrap();
+    let mut container = Container::open(container.into_inner()).unwrap();
+    assert_eq!(container.build_id().unwrap(), Some(0xDEADBEEF00C0FFEE));
+    container.set_build_id(42).unwrap();
+    container.save().unwrap();
+    let mut container = Container::open(container.into_inner()).unwrap();
+    assert_eq!(container.build_id().unwrap(), Some(42));
+    assert_eq!(container.find_all_sections_of_type(SECTION_TYPE_BUILD_ID).len(), 1);
+
+    // The identifier bytes are covered by the CRC32 of their section.
+    let decoder = Decoder::new(Cursor::new(&buf)).unwrap();
+    let handle = decoder.find_section_by_type(SECTION_TYPE_BUILD_ID).unwrap();
+    let pointer = decoder.get_section_header(handle).pointer as usize;
+    let mut corrupted = buf.clone();
+    corrupted[pointer] ^= 0xFF;
+    let mut decoder = Decoder::new(Cursor::new(corrupted)).unwrap();
+    assert!(decoder.build_id().is_err());
+}
+
+#[test]
+fn package_string_offset_overflow()
+{
+    use std::io::{Cursor, Seek, SeekFrom, Write};
+
+    use bpx::{
+        container::Container,
+        header::{MainHeader, SectionHeader, SECTION_TYPE_STRING},
+        section::SectionGuard,
+        variant::package::{PackageBuilder, PackageDecoder, SECTION_TYPE_OBJECT_TABLE},
+        Result,
+        SectionHandle,
+        WriteInterface
+    };
+
+    struct HugeStrings
+    {
+        inner: Encoder<Vec<u8>>,
+        strings: OversizedSection
+    }
+
+    impl Interface for HugeStrings
+    {
+        fn find_section_by_index(&self, index: u32) -> Option<SectionHandle>
+        {
+            self.inner.find_section_by_index(index)
+        }
+
+        fn get_section_header(&self, handle: SectionHandle) -> &SectionHeader
+        {
+            self.inner.get_section_header(handle)
+        }
+
+        fn try_get_section_header(&self, handle: SectionHandle) -> Option<&SectionHeader>
+        {
+            self.inner.try_get_section_header(handle)
+        }
+
+        fn get_section_index(&self, handle: SectionHandle) -> u32
+        {
+            self.inner.get_section_index(handle)
+        }
+
+        fn open_section(&mut self, handle: SectionHandle) -> Result<SectionGuard<'_>>
+        {
+            if self.inner.get_section_header(handle).btype == SECTION_TYPE_STRING {
+                return Ok(SectionGuard::new(&mut self.strings)?);
+            }
+            self.inner.open_section(handle)
+        }
+
+        fn get_main_header(&self) -> &MainHeader
+        {
+            self.inner.get_main_header()
+        }
+    }
+
+    impl WriteInterface for HugeStrings
+    {
+        fn set_main_header(&mut self, main_header: MainHeader)
+        {
+            self.inner.set_main_header(main_header)
+        }
+
+        fn create_section(&mut self, header: SectionHeader) -> Result<SectionHandle>
+        {
+            self.inner.create_section(header)
+        }
+    }
+
+    let mut file = HugeStrings {
+        inner: Encoder::new(Vec::new()).unwrap(),
+        // A string section pretending to already hold almost 4 GB of names.
+        strings: OversizedSection {
+            data: Cursor::new(Vec::new()),
+            extra: (u32::MAX - 16) as usize
+        }
+    };
+    let mut package = PackageBuilder::new().build(&mut file).unwrap();
+    package.pack_object("short", &mut b"data".as_ref()).unwrap();
+    let err = package.pack_object("textures/far_away.png", &mut b"data".as_ref()).err().unwrap();
+    assert!(err.is_capacity(), "{}", err);
+    assert!(err.to_string().contains("string 'textures/far_away.png' does not fit in its section"));
+    assert_eq!(file.strings.data.get_ref().as_slice(), b"short\0");
+
+    // An offset past the end of the strings section names the faulty entry.
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+        package.pack_object("a", &mut b"data".as_ref()).unwrap();
+        package.pack_object("b", &mut b"data".as_ref()).unwrap();
+        encoder.save().unwrap();
+    }
+    let mut container = Container::open(Cursor::new(buf)).unwrap();
+    let table = container.find_section_by_type(SECTION_TYPE_OBJECT_TABLE).unwrap();
+    {
+        let mut section = container.sections().open(table).unwrap();
+        section.seek(SeekFrom::Start(20 + 8)).unwrap();
+        section.write_all(&0x10000u32.to_le_bytes()).unwrap();
+    }
+    let err = PackageDecoder::read(&mut container).unwrap().read_object_table().err().unwrap();
+    assert!(err.is_corruption(), "{}", err);
+    assert!(err.to_string().contains("object #1 name offset 65536 is past the end of the strings section (4 bytes)"));
+}
+
+#[test]
+fn sd_hash_algo_collisions()
+{
+    use std::{convert::TryInto, io::Cursor};
+
+    use bpx::{
+        sd::{HashAlgo, Object, Value},
+        variant::package::{PackageBuilder, PackageDecoder}
+    };
+
+    // Names of the same length collide under this algorithm.
+    let by_len = HashAlgo::custom(0x80, |name| name.len() as u64).unwrap();
+    assert_eq!(by_len.hash("ab"), by_len.hash("cd"));
+
+    let mut colliding = Object::with_hash_algo(by_len);
+    colliding.set("ab", 1u8.into());
+    colliding.set("cd", 2u8.into());
+    assert_eq!(colliding.prop_count(), 1);
+    assert_eq!(colliding.get("ab"), Some(&Value::from(2u8)));
+    let mut distinct = Object::new();
+    distinct.set("ab", 1u8.into());
+    distinct.set("cd", 2u8.into());
+    assert_eq!(distinct.prop_count(), 2);
+    assert_eq!(distinct.get("ab"), Some(&Value::from(1u8)));
+
+    // Nested objects must hash names like their root.
+    let mut root = Object::with_hash_algo(by_len);
+    root.set("nested", Object::new().into());
+    let err = root.write(&mut Vec::new()).err().unwrap();
+    assert!(err.to_string().contains("nested object hashes names with algorithm 0 but its root uses 128"));
+    let mut root = Object::with_hash_algo(by_len);
+    root.set("nested", Object::with_hash_algo(by_len).into());
+    root.set("x", 3u8.into());
+    let mut buf = Vec::new();
+    root.write(&mut buf).unwrap();
+    let read = Object::read_with(&mut buf.as_slice(), by_len).unwrap();
+    assert_eq!(read, root);
+    let nested: &Object = (&read["nested"]).try_into().unwrap();
+    assert_eq!(nested.hash_algo(), by_len);
+
+    // The algorithm is recorded in the user tag of the metadata section; V1 keeps it at 0.
+    let package = |metadata: Object| {
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        PackageBuilder::new().with_metadata(metadata).build(&mut encoder).unwrap();
+        encoder.save().unwrap();
+        drop(encoder);
+        buf
+    };
+    let mut decoder = Decoder::new(Cursor::new(package(distinct.clone()))).unwrap();
+    let handle = decoder.find_section_by_type(bpx::header::SECTION_TYPE_SD).unwrap();
+    assert_eq!(decoder.get_section_header(handle).user_tag(), 0);
+    let metadata = PackageDecoder::read(&mut decoder).unwrap().read_metadata().unwrap().unwrap();
+    assert_eq!(metadata, distinct);
+
+    let mut decoder = Decoder::new(Cursor::new(package(colliding.clone()))).unwrap();
+    let mut reader = PackageDecoder::read(&mut decoder).unwrap();
+    let err = reader.read_metadata().err().unwrap();
+    assert!(err.to_string().contains("metadata name hash algorithm 128"), "{}", err);
+    let err = reader.read_metadata_with(HashAlgo::V1).err().unwrap();
+    assert!(err.to_string().contains("metadata name hash algorithm 128 (expected 0)"), "{}", err);
+    let metadata = reader.read_metadata_with(by_len).unwrap().unwrap();
+    assert_eq!(metadata.get("cd"), Some(&Value::from(2u8)));
+    assert_eq!(metadata.get("zz"), Some(&Value::from(2u8)));
+}
+
+#[test]
+fn package_metadata_update_in_place()
+{
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+    use bpx::{
+        container::Container,
+        header::SECTION_TYPE_SD,
+        variant::package::{
+            utils::unpack_memory,
+            PackageBuilder,
+            PackageDecoder,
+            PackageEditor,
+            PackageMetadata,
+            METADATA_DESCRIPTION,
+            METADATA_VERSION,
+            SECTION_TYPE_DATA
+        },
+        Interface
+    };
+
+    // Counts the bytes written to the file.
+    struct Counting(Cursor<Vec<u8>>, usize);
+
+    impl Read for Counting
+    {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize>
+        {
+            self.0.read(buf)
+        }
+    }
+
+    impl Write for Counting
+    {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>
+        {
+            let len = self.0.write(buf)?;
+            self.1 += len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()>
+        {
+            Ok(())
+        }
+    }
+
+    impl Seek for Counting
+    {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64>
+        {
+            self.0.seek(pos)
+        }
+    }
+
+    let data: Vec<u8> = (0..200000u32).map(|v| (v.wrapping_mul(2654435761) >> 13) as u8).collect();
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let metadata = PackageMetadata::new("game", "1.0.0");
+        let mut package = PackageBuilder::new().with_typed_metadata(metadata).build(&mut encoder).unwrap();
+        package.pack_object("a.bin", &mut data.as_slice()).unwrap();
+        package.pack_object("b.txt", &mut b"hello".as_ref()).unwrap();
+        encoder.save().unwrap();
+    }
+    // (chksum, stored bytes) of every data section.
+    let data_sections = |bytes: &[u8]| {
+        let decoder = Decoder::new(Cursor::new(bytes.to_vec())).unwrap();
+        decoder
+            .find_all_sections_of_type(SECTION_TYPE_DATA)
+            .into_iter()
+            .map(|handle| {
+                let header = decoder.get_section_header(handle);
+                let start = header.pointer as usize;
+                (header.chksum, bytes[start..start + header.csize as usize].to_vec())
+            })
+            .collect::<Vec<_>>()
+    };
+    let read_metadata = |bytes: Vec<u8>| {
+        let mut decoder = Decoder::new(Cursor::new(bytes)).unwrap();
+        let mut package = PackageDecoder::read(&mut decoder).unwrap();
+        let mut table = package.read_object_table().unwrap();
+        table.build_lookup_table(&mut package).unwrap();
+        let obj = *table.find_object("a.bin").unwrap().unwrap();
+        assert_eq!(unpack_memory(&mut package, &obj).unwrap(), data);
+        package.read_typed_metadata().unwrap().unwrap()
+    };
+    let original = data_sections(&buf);
+    assert!(!original.is_empty());
+
+    // Same size: only the metadata section and the headers are patched.
+    let mut container = Container::open(Counting(Cursor::new(buf.clone()), 0)).unwrap();
+    PackageEditor::open(&mut container)
+        .unwrap()
+        .update_metadata(|obj| obj.set(METADATA_VERSION, "1.0.1".into()))
+        .unwrap();
+    container.save().unwrap();
+    let file = container.into_inner();
+    let patched = file.0.into_inner();
+    assert_eq!(patched.len(), buf.len());
+    let decoder = Decoder::new(Cursor::new(patched.clone())).unwrap();
+    let table_end = 40 + 24 * decoder.get_main_header().section_num as usize;
+    let metadata = decoder.find_section_by_type(SECTION_TYPE_SD).unwrap();
+    let metadata = *decoder.get_section_header(metadata);
+    let changed: Vec<usize> = (table_end..buf.len()).filter(|i| buf[*i] != patched[*i]).collect();
+    assert!(!changed.is_empty());
+    assert!(changed
+        .iter()
+        .all(|i| (metadata.pointer..metadata.pointer + metadata.csize as u64).contains(&(*i as u64))));
+    assert_eq!(file.1, table_end + metadata.csize as usize);
+    assert_eq!(data_sections(&patched), original);
+    assert_eq!(read_metadata(patched.clone()).version, "1.0.1");
+
+    // Larger: the file is rewritten but data sections are copied as is.
+    let mut container = Container::open(Counting(Cursor::new(patched), 0)).unwrap();
+    let description: String = (0..4000u32).map(|v| (b'a' + (v.wrapping_mul(7919) % 26) as u8) as char).collect();
+    PackageEditor::open(&mut container)
+        .unwrap()
+        .update_metadata(|obj| obj.set(METADATA_DESCRIPTION, description.as_str().into()))
+        .unwrap();
+    container.save().unwrap();
+    let file = container.into_inner();
+    let grown = file.0.into_inner();
+    assert!(grown.len() > buf.len());
+    assert_eq!(file.1, grown.len());
+    assert_eq!(data_sections(&grown), original);
+    let metadata = read_metadata(grown);
+    assert_eq!(metadata.version, "1.0.1");
+    assert_eq!(metadata.description.as_deref(), Some(description.as_str()));
+
+    // A package without metadata gets a metadata section.
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        PackageBuilder::new().build(&mut encoder).unwrap();
+        encoder.save().unwrap();
+    }
+    let mut container = Container::open(Cursor::new(buf)).unwrap();
+    PackageEditor::open(&mut container)
+        .unwrap()
+        .update_metadata(|obj| {
+            obj.set("name", "new".into());
+            obj.set(METADATA_VERSION, "0.1.0".into());
+        })
+        .unwrap();
+    container.save().unwrap();
+    let mut decoder = Decoder::new(Cursor::new(container.into_inner().into_inner())).unwrap();
+    let metadata = PackageDecoder::read(&mut decoder).unwrap().read_typed_metadata().unwrap().unwrap();
+    assert_eq!(metadata, PackageMetadata::new("new", "0.1.0"));
+}
+
+#[test]
+fn section_policy_all_flag_combinations()
+{
+    use std::io::{Cursor, Write};
+
+    use bpx::{
+        builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+        container::Container,
+        policy::{SectionPolicy, SectionRule, ViolationKind},
+        Interface
+    };
+
+    let compressions = [None, Some(CompressionMethod::Xz), Some(CompressionMethod::Zlib)];
+    let checksums = [None, Some(Checksum::Weak), Some(Checksum::Crc32)];
+    let mut combinations = Vec::new();
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        for compression in compressions {
+            for checksum in checksums {
+                let mut builder = SectionHeaderBuilder::new().with_type(combinations.len() as u8);
+                if let Some(v) = compression {
+                    builder = builder.with_compression(v).with_threshold(0);
+                }
+                if let Some(v) = checksum {
+                    builder = builder.with_checksum(v);
+                }
+                let handle = encoder.create_section(builder.build()).unwrap();
+                encoder.open_section(handle).unwrap().write_all(&[7; 4096]).unwrap();
+                combinations.push((compression, checksum));
+            }
+        }
+        encoder.save().unwrap();
+    }
+
+    let decoder = Decoder::new(Cursor::new(buf.clone())).unwrap();
+    for (handle, (compression, checksum)) in decoder.iter_sections().zip(combinations.iter()) {
+        let info = decoder.section_info(handle);
+        assert_eq!(info.compression, *compression);
+        assert_eq!(info.checksum, *checksum);
+        assert_eq!(info.size, 4096);
+        assert_eq!(info.ratio, info.csize as f64 / 4096.0);
+        assert_eq!(info.ratio < 1.0, compression.is_some());
+    }
+
+    // Everything is accepted by default.
+    assert!(decoder.policy_check(&SectionPolicy::default()).is_empty());
+
+    // Require CRC32 and forbid zlib, except for section type 0 which must only be uncompressed.
+    let policy = SectionPolicy::new(SectionRule {
+        min_checksum: Some(Checksum::Crc32),
+        allowed_compression: Some(vec![None, Some(CompressionMethod::Xz)])
+    })
+    .with_type(0, SectionRule {
+        min_checksum: None,
+        allowed_compression: Some(vec![None])
+    });
+    let violations = decoder.policy_check(&policy);
+    let mut expected = Vec::new();
+    for (i, (compression, checksum)) in combinations.iter().enumerate().skip(1) {
+        if *checksum != Some(Checksum::Crc32) {
+            expected.push((i as u32, ViolationKind::Checksum(*checksum, Checksum::Crc32)));
+        }
+        if *compression == Some(CompressionMethod::Zlib) {
+            expected.push((i as u32, ViolationKind::Compression(*compression)));
+        }
+    }
+    let found: Vec<(u32, ViolationKind)> = violations.iter().map(|v| (v.index, v.kind)).collect();
+    assert_eq!(found, expected);
+    assert!(violations.iter().all(|v| v.btype as u32 == v.index));
+    let text: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+    assert_eq!(text[0], "section 1 (type 0x01): checksum weak is weaker than crc32");
+    assert!(text.contains(&String::from("section 7 (type 0x07): compression zlib is not allowed")));
+
+    // The same analysis works on a container.
+    let container = Container::open(Cursor::new(buf)).unwrap();
+    assert_eq!(container.policy_check(&policy), violations);
+}
+
+#[test]
+fn package_pack_in_memory_tree()
+{
+    use std::io::{Cursor, Read};
+
+    use bpx::{
+        error::Error,
+        variant::package::{
+            source::{PackEntry, PackSource, PackSourceTree},
+            utils::{unpack_memory, PackOptions},
+            PackageBuilder,
+            PackageDecoder
+        }
+    };
+
+    struct MemorySource<'a>
+    {
+        name: &'a str,
+        data: &'a [u8],
+        // The size reported to the encoder.
+        size: u64
+    }
+
+    impl<'a> PackSource for MemorySource<'a>
+    {
+        fn name(&self) -> &str
+        {
+            self.name
+        }
+
+        fn size(&self) -> u64
+        {
+            self.size
+        }
+
+        fn open(&mut self) -> bpx::Result<Box<dyn Read + '_>>
+        {
+            Ok(Box::new(self.data))
+        }
+    }
+
+    enum Node
+    {
+        File(&'static str, &'static [u8]),
+        Dir(&'static str, Vec<Node>)
+    }
+
+    struct MemoryTree<'a>(&'a str, &'a [Node]);
+
+    impl<'a> PackSourceTree for MemoryTree<'a>
+    {
+        fn name(&self) -> &str
+        {
+            self.0
+        }
+
+        fn entries(&mut self) -> bpx::Result<Vec<PackEntry<'_>>>
+        {
+            Ok(self
+                .1
+                .iter()
+                .map(|node| match node {
+                    Node::File(name, data) => PackEntry::Source(Box::new(MemorySource {
+                        name,
+                        data,
+                        size: data.len() as u64
+                    })),
+                    Node::Dir(name, children) => PackEntry::Tree(Box::new(MemoryTree(name, children)))
+                })
+                .collect())
+        }
+    }
+
+    let nodes = vec![
+        Node::File("readme.txt", b"hello"),
+        Node::Dir("textures", vec![
+            Node::File("a.png", b"png data"),
+            Node::Dir("empty", Vec::new()),
+            Node::Dir("ui", vec![Node::File("b.png", b"more png data")])
+        ]),
+        Node::File("empty.bin", b"")
+    ];
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+        package.pack_tree(&mut MemoryTree("game", &nodes)).unwrap();
+        let mut single = MemorySource {
+            name: "single",
+            data: b"alone",
+            size: 5
+        };
+        package.pack_source(&mut single).unwrap();
+
+        // The reported size is only enforced on request.
+        let mut lying = MemorySource {
+            name: "lying",
+            data: b"longer than said",
+            size: 4
+        };
+        let options = PackOptions {
+            fail_on_size_change: true
+        };
+        let err = package.pack_source_with(&mut lying, options).unwrap_err();
+        assert!(matches!(err, Error::SourceChanged(ref name, 4, 5) if name == "lying"));
+        package.pack_source(&mut lying).unwrap();
+        encoder.save().unwrap();
+    }
+
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    let table = package.read_object_table().unwrap();
+    let mut objects = Vec::new();
+    for obj in table.get_objects() {
+        let name = package.get_object_name(obj).unwrap().to_string();
+        objects.push((name, unpack_memory(&mut package, obj).unwrap()));
+    }
+    let expected: Vec<(String, Vec<u8>)> = [
+        ("game/readme.txt", &b"hello"[..]),
+        ("game/textures/a.png", b"png data"),
+        ("game/textures/ui/b.png", b"more png data"),
+        ("game/empty.bin", b""),
+        ("single", b"alone"),
+        ("lying", b"longer than said")
+    ]
+    .iter()
+    .map(|(name, data)| (name.to_string(), data.to_vec()))
+    .collect();
+    assert_eq!(objects, expected);
+}
+
+#[test]
+fn streamed_sections_fail_within_one_checkpoint()
+{
+    use std::{
+        convert::TryInto,
+        io::{Cursor, Read}
+    };
+
+    use bpx::{
+        builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+        error::Error,
+        header::SECTION_TYPE_CHECKPOINTS
+    };
+
+    const INTERVAL: usize = 1024;
+    // Incompressible, so that corrupted stored bytes still inflate.
+    let mut seed = 0x2545F491u32;
+    let content: Vec<u8> = (0..16 * INTERVAL + 100)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            (seed >> 24) as u8
+        })
+        .collect();
+    let encode = |compression: Option<CompressionMethod>, interval: u32| {
+        let mut buf = Vec::new();
+        {
+            let mut encoder = Encoder::new(&mut buf).unwrap();
+            encoder.set_checkpoints(interval);
+            let mut builder = SectionHeaderBuilder::new().with_checksum(Checksum::Crc32);
+            if let Some(method) = compression {
+                builder = builder.with_compression(method);
+            }
+            let data = encoder.create_section(builder.with_threshold(0).build()).unwrap();
+            encoder.open_section(data).unwrap().write_all(&content).unwrap();
+            let header = SectionHeaderBuilder::new().with_checksum(Checksum::Crc32).build();
+            let first = encoder.create_section(header).unwrap();
+            encoder.open_section(first).unwrap().write_all(&content[..INTERVAL]).unwrap();
+            encoder.save().unwrap();
+        }
+        buf
+    };
+    // Returns the number of bytes read before the stream ended or failed.
+    let read_stream = |buf: Vec<u8>| {
+        let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+        let handle = decoder.find_section_by_index(0).unwrap();
+        let mut stream = decoder.stream_section(handle).unwrap();
+        let mut block = [0; 256];
+        let mut read = Vec::new();
+        loop {
+            match stream.read(&mut block) {
+                Ok(0) => return (read, None),
+                Ok(len) => read.extend_from_slice(&block[..len]),
+                Err(e) => return (read, Some(e))
+            }
+        }
+    };
+
+    // The checkpoints section holds the CRC32 of every chunk of the other sections.
+    let mut decoder = Decoder::new(Cursor::new(encode(None, INTERVAL as u32))).unwrap();
+    let handle = decoder.find_section_by_type(SECTION_TYPE_CHECKPOINTS).unwrap();
+    assert_eq!(decoder.get_section_index(handle), 2);
+    let data = decoder.open_section(handle).unwrap().load_in_memory().unwrap();
+    let values: Vec<u32> = data
+        .chunks(4)
+        .map(|v| u32::from_le_bytes(v.try_into().unwrap()))
+        .collect();
+    assert_eq!(values.len(), 1 + 2 + 17 + 2 + 1);
+    assert_eq!(values[..3], [INTERVAL as u32, 0, 17]);
+    assert_eq!(values[20..22], [1, 1]);
+    let first = decoder.find_section_by_index(1).unwrap();
+    let first = decoder.get_section_header(first).chksum;
+    assert_eq!((values[3], values[22]), (first, first));
+    let decoder = Decoder::new(Cursor::new(encode(None, 0))).unwrap();
+    assert!(decoder.find_section_by_type(SECTION_TYPE_CHECKPOINTS).is_none());
+
+    // The running checksum of the first chunk is the checksum of the section holding it alone.
+    let mut decoder = Decoder::new(Cursor::new(encode(Some(CompressionMethod::Zlib), 0))).unwrap();
+    let handle = decoder.find_section_by_index(0).unwrap();
+    let expected = decoder.get_section_header(handle).chksum;
+    let mut stream = decoder.stream_section(handle).unwrap();
+    stream.read_exact(&mut [0; INTERVAL]).unwrap();
+    assert_eq!(stream.checksum_so_far(), first);
+    std::io::copy(&mut stream, &mut std::io::sink()).unwrap();
+    assert_eq!(stream.checksum_so_far(), expected);
+
+    for compression in [None, Some(CompressionMethod::Zlib), Some(CompressionMethod::Xz)] {
+        let corrupt = |interval: u32| {
+            let mut buf = encode(compression, interval);
+            let decoder = Decoder::new(Cursor::new(&buf)).unwrap();
+            let header = *decoder.get_section_header(decoder.find_section_by_index(0).unwrap());
+            assert_eq!(header.compression(), compression);
+            buf[header.pointer as usize + header.csize as usize / 2] ^= 0x10;
+            buf
+        };
+        let (read, err) = read_stream(encode(compression, INTERVAL as u32));
+        assert!(err.is_none());
+        assert_eq!(read, content);
+
+        // Checkpointed sections fail at the end of the corrupted chunk...
+        let (read, err) = read_stream(corrupt(INTERVAL as u32));
+        let err = err.unwrap();
+        let err = err.get_ref().unwrap().downcast_ref::<Error>().unwrap();
+        assert!(err.is_checksum_mismatch(), "{}", err);
+        assert!(read.len().abs_diff(content.len() / 2) < 2 * INTERVAL, "{}", read.len());
+        // Bytes of the corrupted chunk are returned before it is verified.
+        let verified = read.len() / INTERVAL * INTERVAL;
+        assert!(read[..verified] == content[..verified]);
+        if compression.is_none() {
+            let end = (content.len() / 2 / INTERVAL + 1) * INTERVAL;
+            assert!(read.len() < end && read.len() >= end - 256);
+        }
+
+        // ...other sections fail once the whole section was read.
+        let (read, err) = read_stream(corrupt(0));
+        assert!(err.is_some());
+        assert!(read.len() > content.len() - INTERVAL, "{}", read.len());
+    }
+}
+
+#[test]
+fn missing_variant_sections_list_found_sections()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        builder::SectionHeaderBuilder,
+        error::Error,
+        header::SECTION_TYPE_STRING,
+        variant::{
+            package::{PackageDecoder, PackageTypeExt},
+            texture::{PixelFormat, TextureBuilder}
+        }
+    };
+
+    // A plain BPX has the package type byte but none of its sections.
+    let mut buf = Vec::new();
+    Encoder::new(&mut buf).unwrap().save().unwrap();
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let err = PackageDecoder::read(&mut decoder).err().unwrap();
+    assert!(err.is_corruption());
+    assert!(matches!(err, Error::MissingSection { expected: SECTION_TYPE_STRING, ref found } if found.is_empty()));
+    assert_eq!(
+        err.to_string(),
+        "missing required section of type 0xff (found: no section), was this BPX created by the encoder of its variant?"
+    );
+
+    // A texture, with an extra data section, relabeled as a package.
+    let mut decoder = roundtrip_container(|encoder| {
+        {
+            let mut texture = TextureBuilder::new(PixelFormat::R8, 2, 2)
+                .with_mip_count(2)
+                .build(encoder)
+                .unwrap();
+            texture.add_mip(0, &[0; 4]).unwrap();
+            texture.add_mip(1, &[0; 1]).unwrap();
+        }
+        encoder
+            .create_section(SectionHeaderBuilder::new().with_type(0x1).build())
+            .unwrap();
+        let mut header = *encoder.get_main_header();
+        header.btype = b'P';
+        header.type_ext = PackageTypeExt::from_type_ext(&[0; 16]).unwrap().to_type_ext();
+        encoder.set_main_header(header);
+    });
+    let err = PackageDecoder::read(&mut decoder).err().unwrap();
+    match &err {
+        Error::MissingSection { expected, found } => {
+            assert_eq!(*expected, SECTION_TYPE_STRING);
+            assert_eq!(*found, [(0x1, 2), (0x2, 1)]);
+        },
+        _ => panic!("{}", err)
+    }
+    assert!(err.to_string().contains("(found: 0x01 x2, 0x02 x1)"));
+}
+
+#[test]
+fn variant_encoders_use_section_presets()
+{
+    use bpx::{
+        builder::SectionHeaderBuilder,
+        header::{SectionHeader, SECTION_TYPE_SD, SECTION_TYPE_STRING},
+        sd::Object,
+        variant::{
+            package::{PackageBuilder, SECTION_TYPE_DATA, SECTION_TYPE_OBJECT_TABLE},
+            texture::{self, PixelFormat, TextureBuilder}
+        }
+    };
+
+    fn same_config(header: &SectionHeader, preset: SectionHeaderBuilder)
+    {
+        let preset = preset.build();
+        assert_eq!(header.btype, preset.btype);
+        assert_eq!(header.checksum(), preset.checksum());
+        assert_eq!(header.compression(), preset.compression());
+    }
+
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    {
+        let mut package = PackageBuilder::new()
+            .with_metadata(Object::new())
+            .build(&mut encoder)
+            .unwrap();
+        package.pack_object("file", &mut &b"data"[..]).unwrap();
+    }
+    let header = |btype| *encoder.get_section_header(encoder.find_section_by_type(btype).unwrap());
+    same_config(&header(SECTION_TYPE_STRING), SectionHeaderBuilder::strings());
+    same_config(
+        &header(SECTION_TYPE_OBJECT_TABLE),
+        SectionHeaderBuilder::strings().with_type(SECTION_TYPE_OBJECT_TABLE)
+    );
+    same_config(&header(SECTION_TYPE_SD), SectionHeaderBuilder::structured_data());
+    same_config(&header(SECTION_TYPE_DATA), SectionHeaderBuilder::bulk_data().with_type(SECTION_TYPE_DATA));
+
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    TextureBuilder::new(PixelFormat::R8, 1, 1)
+        .build(&mut encoder)
+        .unwrap()
+        .add_mip(0, &[0])
+        .unwrap();
+    let header = |btype| *encoder.get_section_header(encoder.find_section_by_type(btype).unwrap());
+    same_config(
+        &header(texture::SECTION_TYPE_MIP_TABLE),
+        SectionHeaderBuilder::strings().with_type(texture::SECTION_TYPE_MIP_TABLE)
+    );
+    same_config(
+        &header(texture::SECTION_TYPE_DATA),
+        SectionHeaderBuilder::bulk_data().with_type(texture::SECTION_TYPE_DATA)
+    );
+}
+
+#[test]
+fn random_sd_objects_round_trip()
+{
+    use bpx::{sd::Object, testing::random_object};
+
+    for seed in 0..64 {
+        let obj = random_object(4, seed);
+        let mut buf = Vec::new();
+        obj.write(&mut buf).unwrap();
+        assert_eq!(Object::read(&mut buf.as_slice()).unwrap(), obj, "seed {}", seed);
+    }
+}
+
+#[test]
+fn compression_preserves_sections()
+{
+    use std::io::Write;
+
+    use bpx::{
+        builder::{Checksum, CompressionMethod, SectionHeaderBuilder},
+        encoder::Encoder,
+        testing::assert_sections_equal
+    };
+
+    let build = |compression: Option<CompressionMethod>| {
+        move |encoder: &mut Encoder<Vec<u8>>| {
+            for (i, size) in [0usize, 17, 4096, 200000].iter().enumerate() {
+                let mut builder = SectionHeaderBuilder::new()
+                    .with_type(i as u8 + 1)
+                    .with_checksum(Checksum::Crc32);
+                if let Some(method) = compression {
+                    builder = builder.with_compression(method).with_threshold(0);
+                }
+                let handle = encoder.create_section(builder.build()).unwrap();
+                let data: Vec<u8> = (0..*size).map(|v| (v % 251) as u8).collect();
+                encoder.open_section(handle).unwrap().write_all(&data).unwrap();
+            }
+        }
+    };
+    let mut stored = roundtrip_container(build(None));
+    let mut zlib = roundtrip_container(build(Some(CompressionMethod::Zlib)));
+    let mut xz = roundtrip_container(build(Some(CompressionMethod::Xz)));
+    assert_sections_equal(&mut stored, &mut zlib);
+    assert_sections_equal(&mut stored, &mut xz);
+}
+
+#[test]
+fn package_path_tree_round_trip()
+{
+    use bpx::{
+        testing::random_tree,
+        variant::package::{source::PathTree, utils::unpack, PackageBuilder, PackageDecoder}
+    };
+
+    let root = std::env::temp_dir().join(format!("bpx_path_tree_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let files = random_tree(&root.join("src"), 24, 8192);
+    let mut decoder = roundtrip_container(|encoder| {
+        let mut package = PackageBuilder::new().build(encoder).unwrap();
+        package.pack_tree(&mut PathTree::with_name("tree", &root.join("src"))).unwrap();
+    });
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    unpack(&mut package, &root.join("dst")).unwrap();
+    for (path, data) in &files {
+        assert_eq!(&std::fs::read(root.join("dst/tree").join(path)).unwrap(), data, "{}", path);
+    }
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "tempfile")]
+#[test]
+fn memory_budget_spills_sections_to_disk()
+{
+    use bpx::{
+        error::Error,
+        testing::random_object,
+        variant::package::{utils::unpack_memory, PackageBuilder, PackageDecoder},
+        Interface
+    };
+
+    let objects: Vec<(String, Vec<u8>)> = (0..8u32)
+        .map(|i| (format!("obj{}", i), (0..65536u32).map(|v| (v * (i + 3) % 251) as u8).collect()))
+        .collect();
+    let metadata = random_object(2, 7);
+    let decoder = roundtrip_container(|encoder| {
+        let mut package = PackageBuilder::new()
+            .with_split_size(65536)
+            .with_metadata(metadata.clone())
+            .build(encoder)
+            .unwrap();
+        for (name, data) in &objects {
+            package.pack_object(name, &mut data.as_slice()).unwrap();
+        }
+    });
+    let limit = 80000;
+    let mut decoder = decoder.with_memory_budget(limit);
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    assert_eq!(package.read_metadata().unwrap().unwrap(), metadata);
+    let mut table = package.read_object_table().unwrap();
+    table.build_lookup_table(&mut package).unwrap();
+    for (name, data) in &objects {
+        let obj = *table.find_object(name).unwrap().unwrap();
+        assert_eq!(&unpack_memory(&mut package, &obj).unwrap(), data);
+    }
+    drop(package);
+    // Every data section was loaded but most of them live in temporary files.
+    assert!(decoder.memory_usage() <= limit);
+    let loaded: usize = decoder.iter_sections().map(|h| decoder.get_section_header(h).size as usize).sum();
+    assert!(loaded > 4 * limit);
+
+    // Copying a whole data section into memory is refused.
+    let handle = decoder.iter_sections().max_by_key(|h| decoder.get_section_header(*h).size).unwrap();
+    let size = decoder.get_section_header(handle).size as usize;
+    let mut section = decoder.open_section(handle).unwrap();
+    let err = section.load_in_memory().unwrap_err();
+    let err = err.get_ref().unwrap().downcast_ref::<Error>().unwrap();
+    assert!(matches!(err, Error::MemoryBudget(v, _) if *v == size));
+    assert!(err.is_capacity());
+}
+
+#[test]
+fn string_interner_reports_duplication()
+{
+    use bpx::{
+        builder::SectionHeaderBuilder,
+        strings::{string_duplication_report, StringInterner, StringSection},
+        variant::package::{PackageBuilder, PackageDecoder}
+    };
+
+    let names = ["textures/a.png", "textures/b.png", "textures/a.png"];
+    let symbols = ["textures/a.png", "main"];
+    let mut decoder = roundtrip_container(|encoder| {
+        let mut package = PackageBuilder::new().build(encoder).unwrap();
+        for name in &names {
+            package.pack_object(name, &mut name.as_bytes()).unwrap();
+        }
+        let mut interner = package.into_interner();
+        let handle = encoder.create_section(SectionHeaderBuilder::strings().build()).unwrap();
+        for symbol in &symbols {
+            interner.intern(encoder, handle, symbol).unwrap();
+        }
+        assert_eq!(interner.stats().strings, 5);
+        assert_eq!(interner.stats().written_bytes, 50);
+        assert_eq!(interner.stats().saved_bytes, 15);
+        assert_eq!(interner.stats().cross_section_bytes, 15);
+        // Writing without the interner duplicates the string within the section.
+        StringSection::new(handle).put(encoder, "main").unwrap();
+    });
+    let report = string_duplication_report(&mut decoder).unwrap();
+    assert_eq!(report.sections, 2);
+    assert_eq!(report.strings, 5);
+    assert_eq!(report.total_bytes, 55);
+    assert_eq!(report.duplicate_bytes, 5);
+    assert_eq!(report.cross_section_bytes, 15);
+    assert_eq!(report.wasted_bytes(), 20);
+
+    // Deduplicated names still resolve to the right objects.
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    let table = package.read_object_table().unwrap();
+    let objects: Vec<_> = table.get_objects().to_vec();
+    assert_eq!(objects.len(), 3);
+    assert_eq!(objects[0].name, objects[2].name);
+    for (obj, name) in objects.iter().zip(&names) {
+        assert_eq!(package.get_object_name(obj).unwrap(), *name);
+    }
+
+    // An interner loaded from the section reuses its strings.
+    let mut interner = StringInterner::new();
+    let strings = decoder.find_all_sections_of_type(bpx::header::SECTION_TYPE_STRING)[1];
+    interner.load(&mut decoder, strings).unwrap();
+    assert_eq!(interner.address(strings, "main"), Some(15));
+}
+
+#[test]
+fn save_to_path_keeps_destination_on_failure()
+{
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+    use bpx::{
+        builder::SectionHeaderBuilder,
+        container::Container,
+        testing::assert_sections_equal,
+        utils::write_atomically
+    };
+
+    // A backend failing to read past a given position, as a disk going away mid-save.
+    struct FailingBackend
+    {
+        inner: Cursor<Vec<u8>>,
+        fail_after: u64
+    }
+
+    impl Read for FailingBackend
+    {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize>
+        {
+            if self.inner.position() >= self.fail_after {
+                return Err(std::io::Error::other("backend failure"));
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    impl Write for FailingBackend
+    {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>
+        {
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()>
+        {
+            Ok(())
+        }
+    }
+
+    impl Seek for FailingBackend
+    {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64>
+        {
+            self.inner.seek(pos)
+        }
+    }
+
+    let dir = std::env::temp_dir().join(format!("bpx_save_to_path_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let dest = dir.join("out.bpx");
+    std::fs::write(&dest, b"original").unwrap();
+
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    for i in 0..3u8 {
+        let handle = encoder.create_section(SectionHeaderBuilder::new().with_type(i).build()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&[i; 65536]).unwrap();
+    }
+    encoder.save().unwrap();
+    let bpx = encoder.into_inner();
+
+    let backend = FailingBackend {
+        fail_after: bpx.len() as u64 / 2,
+        inner: Cursor::new(bpx.clone())
+    };
+    let mut container = Container::open(backend).unwrap();
+    assert!(container.save_to_path(&dest).is_err());
+    let res = write_atomically(&dest, |file| {
+        file.write_all(b"partial")?;
+        Err(bpx::error::Error::Truncation("test"))
+    });
+    assert!(res.is_err());
+    // The destination is untouched and no temporary file is left behind.
+    assert_eq!(std::fs::read(&dest).unwrap(), b"original");
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    let mut container = Container::open(Cursor::new(bpx.clone())).unwrap();
+    let handle = container.find_section_by_index(1).unwrap();
+    container.sections().open(handle).unwrap().write_all(b"edited").unwrap();
+    container.save_to_path(&dest).unwrap();
+    let mut copy = Decoder::open_file(&dest).unwrap();
+    assert_sections_equal(&mut container, &mut copy);
+    let mut original = Decoder::new(Cursor::new(bpx)).unwrap();
+    encoder = Encoder::new(Vec::new()).unwrap();
+    for handle in original.iter_sections().collect::<Vec<_>>() {
+        let header = *original.get_section_header(handle);
+        let data = original.open_section(handle).unwrap().load_in_memory().unwrap();
+        let copy = encoder.create_section(SectionHeaderBuilder::new().with_type(header.btype).build()).unwrap();
+        encoder.open_section(copy).unwrap().write_all(&data).unwrap();
+    }
+    encoder.save_to_path(&dest).unwrap();
+    assert_sections_equal(&mut original, &mut Decoder::open_file(&dest).unwrap());
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn instrumentation_reports_section_events()
+{
+    use std::{
+        io::{Cursor, Write},
+        sync::{Arc, Mutex}
+    };
+
+    use bpx::{
+        builder::SectionHeaderBuilder,
+        instrument::{BasicStats, Event, Instrumentation}
+    };
+
+    #[derive(Default)]
+    struct Recorder
+    {
+        events: Mutex<Vec<Event>>
+    }
+
+    impl Instrumentation for Recorder
+    {
+        fn on_event(&self, event: Event)
+        {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    impl Recorder
+    {
+        fn count<F: Fn(&Event) -> bool>(&self, filter: F) -> usize
+        {
+            self.events.lock().unwrap().iter().filter(|e| filter(e)).count()
+        }
+    }
+
+    let recorder = Arc::new(Recorder::default());
+    let stats = Arc::new(BasicStats::new());
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    encoder.set_instrumentation(Some(recorder.clone()));
+    let headers = [
+        SectionHeaderBuilder::bulk_data().build(),
+        SectionHeaderBuilder::new().with_size(64).build(),
+        SectionHeaderBuilder::strings().build()
+    ];
+    for (i, header) in headers.iter().enumerate() {
+        let handle = encoder.create_section(*header).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&[i as u8; 64]).unwrap();
+    }
+    encoder.save().unwrap();
+    // Sections created without a size hint go to a temporary file.
+    let spills = if cfg!(feature = "tempfile") { 2 } else { 0 };
+    assert_eq!(recorder.count(|e| matches!(e, Event::Spill { .. })), spills);
+    assert_eq!(recorder.count(|e| matches!(e, Event::CompressStart { .. })), 3);
+    assert_eq!(recorder.count(|e| matches!(e, Event::CompressEnd { size: 64, .. })), 3);
+    // The section without a checksum reports no checksum event.
+    assert_eq!(recorder.count(|e| matches!(e, Event::Checksum { bytes: 64, .. })), 2);
+    let file_size = encoder.get_main_header().file_size;
+    assert_eq!(recorder.count(|e| *e == Event::BackendWrite { bytes: file_size }), 1);
+    assert_eq!(recorder.events.lock().unwrap().len(), spills + 9);
+
+    // Installing nothing reports nothing.
+    encoder.set_instrumentation(None);
+    encoder.save().unwrap();
+    assert_eq!(recorder.events.lock().unwrap().len(), spills + 9);
+
+    let mut decoder = Decoder::new(Cursor::new(encoder.into_inner())).unwrap();
+    decoder.set_instrumentation(Some(stats.clone()));
+    let handles: Vec<_> = decoder.iter_sections().collect();
+    decoder.load_sections(&handles[..2], 2).unwrap();
+    decoder.open_section(handles[2]).unwrap();
+    decoder.open_section(handles[0]).unwrap();
+    let stats = stats.get();
+    assert_eq!(stats.compressed_sections, 0);
+    assert_eq!(stats.decompressed_sections, 3);
+    assert_eq!(stats.decompress_output_bytes, 192);
+    assert_eq!(stats.checksum_bytes, 128);
+    assert_eq!(stats.backend_read_bytes, file_size - 40 - 3 * 24);
+    assert_eq!(stats.spills, 0);
+}
+
+#[test]
+#[cfg(feature = "tempfile")]
+fn temp_storage_falls_back_to_memory()
+{
+    use std::io::{Cursor, Write};
+
+    use bpx::{builder::SectionHeaderBuilder, error::Error, section::TempStorage, testing::assert_sections_equal};
+
+    let dir = std::env::temp_dir().join(format!("bpx_missing_{}", std::process::id())).join("tmp");
+    let storage = TempStorage::new().with_dir(&dir).with_memory_fallback(1 << 20);
+    let mut encoder = Encoder::new(Vec::new()).unwrap().with_temp_storage(storage.clone());
+    for i in 0..4u8 {
+        let handle = encoder.create_section(SectionHeaderBuilder::new().with_type(i).build()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&[i; 4096]).unwrap();
+    }
+    assert_eq!(storage.fallback_usage(), 4 * 4096);
+    encoder.save().unwrap();
+    let bpx = encoder.into_inner();
+    assert_eq!(storage.fallback_usage(), 0);
+
+    // Sections over the budget of a decoder cannot fall back to memory.
+    let mut decoder = Decoder::new(Cursor::new(bpx.clone()))
+        .unwrap()
+        .with_memory_budget(6000)
+        .with_temp_storage(storage.clone());
+    let handles: Vec<_> = decoder.iter_sections().collect();
+    decoder.open_section(handles[0]).unwrap();
+    let err = decoder.open_section(handles[1]).map(|_| ()).unwrap_err();
+    let err = err.as_io().unwrap().get_ref().unwrap().downcast_ref::<Error>().unwrap();
+    assert!(matches!(err, Error::TempFile { dir: d, .. } if *d == dir));
+    let message = err.to_string();
+    assert!(message.contains(&dir.display().to_string()));
+    assert!(message.contains("TempStorage::in_memory()"));
+
+    // The staging buffer of a save only falls back when the sections fit.
+    let storage = TempStorage::new().with_dir(&dir).with_memory_fallback(5000);
+    let mut encoder = Encoder::new(Vec::new()).unwrap().with_temp_storage(storage);
+    for i in 0..2u8 {
+        let handle = encoder.create_section(SectionHeaderBuilder::new().with_size(4096).build()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&[i; 4096]).unwrap();
+    }
+    let err = encoder.save().unwrap_err();
+    let err = err.as_io().unwrap().get_ref().unwrap().downcast_ref::<Error>().unwrap();
+    assert!(matches!(err, Error::TempFile { .. }));
+
+    let mut decoder = Decoder::new(Cursor::new(bpx.clone()))
+        .unwrap()
+        .with_temp_storage(TempStorage::in_memory());
+    assert_sections_equal(&mut decoder, &mut Decoder::new(Cursor::new(bpx)).unwrap());
+    assert!(!dir.exists());
+}
+
+#[test]
+fn kvstore_round_trip()
+{
+    use std::io::Cursor;
+
+    use bpx::variant::kvstore::{key_hash, KvReader, KvWriter, SECTION_TYPE_DATA};
+
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    {
+        let mut store = KvWriter::create(&mut encoder).unwrap().with_split_size(64);
+        store.put("empty", b"").unwrap();
+        store.put("config/name", b"bpx").unwrap();
+        store.put("blob", &[7; 200]).unwrap();
+        store.put("config/name", b"bpx-rs").unwrap();
+        assert_eq!(store.len(), 3);
+        store.finish().unwrap();
+    }
+    encoder.save().unwrap();
+
+    let mut decoder = Decoder::new(Cursor::new(encoder.into_inner())).unwrap();
+    // The value larger than the split size has a data section of its own.
+    let data_sections = decoder
+        .iter_sections()
+        .filter(|h| decoder.get_section_header(*h).btype == SECTION_TYPE_DATA);
+    assert_eq!(data_sections.count(), 3);
+    let mut store = KvReader::read(&mut decoder).unwrap();
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.get("empty").unwrap().unwrap(), b"");
+    assert_eq!(store.get("config/name").unwrap().unwrap(), b"bpx-rs");
+    assert_eq!(store.get("blob").unwrap().unwrap(), [7; 200]);
+    assert!(store.get("config").unwrap().is_none());
+    let entry = store.get_entry("blob").unwrap().unwrap();
+    assert_eq!((entry.hash, entry.size), (key_hash("blob"), 200));
+}
+
+#[test]
+fn kvstore_point_lookups()
+{
+    use std::io::Cursor;
+
+    use bpx::variant::kvstore::{KvReader, KvWriter};
+
+    const KEYS: u32 = 100000;
+
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    {
+        let mut store = KvWriter::create(&mut encoder).unwrap().with_split_size(1 << 20);
+        for i in 0..KEYS {
+            store.put(&format!("key-{}", i), &i.to_le_bytes()).unwrap();
+        }
+        store.finish().unwrap();
+    }
+    encoder.save().unwrap();
+
+    let mut decoder = Decoder::new(Cursor::new(encoder.into_inner())).unwrap();
+    let mut store = KvReader::read(&mut decoder).unwrap();
+    assert_eq!(store.len(), KEYS as usize);
+    for i in (0..KEYS).step_by(7) {
+        let value = store.get(&format!("key-{}", i)).unwrap().unwrap();
+        assert_eq!(value, i.to_le_bytes());
+    }
+    assert!(store.get(&format!("key-{}", KEYS)).unwrap().is_none());
+    assert!(store.get("").unwrap().is_none());
+}
+
+#[test]
+fn package_object_writer_irregular_chunks()
+{
+    use std::io::Write;
+
+    use bpx::{
+        error::Error,
+        variant::package::{utils::unpack_memory, PackageBuilder, PackageDecoder, SECTION_TYPE_DATA}
+    };
+
+    let objects: Vec<(&str, Vec<u8>)> = vec![
+        ("small", (0..10000u32).map(|v| (v % 239) as u8).collect()),
+        ("empty", Vec::new()),
+        ("split", (0..20000u32).map(|v| (v % 17) as u8).collect()),
+        ("tail", b"tail".to_vec())
+    ];
+    let sections = |decoder: &Decoder<_>| -> Vec<u32> {
+        decoder
+            .find_all_sections_of_type(SECTION_TYPE_DATA)
+            .into_iter()
+            .map(|v| decoder.get_section_header(v).size)
+            .collect()
+    };
+    let reference = roundtrip_container(|encoder| {
+        let mut package = PackageBuilder::new().with_split_size(16384).build(encoder).unwrap();
+        for (name, data) in &objects {
+            package.pack_object(name, &mut data.as_slice()).unwrap();
+        }
+    });
+    let mut decoder = roundtrip_container(|encoder| {
+        let mut package = PackageBuilder::new().with_split_size(16384).build(encoder).unwrap();
+        for (name, data) in &objects {
+            let mut writer = package.begin_object(name, Some(data.len() as u64));
+            let mut rest = data.as_slice();
+            let mut chunk = 1;
+            while !rest.is_empty() {
+                let len = chunk.min(rest.len());
+                writer.write_all(&rest[..len]).unwrap();
+                rest = &rest[len..];
+                chunk = chunk * 7 % 1009 + 1;
+            }
+            assert_eq!(writer.size(), data.len() as u64);
+            writer.finish().unwrap();
+        }
+        let mut writer = package.begin_object("changed", Some(3));
+        writer.write_all(b"ab").unwrap();
+        assert!(matches!(writer.finish().unwrap_err(), Error::SourceChanged(_, 3, 2)));
+    });
+    // Chunked writes produce the same layout as the pack functions, across the split boundary;
+    // the rejected object leaves its 2 bytes unreferenced.
+    let mut expected = sections(&reference);
+    assert_eq!(expected, [8192, 10000, 8192, 3620]);
+    expected[3] += 2;
+    assert_eq!(sections(&decoder), expected);
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    let table = package.read_object_table().unwrap();
+    assert_eq!(table.get_objects().len(), objects.len());
+    for (obj, (_, data)) in table.get_objects().iter().zip(&objects) {
+        assert_eq!(&unpack_memory(&mut package, obj).unwrap(), data);
+    }
+    assert_eq!(package.summary().unwrap().total_size, 30004);
+}
+
+#[test]
+fn read_section_range_on_compressed_sections()
+{
+    use std::io::Seek;
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        strings::StringSection
+    };
+
+    let data: Vec<u8> = (0..100000u32).map(|v| (v % 241) as u8).collect();
+    let mut strings = None;
+    let mut decoder = roundtrip_container(|encoder| {
+        let header = SectionHeaderBuilder::new()
+            .with_type(1)
+            .with_compression(CompressionMethod::Zlib)
+            .build();
+        let handle = encoder.create_section(header).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&data).unwrap();
+        let handle = encoder.create_section(SectionHeaderBuilder::new().with_type(2).build()).unwrap();
+        let mut section = StringSection::new(handle);
+        let long = "s".repeat(200);
+        strings = Some((section.put(encoder, "short").unwrap(), section.put(encoder, &long).unwrap(), long));
+    });
+    let handle = decoder.find_section_by_type(1).unwrap();
+    let mut buf = [0; 300];
+    assert_eq!(decoder.read_section_range(handle, 60000, &mut buf).unwrap(), 300);
+    assert_eq!(&buf[..], &data[60000..60300]);
+    assert_eq!(decoder.read_section_range(handle, 99700, &mut buf).unwrap(), 300);
+    assert_eq!(&buf[..], &data[99700..]);
+    let err = decoder.read_section_range(handle, 99701, &mut buf).unwrap_err();
+    assert!(err.is_corruption());
+    assert!(decoder.read_section_range(handle, u64::MAX, &mut buf).is_err());
+    assert_eq!(decoder.read_section_range(handle, 100000, &mut []).unwrap(), 0);
+    // The section is still opened at the start of its data.
+    assert_eq!(decoder.open_section(handle).unwrap().stream_position().unwrap(), 0);
+
+    let (short, long_address, long) = strings.unwrap();
+    let mut section = StringSection::new(decoder.find_section_by_type(2).unwrap());
+    assert_eq!(section.get(&mut decoder, long_address).unwrap(), long);
+    assert_eq!(section.get(&mut decoder, short).unwrap(), "short");
+    assert!(section.get(&mut decoder, long_address + 300).is_err());
+}
+
+fn read_fixture(name: &str) -> Vec<u8>
+{
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("failed to read fixture {:?}: {}", path, e))
+}
+
+#[test]
+fn fixture_sections_decodes()
+{
+    use std::io::Cursor;
+
+    use bpx::builder::{Checksum, CompressionMethod};
+
+    let mut decoder = Decoder::new(Cursor::new(read_fixture("sections.bpx"))).unwrap();
+    let main_header = decoder.get_main_header();
+    assert_eq!(main_header.btype, b'X');
+    assert_eq!(main_header.version, BPX_CURRENT_VERSION);
+    assert_eq!(main_header.type_ext.as_slice(), &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+    let expected = [
+        (1, None, None, 0, b"uncompressed".to_vec()),
+        (
+            2,
+            Some(CompressionMethod::Xz),
+            Some(Checksum::Crc32),
+            0,
+            (0..8192u32).map(|v| (v % 251) as u8).collect()
+        ),
+        (
+            3,
+            Some(CompressionMethod::Zlib),
+            Some(Checksum::Weak),
+            0,
+            (0..8192u32).map(|v| (v * 7 % 256) as u8).collect()
+        ),
+        (4, None, Some(Checksum::Crc32), 0x42, b"checksum".to_vec())
+    ];
+    for (btype, compression, checksum, user_tag, data) in expected {
+        let handle = decoder.find_section_by_type(btype).unwrap();
+        let header = decoder.get_section_header(handle);
+        assert_eq!((header.compression(), header.checksum(), header.user_tag), (compression, checksum, user_tag));
+        assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), data);
+    }
+    let first = decoder.find_section_by_name("first").unwrap().unwrap();
+    assert_eq!(decoder.get_section_header(first).btype, 1);
+    assert_eq!(decoder.build_id().unwrap(), Some(0x0123456789ABCDEF));
+}
+
+#[test]
+fn fixture_structured_decodes()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        header::{SECTION_TYPE_SD, SECTION_TYPE_STRING},
+        sd::{Object, Value},
+        strings::StringSection
+    };
+
+    let mut decoder = Decoder::new(Cursor::new(read_fixture("structured.bpx"))).unwrap();
+    let mut strings = StringSection::new(decoder.find_section_by_type(SECTION_TYPE_STRING).unwrap());
+    assert_eq!(strings.get(&mut decoder, 0).unwrap(), "first string");
+    assert_eq!(strings.get(&mut decoder, 13).unwrap(), "second string");
+    let handle = decoder.find_section_by_type(SECTION_TYPE_SD).unwrap();
+    let obj = Object::read(&mut decoder.open_section(handle).unwrap()).unwrap();
+    assert_eq!(obj.prop_count(), 8);
+    assert_eq!(obj.get("name"), Some(&Value::from("fixture")));
+    assert_eq!(obj.get("count"), Some(&Value::from(42u32)));
+    assert_eq!(obj.get("big"), Some(&Value::from(0x0123456789ABCDEFu64)));
+    assert_eq!(obj.get("ratio"), Some(&Value::from(0.5f64)));
+    assert_eq!(obj.get("scale"), Some(&Value::from(2.5f32)));
+    assert_eq!(obj.get("byte"), Some(&Value::from(0xFFu8)));
+    match obj.get("array") {
+        Some(Value::Array(array)) => {
+            assert_eq!(array.len(), 3);
+            assert_eq!(array[0], Value::from(true));
+            assert_eq!(array[1], Value::Null);
+            assert_eq!(array[2], Value::from("item"));
+        },
+        v => panic!("unexpected array {:?}", v)
+    }
+    match obj.get("nested") {
+        Some(Value::Object(nested)) => assert_eq!(nested.get("negative"), Some(&Value::from(-1i32))),
+        v => panic!("unexpected nested object {:?}", v)
+    }
+}
+
+#[test]
+fn fixture_package_decodes()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        sd::Value,
+        variant::package::{utils::unpack_memory, Architecture, PackageDecoder, Platform, SECTION_TYPE_DATA}
+    };
+
+    let mut decoder = Decoder::new(Cursor::new(read_fixture("package.bpxp"))).unwrap();
+    assert_eq!(decoder.find_all_sections_of_type(SECTION_TYPE_DATA).len(), 3);
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    assert_eq!(package.get_variant(), *b"PH");
+    assert_eq!(package.get_architecture(), Architecture::Any);
+    assert_eq!(package.get_platform(), Platform::Any);
+    let metadata = package.read_metadata().unwrap().unwrap();
+    assert_eq!(metadata.get("version"), Some(&Value::from(1u32)));
+    let summary = package.summary().unwrap();
+    assert_eq!((summary.file_count, summary.total_size), (3, 20009));
+    let table = package.read_object_table().unwrap();
+    let large: Vec<u8> = (0..20000u32).map(|v| (v % 13) as u8).collect();
+    let expected: [(&str, &[u8]); 3] = [("empty", b""), ("dir/small.txt", b"hello bpx"), ("dir/large.bin", &large)];
+    assert_eq!(table.get_objects().len(), expected.len());
+    for (obj, (name, data)) in table.get_objects().iter().zip(expected) {
+        assert_eq!(package.get_object_name(obj).unwrap(), name);
+        assert_eq!(unpack_memory(&mut package, obj).unwrap(), data);
+    }
+}
+
+#[test]
+fn fixtures_reproduced_byte_for_byte()
+{
+    use bpx::testing::FIXTURES;
+
+    for fixture in FIXTURES {
+        assert!(
+            fixture.generate() == read_fixture(fixture.name),
+            "{} differs from the encoder output, run `cargo run --example regen_fixtures` only if the format \
+             was intentionally changed",
+            fixture.name
+        );
+    }
+}
+
+#[test]
+fn decoder_embedded_concatenation()
+{
+    use std::io::{Cursor, Write};
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        decoder::Embedded,
+        error::Error
+    };
+
+    let build = |fill: u8, digest: bool| {
+        let mut encoder = Encoder::new(Vec::new()).unwrap();
+        encoder.set_file_digest(digest);
+        let header = SectionHeaderBuilder::new().with_compression(CompressionMethod::Zlib).with_threshold(0);
+        let handle = encoder.create_section(header.build()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&[fill; 1000]).unwrap();
+        encoder.save().unwrap();
+        encoder.into_inner()
+    };
+    let (first, second) = (build(1, false), build(2, true));
+    let mut stream = b"prefix".to_vec();
+    stream.extend_from_slice(&first);
+    stream.extend_from_slice(&second);
+
+    let mut offset = 6;
+    for (data, fill) in [(&first, 1), (&second, 2)] {
+        let mut decoder = Decoder::new_embedded(Cursor::new(&stream), offset).unwrap();
+        assert_eq!(decoder.bytes_consumed(), data.len() as u64);
+        assert_eq!(decoder.trailing_bytes(), stream.len() as u64 - offset - data.len() as u64);
+        assert_eq!(decoder.verify_file_digest().unwrap(), fill == 2);
+        let handle = decoder.find_section_by_index(0).unwrap();
+        assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), [fill; 1000]);
+        offset += decoder.bytes_consumed();
+    }
+    assert_eq!(offset, stream.len() as u64);
+
+    // Strict decoding only accepts the last BPX of the stream.
+    let strict = |offset| Decoder::new_strict(Embedded::new(Cursor::new(&stream), offset).unwrap());
+    match strict(6) {
+        Err(Error::Corruption(msg)) => assert!(msg.contains("trailing byte(s)"), "{}", msg),
+        Err(e) => panic!("unexpected error {}", e),
+        Ok(_) => panic!("trailing bytes were not reported")
+    }
+    assert!(strict(6 + first.len() as u64).is_ok());
+    assert!(Decoder::new_embedded(Cursor::new(&stream), 0).is_err());
+}
+
+#[test]
+fn package_overlay_references_base_sections()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        decoder::IoBackend,
+        error::Error,
+        variant::package::{
+            reference::BaseSection,
+            utils::unpack_memory,
+            PackageBuilder,
+            PackageDecoder,
+            SECTION_TYPE_DATA
+        }
+    };
+
+    const BASE: u64 = 0xBA5E0000DEADBEEF;
+    let large: Vec<u8> = (0..20000u32).map(|v| (v % 13) as u8).collect();
+    let build_base = |large: &[u8]| {
+        let mut encoder = Encoder::new(Vec::new()).unwrap();
+        {
+            let mut package = PackageBuilder::new().with_split_size(16384).build(&mut encoder).unwrap();
+            package.pack_object("textures/a.png", &mut b"base texture".as_ref()).unwrap();
+            package.pack_object("textures/large.bin", &mut &*large).unwrap();
+        }
+        encoder.set_build_id(BASE).unwrap();
+        encoder.save().unwrap();
+        encoder.into_inner()
+    };
+    let base = build_base(&large);
+
+    let mut base_decoder = Decoder::new(Cursor::new(base.clone())).unwrap();
+    let mut sections = Vec::new();
+    for handle in base_decoder.find_all_sections_of_type(SECTION_TYPE_DATA) {
+        sections.push(BaseSection::of(&mut base_decoder, handle).unwrap());
+    }
+    let objects = PackageDecoder::read(&mut base_decoder).unwrap().read_object_table().unwrap();
+    let mut overlay = roundtrip_container(|encoder| {
+        let mut package = PackageBuilder::new().build(encoder).unwrap();
+        package.pack_object("before", &mut b"overlay data".as_ref()).unwrap();
+        let mut indices = Vec::new();
+        for section in &sections {
+            indices.push((section.index, package.reference_section(BASE, *section).unwrap()));
+        }
+        assert_eq!(indices.windows(2).filter(|v| v[1].1 != v[0].1 + 1).count(), 0);
+        let names = ["textures/a.png", "textures/large.bin"];
+        for (obj, name) in objects.get_objects().iter().zip(names) {
+            let start = indices.iter().find(|v| v.0 == obj.start).unwrap().1;
+            package.reference_object(name, start, obj.offset, obj.size).unwrap();
+        }
+        package.pack_object("after", &mut b"patched".as_ref()).unwrap();
+    });
+    assert_eq!(overlay.find_all_sections_of_type(SECTION_TYPE_DATA).len(), 2);
+
+    let expected: [(&str, &[u8]); 4] = [
+        ("before", b"overlay data"),
+        ("textures/a.png", b"base texture"),
+        ("textures/large.bin", &large),
+        ("after", b"patched")
+    ];
+    // Without a resolver the base package is reported as missing.
+    {
+        let mut package = PackageDecoder::read(&mut overlay).unwrap();
+        assert_eq!(package.missing_bases().unwrap(), [BASE]);
+        let table = package.read_object_table().unwrap();
+        let objects = table.get_objects();
+        assert_eq!(unpack_memory(&mut package, &objects[3]).unwrap(), b"patched");
+        let err = unpack_memory(&mut package, &objects[1]).unwrap_err();
+        assert!(matches!(err, Error::UnresolvedBase(BASE)));
+        assert!(err.to_string().contains("ba5e0000deadbeef"));
+        assert!(matches!(package.layout().unwrap_err(), Error::Unsupported(_)));
+    }
+
+    let resolver = |base: &Vec<u8>| {
+        let base = base.clone();
+        move |id: u64| {
+            if id != BASE {
+                return Ok(None);
+            }
+            Decoder::new(Box::new(Cursor::new(base.clone())) as Box<dyn IoBackend>).map(Some)
+        }
+    };
+    let mut package = PackageDecoder::read(&mut overlay).unwrap().with_resolver(resolver(&base));
+    assert!(package.missing_bases().unwrap().is_empty());
+    let table = package.read_object_table().unwrap();
+    assert_eq!(table.get_objects().len(), expected.len());
+    for (obj, (name, data)) in table.get_objects().iter().zip(expected) {
+        assert_eq!(package.get_object_name(obj).unwrap(), name);
+        assert_eq!(unpack_memory(&mut package, obj).unwrap(), data);
+    }
+
+    // A base whose referenced content changed is rejected.
+    let mut changed = large.clone();
+    changed[19999] ^= 0xFF;
+    let changed = build_base(&changed);
+    let mut package = PackageDecoder::read(&mut overlay).unwrap().with_resolver(resolver(&changed));
+    let table = package.read_object_table().unwrap();
+    assert_eq!(unpack_memory(&mut package, &table.get_objects()[1]).unwrap(), b"base texture");
+    let err = unpack_memory(&mut package, &table.get_objects()[2]).unwrap_err();
+    assert!(err.is_checksum_mismatch(), "{}", err);
+}
+
+#[test]
+#[cfg(feature = "tempfile")]
+fn section_remaining_capacity_near_limit()
+{
+    use std::io::{ErrorKind, Seek, SeekFrom, Write};
+
+    use bpx::{format::MAX_SECTION_SIZE, header::SectionHeader};
+
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    // Sections of unknown size live in temporary files, seeking far ahead keeps them sparse.
+    let handle = encoder.create_section(SectionHeader::new()).unwrap();
+    assert_eq!(encoder.section_remaining_capacity(handle).unwrap(), MAX_SECTION_SIZE);
+    {
+        let mut data = encoder.open_section(handle).unwrap();
+        data.seek(SeekFrom::Start(MAX_SECTION_SIZE - 8)).unwrap();
+        data.write_all(b"hello").unwrap();
+    }
+    assert_eq!(encoder.section_remaining_capacity(handle).unwrap(), 3);
+    {
+        let mut data = encoder.open_section(handle).unwrap();
+        data.seek(SeekFrom::End(0)).unwrap();
+        data.write_all(b"abc").unwrap();
+    }
+    assert_eq!(encoder.section_remaining_capacity(handle).unwrap(), 0);
+    {
+        let mut data = encoder.open_section(handle).unwrap();
+        data.seek(SeekFrom::End(0)).unwrap();
+        // Sections can not grow past the format maximum.
+        assert_eq!(data.write(b"!").unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+    assert_eq!(encoder.section_remaining_capacity(handle).unwrap(), 0);
+}
+
+#[test]
+fn small_sections_stored_uncompressed()
+{
+    use std::io::{Cursor, Write};
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        encoder::DEFAULT_MIN_COMPRESS_SIZE
+    };
+
+    let sizes = [DEFAULT_MIN_COMPRESS_SIZE as usize - 1, DEFAULT_MIN_COMPRESS_SIZE as usize];
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        assert_eq!(encoder.get_min_compress_size(), DEFAULT_MIN_COMPRESS_SIZE);
+        for (i, size) in sizes.iter().enumerate() {
+            let method = if i == 0 { CompressionMethod::Xz } else { CompressionMethod::Zlib };
+            let header = SectionHeaderBuilder::new().with_compression(method).with_threshold(0).build();
+            let handle = encoder.create_section(header).unwrap();
+            encoder.open_section(handle).unwrap().write_all(&vec![b'a'; *size]).unwrap();
+        }
+        // Below the header threshold, the section was never going to be compressed.
+        let header = SectionHeaderBuilder::new().with_compression(CompressionMethod::Xz).build();
+        let handle = encoder.create_section(header).unwrap();
+        encoder.open_section(handle).unwrap().write_all(b"small").unwrap();
+        encoder.save().unwrap();
+        assert_eq!(encoder.get_save_stats().compression_skipped, 1);
+    }
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let below = decoder.find_section_by_index(0).unwrap();
+    let header = decoder.get_section_header(below);
+    assert_eq!(header.flags().compression(), None);
+    assert_eq!(header.csize, header.size);
+    let above = decoder.find_section_by_index(1).unwrap();
+    assert_eq!(decoder.get_section_header(above).flags().compression(), Some(CompressionMethod::Zlib));
+    assert!(decoder.get_section_header(above).csize < sizes[1] as u32);
+    for (i, size) in sizes.iter().enumerate() {
+        let handle = decoder.find_section_by_index(i as u32).unwrap();
+        assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), vec![b'a'; *size]);
+    }
+}
+
+#[test]
+fn package_object_header_layout()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        error::Error,
+        format::package::OBJECT_ENTRY_SIZE,
+        variant::package::{object::ObjectHeader, SECTION_TYPE_OBJECT_TABLE}
+    };
+
+    let header = ObjectHeader {
+        size: 0x0102030405060708,
+        name: 0x11121314,
+        start: 0x21222324,
+        offset: 0x31323334
+    };
+    let expected: [u8; OBJECT_ENTRY_SIZE] = [
+        0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, 0x14, 0x13, 0x12, 0x11, 0x24, 0x23, 0x22, 0x21, 0x34, 0x33,
+        0x32, 0x31
+    ];
+    assert_eq!(header.to_bytes(), expected);
+    let mut buf = Vec::new();
+    header.write(&mut buf).unwrap();
+    assert_eq!(buf, expected);
+    assert_eq!(ObjectHeader::read(&mut expected.as_ref()).unwrap(), header);
+    let err = ObjectHeader::read(&mut &expected[..OBJECT_ENTRY_SIZE - 1]).unwrap_err();
+    assert!(matches!(err, Error::Truncation(_)));
+
+    // Object tables written by earlier versions decode and encode to the same bytes.
+    let mut decoder = Decoder::new(Cursor::new(read_fixture("package.bpxp"))).unwrap();
+    let handle = decoder.find_section_by_type(SECTION_TYPE_OBJECT_TABLE).unwrap();
+    let table = decoder.open_section(handle).unwrap().load_in_memory().unwrap();
+    let mut reader = table.as_slice();
+    let mut encoded = Vec::new();
+    while !reader.is_empty() {
+        ObjectHeader::read(&mut reader).unwrap().write(&mut encoded).unwrap();
+    }
+    assert_eq!(encoded, table);
+    assert_eq!(table.len(), 3 * OBJECT_ENTRY_SIZE);
+}
+
+#[test]
+fn sd_object_macros()
+{
+    use std::convert::TryInto;
+
+    use bpx::{
+        sd::{Array, DebugSymbols, Object, Value},
+        sd_array,
+        sd_object,
+        sd_object_with_symbols,
+        utils::hash
+    };
+
+    let count = 3u16;
+    let obj = sd_object! {
+        "null": Value::Null,
+        "bool": true,
+        "u8": 1u8,
+        "u16": count * 2,
+        "u32": 3u32,
+        "u64": u64::MAX,
+        "i8": -1i8,
+        "i16": -2i16,
+        "i32": -3,
+        "i64": i64::MIN,
+        "f32": 0.5f32,
+        "f64": -0.25,
+        "string": String::from("text"),
+        "optional": None::<u8>,
+        "nested": {
+            "list": [1u8, [2u8, "deep"], { "key": "value" }],
+            "empty": {},
+            "none": []
+        },
+    };
+    let mut expected = Object::new();
+    expected.set("null", Value::Null);
+    expected.set("bool", Value::Bool(true));
+    expected.set("u8", Value::Uint8(1));
+    expected.set("u16", Value::Uint16(6));
+    expected.set("u32", Value::Uint32(3));
+    expected.set("u64", Value::Uint64(u64::MAX));
+    expected.set("i8", Value::Int8(-1));
+    expected.set("i16", Value::Int16(-2));
+    expected.set("i32", Value::Int32(-3));
+    expected.set("i64", Value::Int64(i64::MIN));
+    expected.set("f32", Value::Float(0.5));
+    expected.set("f64", Value::Double(-0.25));
+    expected.set("string", Value::from("text"));
+    expected.set("optional", Value::Null);
+    let mut inner = Array::new();
+    inner.add(2u8.into());
+    inner.add("deep".into());
+    let mut leaf = Object::new();
+    leaf.set("key", "value".into());
+    let mut list = Array::new();
+    list.add(1u8.into());
+    list.add(inner.into());
+    list.add(leaf.into());
+    let mut nested = Object::new();
+    nested.set("list", list.into());
+    nested.set("empty", Object::new().into());
+    nested.set("none", Array::new().into());
+    expected.set("nested", nested.into());
+    assert_eq!(obj, expected);
+    let mut buf = Vec::new();
+    obj.write(&mut buf).unwrap();
+    assert_eq!(Object::read(&mut buf.as_slice()).unwrap(), expected);
+    assert_eq!(sd_array![], Array::new());
+    assert_eq!(sd_array![-1i8, "a",].len(), 2);
+
+    let obj = sd_object_with_symbols! { "name": "demo", "build": { "id": 42u32 }, "list": [{ "x": -1 }] };
+    let symbols = DebugSymbols::read(&obj).unwrap();
+    assert_eq!(symbols.lookup(hash("build")), Some("build"));
+    assert!(symbols.lookup(hash("id")).is_none());
+    let list: &Array = obj.get("list").unwrap().try_into().unwrap();
+    let item: &Object = (&list[0]).try_into().unwrap();
+    assert_eq!(DebugSymbols::read(item).unwrap().lookup(hash("x")), Some("x"));
+
+    // The BPXSD limit is enforced when writing, long literal lists still expand.
+    let arr = sd_array![
+        0u8, 1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8, 9u8, 10u8, 11u8, 12u8, 13u8, 14u8, 15u8, 16u8, 17u8, 18u8, 19u8,
+        20u8, 21u8, 22u8, 23u8, 24u8, 25u8, 26u8, 27u8, 28u8, 29u8, 30u8, 31u8, 32u8, 33u8, 34u8, 35u8, 36u8, 37u8,
+        38u8, 39u8, 40u8, 41u8, 42u8, 43u8, 44u8, 45u8, 46u8, 47u8, 48u8, 49u8, 50u8, 51u8, 52u8, 53u8, 54u8, 55u8,
+        56u8, 57u8, 58u8, 59u8, 60u8, 61u8, 62u8, 63u8, 64u8, 65u8, 66u8, 67u8, 68u8, 69u8, 70u8, 71u8, 72u8, 73u8,
+        74u8, 75u8, 76u8, 77u8, 78u8, 79u8, 80u8, 81u8, 82u8, 83u8, 84u8, 85u8, 86u8, 87u8, 88u8, 89u8, 90u8, 91u8,
+        92u8, 93u8, 94u8, 95u8, 96u8, 97u8, 98u8, 99u8, 100u8, 101u8, 102u8, 103u8, 104u8, 105u8, 106u8, 107u8, 108u8,
+        109u8, 110u8, 111u8, 112u8, 113u8, 114u8, 115u8, 116u8, 117u8, 118u8, 119u8, 120u8, 121u8, 122u8, 123u8,
+        124u8, 125u8, 126u8, 127u8, 128u8, 129u8, 130u8, 131u8, 132u8, 133u8, 134u8, 135u8, 136u8, 137u8, 138u8,
+        139u8, 140u8, 141u8, 142u8, 143u8, 144u8, 145u8, 146u8, 147u8, 148u8, 149u8, 150u8, 151u8, 152u8, 153u8,
+        154u8, 155u8, 156u8, 157u8, 158u8, 159u8, 160u8, 161u8, 162u8, 163u8, 164u8, 165u8, 166u8, 167u8, 168u8,
+        169u8, 170u8, 171u8, 172u8, 173u8, 174u8, 175u8, 176u8, 177u8, 178u8, 179u8, 180u8, 181u8, 182u8, 183u8,
+        184u8, 185u8, 186u8, 187u8, 188u8, 189u8, 190u8, 191u8, 192u8, 193u8, 194u8, 195u8, 196u8, 197u8, 198u8,
+        199u8, 200u8, 201u8, 202u8, 203u8, 204u8, 205u8, 206u8, 207u8, 208u8, 209u8, 210u8, 211u8, 212u8, 213u8,
+        214u8, 215u8, 216u8, 217u8, 218u8, 219u8, 220u8, 221u8, 222u8, 223u8, 224u8, 225u8, 226u8, 227u8, 228u8,
+        229u8, 230u8, 231u8, 232u8, 233u8, 234u8, 235u8, 236u8, 237u8, 238u8, 239u8, 240u8, 241u8, 242u8, 243u8,
+        244u8, 245u8, 246u8, 247u8, 248u8, 249u8, 250u8, 251u8, 252u8, 253u8, 254u8, 255u8
+    ];
+    assert_eq!(arr.len(), 256);
+    let obj = sd_object! { "values": arr };
+    let err = obj.write(&mut Vec::new()).unwrap_err();
+    assert!(err.is_capacity());
+    assert_eq!(err.to_string(), "BPXSD - too many props (count 256, max is 255)");
+}
+
+#[cfg(feature = "tempfile")]
+#[test]
+fn storage_report_before_and_after_spill()
+{
+    use std::io::Cursor;
+
+    use bpx::{builder::SectionHeaderBuilder, inspect::dump, section::StorageKind};
+
+    let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    let small = encoder.create_section(SectionHeaderBuilder::new().with_size(16).build()).unwrap();
+    let large = encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+    encoder.open_section(small).unwrap().write_all(&[1; 16]).unwrap();
+    encoder.open_section(large).unwrap().write_all(&[2; 4096]).unwrap();
+    let report = encoder.storage_report();
+    assert_eq!(report.sections.len(), 2);
+    assert_eq!(report.sections[0].kind, StorageKind::Memory);
+    assert!(report.sections[0].allocated_bytes >= 16);
+    assert_eq!(report.sections[1].kind, StorageKind::TempFile);
+    assert_eq!(report.temp_file_bytes(), 4096);
+    encoder.save().unwrap();
+
+    let mut decoder = Decoder::new(Cursor::new(encoder.into_inner())).unwrap().with_memory_budget(1024);
+    let handles: Vec<_> = decoder.iter_sections().collect();
+    assert_eq!(decoder.storage_report(), Default::default());
+    decoder.open_section(handles[0]).unwrap();
+    let before = decoder.storage_report();
+    assert_eq!(before.sections.len(), 1);
+    assert_eq!(before.sections[0].kind, StorageKind::Memory);
+    assert_eq!(before.temp_file_bytes(), 0);
+
+    // The large section exceeds the budget and is spilled to a temporary file.
+    decoder.open_section(handles[1]).unwrap();
+    let after = decoder.storage_report();
+    assert_eq!(after.sections.len(), 2);
+    assert_eq!(after.sections[0], before.sections[0]);
+    assert_eq!(after.sections[1].index, 1);
+    assert_eq!(after.sections[1].kind, StorageKind::TempFile);
+    assert_eq!(after.sections[1].size, 4096);
+    assert_eq!(after.temp_file_bytes(), 4096);
+    assert_eq!(after.memory_bytes(), before.memory_bytes() + after.sections[1].allocated_bytes);
+    assert_eq!(dump(&mut decoder).unwrap().storage, after);
+}
+
+#[test]
+fn shared_buffer_fan_out_decoding()
+{
+    use std::sync::Arc;
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        section::StorageKind
+    };
+
+    let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    let plain = encoder.create_section(SectionHeaderBuilder::new().with_type(1).build()).unwrap();
+    let header = SectionHeaderBuilder::new()
+        .with_type(2)
+        .with_compression(CompressionMethod::Zlib)
+        .with_threshold(0)
+        .build();
+    let packed = encoder.create_section(header).unwrap();
+    encoder.open_section(plain).unwrap().write_all(&[7; 512]).unwrap();
+    encoder.open_section(packed).unwrap().write_all(&[9; 2048]).unwrap();
+    encoder.save().unwrap();
+    let bytes: Arc<[u8]> = encoder.into_inner().into();
+
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let bytes = bytes.clone();
+            std::thread::spawn(move || {
+                let mut decoder = Decoder::from_shared(bytes).unwrap();
+                let handles: Vec<_> = decoder.iter_sections().collect();
+                let contents: Vec<_> = handles
+                    .iter()
+                    .map(|h| decoder.open_section(*h).unwrap().load_in_memory().unwrap())
+                    .collect();
+                (contents, decoder.storage_report())
+            })
+        })
+        .collect();
+    for worker in workers {
+        let (contents, report) = worker.join().unwrap();
+        assert_eq!(contents, [vec![7; 512], vec![9; 2048]]);
+        assert_eq!(report.sections.len(), 2);
+        assert!(report.sections.iter().all(|v| v.kind == StorageKind::Memory));
+        // The uncompressed section borrows from the shared buffer.
+        assert_eq!(report.sections[0].allocated_bytes, 0);
+        assert!(report.sections[1].allocated_bytes >= 2048);
+    }
+
+    // Writing to a borrowed section copies it without touching the shared buffer.
+    let mut decoder = Decoder::from_shared(bytes.clone()).unwrap();
+    decoder.load_sections(&[plain, packed], 1).unwrap();
+    decoder.open_section(plain).unwrap().write_all(&[1; 4]).unwrap();
+    assert_eq!(decoder.storage_report().sections[0].allocated_bytes, 512);
+    let mut other = Decoder::from_shared(bytes).unwrap();
+    assert_eq!(other.open_section(plain).unwrap().load_in_memory().unwrap(), [7; 512]);
+}
+
+#[test]
+fn section_data_past_end_matrix()
+{
+    use std::{
+        io::{Cursor, ErrorKind, SeekFrom},
+        sync::Arc
+    };
+
+    use bpx::{builder::SectionHeaderBuilder, decoder::SliceDecoder, section::SectionData};
+
+    // Every section starts with the content "ab".
+    fn check(section: &mut dyn SectionData)
+    {
+        let mut buf = [1; 4];
+        assert_eq!(section.seek(SeekFrom::End(0)).unwrap(), 2);
+        assert_eq!(section.read(&mut buf).unwrap(), 0);
+        assert_eq!(section.seek(SeekFrom::Start(10)).unwrap(), 10);
+        assert_eq!(section.read(&mut buf).unwrap(), 0);
+        assert_eq!(section.size(), 2);
+
+        section.seek(SeekFrom::End(0)).unwrap();
+        section.write_all(b"c").unwrap();
+        assert_eq!(section.load_in_memory().unwrap(), b"abc");
+        section.seek(SeekFrom::Current(3)).unwrap();
+        section.write_all(b"d").unwrap();
+        assert_eq!(section.load_in_memory().unwrap(), b"abc\0\0\0d");
+        section.seek(SeekFrom::Start(4)).unwrap();
+        assert_eq!(section.read(&mut buf).unwrap(), 3);
+        assert_eq!(buf, [0, 0, b'd', 1]);
+
+        section.seek(SeekFrom::Start(u32::MAX as u64 - 1)).unwrap();
+        assert_eq!(section.write(b"ef").unwrap_err().kind(), ErrorKind::InvalidInput);
+        section.seek(SeekFrom::Start(u64::MAX)).unwrap();
+        assert_eq!(section.write(b"e").unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(section.size(), 7);
+        assert_eq!(section.load_in_memory().unwrap(), b"abc\0\0\0d");
+    }
+
+    let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    let memory = encoder.create_section(SectionHeaderBuilder::new().with_size(2).build()).unwrap();
+    // Sections of unknown size are stored in a temporary file.
+    let file = encoder.create_section(SectionHeaderBuilder::new().build()).unwrap();
+    for handle in [memory, file] {
+        let mut section = encoder.open_section(handle).unwrap();
+        section.write_all(b"ab").unwrap();
+        check(&mut *section);
+    }
+    encoder.save().unwrap();
+    let buf = encoder.into_inner();
+
+    let mut decoder = SliceDecoder::new(&buf).unwrap();
+    for handle in [memory, file] {
+        assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), b"abc\0\0\0d");
+    }
+
+    let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    let handle = encoder.create_section(SectionHeaderBuilder::new().with_size(2).build()).unwrap();
+    encoder.open_section(handle).unwrap().write_all(b"ab").unwrap();
+    encoder.save().unwrap();
+    let buf = encoder.into_inner();
+    check(&mut *SliceDecoder::new(&buf).unwrap().open_section(handle).unwrap());
+    check(&mut *Decoder::new(Cursor::new(&buf)).unwrap().open_section(handle).unwrap());
+    let shared: Arc<[u8]> = buf.into();
+    check(&mut *Decoder::from_shared(shared).unwrap().open_section(handle).unwrap());
+}
+
+#[test]
+fn container_compact_edited_package()
+{
+    use std::io::{Cursor, Write};
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        container::Container,
+        inspect::dump,
+        variant::package::{
+            utils::unpack_memory,
+            PackageBuilder,
+            PackageDecoder,
+            PackageEditor,
+            PackageMetadata,
+            METADATA_DESCRIPTION
+        },
+        Interface
+    };
+
+    let mut state: u64 = 0x9e3779b97f4a7c15;
+    let noise: Vec<u8> = (0..8192)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    let data: Vec<u8> = (0..50000u32).map(|v| (v % 251) as u8).collect();
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        let mut metadata = PackageMetadata::new("game", "1.0.0");
+        metadata.description = Some((0..4000u32).map(|v| (b'a' + (v.wrapping_mul(7919) % 26) as u8) as char).collect());
+        let mut package = PackageBuilder::new().with_typed_metadata(metadata).build(&mut encoder).unwrap();
+        package.pack_object("a.bin", &mut data.as_slice()).unwrap();
+        package.pack_object("b.txt", &mut b"hello".as_ref()).unwrap();
+        let junk = encoder.create_section(SectionHeaderBuilder::new().with_type(0x60).build()).unwrap();
+        encoder.open_section(junk).unwrap().write_all(&[3; 30000]).unwrap();
+        // Zlib makes random bytes larger.
+        let header = SectionHeaderBuilder::new()
+            .with_type(0x61)
+            .with_compression(CompressionMethod::Zlib)
+            .with_threshold(0)
+            .build();
+        let handle = encoder.create_section(header).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&noise).unwrap();
+        encoder.save().unwrap();
+    }
+
+    // Removing a section rewrites a smaller file, shrinking the metadata patches it in place.
+    let mut container = Container::open(Cursor::new(buf)).unwrap();
+    let junk = container.find_section_by_type(0x60).unwrap();
+    container.sections().remove(junk).unwrap();
+    container.save().unwrap();
+    PackageEditor::open(&mut container)
+        .unwrap()
+        .update_metadata(|obj| obj.set(METADATA_DESCRIPTION, "short".into()))
+        .unwrap();
+    container.save().unwrap();
+
+    let mut out = Cursor::new(Vec::new());
+    let report = container.compact(&mut out).unwrap();
+    let compacted = out.into_inner();
+    assert_eq!(report.input_size, container.into_inner().into_inner().len() as u64);
+    assert_eq!(report.output_size, compacted.len() as u64);
+    assert!(report.trailing >= 30000);
+    assert!(report.slack > 0);
+    assert!(report.recompressed > 0);
+    assert_eq!(report.decompressed_sections, 1);
+    assert_eq!(report.reclaimed(), report.slack + report.trailing + report.recompressed);
+
+    let mut decoder = Decoder::new_strict(Cursor::new(compacted)).unwrap();
+    assert!(dump(&mut decoder).unwrap().is_valid());
+    assert_eq!(decoder.get_main_header().file_size, report.output_size);
+    let handle = decoder.find_section_by_type(0x61).unwrap();
+    assert!(decoder.get_section_header(handle).compression().is_none());
+    assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), noise);
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    let metadata = package.read_typed_metadata().unwrap().unwrap();
+    assert_eq!(metadata.description.as_deref(), Some("short"));
+    let mut table = package.read_object_table().unwrap();
+    table.build_lookup_table(&mut package).unwrap();
+    let obj = *table.find_object("a.bin").unwrap().unwrap();
+    assert_eq!(unpack_memory(&mut package, &obj).unwrap(), data);
+}
+
+#[test]
+fn encoder_validate_issues()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        builder::MainHeaderBuilder,
+        decoder::Decoder,
+        encoder::Encoder,
+        error::Error,
+        header::{SectionHeader, FLAG_CHECK_CRC32, FLAG_CHECK_WEAK},
+        sd::Object,
+        validate::{IssueKind, Severity},
+        variant::{
+            kvstore::{KvStore, KvWriter},
+            package::{PackageBuilder, SECTION_TYPE_OBJECT_TABLE},
+            Variant
+        }
+    };
+
+    fn kinds(encoder: &Encoder<Cursor<Vec<u8>>>) -> Vec<(Severity, IssueKind)>
+    {
+        encoder.validate().into_iter().map(|v| (v.severity, v.kind)).collect()
+    }
+
+    // Plain BPX: checksum flags conflict, unknown flags are only warned about.
+    let mut encoder = Encoder::new(Cursor::new(Vec::new())).unwrap();
+    let mut header = SectionHeader::new();
+    header.flags = FLAG_CHECK_CRC32 | FLAG_CHECK_WEAK;
+    encoder.create_section(header).unwrap();
+    header.flags = 0x80;
+    encoder.create_section(header).unwrap();
+    let issues = encoder.validate();
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[0].section, Some((0, 0)));
+    assert_eq!(issues[0].kind, IssueKind::ConflictingFlags(FLAG_CHECK_CRC32 | FLAG_CHECK_WEAK));
+    assert!(issues[0].is_error());
+    assert_eq!(issues[1].section, Some((1, 0)));
+    assert_eq!(issues[1].kind, IssueKind::UnknownFlags(0x80));
+    assert!(!issues[1].is_error());
+    match encoder.save_checked().unwrap_err() {
+        Error::Validation(issues) => assert_eq!(issues.len(), 2),
+        e => panic!("unexpected error {}", e)
+    }
+    assert!(encoder.into_inner().into_inner().is_empty());
+
+    let mut encoder = Encoder::new(Cursor::new(Vec::new())).unwrap();
+    header.flags = 0x80;
+    encoder.create_section(header).unwrap();
+    let warnings = encoder.save_checked().unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].severity, Severity::Warning);
+    assert!(!encoder.into_inner().into_inner().is_empty());
+
+    // Valid package: no issues, and the saved file decodes.
+    let mut encoder = Encoder::new(Cursor::new(Vec::new())).unwrap();
+    let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    package.pack_object("a", &mut b"first".as_ref()).unwrap();
+    package.pack_object("b", &mut b"second".as_ref()).unwrap();
+    let headers = encoder.section_headers_snapshot();
+    assert!(kinds(&encoder).is_empty());
+    // Validating does not touch the section headers, which are only updated on save.
+    assert_eq!(encoder.section_headers_snapshot(), headers);
+    assert!(encoder.save_checked().unwrap().is_empty());
+    Decoder::new(Cursor::new(encoder.into_inner().into_inner())).unwrap();
+
+    // Empty package: required sections are empty.
+    let mut encoder = Encoder::new(Cursor::new(Vec::new())).unwrap();
+    PackageBuilder::new().build(&mut encoder).unwrap();
+    let issues = kinds(&encoder);
+    assert!(!issues.is_empty());
+    assert!(issues.iter().all(|v| *v == (Severity::Warning, IssueKind::EmptySection)));
+
+    // Duplicate object names are reported once against the object table.
+    let mut encoder = Encoder::new(Cursor::new(Vec::new())).unwrap();
+    let mut package = PackageBuilder::new().build(&mut encoder).unwrap();
+    package.pack_object("a", &mut b"first".as_ref()).unwrap();
+    package.pack_object("a", &mut b"again".as_ref()).unwrap();
+    package.pack_object("a", &mut b"third".as_ref()).unwrap();
+    let issues = encoder.validate();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].kind, IssueKind::DuplicateName("a".into(), 3));
+    assert_eq!(issues[0].section.map(|v| v.1), Some(SECTION_TYPE_OBJECT_TABLE));
+    assert!(matches!(encoder.save_checked(), Err(Error::Validation(_))));
+
+    // Metadata which is not a valid PackageMetadata is only warned about.
+    let mut encoder = Encoder::new(Cursor::new(Vec::new())).unwrap();
+    let mut metadata = Object::new();
+    metadata.set("name", "tool".into());
+    let mut package = PackageBuilder::new().with_metadata(metadata).build(&mut encoder).unwrap();
+    package.pack_object("a", &mut b"first".as_ref()).unwrap();
+    let issues = kinds(&encoder);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].0, Severity::Warning);
+    assert!(matches!(issues[0].1, IssueKind::Metadata(_)));
+
+    // A KvStore missing one of its required sections.
+    let mut encoder = Encoder::new(Cursor::new(Vec::new())).unwrap();
+    encoder.set_main_header(MainHeaderBuilder::new().with_type(KvStore::TYPE_BYTE).build());
+    let mut header = SectionHeader::new();
+    header.btype = KvStore::required_sections()[0];
+    encoder.create_section(header).unwrap();
+    let issues = kinds(&encoder);
+    assert!(issues.iter().any(|v| v.0 == Severity::Error && matches!(v.1, IssueKind::Variant(_))));
+
+    // A KvStore written by its encoder.
+    let mut encoder = Encoder::new(Cursor::new(Vec::new())).unwrap();
+    let mut writer = KvWriter::create(&mut encoder).unwrap();
+    writer.put("key", b"value").unwrap();
+    writer.finish().unwrap();
+    assert!(kinds(&encoder).iter().all(|v| v.0 == Severity::Warning));
+}
+
+#[test]
+fn fixture_shuffled_decodes()
+{
+    use std::{io::Cursor, sync::Arc};
+
+    use bpx::{
+        container::Container,
+        decoder::SliceDecoder,
+        header::{SectionHeader, SIZE_MAIN_HEADER, SIZE_SECTION_HEADER},
+        inspect::dump,
+        section::DEFAULT_SPOOL_THRESHOLD,
+        testing::{assert_sections_equal, relayout_sections}
+    };
+
+    let reference = read_fixture("sections.bpx");
+    let shuffled = read_fixture("shuffled.bpx");
+    let mut expected = Decoder::new(Cursor::new(reference.clone())).unwrap();
+    let mut decoder = Decoder::new_strict(Cursor::new(shuffled.clone())).unwrap();
+    let pointers: Vec<u64> = decoder.iter_sections().map(|h| decoder.get_section_header(h).pointer).collect();
+    assert!(pointers.windows(2).all(|v| v[0] > v[1]));
+    let table_end = (SIZE_MAIN_HEADER + pointers.len() * SIZE_SECTION_HEADER) as u64;
+    assert!(pointers.iter().all(|v| *v >= table_end + 4096));
+    assert_sections_equal(&mut expected, &mut decoder);
+    assert_eq!(decoder.build_id().unwrap(), Some(0x0123456789ABCDEF));
+    let first = decoder.find_section_by_name("first").unwrap().unwrap();
+    assert_eq!(decoder.get_section_header(first).btype, 1);
+
+    let mut decoder = Decoder::new(Cursor::new(shuffled.clone())).unwrap();
+    let handles: Vec<_> = decoder.iter_sections().collect();
+    decoder.load_sections(&handles, 2).unwrap();
+    assert_sections_equal(&mut expected, &mut decoder);
+    let concurrent = Decoder::new(Cursor::new(shuffled.clone())).unwrap().into_concurrent().unwrap();
+    for handle in expected.iter_sections().collect::<Vec<_>>() {
+        let data = expected.open_section(handle).unwrap().load_in_memory().unwrap();
+        assert_eq!(concurrent.load_section(handle).unwrap(), data.as_slice());
+    }
+    assert_sections_equal(&mut expected, &mut SliceDecoder::new(&shuffled).unwrap());
+    assert_sections_equal(&mut expected, &mut Decoder::from_shared(Arc::from(shuffled.as_slice())).unwrap());
+    assert_sections_equal(&mut expected, &mut Decoder::new_spooled(&shuffled[..], 64).unwrap());
+    let mut spooled = Decoder::new_spooled(&shuffled[..], DEFAULT_SPOOL_THRESHOLD).unwrap();
+    assert_sections_equal(&mut expected, &mut spooled);
+    let mut embedded = b"prefix".to_vec();
+    embedded.extend_from_slice(&shuffled);
+    let mut decoder = Decoder::new_embedded(Cursor::new(embedded), 6).unwrap();
+    assert_sections_equal(&mut expected, &mut decoder);
+    assert_eq!(decoder.trailing_bytes(), 0);
+    assert!(dump(&mut decoder).unwrap().is_valid());
+
+    // Gaps are slack which compacting reclaims, the sections move back in creation order.
+    let mut container = Container::open(Cursor::new(shuffled.clone())).unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let report = container.compact(&mut out).unwrap();
+    let gaps: u64 = 4096 + (1..pointers.len() as u64).map(|i| 7 * i).sum::<u64>();
+    assert_eq!(report.slack, gaps);
+    let mut compacted = Decoder::new_strict(Cursor::new(out.into_inner())).unwrap();
+    let pointers: Vec<u64> = compacted.iter_sections().map(|h| compacted.get_section_header(h).pointer).collect();
+    assert!(pointers.windows(2).all(|v| v[0] < v[1]));
+    assert_sections_equal(&mut expected, &mut compacted);
+
+    // Every permutation of a smaller layout, with and without filler.
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    for i in 0..3u8 {
+        let handle = encoder.create_section(SectionHeader::new()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&vec![i; 10 + i as usize]).unwrap();
+    }
+    encoder.save().unwrap();
+    let buf = encoder.into_inner();
+    let permutations = [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+    for order in permutations.iter() {
+        for gaps in [[0, 0, 0], [100, 1, 33]].iter() {
+            let mut decoder = Decoder::new_strict(Cursor::new(relayout_sections(&buf, order, gaps))).unwrap();
+            assert_sections_equal(&mut Decoder::new(Cursor::new(buf.clone())).unwrap(), &mut decoder);
+        }
+    }
+}
+
+#[test]
+fn sd_object_stream_round_trip()
+{
+    use std::io::Cursor;
+
+    use bpx::{
+        header::SectionHeader,
+        sd::{Object, ObjectStreamReader, ObjectStreamWriter, Value},
+        testing::random_object
+    };
+
+    fn read_all(decoder: &mut Decoder<Cursor<Vec<u8>>>) -> Vec<bpx::Result<(u64, Object)>>
+    {
+        let handle = decoder.find_section_by_index(0).unwrap();
+        let mut section = decoder.open_section(handle).unwrap();
+        ObjectStreamReader::new(&mut *section).collect()
+    }
+
+    // Empty section.
+    let mut decoder = roundtrip_container(|encoder| {
+        encoder.create_section(SectionHeader::new()).unwrap();
+    });
+    assert!(read_all(&mut decoder).is_empty());
+
+    // A single object, read again from the start after moving the cursor.
+    let mut decoder = roundtrip_container(|encoder| {
+        let handle = encoder.create_section(SectionHeader::new()).unwrap();
+        let mut section = encoder.open_section(handle).unwrap();
+        let mut obj = Object::new();
+        obj.set("value", 42u32.into());
+        assert_eq!(ObjectStreamWriter::new(&mut *section).append(&obj).unwrap(), 0);
+    });
+    let objects = read_all(&mut decoder);
+    assert_eq!(objects.len(), 1);
+    let (offset, obj) = objects.into_iter().next().unwrap().unwrap();
+    assert_eq!(offset, 0);
+    assert_eq!(obj.get("value"), Some(&Value::from(42u32)));
+    assert_eq!(read_all(&mut decoder).len(), 1);
+
+    // Many objects: the offsets are those returned by the writer and an object
+    // failing to encode is not written.
+    let expected: Vec<Object> = (0..64).map(|seed| random_object(3, seed)).collect();
+    let mut offsets = Vec::new();
+    let mut decoder = roundtrip_container(|encoder| {
+        let handle = encoder.create_section(SectionHeader::new()).unwrap();
+        let mut section = encoder.open_section(handle).unwrap();
+        let mut writer = ObjectStreamWriter::new(&mut *section);
+        let mut too_large = Object::new();
+        for i in 0..300 {
+            too_large.set(&format!("p{}", i), Value::Null);
+        }
+        for obj in &expected {
+            offsets.push(writer.append(obj).unwrap());
+            assert!(writer.append(&too_large).is_err());
+        }
+    });
+    let objects: Vec<(u64, Object)> = read_all(&mut decoder).into_iter().map(|v| v.unwrap()).collect();
+    assert_eq!(objects.iter().map(|v| v.0).collect::<Vec<_>>(), offsets);
+    assert!(objects.into_iter().map(|v| v.1).eq(expected.iter().cloned()));
+
+    // Truncated final object.
+    let mut first = Object::new();
+    first.set("name", "complete".into());
+    let mut last = Object::new();
+    last.set("name", "truncated".into());
+    let mut buf = Vec::new();
+    first.write(&mut buf).unwrap();
+    let start = buf.len() as u64;
+    last.write(&mut buf).unwrap();
+    buf.truncate(buf.len() - 3);
+    let mut decoder = roundtrip_container(|encoder| {
+        let handle = encoder.create_section(SectionHeader::new()).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&buf).unwrap();
+    });
+    let objects = read_all(&mut decoder);
+    assert_eq!(objects.len(), 2);
+    assert_eq!(objects[0].as_ref().unwrap(), &(0, first));
+    let e = objects[1].as_ref().unwrap_err();
+    assert!(e.is_corruption());
+    let msg = e.to_string();
+    assert!(msg.contains(&format!("offset {}", start)), "{}", msg);
+    assert!(msg.contains(&format!("{} byte(s)", buf.len() as u64 - start)), "{}", msg);
+}
+
+#[test]
+fn encoder_write_only_backends()
+{
+    use std::io::{Cursor, Read, Sink, Write};
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        variant::package::{utils::unpack_memory, PackageBuilder, PackageDecoder}
+    };
+
+    /// Implements nothing but Write, so that the encoder cannot rely on anything else.
+    struct WriteOnly<W: Write>(W);
+
+    impl<W: Write> Write for WriteOnly<W>
+    {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>
+        {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()>
+        {
+            self.0.flush()
+        }
+    }
+
+    fn build<W: Write>(encoder: &mut Encoder<W>)
+    {
+        encoder.set_file_digest(true);
+        let mut package = PackageBuilder::new().build(encoder).unwrap();
+        package.pack_object("a.bin", &mut vec![7; 100000].as_slice()).unwrap();
+        let header = SectionHeaderBuilder::new()
+            .with_compression(CompressionMethod::Zlib)
+            .with_threshold(0)
+            .build();
+        let handle = encoder.create_section(header).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&[1; 5000]).unwrap();
+    }
+
+    fn check(buf: Vec<u8>)
+    {
+        let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+        assert!(decoder.verify_file_digest().unwrap());
+        let mut package = PackageDecoder::read(&mut decoder).unwrap();
+        let table = package.read_object_table().unwrap();
+        assert_eq!(unpack_memory(&mut package, &table.get_objects()[0]).unwrap(), vec![7; 100000]);
+    }
+
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    build(&mut encoder);
+    encoder.save().unwrap();
+    let expected = encoder.into_inner();
+    check(expected.clone());
+
+    let mut encoder = Encoder::new(WriteOnly(Vec::new())).unwrap();
+    build(&mut encoder);
+    encoder.save().unwrap();
+    assert!(encoder.into_inner().0 == expected);
+
+    // A sink can never be read back from.
+    let mut encoder: Encoder<Sink> = Encoder::new(std::io::sink()).unwrap();
+    build(&mut encoder);
+    encoder.save().unwrap();
+    encoder.save().unwrap();
+
+    // A socket: the peer sees each save as a complete BPX following the previous one.
+    #[cfg(unix)]
+    {
+        use std::os::unix::net::UnixStream;
+
+        let (writer, mut reader) = UnixStream::pair().unwrap();
+        let peer = std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf).unwrap();
+            buf
+        });
+        let mut encoder = Encoder::new(WriteOnly(writer)).unwrap();
+        build(&mut encoder);
+        encoder.save().unwrap();
+        encoder.save().unwrap();
+        drop(encoder);
+        let received = peer.join().unwrap();
+        assert_eq!(received.len(), 2 * expected.len());
+        assert!(received[..expected.len()] == expected[..] && received[expected.len()..] == expected[..]);
+        let first = Decoder::new_embedded(Cursor::new(received.clone()), 0).unwrap();
+        let second = Decoder::new_embedded(Cursor::new(received.clone()), first.bytes_consumed()).unwrap();
+        assert_eq!(second.trailing_bytes(), 0);
+        check(received[..expected.len()].to_vec());
+    }
+}
+
+#[test]
+fn encoder_profiles_change_save_stats()
+{
+    use std::io::Write;
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        encoder::EncoderProfile,
+        variant::package::{PackageBuilder, SECTION_TYPE_DATA}
+    };
+
+    fn save(profile: EncoderProfile, tune: fn(&mut Encoder<Vec<u8>>)) -> (bpx::encoder::SaveStats, Vec<u8>)
+    {
+        let mut encoder = Encoder::new(Vec::new()).unwrap();
+        encoder.apply_profile(profile);
+        tune(&mut encoder);
+        let data: Vec<u8> = (0..200000u32).map(|v| (v % 97) as u8).collect();
+        for _ in 0..2 {
+            let header = SectionHeaderBuilder::new().with_compression(CompressionMethod::Xz).build();
+            let handle = encoder.create_section(header).unwrap();
+            encoder.open_section(handle).unwrap().write_all(&data).unwrap();
+        }
+        encoder.save().unwrap();
+        (encoder.get_save_stats(), encoder.into_inner())
+    }
+
+    fn roundtrip_check(buf: &[u8])
+    {
+        let mut decoder = Decoder::new(std::io::Cursor::new(buf.to_vec())).unwrap();
+        for handle in decoder.iter_sections().collect::<Vec<_>>() {
+            decoder.open_section(handle).unwrap();
+        }
+    }
+
+    assert_eq!(EncoderProfile::default(), EncoderProfile::Balanced);
+    let (fast, fast_buf) = save(EncoderProfile::Fast, |_| ());
+    let (balanced, balanced_buf) = save(EncoderProfile::Balanced, |_| ());
+    let (smallest, smallest_buf) = save(EncoderProfile::Smallest, |_| ());
+    assert_eq!((fast.compression_skipped, fast.deduplicated), (2, 0));
+    assert_eq!((balanced.compression_skipped, balanced.deduplicated), (0, 0));
+    assert_eq!((smallest.compression_skipped, smallest.deduplicated), (0, 1));
+    assert!(smallest_buf.len() < balanced_buf.len() && balanced_buf.len() < fast_buf.len());
+    roundtrip_check(&fast_buf);
+    roundtrip_check(&smallest_buf);
+
+    // Setters called after the profile override it.
+    let (stats, _) = save(EncoderProfile::Fast, |e| e.set_min_compress_size(0));
+    assert_eq!(stats.compression_skipped, 0);
+    let (stats, buf) = save(EncoderProfile::Smallest, |e| e.set_deduplicate(false));
+    assert_eq!(stats.deduplicated, 0);
+    assert_eq!(buf, balanced_buf);
+
+    // Package data sections follow the profile of the builder.
+    let package = |profile: EncoderProfile| {
+        let mut encoder = Encoder::new(Vec::new()).unwrap();
+        encoder.apply_profile(profile);
+        let mut package = PackageBuilder::new().with_profile(profile).build(&mut encoder).unwrap();
+        for i in 0..64 {
+            package.pack_object(&format!("{}", i), &mut [i as u8; 500].as_ref()).unwrap();
+        }
+        encoder.save().unwrap();
+        let handle = encoder.find_section_by_type(SECTION_TYPE_DATA).unwrap();
+        (*encoder.get_section_header(handle), encoder.into_inner())
+    };
+    let (balanced, balanced_buf) = package(EncoderProfile::Balanced);
+    let (smallest, smallest_buf) = package(EncoderProfile::Smallest);
+    let (fast, _) = package(EncoderProfile::Fast);
+    assert_eq!(PackageBuilder::new().get_profile(), EncoderProfile::Balanced);
+    assert_eq!(balanced.compression(), None);
+    assert_eq!(smallest.compression(), Some(CompressionMethod::Xz));
+    assert_eq!(fast.compression(), None);
+    assert!(smallest_buf.len() < balanced_buf.len());
+    roundtrip_check(&smallest_buf);
+}
+
+#[test]
+fn encoder_finish_then_reopen()
+{
+    use std::io::{sink, Write};
+
+    use bpx::builder::{Checksum, SectionHeaderBuilder};
+
+    struct FailingFlush;
+
+    impl Write for FailingFlush
+    {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>
+        {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()>
+        {
+            Err(std::io::Error::other("flush failed"))
+        }
+    }
+
+    let dir = std::env::temp_dir().join(format!("bpx_finish_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("package.bpx");
+    let header = SectionHeaderBuilder::new().with_checksum(Checksum::Crc32).build();
+
+    // Write, close, then reopen, rename and remove the same file.
+    let mut encoder = Encoder::create_file(&path).unwrap();
+    encoder.set_incremental(true);
+    let handle = encoder.create_section(header).unwrap();
+    encoder.open_section(handle).unwrap().write_all(&[42; 4096]).unwrap();
+    encoder.save().unwrap();
+    drop(encoder.finish().unwrap());
+    let mut decoder = Decoder::open_file(&path).unwrap();
+    let handle = decoder.find_section_by_index(0).unwrap();
+    assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), vec![42; 4096]);
+    drop(decoder);
+    let moved = dir.join("moved.bpx");
+    std::fs::rename(&path, &moved).unwrap();
+    std::fs::remove_file(&moved).unwrap();
+
+    // Flush errors are reported instead of being lost on drop.
+    let encoder = Encoder::new(FailingFlush).unwrap();
+    assert!(encoder.finish().is_err());
+
+    // Write then verify, replacing a previous file, which can be removed right away.
+    for (fill, digest) in [(1u8, false), (2, true)] {
+        let mut encoder = Encoder::new(sink()).unwrap();
+        encoder.set_file_digest(digest);
+        let handle = encoder.create_section(header).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&[fill; 100]).unwrap();
+        let report = encoder.save_and_verify(&path).unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.sections.len(), 1);
+        assert_eq!(report.main_header.file_size, std::fs::metadata(&path).unwrap().len() - if digest { 8 } else { 0 });
+        let mut decoder = Decoder::open_file(&path).unwrap();
+        assert_eq!(decoder.verify_file_digest().unwrap(), digest);
+        let handle = decoder.find_section_by_index(0).unwrap();
+        assert_eq!(decoder.open_section(handle).unwrap().load_in_memory().unwrap(), vec![fill; 100]);
+    }
+    std::fs::remove_file(&path).unwrap();
+    assert!(Encoder::new(sink()).unwrap().save_and_verify(dir.join("missing").join("a.bpx")).is_err());
+    std::fs::remove_dir(&dir).unwrap();
+}
+
+#[test]
+fn decoder_cache_policies()
+{
+    use std::io::{Cursor, Seek, SeekFrom, Write};
+
+    use bpx::builder::SectionHeaderBuilder;
+    use bpx::decoder::CachePolicy;
+    use bpx::variant::package::{utils::UnpackOptions, PackageBuilder, PackageDecoder, SECTION_TYPE_DATA};
+
+    let mut encoder = Encoder::new(Vec::<u8>::new()).unwrap();
+    for fill in 0..3u8 {
+        let header = SectionHeaderBuilder::bulk_data().with_threshold(0).build();
+        let handle = encoder.create_section(header).unwrap();
+        let data: Vec<u8> = (0..4096u32).map(|v| (v % 7) as u8 + fill).collect();
+        encoder.open_section(handle).unwrap().write_all(&data).unwrap();
+    }
+    encoder.save().unwrap();
+    let buf = encoder.into_inner();
+    let open = |policy: CachePolicy| {
+        Decoder::new(Cursor::new(buf.clone())).unwrap().with_memory_budget(1 << 20).with_cache_policy(policy)
+    };
+    fn read(decoder: &mut Decoder<Cursor<Vec<u8>>>, index: u32) -> Vec<u8>
+    {
+        let handle = decoder.find_section_by_index(index).unwrap();
+        let mut data = decoder.open_section(handle).unwrap();
+        data.seek(SeekFrom::Start(0)).unwrap();
+        data.load_in_memory().unwrap()
+    }
+    fn loaded(decoder: &Decoder<Cursor<Vec<u8>>>) -> Vec<u32>
+    {
+        decoder.storage_report().sections.iter().map(|v| v.index).collect()
+    }
+
+    let mut decoder = open(CachePolicy::KeepAll);
+    let expected: Vec<Vec<u8>> = (0..3).map(|i| read(&mut decoder, i)).collect();
+    for i in [0, 1, 2, 0, 1] {
+        assert_eq!(read(&mut decoder, i), expected[i as usize]);
+    }
+    assert_eq!(loaded(&decoder), vec![0, 1, 2]);
+    assert_eq!(decoder.memory_usage(), 3 * 4096);
+    let section_bytes = decoder.storage_report().sections[0].allocated_bytes;
+    assert!(decoder.evict(decoder.find_section_by_index(1).unwrap()));
+    assert_eq!(loaded(&decoder), vec![0, 2]);
+    assert_eq!(decoder.memory_usage(), 2 * 4096);
+    decoder.evict_all();
+    assert!(loaded(&decoder).is_empty());
+    assert_eq!(decoder.memory_usage(), 0);
+    assert_eq!(read(&mut decoder, 2), expected[2]);
+
+    let limit = 2 * section_bytes;
+    let mut decoder = open(CachePolicy::KeepLru(limit));
+    for (i, kept) in [(0, vec![0]), (1, vec![0, 1]), (0, vec![0, 1]), (2, vec![0, 2]), (1, vec![1, 2])] {
+        assert_eq!(read(&mut decoder, i), expected[i as usize]);
+        assert_eq!(loaded(&decoder), kept);
+        assert!(decoder.storage_report().memory_bytes() <= limit);
+        assert_eq!(decoder.memory_usage(), kept.len() * 4096);
+    }
+    // The section being opened is kept even if it does not fit.
+    let mut decoder = open(CachePolicy::KeepLru(0));
+    assert_eq!(read(&mut decoder, 1), expected[1]);
+    assert_eq!(loaded(&decoder), vec![1]);
+
+    let mut decoder = open(CachePolicy::DropAfterUse);
+    for i in [0, 1, 1, 2, 0] {
+        assert_eq!(read(&mut decoder, i), expected[i as usize]);
+        assert_eq!(loaded(&decoder), vec![i]);
+        assert_eq!(decoder.memory_usage(), 4096);
+    }
+
+    // Packages drop each data section once they are done with it.
+    let mut buf = Vec::new();
+    let mut encoder = Encoder::new(&mut buf).unwrap();
+    let mut package = PackageBuilder::new().with_split_size(16384).build(&mut encoder).unwrap();
+    let contents: Vec<Vec<u8>> = (0..4u8).map(|v| vec![v; 10000]).collect();
+    for (i, data) in contents.iter().enumerate() {
+        package.pack_object(&format!("{}.bin", i), &mut data.as_slice()).unwrap();
+    }
+    encoder.save().unwrap();
+    let data_sections = |decoder: &Decoder<Cursor<Vec<u8>>>| {
+        decoder
+            .storage_report()
+            .sections
+            .iter()
+            .filter(|v| {
+                let handle = decoder.find_section_by_index(v.index).unwrap();
+                decoder.get_section_header(handle).btype == SECTION_TYPE_DATA
+            })
+            .count()
+    };
+    let mut decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    assert!(decoder.find_all_sections_of_type(SECTION_TYPE_DATA).len() > 1);
+    let mut package = PackageDecoder::read(&mut decoder).unwrap();
+    let table = package.read_object_table().unwrap();
+    for (obj, data) in table.get_objects().iter().zip(&contents) {
+        let mut out = Vec::new();
+        package.unpack_object(obj, &mut out).unwrap();
+        assert_eq!(&out, data);
+    }
+    assert_eq!(data_sections(&decoder), 1);
+    decoder.evict_all();
+    let dest = std::env::temp_dir().join(format!("bpx_cache_policy_{}", std::process::id()));
+    let report = PackageDecoder::read(&mut decoder).unwrap().extract_all(&dest, UnpackOptions::default()).unwrap();
+    assert_eq!(report.extracted.len(), 4);
+    assert!(decoder.storage_report().sections.is_empty());
+    std::fs::remove_dir_all(&dest).unwrap();
+    let mut package = PackageDecoder::read(&mut decoder).unwrap().with_cache_policy(CachePolicy::KeepAll);
+    for obj in table.get_objects() {
+        package.unpack_object(obj, &mut std::io::sink()).unwrap();
+    }
+    assert_eq!(data_sections(&decoder), decoder.find_all_sections_of_type(SECTION_TYPE_DATA).len());
+}
+
+#[test]
+fn texture_descriptor_overflows()
+{
+    use bpx::{
+        builder::{MainHeaderBuilder, SectionHeaderBuilder},
+        variant::texture::{
+            PixelFormat,
+            TextureBuilder,
+            TextureDescriptor,
+            TexturePackDecoder,
+            SECTION_TYPE_MIP_TABLE,
+            SIZE_MIP_ENTRY,
+            SUPPORTED_VERSION
+        }
+    };
+
+    let huge = TextureDescriptor {
+        format: PixelFormat::Rgba32F,
+        width: u32::MAX,
+        height: u32::MAX,
+        mip_count: 32,
+        layers: u16::MAX
+    };
+    assert_eq!(huge.mip_size(0), None);
+    assert!(huge.validate().is_err());
+    assert!(TextureDescriptor::from_type_ext(&huge.to_type_ext()).is_err());
+
+    // Levels past the width of the dimensions are 1x1.
+    let deep = TextureDescriptor {
+        format: PixelFormat::Rgba8,
+        width: 4,
+        height: 4,
+        mip_count: 40,
+        layers: 2
+    };
+    assert_eq!(deep.mip_size(35), Some(8));
+    assert_eq!(deep.mip_size(40), None);
+
+    fn read(type_ext: [u8; 16], mip_table: &[u8]) -> bpx::Result<()>
+    {
+        let mut encoder = Encoder::new(Vec::new()).unwrap();
+        encoder.set_main_header(
+            MainHeaderBuilder::new()
+                .with_type(b'T')
+                .with_version(SUPPORTED_VERSION)
+                .with_type_ext(type_ext)
+                .build()
+        );
+        let header = SectionHeaderBuilder::new().with_type(SECTION_TYPE_MIP_TABLE).build();
+        let handle = encoder.create_section(header).unwrap();
+        encoder.open_section(handle).unwrap().write_all(mip_table).unwrap();
+        TexturePackDecoder::read(&mut encoder).map(|_| ())
+    }
+    assert!(read(huge.to_type_ext(), &[]).is_err());
+    let valid = TextureDescriptor {
+        mip_count: 3,
+        ..deep
+    };
+    read(valid.to_type_ext(), &[]).unwrap();
+    assert!(read(valid.to_type_ext(), &[0; SIZE_MIP_ENTRY - 1]).is_err());
+
+    // A valid mip table followed by a partial entry is truncated.
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    TextureBuilder::new(PixelFormat::R8, 1, 1)
+        .build(&mut encoder)
+        .unwrap()
+        .add_mip(0, &[7])
+        .unwrap();
+    TexturePackDecoder::read(&mut encoder).unwrap();
+    let handle = encoder.find_section_by_type(SECTION_TYPE_MIP_TABLE).unwrap();
+    encoder.open_section(handle).unwrap().write_all(&[0]).unwrap();
+    assert!(TexturePackDecoder::read(&mut encoder).is_err());
+}
+
+#[test]
+fn load_sections_repeated_handles()
+{
+    use std::{
+        io::{Cursor, Write},
+        sync::Arc
+    };
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        instrument::BasicStats,
+        section::TempStorage
+    };
+
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    encoder.set_deduplicate(true);
+    let header = SectionHeaderBuilder::new().with_compression(CompressionMethod::Zlib).build();
+    for fill in [1u8, 2, 1] {
+        let handle = encoder.create_section(header).unwrap();
+        encoder.open_section(handle).unwrap().write_all(&[fill; 4096]).unwrap();
+    }
+    encoder.save().unwrap();
+    let buf = encoder.into_inner();
+
+    let decoder = Decoder::new(Cursor::new(buf)).unwrap();
+    let handles: Vec<_> = decoder.iter_sections().collect();
+    let (a, c) = (*decoder.get_section_header(handles[0]), *decoder.get_section_header(handles[2]));
+    assert_eq!(a.pointer, c.pointer);
+    // The budget fits each requested section once.
+    let budget = 2 * (a.csize as usize + a.size as usize);
+    let stats = Arc::new(BasicStats::new());
+    let mut decoder = decoder.with_memory_budget(budget).with_temp_storage(TempStorage::in_memory());
+    decoder.set_instrumentation(Some(stats.clone()));
+    decoder.load_sections(&[handles[0], handles[2], handles[0]], 0).unwrap();
+    assert_eq!(stats.get().decompressed_sections, 2);
+    assert_eq!(decoder.memory_usage(), 2 * 4096);
+    let report = decoder.storage_report();
+    assert_eq!(report.sections.iter().map(|v| v.index).collect::<Vec<_>>(), vec![0, 2]);
+    for i in [0, 2] {
+        assert_eq!(decoder.open_section(handles[i]).unwrap().load_in_memory().unwrap(), vec![1; 4096]);
+    }
+}
+
+#[test]
+fn encoder_save_and_verify_rejects_bad_checksums()
+{
+    use std::io::sink;
+
+    use bpx::builder::{Checksum, SectionHeaderBuilder};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bad.bpx");
+    let mut header = SectionHeaderBuilder::new().with_checksum(Checksum::Crc32).build();
+    header.size = 4;
+    header.csize = 4;
+    header.chksum = 0x12345678;
+    let mut encoder = Encoder::new(sink()).unwrap();
+    encoder.create_section_raw(header, &mut b"test".as_ref()).unwrap();
+    let err = encoder.save_and_verify(&path).unwrap_err();
+    assert!(err.is_checksum_mismatch());
+    // The file is still written and can be inspected.
+    assert!(!bpx::inspect::dump(&mut Decoder::open_file(&path).unwrap()).unwrap().is_valid());
+}
+
+#[test]
+fn section_remaining_capacity_does_not_load()
+{
+    use std::io::{Cursor, Seek, SeekFrom, Write};
+
+    use bpx::{
+        builder::{CompressionMethod, SectionHeaderBuilder},
+        container::Container,
+        decoder::CachePolicy,
+        format::MAX_SECTION_SIZE
+    };
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut buf).unwrap();
+        for size in [100usize, 5000] {
+            let header = SectionHeaderBuilder::new().with_compression(CompressionMethod::Xz).build();
+            let handle = encoder.create_section(header).unwrap();
+            encoder.open_section(handle).unwrap().write_all(&vec![0x42; size]).unwrap();
+        }
+        encoder.save().unwrap();
+    }
+
+    let mut decoder = Decoder::new(Cursor::new(buf.clone()))
+        .unwrap()
+        .with_cache_policy(CachePolicy::DropAfterUse);
+    let first = decoder.find_section_by_index(0).unwrap();
+    let second = decoder.find_section_by_index(1).unwrap();
+    {
+        let mut data = decoder.open_section(first).unwrap();
+        data.seek(SeekFrom::End(0)).unwrap();
+        data.write_all(b"more").unwrap();
+    }
+    assert_eq!(decoder.section_remaining_capacity(second).unwrap(), MAX_SECTION_SIZE - 5000);
+    assert_eq!(decoder.section_remaining_capacity(first).unwrap(), MAX_SECTION_SIZE - 104);
+    let loaded: Vec<u32> = decoder.storage_report().sections.iter().map(|v| v.index).collect();
+    assert_eq!(loaded, vec![0]);
+
+    let mut file = Container::open(Cursor::new(buf)).unwrap();
+    let handle = file.find_section_by_index(1).unwrap();
+    assert_eq!(file.section_remaining_capacity(handle).unwrap(), MAX_SECTION_SIZE - 5000);
+    assert!(file.storage_report().sections.is_empty());
+}